@@ -23,7 +23,7 @@ mod widgets;
 
 use widgets::UserInput;
 
-use crate::bot::{Bot, QLearning};
+use private_poker::bots::{self, Bot, BotStatus, QLearning, Reconnect, DEFAULT_THINK_TIME};
 
 const EXIT: &str = "\
 exiting will remove all bots and erase their memory.
@@ -59,50 +59,15 @@ impl Display for WorkerState {
 struct Worker {
     botname: String,
     state: WorkerState,
+    status: Arc<Mutex<BotStatus>>,
     handle: JoinHandle<Result<(), Error>>,
     delete_signaler: Sender<()>,
 }
 
-fn worker(
-    mut env: Bot,
-    policy: Arc<Mutex<QLearning>>,
-    interrupt: Receiver<()>,
-) -> Result<(), Error> {
-    loop {
-        let (mut state1, mut masks1) = env.reset()?;
-        loop {
-            let action = {
-                let mut policy = policy.lock().expect("sample lock");
-                policy.sample(state1.clone(), masks1.clone())
-            };
-            let (state2, masks2, reward, done) = env.step(action.clone())?;
-            if done {
-                let mut policy = policy.lock().expect("done lock");
-                policy.update_done(state1.clone(), action.clone(), reward);
-                break;
-            }
-            {
-                let mut policy = policy.lock().expect("step lock");
-                policy.update_step(
-                    state1.clone(),
-                    action.clone(),
-                    reward,
-                    state2.clone(),
-                    masks2.clone(),
-                );
-            }
-            state1.clone_from(&state2);
-            masks1.clone_from(&masks2);
-        }
-        if interrupt.try_recv().is_ok() {
-            return Ok(());
-        }
-    }
-}
-
 pub struct App {
     addr: String,
     policy: Arc<Mutex<QLearning>>,
+    reconnect: Reconnect,
     workers: Vec<Worker>,
     table_state: TableState,
     user_input: UserInput,
@@ -110,10 +75,11 @@ pub struct App {
 }
 
 impl App {
-    pub fn new(addr: String, policy: Arc<Mutex<QLearning>>) -> Self {
+    pub fn new(addr: String, policy: Arc<Mutex<QLearning>>, reconnect: Reconnect) -> Self {
         Self {
             addr,
             policy,
+            reconnect,
             workers: Vec::new(),
             table_state: TableState::new(),
             user_input: UserInput::new(),
@@ -140,16 +106,18 @@ impl App {
                                 KeyCode::Enter if !self.user_input.value.is_empty() => {
                                     let botname = self.user_input.submit();
                                     let addr = self.addr.clone();
-                                    match Bot::new(&botname, &addr) {
+                                    match Bot::new(&botname, &addr, DEFAULT_THINK_TIME) {
                                         Ok(env) => {
+                                            let env = env.reconnect_with(self.reconnect.clone());
                                             let policy = self.policy.clone();
                                             let (tx_server, rx_worker): (Sender<()>, Receiver<()>) =
                                                 channel();
                                             let worker = Worker {
                                                 botname: botname.clone(),
                                                 state: WorkerState::Active,
+                                                status: env.status(),
                                                 handle: thread::spawn(move || {
-                                                    worker(env, policy, rx_worker)
+                                                    bots::play(env, policy, rx_worker)
                                                 }),
                                                 delete_signaler: tx_server,
                                             };
@@ -181,7 +149,11 @@ impl App {
                                         let worker =
                                             self.workers.get_mut(idx).expect("worker exists");
                                         worker.state = WorkerState::Deleted;
-                                        worker.delete_signaler.send(())?;
+                                        // Bots that gave up reconnecting
+                                        // aren't listening anymore.
+                                        if !worker.handle.is_finished() {
+                                            worker.delete_signaler.send(())?;
+                                        }
                                     }
                                 }
                                 KeyCode::Char('i') => {
@@ -197,8 +169,11 @@ impl App {
                 }
             }
 
-            // Only keep workers that're doing work.
-            self.workers.retain(|w| !w.handle.is_finished());
+            // Keep bots that gave up reconnecting around so the user can
+            // see what happened to them, and only drop bots once they've
+            // been deleted and finished their last hand.
+            self.workers
+                .retain(|w| !(w.handle.is_finished() && matches!(w.state, WorkerState::Deleted)));
         }
     }
 
@@ -212,11 +187,18 @@ impl App {
                 let name_text = Text::raw(w.botname.clone());
                 let name_cell = Cell::new(name_text);
 
+                let status_text = Text::from(w.status.lock().expect("status lock").to_string());
+                let status_cell = Cell::new(status_text);
+
                 let state_text = Text::from(w.state.to_string());
                 let state_cell = Cell::new(state_text);
-                Row::new([name_cell, state_cell])
+                Row::new([name_cell, status_cell, state_cell])
             }),
-            [Constraint::Fill(1), Constraint::Fill(1)],
+            [
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+            ],
         )
         .block(block::Block::bordered().padding(Padding::uniform(1)))
         .highlight_style(Style::new().bg(ratatui::style::Color::White));
@@ -1,12 +1,11 @@
 use anyhow::Error;
 use ctrlc::set_handler;
 use pico_args::Arguments;
+use private_poker::bots::{QLearning, Reconnect, DEFAULT_ALPHA, DEFAULT_GAMMA};
 use std::sync::{Arc, Mutex};
 
 mod app;
-mod bot;
 use app::App;
-use bot::QLearning;
 
 const HELP: &str = "\
 Create poker bots and conect them to a private poker server over TCP
@@ -15,18 +14,21 @@ USAGE:
   pp_bots [OPTIONS]
 
 OPTIONS:
-  --connect IP:PORT     Server socket connection address  [default: 127.0.0.1:6969]
-  --alpha   ALPHA       Bot Q-Learning rate               [default: 0.1]
-  --gamma   GAMMA       Bot discount rate                 [default: 0.95]
+  --connect     IP:PORT   Server socket connection address  [default: 127.0.0.1:6969]
+  --alpha       ALPHA     Bot Q-Learning rate               [default: 0.1]
+  --gamma       GAMMA     Bot discount rate                 [default: 0.95]
+  --max-retries N         Reconnect attempts before a bot   [default: unlimited]
+                          gives up on a lost connection
 
 FLAGS:
-  -h, --help            Print help information
+  -h, --help              Print help information
 ";
 
 struct Args {
     addr: String,
     alpha: f32,
     gamma: f32,
+    max_retries: Option<usize>,
 }
 
 fn main() -> Result<(), Error> {
@@ -42,8 +44,9 @@ fn main() -> Result<(), Error> {
         addr: pargs
             .value_from_str("--connect")
             .unwrap_or("127.0.0.1:6969".into()),
-        alpha: pargs.value_from_str("--alpha").unwrap_or("0.1".parse()?),
-        gamma: pargs.value_from_str("--gamma").unwrap_or("0.95".parse()?),
+        alpha: pargs.value_from_str("--alpha").unwrap_or(DEFAULT_ALPHA),
+        gamma: pargs.value_from_str("--gamma").unwrap_or(DEFAULT_GAMMA),
+        max_retries: pargs.opt_value_from_str("--max-retries")?,
     };
 
     // Catching signals for exit.
@@ -51,7 +54,11 @@ fn main() -> Result<(), Error> {
 
     let policy = Arc::new(Mutex::new(QLearning::new(args.alpha, args.gamma)));
     let terminal = ratatui::init();
-    let app_result = App::new(args.addr, policy).run(terminal);
+    let reconnect = Reconnect {
+        max_retries: args.max_retries,
+        ..Default::default()
+    };
+    let app_result = App::new(args.addr, policy, reconnect).run(terminal);
     ratatui::restore();
     app_result
 }
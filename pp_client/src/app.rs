@@ -2,77 +2,228 @@ use anyhow::{bail, Error};
 use chrono::{DateTime, Utc};
 use mio::{Events, Interest, Poll, Waker};
 use private_poker::{
-    entities::{Action, Card, GameView, Suit, Usd, User, Username},
+    entities::{
+        Action, BetAction, Card, GameEvent, GamePhase, GameVariant, GameView, HandId, HoleCards,
+        Leaderboard, PendingAction, PlayerView, Reveal, Suit, TurnId, Usd, User, Username,
+    },
+    fairness::{Commitment, DeckReveal},
     functional,
-    messages::UserState,
     net::{
-        messages::{ClientMessage, ServerMessage, UserCommand},
-        server::{DEFAULT_POLL_TIMEOUT, SERVER, WAKER},
-        utils::{read_prefixed, write_prefixed},
+        messages::{
+            ClientFrame, ReconnectSecret, Seq, SequencedMessage, ServerMessage, Snapshot,
+            UserCommand,
+        },
+        server::{DEFAULT_ACTION_TIMEOUT, DEFAULT_POLL_TIMEOUT, SERVER, WAKER},
+        utils::{FrameReader, PartialFrame, MAX_FRAME_SIZE},
     },
+    resolve_pending_action, Client,
 };
 use ratatui::{
     crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
-    layout::{Alignment, Constraint, Flex, Layout, Margin, Position},
-    style::{Style, Stylize},
+    layout::{Alignment, Constraint, Flex, Layout, Margin, Position, Rect},
+    style::{Color, Style, Stylize},
     symbols::scrollbar,
     text::{Line, Span, Text},
     widgets::{
-        block, Block, Cell, Clear, List, ListDirection, ListItem, Padding, Paragraph, Row,
-        Scrollbar, ScrollbarOrientation, Table,
+        block,
+        canvas::{Canvas, Circle},
+        Block, Cell, Clear, List, ListDirection, ListItem, Padding, Paragraph, Row, Scrollbar,
+        ScrollbarOrientation, Table,
     },
     DefaultTerminal, Frame,
 };
 use std::{
     collections::{HashSet, VecDeque},
-    io,
+    io, iter, mem,
     net::TcpStream,
-    sync::mpsc::{channel, Receiver, Sender},
+    str::FromStr,
+    sync::{
+        mpsc::{channel, Receiver, Sender, TryRecvError},
+        Arc,
+    },
     thread,
     time::{Duration, Instant},
 };
 
+mod complete;
+mod confirm;
+mod deal;
+mod layout;
+mod notify;
+mod replay;
+mod theme;
 mod widgets;
 
+use complete::complete;
+pub use confirm::{ConfirmConfig, DEFAULT_CONFIRM_FRACTION};
+use confirm::{ConfirmKey, Confirmer};
+use deal::{Dealer, DEAL_INTERVAL};
+use layout::{seat_positions, LayoutMode, LayoutPicker};
+pub use notify::NotifyConfig;
+use notify::{Notification, Notifier};
+use replay::{HandHistory, Review};
+pub use theme::{Theme, ThemeName};
 use widgets::{ScrollableList, UserInput};
 
+use crate::command::parse_command;
+
 const HELP: &str = "\
+accept USERNAME                                                                        
+        Accept the insurance USERNAME offered you while you're all-in.                 
 all-in                                                                                 
         Go all-in, betting all your money on the hand.                                 
+buyin AMOUNT                                                                           
+        Add AMOUNT to your stack, up to the max buy-in. Players' stacks are topped up  
+        after the current hand.                                                        
 call                                                                                   
         Match the investment required to stay in the hand.                             
 check                                                                                  
         Check, voting to move to the next card reveal(s).                              
+Ctrl-U/Ctrl-W                                                                          
+        Delete everything before the cursor, or only the word before it.               
+F1/F2/F3/F4                                                                            
+        Pre-select check/fold, check/call, call any, or fold to any raise, taking it   
+        as soon as your turn comes. Pressing the same key again clears it.             
+F5                                                                                     
+        Mute or unmute notifications for your turn and insurance offers made to you.   
+F6                                                                                     
+        Switch between compact, standard, and wide layouts, or back to picking one     
+        based on the terminal's width.                                                 
+F7                                                                                     
+        Turn confirming calls and raises that commit most of your stack on or off. When
+        on, press y or Enter to send them, or any other key to cancel.                 
+F8                                                                                     
+        Switch to the next user you're playing as. Their hole cards stay hidden until  
+        you press Enter, so only the person playing them sees them.                    
 fold                                                                                   
         Fold, forfeiting your hand.                                                    
+insure USERNAME PREMIUM N:D                                                            
+        Offer insurance to USERNAME while they're all-in. They pay PREMIUM if they win 
+        the hand, and you pay PREMIUM at N:D odds if they don't.                       
+leaderboard                                                                            
+        Toggle a leaderboard of everyone's profit or loss over the session.            
+muck                                                                                   
+        Muck your hand, keeping it hidden at the showdown unless it wins.              
+mute USERNAME                                                                          
+        Stop hearing about what USERNAME does outside of the hand, like joining the    
+        waitlist. Mutes last until you unmute USERNAME, even if you reconnect.         
+name USERNAME                                                                          
+        Change your username, keeping your seat and money. Limited to once per hand.   
 play                                                                                   
         Join the playing waitlist.                                                     
+profile [AVATAR [TAGLINE]]                                                             
+        Show AVATAR, a single character like an emoji or a flag, next to your name,    
+        along with TAGLINE. Entering without values clears your profile.               
+rail                                                                                   
+        Give up your seat and stack but keep watching. Your stack is held for you      
+        until you rejoin. If you're a player, you won't leave until the game is over.  
 raise                                                                                  
         Raise the investment required to stay in the hand. Entering without a value    
         defaults to the min raise amount. Entering AMOUNT will raise by AMOUNT, but    
-        AMOUNT must be >= the min raise.                                               
-show                                                                                   
-        Show your hand. Only possible during the showdown.                             
+        AMOUNT must be between the min raise and your stack. Raising by your whole     
+        stack puts you all-in.                                                         
+rebuy on|off                                                                           
+        Turn auto-rebuy on or off. If the server allows it, your stack is reset instead
+        of you spectating when you can't afford the big blind.                         
+rejoin                                                                                 
+        Come back from the rail as a spectator with the stack that was held for you.   
+seat SEAT                                                                              
+        Move to open seat SEAT, counting from 0. Players are moved between hands.      
+show [CARD]                                                                            
+        Show your hand, or only CARD (1 or 2) of it. Only possible during the showdown 
+        or once you're all-in.                                                         
+sidebet USERNAME AMOUNT                                                                
+        While spectating, bet AMOUNT of your stack on USERNAME winning the hand, if the
+        server allows it. Spectators that bet on a winner split all the side bets.     
 spectate                                                                               
         Join spectators. If you're a player, you won't spectate until the game is over.
 start                                                                                  
-        Start the game. Requires 2+ players or waitlisters.
+        Start the game. Requires 2+ players or waitlisters.                            
+Tab                                                                                    
+        Complete the command or username being typed. If it could complete to more     
+        than one, pressing Tab again lists them. With nothing typed, toggle this menu. 
+unmute USERNAME                                                                        
+        Hear about what USERNAME does again.                                           
+verify                                                                                 
+        Check that the last hand's deck matches what the server committed to before    
+        dealing, and that the cards you saw were dealt from it.                        
+↑/↓                                                                                    
+        Go back and forth through the commands you've entered. PgUp/PgDn scroll the    
+        log instead.
 ";
-const INVALID_ACTION_MESSAGE: &str = "can't do that now";
 const MAX_LOG_RECORDS: usize = 1024;
+const MAX_RECORDED_HANDS: usize = 16;
+const MAX_RECONNECT_ATTEMPTS: usize = 5;
 const POLL_TIMEOUT: Duration = Duration::from_millis(100);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// One line per street summarizing who did what, oldest street first.
+fn action_history_to_lines(view: &GameView) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut street = None;
+    for record in view.action_history.iter() {
+        let action = match record.action {
+            Action::AllIn => "goes all-in".to_string(),
+            Action::Call(amount) => format!("calls ${amount}"),
+            Action::Check => "checks".to_string(),
+            Action::Fold => "folds".to_string(),
+            Action::Raise(amount) => format!("raises ${amount}"),
+        };
+        let action = format!("{} {action}", record.username);
+        match lines.last_mut() {
+            Some(line) if street == Some(record.street) => {
+                line.push_str(", ");
+                line.push_str(&action);
+            }
+            _ => lines.push(format!(" {}: {action}", record.street)),
+        }
+        street = Some(record.street);
+    }
+    lines
+}
+
+/// The chips in front of a player's seat this street along with
+/// everything they've put in the pot this hand.
+fn bets_to_string(player: &PlayerView) -> String {
+    let investment = match player.investment {
+        0 => "".to_string(),
+        investment => format!("(${investment} in)"),
+    };
+    match player.last_bet {
+        Some(ref bet) => {
+            let action = match bet.action {
+                BetAction::AllIn => "all-in",
+                BetAction::Blind => "blind",
+                BetAction::Call => "call",
+                BetAction::Raise => "raise",
+            };
+            format!("{action} ${} {investment}", bet.amount)
+        }
+        None => investment,
+    }
+}
 
 fn blinds_to_string(view: &GameView) -> String {
-    format!(" blinds: ${}/${}  ", view.big_blind, view.small_blind)
+    match view.limit {
+        Some(limit) => format!(
+            " blinds: ${}/${}, {limit}  ",
+            view.big_blind, view.small_blind
+        ),
+        None => format!(" blinds: ${}/${}  ", view.big_blind, view.small_blind),
+    }
 }
 
-fn board_to_vec_of_spans(view: &GameView) -> Vec<Span<'_>> {
+fn board_to_vec_of_spans(
+    view: &GameView,
+    card_style: CardStyle,
+    theme: &Theme,
+) -> Vec<Span<'static>> {
     let mut span = vec![];
     if !view.board.is_empty() {
         span.push(" board: ".into());
         // Player cards styled according to suit.
         for card in view.board.iter() {
-            let card_repr = card_to_span(card);
+            let card_repr = card_to_span(card, card_style, theme);
             span.push(card_repr);
             span.push("  ".into());
         }
@@ -80,46 +231,354 @@ fn board_to_vec_of_spans(view: &GameView) -> Vec<Span<'_>> {
     span
 }
 
-fn card_to_span(card: &Card) -> Span<'_> {
-    let Card(value, suit) = card;
-    let value = match value {
-        1 | 14 => "A",
-        11 => "J",
-        12 => "Q",
-        13 => "K",
-        v => &v.to_string(),
+/// Indicator for the dealer button and what blind a player pays. The
+/// small blind has the button when there are only two players.
+fn button_to_str(view: &GameView, player_idx: usize) -> &'static str {
+    match (
+        player_idx == view.button_idx,
+        player_idx == view.small_blind_idx,
+        player_idx == view.big_blind_idx,
+    ) {
+        (_, _, true) => "BB",
+        (true, true, _) => "D/SB",
+        (true, false, _) => "D",
+        (false, true, _) => "SB",
+        _ => "",
+    }
+}
+
+fn card_to_span(card: &Card, card_style: CardStyle, theme: &Theme) -> Span<'static> {
+    let rank = card.rank_repr();
+    let suit = card.suit();
+    match card_style {
+        CardStyle::Plain => format!("{rank:>2}/{suit}").into(),
+        CardStyle::Unicode => {
+            let repr = format!("{rank:>2}{}", suit.to_unicode());
+            let style = match suit {
+                Suit::Diamond | Suit::Heart => theme.red_suit,
+                Suit::Club | Suit::Spade => theme.spade,
+                Suit::Wild => theme.wild,
+            };
+            Span::styled(repr, style)
+        }
+        CardStyle::FourColor => {
+            let repr = format!("{rank:>2}/{suit}");
+            let style = match suit {
+                Suit::Club => theme.club,
+                Suit::Diamond => theme.diamond,
+                Suit::Heart => theme.heart,
+                Suit::Spade => theme.spade,
+                Suit::Wild => theme.wild,
+            };
+            Span::styled(repr, style)
+        }
+    }
+}
+
+/// A face-down card, the same width as a face-up card in the style.
+fn card_back_to_span(card_style: CardStyle) -> Span<'static> {
+    match card_style {
+        CardStyle::Plain | CardStyle::FourColor => " ?/?".into(),
+        CardStyle::Unicode => " ??".into(),
+    }
+}
+
+/// Render the hand's actions so far, if there are any.
+fn draw_action_history(action_history: Vec<String>, frame: &mut Frame, area: Rect) {
+    if !action_history.is_empty() {
+        let action_history = Paragraph::new(Text::from_iter(action_history))
+            .block(Block::bordered().title(" actions  "));
+        frame.render_widget(action_history, area);
+    }
+}
+
+fn hand_id_to_string(view: &GameView) -> String {
+    if view.bomb_pot {
+        format!(" hand #{} (bomb pot)  ", view.hand_id)
+    } else {
+        format!(" hand #{}  ", view.hand_id)
+    }
+}
+
+/// Name of a player's highest subhand, but only for whole hands, followed
+/// by their chance of winning while an all-in hand runs out.
+fn hand_to_string(view: &GameView, player: &PlayerView) -> String {
+    let hole_cards = player.cards.revealed();
+    if hole_cards.len() < 2 {
+        return "".to_string();
+    }
+    let hand = match view.variant {
+        GameVariant::Holdem => {
+            let mut cards = view.board.clone();
+            cards.extend_from_slice(hole_cards);
+            functional::prepare_hand(&mut cards);
+            functional::eval(&cards)
+        }
+        GameVariant::Omaha => functional::eval_omaha(hole_cards, &view.board).into(),
+        GameVariant::ShortDeck => {
+            functional::eval_short_deck(&[hole_cards, &view.board].concat()).into()
+        }
     };
-    match suit {
-        Suit::Club => format!("{value:>2}/c").light_green(),
-        Suit::Diamond => format!("{value:>2}/d").light_blue(),
-        Suit::Heart => format!("{value:>2}/h").light_red(),
-        Suit::Spade => format!("{value:>2}/s").into(),
-        Suit::Wild => format!("{value:>2}/w").light_magenta(),
+    let equity = view
+        .equities
+        .iter()
+        .find(|(username, _)| *username == player.user.name)
+        .map_or_else(String::new, |(_, equity)| {
+            format!(" {:.0}%", 100.0 * equity)
+        });
+    match hand.first() {
+        Some(subhand) => format!("({}){equity}", subhand.rank),
+        None => equity.trim_start().to_string(),
     }
 }
 
+/// What's drawn in one of a player's hole card slots.
+#[derive(Debug, PartialEq)]
+enum CardSlot<'a> {
+    Back,
+    Empty,
+    Face(&'a Card),
+}
+
+/// A player's hole card slots in order. Other players that only showed
+/// one card have it put in the right position, with the rest face down.
+fn player_cards<'a>(view: &GameView, player: &'a PlayerView) -> Vec<CardSlot<'a>> {
+    (0..view.variant.num_hole_cards())
+        .map(|card_idx| match (&player.cards, &player.revealed) {
+            (HoleCards::None, _) => CardSlot::Empty,
+            (HoleCards::Hidden { count }, _) if card_idx < *count => CardSlot::Back,
+            (HoleCards::Hidden { .. }, _) => CardSlot::Empty,
+            (HoleCards::Revealed(cards), Reveal::Card(shown_idx)) if cards.len() == 1 => {
+                match card_idx == *shown_idx {
+                    true => CardSlot::Face(&cards[0]),
+                    false => CardSlot::Back,
+                }
+            }
+            (HoleCards::Revealed(cards), _) => {
+                cards.get(card_idx).map_or(CardSlot::Empty, CardSlot::Face)
+            }
+        })
+        .collect()
+}
+
 fn pot_to_string(view: &GameView) -> String {
     format!(" pot: {}  ", view.pot)
 }
 
-fn user_to_row(username: &str, user: &User) -> Row<'static> {
+/// Seconds a player has left to act if it's their announced turn.
+fn secs_remaining(turn: Option<&(Username, Instant)>, player: &PlayerView) -> Option<u64> {
+    turn.filter(|(username, _)| *username == player.user.name)
+        .map(|(_, t)| DEFAULT_ACTION_TIMEOUT.saturating_sub(t.elapsed()).as_secs())
+}
+
+/// Spawn a thread that handles networking with the server over the
+/// stream, returning channels for talking to it and a waker for letting
+/// it know there are messages to write. The server channel hangs up if
+/// the connection drops.
+fn spawn_connection(
+    stream: TcpStream,
+) -> Result<(Sender<UserCommand>, Receiver<SequencedMessage>, Waker), Error> {
+    let (tx_client, rx_client): (Sender<UserCommand>, Receiver<UserCommand>) = channel();
+    let (tx_server, rx_server): (Sender<SequencedMessage>, Receiver<SequencedMessage>) = channel();
+
+    let mut poll = Poll::new()?;
+    let waker = Waker::new(poll.registry(), WAKER)?;
+
+    // This thread is where the actual client-server networking happens for
+    // non-blocking IO. Some non-blocking IO between client threads is also
+    // managed by this thread. The UI thread sends client command messages
+    // to this thread; those messages are eventually written to the server.
+    thread::spawn(move || -> Result<(), Error> {
+        let mut events = Events::with_capacity(64);
+        let mut messages_to_write: VecDeque<UserCommand> = VecDeque::new();
+        // A message that was only partially written, which has to be
+        // finished before any other message is written.
+        let mut in_flight: Option<PartialFrame> = None;
+        // Messages can arrive over several readable events.
+        let mut frame_reader = FrameReader::new(MAX_FRAME_SIZE);
+        stream.set_nonblocking(true)?;
+        let mut stream = mio::net::TcpStream::from_std(stream);
+        poll.registry()
+            .register(&mut stream, SERVER, Interest::READABLE)?;
+
+        loop {
+            if let Err(error) = poll.poll(&mut events, Some(DEFAULT_POLL_TIMEOUT)) {
+                match error.kind() {
+                    io::ErrorKind::Interrupted => continue,
+                    _ => bail!(error),
+                }
+            }
+
+            for event in events.iter() {
+                match event.token() {
+                    SERVER => {
+                        if event.is_writable() {
+                            loop {
+                                let mut frame = match in_flight.take() {
+                                    Some(frame) => frame,
+                                    None => match messages_to_write.pop_front() {
+                                        Some(command) => {
+                                            PartialFrame::new(&ClientFrame::Command(command))?
+                                        }
+                                        None => break,
+                                    },
+                                };
+                                if let Err(error) = frame.write_to(&mut stream) {
+                                    match error.kind() {
+                                        // The frame is written with repeated writes, so we know
+                                        // that if any of these occur, then the connection was probably
+                                        // dropped at some point.
+                                        io::ErrorKind::BrokenPipe
+                                        | io::ErrorKind::ConnectionAborted
+                                        | io::ErrorKind::ConnectionReset
+                                        | io::ErrorKind::TimedOut
+                                        | io::ErrorKind::UnexpectedEof => {
+                                            bail!("connection dropped");
+                                        }
+                                        // Would block "errors" are the OS's way of saying that the
+                                        // connection is not actually ready to perform this I/O operation.
+                                        // The frame may have been partially written, so it's finished
+                                        // on the next writable event before anything else is written.
+                                        io::ErrorKind::WouldBlock => {
+                                            in_flight = Some(frame);
+                                        }
+                                        // Retry writing in the case that the full message couldn't
+                                        // be written. This should be infrequent.
+                                        io::ErrorKind::WriteZero => {
+                                            in_flight = Some(frame);
+                                            continue;
+                                        }
+                                        // Other errors we'll consider fatal.
+                                        _ => bail!(error),
+                                    }
+                                    break;
+                                }
+                            }
+                        }
+
+                        if event.is_readable() {
+                            // We can (maybe) read from the connection.
+                            loop {
+                                match frame_reader
+                                    .read_from::<SequencedMessage, mio::net::TcpStream>(&mut stream)
+                                {
+                                    Ok(msg) => {
+                                        tx_server.send(msg)?;
+                                    }
+                                    Err(error) => {
+                                        match error.kind() {
+                                            // Reads only come up empty once the connection was dropped.
+                                            io::ErrorKind::BrokenPipe
+                                            | io::ErrorKind::ConnectionAborted
+                                            | io::ErrorKind::ConnectionReset
+                                            | io::ErrorKind::InvalidData
+                                            | io::ErrorKind::TimedOut
+                                            | io::ErrorKind::UnexpectedEof => {
+                                                bail!("connection dropped");
+                                            }
+                                            // Would block "errors" are the OS's way of saying that the
+                                            // connection is not actually ready to perform this I/O operation.
+                                            // Part of a message may have been read, which the frame reader
+                                            // keeps until the rest of it arrives.
+                                            io::ErrorKind::WouldBlock => {}
+                                            // Other errors we'll consider fatal.
+                                            _ => {
+                                                bail!(error)
+                                            }
+                                        }
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    WAKER => {
+                        while let Ok(msg) = rx_client.try_recv() {
+                            messages_to_write.push_back(msg);
+                            poll.registry().reregister(
+                                &mut stream,
+                                SERVER,
+                                Interest::READABLE | Interest::WRITABLE,
+                            )?;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    });
+    Ok((tx_client, rx_server, waker))
+}
+
+/// Return a username as it's shown in the TUI, marking users that're
+/// muted.
+fn username_to_string(username: &str, muted: &HashSet<Username>) -> String {
+    if muted.contains(username) {
+        format!("{username} (muted)")
+    } else {
+        username.to_string()
+    }
+}
+
+/// Return a user's name along with their avatar, if they have one.
+fn user_to_string(user: &User, muted: &HashSet<Username>) -> String {
+    let name_repr = username_to_string(&user.name, muted);
+    match user.profile.avatar {
+        Some(ref avatar) => format!("{avatar} {name_repr}"),
+        None => name_repr,
+    }
+}
+
+fn user_to_row(
+    username: &str,
+    muted: &HashSet<Username>,
+    user: &User,
+    theme: &Theme,
+) -> Row<'static> {
     let row = Row::new(vec![
-        Cell::new(Text::from(user.name.clone()).alignment(Alignment::Left)),
+        Cell::new(Text::from(user_to_string(user, muted)).alignment(Alignment::Left)),
+        Cell::new(Text::from(user.profile.tagline.clone().unwrap_or_default())),
         Cell::new(Text::from(format!("${}", user.money)).alignment(Alignment::Right)),
     ]);
     if username == user.name {
-        row.bold().white()
+        row.style(theme.you)
     } else {
         row
     }
 }
 
+/// How cards are rendered in the TUI.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CardStyle {
+    /// Rank and suit letter without any colors (e.g., "A/s").
+    Plain,
+    /// Rank and suit symbol with red hearts and diamonds (e.g., "A♠").
+    Unicode,
+    /// Rank and suit letter with a different color for each suit.
+    #[default]
+    FourColor,
+}
+
+impl FromStr for CardStyle {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(CardStyle::Plain),
+            "unicode" => Ok(CardStyle::Unicode),
+            "four-color" => Ok(CardStyle::FourColor),
+            other => bail!("unknown card style: {other}"),
+        }
+    }
+}
+
 #[derive(Clone)]
 enum RecordKind {
     Ack,
     Alert,
     Error,
     Game,
+    Hint,
     You,
 }
 
@@ -140,22 +599,21 @@ impl Record {
             content,
         }
     }
-}
 
-impl From<Record> for ListItem<'_> {
-    fn from(val: Record) -> Self {
-        let repr = match val.kind {
-            RecordKind::Ack => "ACK".light_blue(),
-            RecordKind::Alert => "ALERT".light_magenta(),
-            RecordKind::Error => "ERROR".light_red(),
-            RecordKind::Game => "GAME".light_yellow(),
-            RecordKind::You => "YOU".light_green(),
+    fn into_list_item(self, theme: &Theme) -> ListItem<'static> {
+        let (repr, style) = match self.kind {
+            RecordKind::Ack => ("ACK", theme.ack),
+            RecordKind::Alert => ("ALERT", theme.alert),
+            RecordKind::Error => ("ERROR", theme.error),
+            RecordKind::Game => ("GAME", theme.game),
+            RecordKind::Hint => ("HINT", theme.command),
+            RecordKind::You => ("YOU", theme.command),
         };
 
         let msg = vec![
-            format!("[{} ", val.datetime.format("%H:%M:%S")).into(),
-            Span::styled(format!("{repr:5}"), repr.style),
-            format!("]: {}", val.content).into(),
+            format!("[{} ", self.datetime.format("%H:%M:%S")).into(),
+            Span::styled(format!("{repr:5}"), style),
+            format!("]: {}", self.content).into(),
         ];
 
         let content = Line::from(msg);
@@ -205,306 +663,263 @@ impl TurnWarnings {
     }
 }
 
+/// Another user this client plays as, along with everything that's tied
+/// to them, waiting for whoever's at the keyboard to switch to them.
+struct Identity {
+    username: Username,
+    tx_client: Sender<UserCommand>,
+    rx_server: Receiver<SequencedMessage>,
+    waker: Waker,
+    secret: Option<ReconnectSecret>,
+    /// Sequence number of the last message received for the user
+    seq: Seq,
+    /// The user's latest view, which has their hole cards
+    view: GameView,
+    action_options: HashSet<Action>,
+    max_raise: Option<Usd>,
+    turn_id: Option<TurnId>,
+    turn_warnings: TurnWarnings,
+    pending_action: Option<PendingAction>,
+    hand_history: HandHistory,
+}
+
 /// App holds the application state.
 pub struct App {
     username: Username,
     addr: String,
+    /// Secret for reconnecting as the user, if the server issued one
+    secret: Option<ReconnectSecret>,
+    card_style: CardStyle,
+    theme: Theme,
+    /// Picks how the game is arranged on screen
+    layout: LayoutPicker,
     /// Whether to display the help menu window
     show_help_menu: bool,
+    /// Most recent leaderboard received from the server
+    leaderboard: Leaderboard,
+    /// Whether to display the leaderboard window
+    show_leaderboard: bool,
+    /// Paces revealing community cards as they're dealt
+    dealer: Dealer,
+    /// Commitment to the latest hand's deck, if it was received
+    deck_commitment: Option<(HandId, Commitment)>,
+    /// Deck revealed at the end of the last hand, if it was received
+    deck_reveal: Option<(HandId, DeckReveal)>,
+    /// Views of the last few completed hands
+    hand_history: HandHistory,
+    /// Snapshot being reviewed instead of the live game, if any
+    review: Option<Review>,
+    /// Action to take as soon as it's the user's turn, if any
+    pending_action: Option<PendingAction>,
+    /// Holds actions that commit too much of the user's stack until
+    /// they're confirmed
+    confirmer: Confirmer,
+    /// Users the user muted
+    muted: HashSet<Username>,
+    /// User whose turn was last announced and when it was announced, if
+    /// they haven't acted yet
+    turn: Option<(Username, Instant)>,
+    /// Turn the user was last signaled for, which actions are tied to
+    turn_id: Option<TurnId>,
+    /// Hooks for getting the user's attention when they're needed
+    notifier: Notifier,
     /// History of recorded messages
     log_handle: ScrollableList,
     /// Current value of the input box
     user_input: UserInput,
+    /// Other users the user is playing as, in the order they're switched
+    /// to
+    identities: VecDeque<Identity>,
+    /// Whether the user's hole cards are hidden since they were switched
+    /// to, until whoever's at the keyboard shows them
+    hidden: bool,
 }
 
 impl App {
+    /// Complete the word before the cursor, listing the candidates if
+    /// there are several and none of them could be filled in any further.
+    fn complete_input(&mut self, view: &GameView) {
+        let Some(completion) = complete(
+            &self.user_input.value,
+            self.user_input.char_idx,
+            &self.username,
+            view,
+        ) else {
+            return;
+        };
+        if completion.value == self.user_input.value {
+            let record = Record::new(RecordKind::Hint, completion.candidates.join(", "));
+            self.log_handle.push(record.into_list_item(&self.theme));
+        } else {
+            self.user_input
+                .replace(completion.value, completion.char_idx);
+        }
+    }
+
     fn handle_command(
         &mut self,
         user_input: &str,
+        view: &GameView,
         action_options: &HashSet<Action>,
-        tx_client: &Sender<ClientMessage>,
+        max_raise: Option<Usd>,
+        tx_client: &Sender<UserCommand>,
         waker: &Waker,
     ) -> Result<(), Error> {
         match user_input.trim() {
-            "all-in" => {
-                if let Some(action) = action_options.get(&Action::AllIn) {
-                    let msg = ClientMessage {
-                        username: self.username.to_string(),
-                        command: UserCommand::TakeAction(action.clone()),
-                    };
-                    tx_client.send(msg)?;
-                    waker.wake()?;
-                } else {
-                    let record = Record::new(RecordKind::Error, INVALID_ACTION_MESSAGE.to_string());
-                    self.log_handle.push(record.into());
-                }
-            }
-            "call" => {
-                // Actions use their variant for comparisons,
-                // so we don't need to provide the correct call
-                // amount to see if it exists within the action
-                // options.
-                if let Some(action) = action_options.get(&Action::Call(0)) {
-                    let msg = ClientMessage {
-                        username: self.username.to_string(),
-                        command: UserCommand::TakeAction(action.clone()),
-                    };
-                    tx_client.send(msg)?;
-                    waker.wake()?;
-                } else {
-                    let record = Record::new(RecordKind::Error, INVALID_ACTION_MESSAGE.to_string());
-                    self.log_handle.push(record.into());
-                }
-            }
-            "check" => {
-                if let Some(action) = action_options.get(&Action::Check) {
-                    let msg = ClientMessage {
-                        username: self.username.to_string(),
-                        command: UserCommand::TakeAction(action.clone()),
-                    };
-                    tx_client.send(msg)?;
-                    waker.wake()?;
-                } else {
-                    let record = Record::new(RecordKind::Error, INVALID_ACTION_MESSAGE.to_string());
-                    self.log_handle.push(record.into());
-                }
-            }
-            "fold" => {
-                if let Some(action) = action_options.get(&Action::Fold) {
-                    let msg = ClientMessage {
-                        username: self.username.clone(),
-                        command: UserCommand::TakeAction(action.clone()),
-                    };
-                    tx_client.send(msg)?;
-                    waker.wake()?;
-                } else {
-                    let record = Record::new(RecordKind::Error, INVALID_ACTION_MESSAGE.to_string());
-                    self.log_handle.push(record.into());
-                }
-            }
-            "play" => {
-                let msg = ClientMessage {
-                    username: self.username.clone(),
-                    command: UserCommand::ChangeState(UserState::Play),
-                };
-                tx_client.send(msg)?;
-                waker.wake()?;
-            }
-            "show" => {
-                let msg = ClientMessage {
-                    username: self.username.clone(),
-                    command: UserCommand::ShowHand,
-                };
-                tx_client.send(msg)?;
-                waker.wake()?;
-            }
-            "spectate" => {
-                let msg = ClientMessage {
-                    username: self.username.clone(),
-                    command: UserCommand::ChangeState(UserState::Spectate),
-                };
-                tx_client.send(msg)?;
-                waker.wake()?;
+            // The leaderboard is only shown once the server responds
+            // with the latest one.
+            "leaderboard" if self.show_leaderboard => self.show_leaderboard = false,
+            "verify" => {
+                let record = self.verify_last_hand();
+                self.log_handle.push(record.into_list_item(&self.theme));
             }
-            "start" => {
-                let msg = ClientMessage {
-                    username: self.username.clone(),
-                    command: UserCommand::StartGame,
-                };
-                tx_client.send(msg)?;
-                waker.wake()?;
-            }
-            other => {
-                let other: Vec<&str> = other.split_ascii_whitespace().collect();
-                let action = match (
-                    action_options.get(&Action::Raise(0)),
-                    other.first(),
-                    other.get(1),
-                ) {
-                    // Raise with a specific amount.
-                    (Some(_), Some(&"raise"), Some(value)) => match value.parse::<Usd>() {
-                        Ok(amount) => Action::Raise(amount),
-                        Err(_) => {
-                            let record =
-                                Record::new(RecordKind::Error, "invalid raise amount".to_string());
-                            self.log_handle.push(record.into());
-                            return Ok(());
+            user_input => {
+                let money = view
+                    .players
+                    .iter()
+                    .find(|player| player.user.name == self.username)
+                    .map_or(0, |player| player.user.money);
+                match parse_command(user_input, action_options, max_raise, money) {
+                    // Big bets wait until they're confirmed.
+                    Ok(UserCommand::TakeAction(action)) => {
+                        if let Some(action) = self.confirmer.review(action, money) {
+                            tx_client
+                                .send(UserCommand::TakeAction(action).for_turn(self.turn_id))?;
+                            waker.wake()?;
                         }
-                    },
-                    // Valid raise without specified amount defaults to the default raise.
-                    (Some(action), Some(&"raise"), None) => action.clone(),
-                    // Invalid action.
-                    (None, Some(&"raise"), ..) => {
-                        let record =
-                            Record::new(RecordKind::Error, INVALID_ACTION_MESSAGE.to_string());
-                        self.log_handle.push(record.into());
-                        return Ok(());
                     }
-                    // Unknown command.
-                    _ => {
-                        let record =
-                            Record::new(RecordKind::Error, "unrecognized command".to_string());
-                        self.log_handle.push(record.into());
-                        return Ok(());
+                    Ok(command) => {
+                        tx_client.send(command.for_turn(self.turn_id))?;
+                        waker.wake()?;
                     }
-                };
-                let msg = ClientMessage {
-                    username: self.username.to_string(),
-                    command: UserCommand::TakeAction(action),
-                };
-                tx_client.send(msg)?;
+                    Err(error) => {
+                        let record = Record::new(RecordKind::Error, error.to_string());
+                        self.log_handle.push(record.into_list_item(&self.theme));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Send the action waiting to be confirmed if the key confirms it,
+    /// otherwise drop it.
+    fn handle_confirm_key(
+        &mut self,
+        code: KeyCode,
+        tx_client: &Sender<UserCommand>,
+        waker: &Waker,
+    ) -> Result<(), Error> {
+        let key = match code {
+            KeyCode::Enter | KeyCode::Char('y' | 'Y') => ConfirmKey::Yes,
+            _ => ConfirmKey::Other,
+        };
+        match self.confirmer.press(key) {
+            Some(action) => {
+                let record = Record::new(RecordKind::You, format!("confirmed: {action}"));
+                self.log_handle.push(record.into_list_item(&self.theme));
+                tx_client.send(UserCommand::TakeAction(action).for_turn(self.turn_id))?;
                 waker.wake()?;
             }
+            None => {
+                let record = Record::new(RecordKind::Alert, "cancelled".to_string());
+                self.log_handle.push(record.into_list_item(&self.theme));
+            }
         }
         Ok(())
     }
 
-    pub fn new(username: Username, addr: String) -> Result<Self, Error> {
+    /// Drop the action waiting to be confirmed since the turn it was for
+    /// is over.
+    fn cancel_confirmation(&mut self) {
+        if self.confirmer.cancel() {
+            let record = Record::new(
+                RecordKind::Alert,
+                "turn's over, dropped the action waiting to be confirmed".to_string(),
+            );
+            self.log_handle.push(record.into_list_item(&self.theme));
+        }
+    }
+
+    pub fn new(
+        username: Username,
+        addr: String,
+        secret: Option<ReconnectSecret>,
+        card_style: CardStyle,
+        theme: Theme,
+        notify_config: NotifyConfig,
+        confirm_config: ConfirmConfig,
+    ) -> Result<Self, Error> {
         Ok(Self {
             username,
             addr,
+            secret,
+            card_style,
+            theme,
+            layout: LayoutPicker::default(),
             show_help_menu: false,
+            leaderboard: Leaderboard::new(),
+            show_leaderboard: false,
+            dealer: Dealer::new(DEAL_INTERVAL),
+            deck_commitment: None,
+            deck_reveal: None,
+            hand_history: HandHistory::new(MAX_RECORDED_HANDS),
+            review: None,
+            pending_action: None,
+            confirmer: Confirmer::new(confirm_config),
+            muted: HashSet::new(),
+            turn: None,
+            turn_id: None,
+            notifier: Notifier::new(notify_config),
             log_handle: ScrollableList::new(MAX_LOG_RECORDS),
             user_input: UserInput::new(),
+            identities: VecDeque::new(),
+            hidden: false,
         })
     }
 
+    /// Also play as the users the clients are connected as, switching
+    /// between them with F8.
+    pub fn with_identities(mut self, clients: Vec<(Client, GameView)>) -> Result<Self, Error> {
+        for (client, view) in clients {
+            let (tx_client, rx_server, waker) = spawn_connection(client.stream)?;
+            self.identities.push_back(Identity {
+                username: client.username,
+                tx_client,
+                rx_server,
+                waker,
+                secret: client.secret,
+                seq: 0,
+                view,
+                action_options: HashSet::new(),
+                max_raise: None,
+                turn_id: None,
+                turn_warnings: TurnWarnings::new(),
+                pending_action: None,
+                hand_history: HandHistory::new(MAX_RECORDED_HANDS),
+            });
+        }
+        Ok(self)
+    }
+
     pub fn run(
         mut self,
         stream: TcpStream,
         mut view: GameView,
         mut terminal: DefaultTerminal,
     ) -> Result<(), Error> {
-        let (tx_client, rx_client): (Sender<ClientMessage>, Receiver<ClientMessage>) = channel();
-        let (tx_server, rx_server): (Sender<ServerMessage>, Receiver<ServerMessage>) = channel();
-
-        let mut poll = Poll::new()?;
-        let waker = Waker::new(poll.registry(), WAKER)?;
-
-        // This thread is where the actual client-server networking happens for
-        // non-blocking IO. Some non-blocking IO between client threads is also
-        // managed by this thread. The UI thread sends client command messages
-        // to this thread; those messages are eventually written to the server.
-        thread::spawn(move || -> Result<(), Error> {
-            let mut events = Events::with_capacity(64);
-            let mut messages_to_write: VecDeque<ClientMessage> = VecDeque::new();
-            stream.set_nonblocking(true)?;
-            let mut stream = mio::net::TcpStream::from_std(stream);
-            poll.registry()
-                .register(&mut stream, SERVER, Interest::READABLE)?;
-
-            loop {
-                if let Err(error) = poll.poll(&mut events, Some(DEFAULT_POLL_TIMEOUT)) {
-                    match error.kind() {
-                        io::ErrorKind::Interrupted => continue,
-                        _ => bail!(error),
-                    }
-                }
-
-                for event in events.iter() {
-                    match event.token() {
-                        SERVER => {
-                            if event.is_writable() && !messages_to_write.is_empty() {
-                                while let Some(msg) = messages_to_write.pop_front() {
-                                    if let Err(error) =
-                                        write_prefixed::<ClientMessage, mio::net::TcpStream>(
-                                            &mut stream,
-                                            &msg,
-                                        )
-                                    {
-                                        match error.kind() {
-                                            // `write_prefixed` uses `write_all` under the hood, so we know
-                                            // that if any of these occur, then the connection was probably
-                                            // dropped at some point.
-                                            io::ErrorKind::BrokenPipe
-                                            | io::ErrorKind::ConnectionAborted
-                                            | io::ErrorKind::ConnectionReset
-                                            | io::ErrorKind::TimedOut
-                                            | io::ErrorKind::UnexpectedEof => {
-                                                bail!("connection dropped");
-                                            }
-                                            // Would block "errors" are the OS's way of saying that the
-                                            // connection is not actually ready to perform this I/O operation.
-                                            io::ErrorKind::WouldBlock => {
-                                                // The message couldn't be sent, so we need to push it back
-                                                // onto the queue so we don't accidentally forget about it.
-                                                messages_to_write.push_front(msg);
-                                            }
-                                            // Retry writing in the case that the full message couldn't
-                                            // be written. This should be infrequent.
-                                            io::ErrorKind::WriteZero => {
-                                                messages_to_write.push_front(msg);
-                                                continue;
-                                            }
-                                            // Other errors we'll consider fatal.
-                                            _ => bail!(error),
-                                        }
-                                        poll.registry().reregister(
-                                            &mut stream,
-                                            SERVER,
-                                            Interest::READABLE,
-                                        )?;
-                                        break;
-                                    }
-                                }
-                            }
-
-                            if event.is_readable() {
-                                // We can (maybe) read from the connection.
-                                loop {
-                                    match read_prefixed::<ServerMessage, mio::net::TcpStream>(
-                                        &mut stream,
-                                    ) {
-                                        Ok(msg) => {
-                                            tx_server.send(msg)?;
-                                        }
-                                        Err(error) => {
-                                            match error.kind() {
-                                                // `read_prefixed` uses `read_exact` under the hood, so we know
-                                                // that an Eof error means the connection was dropped.
-                                                io::ErrorKind::BrokenPipe
-                                                | io::ErrorKind::ConnectionAborted
-                                                | io::ErrorKind::ConnectionReset
-                                                | io::ErrorKind::InvalidData
-                                                | io::ErrorKind::TimedOut
-                                                | io::ErrorKind::UnexpectedEof => {
-                                                    bail!("connection dropped");
-                                                }
-                                                // Would block "errors" are the OS's way of saying that the
-                                                // connection is not actually ready to perform this I/O operation.
-                                                io::ErrorKind::WouldBlock => {}
-                                                // Other errors we'll consider fatal.
-                                                _ => {
-                                                    bail!(error)
-                                                }
-                                            }
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        WAKER => {
-                            while let Ok(msg) = rx_client.try_recv() {
-                                messages_to_write.push_back(msg);
-                                poll.registry().reregister(
-                                    &mut stream,
-                                    SERVER,
-                                    Interest::READABLE | Interest::WRITABLE,
-                                )?;
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-            }
-        });
+        let (mut tx_client, mut rx_server, mut waker) = spawn_connection(stream)?;
+        // Sequence number of the last message received from the server.
+        let mut seq = 0;
 
         let mut action_options = HashSet::new();
+        let mut max_raise = None;
         let mut turn_warnings = TurnWarnings::new();
         loop {
+            self.dealer.reveal(Instant::now());
             terminal.draw(|frame| self.draw(&view, frame))?;
 
+            let mut switch_identity = false;
             if event::poll(POLL_TIMEOUT)? {
                 if let Event::Key(KeyEvent {
                     code,
@@ -515,19 +930,54 @@ impl App {
                 {
                     if kind == KeyEventKind::Press {
                         match modifiers {
+                            // Nothing else can be done after switching users
+                            // until their cards are shown.
+                            _ if self.hidden => match (modifiers, code) {
+                                (KeyModifiers::CONTROL, KeyCode::Char('c')) => return Ok(()),
+                                (KeyModifiers::NONE, KeyCode::Enter) => self.hidden = false,
+                                (KeyModifiers::NONE, KeyCode::F(8)) => switch_identity = true,
+                                _ => {}
+                            },
                             KeyModifiers::CONTROL => match code {
+                                // The terminal is in raw mode, so interrupts
+                                // come through as key presses.
+                                KeyCode::Char('c') => return Ok(()),
+                                KeyCode::Char('r') => self.toggle_review(),
+                                KeyCode::Char('u') => self.user_input.delete_to_start(),
+                                KeyCode::Char('w') => self.user_input.delete_word(),
                                 KeyCode::Home => self.log_handle.jump_to_first(),
                                 KeyCode::End => self.log_handle.jump_to_last(),
                                 _ => {}
                             },
+                            // The next key press after a big bet either confirms
+                            // or cancels it.
+                            _ if self.confirmer.pending().is_some() => {
+                                self.handle_confirm_key(code, &tx_client, &waker)?;
+                            }
+                            // Arrow keys step through the review instead while
+                            // reviewing, and escaping goes back to the live game.
+                            KeyModifiers::NONE if self.review.is_some() => {
+                                let history = &self.hand_history;
+                                let review = self.review.as_mut().expect("reviewing");
+                                match code {
+                                    KeyCode::Left => review.back(),
+                                    KeyCode::Right => review.forward(history),
+                                    KeyCode::Up => review.older(history),
+                                    KeyCode::Down => review.newer(history),
+                                    KeyCode::Esc => self.review = None,
+                                    _ => {}
+                                }
+                            }
                             KeyModifiers::NONE => match code {
                                 KeyCode::Enter => {
                                     let user_input = self.user_input.submit();
                                     let record = Record::new(RecordKind::You, user_input.clone());
-                                    self.log_handle.push(record.into());
+                                    self.log_handle.push(record.into_list_item(&self.theme));
                                     self.handle_command(
                                         &user_input,
+                                        &view,
                                         &action_options,
+                                        max_raise,
                                         &tx_client,
                                         &waker,
                                     )?;
@@ -537,11 +987,32 @@ impl App {
                                 KeyCode::Delete => self.user_input.delete(),
                                 KeyCode::Left => self.user_input.move_left(),
                                 KeyCode::Right => self.user_input.move_right(),
-                                KeyCode::Up => self.log_handle.move_up(),
-                                KeyCode::Down => self.log_handle.move_down(),
+                                KeyCode::Up => self.user_input.older(),
+                                KeyCode::Down => self.user_input.newer(),
+                                KeyCode::PageUp => self.log_handle.move_up(),
+                                KeyCode::PageDown => self.log_handle.move_down(),
                                 KeyCode::Home => self.user_input.jump_to_first(),
                                 KeyCode::End => self.user_input.jump_to_last(),
-                                KeyCode::Tab => self.show_help_menu = !self.show_help_menu,
+                                // Tab only brings up help if there's nothing
+                                // to complete.
+                                KeyCode::Tab if self.user_input.value.is_empty() => {
+                                    self.show_help_menu = !self.show_help_menu
+                                }
+                                KeyCode::Tab => self.complete_input(&view),
+                                KeyCode::F(1) => {
+                                    self.toggle_pending_action(PendingAction::CheckFold)
+                                }
+                                KeyCode::F(2) => {
+                                    self.toggle_pending_action(PendingAction::CheckCall)
+                                }
+                                KeyCode::F(3) => self.toggle_pending_action(PendingAction::CallAny),
+                                KeyCode::F(4) => {
+                                    self.toggle_pending_action(PendingAction::FoldToAnyRaise)
+                                }
+                                KeyCode::F(5) => self.toggle_mute(),
+                                KeyCode::F(6) => self.toggle_layout(),
+                                KeyCode::F(7) => self.toggle_confirm(),
+                                KeyCode::F(8) => switch_identity = true,
                                 KeyCode::Esc => return Ok(()),
                                 _ => {}
                             },
@@ -551,78 +1022,887 @@ impl App {
                 }
             }
 
-            if let Ok(msg) = rx_server.try_recv() {
+            // Everything tied to the user is swapped out for the next
+            // user's, and the old user waits at the back of the line.
+            if switch_identity {
+                match self.identities.pop_front() {
+                    Some(mut identity) => {
+                        mem::swap(&mut self.username, &mut identity.username);
+                        mem::swap(&mut tx_client, &mut identity.tx_client);
+                        mem::swap(&mut rx_server, &mut identity.rx_server);
+                        mem::swap(&mut waker, &mut identity.waker);
+                        mem::swap(&mut self.secret, &mut identity.secret);
+                        mem::swap(&mut seq, &mut identity.seq);
+                        mem::swap(&mut view, &mut identity.view);
+                        mem::swap(&mut action_options, &mut identity.action_options);
+                        mem::swap(&mut max_raise, &mut identity.max_raise);
+                        mem::swap(&mut self.turn_id, &mut identity.turn_id);
+                        mem::swap(&mut turn_warnings, &mut identity.turn_warnings);
+                        mem::swap(&mut self.pending_action, &mut identity.pending_action);
+                        mem::swap(&mut self.hand_history, &mut identity.hand_history);
+                        self.identities.push_back(identity);
+                        self.cancel_confirmation();
+                        self.review = None;
+                        self.hidden = true;
+                        let record =
+                            Record::new(RecordKind::Alert, format!("playing as {}", self.username));
+                        self.log_handle.push(record.into_list_item(&self.theme));
+                    }
+                    None => {
+                        let record = Record::new(
+                            RecordKind::Error,
+                            "not playing as anyone else".to_string(),
+                        );
+                        self.log_handle.push(record.into_list_item(&self.theme));
+                    }
+                }
+            }
+
+            let msg = match rx_server.try_recv() {
+                Ok(msg) => Some(msg),
+                Err(TryRecvError::Empty) => None,
+                // The connection dropped, so connect again and catch up on
+                // whatever was missed in the meantime all at once.
+                Err(TryRecvError::Disconnected) => {
+                    let record = Record::new(
+                        RecordKind::Error,
+                        "connection dropped, reconnecting".to_string(),
+                    );
+                    self.log_handle.push(record.into_list_item(&self.theme));
+                    terminal.draw(|frame| self.draw(&view, frame))?;
+                    let client = self.reconnect(&self.username, self.secret)?;
+                    self.secret = client.secret;
+                    (tx_client, rx_server, waker) = spawn_connection(client.stream)?;
+                    seq = 0;
+                    self.pending_action = None;
+                    self.cancel_confirmation();
+                    tx_client.send(UserCommand::Resync)?;
+                    waker.wake()?;
+                    None
+                }
+            };
+            if let Some(SequencedMessage { seq: msg_seq, msg }) = msg {
+                // Messages are supposed to arrive in order, so the view can't
+                // be trusted if one doesn't; catch up with a snapshot.
+                if msg_seq < seq {
+                    let record = Record::new(
+                        RecordKind::Error,
+                        "received a message out of order, resyncing".to_string(),
+                    );
+                    self.log_handle.push(record.into_list_item(&self.theme));
+                    tx_client.send(UserCommand::Resync)?;
+                    waker.wake()?;
+                }
+                seq = msg_seq;
+                if let Some(notification) = Notification::from_message(&self.username, &msg) {
+                    self.notify(&notification);
+                }
                 match msg {
                     ServerMessage::Ack(msg) => {
+                        if self
+                            .turn
+                            .as_ref()
+                            .is_some_and(|(username, _)| *username == msg.username)
+                        {
+                            match msg.command {
+                                UserCommand::TakeAction(_) => self.turn = None,
+                                UserCommand::Rename(ref new_username) => {
+                                    if let Some((ref mut username, _)) = self.turn {
+                                        username.clone_from(new_username);
+                                    }
+                                }
+                                _ => {}
+                            }
+                        }
                         if msg.username == self.username {
                             match msg.command {
                                 // Our action was acknowledged, so we don't need warnings anymore.
                                 UserCommand::TakeAction(_) => {
                                     turn_warnings.clear();
+                                    self.cancel_confirmation();
+                                    if let Err(error) = self.notifier.restore_title() {
+                                        let record =
+                                            Record::new(RecordKind::Error, error.to_string());
+                                        self.log_handle.push(record.into_list_item(&self.theme));
+                                    }
                                 }
                                 // Our action timed-out and so the server booted us; let's exit.
                                 UserCommand::Leave => return Ok(()),
+                                // Our new username was accepted, so we need to use it from now on.
+                                UserCommand::Rename(ref new_username) => {
+                                    self.username.clone_from(new_username);
+                                }
+                                UserCommand::Mute(ref username) => {
+                                    self.muted.insert(username.clone());
+                                }
+                                UserCommand::Unmute(ref username) => {
+                                    self.muted.remove(username);
+                                }
                                 _ => {}
                             }
                         }
-                        let record = Record::new(RecordKind::Ack, msg.to_string());
-                        self.log_handle.push(record.into());
+                        // Mutes follow users through renames, same as the server.
+                        if let UserCommand::Rename(ref new_username) = msg.command {
+                            if self.muted.remove(&msg.username) {
+                                self.muted.insert(new_username.clone());
+                            }
+                        }
+                        // All-ins are logged from the game event that follows
+                        // them, which says how much the player committed.
+                        if msg.command != UserCommand::TakeAction(Action::AllIn) {
+                            let record = Record::new(RecordKind::Ack, msg.to_string());
+                            self.log_handle.push(record.into_list_item(&self.theme));
+                        }
                     }
                     ServerMessage::ClientError(error) => {
                         let record = Record::new(RecordKind::Error, error.to_string());
-                        self.log_handle.push(record.into());
+                        self.log_handle.push(record.into_list_item(&self.theme));
+                    }
+                    // Dealt cards show up on the board instead of the log.
+                    ServerMessage::GameEvent(
+                        hand_id,
+                        GameEvent::CommunityCardDealt(card, position),
+                    ) => {
+                        self.dealer.deal(hand_id, card, position);
+                    }
+                    // Decks are kept around so they can be verified later.
+                    ServerMessage::GameEvent(hand_id, event) => {
+                        match event {
+                            GameEvent::DeckCommitment(commitment) => {
+                                self.deck_commitment = Some((hand_id, commitment));
+                            }
+                            GameEvent::DeckReveal(ref reveal) => {
+                                self.deck_reveal = Some((hand_id, reveal.clone()));
+                            }
+                            _ => {}
+                        }
+                        let record = Record::new(RecordKind::Game, event.to_string());
+                        self.log_handle.push(record.into_list_item(&self.theme));
+                    }
+                    // Operators are sent views with every hand showing right
+                    // after their regular views, which they're shown instead.
+                    ServerMessage::GameView(new_view) | ServerMessage::OmniscientView(new_view) => {
+                        self.hand_history.record(&new_view);
+                        view = Arc::unwrap_or_clone(new_view);
+                    }
+                    ServerMessage::Leaderboard(leaderboard) => {
+                        self.leaderboard = leaderboard;
+                        self.show_leaderboard = true;
+                    }
+                    // Booting players is always the last step of a hand, and
+                    // pending actions don't carry over to the next one.
+                    ServerMessage::Phase(GamePhase::BootingPlayers) => {
+                        self.hand_history.finish();
+                        self.pending_action = None;
+                        self.turn = None;
+                    }
+                    // The status already describes the phase for people.
+                    ServerMessage::Phase(_) => {}
+                    // Let the server know we're still here.
+                    ServerMessage::Ping => {
+                        tx_client.send(UserCommand::Pong)?;
+                        waker.wake()?;
+                    }
+                    ServerMessage::ReconnectSecret(secret) => self.secret = Some(secret),
+                    // Snapshots replace everything we know about the game.
+                    ServerMessage::Snapshot(snapshot) => {
+                        let Snapshot {
+                            view: new_view,
+                            status,
+                            action_options: new_action_options,
+                            max_raise: new_max_raise,
+                            turn_id: new_turn_id,
+                            ..
+                        } = *snapshot;
+                        self.hand_history.record(&new_view);
+                        view = Arc::unwrap_or_clone(new_view);
+                        self.dealer.clear();
+                        // Snapshots don't say when the turn started.
+                        self.turn = None;
+                        self.cancel_confirmation();
+                        let record = Record::new(RecordKind::Game, status);
+                        self.log_handle.push(record.into_list_item(&self.theme));
+                        max_raise = new_max_raise;
+                        self.turn_id = new_turn_id;
+                        match new_action_options {
+                            Some(new_action_options) => {
+                                action_options = new_action_options;
+                                turn_warnings.reset();
+                                let record =
+                                    Record::new(RecordKind::Alert, "it's your turn!".to_string());
+                                self.log_handle.push(record.into_list_item(&self.theme));
+                            }
+                            None => {
+                                action_options.clear();
+                                turn_warnings.clear();
+                            }
+                        }
                     }
-                    ServerMessage::GameView(new_view) => view = new_view,
                     ServerMessage::Status(msg) => {
                         let record = Record::new(RecordKind::Game, msg);
-                        self.log_handle.push(record.into());
+                        self.log_handle.push(record.into_list_item(&self.theme));
+                    }
+                    ServerMessage::TurnAnnouncement { ref username, .. } => {
+                        self.turn = Some((username.clone(), Instant::now()));
+                        let record = Record::new(RecordKind::Game, msg.to_string());
+                        self.log_handle.push(record.into_list_item(&self.theme));
                     }
-                    ServerMessage::TurnSignal(new_action_options) => {
+                    ServerMessage::TurnSignal {
+                        action_options: new_action_options,
+                        max_raise: new_max_raise,
+                        turn_id: new_turn_id,
+                        ..
+                    } => {
                         action_options = new_action_options;
+                        max_raise = new_max_raise;
+                        self.turn_id = Some(new_turn_id);
                         turn_warnings.reset();
+                        self.cancel_confirmation();
+                        // Reviews can wait, but the turn can't.
+                        self.review = None;
                         let record = Record::new(RecordKind::Alert, "it's your turn!".to_string());
-                        self.log_handle.push(record.into());
+                        self.log_handle.push(record.into_list_item(&self.theme));
+
+                        // Pending actions are only good for one turn, and are
+                        // dropped if they no longer fit the situation.
+                        if let Some(pending_action) = self.pending_action.take() {
+                            let big_blind = view.board.is_empty().then_some(view.big_blind);
+                            match resolve_pending_action(pending_action, &action_options, big_blind)
+                            {
+                                Some(action) => {
+                                    let record = Record::new(
+                                        RecordKind::You,
+                                        format!("{pending_action}: {action}"),
+                                    );
+                                    self.log_handle.push(record.into_list_item(&self.theme));
+                                    let command = UserCommand::TakeAction(action);
+                                    tx_client.send(command.for_turn(self.turn_id))?;
+                                    waker.wake()?;
+                                }
+                                None => {
+                                    let record = Record::new(
+                                        RecordKind::Alert,
+                                        format!("{pending_action} no longer applies"),
+                                    );
+                                    self.log_handle.push(record.into_list_item(&self.theme));
+                                }
+                            }
+                        }
                     }
                     ServerMessage::UserError(error) => {
                         let record = Record::new(RecordKind::Error, error.to_string());
-                        self.log_handle.push(record.into());
+                        self.log_handle.push(record.into_list_item(&self.theme));
                     }
                 };
             }
+            self.handle_identity_messages()?;
 
             // Signal how much time is left to the user at specific intervals.
             if let Some(warning) = turn_warnings.check() {
                 let record = Record::new(RecordKind::Alert, format!("{warning:>2} second(s) left"));
-                self.log_handle.push(record.into());
+                self.log_handle.push(record.into_list_item(&self.theme));
+            }
+        }
+    }
+
+    /// Handle what the server sent the other users the user is playing as.
+    /// Only what's tied to them is kept track of, since everything else
+    /// is logged from what's sent to the user.
+    fn handle_identity_messages(&mut self) -> Result<(), Error> {
+        let mut identities = mem::take(&mut self.identities);
+        let mut left = HashSet::new();
+        for identity in identities.iter_mut() {
+            loop {
+                let SequencedMessage { seq, msg } = match identity.rx_server.try_recv() {
+                    Ok(msg) => msg,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        let record = Record::new(
+                            RecordKind::Error,
+                            format!("{}'s connection dropped, reconnecting", identity.username),
+                        );
+                        self.log_handle.push(record.into_list_item(&self.theme));
+                        let client = self.reconnect(&identity.username, identity.secret)?;
+                        identity.secret = client.secret;
+                        (identity.tx_client, identity.rx_server, identity.waker) =
+                            spawn_connection(client.stream)?;
+                        identity.seq = 0;
+                        identity.pending_action = None;
+                        identity.tx_client.send(UserCommand::Resync)?;
+                        identity.waker.wake()?;
+                        break;
+                    }
+                };
+                if seq < identity.seq {
+                    identity.tx_client.send(UserCommand::Resync)?;
+                    identity.waker.wake()?;
+                }
+                identity.seq = seq;
+                if let Some(notification) = Notification::from_message(&identity.username, &msg) {
+                    self.notify(&notification);
+                }
+                match msg {
+                    ServerMessage::Ack(msg) if msg.username == identity.username => {
+                        match msg.command {
+                            UserCommand::TakeAction(_) => identity.turn_warnings.clear(),
+                            UserCommand::Leave => {
+                                let record = Record::new(
+                                    RecordKind::Alert,
+                                    format!("no longer playing as {}", identity.username),
+                                );
+                                self.log_handle.push(record.into_list_item(&self.theme));
+                                left.insert(identity.username.clone());
+                            }
+                            UserCommand::Rename(new_username) => identity.username = new_username,
+                            _ => {}
+                        }
+                    }
+                    ServerMessage::ClientError(error) => {
+                        let record = Record::new(
+                            RecordKind::Error,
+                            format!("{}: {error}", identity.username),
+                        );
+                        self.log_handle.push(record.into_list_item(&self.theme));
+                    }
+                    ServerMessage::GameView(new_view) => {
+                        identity.hand_history.record(&new_view);
+                        identity.view = Arc::unwrap_or_clone(new_view);
+                    }
+                    ServerMessage::Phase(GamePhase::BootingPlayers) => {
+                        identity.hand_history.finish();
+                        identity.pending_action = None;
+                    }
+                    ServerMessage::Ping => {
+                        identity.tx_client.send(UserCommand::Pong)?;
+                        identity.waker.wake()?;
+                    }
+                    ServerMessage::ReconnectSecret(secret) => identity.secret = Some(secret),
+                    ServerMessage::Snapshot(snapshot) => {
+                        identity.hand_history.record(&snapshot.view);
+                        identity.view = Arc::unwrap_or_clone(snapshot.view);
+                        identity.max_raise = snapshot.max_raise;
+                        identity.turn_id = snapshot.turn_id;
+                        match snapshot.action_options {
+                            Some(action_options) => {
+                                identity.action_options = action_options;
+                                identity.turn_warnings.reset();
+                            }
+                            None => {
+                                identity.action_options.clear();
+                                identity.turn_warnings.clear();
+                            }
+                        }
+                    }
+                    ServerMessage::TurnSignal {
+                        action_options,
+                        max_raise,
+                        turn_id,
+                        ..
+                    } => {
+                        identity.action_options = action_options;
+                        identity.max_raise = max_raise;
+                        identity.turn_id = Some(turn_id);
+                        identity.turn_warnings.reset();
+
+                        // Pending actions are taken for the user the same as
+                        // they would be if they were switched to.
+                        let big_blind = identity
+                            .view
+                            .board
+                            .is_empty()
+                            .then_some(identity.view.big_blind);
+                        let action = identity.pending_action.take().map(|pending_action| {
+                            let action = resolve_pending_action(
+                                pending_action,
+                                &identity.action_options,
+                                big_blind,
+                            );
+                            (pending_action, action)
+                        });
+                        let record = match action {
+                            Some((pending_action, Some(action))) => {
+                                let record = Record::new(
+                                    RecordKind::You,
+                                    format!("{}: {pending_action}: {action}", identity.username),
+                                );
+                                let command = UserCommand::TakeAction(action);
+                                identity.tx_client.send(command.for_turn(identity.turn_id))?;
+                                identity.waker.wake()?;
+                                record
+                            }
+                            Some((pending_action, None)) => Record::new(
+                                RecordKind::Alert,
+                                format!(
+                                    "{pending_action} no longer applies, it's {}'s turn! press F8 to switch",
+                                    identity.username
+                                ),
+                            ),
+                            None => Record::new(
+                                RecordKind::Alert,
+                                format!("it's {}'s turn! press F8 to switch", identity.username),
+                            ),
+                        };
+                        self.log_handle.push(record.into_list_item(&self.theme));
+                    }
+                    ServerMessage::UserError(error) => {
+                        let record = Record::new(
+                            RecordKind::Error,
+                            format!("{}: {error}", identity.username),
+                        );
+                        self.log_handle.push(record.into_list_item(&self.theme));
+                    }
+                    // Everything else is the same for every user.
+                    _ => {}
+                }
+            }
+        }
+        identities.retain(|identity| !left.contains(&identity.username));
+        self.identities = identities;
+        Ok(())
+    }
+
+    /// Run the notification's hooks, logging any that fail.
+    fn notify(&mut self, notification: &Notification) {
+        if let Err(error) = self.notifier.notify(notification) {
+            let record = Record::new(RecordKind::Error, format!("couldn't notify: {error}"));
+            self.log_handle.push(record.into_list_item(&self.theme));
+        }
+    }
+
+    /// Connect to the server again as the same user, presenting their
+    /// reconnect secret so the server hands their old connection over.
+    /// Without a secret, the server may take a moment to notice the old
+    /// connection dropped, so connecting is retried a few times before
+    /// giving up.
+    fn reconnect(&self, username: &str, secret: Option<ReconnectSecret>) -> Result<Client, Error> {
+        for _ in 0..MAX_RECONNECT_ATTEMPTS {
+            match Client::reconnect(username, &self.addr, secret) {
+                Ok(client) => return Ok(client),
+                Err(_) => thread::sleep(RECONNECT_INTERVAL),
+            }
+        }
+        bail!("couldn't reconnect to {} as {username}", self.addr)
+    }
+
+    fn toggle_confirm(&mut self) {
+        self.confirmer.toggle();
+        let repr = if self.confirmer.is_enabled() {
+            "confirming big bets"
+        } else {
+            "not confirming big bets"
+        };
+        let record = Record::new(RecordKind::Alert, repr.to_string());
+        self.log_handle.push(record.into_list_item(&self.theme));
+    }
+
+    fn toggle_layout(&mut self) {
+        self.layout.toggle();
+        let repr = match self.layout.fixed() {
+            Some(mode) => format!("{mode} layout"),
+            None => "layout follows the terminal's width".to_string(),
+        };
+        let record = Record::new(RecordKind::Alert, repr);
+        self.log_handle.push(record.into_list_item(&self.theme));
+    }
+
+    fn toggle_mute(&mut self) {
+        self.notifier.toggle_mute();
+        let repr = if self.notifier.is_muted() {
+            "notifications muted"
+        } else {
+            "notifications unmuted"
+        };
+        let record = Record::new(RecordKind::Alert, repr.to_string());
+        self.log_handle.push(record.into_list_item(&self.theme));
+    }
+
+    /// Pick an action to take as soon as it's the user's turn, or clear
+    /// it if it's already picked.
+    fn toggle_pending_action(&mut self, pending_action: PendingAction) {
+        if self.pending_action == Some(pending_action) {
+            self.pending_action = None;
+        } else {
+            self.pending_action = Some(pending_action);
+        }
+    }
+
+    /// Start reviewing the last completed hand, or go back to the live
+    /// game if already reviewing.
+    fn toggle_review(&mut self) {
+        if self.review.is_some() {
+            self.review = None;
+        } else {
+            self.review = self.hand_history.review();
+            if self.review.is_none() {
+                let record = Record::new(RecordKind::Error, "no hands to review yet".to_string());
+                self.log_handle.push(record.into_list_item(&self.theme));
+            }
+        }
+    }
+
+    /// Check the last revealed deck against the commitment made to it
+    /// and the cards seen during its hand, describing how it went.
+    fn verify_last_hand(&self) -> Record {
+        let Some((hand_id, ref reveal)) = self.deck_reveal else {
+            return Record::new(RecordKind::Error, "no hands to verify yet".to_string());
+        };
+        let commitment = match self.deck_commitment {
+            Some((commitment_hand_id, ref commitment)) if commitment_hand_id == hand_id => {
+                commitment
+            }
+            _ => {
+                return Record::new(
+                    RecordKind::Error,
+                    format!("missed the deck commitment for hand #{hand_id}"),
+                )
             }
+        };
+        if !reveal.verify(commitment) {
+            return Record::new(
+                RecordKind::Error,
+                format!("hand #{hand_id}'s deck doesn't match its commitment"),
+            );
+        }
+        match self.hand_history.cards_seen(hand_id) {
+            Some((hole_cards, board)) if !reveal.dealt(&hole_cards, &board) => Record::new(
+                RecordKind::Error,
+                format!("hand #{hand_id}'s cards weren't dealt from its deck"),
+            ),
+            Some(_) => Record::new(
+                RecordKind::Game,
+                format!("hand #{hand_id}'s deck matches its commitment and the cards dealt"),
+            ),
+            // Hands that weren't watched from the start weren't recorded.
+            None => Record::new(
+                RecordKind::Game,
+                format!("hand #{hand_id}'s deck matches its commitment"),
+            ),
         }
     }
 
-    fn draw(&mut self, view: &GameView, frame: &mut Frame) {
+    fn draw(&mut self, live_view: &GameView, frame: &mut Frame) {
+        // Draw the snapshot being reviewed in place of the live game. The
+        // review is over if its hand was dropped from the history.
+        let reviewed = self.review.as_ref().and_then(|review| {
+            self.hand_history
+                .get(review)
+                .map(|(view, snapshot_idx, num_snapshots)| {
+                    (
+                        Arc::clone(view),
+                        review.hand_id(),
+                        snapshot_idx,
+                        num_snapshots,
+                    )
+                })
+        });
+        if reviewed.is_none() {
+            self.review = None;
+        }
+        // Community cards that haven't been revealed yet are left off the
+        // live game's board.
+        let dealt_view;
+        let live_view = match self.dealer.board(live_view) {
+            board if board.len() < live_view.board.len() => {
+                dealt_view = GameView {
+                    board: board.to_vec(),
+                    ..live_view.clone()
+                };
+                &dealt_view
+            }
+            _ => live_view,
+        };
+        // The user's hole cards stay hidden after switching to them until
+        // they're shown, since someone else may still be looking.
+        let concealed_view;
+        let live_view = if self.hidden {
+            let mut view = live_view.clone();
+            for player in view.players.iter_mut() {
+                if player.user.name == self.username {
+                    player.conceal_cards();
+                }
+            }
+            concealed_view = view;
+            &concealed_view
+        } else {
+            live_view
+        };
+        let view = reviewed
+            .as_ref()
+            .map_or(live_view, |(view, ..)| view.as_ref());
+        // Turn announcements only apply to the live game.
+        let turn = self.turn.clone().filter(|_| reviewed.is_none());
+        let turn = turn.as_ref();
+
+        // Paint the theme's background before anything else.
+        frame.render_widget(Block::new().style(self.theme.base), frame.area());
+
         let window = Layout::vertical([
             Constraint::Min(6),
             Constraint::Length(3),
             Constraint::Length(1),
         ]);
         let [top_area, user_input_area, help_area] = window.areas(frame.area());
-        let [view_area, log_area] =
-            Layout::vertical([Constraint::Percentage(55), Constraint::Percentage(45)])
+        let action_history = action_history_to_lines(view);
+        let action_history_height = match action_history.len() {
+            0 => 0,
+            num_streets => num_streets as u16 + 2,
+        };
+        match self.layout.pick(frame.area().width) {
+            LayoutMode::Compact => {
+                let [header_area, players_area, log_area] = Layout::vertical([
+                    Constraint::Length(3),
+                    Constraint::Max(view.players.len() as u16 + 2),
+                    Constraint::Min(3),
+                ])
                 .areas(top_area);
-        let [lobby_area, table_area] =
-            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
-                .areas(view_area);
+                self.draw_header(view, frame, header_area);
+                let players = Table::new(
+                    self.player_rows(view, turn, true),
+                    [
+                        Constraint::Max(1),
+                        Constraint::Max(4),
+                        Constraint::Fill(2),
+                        Constraint::Fill(1),
+                        Constraint::Fill(1),
+                    ]
+                    .into_iter()
+                    .chain(iter::repeat_n(
+                        Constraint::Max(4),
+                        view.variant.num_hole_cards(),
+                    )),
+                )
+                .block(Block::bordered().title(" players  "));
+                frame.render_widget(players, players_area);
+                self.draw_log(frame, log_area);
+            }
+            LayoutMode::Standard => {
+                let [view_area, log_area] =
+                    Layout::vertical([Constraint::Percentage(55), Constraint::Percentage(45)])
+                        .areas(top_area);
+                let [lobby_area, table_area] =
+                    Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                        .areas(view_area);
+                let [table_area, action_history_area] = Layout::vertical([
+                    Constraint::Min(6),
+                    Constraint::Length(action_history_height),
+                ])
+                .areas(table_area);
+                self.draw_lobby(view, frame, lobby_area);
+                let table = Table::new(
+                    self.player_rows(view, turn, false),
+                    [
+                        Constraint::Max(3),
+                        Constraint::Fill(1),
+                        Constraint::Fill(2),
+                        Constraint::Fill(2),
+                        Constraint::Fill(2),
+                        Constraint::Fill(3),
+                    ]
+                    .into_iter()
+                    // One column per hole card and another for the hand.
+                    .chain(iter::repeat_n(
+                        Constraint::Fill(1),
+                        view.variant.num_hole_cards() + 1,
+                    )),
+                )
+                .block(
+                    self.table_block(view).title(
+                        block::Title::from(board_to_vec_of_spans(
+                            view,
+                            self.card_style,
+                            &self.theme,
+                        ))
+                        .position(block::Position::Top)
+                        .alignment(Alignment::Left),
+                    ),
+                );
+                frame.render_widget(table, table_area);
+                draw_action_history(action_history, frame, action_history_area);
+                self.draw_log(frame, log_area);
+            }
+            LayoutMode::Wide => {
+                let [table_area, side_area] =
+                    Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)])
+                        .areas(top_area);
+                let [table_area, action_history_area] = Layout::vertical([
+                    Constraint::Min(12),
+                    Constraint::Length(action_history_height),
+                ])
+                .areas(table_area);
+                let [lobby_area, log_area] =
+                    Layout::vertical([Constraint::Percentage(35), Constraint::Percentage(65)])
+                        .areas(side_area);
+                self.draw_seats(view, turn, frame, table_area);
+                draw_action_history(action_history, frame, action_history_area);
+                self.draw_lobby(view, frame, lobby_area);
+                self.draw_log(frame, log_area);
+            }
+        }
+
+        // Render user input area.
+        let username = self.username.clone();
+        let addr = self.addr.clone();
+        let mut user_input_block = block::Block::bordered().title(Span::styled(
+            format!(" {username}@{addr}  "),
+            self.theme.prompt,
+        ));
+        if let Some(pending_action) = self.pending_action {
+            user_input_block = user_input_block.title(
+                block::Title::from(Span::styled(
+                    format!(" pre-selected: {pending_action}  "),
+                    self.theme.pending_action,
+                ))
+                .alignment(Alignment::Right),
+            );
+        }
+        let user_input = Paragraph::new(self.user_input.value.as_str())
+            .style(Style::default())
+            .block(user_input_block);
+        frame.render_widget(user_input, user_input_area);
+        frame.set_cursor_position(Position::new(
+            // Draw the cursor at the current position in the input field.
+            // This position is can be controlled via the left and right arrow key
+            user_input_area.x + self.user_input.char_idx as u16 + 1,
+            // Move one line down, from the border to the input line
+            user_input_area.y + 1,
+        ));
+
+        // Render user input help message, or a reminder of how to get
+        // around when reviewing.
+        let key = self.theme.key;
+        let help_message = match (self.confirmer.pending(), reviewed) {
+            _ if self.hidden => vec![
+                Span::styled(format!("playing as {}: ", self.username), self.theme.alert),
+                "press ".into(),
+                Span::styled("Enter", key),
+                " to show their cards, or ".into(),
+                Span::styled("F8", key),
+                " to switch to the next user".into(),
+            ],
+            // The user has to see what they're confirming and how long
+            // they have left to do it.
+            (Some(confirmation), _) => {
+                let secs_remaining = self
+                    .turn
+                    .as_ref()
+                    .filter(|(username, _)| *username == self.username)
+                    .map(|(_, t)| DEFAULT_ACTION_TIMEOUT.saturating_sub(t.elapsed()).as_secs());
+                vec![
+                    Span::styled(confirmation.prompt(secs_remaining), self.theme.alert),
+                    " press ".into(),
+                    Span::styled("y", key),
+                    " or ".into(),
+                    Span::styled("Enter", key),
+                    " to confirm, or any other key to cancel".into(),
+                ]
+            }
+            (None, Some((_, hand_id, snapshot_idx, num_snapshots))) => vec![
+                Span::styled(
+                    format!(
+                        "reviewing hand #{hand_id} ({}/{num_snapshots}): ",
+                        snapshot_idx + 1
+                    ),
+                    self.theme.review,
+                ),
+                Span::styled("←/→", key),
+                " to step, ".into(),
+                Span::styled("↑/↓", key),
+                " to change hands, or ".into(),
+                Span::styled("Esc", key),
+                " to go back to the live game".into(),
+            ],
+            (None, None) => vec![
+                "press ".into(),
+                Span::styled("Tab", key),
+                " to view help, press ".into(),
+                Span::styled("Enter", key),
+                " to record a command, or press ".into(),
+                Span::styled("Esc", key),
+                " to exit".into(),
+            ],
+        };
+        let help_style = Style::default();
+        let help_message = Text::from(Line::from(help_message)).patch_style(help_style);
+        let help_message = Paragraph::new(help_message);
+        frame.render_widget(help_message, help_area);
+
+        // Render the help menu.
+        if self.show_help_menu {
+            let vertical = Layout::vertical([Constraint::Max(60)]).flex(Flex::Center);
+            let horizontal = Layout::horizontal([Constraint::Max(92)]).flex(Flex::Center);
+            let [help_menu_area] = vertical.areas(frame.area());
+            let [help_menu_area] = horizontal.areas(help_menu_area);
+            frame.render_widget(Clear, help_menu_area); // clears out the background
+
+            // Render help text.
+            let help_text = Paragraph::new(HELP).style(self.theme.base).block(
+                block::Block::bordered()
+                    .title(" commands  ")
+                    .padding(Padding::uniform(1)),
+            );
+            frame.render_widget(help_text, help_menu_area);
+        }
+
+        // Render the leaderboard.
+        if self.show_leaderboard {
+            let height = self.leaderboard.len() as u16 + 2;
+            let vertical = Layout::vertical([Constraint::Max(height)]).flex(Flex::Center);
+            let horizontal = Layout::horizontal([Constraint::Max(40)]).flex(Flex::Center);
+            let [leaderboard_area] = vertical.areas(frame.area());
+            let [leaderboard_area] = horizontal.areas(leaderboard_area);
+            frame.render_widget(Clear, leaderboard_area); // clears out the background
+
+            let rows = self.leaderboard.iter().map(|(username, net)| {
+                let net = Cell::new(Text::from(format!("{net:+}")).alignment(Alignment::Right));
+                let row = Row::new(vec![Cell::new(username.as_str()), net]);
+                if username == &self.username {
+                    row.bold()
+                } else {
+                    row
+                }
+            });
+            let table = Table::new(rows, [Constraint::Fill(1), Constraint::Max(12)])
+                .style(self.theme.base)
+                .block(
+                    block::Block::bordered()
+                        .title(" leaderboard  ")
+                        .padding(Padding::horizontal(1)),
+                );
+            frame.render_widget(table, leaderboard_area);
+        }
+    }
+
+    /// Render the board, pot, and blinds on their own for the compact
+    /// layout.
+    fn draw_header(&self, view: &GameView, frame: &mut Frame, area: Rect) {
+        let mut header = board_to_vec_of_spans(view, self.card_style, &self.theme);
+        header.push(pot_to_string(view).into());
+        header.push(blinds_to_string(view).into());
+        let header =
+            Paragraph::new(Line::from(header))
+                .block(Block::bordered().title(
+                    block::Title::from(hand_id_to_string(view)).alignment(Alignment::Right),
+                ));
+        frame.render_widget(header, area);
+    }
+
+    /// Render spectators and waitlisters side by side.
+    fn draw_lobby(&self, view: &GameView, frame: &mut Frame, area: Rect) {
         let [spectator_area, waitlister_area] =
             Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
-                .areas(lobby_area);
+                .areas(area);
 
-        // Render spectators area.
         let mut spectators = Vec::from_iter(view.spectators.values());
         spectators.sort_unstable();
         let spectators = Table::new(
             spectators
                 .iter()
-                .map(|user| user_to_row(&self.username, user)),
-            [Constraint::Percentage(50), Constraint::Percentage(50)],
+                .map(|user| user_to_row(&self.username, &self.muted, user, &self.theme)),
+            [
+                Constraint::Percentage(35),
+                Constraint::Percentage(45),
+                Constraint::Percentage(20),
+            ],
         )
         .block(
             Block::bordered()
@@ -631,12 +1911,15 @@ impl App {
         );
         frame.render_widget(spectators, spectator_area);
 
-        // Render waitlisters area.
         let waitlisters = Table::new(
             view.waitlist
                 .iter()
-                .map(|user| user_to_row(&self.username, user)),
-            [Constraint::Percentage(50), Constraint::Percentage(50)],
+                .map(|user| user_to_row(&self.username, &self.muted, user, &self.theme)),
+            [
+                Constraint::Percentage(35),
+                Constraint::Percentage(45),
+                Constraint::Percentage(20),
+            ],
         )
         .block(
             Block::bordered()
@@ -644,180 +1927,456 @@ impl App {
                 .title(" waitlisters  "),
         );
         frame.render_widget(waitlisters, waitlister_area);
+    }
 
-        // Render table area.
-        let table = Table::new(
-            view.players.iter().enumerate().map(|(player_idx, player)| {
-                // Indicator if it's the player's move.
-                let move_repr = match view.next_action_idx {
-                    Some(next_action_idx) if player_idx == next_action_idx => "→",
-                    _ => "",
-                };
-                let move_repr = Text::from(move_repr);
-
-                // Indicator for what blind each player pays.
-                let button_repr = if player_idx == view.big_blind_idx {
-                    "BB"
-                } else if player_idx == view.small_blind_idx {
-                    "SB"
-                } else {
-                    ""
-                };
-                let button_repr = Text::from(button_repr);
-
-                // Username column.
-                let username_repr = player.user.name.clone();
-                let username_repr = Text::from(username_repr);
-
-                // Money column.
-                let money_repr = format!("${}", player.user.money);
-                let money_repr = Text::from(money_repr);
-
-                // State column.
-                let state_repr = player.state.to_string();
-                let state_repr = Text::from(state_repr);
-
-                // This is the final row representation for the table entry.
-                let mut row = vec![
-                    Cell::new(move_repr.alignment(Alignment::Center)),
-                    Cell::new(button_repr.alignment(Alignment::Left)),
-                    Cell::new(username_repr.alignment(Alignment::Left)),
-                    Cell::new(money_repr.alignment(Alignment::Right)),
-                    Cell::new(state_repr.alignment(Alignment::Center)),
-                ];
-
-                // Player cards styled according to suit.
-                for card_idx in 0..2 {
-                    let card_repr = match player.cards.get(card_idx) {
-                        Some(card) => Text::from(card_to_span(card)),
-                        None => Text::from(""),
-                    };
-                    let card_cell = Cell::new(card_repr.alignment(Alignment::Right));
-                    row.push(card_cell);
-                }
-
-                // Player's highest subhand displayed.
-                let hand_repr = if player.cards.is_empty() {
-                    "".to_string()
-                } else {
-                    let mut cards = view.board.clone();
-                    cards.extend(player.cards.clone());
-                    functional::prepare_hand(&mut cards);
-                    let hand = functional::eval(&cards);
-                    if let Some(subhand) = hand.first() {
-                        format!("({})", subhand.rank)
-                    } else {
-                        "".to_string()
-                    }
-                };
-                let hand_repr = Text::from(hand_repr).alignment(Alignment::Right);
-                let hand_cell = Cell::new(hand_repr);
-                row.push(hand_cell);
-
-                let row = Row::new(row);
-                if self.username == player.user.name {
-                    row.bold().white()
-                } else {
-                    row
-                }
-            }),
-            [
-                Constraint::Max(3),
-                Constraint::Fill(1),
-                Constraint::Fill(2),
-                Constraint::Fill(2),
-                Constraint::Fill(2),
-                Constraint::Fill(1),
-                Constraint::Fill(1),
-                Constraint::Fill(1),
-            ],
-        )
-        .block(
-            block::Block::bordered()
-                .padding(Padding::uniform(1))
-                .title(
-                    block::Title::from(board_to_vec_of_spans(view))
-                        .position(block::Position::Top)
-                        .alignment(Alignment::Left),
-                )
-                .title(
-                    block::Title::from(blinds_to_string(view))
-                        .position(block::Position::Bottom)
-                        .alignment(Alignment::Right),
-                )
-                .title(
-                    block::Title::from(pot_to_string(view))
-                        .position(block::Position::Bottom)
-                        .alignment(Alignment::Left),
-                ),
-        );
-        frame.render_widget(table, table_area);
-
-        // Render log window.
+    /// Render the log window and its scrollbar.
+    fn draw_log(&mut self, frame: &mut Frame, area: Rect) {
         let log_records = self.log_handle.list_items.clone();
         let log_records = List::new(log_records)
             .direction(ListDirection::BottomToTop)
             .block(block::Block::bordered().title(" history  "));
-        frame.render_stateful_widget(log_records, log_area, &mut self.log_handle.list_state);
+        frame.render_stateful_widget(log_records, area, &mut self.log_handle.list_state);
 
-        // Render log window scrollbar.
         frame.render_stateful_widget(
             Scrollbar::new(ScrollbarOrientation::VerticalRight)
                 .symbols(scrollbar::VERTICAL)
                 .begin_symbol(None)
                 .end_symbol(None),
-            log_area.inner(Margin {
+            area.inner(Margin {
                 vertical: 1,
                 horizontal: 1,
             }),
             &mut self.log_handle.scroll_state,
         );
+    }
 
-        // Render user input area.
-        let username = self.username.clone();
-        let addr = self.addr.clone();
-        let user_input = Paragraph::new(self.user_input.value.as_str())
-            .style(Style::default())
-            .block(block::Block::bordered().title(format!(" {username}@{addr}  ").light_green()));
-        frame.render_widget(user_input, user_input_area);
-        frame.set_cursor_position(Position::new(
-            // Draw the cursor at the current position in the input field.
-            // This position is can be controlled via the left and right arrow key
-            user_input_area.x + self.user_input.char_idx as u16 + 1,
-            // Move one line down, from the border to the input line
-            user_input_area.y + 1,
-        ));
+    /// Render seats around an oval table for the wide layout, with the
+    /// user sitting at the bottom if they're playing and the board in the
+    /// middle.
+    fn draw_seats(
+        &self,
+        view: &GameView,
+        turn: Option<&(Username, Instant)>,
+        frame: &mut Frame,
+        area: Rect,
+    ) {
+        let block = self.table_block(view);
+        let inner_area = block.inner(area);
+        // Size of a terminal cell in canvas coordinates, for centering
+        // text on seats.
+        let cell_width = 2.0 / inner_area.width.max(1) as f64;
+        let cell_height = 2.0 / inner_area.height.max(1) as f64;
+        let num_players = view.players.len();
+        let positions = seat_positions(num_players);
+        let user_idx = view
+            .players
+            .iter()
+            .position(|player| player.user.name == self.username)
+            .unwrap_or(0);
+        let mut labels = vec![];
+        for (player_idx, player) in view.players.iter().enumerate() {
+            let (x, y) = positions[(player_idx + num_players - user_idx) % num_players];
+            let secs_remaining = secs_remaining(turn, player);
+            let move_repr = match view.next_action_idx {
+                Some(next_action_idx) if player_idx == next_action_idx => "→ ",
+                _ => "",
+            };
+            let name_repr = user_to_string(&player.user, &self.muted);
+            let name_repr = match secs_remaining {
+                Some(secs) => format!("{name_repr} ({secs}s)"),
+                None => name_repr,
+            };
+            let name_repr = format!("{move_repr}{} {name_repr}", button_to_str(view, player_idx));
+            let mut cards = vec![];
+            for slot in player_cards(view, player) {
+                let card = match slot {
+                    CardSlot::Back => card_back_to_span(self.card_style),
+                    CardSlot::Empty => continue,
+                    CardSlot::Face(card) => card_to_span(card, self.card_style, &self.theme),
+                };
+                cards.push(card);
+                cards.push(" ".into());
+            }
+            cards.push(hand_to_string(view, player).into());
+            let lines = vec![
+                Line::styled(
+                    name_repr.trim().to_string(),
+                    self.player_style(player, secs_remaining.is_some()),
+                ),
+                Line::from(
+                    format!(
+                        "${} {} {}",
+                        player.user.money,
+                        player.state,
+                        bets_to_string(player)
+                    )
+                    .trim()
+                    .to_string(),
+                ),
+                Line::from(cards),
+            ];
+            // Seats sit just outside the table's edge.
+            labels.push((0.8 * x, 0.75 * y, lines));
+        }
+        let mut board = board_to_vec_of_spans(view, self.card_style, &self.theme);
+        board.push(pot_to_string(view).into());
+        labels.push((0.0, 0.0, vec![Line::from(board)]));
 
-        // Render user input help message.
-        let help_message = vec![
-            "press ".into(),
-            "Tab".bold().white(),
-            " to view help, press ".into(),
-            "Enter".bold().white(),
-            " to record a command, or press ".into(),
-            "Esc".bold().white(),
-            " to exit".into(),
-        ];
-        let help_style = Style::default();
-        let help_message = Text::from(Line::from(help_message)).patch_style(help_style);
-        let help_message = Paragraph::new(help_message);
-        frame.render_widget(help_message, help_area);
+        let felt = self.theme.felt;
+        let canvas = Canvas::default()
+            .block(block)
+            .background_color(self.theme.base.bg.unwrap_or(Color::Reset))
+            .x_bounds([-1.0, 1.0])
+            .y_bounds([-1.0, 1.0])
+            .paint(move |ctx| {
+                ctx.draw(&Circle {
+                    x: 0.0,
+                    y: 0.0,
+                    radius: 0.5,
+                    color: felt,
+                });
+                for (x, y, lines) in labels.iter() {
+                    let top = y + cell_height * (lines.len() - 1) as f64 / 2.0;
+                    for (line_idx, line) in lines.iter().enumerate() {
+                        // Labels that'd hang off the canvas aren't drawn
+                        // at all, so keep them inside it.
+                        let width = cell_width * line.width() as f64;
+                        let x = (x - width / 2.0).clamp(-1.0, (1.0 - width).max(-1.0));
+                        ctx.print(x, top - cell_height * line_idx as f64, line.clone());
+                    }
+                }
+            });
+        frame.render_widget(canvas, area);
+    }
 
-        // Render the help menu.
-        if self.show_help_menu {
-            let vertical = Layout::vertical([Constraint::Max(24)]).flex(Flex::Center);
-            let horizontal = Layout::horizontal([Constraint::Max(92)]).flex(Flex::Center);
-            let [help_menu_area] = vertical.areas(frame.area());
-            let [help_menu_area] = horizontal.areas(help_menu_area);
-            frame.render_widget(Clear, help_menu_area); // clears out the background
+    /// Return table rows for players. Compact rows leave out bets and
+    /// hands.
+    fn player_rows(
+        &self,
+        view: &GameView,
+        turn: Option<&(Username, Instant)>,
+        compact: bool,
+    ) -> Vec<Row<'static>> {
+        let mut rows = vec![];
+        for (player_idx, player) in view.players.iter().enumerate() {
+            // Indicator if it's the player's move.
+            let move_repr = match view.next_action_idx {
+                Some(next_action_idx) if player_idx == next_action_idx => "→",
+                _ => "",
+            };
+            let move_repr = Text::from(move_repr);
 
-            // Render help text.
-            let help_text = Paragraph::new(HELP).style(Style::default()).block(
-                block::Block::bordered()
-                    .title(" commands  ")
-                    .padding(Padding::uniform(1)),
-            );
-            frame.render_widget(help_text, help_menu_area);
+            // Indicator for the dealer button and what blind each player
+            // pays.
+            let button_repr = Text::from(button_to_str(view, player_idx));
+
+            // Username column, along with how long the player has left to
+            // act if it's their turn.
+            let secs_remaining = secs_remaining(turn, player);
+            let username_repr = user_to_string(&player.user, &self.muted);
+            let username_repr = match secs_remaining {
+                Some(secs) => format!("{username_repr} ({secs}s)"),
+                None => username_repr,
+            };
+            let username_repr = Text::from(username_repr);
+
+            // Money column.
+            let money_repr = format!("${}", player.user.money);
+            let money_repr = Text::from(money_repr);
+
+            // State column.
+            let state_repr = player.state.to_string();
+            let state_repr = Text::from(state_repr);
+
+            // This is the final row representation for the table entry.
+            let mut row = vec![
+                Cell::new(move_repr.alignment(Alignment::Center)),
+                Cell::new(button_repr.alignment(Alignment::Left)),
+                Cell::new(username_repr.alignment(Alignment::Left)),
+                Cell::new(money_repr.alignment(Alignment::Right)),
+                Cell::new(state_repr.alignment(Alignment::Center)),
+            ];
+
+            // Bets column.
+            if !compact {
+                let bets_repr = Text::from(bets_to_string(player));
+                row.push(Cell::new(bets_repr.alignment(Alignment::Right)));
+            }
+
+            // Player cards styled according to suit.
+            for slot in player_cards(view, player) {
+                let card_repr = match slot {
+                    CardSlot::Back => Text::from(card_back_to_span(self.card_style)),
+                    CardSlot::Empty => Text::from(""),
+                    CardSlot::Face(card) => {
+                        Text::from(card_to_span(card, self.card_style, &self.theme))
+                    }
+                };
+                let card_cell = Cell::new(card_repr.alignment(Alignment::Right));
+                row.push(card_cell);
+            }
+
+            // Player's highest subhand displayed, but only for whole hands.
+            if !compact {
+                let hand_repr =
+                    Text::from(hand_to_string(view, player)).alignment(Alignment::Right);
+                row.push(Cell::new(hand_repr));
+            }
+
+            let row = Row::new(row).style(self.player_style(player, secs_remaining.is_some()));
+            rows.push(row);
+        }
+        rows
+    }
+
+    /// Return how a player's row or seat is styled, highlighting the user
+    /// and whoever's turn it is.
+    fn player_style(&self, player: &PlayerView, is_turn: bool) -> Style {
+        let style = if self.username == player.user.name {
+            self.theme.you
+        } else if player.disconnected {
+            self.theme.disconnected
+        } else {
+            Style::default()
+        };
+        if is_turn {
+            style.patch(self.theme.turn)
+        } else {
+            style
+        }
+    }
+
+    /// Return the block that frames the table, titled with the hand,
+    /// blinds, and pot.
+    fn table_block<'a>(&self, view: &'a GameView) -> Block<'a> {
+        block::Block::bordered()
+            .padding(Padding::uniform(1))
+            .title(
+                block::Title::from(hand_id_to_string(view))
+                    .position(block::Position::Top)
+                    .alignment(Alignment::Right),
+            )
+            .title(
+                block::Title::from(blinds_to_string(view))
+                    .position(block::Position::Bottom)
+                    .alignment(Alignment::Right),
+            )
+            .title(
+                block::Title::from(pot_to_string(view))
+                    .position(block::Position::Bottom)
+                    .alignment(Alignment::Left),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, VecDeque};
+
+    use private_poker::entities::{
+        Action, ActionRecord, Bet, BetAction, Card, GameVariant, GameView, HoleCards, PlayerState,
+        PlayerView, PotView, Privacy, Profile, Reveal, Street, Suit, User,
+    };
+    use ratatui::{style::Stylize, text::Span};
+
+    use super::{
+        action_history_to_lines, bets_to_string, card_to_span, player_cards, CardSlot, CardStyle,
+        Theme,
+    };
+
+    #[test]
+    fn summarize_actions_by_street() {
+        let record = |username: &str, action, street| ActionRecord {
+            username: username.to_string(),
+            action,
+            street,
+        };
+        let view = GameView {
+            hand_id: 1,
+            donations: 0.0,
+            small_blind: 5,
+            big_blind: 10,
+            spectators: HashMap::new(),
+            waitlist: VecDeque::new(),
+            open_seats: VecDeque::new(),
+            players: vec![],
+            board: vec![],
+            pot: PotView {
+                size: 0,
+                street_bets: 0,
+            },
+            button_idx: 0,
+            small_blind_idx: 0,
+            big_blind_idx: 0,
+            next_action_idx: None,
+            bomb_pot: false,
+            variant: GameVariant::Holdem,
+            privacy: Privacy::Open,
+            limit: None,
+            action_history: vec![
+                record("alice", Action::Raise(20), Street::Preflop),
+                record("bob", Action::Call(10), Street::Preflop),
+                record("bob", Action::Check, Street::Flop),
+                record("alice", Action::AllIn, Street::Flop),
+                record("bob", Action::Fold, Street::Flop),
+            ],
+            equities: vec![],
+        };
+        assert_eq!(
+            action_history_to_lines(&view),
+            vec![
+                " preflop: alice raises $20, bob calls $10",
+                " flop: bob checks, alice goes all-in, bob folds",
+            ]
+        );
+    }
+
+    #[test]
+    fn render_bets_in_front_of_seats() {
+        let player = |last_bet, investment| PlayerView {
+            user: User {
+                name: "bob".to_string(),
+                money: 100,
+                profile: Profile::default(),
+            },
+            state: PlayerState::Wait,
+            cards: HoleCards::None,
+            revealed: Reveal::Hidden,
+            disconnected: false,
+            last_bet,
+            investment,
+        };
+        let raise = Bet {
+            action: BetAction::Raise,
+            amount: 40,
+        };
+        assert_eq!(bets_to_string(&player(None, 0)), "");
+        assert_eq!(bets_to_string(&player(None, 20)), "($20 in)");
+        assert_eq!(
+            bets_to_string(&player(Some(raise), 60)),
+            "raise $40 ($60 in)"
+        );
+    }
+
+    #[test]
+    fn card_slots_show_backs_only_for_hidden_cards() {
+        let view = GameView {
+            hand_id: 1,
+            donations: 0.0,
+            small_blind: 5,
+            big_blind: 10,
+            spectators: HashMap::new(),
+            waitlist: VecDeque::new(),
+            open_seats: VecDeque::new(),
+            players: vec![],
+            board: vec![],
+            pot: PotView {
+                size: 0,
+                street_bets: 0,
+            },
+            button_idx: 0,
+            small_blind_idx: 0,
+            big_blind_idx: 0,
+            next_action_idx: None,
+            bomb_pot: false,
+            variant: GameVariant::Holdem,
+            privacy: Privacy::Open,
+            limit: None,
+            action_history: vec![],
+            equities: vec![],
+        };
+        let player = |state, cards, revealed| PlayerView {
+            user: User {
+                name: "bob".to_string(),
+                money: 100,
+                profile: Profile::default(),
+            },
+            state,
+            cards,
+            revealed,
+            disconnected: false,
+            last_bet: None,
+            investment: 0,
+        };
+        let (ace, king) = (Card(1, Suit::Spade), Card(13, Suit::Heart));
+
+        let folded = player(PlayerState::Fold, HoleCards::None, Reveal::Hidden);
+        assert_eq!(
+            player_cards(&view, &folded),
+            [CardSlot::Empty, CardSlot::Empty]
+        );
+        let hidden = player(
+            PlayerState::AllIn,
+            HoleCards::Hidden { count: 2 },
+            Reveal::Hidden,
+        );
+        assert_eq!(
+            player_cards(&view, &hidden),
+            [CardSlot::Back, CardSlot::Back]
+        );
+        // One shown card is put where it is in the hand.
+        let one_shown = player(
+            PlayerState::AllIn,
+            HoleCards::Revealed(vec![king]),
+            Reveal::Card(1),
+        );
+        assert_eq!(
+            player_cards(&view, &one_shown),
+            [CardSlot::Back, CardSlot::Face(&king)]
+        );
+        let mut own = player(
+            PlayerState::Call,
+            HoleCards::Revealed(vec![ace, king]),
+            Reveal::Hidden,
+        );
+        assert_eq!(
+            player_cards(&view, &own),
+            [CardSlot::Face(&ace), CardSlot::Face(&king)]
+        );
+        own.conceal_cards();
+        assert_eq!(own.cards, HoleCards::Hidden { count: 2 });
+    }
+
+    #[test]
+    fn render_cards_with_each_style() {
+        let cards = [
+            Card(1, Suit::Club),
+            Card(14, Suit::Diamond),
+            Card(10, Suit::Heart),
+            Card(13, Suit::Spade),
+        ];
+        for (card_style, expected) in [
+            (
+                CardStyle::Plain,
+                [
+                    Span::from(" A/c"),
+                    Span::from(" A/d"),
+                    Span::from("10/h"),
+                    Span::from(" K/s"),
+                ],
+            ),
+            (
+                CardStyle::Unicode,
+                [
+                    Span::from(" A♣"),
+                    " A♦".light_red(),
+                    "10♥".light_red(),
+                    Span::from(" K♠"),
+                ],
+            ),
+            (
+                CardStyle::FourColor,
+                [
+                    " A/c".light_green(),
+                    " A/d".light_blue(),
+                    "10/h".light_red(),
+                    Span::from(" K/s"),
+                ],
+            ),
+        ] {
+            for (card, expected) in cards.iter().zip(expected) {
+                assert_eq!(card_to_span(card, card_style, &Theme::default()), expected);
+            }
         }
     }
 }
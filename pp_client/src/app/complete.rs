@@ -0,0 +1,239 @@
+use private_poker::entities::GameView;
+
+use crate::command::COMMANDS;
+
+/// Commands whose first argument is a username.
+const USERNAME_COMMANDS: [&str; 5] = ["accept", "insure", "mute", "sidebet", "unmute"];
+
+/// The word before the cursor completed as far as it can be.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Completion {
+    /// Everything the word could complete to, sorted.
+    pub candidates: Vec<String>,
+    /// The input with the word replaced by what all the candidates
+    /// start with, or by the only candidate and a space.
+    pub value: String,
+    /// Cursor position right after the replaced word.
+    pub char_idx: usize,
+}
+
+/// Return what a partially typed word could complete to given the words
+/// typed before it. The first word completes to a command, and a
+/// command's first argument completes depending on the command, like
+/// usernames for muting.
+pub fn candidates(
+    preceding: &[&str],
+    partial: &str,
+    username: &str,
+    view: &GameView,
+) -> Vec<String> {
+    let options = match preceding {
+        [] => COMMANDS.iter().map(ToString::to_string).collect(),
+        [command] if USERNAME_COMMANDS.contains(command) => usernames(username, view),
+        ["rebuy"] => vec!["off".to_string(), "on".to_string()],
+        _ => vec![],
+    };
+    options
+        .into_iter()
+        .filter(|option| option.starts_with(partial))
+        .collect()
+}
+
+/// Complete the word before the cursor, returning `None` if it can't be
+/// completed.
+pub fn complete(
+    value: &str,
+    char_idx: usize,
+    username: &str,
+    view: &GameView,
+) -> Option<Completion> {
+    let before: String = value.chars().take(char_idx).collect();
+    let after: String = value.chars().skip(char_idx).collect();
+    // Commands are split on ASCII whitespace, which is always one byte.
+    let word_idx = before
+        .rfind(|c: char| c.is_ascii_whitespace())
+        .map_or(0, |idx| idx + 1);
+    let (head, partial) = before.split_at(word_idx);
+    let preceding: Vec<&str> = head.split_ascii_whitespace().collect();
+    let candidates = candidates(&preceding, partial, username, view);
+    let replacement = match candidates.as_slice() {
+        [] => return None,
+        [candidate] if after.starts_with(|c: char| c.is_ascii_whitespace()) => candidate.clone(),
+        [candidate] => format!("{candidate} "),
+        _ => common_prefix(&candidates),
+    };
+    let before = format!("{head}{replacement}");
+    Some(Completion {
+        candidates,
+        char_idx: before.chars().count(),
+        value: before + &after,
+    })
+}
+
+/// Return the longest prefix every candidate starts with.
+fn common_prefix(candidates: &[String]) -> String {
+    let first = &candidates[0];
+    let len = candidates[1..].iter().fold(first.len(), |len, candidate| {
+        first
+            .chars()
+            .zip(candidate.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .sum::<usize>()
+            .min(len)
+    });
+    first[..len].to_string()
+}
+
+/// Return everyone in the game other than the user, sorted.
+fn usernames(username: &str, view: &GameView) -> Vec<String> {
+    let mut usernames: Vec<String> = view
+        .players
+        .iter()
+        .map(|player| &player.user)
+        .chain(view.waitlist.iter())
+        .chain(view.spectators.values())
+        .filter(|user| user.name != username)
+        .map(|user| user.name.clone())
+        .collect();
+    usernames.sort();
+    usernames.dedup();
+    usernames
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, VecDeque};
+
+    use private_poker::entities::{
+        GameVariant, GameView, HoleCards, PlayerState, PlayerView, PotView, Privacy, Profile,
+        Reveal, User,
+    };
+
+    use super::{candidates, complete, Completion};
+
+    fn user(name: &str) -> User {
+        User {
+            name: name.to_string(),
+            money: 200,
+            profile: Profile::default(),
+        }
+    }
+
+    fn view(players: &[&str], waitlist: &[&str], spectators: &[&str]) -> GameView {
+        GameView {
+            hand_id: 1,
+            donations: 0.0,
+            small_blind: 5,
+            big_blind: 10,
+            spectators: spectators
+                .iter()
+                .map(|name| (name.to_string(), user(name)))
+                .collect::<HashMap<_, _>>(),
+            waitlist: waitlist
+                .iter()
+                .map(|name| user(name))
+                .collect::<VecDeque<_>>(),
+            open_seats: VecDeque::new(),
+            players: players
+                .iter()
+                .map(|name| PlayerView {
+                    user: user(name),
+                    state: PlayerState::Wait,
+                    cards: HoleCards::None,
+                    revealed: Reveal::default(),
+                    disconnected: false,
+                    last_bet: None,
+                    investment: 0,
+                })
+                .collect(),
+            board: vec![],
+            pot: PotView {
+                size: 0,
+                street_bets: 0,
+            },
+            button_idx: 0,
+            small_blind_idx: 0,
+            big_blind_idx: 0,
+            next_action_idx: None,
+            bomb_pot: false,
+            variant: GameVariant::Holdem,
+            privacy: Privacy::Open,
+            limit: None,
+            action_history: vec![],
+            equities: vec![],
+        }
+    }
+
+    #[test]
+    fn candidates_depend_on_the_command() {
+        let view = view(&["ognf", "alice"], &["bob"], &["alfred"]);
+        let candidates =
+            |preceding: &[&str], partial: &str| candidates(preceding, partial, "ognf", &view);
+
+        assert_eq!(
+            candidates(&[], "s"),
+            ["seat", "show", "sidebet", "spectate", "start"]
+        );
+        assert_eq!(candidates(&[], "ca"), ["call"]);
+        assert!(candidates(&[], "x").is_empty());
+
+        // Usernames come from players, the waitlist, and spectators, but
+        // never include the user.
+        assert_eq!(candidates(&["mute"], ""), ["alfred", "alice", "bob"]);
+        assert_eq!(candidates(&["insure"], "al"), ["alfred", "alice"]);
+        assert!(candidates(&["mute"], "o").is_empty());
+        assert_eq!(candidates(&["rebuy"], "o"), ["off", "on"]);
+
+        // Only a command's first argument completes, and commands that
+        // don't take a username never complete one.
+        assert!(candidates(&["insure", "alice"], "").is_empty());
+        assert!(candidates(&["name"], "al").is_empty());
+        assert!(candidates(&["raise"], "").is_empty());
+    }
+
+    #[test]
+    fn candidates_follow_the_view() {
+        let mut view = view(&["ognf"], &[], &["alice"]);
+        assert_eq!(candidates(&["accept"], "", "ognf", &view), ["alice"]);
+        view.spectators.clear();
+        view.waitlist.push_back(user("bob"));
+        assert_eq!(candidates(&["accept"], "", "ognf", &view), ["bob"]);
+    }
+
+    #[test]
+    fn completions_replace_the_word_before_the_cursor() {
+        let view = view(&["ognf", "alice"], &["alfred"], &[]);
+        let complete = |value: &str, char_idx: usize| complete(value, char_idx, "ognf", &view);
+
+        // A single candidate is filled in and followed by a space.
+        assert_eq!(
+            complete("mute ali", 8),
+            Some(Completion {
+                candidates: vec!["alice".to_string()],
+                value: "mute alice ".to_string(),
+                char_idx: 11,
+            })
+        );
+        // Several candidates are completed as far as they agree.
+        assert_eq!(
+            complete("unmute a", 8),
+            Some(Completion {
+                candidates: vec!["alfred".to_string(), "alice".to_string()],
+                value: "unmute al".to_string(),
+                char_idx: 9,
+            })
+        );
+        // Only what's before the cursor is completed, keeping the rest.
+        assert_eq!(
+            complete("le 10", 2),
+            Some(Completion {
+                candidates: vec!["leaderboard".to_string()],
+                value: "leaderboard 10".to_string(),
+                char_idx: 11,
+            })
+        );
+        assert_eq!(complete("raise 1", 7), None);
+        assert_eq!(complete("dance", 5), None);
+    }
+}
@@ -0,0 +1,247 @@
+use private_poker::entities::{Action, Usd};
+use std::fmt;
+
+/// Actions committing more than this fraction of the user's stack have
+/// to be confirmed by default.
+pub const DEFAULT_CONFIRM_FRACTION: f64 = 0.5;
+
+/// When actions have to be confirmed before they're sent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConfirmConfig {
+    pub enabled: bool,
+    /// Fraction of the user's remaining stack an action has to commit
+    /// more than to need confirming.
+    pub fraction: f64,
+}
+
+impl Default for ConfirmConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            fraction: DEFAULT_CONFIRM_FRACTION,
+        }
+    }
+}
+
+/// An action waiting on the user to confirm it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Confirmation {
+    pub action: Action,
+    /// How much the action commits.
+    pub amount: Usd,
+    /// The user's stack after the action.
+    pub stack: Usd,
+}
+
+impl Confirmation {
+    /// Return what confirming the action would look like if it commits
+    /// more than the fraction of the user's stack.
+    pub fn check(action: &Action, money: Usd, fraction: f64) -> Option<Self> {
+        let amount = match action {
+            Action::AllIn => money,
+            Action::Call(amount) | Action::Raise(amount) => (*amount).min(money),
+            Action::Check | Action::Fold => return None,
+        };
+        (amount as f64 > fraction * money as f64).then_some(Self {
+            action: action.clone(),
+            amount,
+            stack: money - amount,
+        })
+    }
+
+    /// Return the prompt asking the user to confirm, along with how long
+    /// they have left to act if it's known.
+    pub fn prompt(&self, secs_remaining: Option<u64>) -> String {
+        match secs_remaining {
+            Some(secs_remaining) => format!("{self} ({secs_remaining}s left)"),
+            None => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Confirmation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self.action {
+            Action::AllIn => "go all-in with",
+            Action::Call(_) => "call",
+            _ => "raise",
+        };
+        write!(
+            f,
+            "{action} ${}, leaving ${} in your stack?",
+            self.amount, self.stack
+        )
+    }
+}
+
+/// The key the user pressed while confirming an action.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConfirmKey {
+    Yes,
+    Other,
+}
+
+/// Holds actions that commit too much of the user's stack until the
+/// user presses a key confirming them.
+#[derive(Debug)]
+pub struct Confirmer {
+    config: ConfirmConfig,
+    pending: Option<Confirmation>,
+}
+
+impl Confirmer {
+    /// Drop the action waiting to be confirmed, returning whether there
+    /// was one.
+    pub fn cancel(&mut self) -> bool {
+        self.pending.take().is_some()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn new(config: ConfirmConfig) -> Self {
+        Self {
+            config,
+            pending: None,
+        }
+    }
+
+    pub fn pending(&self) -> Option<&Confirmation> {
+        self.pending.as_ref()
+    }
+
+    /// Respond to a key press while an action is waiting to be
+    /// confirmed, returning the action if it was confirmed. Any other key
+    /// cancels the action.
+    pub fn press(&mut self, key: ConfirmKey) -> Option<Action> {
+        let confirmation = self.pending.take()?;
+        match key {
+            ConfirmKey::Yes => Some(confirmation.action),
+            ConfirmKey::Other => None,
+        }
+    }
+
+    /// Return the action if it can be sent right away, otherwise hold on
+    /// to it until it's confirmed.
+    pub fn review(&mut self, action: Action, money: Usd) -> Option<Action> {
+        if !self.config.enabled {
+            return Some(action);
+        }
+        match Confirmation::check(&action, money, self.config.fraction) {
+            Some(confirmation) => {
+                self.pending = Some(confirmation);
+                None
+            }
+            None => Some(action),
+        }
+    }
+
+    /// Turn confirming actions on or off, dropping any action waiting to
+    /// be confirmed.
+    pub fn toggle(&mut self) {
+        self.config.enabled = !self.config.enabled;
+        self.pending = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use private_poker::entities::Action;
+
+    use super::{ConfirmConfig, ConfirmKey, Confirmation, Confirmer};
+
+    #[test]
+    fn only_actions_past_the_threshold_need_confirming() {
+        let check = |action: Action| Confirmation::check(&action, 200, 0.5);
+        assert_eq!(check(Action::Raise(100)), None);
+        assert_eq!(
+            check(Action::Raise(101)),
+            Some(Confirmation {
+                action: Action::Raise(101),
+                amount: 101,
+                stack: 99,
+            })
+        );
+        assert_eq!(check(Action::Call(15)), None);
+        // Calls and raises past the stack only commit the stack.
+        assert_eq!(
+            check(Action::Call(300)),
+            Some(Confirmation {
+                action: Action::Call(300),
+                amount: 200,
+                stack: 0,
+            })
+        );
+        assert_eq!(check(Action::AllIn).map(|c| c.amount), Some(200));
+        assert_eq!(check(Action::Check), None);
+        assert_eq!(check(Action::Fold), None);
+
+        // Short stacks need confirming for smaller amounts.
+        assert!(Confirmation::check(&Action::Call(15), 20, 0.5).is_some());
+        // Nothing commits more than the whole stack.
+        assert_eq!(Confirmation::check(&Action::AllIn, 200, 1.0), None);
+        assert!(Confirmation::check(&Action::Call(1), 200, 0.0).is_some());
+    }
+
+    #[test]
+    fn confirmations_prompt_with_the_time_left() {
+        let confirmation = Confirmation::check(&Action::Raise(150), 200, 0.5).unwrap();
+        assert_eq!(
+            confirmation.prompt(Some(12)),
+            "raise $150, leaving $50 in your stack? (12s left)"
+        );
+        let confirmation = Confirmation::check(&Action::AllIn, 200, 0.5).unwrap();
+        assert_eq!(
+            confirmation.prompt(None),
+            "go all-in with $200, leaving $0 in your stack?"
+        );
+    }
+
+    #[test]
+    fn confirming_takes_a_second_key_press() {
+        let mut confirmer = Confirmer::new(ConfirmConfig::default());
+        assert_eq!(confirmer.press(ConfirmKey::Yes), None);
+
+        // Small actions go through right away.
+        assert_eq!(
+            confirmer.review(Action::Raise(15), 200),
+            Some(Action::Raise(15))
+        );
+        assert_eq!(confirmer.pending(), None);
+
+        // Big actions wait for a yes.
+        assert_eq!(confirmer.review(Action::Raise(150), 200), None);
+        assert_eq!(confirmer.pending().map(|c| c.amount), Some(150));
+        assert_eq!(confirmer.press(ConfirmKey::Yes), Some(Action::Raise(150)));
+        assert_eq!(confirmer.pending(), None);
+        assert_eq!(confirmer.press(ConfirmKey::Yes), None);
+
+        // Anything else cancels them.
+        assert_eq!(confirmer.review(Action::AllIn, 200), None);
+        assert_eq!(confirmer.press(ConfirmKey::Other), None);
+        assert_eq!(confirmer.pending(), None);
+
+        // Turns can end before the user makes up their mind.
+        assert_eq!(confirmer.review(Action::AllIn, 200), None);
+        assert!(confirmer.cancel());
+        assert!(!confirmer.cancel());
+    }
+
+    #[test]
+    fn confirming_can_be_turned_off() {
+        let mut confirmer = Confirmer::new(ConfirmConfig {
+            enabled: false,
+            ..Default::default()
+        });
+        assert_eq!(confirmer.review(Action::AllIn, 200), Some(Action::AllIn));
+        confirmer.toggle();
+        assert!(confirmer.is_enabled());
+        assert_eq!(confirmer.review(Action::AllIn, 200), None);
+        // Turning it off drops whatever was waiting.
+        confirmer.toggle();
+        assert!(!confirmer.is_enabled());
+        assert_eq!(confirmer.pending(), None);
+        assert_eq!(confirmer.review(Action::AllIn, 200), Some(Action::AllIn));
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use private_poker::entities::{Card, GameView, HandId};
+
+/// Time between revealing community cards dealt in quick succession.
+pub const DEAL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Reveals community cards one at a time as the server deals them, even
+/// though views show every card dealt for a street at once. Views stay
+/// the authority on what's on the board; dealt cards only hold back the
+/// cards in views that haven't been revealed yet.
+#[derive(Debug)]
+pub struct Dealer {
+    interval: Duration,
+    /// Cards dealt but not revealed yet, with the hand they were dealt
+    /// in and their position on the board, in the order they were dealt.
+    unrevealed: VecDeque<(HandId, Card, usize)>,
+    /// When the last card was revealed.
+    last_reveal: Option<Instant>,
+}
+
+impl Dealer {
+    /// Return the part of the view's board that's been revealed. Cards
+    /// that were never dealt, or that don't match what was dealt, are
+    /// revealed right away so a missed event can't desync the board.
+    pub fn board<'a>(&self, view: &'a GameView) -> &'a [Card] {
+        let num_revealed = self
+            .unrevealed
+            .iter()
+            .filter(|(hand_id, card, position)| {
+                *hand_id == view.hand_id && view.board.get(*position) == Some(card)
+            })
+            .map(|(_, _, position)| *position)
+            .min()
+            .unwrap_or(view.board.len());
+        &view.board[..num_revealed]
+    }
+
+    /// Forget about cards that haven't been revealed yet, revealing them
+    /// all at once.
+    pub fn clear(&mut self) {
+        self.unrevealed.clear();
+    }
+
+    /// Queue a dealt card to be revealed. Cards left over from earlier
+    /// hands are dropped.
+    pub fn deal(&mut self, hand_id: HandId, card: Card, position: usize) {
+        self.unrevealed.retain(|(id, ..)| *id == hand_id);
+        self.unrevealed.push_back((hand_id, card, position));
+    }
+
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            unrevealed: VecDeque::new(),
+            last_reveal: None,
+        }
+    }
+
+    /// Reveal the next dealt card if it's been long enough since the
+    /// last one was revealed.
+    pub fn reveal(&mut self, now: Instant) {
+        if self
+            .last_reveal
+            .is_some_and(|last_reveal| now.duration_since(last_reveal) < self.interval)
+        {
+            return;
+        }
+        if self.unrevealed.pop_front().is_some() {
+            self.last_reveal = Some(now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{HashMap, VecDeque},
+        time::{Duration, Instant},
+    };
+
+    use private_poker::entities::{Card, GameVariant, GameView, HandId, PotView, Privacy, Suit};
+
+    use super::Dealer;
+
+    const INTERVAL: Duration = Duration::from_millis(300);
+
+    fn view(hand_id: HandId, board: &[Card]) -> GameView {
+        GameView {
+            hand_id,
+            donations: 0.0,
+            small_blind: 5,
+            big_blind: 10,
+            spectators: HashMap::new(),
+            waitlist: VecDeque::new(),
+            open_seats: VecDeque::new(),
+            players: vec![],
+            board: board.to_vec(),
+            pot: PotView {
+                size: 0,
+                street_bets: 0,
+            },
+            button_idx: 0,
+            small_blind_idx: 0,
+            big_blind_idx: 0,
+            next_action_idx: None,
+            bomb_pot: false,
+            variant: GameVariant::Holdem,
+            privacy: Privacy::Open,
+            limit: None,
+            action_history: vec![],
+            equities: vec![],
+        }
+    }
+
+    fn board() -> Vec<Card> {
+        vec![
+            Card(2, Suit::Club),
+            Card(7, Suit::Diamond),
+            Card(11, Suit::Heart),
+            Card(13, Suit::Spade),
+            Card(1, Suit::Spade),
+        ]
+    }
+
+    #[test]
+    fn flop_is_revealed_one_card_at_a_time() {
+        let board = board();
+        let mut dealer = Dealer::new(INTERVAL);
+        for (position, card) in board.iter().take(3).enumerate() {
+            dealer.deal(1, *card, position);
+        }
+        let flop = view(1, &board[..3]);
+        assert!(dealer.board(&flop).is_empty());
+
+        let start = Instant::now();
+        dealer.reveal(start);
+        assert_eq!(dealer.board(&flop), &board[..1]);
+        dealer.reveal(start + INTERVAL / 2);
+        assert_eq!(dealer.board(&flop), &board[..1]);
+        dealer.reveal(start + INTERVAL);
+        assert_eq!(dealer.board(&flop), &board[..2]);
+        dealer.reveal(start + 2 * INTERVAL);
+        assert_eq!(dealer.board(&flop), &board[..3]);
+
+        // Cards dealt before the view catches up aren't shown early.
+        dealer.deal(1, board[3], 3);
+        dealer.reveal(start + 3 * INTERVAL);
+        assert_eq!(dealer.board(&flop), &board[..3]);
+    }
+
+    #[test]
+    fn views_win_over_missed_or_mismatched_cards() {
+        let board = board();
+        let mut dealer = Dealer::new(INTERVAL);
+
+        // The flop was missed entirely and the turn doesn't match the
+        // view, so the view's board is shown as is.
+        dealer.deal(1, board[4], 3);
+        assert_eq!(dealer.board(&view(1, &board[..4])), &board[..4]);
+
+        // Cards from an old hand don't hold back a new hand's board, and
+        // are dropped once the new hand deals.
+        assert_eq!(dealer.board(&view(2, &board[..4])), &board[..4]);
+        dealer.deal(2, board[0], 0);
+        assert!(dealer.board(&view(2, &board[..1])).is_empty());
+        dealer.clear();
+        assert_eq!(dealer.board(&view(2, &board[..1])), &board[..1]);
+    }
+}
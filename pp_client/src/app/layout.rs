@@ -0,0 +1,144 @@
+use std::{f64::consts::PI, fmt};
+
+/// Terminals narrower than this get the compact layout.
+pub const COMPACT_MAX_WIDTH: u16 = 100;
+/// Terminals at least this wide get the wide layout.
+pub const WIDE_MIN_WIDTH: u16 = 160;
+
+/// How the game is arranged on screen.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LayoutMode {
+    /// A single column with the board and pot on top, then players, then
+    /// the log.
+    Compact,
+    /// The lobby and a table of players side by side above the log.
+    Standard,
+    /// Seats drawn around a table, with the lobby and log off to the side.
+    Wide,
+}
+
+impl fmt::Display for LayoutMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            LayoutMode::Compact => "compact",
+            LayoutMode::Standard => "standard",
+            LayoutMode::Wide => "wide",
+        };
+        write!(f, "{repr}")
+    }
+}
+
+/// Picks a layout based on the terminal's width unless the user fixed
+/// one.
+#[derive(Debug, Default)]
+pub struct LayoutPicker {
+    fixed: Option<LayoutMode>,
+}
+
+impl LayoutPicker {
+    /// Return the layout the user fixed, if any.
+    pub fn fixed(&self) -> Option<LayoutMode> {
+        self.fixed
+    }
+
+    pub fn pick(&self, width: u16) -> LayoutMode {
+        match self.fixed {
+            Some(mode) => mode,
+            None if width < COMPACT_MAX_WIDTH => LayoutMode::Compact,
+            None if width < WIDE_MIN_WIDTH => LayoutMode::Standard,
+            None => LayoutMode::Wide,
+        }
+    }
+
+    /// Cycle through fixing each layout, then going back to picking one
+    /// automatically.
+    pub fn toggle(&mut self) {
+        self.fixed = match self.fixed {
+            None => Some(LayoutMode::Compact),
+            Some(LayoutMode::Compact) => Some(LayoutMode::Standard),
+            Some(LayoutMode::Standard) => Some(LayoutMode::Wide),
+            Some(LayoutMode::Wide) => None,
+        };
+    }
+}
+
+/// Return where each seat goes around a table drawn as the unit circle,
+/// starting with seat 0 at the bottom and going clockwise. Canvases
+/// stretch the circle into an oval to fit the space they're given.
+pub fn seat_positions(num_seats: usize) -> Vec<(f64, f64)> {
+    (0..num_seats)
+        .map(|seat_idx| {
+            let angle = -PI / 2.0 - 2.0 * PI * seat_idx as f64 / num_seats as f64;
+            (angle.cos(), angle.sin())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{seat_positions, LayoutMode, LayoutPicker, COMPACT_MAX_WIDTH, WIDE_MIN_WIDTH};
+
+    const EPSILON: f64 = 1e-9;
+
+    #[test]
+    fn layout_follows_width_until_fixed() {
+        let mut picker = LayoutPicker::default();
+        assert_eq!(picker.fixed(), None);
+        for (width, expected) in [
+            (40, LayoutMode::Compact),
+            (COMPACT_MAX_WIDTH - 1, LayoutMode::Compact),
+            (COMPACT_MAX_WIDTH, LayoutMode::Standard),
+            (WIDE_MIN_WIDTH - 1, LayoutMode::Standard),
+            (WIDE_MIN_WIDTH, LayoutMode::Wide),
+            (300, LayoutMode::Wide),
+        ] {
+            assert_eq!(picker.pick(width), expected, "width {width}");
+        }
+
+        // Toggling fixes each layout in turn regardless of width, then
+        // goes back to following the width.
+        for expected in [LayoutMode::Compact, LayoutMode::Standard, LayoutMode::Wide] {
+            picker.toggle();
+            assert_eq!(picker.fixed(), Some(expected));
+            for width in [40, COMPACT_MAX_WIDTH, WIDE_MIN_WIDTH] {
+                assert_eq!(picker.pick(width), expected);
+            }
+        }
+        picker.toggle();
+        assert_eq!(picker.fixed(), None);
+        assert_eq!(picker.pick(40), LayoutMode::Compact);
+    }
+
+    #[test]
+    fn seats_are_spread_evenly_around_the_table() {
+        for num_seats in 2..=12 {
+            let positions = seat_positions(num_seats);
+            assert_eq!(positions.len(), num_seats);
+
+            // The first seat is at the bottom and the next one is to its
+            // left, going clockwise.
+            let (x, y) = positions[0];
+            assert!(x.abs() < EPSILON && (y + 1.0).abs() < EPSILON);
+            assert!(positions[1].0 < 0.0);
+
+            // Seats are on the table's edge, the same distance apart, and
+            // balanced around its center.
+            let distance = |(x1, y1): (f64, f64), (x2, y2): (f64, f64)| {
+                ((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt()
+            };
+            let spacing = distance(positions[0], positions[1]);
+            for (seat_idx, &position) in positions.iter().enumerate() {
+                assert!((distance(position, (0.0, 0.0)) - 1.0).abs() < EPSILON);
+                let next_position = positions[(seat_idx + 1) % num_seats];
+                assert!((distance(position, next_position) - spacing).abs() < EPSILON);
+            }
+            let (sum_x, sum_y) = positions
+                .iter()
+                .fold((0.0, 0.0), |(sum_x, sum_y), (x, y)| (sum_x + x, sum_y + y));
+            assert!(sum_x.abs() < EPSILON && sum_y.abs() < EPSILON);
+        }
+        // Heads-up players sit across from each other.
+        let positions = seat_positions(2);
+        assert!(positions[1].0.abs() < EPSILON && (positions[1].1 - 1.0).abs() < EPSILON);
+    }
+}
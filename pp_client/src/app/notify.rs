@@ -0,0 +1,261 @@
+use ratatui::crossterm::{execute, terminal::SetTitle};
+use std::{
+    io::{self, Write},
+    process::Command,
+    thread,
+};
+
+use private_poker::{entities::GameEvent, messages::ServerMessage, server::DEFAULT_ACTION_TIMEOUT};
+
+const TITLE: &str = "pp_client";
+
+/// Something the user should hear about even if they aren't looking at
+/// the game.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Notification {
+    /// It's the user's turn, and they have this many seconds to act.
+    Turn { secs_remaining: u64 },
+    /// Another user did something that names the user and waits on
+    /// their response, like offering them insurance.
+    Mentioned(String),
+}
+
+impl Notification {
+    /// Return the notification a message from the server calls for, if
+    /// any.
+    pub fn from_message(username: &str, msg: &ServerMessage) -> Option<Self> {
+        let turn = Notification::Turn {
+            secs_remaining: DEFAULT_ACTION_TIMEOUT.as_secs(),
+        };
+        match msg {
+            // Turn signals are only ever sent to the user whose turn it is.
+            ServerMessage::TurnSignal { .. } => Some(turn),
+            ServerMessage::Snapshot(snapshot) if snapshot.action_options.is_some() => Some(turn),
+            ServerMessage::GameEvent(_, event @ GameEvent::InsuranceOffered(insurance))
+                if insurance.insured == username =>
+            {
+                Some(Notification::Mentioned(event.to_string()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Ways of getting the user's attention.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Hook {
+    /// Ring the terminal bell.
+    Bell,
+    /// Set the terminal's title until the user acts.
+    Flash(String),
+    /// Run a program with the given arguments.
+    Command(String, Vec<String>),
+}
+
+/// Which hooks run for notifications.
+#[derive(Clone, Debug, Default)]
+pub struct NotifyConfig {
+    pub bell: bool,
+    pub flash: bool,
+    /// Program (and any leading arguments) to run. On the user's turn,
+    /// the seconds they have left to act are passed as the last argument.
+    pub command: Option<String>,
+}
+
+/// Runs hooks for notifications. Notifications can be muted without
+/// changing which hooks are configured.
+pub struct Notifier {
+    config: NotifyConfig,
+    muted: bool,
+    /// Whether the terminal's title needs to be restored.
+    flashed: bool,
+}
+
+impl Notifier {
+    /// Return the hooks to run for a notification.
+    pub fn get_hooks(&self, notification: &Notification) -> Vec<Hook> {
+        let mut hooks = vec![];
+        if self.muted {
+            return hooks;
+        }
+        if self.config.bell {
+            hooks.push(Hook::Bell);
+        }
+        let (title, extra_arg) = match notification {
+            Notification::Turn { secs_remaining } => (
+                "it's your turn!".to_string(),
+                Some(secs_remaining.to_string()),
+            ),
+            Notification::Mentioned(repr) => (repr.clone(), None),
+        };
+        if self.config.flash {
+            hooks.push(Hook::Flash(format!("{TITLE}: {title}")));
+        }
+        if let Some(ref command) = self.config.command {
+            let mut args = command.split_whitespace().map(str::to_string);
+            if let Some(program) = args.next() {
+                let args = args.chain(extra_arg).collect();
+                hooks.push(Hook::Command(program, args));
+            }
+        }
+        hooks
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn new(config: NotifyConfig) -> Self {
+        Self {
+            config,
+            muted: false,
+            flashed: false,
+        }
+    }
+
+    /// Run the hooks for a notification. Commands run in the background.
+    pub fn notify(&mut self, notification: &Notification) -> io::Result<()> {
+        for hook in self.get_hooks(notification) {
+            match hook {
+                Hook::Bell => {
+                    let mut stdout = io::stdout();
+                    stdout.write_all(b"\x07")?;
+                    stdout.flush()?;
+                }
+                Hook::Flash(title) => {
+                    execute!(io::stdout(), SetTitle(title))?;
+                    self.flashed = true;
+                }
+                Hook::Command(program, args) => {
+                    let mut child = Command::new(program).args(args).spawn()?;
+                    thread::spawn(move || child.wait());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Restore the terminal's title if it was changed for a notification.
+    pub fn restore_title(&mut self) -> io::Result<()> {
+        if self.flashed {
+            execute!(io::stdout(), SetTitle(TITLE))?;
+            self.flashed = false;
+        }
+        Ok(())
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use private_poker::{
+        entities::{Action, DecisionContext, GameEvent, GameVariant, Insurance},
+        messages::{ClientMessage, ServerMessage, UserCommand},
+        server::DEFAULT_ACTION_TIMEOUT,
+    };
+
+    use super::{Hook, Notification, Notifier, NotifyConfig};
+
+    fn insurance_offer(insured: &str) -> ServerMessage {
+        ServerMessage::GameEvent(
+            1,
+            GameEvent::InsuranceOffered(Insurance {
+                insurer: "ognf2".to_string(),
+                insured: insured.to_string(),
+                premium: 10,
+                payout: 20,
+            }),
+        )
+    }
+
+    #[test]
+    fn only_turns_and_mentions_notify() {
+        let msgs = [
+            ServerMessage::Status("game starting in 5 seconds".to_string()),
+            // Only the turn signal notifies, not the announcement before it.
+            ServerMessage::TurnAnnouncement {
+                username: "ognf".to_string(),
+                choices_summary: "check or fold".to_string(),
+            },
+            ServerMessage::Ack(ClientMessage {
+                username: "ognf2".to_string(),
+                command: UserCommand::TakeAction(Action::Check),
+            }),
+            insurance_offer("ognf2"),
+            ServerMessage::TurnSignal {
+                action_options: HashSet::from([Action::Check, Action::Fold]),
+                max_raise: None,
+                context: DecisionContext {
+                    variant: GameVariant::Holdem,
+                    board: vec![],
+                    cards: vec![],
+                    pot: 15,
+                    call: 0,
+                    min_raise: None,
+                    max_raise: None,
+                    stack: 990,
+                    position: 2,
+                    num_players: 3,
+                },
+                turn_id: 1,
+            },
+            insurance_offer("ognf"),
+        ];
+        let notifications: Vec<_> = msgs
+            .iter()
+            .filter_map(|msg| Notification::from_message("ognf", msg))
+            .collect();
+        assert_eq!(
+            notifications,
+            [
+                Notification::Turn {
+                    secs_remaining: DEFAULT_ACTION_TIMEOUT.as_secs()
+                },
+                Notification::Mentioned("ognf2 offered ognf $20 of insurance for $10".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn hooks_follow_config_and_mute() {
+        let turn = Notification::Turn { secs_remaining: 30 };
+        let mention = Notification::Mentioned("ognf2 offered ognf insurance".to_string());
+
+        // Nothing is configured by default.
+        let notifier = Notifier::new(NotifyConfig::default());
+        assert!(notifier.get_hooks(&turn).is_empty());
+
+        let mut notifier = Notifier::new(NotifyConfig {
+            bell: true,
+            flash: true,
+            command: Some("notify-send poker".to_string()),
+        });
+        assert_eq!(
+            notifier.get_hooks(&turn),
+            [
+                Hook::Bell,
+                Hook::Flash("pp_client: it's your turn!".to_string()),
+                Hook::Command(
+                    "notify-send".to_string(),
+                    vec!["poker".to_string(), "30".to_string()]
+                ),
+            ]
+        );
+        // Mentions don't come with a time limit.
+        assert_eq!(
+            notifier.get_hooks(&mention)[2],
+            Hook::Command("notify-send".to_string(), vec!["poker".to_string()])
+        );
+
+        notifier.toggle_mute();
+        assert!(notifier.get_hooks(&turn).is_empty());
+        assert!(notifier.get_hooks(&mention).is_empty());
+        notifier.toggle_mute();
+        assert_eq!(notifier.get_hooks(&mention).len(), 3);
+    }
+}
@@ -0,0 +1,311 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use private_poker::entities::{Card, GameView, HandId};
+
+/// Every view received while a hand was being played, in the order they
+/// were received.
+struct RecordedHand {
+    hand_id: HandId,
+    snapshots: Vec<Arc<GameView>>,
+}
+
+/// Keeps the views of the last few completed hands so they can be
+/// reviewed after the fact. Only hands that were watched from the start
+/// are recorded.
+pub struct HandHistory {
+    max_hands: usize,
+    /// Completed hands, oldest first.
+    hands: VecDeque<RecordedHand>,
+    /// The hand that's being played, if it was watched from the start.
+    current: Option<RecordedHand>,
+    /// Hand ID of the most recent view, used to tell when a new hand starts.
+    latest_hand_id: Option<HandId>,
+}
+
+impl HandHistory {
+    /// Return the hole cards that were visible at any point during a
+    /// recorded hand and the board as far as it was dealt, or `None` if
+    /// the hand wasn't recorded.
+    pub fn cards_seen(&self, hand_id: HandId) -> Option<(Vec<Card>, Vec<Card>)> {
+        let hand = self
+            .hands
+            .iter()
+            .chain(self.current.iter())
+            .find(|hand| hand.hand_id == hand_id)?;
+        let mut hole_cards = vec![];
+        for card in hand.snapshots.iter().flat_map(|view| {
+            view.players
+                .iter()
+                .flat_map(|player| player.cards.revealed())
+        }) {
+            if !hole_cards.contains(card) {
+                hole_cards.push(*card);
+            }
+        }
+        let board = hand
+            .snapshots
+            .iter()
+            .map(|view| &view.board)
+            .max_by_key(|board| board.len())
+            .cloned()
+            .unwrap_or_default();
+        Some((hole_cards, board))
+    }
+
+    /// Complete the hand that's being played, if there is one. Views
+    /// received afterwards for the same hand are ignored.
+    pub fn finish(&mut self) {
+        if let Some(hand) = self.current.take() {
+            if self.hands.len() == self.max_hands {
+                self.hands.pop_front();
+            }
+            self.hands.push_back(hand);
+        }
+    }
+
+    /// Return the snapshot that's being reviewed along with its position
+    /// in its hand, or `None` if the hand is no longer recorded.
+    pub fn get(&self, review: &Review) -> Option<(&Arc<GameView>, usize, usize)> {
+        let hand = self.hands.iter().find(|h| h.hand_id == review.hand_id)?;
+        let snapshot = hand.snapshots.get(review.snapshot_idx)?;
+        Some((snapshot, review.snapshot_idx, hand.snapshots.len()))
+    }
+
+    pub fn new(max_hands: usize) -> Self {
+        Self {
+            max_hands,
+            hands: VecDeque::with_capacity(max_hands),
+            current: None,
+            latest_hand_id: None,
+        }
+    }
+
+    /// Record a view, bucketing it with the other views of its hand. A
+    /// change in hand ID means a new hand started, completing the last
+    /// one if it wasn't already.
+    pub fn record(&mut self, view: &Arc<GameView>) {
+        match self.current {
+            Some(ref mut hand) if hand.hand_id == view.hand_id => {
+                hand.snapshots.push(Arc::clone(view));
+            }
+            _ if self.latest_hand_id.is_some_and(|id| id != view.hand_id) => {
+                self.finish();
+                self.current = Some(RecordedHand {
+                    hand_id: view.hand_id,
+                    snapshots: vec![Arc::clone(view)],
+                });
+            }
+            _ => {}
+        }
+        self.latest_hand_id = Some(view.hand_id);
+    }
+
+    /// Start reviewing the most recently completed hand from its first
+    /// snapshot, or return `None` if there aren't any completed hands.
+    pub fn review(&self) -> Option<Review> {
+        self.hands.back().map(|hand| Review {
+            hand_id: hand.hand_id,
+            snapshot_idx: 0,
+        })
+    }
+
+    fn position(&self, hand_id: HandId) -> Option<usize> {
+        self.hands.iter().position(|hand| hand.hand_id == hand_id)
+    }
+}
+
+/// The snapshot of a recorded hand that's being reviewed. Hands are
+/// referred to by ID so hands completed during a review don't change
+/// which hand is being reviewed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Review {
+    hand_id: HandId,
+    snapshot_idx: usize,
+}
+
+impl Review {
+    /// Step back to the previous snapshot of the hand, if there is one.
+    pub fn back(&mut self) {
+        self.snapshot_idx = self.snapshot_idx.saturating_sub(1);
+    }
+
+    /// Step forward to the next snapshot of the hand, if there is one.
+    pub fn forward(&mut self, history: &HandHistory) {
+        if let Some((_, snapshot_idx, num_snapshots)) = history.get(self) {
+            self.snapshot_idx = (snapshot_idx + 1).min(num_snapshots - 1);
+        }
+    }
+
+    pub fn hand_id(&self) -> HandId {
+        self.hand_id
+    }
+
+    /// Switch to the first snapshot of the next recorded hand, if there
+    /// is one.
+    pub fn newer(&mut self, history: &HandHistory) {
+        if let Some(hand) = history
+            .position(self.hand_id)
+            .and_then(|hand_idx| history.hands.get(hand_idx + 1))
+        {
+            self.hand_id = hand.hand_id;
+            self.snapshot_idx = 0;
+        }
+    }
+
+    /// Switch to the first snapshot of the previous recorded hand, if
+    /// there is one.
+    pub fn older(&mut self, history: &HandHistory) {
+        if let Some(hand) = history
+            .position(self.hand_id)
+            .and_then(|hand_idx| hand_idx.checked_sub(1))
+            .and_then(|hand_idx| history.hands.get(hand_idx))
+        {
+            self.hand_id = hand.hand_id;
+            self.snapshot_idx = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{HashMap, VecDeque},
+        sync::Arc,
+    };
+
+    use private_poker::entities::{Card, GameVariant, GameView, HandId, PotView, Privacy, Suit};
+
+    use super::HandHistory;
+
+    fn view(hand_id: HandId, pot: u32) -> Arc<GameView> {
+        Arc::new(GameView {
+            hand_id,
+            donations: 0.0,
+            small_blind: 5,
+            big_blind: 10,
+            spectators: HashMap::new(),
+            waitlist: VecDeque::new(),
+            open_seats: VecDeque::new(),
+            players: vec![],
+            board: vec![],
+            pot: PotView {
+                size: pot,
+                street_bets: 0,
+            },
+            button_idx: 0,
+            small_blind_idx: 0,
+            big_blind_idx: 0,
+            next_action_idx: None,
+            bomb_pot: false,
+            variant: GameVariant::Holdem,
+            privacy: Privacy::Open,
+            limit: None,
+            action_history: vec![],
+            equities: vec![],
+        })
+    }
+
+    #[test]
+    fn bucket_views_by_hand() {
+        let mut history = HandHistory::new(2);
+
+        // Views from before the first hand we watched from the start
+        // aren't recorded.
+        history.record(&view(0, 0));
+        history.record(&view(0, 0));
+        assert!(history.review().is_none());
+
+        // A new hand ID starts a hand, but it isn't reviewable until
+        // it's complete.
+        history.record(&view(1, 15));
+        history.record(&view(1, 30));
+        assert!(history.review().is_none());
+        history.finish();
+        let review = history.review().unwrap();
+        assert_eq!(review.hand_id(), 1);
+        let (snapshot, snapshot_idx, num_snapshots) = history.get(&review).unwrap();
+        assert_eq!(snapshot.pot.size, 15);
+        assert_eq!((snapshot_idx, num_snapshots), (0, 2));
+
+        // Lobby views after the hand is complete aren't recorded.
+        history.record(&view(1, 0));
+        assert_eq!(history.get(&review).unwrap().2, 2);
+
+        // A hand that never got a finish is completed by the next hand,
+        // and the oldest hand is dropped to make room.
+        history.record(&view(2, 15));
+        history.record(&view(3, 15));
+        assert_eq!(history.review().unwrap().hand_id(), 2);
+        history.finish();
+        assert_eq!(history.review().unwrap().hand_id(), 3);
+        assert!(history.get(&review).is_none());
+    }
+
+    #[test]
+    fn cards_seen_during_hand() {
+        let mut history = HandHistory::new(2);
+        history.record(&view(0, 0));
+        let board = vec![
+            Card(2, Suit::Club),
+            Card(3, Suit::Club),
+            Card(4, Suit::Club),
+        ];
+        for num_cards in [0, 3, 0] {
+            history.record(&Arc::new(GameView {
+                board: board[..num_cards].to_vec(),
+                ..Arc::unwrap_or_clone(view(1, 15))
+            }));
+        }
+
+        // The board is taken from wherever it was furthest along, even if
+        // it was cleared by the end of the hand.
+        assert_eq!(history.cards_seen(1), Some((vec![], board.clone())));
+        history.finish();
+        assert_eq!(history.cards_seen(1), Some((vec![], board)));
+        assert_eq!(history.cards_seen(0), None);
+    }
+
+    #[test]
+    fn navigate_review() {
+        let mut history = HandHistory::new(4);
+        history.record(&view(0, 0));
+        for hand_id in 1..=2 {
+            for pot in [15, 30, 60] {
+                history.record(&view(hand_id, pot));
+            }
+            history.finish();
+        }
+
+        // Reviews start at the beginning of the latest hand and can't step
+        // past either end of it.
+        let mut review = history.review().unwrap();
+        assert_eq!(review.hand_id(), 2);
+        review.back();
+        assert_eq!(history.get(&review).unwrap().1, 0);
+        for _ in 0..5 {
+            review.forward(&history);
+        }
+        let (snapshot, snapshot_idx, _) = history.get(&review).unwrap();
+        assert_eq!((snapshot.pot.size, snapshot_idx), (60, 2));
+        review.back();
+        assert_eq!(history.get(&review).unwrap().0.pot.size, 30);
+
+        // Changing hands starts from the beginning of the other hand, and
+        // there's nothing beyond the oldest or newest hands.
+        review.newer(&history);
+        assert_eq!(review.hand_id(), 2);
+        assert_eq!(history.get(&review).unwrap().1, 1);
+        review.older(&history);
+        assert_eq!(review.hand_id(), 1);
+        assert_eq!(history.get(&review).unwrap().1, 0);
+        review.older(&history);
+        assert_eq!(review.hand_id(), 1);
+
+        // New hands don't change the hand that's being reviewed.
+        history.record(&view(3, 15));
+        history.finish();
+        assert_eq!(review.hand_id(), 1);
+        review.newer(&history);
+        assert_eq!(review.hand_id(), 2);
+    }
+}
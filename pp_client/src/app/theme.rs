@@ -0,0 +1,144 @@
+use anyhow::{bail, Error};
+use ratatui::style::{Color, Style, Stylize};
+use std::str::FromStr;
+
+/// Named color themes users can pick from.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ThemeName {
+    /// Bright colors on the terminal's own (usually dark) background.
+    #[default]
+    Dark,
+    /// Dark colors on a white background.
+    Light,
+    /// Bold, saturated colors on a black background.
+    HighContrast,
+}
+
+impl FromStr for ThemeName {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dark" => Ok(ThemeName::Dark),
+            "light" => Ok(ThemeName::Light),
+            "high-contrast" => Ok(ThemeName::HighContrast),
+            other => bail!("unknown theme: {other}"),
+        }
+    }
+}
+
+/// Styles for everything the TUI draws in color.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Theme {
+    /// Everything that isn't styled otherwise, including the background.
+    pub base: Style,
+    /// The user's own rows and seat.
+    pub you: Style,
+    /// Players that're disconnected.
+    pub disconnected: Style,
+    /// The player whose turn it is.
+    pub turn: Style,
+    /// Keys named in help messages.
+    pub key: Style,
+    /// Title of the input box.
+    pub prompt: Style,
+    /// Action pre-selected for the user's next turn.
+    pub pending_action: Style,
+    /// Reminder that a past hand is being reviewed.
+    pub review: Style,
+    /// Outline of the table in the wide layout.
+    pub felt: Color,
+    /// Log record labels.
+    pub ack: Style,
+    pub alert: Style,
+    pub error: Style,
+    pub game: Style,
+    pub command: Style,
+    /// Suits of four-color cards.
+    pub club: Style,
+    pub diamond: Style,
+    pub heart: Style,
+    pub spade: Style,
+    pub wild: Style,
+    /// Hearts and diamonds of unicode cards.
+    pub red_suit: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        ThemeName::default().into()
+    }
+}
+
+impl From<ThemeName> for Theme {
+    fn from(value: ThemeName) -> Self {
+        match value {
+            ThemeName::Dark => Self {
+                base: Style::new(),
+                you: Style::new().bold().white(),
+                disconnected: Style::new().dark_gray(),
+                turn: Style::new().yellow(),
+                key: Style::new().bold().white(),
+                prompt: Style::new().light_green(),
+                pending_action: Style::new().light_yellow(),
+                review: Style::new().bold().light_magenta(),
+                felt: Color::Green,
+                ack: Style::new().light_blue(),
+                alert: Style::new().light_magenta(),
+                error: Style::new().light_red(),
+                game: Style::new().light_yellow(),
+                command: Style::new().light_green(),
+                club: Style::new().light_green(),
+                diamond: Style::new().light_blue(),
+                heart: Style::new().light_red(),
+                spade: Style::new(),
+                wild: Style::new().light_magenta(),
+                red_suit: Style::new().light_red(),
+            },
+            ThemeName::Light => Self {
+                base: Style::new().black().on_white(),
+                you: Style::new().bold().black(),
+                disconnected: Style::new().gray(),
+                turn: Style::new().magenta(),
+                key: Style::new().bold().black(),
+                prompt: Style::new().green(),
+                pending_action: Style::new().magenta(),
+                review: Style::new().bold().magenta(),
+                felt: Color::Green,
+                ack: Style::new().blue(),
+                alert: Style::new().magenta(),
+                error: Style::new().red(),
+                game: Style::new().cyan(),
+                command: Style::new().green(),
+                club: Style::new().green(),
+                diamond: Style::new().blue(),
+                heart: Style::new().red(),
+                spade: Style::new().black(),
+                wild: Style::new().magenta(),
+                red_suit: Style::new().red(),
+            },
+            ThemeName::HighContrast => Self {
+                base: Style::new().white().on_black(),
+                you: Style::new().bold().underlined().white(),
+                disconnected: Style::new().dark_gray().crossed_out(),
+                turn: Style::new().bold().black().on_yellow(),
+                key: Style::new().bold().black().on_white(),
+                prompt: Style::new().bold().light_green(),
+                pending_action: Style::new().bold().black().on_light_yellow(),
+                review: Style::new().bold().black().on_light_magenta(),
+                felt: Color::LightGreen,
+                ack: Style::new().bold().light_cyan(),
+                alert: Style::new().bold().light_magenta(),
+                error: Style::new().bold().white().on_red(),
+                game: Style::new().bold().light_yellow(),
+                command: Style::new().bold().light_green(),
+                club: Style::new().bold().light_green(),
+                diamond: Style::new().bold().light_cyan(),
+                heart: Style::new().bold().light_red(),
+                spade: Style::new().bold().white(),
+                wild: Style::new().bold().light_magenta(),
+                red_suit: Style::new().bold().light_red(),
+            },
+        }
+    }
+}
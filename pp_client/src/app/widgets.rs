@@ -3,10 +3,13 @@ use ratatui::{
     widgets::{ListItem, ListState, ScrollDirection, ScrollbarState},
 };
 
-use std::collections::VecDeque;
+use std::{collections::VecDeque, mem};
 
 use private_poker::constants::MAX_USER_INPUT_LENGTH;
 
+/// Inputs remembered for going back through with the arrow keys.
+const MAX_INPUT_HISTORY: usize = 128;
+
 /// Manages terminal messages and the terminal view position.
 pub struct ScrollableList {
     max_items: usize,
@@ -65,6 +68,12 @@ pub struct UserInput {
     pub char_idx: usize,
     /// Current value of the input box.
     pub value: String,
+    /// Inputs submitted this session, oldest first.
+    history: VecDeque<String>,
+    /// Position in the history while going back through it.
+    history_idx: Option<usize>,
+    /// What was being typed before going back through the history.
+    draft: String,
 }
 
 impl UserInput {
@@ -101,6 +110,33 @@ impl UserInput {
         new_cursor_pos.clamp(0, self.value.chars().count())
     }
 
+    /// Delete everything before the cursor.
+    pub fn delete_to_start(&mut self) {
+        self.value = self.value.chars().skip(self.char_idx).collect();
+        self.char_idx = 0;
+    }
+
+    /// Delete the word before the cursor, along with any whitespace
+    /// between it and the cursor.
+    pub fn delete_word(&mut self) {
+        let before: Vec<char> = self.value.chars().take(self.char_idx).collect();
+        let spaces = before
+            .iter()
+            .rev()
+            .take_while(|c| c.is_whitespace())
+            .count();
+        let word = before
+            .iter()
+            .rev()
+            .skip(spaces)
+            .take_while(|c| !c.is_whitespace())
+            .count();
+        let word_idx = self.char_idx - spaces - word;
+        let after = self.value.chars().skip(self.char_idx);
+        self.value = before[..word_idx].iter().copied().chain(after).collect();
+        self.char_idx = word_idx;
+    }
+
     pub fn delete(&mut self) {
         // Method "remove" is not used on the saved text for deleting the selected char.
         // Reason: Using remove on String works on bytes instead of the chars.
@@ -148,13 +184,125 @@ impl UserInput {
         Self {
             char_idx: 0,
             value: String::new(),
+            history: VecDeque::with_capacity(MAX_INPUT_HISTORY),
+            history_idx: None,
+            draft: String::new(),
         }
     }
 
+    /// Go to the next newer input in the history, or back to what was
+    /// being typed after the newest one.
+    pub fn newer(&mut self) {
+        match self.history_idx {
+            Some(idx) if idx + 1 < self.history.len() => {
+                self.history_idx = Some(idx + 1);
+                self.set(self.history[idx + 1].clone());
+            }
+            Some(_) => {
+                self.history_idx = None;
+                let draft = mem::take(&mut self.draft);
+                self.set(draft);
+            }
+            None => {}
+        }
+    }
+
+    /// Go to the next older input in the history, holding onto what was
+    /// being typed.
+    pub fn older(&mut self) {
+        let idx = match self.history_idx {
+            Some(idx) => idx.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => {
+                self.draft = self.value.clone();
+                self.history.len() - 1
+            }
+        };
+        self.history_idx = Some(idx);
+        self.set(self.history[idx].clone());
+    }
+
+    /// Replace the input, moving the cursor to the end. Values longer than
+    /// the input box allows are ignored.
+    pub fn replace(&mut self, value: String, char_idx: usize) {
+        if value.len() <= MAX_USER_INPUT_LENGTH {
+            self.value = value;
+            self.char_idx = self.clamp_cursor(char_idx);
+        }
+    }
+
+    fn set(&mut self, value: String) {
+        let char_idx = value.chars().count();
+        self.replace(value, char_idx);
+    }
+
     pub fn submit(&mut self) -> String {
-        let input = self.value.clone();
+        let input = mem::take(&mut self.value);
         self.char_idx = 0;
-        self.value.clear();
+        self.history_idx = None;
+        self.draft.clear();
+        // Repeating the last input doesn't need another entry.
+        if !input.trim().is_empty() && self.history.back() != Some(&input) {
+            if self.history.len() == MAX_INPUT_HISTORY {
+                self.history.pop_front();
+            }
+            self.history.push_back(input.clone());
+        }
         input
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::UserInput;
+
+    fn type_input(input: &mut UserInput, value: &str) {
+        for c in value.chars() {
+            input.input(c);
+        }
+    }
+
+    #[test]
+    fn history_goes_back_and_forth() {
+        let mut input = UserInput::new();
+        for value in ["mute ognf", "fold", "fold", " "] {
+            type_input(&mut input, value);
+            input.submit();
+        }
+
+        // Repeats and blank inputs aren't remembered, and what was being
+        // typed comes back after the newest input.
+        type_input(&mut input, "ra");
+        input.older();
+        assert_eq!(input.value, "fold");
+        assert_eq!(input.char_idx, 4);
+        input.older();
+        input.older();
+        assert_eq!(input.value, "mute ognf");
+        input.newer();
+        assert_eq!(input.value, "fold");
+        input.newer();
+        assert_eq!(input.value, "ra");
+        input.newer();
+        assert_eq!(input.value, "ra");
+    }
+
+    #[test]
+    fn words_and_lines_are_deleted_before_the_cursor() {
+        let mut input = UserInput::new();
+        type_input(&mut input, "insure ognf  10");
+        input.move_left();
+        input.move_left();
+        input.delete_word();
+        assert_eq!(input.value, "insure 10");
+        assert_eq!(input.char_idx, 7);
+        input.delete_word();
+        assert_eq!(input.value, "10");
+        assert_eq!(input.char_idx, 0);
+
+        type_input(&mut input, "raise ");
+        input.delete_to_start();
+        assert_eq!(input.value, "10");
+        assert_eq!(input.char_idx, 0);
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::{bail, Error};
+use private_poker::{
+    entities::{Action, Profile, SeatIndex, Usd},
+    messages::UserState,
+    net::messages::UserCommand,
+    validate_action,
+};
+use std::collections::HashSet;
+
+/// Keywords every text command starts with, for completing them. The app
+/// handles "verify" itself rather than parsing it into a command.
+pub const COMMANDS: &[&str] = &[
+    "accept",
+    "all-in",
+    "buyin",
+    "call",
+    "check",
+    "fold",
+    "insure",
+    "leaderboard",
+    "muck",
+    "mute",
+    "name",
+    "play",
+    "profile",
+    "rail",
+    "raise",
+    "rebuy",
+    "rejoin",
+    "seat",
+    "show",
+    "sidebet",
+    "spectate",
+    "start",
+    "unmute",
+    "verify",
+];
+pub const INVALID_ACTION_MESSAGE: &str = "can't do that now";
+
+/// Parse a command in the client's text command language. Actions are
+/// checked against the user's action options and stack so illegal ones
+/// don't cost a round trip to the server. Everything else is left to the
+/// server to validate.
+pub fn parse_command(
+    user_input: &str,
+    action_options: &HashSet<Action>,
+    max_raise: Option<Usd>,
+    money: Usd,
+) -> Result<UserCommand, Error> {
+    let command = match user_input.trim() {
+        // Actions use their variant for comparisons, so we don't need
+        // to provide the correct call amount to see if it exists within
+        // the action options.
+        "all-in" | "call" | "check" | "fold" => {
+            let action = match user_input.trim() {
+                "all-in" => Action::AllIn,
+                "call" => Action::Call(0),
+                "check" => Action::Check,
+                _ => Action::Fold,
+            };
+            match action_options.get(&action) {
+                Some(action) => UserCommand::TakeAction(action.clone()),
+                None => bail!(INVALID_ACTION_MESSAGE),
+            }
+        }
+        "leaderboard" => UserCommand::QueryLeaderboard,
+        "muck" => UserCommand::MuckHand,
+        "play" => UserCommand::ChangeState(UserState::Play),
+        "rail" => UserCommand::ChangeState(UserState::Railbird),
+        "rejoin" => UserCommand::Rejoin,
+        "show" => UserCommand::ShowHand,
+        "spectate" => UserCommand::ChangeState(UserState::Spectate),
+        "start" => UserCommand::StartGame,
+        other => {
+            let other: Vec<&str> = other.split_ascii_whitespace().collect();
+            match other.first() {
+                // Adding chips requires a specific amount.
+                Some(&"buyin") => match other.get(1).map(|value| value.parse::<Usd>()) {
+                    Some(Ok(amount)) if amount > 0 => UserCommand::AddChips(amount),
+                    _ => bail!("invalid buy-in amount"),
+                },
+                // Insurance requires a player, a premium, and odds.
+                Some(&"insure") => {
+                    let odds = other.get(3).and_then(|odds| odds.split_once(':'));
+                    match (other.get(1), other.get(2).map(|v| v.parse::<Usd>()), odds) {
+                        (Some(to), Some(Ok(amount)), Some((numer, denom))) => {
+                            match (numer.parse::<Usd>(), denom.parse::<Usd>()) {
+                                (Ok(odds_numer), Ok(odds_denom)) => UserCommand::OfferInsurance {
+                                    to: to.to_string(),
+                                    amount,
+                                    odds_numer,
+                                    odds_denom,
+                                },
+                                _ => bail!("invalid odds"),
+                            }
+                        }
+                        _ => bail!("invalid insurance"),
+                    }
+                }
+                Some(&"accept") => match other[1..] {
+                    [from] => UserCommand::AcceptInsurance {
+                        from: from.to_string(),
+                    },
+                    _ => bail!("invalid username"),
+                },
+                // Showing one card requires knowing which card.
+                Some(&"show") => match other.get(1).map(|value| value.parse::<usize>()) {
+                    Some(Ok(card)) if card > 0 => UserCommand::ShowCard(card - 1),
+                    _ => bail!("invalid card"),
+                },
+                // Muting and unmuting require exactly one username. Users
+                // can be muted before they even connect.
+                Some(&mute_repr @ ("mute" | "unmute")) => match other[1..] {
+                    [username] if mute_repr == "mute" => UserCommand::Mute(username.to_string()),
+                    [username] => UserCommand::Unmute(username.to_string()),
+                    _ => bail!("invalid username"),
+                },
+                Some(&"rebuy") => match other[1..] {
+                    ["on"] => UserCommand::SetAutoRebuy(true),
+                    ["off"] => UserCommand::SetAutoRebuy(false),
+                    _ => bail!("rebuy is on or off"),
+                },
+                // Taglines can be several words, and leaving everything
+                // out clears the profile.
+                Some(&"profile") => UserCommand::SetProfile(Profile {
+                    avatar: other.get(1).map(|avatar| avatar.to_string()),
+                    tagline: other
+                        .get(2..)
+                        .filter(|words| !words.is_empty())
+                        .map(|words| words.join(" ")),
+                }),
+                // Side bets require a player and an amount.
+                Some(&"sidebet") => match (other.get(1), other.get(2).map(|v| v.parse::<Usd>())) {
+                    (Some(player), Some(Ok(amount))) if amount > 0 && other.len() == 3 => {
+                        UserCommand::SideBet(player.to_string(), amount)
+                    }
+                    _ => bail!("invalid side bet"),
+                },
+                // Changing seats requires a seat. The server checks that
+                // it's open.
+                Some(&"seat") => match other.get(1).map(|value| value.parse::<SeatIndex>()) {
+                    Some(Ok(seat_idx)) => UserCommand::ChangeSeat(seat_idx),
+                    _ => bail!("invalid seat"),
+                },
+                // Renaming requires exactly one new username.
+                Some(&"name") => match other[1..] {
+                    [new_username] => UserCommand::Rename(new_username.to_string()),
+                    _ => bail!("invalid username"),
+                },
+                Some(&"raise") => {
+                    let action = match (action_options.get(&Action::Raise(0)), other.get(1)) {
+                        // Raise with a specific amount. Amounts beyond the
+                        // player's stack would silently become an all-in, so
+                        // they're caught here instead.
+                        (Some(action), Some(value)) => match value.parse::<Usd>() {
+                            Ok(amount) => match (action, max_raise) {
+                                (Action::Raise(min_raise), Some(max_raise))
+                                    if amount > max_raise =>
+                                {
+                                    bail!(
+                                        "raise amount must be between ${min_raise} and ${max_raise}"
+                                    )
+                                }
+                                _ => Action::Raise(amount),
+                            },
+                            Err(_) => bail!("invalid raise amount"),
+                        },
+                        // Valid raise without specified amount defaults to the
+                        // default raise.
+                        (Some(action), None) => action.clone(),
+                        (None, _) => bail!(INVALID_ACTION_MESSAGE),
+                    };
+                    // Catch illegal raises before they cost a round trip to
+                    // the server.
+                    UserCommand::TakeAction(validate_action(action_options, money, action)?)
+                }
+                _ => bail!("unrecognized command"),
+            }
+        }
+    };
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use private_poker::{
+        entities::{Action, Profile},
+        messages::UserState,
+        net::messages::UserCommand,
+    };
+    use std::collections::HashSet;
+
+    use super::{parse_command, INVALID_ACTION_MESSAGE};
+
+    #[test]
+    fn parse_text_commands() {
+        let action_options = HashSet::from([Action::Call(10), Action::Fold, Action::Raise(20)]);
+        let parse = |user_input: &str| parse_command(user_input, &action_options, Some(100), 100);
+
+        // Actions are filled in from the action options.
+        assert_eq!(
+            parse(" call ").unwrap(),
+            UserCommand::TakeAction(Action::Call(10))
+        );
+        assert_eq!(
+            parse("raise").unwrap(),
+            UserCommand::TakeAction(Action::Raise(20))
+        );
+        assert_eq!(
+            parse("raise 30").unwrap(),
+            UserCommand::TakeAction(Action::Raise(30))
+        );
+        assert_eq!(
+            parse("check").unwrap_err().to_string(),
+            INVALID_ACTION_MESSAGE
+        );
+        assert_eq!(
+            parse("raise 101").unwrap_err().to_string(),
+            "raise amount must be between $20 and $100"
+        );
+
+        // Everything else only needs its arguments.
+        assert_eq!(
+            parse("play").unwrap(),
+            UserCommand::ChangeState(UserState::Play)
+        );
+        assert_eq!(
+            parse("rail").unwrap(),
+            UserCommand::ChangeState(UserState::Railbird)
+        );
+        assert_eq!(parse("show 2").unwrap(), UserCommand::ShowCard(1));
+        assert_eq!(
+            parse("mute ognf").unwrap(),
+            UserCommand::Mute("ognf".to_string())
+        );
+        assert_eq!(
+            parse("rebuy off").unwrap(),
+            UserCommand::SetAutoRebuy(false)
+        );
+        assert_eq!(
+            parse("insure ognf 10 2:1").unwrap(),
+            UserCommand::OfferInsurance {
+                to: "ognf".to_string(),
+                amount: 10,
+                odds_numer: 2,
+                odds_denom: 1,
+            }
+        );
+        assert_eq!(
+            parse("profile 🇨🇦 eh, I'll call").unwrap(),
+            UserCommand::SetProfile(Profile {
+                avatar: Some("🇨🇦".to_string()),
+                tagline: Some("eh, I'll call".to_string()),
+            })
+        );
+        assert_eq!(
+            parse("profile").unwrap(),
+            UserCommand::SetProfile(Profile::default())
+        );
+        assert_eq!(
+            parse("sidebet ognf 25").unwrap(),
+            UserCommand::SideBet("ognf".to_string(), 25)
+        );
+        assert!(parse("sidebet ognf").is_err());
+        assert!(parse("sidebet ognf 0").is_err());
+        assert!(parse("sidebet ognf 25 more").is_err());
+        assert!(parse("buyin 0").is_err());
+        assert!(parse("name two words").is_err());
+        assert_eq!(
+            parse("dance").unwrap_err().to_string(),
+            "unrecognized command"
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use anyhow::{bail, Error};
+use private_poker::{
+    entities::{Action, GameView, TurnId, Usd, Username},
+    net::{
+        messages::{ClientFrame, SequencedMessage, ServerMessage, UserCommand},
+        utils::{read_prefixed, write_prefixed},
+    },
+    Client,
+};
+use std::{
+    collections::HashSet,
+    io::{BufRead, Write},
+    net::TcpStream,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use crate::command::parse_command;
+
+/// What's needed to send commands on the user's behalf, kept up to date
+/// with messages from the server.
+struct Context {
+    username: Username,
+    action_options: HashSet<Action>,
+    max_raise: Option<Usd>,
+    money: Usd,
+    turn_id: Option<TurnId>,
+}
+
+impl Context {
+    fn update(&mut self, msg: &ServerMessage) {
+        match msg {
+            ServerMessage::Ack(msg) if msg.username == self.username => match msg.command {
+                UserCommand::Rename(ref new_username) => self.username.clone_from(new_username),
+                UserCommand::TakeAction(_) => self.action_options.clear(),
+                _ => {}
+            },
+            ServerMessage::GameView(view) => self.update_money(view),
+            ServerMessage::Snapshot(snapshot) => {
+                self.update_money(&snapshot.view);
+                self.action_options = snapshot.action_options.clone().unwrap_or_default();
+                self.max_raise = snapshot.max_raise;
+                self.turn_id = snapshot.turn_id;
+            }
+            ServerMessage::TurnSignal {
+                action_options,
+                max_raise,
+                turn_id,
+                ..
+            } => {
+                self.action_options.clone_from(action_options);
+                self.max_raise = *max_raise;
+                self.turn_id = Some(*turn_id);
+            }
+            _ => {}
+        }
+    }
+
+    fn update_money(&mut self, view: &GameView) {
+        if let Some(player) = view
+            .players
+            .iter()
+            .find(|player| player.user.name == self.username)
+        {
+            self.money = player.user.money;
+        }
+    }
+}
+
+/// Sends commands to the server on the user's behalf. Commanders can be
+/// cloned and moved elsewhere, like into a signal handler.
+#[derive(Clone)]
+pub struct Commander {
+    context: Arc<Mutex<Context>>,
+    stream: Arc<Mutex<TcpStream>>,
+}
+
+impl Commander {
+    /// Leave the game, disconnecting once the server acknowledges it.
+    pub fn leave(&self) -> Result<(), Error> {
+        self.send(UserCommand::Leave)
+    }
+
+    /// Parse a line of input as a JSON command, falling back to the text
+    /// command language, and send it. Text actions are tied to the latest
+    /// turn, but JSON commands are sent as they are.
+    fn send_line(&self, line: &str) -> Result<(), Error> {
+        let command = match serde_json::from_str(line) {
+            Ok(command) => command,
+            Err(_) => {
+                let context = self.context.lock().expect("context lock");
+                parse_command(
+                    line,
+                    &context.action_options,
+                    context.max_raise,
+                    context.money,
+                )?
+                .for_turn(context.turn_id)
+            }
+        };
+        self.send(command)
+    }
+
+    fn send(&self, command: UserCommand) -> Result<(), Error> {
+        let mut stream = self.stream.lock().expect("stream lock");
+        write_prefixed(&mut *stream, &ClientFrame::Command(command))?;
+        Ok(())
+    }
+}
+
+/// Plays without a TUI so the client can be scripted or used as a
+/// subprocess. Every message from the server is written out as a line of
+/// JSON, and commands are read in as lines of either JSON or the text
+/// command language.
+pub struct Headless {
+    commander: Commander,
+    stream: TcpStream,
+    view: GameView,
+}
+
+impl Headless {
+    pub fn commander(&self) -> Commander {
+        self.commander.clone()
+    }
+
+    pub fn new(client: Client, view: GameView) -> Result<Self, Error> {
+        let Client {
+            username, stream, ..
+        } = client;
+        // Scripts can go a long time without saying anything, and so can
+        // the server between hands.
+        stream.set_read_timeout(None)?;
+        let mut context = Context {
+            username,
+            action_options: HashSet::new(),
+            max_raise: None,
+            money: 0,
+            turn_id: None,
+        };
+        context.update_money(&view);
+        let commander = Commander {
+            context: Arc::new(Mutex::new(context)),
+            stream: Arc::new(Mutex::new(stream.try_clone()?)),
+        };
+        Ok(Self {
+            commander,
+            stream,
+            view,
+        })
+    }
+
+    /// Relay messages and commands until the user leaves. Reaching the
+    /// end of the input leaves the game. Commands that can't be parsed
+    /// are reported on stderr so the output stays valid JSON.
+    pub fn run<R, W>(mut self, input: R, output: &mut W) -> Result<(), Error>
+    where
+        R: BufRead + Send + 'static,
+        W: Write,
+    {
+        write_json(output, &ServerMessage::GameView(Arc::new(self.view)))?;
+
+        let commander = self.commander.clone();
+        thread::spawn(move || {
+            for line in input.lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if let Err(error) = commander.send_line(&line) {
+                    eprintln!("{error}");
+                }
+            }
+            if let Err(error) = commander.leave() {
+                eprintln!("couldn't leave: {error}");
+            }
+        });
+
+        loop {
+            let msg = match read_prefixed::<SequencedMessage, TcpStream>(&mut self.stream) {
+                Ok(SequencedMessage { msg, .. }) => msg,
+                Err(error) => bail!("lost connection to the server: {error}"),
+            };
+            // Let the server know we're still here.
+            if let ServerMessage::Ping = msg {
+                self.commander.send(UserCommand::Pong)?;
+            }
+            let left = {
+                let mut context = self.commander.context.lock().expect("context lock");
+                let left = matches!(
+                    msg,
+                    ServerMessage::Ack(ref ack)
+                        if ack.username == context.username && ack.command == UserCommand::Leave
+                );
+                context.update(&msg);
+                left
+            };
+            write_json(output, &msg)?;
+            if left {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn write_json<W: Write>(output: &mut W, msg: &ServerMessage) -> Result<(), Error> {
+    serde_json::to_writer(&mut *output, msg)?;
+    writeln!(output)?;
+    output.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use private_poker::{
+        messages::{ServerMessage, UserCommand},
+        server::{self, PokerConfig},
+        Client,
+    };
+    use std::io::{BufRead, Cursor};
+
+    use super::Headless;
+
+    #[test]
+    fn headless_client_speaks_json() {
+        let server = server::spawn("127.0.0.1:0", PokerConfig::default()).unwrap();
+        let (client, view) = Client::connect("ognf", &server.addr().to_string()).unwrap();
+        let input = Cursor::new("mute someone\n\n{\"Unmute\":\"someone\"}\ndance\n");
+        let mut output = vec![];
+        Headless::new(client, view)
+            .unwrap()
+            .run(input, &mut output)
+            .unwrap();
+
+        // The view from connecting comes first, then everything the
+        // server sends, ending with the ack for leaving once the input
+        // runs out. Unrecognized commands are skipped.
+        let msgs: Vec<ServerMessage> = output
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        assert!(matches!(msgs.first(), Some(ServerMessage::GameView(_))));
+        let acks: Vec<UserCommand> = msgs
+            .into_iter()
+            .filter_map(|msg| match msg {
+                ServerMessage::Ack(msg) if msg.username == "ognf" => Some(msg.command),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            acks,
+            [
+                UserCommand::Mute("someone".to_string()),
+                UserCommand::Unmute("someone".to_string()),
+                UserCommand::Leave,
+            ]
+        );
+        server.shutdown().unwrap();
+    }
+}
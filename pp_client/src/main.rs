@@ -2,17 +2,30 @@
 //!
 //! The client runs with two threads; one for managing the TCP connection
 //! and exchanging data, and another for updating the TUI at fixed
-//! intervals and in response to user commands.
+//! intervals and in response to user commands. In headless mode, the TUI
+//! is replaced with line-delimited JSON on stdout and commands on stdin.
 //!
 //! [`ratatui`]: https://github.com/ratatui/ratatui
 
-use anyhow::Error;
+use anyhow::{bail, Error};
 
+use ctrlc::set_handler;
 use pico_args::Arguments;
-use private_poker::{constants::MAX_USER_INPUT_LENGTH, entities::Username, Client};
+use private_poker::{
+    bots::PracticeTable, constants::MAX_USER_INPUT_LENGTH, entities::Username, Client,
+};
+use std::{
+    env,
+    io::{self, BufReader},
+    ops::Range,
+    time::Duration,
+};
 
 mod app;
-use app::App;
+mod command;
+mod headless;
+use app::{App, CardStyle, ConfirmConfig, NotifyConfig, ThemeName, DEFAULT_CONFIRM_FRACTION};
+use headless::Headless;
 
 const HELP: &str = "\
 Connect to a private poker server over TCP
@@ -21,15 +34,50 @@ USAGE:
   pp_client [OPTIONS] USERNAME
 
 OPTIONS:
+  --card-style STYLE    Card style: plain, unicode, or four-color  [default: four-color]
+  --confirm-fraction F  Ask to confirm calls and raises committing more than fraction F
+                        of your stack, between 0 and 1  [default: 0.5]
   --connect IP:PORT     Server socket connection address  [default: 127.0.0.1:6969]
+  --notify-cmd CMD      Run CMD when it's your turn (with the seconds left to act as
+                        its last argument) or when you're offered insurance
+  --practice N          Play against N local bots instead of connecting to a server
+  --theme THEME         Color theme: dark, light, or high-contrast  [default: dark]
+  --usernames A,B,...   Play as each of these users instead of USERNAME, starting as
+                        the first and switching between them with F8
 
 FLAGS:
+  --bell                Ring the terminal bell when it's your turn or you're offered
+                        insurance
+  --flash               Set the terminal title when it's your turn or you're offered
+                        insurance
+  --headless            Skip the TUI, writing messages from the server to stdout as
+                        JSON lines and reading commands from stdin as JSON or text
+                        lines until it closes
+  --no-confirm          Send calls and raises without asking to confirm them, no
+                        matter how much of your stack they commit
   -h, --help            Print help information
+
+ENVIRONMENT:
+  PP_OPERATOR_SECRET    Connect as the server's operator with this secret
 ";
 
+/// Bots act a bit faster at practice tables since there's only one
+/// person that needs time to follow along.
+const PRACTICE_THINK_TIME: Range<Duration> = Duration::from_secs(1)..Duration::from_secs(3);
+
+/// Environment variable the operator's secret is read from, so it isn't
+/// shown in the client's command line.
+const OPERATOR_SECRET_VAR: &str = "PP_OPERATOR_SECRET";
+
 struct Args {
-    username: Username,
+    usernames: Vec<Username>,
     addr: String,
+    card_style: CardStyle,
+    confirm_config: ConfirmConfig,
+    headless: bool,
+    notify_config: NotifyConfig,
+    practice: Option<usize>,
+    theme: ThemeName,
 }
 
 fn main() -> Result<(), Error> {
@@ -45,22 +93,106 @@ fn main() -> Result<(), Error> {
         addr: pargs
             .value_from_str("--connect")
             .unwrap_or("127.0.0.1:6969".into()),
-        username: pargs.free_from_str().unwrap_or(whoami::username()),
+        card_style: pargs
+            .opt_value_from_str("--card-style")?
+            .unwrap_or_default(),
+        confirm_config: ConfirmConfig {
+            enabled: !pargs.contains("--no-confirm"),
+            fraction: pargs
+                .opt_value_from_str("--confirm-fraction")?
+                .unwrap_or(DEFAULT_CONFIRM_FRACTION),
+        },
+        headless: pargs.contains("--headless"),
+        notify_config: NotifyConfig {
+            bell: pargs.contains("--bell"),
+            flash: pargs.contains("--flash"),
+            command: pargs.opt_value_from_str("--notify-cmd")?,
+        },
+        practice: pargs.opt_value_from_str("--practice")?,
+        theme: pargs.opt_value_from_str("--theme")?.unwrap_or_default(),
+        usernames: pargs
+            .opt_value_from_fn("--usernames", |usernames: &str| {
+                let usernames = usernames.split(',').filter(|username| !username.is_empty());
+                Ok::<_, Error>(usernames.map(str::to_string).collect())
+            })?
+            .unwrap_or_default(),
+    };
+    if args.usernames.is_empty() {
+        args.usernames
+            .push(pargs.free_from_str().unwrap_or(whoami::username()));
+    }
+    for username in args.usernames.iter_mut() {
+        username.truncate(MAX_USER_INPUT_LENGTH);
+    }
+    if !(0.0..=1.0).contains(&args.confirm_config.fraction) {
+        bail!("confirm fraction must be between 0 and 1");
+    }
+    if args.headless && args.usernames.len() > 1 {
+        bail!("headless mode can only play as one user");
+    }
+
+    // Practice tables run in the background of this process, so the
+    // client connects to them just like any other server.
+    let practice_table = match args.practice {
+        Some(num_bots) => {
+            let practice_table =
+                PracticeTable::start(Default::default(), num_bots, PRACTICE_THINK_TIME)?;
+            args.addr = practice_table.addr();
+            Some(practice_table)
+        }
+        None => None,
     };
-    args.username.truncate(MAX_USER_INPUT_LENGTH);
 
     // Doesn't make sense to use the complexity of non-blocking IO
     // for connecting to the poker server, so we try to connect with
     // a blocking client instead. The client is then eventually
     // converted to a non-blocking stream and polled for events.
-    let (client, view) = Client::connect(&args.username, &args.addr)?;
+    let (client, view) = match env::var(OPERATOR_SECRET_VAR) {
+        Ok(secret) => Client::connect_as_operator(&args.usernames[0], &secret, &args.addr)?,
+        Err(_) => Client::connect(&args.usernames[0], &args.addr)?,
+    };
+    let identities = args.usernames[1..]
+        .iter()
+        .map(|username| Client::connect(username, &args.addr))
+        .collect::<Result<Vec<_>, _>>()?;
+    if args.headless {
+        let headless = Headless::new(client, view)?;
+        // Interrupts leave the game just like closing stdin does.
+        let commander = headless.commander();
+        set_handler(move || {
+            if let Err(error) = commander.leave() {
+                eprintln!("couldn't leave: {error}");
+                std::process::exit(1);
+            }
+        })?;
+        let result = headless.run(BufReader::new(io::stdin()), &mut io::stdout().lock());
+        if let Some(practice_table) = practice_table {
+            practice_table.shutdown()?;
+        }
+        return result;
+    }
     let Client {
         username,
         addr,
         stream,
+        secret,
+        ..
     } = client;
     let terminal = ratatui::init();
-    let app_result = App::new(username, addr)?.run(stream, view, terminal);
+    let app_result = App::new(
+        username,
+        addr,
+        secret,
+        args.card_style,
+        args.theme.into(),
+        args.notify_config,
+        args.confirm_config,
+    )?
+    .with_identities(identities)?
+    .run(stream, view, terminal);
     ratatui::restore();
+    if let Some(practice_table) = practice_table {
+        practice_table.shutdown()?;
+    }
     app_result
 }
@@ -0,0 +1,475 @@
+//! C functions for evaluating and comparing hands, and for stepping
+//! through games and querying their state, from other languages. They're
+//! exported from this crate's shared library. Nothing here touches the
+//! network.
+//!
+//! Games are only exposed through an opaque handle and read-only
+//! queries, besides stepping them, so their layout can change without
+//! breaking the ABI.
+//!
+//! The ABI, written as a C header:
+//!
+//! ```c
+//! // Suits are 0 (clubs), 1 (spades), 2 (diamonds), and 3 (hearts).
+//! // Values are 2-13 for two through king, and either 1 or 14 for aces.
+//! typedef struct { uint8_t value; uint8_t suit; } pp_card;
+//!
+//! // Version of this ABI, bumped whenever it changes.
+//! uint32_t pp_abi_version(void);
+//!
+//! // Compact value of the best hand in 1-52 distinct cards. Values
+//! // compare the same way hands do. Returns 0 on error.
+//! uint32_t pp_eval(const pp_card *cards, size_t len);
+//!
+//! // 1 if the first hand wins, -1 if the second hand wins, or 0 if
+//! // they tie. Returns a negative PP_ERR_* code below -1 on error.
+//! int32_t pp_compare_hands(const pp_card *cards1, size_t len1,
+//!                          const pp_card *cards2, size_t len2);
+//!
+//! // Write a NUL-terminated description of the best hand in the cards
+//! // (e.g., "pair of aces") to buf, returning its length without the
+//! // NUL. Returns a negative PP_ERR_* code on error.
+//! int32_t pp_describe_hand(const pp_card *cards, size_t len,
+//!                          char *buf, size_t buf_len);
+//!
+//! // A game, which is only ever used through a pointer.
+//! typedef struct pp_game pp_game;
+//!
+//! // New game with default settings and the given users waiting to
+//! // play. Usernames are NUL-terminated UTF-8. Returns NULL on error.
+//! // The game is freed with pp_game_free.
+//! pp_game *pp_game_new(const char *const *usernames, size_t len);
+//!
+//! // Free a game. Freeing NULL does nothing.
+//! void pp_game_free(pp_game *game);
+//!
+//! // Step the game to its next phase, starting a hand from the lobby if
+//! // there are enough players. Players check or fold when it's their
+//! // turn. Returns 0, or a negative PP_ERR_* code on error.
+//! int32_t pp_game_step(pp_game *game);
+//!
+//! // One of the PP_PHASE_* codes below for the game's phase. Returns a
+//! // negative PP_ERR_* code on error.
+//! int32_t pp_game_phase(const pp_game *game);
+//!
+//! // Write each player's stack to stacks in seat order, returning the
+//! // number of players. Returns a negative PP_ERR_* code on error.
+//! int32_t pp_game_stacks(const pp_game *game, uint32_t *stacks,
+//!                        size_t len);
+//!
+//! // Write the board's cards to cards, returning the number of cards.
+//! // Returns a negative PP_ERR_* code on error.
+//! int32_t pp_game_board(const pp_game *game, pp_card *cards, size_t len);
+//!
+//! #define PP_PHASE_LOBBY 0
+//! #define PP_PHASE_SEATING_PLAYERS 1
+//! #define PP_PHASE_MOVING_BUTTON 2
+//! #define PP_PHASE_COLLECTING_BLINDS 3
+//! #define PP_PHASE_COLLECTING_ANTES 4
+//! #define PP_PHASE_DEALING 5
+//! #define PP_PHASE_BETTING 6
+//! #define PP_PHASE_FLOP 7
+//! #define PP_PHASE_TURN 8
+//! #define PP_PHASE_RIVER 9
+//! #define PP_PHASE_SHOWING_POT 10
+//! #define PP_PHASE_DISTRIBUTING_POT 11
+//! #define PP_PHASE_REMOVING_PLAYERS 12
+//! #define PP_PHASE_DIVIDING_DONATIONS 13
+//! #define PP_PHASE_UPDATING_BLINDS 14
+//! #define PP_PHASE_BOOTING_PLAYERS 15
+//!
+//! #define PP_ERR_INVALID_ARGUMENT -2
+//! #define PP_ERR_BUFFER_TOO_SMALL -3
+//! #define PP_ERR_PANIC -4
+//! ```
+//!
+//! Panics are caught before they reach the caller and reported as
+//! `PP_ERR_PANIC`, except in builds that abort on panic, like the
+//! workspace's release profile. Functions that return a count or a
+//! length write nothing if the caller's buffer is too small, returning
+//! `PP_ERR_BUFFER_TOO_SMALL` instead.
+
+use std::{
+    cmp::Ordering,
+    ffi::{c_char, CStr},
+    mem,
+    panic::{self, AssertUnwindSafe, UnwindSafe},
+    ptr, slice,
+};
+
+use private_poker::{
+    entities::{Card, GamePhase, HandValue, Suit, Usd},
+    functional, PokerState,
+};
+
+/// Version of the ABI, bumped whenever it changes.
+pub const PP_ABI_VERSION: u32 = 2;
+
+pub const PP_PHASE_LOBBY: i32 = 0;
+pub const PP_PHASE_SEATING_PLAYERS: i32 = 1;
+pub const PP_PHASE_MOVING_BUTTON: i32 = 2;
+pub const PP_PHASE_COLLECTING_BLINDS: i32 = 3;
+pub const PP_PHASE_COLLECTING_ANTES: i32 = 4;
+pub const PP_PHASE_DEALING: i32 = 5;
+pub const PP_PHASE_BETTING: i32 = 6;
+pub const PP_PHASE_FLOP: i32 = 7;
+pub const PP_PHASE_TURN: i32 = 8;
+pub const PP_PHASE_RIVER: i32 = 9;
+pub const PP_PHASE_SHOWING_POT: i32 = 10;
+pub const PP_PHASE_DISTRIBUTING_POT: i32 = 11;
+pub const PP_PHASE_REMOVING_PLAYERS: i32 = 12;
+pub const PP_PHASE_DIVIDING_DONATIONS: i32 = 13;
+pub const PP_PHASE_UPDATING_BLINDS: i32 = 14;
+pub const PP_PHASE_BOOTING_PLAYERS: i32 = 15;
+
+/// A pointer or length was invalid, or a card wasn't a real card.
+pub const PP_ERR_INVALID_ARGUMENT: i32 = -2;
+/// The buffer can't fit the description and its NUL terminator.
+pub const PP_ERR_BUFFER_TOO_SMALL: i32 = -3;
+/// Something went wrong that should never go wrong.
+pub const PP_ERR_PANIC: i32 = -4;
+
+/// Most cards that can be evaluated at once, a whole deck.
+const MAX_CARDS: usize = 52;
+
+/// A card as C sees it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(C)]
+pub struct PpCard {
+    pub value: u8,
+    pub suit: u8,
+}
+
+impl From<Card> for PpCard {
+    fn from(value: Card) -> Self {
+        let suit = match value.1 {
+            Suit::Club => 0,
+            Suit::Spade => 1,
+            Suit::Diamond => 2,
+            Suit::Heart => 3,
+            Suit::Wild => u8::MAX,
+        };
+        Self {
+            value: value.0,
+            suit,
+        }
+    }
+}
+
+/// Run `f`, returning `on_panic` instead if it panics so panics never
+/// unwind into the caller.
+fn guard<T>(on_panic: T, f: impl FnOnce() -> T + UnwindSafe) -> T {
+    panic::catch_unwind(f).unwrap_or(on_panic)
+}
+
+/// Copy cards from the caller, returning `None` if any of them aren't
+/// real cards or the same card is given twice.
+///
+/// # Safety
+///
+/// `cards` must point to `len` cards, unless it's null.
+unsafe fn read_cards(cards: *const PpCard, len: usize) -> Option<Vec<Card>> {
+    if cards.is_null() || !(1..=MAX_CARDS).contains(&len) {
+        return None;
+    }
+    let mut seen = [false; MAX_CARDS];
+    slice::from_raw_parts(cards, len)
+        .iter()
+        .map(|card| {
+            let suit = match card.suit {
+                0 => Suit::Club,
+                1 => Suit::Spade,
+                2 => Suit::Diamond,
+                3 => Suit::Heart,
+                _ => return None,
+            };
+            // Aces are the same card whether they're valued low or high.
+            let value = match card.value {
+                1..=13 => card.value,
+                14 => 1,
+                _ => return None,
+            };
+            let card_idx = 4 * (value as usize - 1) + card.suit as usize;
+            (!mem::replace(&mut seen[card_idx], true)).then_some(Card(value, suit))
+        })
+        .collect()
+}
+
+/// Evaluate cards from the caller, returning `None` if they're invalid.
+///
+/// # Safety
+///
+/// Same as `read_cards`.
+unsafe fn eval(cards: *const PpCard, len: usize) -> Option<HandValue> {
+    read_cards(cards, len).map(|cards| functional::eval_value(&cards))
+}
+
+/// Return the version of the ABI so callers can check it's the one they
+/// were written for.
+#[no_mangle]
+pub extern "C" fn pp_abi_version() -> u32 {
+    PP_ABI_VERSION
+}
+
+/// Return the compact value of the best hand in the cards, or 0 if the
+/// cards are invalid. Every hand's value is nonzero.
+///
+/// # Safety
+///
+/// `cards` must point to `len` cards, unless it's null.
+#[no_mangle]
+pub unsafe extern "C" fn pp_eval(cards: *const PpCard, len: usize) -> u32 {
+    guard(0, || eval(cards, len).map_or(0, |hand_value| hand_value.0))
+}
+
+/// Return 1 if the first hand wins, -1 if the second hand wins, or 0 if
+/// they tie. Returns `PP_ERR_INVALID_ARGUMENT` if either hand is
+/// invalid.
+///
+/// # Safety
+///
+/// `cards1` and `cards2` must point to `len1` and `len2` cards,
+/// respectively, unless they're null.
+#[no_mangle]
+pub unsafe extern "C" fn pp_compare_hands(
+    cards1: *const PpCard,
+    len1: usize,
+    cards2: *const PpCard,
+    len2: usize,
+) -> i32 {
+    guard(PP_ERR_PANIC, || {
+        match (eval(cards1, len1), eval(cards2, len2)) {
+            (Some(hand_value1), Some(hand_value2)) => match hand_value1.cmp(&hand_value2) {
+                Ordering::Less => -1,
+                Ordering::Equal => 0,
+                Ordering::Greater => 1,
+            },
+            _ => PP_ERR_INVALID_ARGUMENT,
+        }
+    })
+}
+
+/// Write a NUL-terminated description of the best hand in the cards to
+/// the buffer, returning the description's length in bytes without the
+/// NUL. Returns `PP_ERR_INVALID_ARGUMENT` if the cards or buffer are
+/// invalid, or `PP_ERR_BUFFER_TOO_SMALL` if the description doesn't fit,
+/// in which case nothing is written.
+///
+/// # Safety
+///
+/// `cards` must point to `len` cards, and `buf` must point to `buf_len`
+/// writable bytes, unless they're null.
+#[no_mangle]
+pub unsafe extern "C" fn pp_describe_hand(
+    cards: *const PpCard,
+    len: usize,
+    buf: *mut c_char,
+    buf_len: usize,
+) -> i32 {
+    guard(PP_ERR_PANIC, || {
+        let Some(hand_value) = eval(cards, len) else {
+            return PP_ERR_INVALID_ARGUMENT;
+        };
+        if buf.is_null() {
+            return PP_ERR_INVALID_ARGUMENT;
+        }
+        let description = hand_value.describe();
+        if description.len() >= buf_len {
+            return PP_ERR_BUFFER_TOO_SMALL;
+        }
+        ptr::copy_nonoverlapping(description.as_ptr(), buf.cast(), description.len());
+        *buf.add(description.len()) = 0;
+        description.len() as i32
+    })
+}
+
+/// A game as C sees it, only ever behind a pointer.
+pub struct PpGame {
+    state: PokerState,
+}
+
+/// Return the code for the phase. Every phase has its own code so new
+/// phases can't be mistaken for old ones.
+fn phase_code(phase: &GamePhase) -> i32 {
+    match phase {
+        GamePhase::Lobby => PP_PHASE_LOBBY,
+        GamePhase::SeatingPlayers => PP_PHASE_SEATING_PLAYERS,
+        GamePhase::MovingButton => PP_PHASE_MOVING_BUTTON,
+        GamePhase::CollectingBlinds { .. } => PP_PHASE_COLLECTING_BLINDS,
+        GamePhase::CollectingAntes { .. } => PP_PHASE_COLLECTING_ANTES,
+        GamePhase::Dealing => PP_PHASE_DEALING,
+        GamePhase::Betting { .. } => PP_PHASE_BETTING,
+        GamePhase::Flop => PP_PHASE_FLOP,
+        GamePhase::Turn => PP_PHASE_TURN,
+        GamePhase::River => PP_PHASE_RIVER,
+        GamePhase::ShowingPot { .. } => PP_PHASE_SHOWING_POT,
+        GamePhase::DistributingPot { .. } => PP_PHASE_DISTRIBUTING_POT,
+        GamePhase::RemovingPlayers => PP_PHASE_REMOVING_PLAYERS,
+        GamePhase::DividingDonations => PP_PHASE_DIVIDING_DONATIONS,
+        GamePhase::UpdatingBlinds => PP_PHASE_UPDATING_BLINDS,
+        GamePhase::BootingPlayers => PP_PHASE_BOOTING_PLAYERS,
+    }
+}
+
+/// Copy values into the caller's buffer, returning how many there are,
+/// or `PP_ERR_BUFFER_TOO_SMALL` without writing anything if they don't
+/// fit.
+///
+/// # Safety
+///
+/// `buf` must point to `buf_len` writable values, unless it's null.
+unsafe fn write_all<T: Copy>(values: &[T], buf: *mut T, buf_len: usize) -> i32 {
+    if values.len() > buf_len {
+        return PP_ERR_BUFFER_TOO_SMALL;
+    }
+    if !values.is_empty() {
+        if buf.is_null() {
+            return PP_ERR_INVALID_ARGUMENT;
+        }
+        ptr::copy_nonoverlapping(values.as_ptr(), buf, values.len());
+    }
+    values.len() as i32
+}
+
+/// Return a new game with default settings and the users waiting to
+/// play, or null if any of the usernames are invalid or taken, or if
+/// there are too many users.
+///
+/// # Safety
+///
+/// `usernames` must point to `len` NUL-terminated strings, unless it's
+/// null.
+#[no_mangle]
+pub unsafe extern "C" fn pp_game_new(usernames: *const *const c_char, len: usize) -> *mut PpGame {
+    guard(ptr::null_mut(), || {
+        if usernames.is_null() && len > 0 {
+            return ptr::null_mut();
+        }
+        let mut state = PokerState::new();
+        let usernames = if len > 0 {
+            slice::from_raw_parts(usernames, len)
+        } else {
+            &[]
+        };
+        for username in usernames {
+            if username.is_null() {
+                return ptr::null_mut();
+            }
+            let Ok(username) = CStr::from_ptr(*username).to_str() else {
+                return ptr::null_mut();
+            };
+            if state.new_user(username).is_err() || state.waitlist_user(username).is_err() {
+                return ptr::null_mut();
+            }
+        }
+        Box::into_raw(Box::new(PpGame { state }))
+    })
+}
+
+/// Free a game made by `pp_game_new`. Freeing null does nothing.
+///
+/// # Safety
+///
+/// `game` must be a game from `pp_game_new` that hasn't been freed yet,
+/// unless it's null.
+#[no_mangle]
+pub unsafe extern "C" fn pp_game_free(game: *mut PpGame) {
+    if !game.is_null() {
+        drop(Box::from_raw(game));
+    }
+}
+
+/// Step the game to its next phase, starting a hand from the lobby if
+/// there are enough players. Players whose turn it is check if they can
+/// and fold otherwise. Returns 0, or `PP_ERR_INVALID_ARGUMENT` if the
+/// game is null.
+///
+/// # Safety
+///
+/// `game` must be a game from `pp_game_new` that hasn't been freed yet,
+/// unless it's null.
+#[no_mangle]
+pub unsafe extern "C" fn pp_game_step(game: *mut PpGame) -> i32 {
+    let Some(game) = game.as_mut() else {
+        return PP_ERR_INVALID_ARGUMENT;
+    };
+    guard(
+        PP_ERR_PANIC,
+        AssertUnwindSafe(|| {
+            // The game's left in the lobby if stepping panics.
+            let mut state = mem::take(&mut game.state);
+            if let PokerState::Lobby(_) = state {
+                // Not having enough players just means staying put.
+                let _ = state.init_autostart();
+            }
+            game.state = state.step();
+            0
+        }),
+    )
+}
+
+/// Return the code for the game's phase, or `PP_ERR_INVALID_ARGUMENT` if
+/// the game is null.
+///
+/// # Safety
+///
+/// `game` must be a game from `pp_game_new` that hasn't been freed yet,
+/// unless it's null.
+#[no_mangle]
+pub unsafe extern "C" fn pp_game_phase(game: *const PpGame) -> i32 {
+    let Some(game) = game.as_ref() else {
+        return PP_ERR_INVALID_ARGUMENT;
+    };
+    guard(
+        PP_ERR_PANIC,
+        AssertUnwindSafe(|| phase_code(&game.state.phase())),
+    )
+}
+
+/// Write each player's stack to the buffer in seat order, returning the
+/// number of players. Returns `PP_ERR_INVALID_ARGUMENT` if the game or
+/// buffer is invalid, or `PP_ERR_BUFFER_TOO_SMALL` if the stacks don't
+/// fit, in which case nothing is written.
+///
+/// # Safety
+///
+/// `game` must be a game from `pp_game_new` that hasn't been freed yet,
+/// and `stacks` must point to `len` writable stacks, unless they're null.
+#[no_mangle]
+pub unsafe extern "C" fn pp_game_stacks(game: *const PpGame, stacks: *mut Usd, len: usize) -> i32 {
+    let Some(game) = game.as_ref() else {
+        return PP_ERR_INVALID_ARGUMENT;
+    };
+    guard(
+        PP_ERR_PANIC,
+        AssertUnwindSafe(|| {
+            let view = game.state.as_omniscient_view();
+            let values: Vec<Usd> = view.players.iter().map(|p| p.user.money).collect();
+            write_all(&values, stacks, len)
+        }),
+    )
+}
+
+/// Write the board's cards to the buffer, returning the number of cards.
+/// Returns `PP_ERR_INVALID_ARGUMENT` if the game or buffer is invalid,
+/// or `PP_ERR_BUFFER_TOO_SMALL` if the cards don't fit, in which case
+/// nothing is written.
+///
+/// # Safety
+///
+/// `game` must be a game from `pp_game_new` that hasn't been freed yet,
+/// and `cards` must point to `len` writable cards, unless they're null.
+#[no_mangle]
+pub unsafe extern "C" fn pp_game_board(game: *const PpGame, cards: *mut PpCard, len: usize) -> i32 {
+    let Some(game) = game.as_ref() else {
+        return PP_ERR_INVALID_ARGUMENT;
+    };
+    guard(
+        PP_ERR_PANIC,
+        AssertUnwindSafe(|| {
+            let view = game.state.as_omniscient_view();
+            let values: Vec<PpCard> = view.board.iter().copied().map(PpCard::from).collect();
+            write_all(&values, cards, len)
+        }),
+    )
+}
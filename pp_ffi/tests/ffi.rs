@@ -0,0 +1,311 @@
+use libloading::{Library, Symbol};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use std::{
+    cmp::Ordering,
+    env,
+    ffi::{c_char, CString},
+    path::PathBuf,
+    ptr,
+};
+
+use pp_ffi::{
+    PpCard, PpGame, PP_ABI_VERSION, PP_ERR_BUFFER_TOO_SMALL, PP_ERR_INVALID_ARGUMENT,
+    PP_PHASE_BETTING, PP_PHASE_BOOTING_PLAYERS, PP_PHASE_LOBBY,
+};
+use private_poker::{
+    entities::{Card, Usd},
+    functional::{eval_value, new_deck, parse_cards},
+    PokerState,
+};
+
+type AbiVersion = unsafe extern "C" fn() -> u32;
+type Eval = unsafe extern "C" fn(*const PpCard, usize) -> u32;
+type CompareHands = unsafe extern "C" fn(*const PpCard, usize, *const PpCard, usize) -> i32;
+type DescribeHand = unsafe extern "C" fn(*const PpCard, usize, *mut c_char, usize) -> i32;
+type GameNew = unsafe extern "C" fn(*const *const c_char, usize) -> *mut PpGame;
+type GameFree = unsafe extern "C" fn(*mut PpGame);
+type GameStep = unsafe extern "C" fn(*mut PpGame) -> i32;
+type GamePhase = unsafe extern "C" fn(*const PpGame) -> i32;
+type GameStacks = unsafe extern "C" fn(*const PpGame, *mut Usd, usize) -> i32;
+type GameBoard = unsafe extern "C" fn(*const PpGame, *mut PpCard, usize) -> i32;
+
+/// Load the shared library that's built next to the test binary.
+fn load_library() -> Library {
+    let exe = env::current_exe().unwrap();
+    let deps_dir = exe.parent().unwrap();
+    let filename = libloading::library_filename("pp_ffi");
+    let path = [deps_dir, deps_dir.parent().unwrap()]
+        .iter()
+        .map(|dir| dir.join(&filename))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from(&filename));
+    unsafe { Library::new(path).unwrap() }
+}
+
+fn to_ffi(cards: &[Card]) -> Vec<PpCard> {
+    cards.iter().copied().map(PpCard::from).collect()
+}
+
+/// Describe the cards through the library, returning the description or
+/// the error code.
+fn describe(
+    describe_hand: &Symbol<DescribeHand>,
+    cards: &[PpCard],
+    buf_len: usize,
+) -> Result<String, i32> {
+    let mut buf = vec![1 as c_char; buf_len];
+    let len = unsafe { describe_hand(cards.as_ptr(), cards.len(), buf.as_mut_ptr(), buf.len()) };
+    if len < 0 {
+        assert!(buf.iter().all(|c| *c == 1), "nothing's written on errors");
+        return Err(len);
+    }
+    let len = len as usize;
+    assert_eq!(buf[len], 0);
+    let bytes: Vec<u8> = buf[..len].iter().map(|c| *c as u8).collect();
+    Ok(String::from_utf8(bytes).unwrap())
+}
+
+#[test]
+fn exported_functions_match_the_native_evaluator() {
+    let library = load_library();
+    let (abi_version, eval, compare_hands, describe_hand) = unsafe {
+        (
+            library.get::<AbiVersion>(b"pp_abi_version").unwrap(),
+            library.get::<Eval>(b"pp_eval").unwrap(),
+            library.get::<CompareHands>(b"pp_compare_hands").unwrap(),
+            library.get::<DescribeHand>(b"pp_describe_hand").unwrap(),
+        )
+    };
+    assert_eq!(unsafe { abi_version() }, PP_ABI_VERSION);
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut deck = new_deck();
+    for _ in 0..10_000 {
+        deck.shuffle(&mut rng);
+        let len1 = rng.gen_range(1..=7);
+        let len2 = rng.gen_range(1..=7);
+        let (cards1, cards2) = (&deck[..len1], &deck[len1..len1 + len2]);
+        let (hand_value1, hand_value2) = (eval_value(cards1), eval_value(cards2));
+        let (ffi_cards1, ffi_cards2) = (to_ffi(cards1), to_ffi(cards2));
+
+        assert_eq!(unsafe { eval(ffi_cards1.as_ptr(), len1) }, hand_value1.0);
+        let ordering = match hand_value1.cmp(&hand_value2) {
+            Ordering::Less => -1,
+            Ordering::Equal => 0,
+            Ordering::Greater => 1,
+        };
+        assert_eq!(
+            unsafe { compare_hands(ffi_cards1.as_ptr(), len1, ffi_cards2.as_ptr(), len2) },
+            ordering,
+            "{cards1:?} {cards2:?}"
+        );
+        assert_eq!(
+            describe(&describe_hand, &ffi_cards1, 64),
+            Ok(hand_value1.describe())
+        );
+    }
+}
+
+#[test]
+fn exported_functions_reject_invalid_arguments() {
+    let library = load_library();
+    let (eval, compare_hands, describe_hand) = unsafe {
+        (
+            library.get::<Eval>(b"pp_eval").unwrap(),
+            library.get::<CompareHands>(b"pp_compare_hands").unwrap(),
+            library.get::<DescribeHand>(b"pp_describe_hand").unwrap(),
+        )
+    };
+    let cards = to_ffi(&parse_cards("As Ah Kd").unwrap());
+    assert_eq!(
+        describe(&describe_hand, &cards, 64),
+        Ok("pair of aces".to_string())
+    );
+
+    // Aces can be valued high, but they're still the same card.
+    let high_ace = PpCard { value: 14, suit: 1 };
+    assert_eq!(unsafe { eval([high_ace].as_ptr(), 1) }, unsafe {
+        eval(cards.as_ptr(), 1)
+    });
+    for invalid in [
+        vec![PpCard { value: 0, suit: 0 }],
+        vec![PpCard { value: 15, suit: 0 }],
+        vec![PpCard { value: 2, suit: 4 }],
+        vec![cards[0], high_ace],
+        vec![],
+    ] {
+        assert_eq!(
+            unsafe { eval(invalid.as_ptr(), invalid.len()) },
+            0,
+            "{invalid:?}"
+        );
+        assert_eq!(
+            unsafe { compare_hands(cards.as_ptr(), cards.len(), invalid.as_ptr(), invalid.len()) },
+            PP_ERR_INVALID_ARGUMENT
+        );
+        assert_eq!(
+            describe(&describe_hand, &invalid, 64),
+            Err(PP_ERR_INVALID_ARGUMENT)
+        );
+    }
+    assert_eq!(unsafe { eval(std::ptr::null(), 3) }, 0);
+    assert_eq!(
+        unsafe { describe_hand(cards.as_ptr(), cards.len(), std::ptr::null_mut(), 64) },
+        PP_ERR_INVALID_ARGUMENT
+    );
+
+    // The buffer has to fit the NUL terminator too.
+    let len = "pair of aces".len();
+    assert_eq!(
+        describe(&describe_hand, &cards, len),
+        Err(PP_ERR_BUFFER_TOO_SMALL)
+    );
+    assert_eq!(
+        describe(&describe_hand, &cards, len + 1),
+        Ok("pair of aces".to_string())
+    );
+}
+
+/// Make a game through the library, returning null if it's rejected.
+fn new_game(game_new: &Symbol<GameNew>, usernames: &[&str]) -> *mut PpGame {
+    let usernames: Vec<CString> = usernames
+        .iter()
+        .map(|username| CString::new(*username).unwrap())
+        .collect();
+    let ptrs: Vec<*const c_char> = usernames.iter().map(|username| username.as_ptr()).collect();
+    unsafe { game_new(ptrs.as_ptr(), ptrs.len()) }
+}
+
+#[test]
+fn exported_games_follow_the_native_game() {
+    let library = load_library();
+    let (game_new, game_free, game_step, game_phase, game_stacks, game_board) = unsafe {
+        (
+            library.get::<GameNew>(b"pp_game_new").unwrap(),
+            library.get::<GameFree>(b"pp_game_free").unwrap(),
+            library.get::<GameStep>(b"pp_game_step").unwrap(),
+            library.get::<GamePhase>(b"pp_game_phase").unwrap(),
+            library.get::<GameStacks>(b"pp_game_stacks").unwrap(),
+            library.get::<GameBoard>(b"pp_game_board").unwrap(),
+        )
+    };
+    let usernames = ["0", "1", "2"];
+    let game = new_game(&game_new, &usernames);
+    assert!(!game.is_null());
+    let mut state = PokerState::new();
+    for username in usernames {
+        state.new_user(username).unwrap();
+        state.waitlist_user(username).unwrap();
+    }
+    assert_eq!(unsafe { game_phase(game) }, PP_PHASE_LOBBY);
+    assert_eq!(
+        unsafe { game_stacks(game, ptr::null_mut(), 0) },
+        0,
+        "no one's seated in the lobby"
+    );
+
+    // Players check or fold the same way, so only the cards differ.
+    let mut phases = Vec::new();
+    loop {
+        assert_eq!(unsafe { game_step(game) }, 0);
+        if let PokerState::Lobby(_) = state {
+            state.init_autostart().unwrap();
+        }
+        state = state.step();
+        let view = state.as_omniscient_view();
+
+        let phase = unsafe { game_phase(game) };
+        phases.push(phase);
+        let mut stacks = [0; 3];
+        assert_eq!(
+            unsafe { game_stacks(game, stacks.as_mut_ptr(), stacks.len()) },
+            view.players.len() as i32
+        );
+        let native_stacks: Vec<Usd> = view.players.iter().map(|p| p.user.money).collect();
+        assert_eq!(stacks[..view.players.len()], native_stacks);
+        let mut board = [PpCard { value: 0, suit: 0 }; 5];
+        assert_eq!(
+            unsafe { game_board(game, board.as_mut_ptr(), board.len()) },
+            view.board.len() as i32
+        );
+        if phase == PP_PHASE_BOOTING_PLAYERS {
+            break;
+        }
+    }
+    assert!(phases.contains(&PP_PHASE_BETTING));
+    assert_eq!(phases.len(), {
+        let mut native_phases = 0;
+        let mut state = PokerState::new();
+        for username in usernames {
+            state.new_user(username).unwrap();
+            state.waitlist_user(username).unwrap();
+        }
+        state.init_autostart().unwrap();
+        loop {
+            state = state.step();
+            native_phases += 1;
+            if let PokerState::BootPlayers(_) = state {
+                break native_phases;
+            }
+        }
+    });
+    unsafe { game_free(game) };
+}
+
+#[test]
+fn exported_games_reject_invalid_arguments() {
+    let library = load_library();
+    let (game_new, game_free, game_step, game_phase, game_stacks, game_board) = unsafe {
+        (
+            library.get::<GameNew>(b"pp_game_new").unwrap(),
+            library.get::<GameFree>(b"pp_game_free").unwrap(),
+            library.get::<GameStep>(b"pp_game_step").unwrap(),
+            library.get::<GamePhase>(b"pp_game_phase").unwrap(),
+            library.get::<GameStacks>(b"pp_game_stacks").unwrap(),
+            library.get::<GameBoard>(b"pp_game_board").unwrap(),
+        )
+    };
+    assert!(new_game(&game_new, &["0", "0"]).is_null());
+    let invalid_utf8 = CString::new([0xff]).unwrap();
+    assert!(unsafe { game_new([invalid_utf8.as_ptr()].as_ptr(), 1) }.is_null());
+    assert!(unsafe { game_new(ptr::null(), 1) }.is_null());
+    unsafe { game_free(ptr::null_mut()) };
+    assert_eq!(
+        unsafe { game_step(ptr::null_mut()) },
+        PP_ERR_INVALID_ARGUMENT
+    );
+    assert_eq!(unsafe { game_phase(ptr::null()) }, PP_ERR_INVALID_ARGUMENT);
+    assert_eq!(
+        unsafe { game_stacks(ptr::null(), ptr::null_mut(), 0) },
+        PP_ERR_INVALID_ARGUMENT
+    );
+    assert_eq!(
+        unsafe { game_board(ptr::null(), ptr::null_mut(), 0) },
+        PP_ERR_INVALID_ARGUMENT
+    );
+
+    // Games without enough players stay in the lobby.
+    let game = new_game(&game_new, &["0"]);
+    assert!(!game.is_null());
+    assert_eq!(unsafe { game_step(game) }, 0);
+    assert_eq!(unsafe { game_phase(game) }, PP_PHASE_LOBBY);
+    unsafe { game_free(game) };
+
+    // Nothing's written when the stacks don't fit.
+    let game = new_game(&game_new, &["0", "1", "2"]);
+    while unsafe { game_phase(game) } != PP_PHASE_BETTING {
+        assert_eq!(unsafe { game_step(game) }, 0);
+    }
+    let mut stacks = [0; 2];
+    assert_eq!(
+        unsafe { game_stacks(game, stacks.as_mut_ptr(), stacks.len()) },
+        PP_ERR_BUFFER_TOO_SMALL
+    );
+    assert_eq!(stacks, [0; 2]);
+    assert_eq!(
+        unsafe { game_stacks(game, ptr::null_mut(), 3) },
+        PP_ERR_INVALID_ARGUMENT
+    );
+    unsafe { game_free(game) };
+}
@@ -4,15 +4,26 @@
 //! and exchanging data, and another for updating the poker game state
 //! at fixed intervals and in response to user commands.
 
-use anyhow::Error;
+use anyhow::{anyhow, bail, Error};
 use ctrlc::set_handler;
-use log::info;
 use pico_args::Arguments;
 use private_poker::{
-    entities::Usd,
-    server::{self, PokerConfig},
-    GameSettings, DEFAULT_MAX_USERS, MAX_PLAYERS,
+    collusion::{SoftPlayConfig, SoftPlayDetector},
+    entities::{GameVariant, LimitStructure, Privacy, Usd},
+    server::{
+        self, journal::JournalConfig, OperatorConfig, PlaylistEntry, PokerConfig, TimeoutPolicy,
+    },
+    Autostart, GameSettings, DEFAULT_BUY_IN, DEFAULT_MAX_USERS, DEFAULT_MIN_BIG_BLIND,
+    DEFAULT_MIN_SMALL_BLIND, MAX_PLAYERS,
 };
+use std::{env, fs::File, io::BufReader, net::SocketAddr, time::Duration};
+use tracing::{error, info};
+use tracing_subscriber::EnvFilter;
+
+/// Loopback on both IPv4 and IPv6, since "localhost" can resolve to either.
+const DEFAULT_BIND: &str = "127.0.0.1:6969,[::1]:6969";
+/// A bet and three raises per street, as is usual for fixed-limit games.
+const MAX_RAISES_PER_STREET: usize = 4;
 
 const HELP: &str = "\
 Run a private poker server
@@ -21,16 +32,182 @@ USAGE:
   pp_server [OPTIONS]
 
 OPTIONS:
-  --bind    IP:PORT     Server socket bind address  [default: 127.0.0.1:6969]
-  --buy_in  USD         New user starting money     [default: 200]
+  --autostart   POLICY      Autostart: off, always,     [default: off]
+                            or # of players
+  --big_blind   USD         Starting big blind          [default: 10]
+  --bind        IP:PORT,... Comma-separated server      [default: 127.0.0.1:6969,
+                            socket bind addresses                 [::1]:6969]
+  --bomb_pots   N           Bomb pot every Nth hand     [default: never]
+  --collusion   SCORE       Log pairs of players whose  [default: disabled]
+                            soft play scores this high
+  --journal     PATH        Journal of the game's moves [default: disabled]
+  --limit       SMALL/BIG   Fixed-limit bet sizes       [default: no-limit]
+  --log_format  FORMAT      Log format: plain or json   [default: plain]
+  --max_buy_in  USD         Max stack from adding chips [default: stack]
+  --max_players N           Max players seated per hand [default: 10]
+  --max_session MINUTES     Session length, after which [default: unlimited]
+                            no new hands are started
+  --max_users   N           Max players and spectators  [default: 16]
+  --metrics     IP:PORT     Metrics HTTP bind address   [default: disabled]
+  --min_buy_in  USD         Min stack from adding chips [default: 0]
+  --on_timeout  POLICY      After timing out: fold,     [default: remove:1]
+                            sit_out:N, or remove:N,
+                            N being timeouts in a row
+  --operator    USERNAME    User shown every hole card  [default: disabled]
+                            while they aren't playing,
+                            who connects with the secret
+                            in $PP_OPERATOR_SECRET
+  --playlist    PATH        JSON list of formats to     [default: disabled]
+                            play in order, overriding
+                            the game's options
+  --privacy     LEVEL       Privacy: open, hide_money,  [default: open]
+                            or hide_users
+  --small_blind USD         Starting small blind        [default: 5]
+  --stack       USD         New user starting money     [default: 200]
+  --variant     VARIANT     Variant: holdem, omaha, or  [default: holdem]
+                            short_deck
 
 FLAGS:
-  -h, --help            Print help information
+  -h, --help                Print help information
+  --auto_rebuy              Allow opting into auto-rebuy
+  --burn_cards              Burn a card before dealing
+                            each street
+  --insurance               Allow insuring all-in players
+  --random_seating          Draw for seats and the button
+                            at new tables
+  --spectator_bets          Allow spectators to bet play
+                            money on who wins each hand
 ";
 
+/// Environment variable the operator's secret is read from, so it isn't
+/// shown in the server's command line.
+const OPERATOR_SECRET_VAR: &str = "PP_OPERATOR_SECRET";
+
 struct Args {
-    bind: String,
-    buy_in: Usd,
+    auto_rebuy: bool,
+    autostart: Autostart,
+    big_blind: Usd,
+    bind: Vec<SocketAddr>,
+    bomb_pots: Option<usize>,
+    burn_cards: bool,
+    collusion: Option<u32>,
+    insurance: bool,
+    journal: Option<String>,
+    limit: Option<LimitStructure>,
+    log_format: LogFormat,
+    max_buy_in: Option<Usd>,
+    max_players: usize,
+    max_session: Option<u64>,
+    max_users: usize,
+    metrics: Option<String>,
+    min_buy_in: Usd,
+    on_timeout: TimeoutPolicy,
+    operator: Option<String>,
+    playlist: Vec<PlaylistEntry>,
+    privacy: Privacy,
+    random_seating: bool,
+    small_blind: Usd,
+    spectator_bets: bool,
+    stack: Usd,
+    variant: GameVariant,
+}
+
+#[derive(Default)]
+enum LogFormat {
+    Json,
+    #[default]
+    Plain,
+}
+
+fn parse_autostart(s: &str) -> Result<Autostart, Error> {
+    match s {
+        "off" => Ok(Autostart::Off),
+        "always" => Ok(Autostart::Always),
+        other => match other.parse() {
+            Ok(num_players) => Ok(Autostart::WhenPlayers(num_players)),
+            Err(_) => bail!("unknown autostart policy: {other}"),
+        },
+    }
+}
+
+fn parse_bind(s: &str) -> Result<Vec<SocketAddr>, Error> {
+    s.split(',')
+        .map(|addr| {
+            addr.trim()
+                .parse()
+                .map_err(|_| anyhow!("invalid bind address: {addr}"))
+        })
+        .collect()
+}
+
+fn parse_limit(s: &str) -> Result<LimitStructure, Error> {
+    match s
+        .split_once('/')
+        .map(|(small, big)| (small.parse(), big.parse()))
+    {
+        Some((Ok(small_bet), Ok(big_bet))) => Ok(LimitStructure {
+            small_bet,
+            big_bet,
+            max_raises_per_street: MAX_RAISES_PER_STREET,
+        }),
+        _ => bail!("invalid limit: {s}"),
+    }
+}
+
+fn parse_log_format(s: &str) -> Result<LogFormat, Error> {
+    match s {
+        "json" => Ok(LogFormat::Json),
+        "plain" => Ok(LogFormat::Plain),
+        other => bail!("unknown log format: {other}"),
+    }
+}
+
+fn parse_on_timeout(s: &str) -> Result<TimeoutPolicy, Error> {
+    let policy = match s.split_once(':') {
+        None if s == "fold" => TimeoutPolicy::FoldOnly,
+        Some(("sit_out", n)) => TimeoutPolicy::SitOutAfter(n.parse()?),
+        Some(("remove", n)) => TimeoutPolicy::RemoveAfter(n.parse()?),
+        _ => bail!("unknown timeout policy: {s}"),
+    };
+    if matches!(
+        policy,
+        TimeoutPolicy::SitOutAfter(0) | TimeoutPolicy::RemoveAfter(0)
+    ) {
+        bail!("timeout policies need at least one timeout: {s}");
+    }
+    Ok(policy)
+}
+
+/// Load a playlist from a JSON list of formats, checking each format's
+/// settings along the way.
+fn parse_playlist(s: &str) -> Result<Vec<PlaylistEntry>, Error> {
+    let file = File::open(s)?;
+    let playlist: Vec<PlaylistEntry> = serde_json::from_reader(BufReader::new(file))?;
+    for entry in &playlist {
+        entry
+            .settings
+            .validate()
+            .map_err(|error| anyhow!("invalid format {}: {error}", entry.name))?;
+    }
+    Ok(playlist)
+}
+
+fn parse_privacy(s: &str) -> Result<Privacy, Error> {
+    match s {
+        "open" => Ok(Privacy::Open),
+        "hide_money" => Ok(Privacy::HideSpectatorMoney),
+        "hide_users" => Ok(Privacy::HideNonPlayers),
+        other => bail!("unknown privacy level: {other}"),
+    }
+}
+
+fn parse_variant(s: &str) -> Result<GameVariant, Error> {
+    match s {
+        "holdem" => Ok(GameVariant::Holdem),
+        "omaha" => Ok(GameVariant::Omaha),
+        "short_deck" => Ok(GameVariant::ShortDeck),
+        other => bail!("unknown variant: {other}"),
+    }
 }
 
 fn main() -> Result<(), Error> {
@@ -43,21 +220,137 @@ fn main() -> Result<(), Error> {
     }
 
     let args = Args {
+        auto_rebuy: pargs.contains("--auto_rebuy"),
+        autostart: pargs
+            .opt_value_from_fn("--autostart", parse_autostart)?
+            .unwrap_or_default(),
+        big_blind: pargs
+            .value_from_str("--big_blind")
+            .unwrap_or(DEFAULT_MIN_BIG_BLIND),
         bind: pargs
-            .value_from_str("--bind")
-            .unwrap_or("127.0.0.1:6969".into()),
-        buy_in: pargs.value_from_str("--buy_in").unwrap_or(200),
+            .opt_value_from_fn("--bind", parse_bind)?
+            .unwrap_or_else(|| parse_bind(DEFAULT_BIND).expect("valid default addresses")),
+        bomb_pots: pargs.opt_value_from_str("--bomb_pots")?,
+        burn_cards: pargs.contains("--burn_cards"),
+        collusion: pargs.opt_value_from_str("--collusion")?,
+        insurance: pargs.contains("--insurance"),
+        journal: pargs.opt_value_from_str("--journal")?,
+        limit: pargs.opt_value_from_fn("--limit", parse_limit)?,
+        log_format: pargs
+            .opt_value_from_fn("--log_format", parse_log_format)?
+            .unwrap_or_default(),
+        max_buy_in: pargs.opt_value_from_str("--max_buy_in")?,
+        max_players: pargs.value_from_str("--max_players").unwrap_or(MAX_PLAYERS),
+        max_session: pargs.opt_value_from_str("--max_session")?,
+        max_users: pargs
+            .value_from_str("--max_users")
+            .unwrap_or(DEFAULT_MAX_USERS),
+        metrics: pargs.opt_value_from_str("--metrics")?,
+        min_buy_in: pargs.value_from_str("--min_buy_in").unwrap_or(0),
+        on_timeout: pargs
+            .opt_value_from_fn("--on_timeout", parse_on_timeout)?
+            .unwrap_or_default(),
+        operator: pargs.opt_value_from_str("--operator")?,
+        playlist: pargs
+            .opt_value_from_fn("--playlist", parse_playlist)?
+            .unwrap_or_default(),
+        privacy: pargs
+            .opt_value_from_fn("--privacy", parse_privacy)?
+            .unwrap_or_default(),
+        random_seating: pargs.contains("--random_seating"),
+        small_blind: pargs
+            .value_from_str("--small_blind")
+            .unwrap_or(DEFAULT_MIN_SMALL_BLIND),
+        spectator_bets: pargs.contains("--spectator_bets"),
+        stack: pargs.value_from_str("--stack").unwrap_or(DEFAULT_BUY_IN),
+        variant: pargs
+            .opt_value_from_fn("--variant", parse_variant)?
+            .unwrap_or_default(),
     };
 
-    let game_settings = GameSettings::new(MAX_PLAYERS, DEFAULT_MAX_USERS, args.buy_in);
-    let config: PokerConfig = game_settings.into();
+    let mut builder = GameSettings::builder()
+        .max_players(args.max_players)
+        .max_users(args.max_users)
+        .starting_stack(args.stack)
+        .blinds(args.small_blind, args.big_blind)
+        .min_buy_in(args.min_buy_in);
+    if let Some(max_buy_in) = args.max_buy_in {
+        builder = builder.max_buy_in(max_buy_in);
+    }
+    let mut game_settings = builder
+        .build()
+        .map_err(|error| anyhow!("invalid settings: {error}"))?;
+    if let Some(bomb_pots) = args.bomb_pots {
+        game_settings.bomb_pot_frequency = bomb_pots;
+    }
+    game_settings.auto_rebuy = args.auto_rebuy;
+    game_settings.burn_cards = args.burn_cards;
+    game_settings.random_first_seating = args.random_seating;
+    game_settings.spectator_bets = args.spectator_bets;
+    game_settings.autostart = args.autostart;
+    let mut playlist = args.playlist;
+    if let Some(threshold) = args.collusion {
+        let config = SoftPlayConfig {
+            threshold,
+            ..Default::default()
+        };
+        // Collusion is looked out for in every format.
+        for settings in playlist
+            .iter_mut()
+            .map(|entry| &mut entry.settings)
+            .chain([&mut game_settings])
+        {
+            settings.collusion_detector = Some(Box::new(SoftPlayDetector::new(config.clone())));
+        }
+    }
+    game_settings.insurance = args.insurance;
+    game_settings.limit = args.limit;
+    game_settings.privacy = args.privacy;
+    game_settings.variant = args.variant;
+    let mut config: PokerConfig = game_settings.into();
+    config.journal = args.journal.map(JournalConfig::new);
+    config.max_session = args
+        .max_session
+        .map(|minutes| Duration::from_secs(60 * minutes));
+    config.metrics_addr = args.metrics;
+    config.operator = match args.operator {
+        Some(username) => {
+            let secret = env::var(OPERATOR_SECRET_VAR)
+                .ok()
+                .filter(|secret| !secret.is_empty())
+                .ok_or_else(|| anyhow!("the operator needs a secret in ${OPERATOR_SECRET_VAR}"))?;
+            Some(OperatorConfig { username, secret })
+        }
+        None => None,
+    };
+    config.playlist = playlist;
+    config.timeout_policy = args.on_timeout;
 
-    // Catching signals for exit.
-    set_handler(|| std::process::exit(0))?;
+    // Log levels are still set with RUST_LOG.
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_target(false);
+    match args.log_format {
+        LogFormat::Json => subscriber.json().init(),
+        LogFormat::Plain => subscriber.init(),
+    }
+    let addrs: Vec<String> = args.bind.iter().map(ToString::to_string).collect();
+    info!(addrs = addrs.join(", "), "starting");
+    let server = server::spawn_multi(args.bind, config)?;
 
-    env_logger::builder().format_target(false).init();
-    info!("starting at {}", args.bind);
-    server::run(&args.bind, config)?;
+    // Signals drain the server so upgrades don't cut a hand short. Another
+    // signal while draining exits right away.
+    let drainer = server.drainer();
+    set_handler(move || {
+        if drainer.is_draining() {
+            std::process::exit(0);
+        }
+        if let Err(error) = drainer.drain() {
+            error!("couldn't drain: {error}");
+            std::process::exit(1);
+        }
+    })?;
+    server.join()?;
 
     Ok(())
 }
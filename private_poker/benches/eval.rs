@@ -0,0 +1,45 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use private_poker::{
+    entities::{Card, Suit},
+    functional::{eval, eval_7, new_deck, prepare_hand},
+};
+
+const NUM_HANDS: usize = 1000;
+
+fn random_hands() -> Vec<[Card; 7]> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut deck = new_deck();
+    (0..NUM_HANDS)
+        .map(|_| {
+            deck.shuffle(&mut rng);
+            let mut hand = [Card(0, Suit::Wild); 7];
+            hand.copy_from_slice(&deck[..7]);
+            hand
+        })
+        .collect()
+}
+
+fn bench_eval(c: &mut Criterion) {
+    let hands = random_hands();
+    c.bench_function("eval", |b| {
+        b.iter(|| {
+            for hand in hands.iter() {
+                let mut cards = hand.to_vec();
+                prepare_hand(&mut cards);
+                black_box(eval(&cards));
+            }
+        })
+    });
+    c.bench_function("eval_7", |b| {
+        b.iter(|| {
+            for hand in hands.iter() {
+                black_box(eval_7(hand));
+            }
+        })
+    });
+}
+
+criterion_group!(benches, bench_eval);
+criterion_main!(benches);
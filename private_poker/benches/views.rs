@@ -0,0 +1,42 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use private_poker::{GameSettings, PokerState, DEFAULT_BUY_IN, MAX_PLAYERS};
+
+const NUM_SPECTATORS: usize = 8;
+
+/// Return a full table that's waiting on the first player to act, with
+/// a crowd of spectators watching.
+fn full_table() -> PokerState {
+    let settings =
+        GameSettings::new(MAX_PLAYERS, MAX_PLAYERS + NUM_SPECTATORS, DEFAULT_BUY_IN).unwrap();
+    let mut state: PokerState = settings.into();
+    for i in 0..MAX_PLAYERS + NUM_SPECTATORS {
+        let username = i.to_string();
+        state.new_user(&username).unwrap();
+        if i < MAX_PLAYERS {
+            state.waitlist_user(&username).unwrap();
+        }
+    }
+    state.init_start("0").unwrap();
+    while state.get_next_action_username().is_none() {
+        state = state.step();
+    }
+    state
+}
+
+fn bench_views(c: &mut Criterion) {
+    let state = full_table();
+    // Get views like the server does for a broadcast, building each
+    // user's view.
+    c.bench_function("views", |b| {
+        b.iter(|| {
+            let views = state.get_views();
+            for username in views.usernames() {
+                black_box(views.get(username));
+            }
+        })
+    });
+}
+
+criterion_group!(benches, bench_views);
+criterion_main!(benches);
@@ -0,0 +1,537 @@
+//! Adaptive poker bots that play over TCP like any other client, and
+//! practice tables that seat bots at an in-process server.
+
+use anyhow::Error;
+use rand::{distributions::WeightedIndex, prelude::Distribution, thread_rng, Rng};
+use std::{
+    cmp::min,
+    collections::{HashMap, HashSet},
+    fmt,
+    ops::Range,
+    sync::{
+        mpsc::{channel, Receiver, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    game::{
+        entities::{
+            Action, DecisionContext, GameEvent, GameVariant, HoleCards, PlayerView, Street,
+            SubHand, Usd, Usdf,
+        },
+        functional,
+    },
+    net::{
+        client::Session,
+        messages::{ServerMessage, UserState},
+        server::{self, PokerConfig, ServerHandle},
+    },
+};
+
+/// The street and the bot's best hand as of the bot's latest turn.
+type State = (Street, Vec<SubHand>);
+type ActionMasks = HashSet<Action>;
+type ActionWeight = f32;
+type ActionWeights = [f32; 5];
+type Reward = f32;
+type Done = bool;
+
+const ACTIONS_ARRAY: [Action; 5] = [
+    Action::AllIn,
+    Action::Call(0),
+    Action::Check,
+    Action::Fold,
+    Action::Raise(0),
+];
+const Q_S_DEFAULT: ActionWeights = [0.2, 1.0, 1.0, 1.0, 0.2];
+
+pub const DEFAULT_ALPHA: f32 = 0.1;
+pub const DEFAULT_GAMMA: f32 = 0.95;
+/// How long bots take to act so real users have time to process info.
+pub const DEFAULT_THINK_TIME: Range<Duration> = Duration::from_secs(1)..Duration::from_secs(8);
+
+/// How a bot's connection to the server is doing.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BotStatus {
+    /// Connected and waiting to be seated.
+    Connected,
+    /// Seated at the table and playing hands.
+    Seated,
+    /// Lost the connection and waiting to make this attempt at
+    /// reconnecting.
+    Retrying { attempt: usize },
+    /// Lost the connection for good.
+    Disconnected,
+}
+
+impl fmt::Display for BotStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BotStatus::Connected => write!(f, "connected"),
+            BotStatus::Seated => write!(f, "seated"),
+            BotStatus::Retrying { attempt } => write!(f, "retrying (attempt {attempt})"),
+            BotStatus::Disconnected => write!(f, "disconnected"),
+        }
+    }
+}
+
+/// How bots get back to the table after losing their connection, like
+/// when the server restarts. Bots wait between attempts, doubling the
+/// wait after each failed attempt up to the max backoff.
+#[derive(Clone, Debug)]
+pub struct Reconnect {
+    /// Give up after this many failed attempts in a row, or never give
+    /// up if `None`.
+    pub max_retries: Option<usize>,
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for Reconnect {
+    fn default() -> Self {
+        Self {
+            max_retries: None,
+            min_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl Reconnect {
+    /// Return how long to wait before the attempt, starting from one.
+    fn backoff(&self, attempt: usize) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1) as u32);
+        min(self.min_backoff.saturating_mul(factor), self.max_backoff)
+    }
+}
+
+struct QLearningParams {
+    alpha: f32,
+    gamma: f32,
+}
+
+pub struct QLearning {
+    params: QLearningParams,
+    dist: WeightedIndex<ActionWeight>,
+    table: HashMap<State, ActionWeights>,
+}
+
+impl QLearning {
+    pub fn new(alpha: f32, gamma: f32) -> Self {
+        Self {
+            params: QLearningParams { alpha, gamma },
+            dist: WeightedIndex::new(Q_S_DEFAULT).expect("valid weights"),
+            table: HashMap::new(),
+        }
+    }
+
+    pub fn sample(&mut self, state: State, masks: ActionMasks) -> Action {
+        let old_weights = self.table.entry(state).or_insert(Q_S_DEFAULT);
+        let new_weights: Vec<ActionWeight> = ACTIONS_ARRAY
+            .iter()
+            .enumerate()
+            .map(|(idx, action)| {
+                if masks.contains(action) {
+                    old_weights[idx].exp()
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let new_weights: Vec<(usize, &ActionWeight)> = new_weights.iter().enumerate().collect();
+        self.dist
+            .update_weights(&new_weights)
+            .expect("valid weights");
+        let action_idx = self.dist.sample(&mut thread_rng());
+        let action = &ACTIONS_ARRAY[action_idx];
+        masks.get(action).expect("valid action").clone()
+    }
+
+    pub fn update_done(&mut self, state: State, action: Action, reward: Reward) {
+        let q_s = self.table.entry(state).or_insert(Q_S_DEFAULT);
+        let action_idx: usize = action.into();
+        q_s[action_idx] = reward;
+    }
+
+    pub fn update_step(
+        &mut self,
+        state1: State,
+        action: Action,
+        reward: Reward,
+        state2: State,
+        masks2: ActionMasks,
+    ) {
+        let td_target = {
+            let idx_masks2: HashSet<usize> = masks2.into_iter().map(|a| a.into()).collect();
+            let q_s2 = self.table.entry(state2).or_insert(Q_S_DEFAULT);
+            reward
+                + self.params.gamma
+                    * q_s2
+                        .iter()
+                        .enumerate()
+                        .filter(|(idx, _)| idx_masks2.contains(idx))
+                        .map(|(_, w)| w)
+                        .max_by(|a, b| a.partial_cmp(b).expect("valid weights"))
+                        .expect("valid weights")
+        };
+        let q_s1 = self.table.entry(state1).or_insert(Q_S_DEFAULT);
+        let action_idx: usize = action.into();
+        q_s1[action_idx] = q_s1[action_idx] + self.params.alpha * (td_target - q_s1[action_idx]);
+    }
+}
+
+pub struct Bot {
+    addr: String,
+    session: Session,
+    state: State,
+    starting_money: Usd,
+    think_time: Range<Duration>,
+    /// How the bot reconnects if it loses its connection, or `None` if
+    /// it stops playing instead.
+    reconnect: Option<Reconnect>,
+    status: Arc<Mutex<BotStatus>>,
+}
+
+impl Bot {
+    pub fn new(botname: &str, addr: &str, think_time: Range<Duration>) -> Result<Self, Error> {
+        let mut session = Session::connect(botname, addr)?;
+        let user = session
+            .latest_view()
+            .spectators
+            .get(botname)
+            .expect("user exists");
+        let starting_money = user.money;
+        Self::join(&mut session)?;
+        Ok(Self {
+            addr: addr.to_string(),
+            session,
+            state: (Street::Preflop, vec![]),
+            starting_money,
+            think_time,
+            reconnect: None,
+            status: Arc::new(Mutex::new(BotStatus::Connected)),
+        })
+    }
+
+    /// Reconnect instead of giving up when the connection is lost.
+    pub fn reconnect_with(mut self, reconnect: Reconnect) -> Self {
+        self.reconnect = Some(reconnect);
+        self
+    }
+
+    /// Return a handle to the bot's connection status that stays up to
+    /// date while the bot plays.
+    pub fn status(&self) -> Arc<Mutex<BotStatus>> {
+        self.status.clone()
+    }
+
+    fn set_status(&self, status: BotStatus) {
+        *self.status.lock().expect("status lock") = status;
+    }
+
+    /// Update the bot's status from whether it's seated in the latest
+    /// view.
+    fn update_status(&self) {
+        if self.get_player().is_some() {
+            self.set_status(BotStatus::Seated);
+        } else {
+            self.set_status(BotStatus::Connected);
+        }
+    }
+
+    /// Ask to play. Servers that allow it keep busted bots seated with a
+    /// fresh stack, so they don't have to reconnect between hands.
+    fn join(session: &mut Session) -> Result<(), Error> {
+        session.set_auto_rebuy(true, None)?;
+        session.change_state(UserState::Play)?;
+        Ok(())
+    }
+
+    /// Drop the current connection and connect again as the same user,
+    /// asking to play again. The hand the bot was in is forgotten.
+    fn reconnect(&mut self) -> Result<(), Error> {
+        self.session.shutdown().ok();
+        let username = self.session.username().to_string();
+        let mut session = Session::connect(&username, &self.addr)?;
+        Self::join(&mut session)?;
+        self.session = session;
+        self.state = (Street::Preflop, vec![]);
+        self.set_status(BotStatus::Connected);
+        Ok(())
+    }
+
+    fn get_state(&self) -> State {
+        self.state.clone()
+    }
+
+    /// Return the bot's player from the latest view if it's still at the
+    /// table.
+    fn get_player(&self) -> Option<&PlayerView> {
+        let view = self.session.latest_view();
+        view.players
+            .iter()
+            .find(|p| p.user.name == self.session.username())
+    }
+
+    /// Update the bot's state from the context of its turn.
+    fn update_state(&mut self, context: &DecisionContext) {
+        let hand = match context.variant {
+            GameVariant::Holdem => {
+                let mut cards = context.board.clone();
+                cards.extend(context.cards.clone());
+                functional::prepare_hand(&mut cards);
+                functional::eval(&cards)
+            }
+            GameVariant::Omaha => functional::eval_omaha(&context.cards, &context.board).into(),
+            GameVariant::ShortDeck => {
+                functional::eval_short_deck(&[&context.cards[..], &context.board].concat()).into()
+            }
+        };
+        self.state = (Street::from_num_community_cards(context.board.len()), hand);
+    }
+
+    pub fn reset(&mut self) -> Result<(State, ActionMasks), Error> {
+        // Hand is only empty before the first turn. Naturally, we'll be in
+        // spectate when we first connect, so check that our hand isn't empty
+        // before we try restarting our connection.
+        let username = self.session.username().to_string();
+        if !self.state.1.is_empty()
+            && self
+                .session
+                .latest_view()
+                .spectators
+                .contains_key(&username)
+        {
+            // If we were moved to spectate, disconnect and then immediately
+            // reconnect to the game to get a fresh money stack.
+            self.reconnect()?;
+        }
+
+        // Wait until it's our turn so we can get our hand and available
+        // actions.
+        let masks = loop {
+            match self.session.recv(None)? {
+                ServerMessage::GameView(_) => {
+                    self.update_status();
+                    if let Some(money) = self.get_player().map(|p| p.user.money) {
+                        self.starting_money = money;
+                    }
+                }
+                ServerMessage::TurnSignal {
+                    action_options,
+                    context,
+                    ..
+                } => {
+                    self.update_state(&context);
+                    break action_options;
+                }
+                _ => {}
+            }
+        };
+        Ok((self.get_state(), masks))
+    }
+
+    pub fn step(&mut self, action: Action) -> Result<(State, ActionMasks, Reward, Done), Error> {
+        let money = self.get_player().expect("player exists").user.money;
+        // Sleep some random amount so real users have time to process info.
+        if !self.think_time.is_empty() {
+            let dur = thread_rng().gen_range(self.think_time.clone());
+            thread::sleep(dur);
+        }
+        let bet = match action {
+            Action::AllIn => money,
+            Action::Check => 0,
+            Action::Fold => 0,
+            Action::Call(amount) => amount,
+            Action::Raise(amount) => amount,
+        };
+        self.session.act(action.clone())?;
+        if action == Action::Fold {
+            return Ok((self.get_state(), HashSet::new(), 0.0, true));
+        }
+        let mut remaining_money = money - bet;
+        let mut reward = -(bet as Usdf) / (self.starting_money as Usdf);
+        // We have to wait until the game is over or wait until it's our turn
+        // again so we can get masks and get the final reward for our action.
+        let masks = loop {
+            match self.session.recv(None)? {
+                // The server says what an all-in actually committed, which
+                // is what the reward is charged for.
+                ServerMessage::GameEvent(_, GameEvent::AllIn(ref username, amount))
+                    if username == self.session.username() && action == Action::AllIn =>
+                {
+                    remaining_money = money - amount;
+                    reward = -(amount as Usdf) / (self.starting_money as Usdf);
+                }
+                ServerMessage::GameView(_) => {
+                    self.update_status();
+                    let username = self.session.username().to_string();
+                    let money = match self.get_player() {
+                        // If we don't have anymore cards, then the game is over.
+                        Some(player) if player.cards == HoleCards::None => Some(player.user.money),
+                        Some(_) => None,
+                        // We were forcibly moved to spectate because we don't have
+                        // enough money. This means the current game is over.
+                        None => self
+                            .session
+                            .latest_view()
+                            .spectators
+                            .get(&username)
+                            .map(|user| user.money),
+                    };
+                    if let Some(money) = money {
+                        reward +=
+                            ((money - remaining_money) as Usdf) / (self.starting_money as Usdf);
+                        return Ok((self.get_state(), HashSet::new(), reward, true));
+                    }
+                }
+                ServerMessage::TurnSignal {
+                    action_options,
+                    context,
+                    ..
+                } => {
+                    self.update_state(&context);
+                    break action_options;
+                }
+                _ => {}
+            }
+        };
+        Ok((self.get_state(), masks, reward, false))
+    }
+}
+
+/// Have a bot play a hand, sampling actions from and updating the policy.
+fn play_hand(env: &mut Bot, policy: &Mutex<QLearning>) -> Result<(), Error> {
+    let (mut state1, mut masks1) = env.reset()?;
+    loop {
+        let action = {
+            let mut policy = policy.lock().expect("sample lock");
+            policy.sample(state1.clone(), masks1.clone())
+        };
+        let (state2, masks2, reward, done) = env.step(action.clone())?;
+        if done {
+            let mut policy = policy.lock().expect("done lock");
+            policy.update_done(state1.clone(), action.clone(), reward);
+            return Ok(());
+        }
+        {
+            let mut policy = policy.lock().expect("step lock");
+            policy.update_step(
+                state1.clone(),
+                action.clone(),
+                reward,
+                state2.clone(),
+                masks2.clone(),
+            );
+        }
+        state1.clone_from(&state2);
+        masks1.clone_from(&masks2);
+    }
+}
+
+/// Have a bot play hands, sampling actions from and updating the policy,
+/// until the bot is interrupted between hands or loses its connection.
+/// Bots that reconnect keep playing with the same policy once they're
+/// back, and can also be interrupted while they wait to retry.
+pub fn play(
+    mut env: Bot,
+    policy: Arc<Mutex<QLearning>>,
+    interrupt: Receiver<()>,
+) -> Result<(), Error> {
+    loop {
+        if let Err(mut error) = play_hand(&mut env, &policy) {
+            let Some(reconnect) = env.reconnect.clone() else {
+                env.set_status(BotStatus::Disconnected);
+                return Err(error);
+            };
+            let mut attempt = 1;
+            loop {
+                if reconnect
+                    .max_retries
+                    .is_some_and(|max_retries| attempt > max_retries)
+                {
+                    env.set_status(BotStatus::Disconnected);
+                    return Err(error);
+                }
+                env.set_status(BotStatus::Retrying { attempt });
+                let backoff = reconnect.backoff(attempt);
+                match interrupt.recv_timeout(backoff) {
+                    Ok(()) => return Ok(()),
+                    Err(RecvTimeoutError::Timeout) => {}
+                    // No one can interrupt the bot, but it should still
+                    // wait before retrying.
+                    Err(RecvTimeoutError::Disconnected) => thread::sleep(backoff),
+                }
+                match env.reconnect() {
+                    Ok(()) => break,
+                    Err(retry_error) => error = retry_error,
+                }
+                attempt += 1;
+            }
+        }
+        if interrupt.try_recv().is_ok() {
+            return Ok(());
+        }
+    }
+}
+
+/// A server running in the background with bots seated at it, so a user
+/// can practice without having to find other people to play with.
+pub struct PracticeTable {
+    bots: Vec<JoinHandle<Result<(), Error>>>,
+    server: ServerHandle,
+}
+
+impl PracticeTable {
+    /// Start a server on a random localhost port and seat bots at it. The
+    /// bots share a policy and learn from each other's hands.
+    pub fn start(
+        config: PokerConfig,
+        num_bots: usize,
+        think_time: Range<Duration>,
+    ) -> Result<Self, Error> {
+        let server = server::spawn("127.0.0.1:0", config)?;
+        let addr = server.addr().to_string();
+        let policy = Arc::new(Mutex::new(QLearning::new(DEFAULT_ALPHA, DEFAULT_GAMMA)));
+        let mut table = Self {
+            bots: Vec::with_capacity(num_bots),
+            server,
+        };
+        for idx in 1..=num_bots {
+            let bot = match Bot::new(&format!("bot{idx}"), &addr, think_time.clone()) {
+                Ok(bot) => bot,
+                Err(error) => {
+                    table.shutdown()?;
+                    return Err(error);
+                }
+            };
+            let policy = policy.clone();
+            // Practice bots are never interrupted; they play until the
+            // server shuts down.
+            let (_, interrupt) = channel();
+            table
+                .bots
+                .push(thread::spawn(move || play(bot, policy, interrupt)));
+        }
+        Ok(table)
+    }
+
+    pub fn addr(&self) -> String {
+        self.server.addr().to_string()
+    }
+
+    /// Shut down the server and wait for the bots to notice they've been
+    /// disconnected.
+    pub fn shutdown(self) -> Result<(), Error> {
+        self.server.shutdown()?;
+        for bot in self.bots {
+            // Bots can only exit by losing their connection, so their
+            // errors are expected.
+            bot.join().ok();
+        }
+        Ok(())
+    }
+}
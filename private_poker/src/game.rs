@@ -1,25 +1,60 @@
-use rand::{seq::SliceRandom, thread_rng};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use std::{
-    cmp::{max, min, Ordering},
-    collections::{BTreeSet, HashMap, HashSet, VecDeque},
-    fmt,
+    cmp::{max, min, Ordering, Reverse},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    fmt, mem,
+    time::Duration,
 };
 use thiserror::Error;
+use unicode_segmentation::UnicodeSegmentation;
 
+pub mod collusion;
 pub mod constants;
 pub mod entities;
+pub mod equity;
+pub mod fairness;
 pub mod functional;
-
-use constants::{DEFAULT_MAX_USERS, MAX_PLAYERS};
+pub mod sim;
+
+use collusion::CollusionDetector;
+use constants::{
+    DEFAULT_BOMB_POT_ANTE, DEFAULT_BRIEF_STEP_TIMEOUT, DEFAULT_DISCONNECT_GRACE_HANDS,
+    DEFAULT_MAX_RAILBIRDS, DEFAULT_MAX_USERS, DEFAULT_STEP_TIMEOUT, MAX_COMMUNITY_CARDS,
+    MAX_EQUITY_SAMPLES, MAX_PLAYERS, MAX_TAGLINE_LENGTH, MAX_USER_INPUT_LENGTH, MAX_VIEWED_ACTIONS,
+    MIN_PLAYERS,
+};
 use entities::{
-    Action, Bet, BetAction, Card, GameView, GameViews, Player, PlayerState, PlayerView, Pot,
-    PotView, SubHand, Usd, Usdf, User, DEFAULT_BUY_IN, DEFAULT_MIN_BIG_BLIND,
-    DEFAULT_MIN_SMALL_BLIND,
+    Action, ActionRecord, Bet, BetAction, Card, DecisionContext, DistributionOutcome, GameEvent,
+    GamePhase, GameVariant, GameView, GameViews, HandId, HandValue, HoleCards, Insurance,
+    Leaderboard, LedgerEntry, LimitStructure, PendingAction, Player, PlayerState, PlayerView, Pot,
+    PotSegment, PotView, Privacy, Profile, Reveal, SeatIndex, SideBet, SpectatorPool, Street, Suit,
+    TurnId, Usd, Usdf, User, DEFAULT_BUY_IN, DEFAULT_MIN_BIG_BLIND, DEFAULT_MIN_SMALL_BLIND,
 };
+use fairness::{DeckReveal, Nonce};
+
+/// Errors from invalid game settings. These are caught before a game
+/// is created rather than during gameplay.
+#[derive(Debug, Eq, Error, PartialEq)]
+pub enum ConfigError {
+    #[error("max players must be {min}-{max}")]
+    InvalidMaxPlayers { min: usize, max: usize },
+    #[error("max users must be >= max players ({max_players})")]
+    InvalidMaxUsers { max_players: usize },
+    #[error("big blind must be > small blind > 0")]
+    InvalidBlinds,
+    #[error("starting stack must be >= the big blind (${big_blind})")]
+    InvalidStartingStack { big_blind: Usd },
+    #[error("buy-ins must be from ${min_buy_in} to ${max_buy_in}, including the starting stack")]
+    InvalidBuyIns { min_buy_in: Usd, max_buy_in: Usd },
+}
 
 #[derive(Debug, Deserialize, Eq, Error, PartialEq, Serialize)]
 pub enum UserError {
+    #[error("can't muck hand")]
+    CannotMuckHand,
+    #[error("can only insure all-in players before the showdown")]
+    CannotInsure,
     #[error("can't show hand")]
     CannotShowHand,
     #[error("can't start unless you're waitlisted or a player")]
@@ -30,66 +65,314 @@ pub enum UserError {
     GameAlreadyInProgress,
     #[error("game already starting")]
     GameAlreadyStarting,
+    #[error("auto-rebuy is disabled")]
+    AutoRebuyDisabled,
     #[error("need >= ${big_blind} for the big blind")]
     InsufficientFunds { big_blind: Usd },
+    #[error("insurer can't cover the ${payout} payout")]
+    InsurerCannotCover { payout: Usd },
     #[error("{action} is invalid")]
     InvalidAction { action: Action },
     #[error("illegal {bet}")]
     InvalidBet { bet: Bet },
+    #[error("insurance needs a nonzero premium and odds")]
+    InvalidInsurance,
+    #[error("can only show one of your {num_cards} cards")]
+    InvalidCard { num_cards: usize },
+    #[error("seat {seat_idx} isn't open")]
+    SeatUnavailable { seat_idx: SeatIndex },
+    #[error("username must be 1-{max_length} characters without whitespace")]
+    InvalidUsername { max_length: usize },
+    #[error("avatar must be one character and tagline 1-{max_length} characters")]
+    InvalidProfile { max_length: usize },
+    #[error("can only add chips up to a ${max_buy_in} stack")]
+    MaxBuyInExceeded { max_buy_in: Usd },
+    #[error("must add chips up to at least a ${min_buy_in} stack")]
+    MinBuyInNotMet { min_buy_in: Usd },
+    #[error("no insurance offer from that user")]
+    NoInsuranceOffer,
     #[error("need 2+ players")]
     NotEnoughPlayers,
+    #[error("can only rename once per hand")]
+    RenameLimitReached,
     #[error("not your turn")]
     OutOfTurnAction,
+    #[error("that turn is already over")]
+    StaleAction,
     #[error("user already exists")]
     UserAlreadyExists,
     #[error("user does not exist")]
     UserDoesNotExist,
     #[error("not playing")]
     UserNotPlaying,
+    #[error("not a railbird")]
+    UserNotRailbird,
     #[error("already showing hand")]
     UserAlreadyShowingHand,
+    #[error("hand already mucked")]
+    UserMuckedHand,
+    #[error("session is ending")]
+    SessionEnding,
+    #[error("spectators can only bet on players still in the hand while they're betting")]
+    CannotSideBet,
+    #[error("side bets must be from $1 up to your ${money} stack")]
+    InvalidSideBet { money: Usd },
+    #[error("side bets are disabled")]
+    SideBetsDisabled,
+    #[error("can't see every hand while playing")]
+    CannotViewAllCards,
+    #[error("username belongs to someone that left")]
+    UsernameTaken,
 }
 
-#[derive(Debug)]
+/// When the game starts on its own rather than waiting for a user to
+/// start it.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Autostart {
+    /// Only users can start the game.
+    #[default]
+    Off,
+    /// Start once this many users can play. Thresholds below the
+    /// minimum number of players are treated as the minimum.
+    WhenPlayers(usize),
+    /// Start as soon as there are enough users to play.
+    Always,
+}
+
+/// Settings can be deserialized, like from a config file, with missing
+/// settings taking their defaults. Deserialized settings should be
+/// validated before they're used.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
 pub struct GameSettings {
-    pub buy_in: Usd,
+    /// Money new users start with.
+    pub starting_stack: Usd,
+    /// Smallest stack a user can have after adding chips to it.
+    pub min_buy_in: Usd,
+    /// Largest stack a user can have after adding chips to it.
+    pub max_buy_in: Usd,
+    /// Blinds the game starts with. Blinds are raised in multiples of
+    /// these as the shortest stack grows in multiples of the starting
+    /// stack.
     pub min_big_blind: Usd,
     pub min_small_blind: Usd,
     pub max_players: usize,
     pub max_users: usize,
+    /// Railbirds don't count towards the max users, so they're capped
+    /// separately.
+    pub max_railbirds: usize,
+    /// Whether all hands in a contested pot are revealed at the showdown.
+    /// If disabled, only the winning hand(s) and hands that players
+    /// explicitly chose to show are revealed.
+    pub auto_show_losers: bool,
+    /// Number of hands after the current one that a disconnected player
+    /// keeps their seat for. Disconnected players check when they can and
+    /// fold otherwise. If zero, disconnected players are removed at the
+    /// end of the current hand.
+    pub disconnect_grace_hands: usize,
+    /// Every Nth hand is a bomb pot, where every player antes and the
+    /// hand starts at the flop. If zero, there are no bomb pots.
+    pub bomb_pot_frequency: usize,
+    /// Number of big blinds each player antes in a bomb pot.
+    pub bomb_pot_ante: Usd,
+    /// Whether users can offer insurance to all-in players during the
+    /// runout to the showdown.
+    pub insurance: bool,
+    /// Whether spectators can bet play money from their stacks on which
+    /// player wins the hand.
+    pub spectator_bets: bool,
+    pub variant: GameVariant,
+    /// How much of the spectators and waitlist users can see of each other.
+    pub privacy: Privacy,
+    pub autostart: Autostart,
+    /// Betting structure for fixed-limit games. If `None`, the game is
+    /// no-limit.
+    pub limit: Option<LimitStructure>,
+    /// Whether users can opt into having their stack reset to the
+    /// starting stack instead of being spectated when they can't afford
+    /// the big blind.
+    pub auto_rebuy: bool,
+    /// Whether players seated at an empty table draw for their seats and
+    /// the button rather than being seated in waitlist order with the
+    /// button on the first seat. Draws use the same seeded RNG as the
+    /// deck.
+    pub random_first_seating: bool,
+    /// Whether a card is burned before dealing the flop, turn, and river.
+    /// Burned cards are only shown when the deck is revealed.
+    pub burn_cards: bool,
+    /// Looks over each hand once it's over for signs of collusion. Its
+    /// suspicions are raised as game events meant for operators.
+    #[serde(skip)]
+    pub collusion_detector: Option<Box<dyn CollusionDetector>>,
+    /// Seed for shuffling the deck so games can be replayed exactly, like
+    /// in simulations. If `None`, the deck is shuffled from entropy.
+    pub seed: Option<u64>,
 }
 
 impl GameSettings {
-    pub fn new(max_players: usize, max_users: usize, buy_in: Usd) -> Self {
+    /// Start building settings from the defaults.
+    pub fn builder() -> GameSettingsBuilder {
+        GameSettingsBuilder::default()
+    }
+
+    /// Settings where the buy-in is both the starting stack and the max
+    /// buy-in, and the blinds are derived from it. Use the builder to set
+    /// these independently. Buy-ins too small for the derived blinds to
+    /// be at least a dollar are invalid.
+    pub fn new(max_players: usize, max_users: usize, buy_in: Usd) -> Result<Self, ConfigError> {
         let min_big_blind = buy_in / 20;
         let min_small_blind = min_big_blind / 2;
-        Self {
-            buy_in,
+        let settings = Self {
+            starting_stack: buy_in,
+            min_buy_in: 0,
+            max_buy_in: buy_in,
             min_big_blind,
             min_small_blind,
             max_players,
             max_users,
+            max_railbirds: DEFAULT_MAX_RAILBIRDS,
+            auto_show_losers: true,
+            disconnect_grace_hands: DEFAULT_DISCONNECT_GRACE_HANDS,
+            bomb_pot_frequency: 0,
+            bomb_pot_ante: DEFAULT_BOMB_POT_ANTE,
+            insurance: false,
+            spectator_bets: false,
+            variant: GameVariant::Holdem,
+            privacy: Privacy::Open,
+            autostart: Autostart::Off,
+            limit: None,
+            auto_rebuy: false,
+            random_first_seating: false,
+            burn_cards: false,
+            collusion_detector: None,
+            seed: None,
+        };
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Check that the settings make for a playable game, like the builder
+    /// does when it builds them.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&self.max_players) {
+            return Err(ConfigError::InvalidMaxPlayers {
+                min: MIN_PLAYERS,
+                max: MAX_PLAYERS,
+            });
+        } else if self.max_users < self.max_players {
+            return Err(ConfigError::InvalidMaxUsers {
+                max_players: self.max_players,
+            });
+        } else if self.min_small_blind == 0 || self.min_big_blind <= self.min_small_blind {
+            return Err(ConfigError::InvalidBlinds);
+        } else if self.starting_stack < self.min_big_blind {
+            return Err(ConfigError::InvalidStartingStack {
+                big_blind: self.min_big_blind,
+            });
+        } else if !(self.min_buy_in..=self.max_buy_in).contains(&self.starting_stack) {
+            return Err(ConfigError::InvalidBuyIns {
+                min_buy_in: self.min_buy_in,
+                max_buy_in: self.max_buy_in,
+            });
         }
+        Ok(())
     }
 }
 
 impl Default for GameSettings {
     fn default() -> Self {
         Self {
-            buy_in: DEFAULT_BUY_IN,
+            starting_stack: DEFAULT_BUY_IN,
+            min_buy_in: 0,
+            max_buy_in: DEFAULT_BUY_IN,
             min_big_blind: DEFAULT_MIN_BIG_BLIND,
             min_small_blind: DEFAULT_MIN_SMALL_BLIND,
             max_players: MAX_PLAYERS,
             max_users: DEFAULT_MAX_USERS,
+            max_railbirds: DEFAULT_MAX_RAILBIRDS,
+            auto_show_losers: true,
+            disconnect_grace_hands: DEFAULT_DISCONNECT_GRACE_HANDS,
+            bomb_pot_frequency: 0,
+            bomb_pot_ante: DEFAULT_BOMB_POT_ANTE,
+            insurance: false,
+            spectator_bets: false,
+            variant: GameVariant::Holdem,
+            privacy: Privacy::Open,
+            autostart: Autostart::Off,
+            limit: None,
+            auto_rebuy: false,
+            random_first_seating: false,
+            burn_cards: false,
+            collusion_detector: None,
+            seed: None,
         }
     }
 }
 
+/// Builds game settings where the starting stack, buy-ins, and blinds
+/// are all set independently, validating them together once they're
+/// built. Settings the builder doesn't cover can be changed on the built
+/// settings.
+#[derive(Debug, Default)]
+pub struct GameSettingsBuilder {
+    settings: GameSettings,
+    /// The max buy-in follows the starting stack unless it's set.
+    max_buy_in: Option<Usd>,
+}
+
+impl GameSettingsBuilder {
+    pub fn blinds(mut self, small_blind: Usd, big_blind: Usd) -> Self {
+        self.settings.min_small_blind = small_blind;
+        self.settings.min_big_blind = big_blind;
+        self
+    }
+
+    pub fn build(mut self) -> Result<GameSettings, ConfigError> {
+        self.settings.max_buy_in = self.max_buy_in.unwrap_or(self.settings.starting_stack);
+        self.settings.validate()?;
+        Ok(self.settings)
+    }
+
+    pub fn max_buy_in(mut self, max_buy_in: Usd) -> Self {
+        self.max_buy_in = Some(max_buy_in);
+        self
+    }
+
+    pub fn max_players(mut self, max_players: usize) -> Self {
+        self.settings.max_players = max_players;
+        self
+    }
+
+    pub fn max_users(mut self, max_users: usize) -> Self {
+        self.settings.max_users = max_users;
+        self
+    }
+
+    pub fn min_buy_in(mut self, min_buy_in: Usd) -> Self {
+        self.settings.min_buy_in = min_buy_in;
+        self
+    }
+
+    pub fn starting_stack(mut self, starting_stack: Usd) -> Self {
+        self.settings.starting_stack = starting_stack;
+        self
+    }
+}
+
 #[derive(Debug)]
 pub struct GameData {
     /// Deck of cards. This is instantiated once and reshuffled
-    /// each deal.
-    deck: [Card; 52],
+    /// each deal, unless the variant changes to one that's played with
+    /// a different deck.
+    deck: Vec<Card>,
+    /// Nonce the current hand's deck order was committed to with. It's
+    /// kept secret until the hand is over.
+    deck_nonce: Option<Nonce>,
+    /// Cards burned during the current hand, in the order they were
+    /// burned. They're kept secret until the deck is revealed.
+    burned: Vec<Card>,
+    /// Shuffles the deck, seeded by the settings if they have a seed.
+    rng: StdRng,
     /// Money from users that've left the game. This money is
     /// split equally amongst all users at a particular game state.
     /// This helps keep the amount of money in the game constant,
@@ -99,7 +382,11 @@ pub struct GameData {
     pub big_blind: Usd,
     pub spectators: HashMap<String, User>,
     pub waitlist: VecDeque<User>,
-    pub open_seats: VecDeque<usize>,
+    /// Users that gave up their seat and stack but are still following
+    /// the game. Their stacks are held for them in the ledger until they
+    /// rejoin.
+    pub railbirds: BTreeSet<String>,
+    pub open_seats: VecDeque<SeatIndex>,
     pub players: Vec<Player>,
     /// Community cards shared amongst all players.
     pub board: Vec<Card>,
@@ -115,6 +402,17 @@ pub struct GameData {
     /// raises (since they've increased the minimum call).
     num_players_called: usize,
     pub pot: Pot,
+    /// Game events that haven't been relayed to users yet.
+    events: VecDeque<GameEvent>,
+    /// Queue of players that want to add chips to their stack. We
+    /// can't safely change a player's stack mid gameplay, so we
+    /// instead queue the amount and add it after the hand.
+    players_to_top_up: BTreeMap<String, Usd>,
+    /// The hand each user last renamed themselves during. Users can
+    /// only rename themselves once per hand, and users in the lobby
+    /// count as being in the hand that's about to start. Renames made
+    /// between hands are moved to that hand once it starts.
+    renamed_users: HashMap<String, HandId>,
     /// Queue of users that're playing the game but have opted
     /// to spectate. We can't safely remove them from the game mid gameplay,
     /// so we instead queue them for removal.
@@ -123,7 +421,58 @@ pub struct GameData {
     /// to leave. We can't safely remove them from the game mid gameplay,
     /// so we instead queue them for removal.
     players_to_remove: BTreeSet<String>,
+    /// Players queued for removal that are moving to the rail rather
+    /// than leaving.
+    players_to_rail: BTreeSet<String>,
+    /// Queue of players that want to move to another seat. We can't
+    /// safely reorder players mid gameplay, so we instead queue the seat
+    /// they want and move them after the hand.
+    players_to_change_seat: BTreeMap<String, SeatIndex>,
+    /// Players whose connection dropped mid hand, mapped to the number
+    /// of additional hands they keep their seat for. They're removed
+    /// once their grace period runs out unless they reconnect.
+    disconnected_players: BTreeMap<String, usize>,
+    /// Money each user has put into and taken out of the game, used
+    /// for tracking profits and losses over the session.
+    ledger: HashMap<String, LedgerEntry>,
+    /// Insurance offered during the current hand that hasn't been
+    /// accepted yet. Offers expire when the hand ends.
+    insurance_offers: Vec<Insurance>,
+    /// Insurance accepted during the current hand. The insurer's payout
+    /// is held here until the insurance is settled.
+    insurance: Vec<Insurance>,
+    /// Side bets spectators placed on the current hand. The pool locks
+    /// once betting is over and pays out after the pot is distributed.
+    spectator_pool: SpectatorPool,
+    /// Indices of players that won (part of) a pot during the current
+    /// hand, used for settling insurance and side bets.
+    hand_winners: BTreeSet<usize>,
+    /// Actions taken during the current hand, or the last hand if one
+    /// isn't in progress.
+    action_history: Vec<ActionRecord>,
+    /// Each remaining player's chance of winning the pot while an all-in
+    /// hand runs out with every remaining hand shown.
+    equities: Vec<(String, f32)>,
+    /// Users that opted into rebuying when they can't afford the big
+    /// blind.
+    auto_rebuy_users: HashSet<String>,
     deck_idx: usize,
+    /// Whether the session is winding down, in which case the hand in
+    /// progress is the last one.
+    session_ending: bool,
+    /// Identifier of the current hand, or the last hand that was played
+    /// if a hand isn't in progress.
+    pub hand_id: HandId,
+    /// Identifier of the current turn, or the last turn that was given if
+    /// it isn't anyone's turn.
+    turn_id: TurnId,
+    /// Whether the current hand is a bomb pot.
+    pub bomb_pot: bool,
+    pub button_idx: usize,
+    /// Seat the button was on last hand, if there's been one. The button
+    /// moves on from this seat rather than from a player so it doesn't
+    /// skip or repeat seats as players come, go, and change seats.
+    button_seat_idx: Option<SeatIndex>,
     pub small_blind_idx: usize,
     pub big_blind_idx: usize,
     starting_action_idx: usize,
@@ -135,21 +484,45 @@ impl GameData {
     fn new() -> Self {
         let settings = GameSettings::default();
         Self {
-            deck: functional::new_deck(),
+            deck: new_deck(settings.variant),
+            deck_nonce: None,
+            burned: Vec::with_capacity(3),
+            rng: StdRng::from_entropy(),
             donations: 0.0,
             small_blind: settings.min_small_blind,
             big_blind: settings.min_big_blind,
             spectators: HashMap::with_capacity(settings.max_users),
             waitlist: VecDeque::with_capacity(settings.max_users),
+            railbirds: BTreeSet::new(),
             open_seats: VecDeque::from_iter(0..settings.max_players),
             players: Vec::with_capacity(settings.max_players),
             board: Vec::with_capacity(5),
             num_players_active: 0,
             num_players_called: 0,
             pot: Pot::new(settings.max_players),
+            events: VecDeque::new(),
+            players_to_top_up: BTreeMap::new(),
+            renamed_users: HashMap::new(),
             players_to_remove: BTreeSet::new(),
+            players_to_rail: BTreeSet::new(),
             players_to_spectate: BTreeSet::new(),
+            players_to_change_seat: BTreeMap::new(),
+            disconnected_players: BTreeMap::new(),
+            ledger: HashMap::new(),
+            insurance_offers: Vec::new(),
+            insurance: Vec::new(),
+            spectator_pool: SpectatorPool::default(),
+            hand_winners: BTreeSet::new(),
+            action_history: Vec::new(),
+            equities: Vec::new(),
+            auto_rebuy_users: HashSet::new(),
             deck_idx: 0,
+            session_ending: false,
+            hand_id: 0,
+            turn_id: 0,
+            bomb_pot: false,
+            button_idx: 0,
+            button_seat_idx: None,
             small_blind_idx: 0,
             big_blind_idx: 1,
             starting_action_idx: 2,
@@ -162,21 +535,47 @@ impl GameData {
 impl From<GameSettings> for GameData {
     fn from(value: GameSettings) -> Self {
         Self {
-            deck: functional::new_deck(),
+            deck: new_deck(value.variant),
+            deck_nonce: None,
+            burned: Vec::with_capacity(3),
+            rng: value
+                .seed
+                .map_or_else(StdRng::from_entropy, StdRng::seed_from_u64),
             donations: 0.0,
             small_blind: value.min_small_blind,
             big_blind: value.min_big_blind,
             spectators: HashMap::with_capacity(value.max_users),
             waitlist: VecDeque::with_capacity(value.max_users),
+            railbirds: BTreeSet::new(),
             open_seats: VecDeque::from_iter(0..value.max_players),
             players: Vec::with_capacity(value.max_players),
             board: Vec::with_capacity(5),
             num_players_active: 0,
             num_players_called: 0,
             pot: Pot::new(value.max_players),
+            events: VecDeque::new(),
+            players_to_top_up: BTreeMap::new(),
+            renamed_users: HashMap::new(),
             players_to_remove: BTreeSet::new(),
+            players_to_rail: BTreeSet::new(),
             players_to_spectate: BTreeSet::new(),
+            players_to_change_seat: BTreeMap::new(),
+            disconnected_players: BTreeMap::new(),
+            ledger: HashMap::new(),
+            insurance_offers: Vec::new(),
+            insurance: Vec::new(),
+            spectator_pool: SpectatorPool::default(),
+            hand_winners: BTreeSet::new(),
+            action_history: Vec::new(),
+            equities: Vec::new(),
+            auto_rebuy_users: HashSet::new(),
             deck_idx: 0,
+            session_ending: false,
+            hand_id: 0,
+            turn_id: 0,
+            bomb_pot: false,
+            button_idx: 0,
+            button_seat_idx: None,
             small_blind_idx: 0,
             big_blind_idx: 1,
             starting_action_idx: 2,
@@ -233,28 +632,65 @@ pub struct River {}
 pub struct ShowHands {
     /// Temporarily maps player seats to poker hand evaluations so a player's
     /// hand doesn't have to be evaluated multiple times per game.
-    hand_eval_cache: HashMap<usize, Vec<SubHand>>,
+    hand_eval_cache: HashMap<usize, HandValue>,
 }
 
 impl Default for ShowHands {
     fn default() -> Self {
-        Self::new()
+        Self::new(MAX_PLAYERS)
     }
 }
 
 impl ShowHands {
-    pub fn new() -> Self {
+    pub fn new(max_players: usize) -> Self {
         ShowHands {
-            hand_eval_cache: HashMap::with_capacity(MAX_PLAYERS),
+            hand_eval_cache: HashMap::with_capacity(max_players),
         }
     }
 }
 
+/// Evaluate a player's hand with the board, caching the evaluation so a
+/// player's hand doesn't have to be evaluated multiple times per game.
+fn eval_player_hand(
+    hand_eval_cache: &mut HashMap<usize, HandValue>,
+    player_idx: usize,
+    player: &Player,
+    board: &[Card],
+    variant: GameVariant,
+) -> HandValue {
+    *hand_eval_cache
+        .entry(player_idx)
+        .or_insert_with(|| eval_hand(&player.cards, board, variant))
+}
+
+/// Evaluate hole cards with the board by the variant's rules.
+fn eval_hand(cards: &[Card], board: &[Card], variant: GameVariant) -> HandValue {
+    match variant {
+        GameVariant::Holdem => {
+            let mut hand = [Card(0, Suit::Wild); 7];
+            for (card, dealt_card) in hand.iter_mut().zip(cards.iter().chain(board)) {
+                *card = *dealt_card;
+            }
+            functional::eval_7(&hand)
+        }
+        GameVariant::Omaha => functional::eval_omaha(cards, board),
+        GameVariant::ShortDeck => functional::eval_short_deck(&[cards, board].concat()),
+    }
+}
+
+/// Create a new, unshuffled deck for playing the variant.
+fn new_deck(variant: GameVariant) -> Vec<Card> {
+    match variant {
+        GameVariant::Holdem | GameVariant::Omaha => functional::new_deck().to_vec(),
+        GameVariant::ShortDeck => functional::new_short_deck().to_vec(),
+    }
+}
+
 #[derive(Debug)]
 pub struct DistributePot {
     /// Temporarily maps player seats to poker hand evaluations so a player's
     /// hand doesn't have to be evaluated multiple times per game.
-    hand_eval_cache: HashMap<usize, Vec<SubHand>>,
+    hand_eval_cache: HashMap<usize, HandValue>,
 }
 
 #[derive(Debug)]
@@ -278,13 +714,23 @@ pub struct Game<T> {
 
 /// General game methods.
 impl<T> Game<T> {
-    pub fn action_options_to_string(action_options: &HashSet<Action>) -> String {
+    /// Describe action options for people. Raises are described with
+    /// their upper bound if there is one.
+    pub fn action_options_to_string(
+        action_options: &HashSet<Action>,
+        max_raise: Option<Usd>,
+    ) -> String {
         let num_options = action_options.len();
         action_options
             .iter()
             .enumerate()
             .map(|(i, action)| {
-                let repr = action.to_option_string();
+                let repr = match (action, max_raise) {
+                    (Action::Raise(min_raise), Some(max_raise)) => {
+                        format!("raise between ${min_raise} and ${max_raise}")
+                    }
+                    _ => action.to_option_string(),
+                };
                 match i {
                     0 if num_options == 1 => repr,
                     0 if num_options == 2 => format!("{repr} "),
@@ -297,18 +743,129 @@ impl<T> Game<T> {
             .join("")
     }
 
-    fn as_view(&self, username: &str) -> GameView {
+    /// Burn the next card in the deck before dealing a street if the
+    /// game burns cards. Burning is skipped rather than leaving the deck
+    /// without enough cards to finish the board, though no table is big
+    /// enough for that to happen.
+    fn burn_card(&mut self) {
+        if !self.data.settings.burn_cards {
+            return;
+        }
+        let num_cards_left = self.data.deck.len() - self.data.deck_idx;
+        let num_cards_needed = MAX_COMMUNITY_CARDS - self.data.board.len();
+        if num_cards_left <= num_cards_needed {
+            return;
+        }
+        let card = self.data.deck[self.data.deck_idx];
+        self.data.burned.push(card);
+        self.data.deck_idx += 1;
+    }
+
+    /// Put the next card in the deck on the board, letting everyone know
+    /// which card it is and where it went.
+    fn deal_community_card(&mut self) {
+        let card = self.data.deck[self.data.deck_idx];
+        let position = self.data.board.len();
+        self.data.board.push(card);
+        self.data.deck_idx += 1;
+        self.data
+            .events
+            .push_back(GameEvent::CommunityCardDealt(card, position));
+    }
+
+    /// Update every remaining player's equity once no one can bet anymore
+    /// and every remaining hand is shown. Equities aren't computed while
+    /// any remaining hand is hidden since they'd give that hand away.
+    fn update_equities(&mut self) {
+        let remaining: Vec<&Player> = self
+            .data
+            .players
+            .iter()
+            .filter(|player| player.state != PlayerState::Fold)
+            .collect();
+        if !self.is_ready_for_showdown()
+            || remaining.len() < 2
+            || remaining.iter().any(|player| !player.showing)
+        {
+            return;
+        }
+        let usernames: Vec<String> = remaining
+            .iter()
+            .map(|player| player.user.name.clone())
+            .collect();
+        let hands: Vec<Vec<Card>> = remaining
+            .iter()
+            .map(|player| player.cards.clone())
+            .collect();
+        let unseen: Vec<Card> = new_deck(self.data.settings.variant)
+            .into_iter()
+            .filter(|card| {
+                !self.data.board.contains(card) && !hands.iter().any(|hand| hand.contains(card))
+            })
+            .collect();
+        let equities = equity::equities(
+            &hands,
+            &self.data.board,
+            &unseen,
+            self.data.settings.variant,
+            MAX_EQUITY_SAMPLES,
+            &mut self.data.rng,
+        );
+        self.data.equities = usernames.into_iter().zip(equities).collect();
+        self.data
+            .events
+            .push_back(GameEvent::Equity(self.data.equities.clone()));
+    }
+
+    /// Return a view of the game with every player's hole cards, whether
+    /// they've been shown or not, and no users redacted. Only ever meant
+    /// for the server's operator, never for users.
+    pub fn as_omniscient_view(&self) -> GameView {
+        let mut view = self.as_public_view();
+        for (player_view, player) in view.players.iter_mut().zip(self.data.players.iter()) {
+            if !player.cards.is_empty() {
+                player_view.cards = HoleCards::Revealed(player.cards.clone());
+            }
+        }
+        view.spectators = self.data.spectators.clone();
+        view.waitlist = self.data.waitlist.clone();
+        view
+    }
+
+    /// Return the view of the game that everyone can see. Spectators and
+    /// waitlisted users are redacted according to the game's privacy.
+    fn as_public_view(&self) -> GameView {
         let mut players = Vec::with_capacity(self.data.settings.max_players);
-        for player in self.data.players.iter() {
-            let cards = if player.user.name == username || player.showing {
-                player.cards.clone()
-            } else {
-                vec![]
+        for (player_idx, player) in self.data.players.iter().enumerate() {
+            let revealed = match (player.showing, player.shown_card) {
+                (true, _) => Reveal::Hand,
+                (false, Some(card_idx)) => Reveal::Card(card_idx),
+                (false, None) => Reveal::Hidden,
+            };
+            // Folded and mucked hands are out of play, so there's nothing
+            // left at their seat to see.
+            let cards = match revealed {
+                Reveal::Hand => HoleCards::Revealed(player.cards.clone()),
+                Reveal::Card(card_idx) => HoleCards::Revealed(vec![player.cards[card_idx]]),
+                Reveal::Hidden
+                    if player.cards.is_empty()
+                        || player.mucked
+                        || player.state == PlayerState::Fold =>
+                {
+                    HoleCards::None
+                }
+                Reveal::Hidden => HoleCards::Hidden {
+                    count: player.cards.len(),
+                },
             };
             let player_view = PlayerView {
                 user: player.user.clone(),
                 state: player.state.clone(),
                 cards,
+                revealed,
+                disconnected: self.is_disconnected(&player.user.name),
+                last_bet: player.last_bet.clone(),
+                investment: self.data.pot.get_investment_by_player_idx(player_idx),
             };
             players.push(player_view);
         }
@@ -318,30 +875,86 @@ impl<T> Game<T> {
         } else {
             self.data.next_action_idx
         };
+        let privacy = self.data.settings.privacy;
+        let (spectators, waitlist) = match privacy {
+            Privacy::Open => (self.data.spectators.clone(), self.data.waitlist.clone()),
+            Privacy::HideSpectatorMoney => {
+                let redact = |user: &User| User {
+                    name: user.name.clone(),
+                    money: 0,
+                    profile: user.profile.clone(),
+                };
+                (
+                    self.data
+                        .spectators
+                        .iter()
+                        .map(|(username, user)| (username.clone(), redact(user)))
+                        .collect(),
+                    self.data.waitlist.iter().map(redact).collect(),
+                )
+            }
+            Privacy::HideNonPlayers => (HashMap::new(), VecDeque::new()),
+        };
         GameView {
+            hand_id: self.data.hand_id,
             donations: self.data.donations,
             small_blind: self.data.small_blind,
             big_blind: self.data.big_blind,
-            spectators: self.data.spectators.clone(),
-            waitlist: self.data.waitlist.clone(),
+            spectators,
+            waitlist,
             open_seats: self.data.open_seats.clone(),
             players,
             board: self.data.board.clone(),
             pot: PotView {
                 size: self.data.pot.get_size(),
+                street_bets: self.data.pot.get_street_bets(),
             },
+            button_idx: self.data.button_idx,
             small_blind_idx: self.data.small_blind_idx,
             big_blind_idx: self.data.big_blind_idx,
             next_action_idx,
+            bomb_pot: self.data.bomb_pot,
+            variant: self.data.settings.variant,
+            privacy,
+            limit: self.data.settings.limit,
+            action_history: self.data.action_history[self
+                .data
+                .action_history
+                .len()
+                .saturating_sub(MAX_VIEWED_ACTIONS)..]
+                .to_vec(),
+            equities: self.data.equities.clone(),
         }
     }
 
+    /// Add chips to a stack, capping the stack at the max buy-in. The
+    /// stack has to reach the min buy-in. Returns the amount that was
+    /// actually added.
+    fn add_chips_to_stack(
+        money: &mut Usd,
+        amount: Usd,
+        settings: &GameSettings,
+    ) -> Result<Usd, UserError> {
+        let max_buy_in = settings.max_buy_in;
+        let amount = min(amount, max_buy_in.saturating_sub(*money));
+        if amount == 0 {
+            return Err(UserError::MaxBuyInExceeded { max_buy_in });
+        } else if *money + amount < settings.min_buy_in {
+            return Err(UserError::MinBuyInNotMet {
+                min_buy_in: settings.min_buy_in,
+            });
+        }
+        *money += amount;
+        Ok(amount)
+    }
+
     pub fn contains_player(&self, username: &str) -> bool {
         self.data.players.iter().any(|p| p.user.name == username)
     }
 
     fn contains_user(&self, username: &str) -> bool {
         self.data.spectators.contains_key(username)
+            || self.data.railbirds.contains(username)
             || self
                 .data
                 .waitlist
@@ -350,6 +963,10 @@ impl<T> Game<T> {
                 .any(|u| u.name == username)
     }
 
+    pub fn contains_railbird(&self, username: &str) -> bool {
+        self.data.railbirds.contains(username)
+    }
+
     pub fn contains_spectator(&self, username: &str) -> bool {
         self.data.spectators.contains_key(username)
     }
@@ -358,6 +975,19 @@ impl<T> Game<T> {
         self.data.waitlist.iter().any(|u| u.name == username)
     }
 
+    pub fn get_hand_id(&self) -> HandId {
+        self.data.hand_id
+    }
+
+    pub fn get_turn_id(&self) -> TurnId {
+        self.data.turn_id
+    }
+
+    /// Return game events that've occurred since the last drain.
+    pub fn drain_events(&mut self) -> VecDeque<GameEvent> {
+        mem::take(&mut self.data.events)
+    }
+
     /// Return the index of the player who has the next action, or
     /// nothing if no one has the next turn.
     fn get_next_action_idx(&self, new_phase: bool) -> Option<usize> {
@@ -389,9 +1019,26 @@ impl<T> Game<T> {
             Some(action_idx) => {
                 let mut action_options = HashSet::from([Action::Fold]);
                 let user = &self.data.players[action_idx].user;
-                let raise = self.data.pot.get_min_raise_by_player_idx(action_idx);
                 let call = self.data.pot.get_call_by_player_idx(action_idx);
-                if self.data.num_players_active > 1 || call >= user.money {
+                // Fixed-limit raises are exactly one bet, and there are
+                // only so many of them per street.
+                let limit = self.data.settings.limit;
+                let raise = match limit {
+                    Some(limit) => call + limit.get_bet_size(self.data.board.len()),
+                    None => self
+                        .data
+                        .pot
+                        .get_min_raise_by_player_idx(action_idx, self.data.big_blind),
+                };
+                let can_raise = self.data.num_players_active > 1
+                    && self.data.pot.is_open_to_raises(action_idx)
+                    && limit
+                        .is_none_or(|limit| self.data.pot.num_raises < limit.max_raises_per_street);
+                // Players that can't raise can still go all-in if it's
+                // only enough to call. Fixed-limit all-ins can't be more
+                // than a raise.
+                let can_go_all_in = can_raise && (limit.is_none() || user.money <= raise);
+                if can_go_all_in || call >= user.money {
                     action_options.insert(Action::AllIn);
                 }
                 if call > 0 && call < user.money {
@@ -399,7 +1046,7 @@ impl<T> Game<T> {
                 } else if call == 0 {
                     action_options.insert(Action::Check);
                 }
-                if self.data.num_players_active > 1 && user.money > raise {
+                if can_raise && user.money > raise {
                     action_options.insert(Action::Raise(raise));
                 }
                 Some(action_options)
@@ -457,25 +1104,117 @@ impl<T> Game<T> {
         unique_investments.len()
     }
 
+    /// Return the indices of players that are eligible to win the next
+    /// pot that'll be distributed. These are the players that haven't
+    /// folded and have the largest investment in the pot.
     fn get_num_users(&self) -> usize {
         self.data.spectators.len() + self.data.waitlist.len() + self.data.players.len()
     }
 
-    /// Return independent views of the game for each user. For non-players,
-    /// only the board is shown until the showdown. For players, only their
-    /// hand and the board is shown until the showdown.
-    pub fn get_views(&self) -> GameViews {
-        let mut views = HashMap::with_capacity(self.data.settings.max_users);
-        for username in self
+    fn get_user_mut(&mut self, username: &str) -> Option<&mut User> {
+        if let Some(user) = self.data.spectators.get_mut(username) {
+            Some(user)
+        } else {
+            self.data
+                .waitlist
+                .iter_mut()
+                .chain(self.data.players.iter_mut().map(|p| &mut p.user))
+                .find(|u| u.name == username)
+        }
+    }
+
+    /// Show the player's whole hand, or only the card at the given index,
+    /// to everyone. Showing a second card shows the whole hand.
+    fn reveal_cards(&mut self, username: &str, card_idx: Option<usize>) -> Result<(), UserError> {
+        let Some(player) = self
             .data
-            .spectators
-            .keys()
-            .chain(self.data.waitlist.iter().map(|u| &u.name))
-            .chain(self.data.players.iter().map(|p| &p.user.name))
-        {
-            views.insert(username.to_string(), self.as_view(username));
+            .players
+            .iter_mut()
+            .find(|p| p.user.name == username)
+        else {
+            return Err(UserError::UserNotPlaying);
+        };
+        if player.mucked {
+            return Err(UserError::UserMuckedHand);
+        } else if player.showing || (card_idx.is_some() && player.shown_card == card_idx) {
+            return Err(UserError::UserAlreadyShowingHand);
         }
-        views
+        match card_idx {
+            Some(card_idx) if card_idx >= player.cards.len() => {
+                return Err(UserError::InvalidCard {
+                    num_cards: player.cards.len(),
+                });
+            }
+            Some(card_idx) if player.shown_card.is_none() => player.shown_card = Some(card_idx),
+            _ => player.showing = true,
+        }
+        Ok(())
+    }
+
+    /// Return the net profit or loss of every user that's played during
+    /// the session, including users that've left. Stacks are counted as
+    /// they are, so money a player has in the pot counts against them
+    /// until the pot is distributed.
+    pub fn get_leaderboard(&self) -> Leaderboard {
+        let mut leaderboard: Leaderboard = self
+            .data
+            .ledger
+            .iter()
+            .map(|(username, entry)| {
+                let money = self
+                    .data
+                    .spectators
+                    .get(username)
+                    .or_else(|| self.data.waitlist.iter().find(|u| &u.name == username))
+                    .or_else(|| {
+                        self.data
+                            .players
+                            .iter()
+                            .map(|p| &p.user)
+                            .find(|u| &u.name == username)
+                    })
+                    .map_or(0, |u| u.money);
+                let net = (entry.cashed_out + money) as i64 - entry.bought_in as i64;
+                (username.clone(), net)
+            })
+            .collect();
+        leaderboard.sort_by(|(username1, net1), (username2, net2)| {
+            net2.cmp(net1).then_with(|| username1.cmp(username2))
+        });
+        leaderboard
+    }
+
+    /// Return views of the game for each user. For non-players, only the
+    /// board and cards players chose to show are shown until the showdown.
+    /// Players can also see their own hand.
+    pub fn get_views(&self) -> GameViews {
+        let hands = self
+            .data
+            .players
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| !p.showing && !p.cards.is_empty())
+            .map(|(player_idx, p)| (p.user.name.clone(), (player_idx, p.cards.clone())))
+            .collect();
+        GameViews::new(
+            self.as_public_view(),
+            hands,
+            self.data.spectators.clone(),
+            self.data.waitlist.clone(),
+            self.data.railbirds.iter().cloned().collect(),
+        )
+    }
+
+    /// Return whether more than one player is still in the hand. Hands
+    /// that aren't contested are decided, so there's nothing left to
+    /// follow along with.
+    fn is_contested(&self) -> bool {
+        self.data
+            .players
+            .iter()
+            .filter(|p| p.state != PlayerState::Fold)
+            .count()
+            > 1
     }
 
     /// Return whether the game is ready to move onto the next phase
@@ -484,6 +1223,19 @@ impl<T> Game<T> {
         self.data.num_players_active == self.data.num_players_called
     }
 
+    /// Return whether the user is a player whose connection dropped and
+    /// who's being acted for until they reconnect or their grace period
+    /// runs out.
+    pub fn is_disconnected(&self, username: &str) -> bool {
+        self.data.disconnected_players.contains_key(username)
+    }
+
+    /// Stop new hands from being started. The hand in progress, if there
+    /// is one, is still played out.
+    pub fn end_session(&mut self) {
+        self.data.session_ending = true;
+    }
+
     pub fn is_pot_empty(&self) -> bool {
         self.data.pot.is_empty()
     }
@@ -531,17 +1283,24 @@ impl<T> Game<T> {
             // Check if player already exists but is queued for removal.
             // This probably means the user disconnected and is trying
             // to reconnect.
-            if !self.data.players_to_remove.remove(username) {
+            self.data.players_to_rail.remove(username);
+            if !self.data.players_to_remove.remove(username)
+                && self.data.disconnected_players.remove(username).is_none()
+            {
                 return Err(UserError::UserAlreadyExists);
             } else {
                 return Ok(false);
             }
         }
+        // Users that rejoin get their profile back.
+        let entry = self.data.ledger.entry(username.to_string()).or_default();
+        entry.bought_in += self.data.settings.starting_stack;
         self.data.spectators.insert(
             username.to_string(),
             User {
                 name: username.to_string(),
-                money: self.data.settings.buy_in,
+                money: self.data.settings.starting_stack,
+                profile: entry.profile.clone(),
             },
         );
         Ok(true)
@@ -552,6 +1311,7 @@ impl<T> Game<T> {
     /// in preparation for a new round of betting.
     fn prepare_for_next_phase(&mut self) -> Option<HashSet<Action>> {
         self.data.num_players_called = 0;
+        self.data.pot.start_betting_round();
         // Reset player states for players that are still in the hand.
         for player in self.data.players.iter_mut().filter(|player| {
             matches!(
@@ -561,14 +1321,317 @@ impl<T> Game<T> {
         }) {
             player.state = PlayerState::Wait
         }
+        // Bets from the last street are in the pot now, but the blinds
+        // are still the bets to beat when preflop betting starts.
+        if !self.data.board.is_empty() {
+            self.data.pot.start_street();
+            for player in self.data.players.iter_mut() {
+                player.last_bet = None;
+            }
+        }
+        self.fold_players_to_remove();
         self.data.next_action_idx = Some(self.data.starting_action_idx);
         self.data.next_action_idx = self.get_next_action_idx(true);
+        self.data.turn_id += 1;
         self.get_next_action_options()
     }
 
-    fn redistribute_user_money(&mut self, money: &mut Usd) {
-        self.data.donations += (*money as Usdf) - (self.data.settings.buy_in as Usdf);
-        *money = 0;
+    /// Fold players that're queued for removal but are still in the hand
+    /// so betting doesn't wait on them. All-in players keep their claim
+    /// on the pot, and the last player in the hand is never folded.
+    /// Returns whether any players were folded.
+    fn fold_players_to_remove(&mut self) -> bool {
+        let street = Street::from_num_community_cards(self.data.board.len());
+        let mut folded = false;
+        for player_idx in 0..self.data.players.len() {
+            if !self.is_contested() {
+                break;
+            }
+            let player = &mut self.data.players[player_idx];
+            if !self.data.players_to_remove.contains(&player.user.name) {
+                continue;
+            }
+            match player.state {
+                PlayerState::Wait => {}
+                // These players have already matched the call.
+                PlayerState::Call | PlayerState::Check | PlayerState::Raise => {
+                    self.data.num_players_called -= 1;
+                }
+                PlayerState::AllIn | PlayerState::Fold => continue,
+            }
+            self.data.num_players_active -= 1;
+            player.state = PlayerState::Fold;
+            self.data.action_history.push(ActionRecord {
+                username: player.user.name.clone(),
+                action: Action::Fold,
+                street,
+            });
+            folded = true;
+        }
+        folded
+    }
+
+    /// Change a user's username wherever they are in the game, keeping
+    /// their stack, seat, and any queued changes. Users can only rename
+    /// themselves once per hand.
+    pub fn rename_user(&mut self, username: &str, new_username: &str) -> Result<(), UserError> {
+        if new_username.is_empty()
+            || new_username.chars().count() > MAX_USER_INPUT_LENGTH
+            || new_username.chars().any(char::is_whitespace)
+        {
+            return Err(UserError::InvalidUsername {
+                max_length: MAX_USER_INPUT_LENGTH,
+            });
+        } else if self.contains_user(new_username) {
+            return Err(UserError::UserAlreadyExists);
+        } else if self.data.ledger.contains_key(new_username) {
+            return Err(UserError::UsernameTaken);
+        } else if self.data.renamed_users.get(username) == Some(&self.data.hand_id) {
+            return Err(UserError::RenameLimitReached);
+        }
+
+        if let Some(mut user) = self.data.spectators.remove(username) {
+            user.name = new_username.to_string();
+            self.data.spectators.insert(new_username.to_string(), user);
+        } else if self.data.railbirds.remove(username) {
+            self.data.railbirds.insert(new_username.to_string());
+        } else if let Some(user) = self
+            .data
+            .waitlist
+            .iter_mut()
+            .chain(self.data.players.iter_mut().map(|p| &mut p.user))
+            .find(|u| u.name == username)
+        {
+            user.name = new_username.to_string();
+        } else {
+            return Err(UserError::UserDoesNotExist);
+        }
+
+        // Move the user's queued changes over to their new username.
+        for queue in [
+            &mut self.data.players_to_remove,
+            &mut self.data.players_to_rail,
+            &mut self.data.players_to_spectate,
+        ] {
+            if queue.remove(username) {
+                queue.insert(new_username.to_string());
+            }
+        }
+        if let Some(amount) = self.data.players_to_top_up.remove(username) {
+            self.data
+                .players_to_top_up
+                .insert(new_username.to_string(), amount);
+        }
+        if let Some(seat_idx) = self.data.players_to_change_seat.remove(username) {
+            self.data
+                .players_to_change_seat
+                .insert(new_username.to_string(), seat_idx);
+        }
+        // The ledger is tracked by username, which is why users can't
+        // take the name of someone that left and their results with it.
+        if let Some(entry) = self.data.ledger.remove(username) {
+            self.data.ledger.insert(new_username.to_string(), entry);
+        }
+        if let Some(grace_hands) = self.data.disconnected_players.remove(username) {
+            self.data
+                .disconnected_players
+                .insert(new_username.to_string(), grace_hands);
+        }
+        if self.data.auto_rebuy_users.remove(username) {
+            self.data.auto_rebuy_users.insert(new_username.to_string());
+        }
+        for insurance in self
+            .data
+            .insurance_offers
+            .iter_mut()
+            .chain(self.data.insurance.iter_mut())
+        {
+            for name in [&mut insurance.insurer, &mut insurance.insured] {
+                if name == username {
+                    *name = new_username.to_string();
+                }
+            }
+        }
+        for bet in self.data.spectator_pool.bets.iter_mut() {
+            for name in [&mut bet.spectator, &mut bet.player] {
+                if name == username {
+                    *name = new_username.to_string();
+                }
+            }
+        }
+
+        self.data.renamed_users.remove(username);
+        self.data
+            .renamed_users
+            .insert(new_username.to_string(), self.data.hand_id);
+        self.data.events.push_back(GameEvent::Renamed(
+            username.to_string(),
+            new_username.to_string(),
+        ));
+        Ok(())
+    }
+
+    /// Opt a user in or out of rebuying when they can't afford the big
+    /// blind, if the game allows it.
+    pub fn set_auto_rebuy(&mut self, username: &str, enabled: bool) -> Result<(), UserError> {
+        if !self.data.settings.auto_rebuy {
+            return Err(UserError::AutoRebuyDisabled);
+        } else if !self.contains_user(username) {
+            return Err(UserError::UserDoesNotExist);
+        }
+        if enabled {
+            self.data.auto_rebuy_users.insert(username.to_string());
+        } else {
+            self.data.auto_rebuy_users.remove(username);
+        }
+        Ok(())
+    }
+
+    /// Change how a user presents themselves. Avatars must be a single
+    /// character (though that character can be made of several code
+    /// points, like a flag), and neither part can have control
+    /// characters. Surrounding whitespace is trimmed, and empty parts
+    /// are cleared.
+    pub fn set_profile(&mut self, username: &str, profile: Profile) -> Result<(), UserError> {
+        let trim = |part: Option<String>| {
+            part.map(|part| part.trim().to_string())
+                .filter(|part| !part.is_empty())
+        };
+        let profile = Profile {
+            avatar: trim(profile.avatar),
+            tagline: trim(profile.tagline),
+        };
+        let has_control = |part: &Option<String>| {
+            part.as_ref()
+                .is_some_and(|part| part.chars().any(char::is_control))
+        };
+        if has_control(&profile.avatar)
+            || has_control(&profile.tagline)
+            || profile
+                .avatar
+                .as_ref()
+                .is_some_and(|avatar| avatar.graphemes(true).count() != 1)
+            || profile
+                .tagline
+                .as_ref()
+                .is_some_and(|tagline| tagline.chars().count() > MAX_TAGLINE_LENGTH)
+        {
+            return Err(UserError::InvalidProfile {
+                max_length: MAX_TAGLINE_LENGTH,
+            });
+        }
+
+        if let Some(user) = self.data.spectators.get_mut(username) {
+            user.profile = profile.clone();
+        } else if let Some(user) = self
+            .data
+            .players
+            .iter_mut()
+            .map(|player| &mut player.user)
+            .chain(self.data.waitlist.iter_mut())
+            .find(|user| user.name == username)
+        {
+            user.profile = profile.clone();
+        } else if !self.data.railbirds.contains(username) {
+            // Railbirds' profiles are only kept in the ledger.
+            return Err(UserError::UserDoesNotExist);
+        }
+        self.data
+            .ledger
+            .entry(username.to_string())
+            .or_default()
+            .profile = profile;
+        Ok(())
+    }
+
+    /// Return whether a player other than the given user has queued a
+    /// move to the seat.
+    fn is_seat_claimed(&self, username: &str, seat_idx: SeatIndex) -> bool {
+        self.data
+            .players_to_change_seat
+            .iter()
+            .any(|(other, other_seat_idx)| other != username && *other_seat_idx == seat_idx)
+    }
+
+    /// Move a player to an open seat, keeping players sorted by seat.
+    fn move_player(&mut self, player_idx: usize, seat_idx: SeatIndex) {
+        let player = &mut self.data.players[player_idx];
+        let old_seat_idx = player.seat_idx;
+        player.seat_idx = seat_idx;
+        let username = player.user.name.clone();
+        self.data
+            .open_seats
+            .retain(|open_seat_idx| *open_seat_idx != seat_idx);
+        self.data.open_seats.push_back(old_seat_idx);
+        self.data.players.sort_by_key(|player| player.seat_idx);
+        self.data
+            .events
+            .push_back(GameEvent::ChangedSeat(username, old_seat_idx, seat_idx));
+    }
+
+    /// Donate a leaving user's money, recording what they left with.
+    /// Their preferences are forgotten with them.
+    fn redistribute_user_money(&mut self, user: &mut User) {
+        self.data.auto_rebuy_users.remove(&user.name);
+        self.data
+            .ledger
+            .entry(user.name.clone())
+            .or_default()
+            .cashed_out += user.money;
+        self.data.donations += (user.money as Usdf) - (self.data.settings.starting_stack as Usdf);
+        user.money = 0;
+    }
+
+    /// Move a user to the rail, cashing out their stack to the ledger
+    /// where it's held until they rejoin. Their preferences are forgotten
+    /// like they left.
+    fn rail(&mut self, user: User) {
+        self.data.auto_rebuy_users.remove(&user.name);
+        let entry = self.data.ledger.entry(user.name.clone()).or_default();
+        entry.cashed_out += user.money;
+        entry.held += user.money;
+        self.data.railbirds.insert(user.name);
+    }
+
+    /// Bring a railbird back as a spectator with the stack that was held
+    /// for them, like they bought back in with it.
+    pub fn rejoin_user(&mut self, username: &str) -> Result<bool, UserError> {
+        if !self.data.railbirds.contains(username) {
+            return Err(UserError::UserNotRailbird);
+        } else if self.get_num_users() == self.data.settings.max_users {
+            return Err(UserError::CapacityReached);
+        }
+        self.data.railbirds.remove(username);
+        let entry = self.data.ledger.entry(username.to_string()).or_default();
+        let money = mem::take(&mut entry.held);
+        entry.bought_in += money;
+        self.data.spectators.insert(
+            username.to_string(),
+            User {
+                name: username.to_string(),
+                money,
+                profile: entry.profile.clone(),
+            },
+        );
+        Ok(true)
+    }
+
+    /// Remove a railbird, donating the stack that was held for them like
+    /// they left from their seat. Returns whether the user was a railbird.
+    fn remove_railbird(&mut self, username: &str) -> bool {
+        if !self.data.railbirds.remove(username) {
+            return false;
+        }
+        let held = mem::take(
+            &mut self
+                .data
+                .ledger
+                .entry(username.to_string())
+                .or_default()
+                .held,
+        );
+        self.data.donations += (held as Usdf) - (self.data.settings.starting_stack as Usdf);
+        true
     }
 
     /// Add a user to the waitlist, putting them in queue to play. The queue
@@ -580,6 +1643,7 @@ impl<T> Game<T> {
         // want to play again.
         self.data.players_to_spectate.remove(username);
         self.data.players_to_remove.remove(username);
+        self.data.players_to_rail.remove(username);
         if let Some(user) = self.data.spectators.remove(username) {
             if user.money < self.data.big_blind {
                 self.data.spectators.insert(username.to_string(), user);
@@ -606,20 +1670,83 @@ impl<T> Game<T> {
 macro_rules! impl_user_managers {
     ($($t:ty),+) => {
         $(impl $t {
+            pub fn add_chips(&mut self, username: &str, amount: Usd) -> Result<bool, UserError> {
+                let user = if let Some(user) = self.data.spectators.get_mut(username) {
+                    user
+                } else if let Some(user) = self.data.waitlist.iter_mut().find(|u| u.name == username) {
+                    user
+                } else if let Some(player) = self.data.players.iter_mut().find(|p| p.user.name == username) {
+                    &mut player.user
+                } else {
+                    return Err(UserError::UserDoesNotExist);
+                };
+                let amount = Self::add_chips_to_stack(&mut user.money, amount, &self.data.settings)?;
+                self.data.ledger.entry(username.to_string()).or_default().bought_in += amount;
+                self.data.events.push_back(GameEvent::AddedChips(username.to_string(), amount));
+                Ok(true)
+            }
+
+            pub fn change_seat(&mut self, username: &str, seat_idx: SeatIndex) -> Result<bool, UserError> {
+                let Some(player_idx) = self.data.players.iter().position(|p| p.user.name == username) else {
+                    return Err(UserError::UserNotPlaying);
+                };
+                if !self.data.open_seats.contains(&seat_idx) || self.is_seat_claimed(username, seat_idx) {
+                    return Err(UserError::SeatUnavailable { seat_idx });
+                }
+                self.data.players_to_change_seat.remove(username);
+                self.move_player(player_idx, seat_idx);
+                Ok(true)
+            }
+
+            /// There's no hand to finish, so disconnected users are
+            /// removed right away.
+            pub fn disconnect_user(&mut self, username: &str) -> Result<bool, UserError> {
+                self.remove_user(username)
+            }
+
+            /// Move a user to the rail, giving up their seat and stack
+            /// but still following the game.
+            pub fn rail_user(&mut self, username: &str) -> Result<bool, UserError> {
+                if self.data.railbirds.contains(username) {
+                    return Ok(true);
+                } else if self.data.railbirds.len() == self.data.settings.max_railbirds {
+                    return Err(UserError::CapacityReached);
+                }
+                let user = if let Some(user) = self.data.spectators.remove(username) {
+                    user
+                } else if let Some(waitlist_idx) = self.data.waitlist.iter().position(|u| u.name == username) {
+                    self.data.waitlist.remove(waitlist_idx).expect("waitlister exists")
+                } else if let Some(player_idx) = self.data.players.iter().position(|p| p.user.name == username) {
+                    self.data.players_to_spectate.remove(username);
+                    self.data.disconnected_players.remove(username);
+                    let player = self.data.players.remove(player_idx);
+                    self.data.open_seats.push_back(player.seat_idx);
+                    player.user
+                } else {
+                    return Err(UserError::UserDoesNotExist);
+                };
+                self.rail(user);
+                Ok(true)
+            }
+
             pub fn remove_user(&mut self, username: &str) -> Result<bool, UserError> {
+                if self.remove_railbird(username) {
+                    return Ok(true);
+                }
                 let mut user = if let Some(user) = self.data.spectators.remove(username) {
                     user
                 } else if let Some(waitlist_idx) = self.data.waitlist.iter().position(|u| u.name == username) {
                     self.data.waitlist.remove(waitlist_idx).expect("waitlister exists")
                 } else if let Some(player_idx) = self.data.players.iter().position(|p| p.user.name == username) {
                     self.data.players_to_spectate.remove(username);
+                    self.data.disconnected_players.remove(username);
                     let player = self.data.players.remove(player_idx);
                     self.data.open_seats.push_back(player.seat_idx);
                     player.user
                 } else {
                     return Err(UserError::UserDoesNotExist);
                 };
-                self.redistribute_user_money(&mut user.money);
+                self.redistribute_user_money(&mut user);
                 Ok(true)
             }
 
@@ -635,6 +1762,8 @@ macro_rules! impl_user_managers {
                     self.data.waitlist.remove(waitlist_idx).expect("waitlister exists")
                 } else if let Some(player_idx) = self.data.players.iter().position(|p| p.user.name == username) {
                     self.data.players_to_remove.remove(username);
+                    self.data.players_to_rail.remove(username);
+                    self.data.disconnected_players.remove(username);
                     let player = self.data.players.remove(player_idx);
                     self.data.open_seats.push_back(player.seat_idx);
                     player.user
@@ -651,11 +1780,105 @@ macro_rules! impl_user_managers {
 macro_rules! impl_user_managers_with_queue {
     ($($t:ty),+) => {
         $(impl $t {
-            pub fn remove_user(&mut self, username: &str) -> Result<bool, UserError> {
-                // The player has already been queued for removal. Just wait for
-                // the next removal phase.
-                if self.data.players_to_remove.contains(username) {
+            pub fn add_chips(&mut self, username: &str, amount: Usd) -> Result<bool, UserError> {
+                let user = if let Some(user) = self.data.spectators.get_mut(username) {
+                    user
+                } else if let Some(user) = self.data.waitlist.iter_mut().find(|u| u.name == username) {
+                    user
+                } else if let Some(player) = self.data.players.iter().find(|p| p.user.name == username) {
+                    // Make sure the player can actually add chips so they
+                    // get immediate feedback, but don't change their stack
+                    // until the hand is over.
+                    let mut money = player.user.money;
+                    Self::add_chips_to_stack(&mut money, amount, &self.data.settings)?;
+                    self.data.players_to_top_up.insert(username.to_string(), amount);
+                    return Ok(false);
+                } else {
+                    return Err(UserError::UserDoesNotExist);
+                };
+                let amount = Self::add_chips_to_stack(&mut user.money, amount, &self.data.settings)?;
+                self.data.ledger.entry(username.to_string()).or_default().bought_in += amount;
+                self.data.events.push_back(GameEvent::AddedChips(username.to_string(), amount));
+                Ok(true)
+            }
+
+            /// Check that the seat will still be open after the hand and
+            /// queue the player to move to it. Seats of players that're
+            /// leaving are considered open.
+            pub fn change_seat(&mut self, username: &str, seat_idx: SeatIndex) -> Result<bool, UserError> {
+                if !self.contains_player(username) {
+                    return Err(UserError::UserNotPlaying);
+                }
+                let is_open = self.data.open_seats.contains(&seat_idx)
+                    || self.data.players.iter().any(|p| {
+                        p.seat_idx == seat_idx
+                            && (self.data.players_to_remove.contains(&p.user.name)
+                                || self.data.players_to_spectate.contains(&p.user.name))
+                    });
+                if !is_open || self.is_seat_claimed(username, seat_idx) {
+                    return Err(UserError::SeatUnavailable { seat_idx });
+                }
+                self.data.players_to_change_seat.insert(username.to_string(), seat_idx);
+                Ok(false)
+            }
+
+            /// Keep a disconnected player seated for the grace period set in
+            /// the game settings, acting for them on their turns, rather than
+            /// queueing them for removal right away.
+            pub fn disconnect_user(&mut self, username: &str) -> Result<bool, UserError> {
+                let grace_hands = self.data.settings.disconnect_grace_hands;
+                if grace_hands == 0
+                    || !self.contains_player(username)
+                    || self.data.players_to_remove.contains(username)
+                {
+                    return self.remove_user(username);
+                }
+                self.data.players_to_spectate.remove(username);
+                self.data
+                    .disconnected_players
+                    .entry(username.to_string())
+                    .or_insert(grace_hands);
+                Ok(false)
+            }
+
+            /// Move a user to the rail, giving up their seat and stack
+            /// but still following the game. Players are folded and
+            /// moved once the hand is over, like they're leaving.
+            pub fn rail_user(&mut self, username: &str) -> Result<bool, UserError> {
+                if self.data.players_to_rail.contains(username) {
+                    return Ok(false);
+                } else if self.data.railbirds.contains(username) {
+                    return Ok(true);
+                } else if self.data.railbirds.len() + self.data.players_to_rail.len()
+                    == self.data.settings.max_railbirds
+                {
+                    return Err(UserError::CapacityReached);
+                }
+                let user = if let Some(user) = self.data.spectators.remove(username) {
+                    user
+                } else if let Some(waitlist_idx) = self.data.waitlist.iter().position(|u| u.name == username) {
+                    self.data.waitlist.remove(waitlist_idx).expect("waitlister exists")
+                } else if self.contains_player(username) {
+                    self.data.players_to_spectate.remove(username);
+                    self.data.players_to_remove.insert(username.to_string());
+                    self.data.players_to_rail.insert(username.to_string());
+                    return Ok(false);
+                } else {
+                    return Err(UserError::UserDoesNotExist);
+                };
+                self.rail(user);
+                Ok(true)
+            }
+
+            pub fn remove_user(&mut self, username: &str) -> Result<bool, UserError> {
+                // The player has already been queued for removal. Just wait for
+                // the next removal phase, but they're leaving for good if they
+                // were only moving to the rail.
+                if self.data.players_to_remove.contains(username) {
+                    self.data.players_to_rail.remove(username);
                     return Ok(false);
+                } else if self.remove_railbird(username) {
+                    return Ok(true);
                 }
                 let mut user = if let Some(user) = self.data.spectators.remove(username) {
                     user
@@ -667,13 +1890,15 @@ macro_rules! impl_user_managers_with_queue {
                     self.data.players_to_spectate.remove(username);
                     // The player is still at the table while the game is ongoing.
                     // We don't want to disrupt gameplay, so we just queue the
-                    // player for removal and remove them later.
+                    // player for removal and remove them later. This also means
+                    // all-in players keep their claim on the pot until it's
+                    // distributed.
                     self.data.players_to_remove.insert(username.to_string());
                     return Ok(false);
                 } else {
                     return Err(UserError::UserDoesNotExist);
                 };
-                self.redistribute_user_money(&mut user.money);
+                self.redistribute_user_money(&mut user);
                 Ok(true)
             }
 
@@ -691,6 +1916,7 @@ macro_rules! impl_user_managers_with_queue {
                     // Need to remove the player from other queues just in
                     // case they changed their mind.
                     self.data.players_to_remove.remove(username);
+                    self.data.players_to_rail.remove(username);
                     self.data.players_to_spectate.insert(username.to_string());
                     return Ok(false);
                 } else {
@@ -725,20 +1951,89 @@ impl_user_managers_with_queue!(
 );
 
 impl Game<Lobby> {
+    /// Start the game on the next step. Starting more than once before
+    /// then is fine, but a start only lasts until the next step, so it
+    /// can't fire later on if players leave before the game can start.
     pub fn init_start(&mut self) -> Result<(), UserError> {
-        match (self.state.start_game, self.get_num_potential_players() >= 2) {
-            (false, false) => Err(UserError::NotEnoughPlayers),
-            (false, true) => {
-                self.state.start_game = true;
-                Ok(())
-            }
-            (true, _) => Err(UserError::GameAlreadyStarting),
+        if self.data.session_ending {
+            self.state.start_game = false;
+            return Err(UserError::SessionEnding);
+        }
+        if self.get_num_potential_players() < 2 {
+            self.state.start_game = false;
+            return Err(UserError::NotEnoughPlayers);
         }
+        self.state.start_game = true;
+        Ok(())
+    }
+
+    /// Return whether the game's autostart policy would start the game,
+    /// ignoring any delay the server puts in front of it.
+    pub fn is_ready_to_autostart(&self) -> bool {
+        if self.data.session_ending {
+            return false;
+        }
+        let min_players = match self.data.settings.autostart {
+            Autostart::Off => return false,
+            Autostart::WhenPlayers(min_players) => max(min_players, MIN_PLAYERS),
+            Autostart::Always => MIN_PLAYERS,
+        };
+        self.get_num_potential_players() >= min_players
     }
 
     pub fn is_ready_to_start(&self) -> bool {
         self.state.start_game && self.get_num_potential_players() >= 2
     }
+
+    /// Switch the game to a different format between hands. Players
+    /// seated beyond the new table size are moved to the front of the
+    /// waitlist. Stacks carry over unless they're reset, in which case
+    /// every user cashes out what they have and buys back in with the
+    /// new starting stack.
+    pub fn change_format(&mut self, name: &str, settings: GameSettings, reset_stacks: bool) {
+        let max_players = settings.max_players;
+        let (seated, unseated): (Vec<Player>, Vec<Player>) = mem::take(&mut self.data.players)
+            .into_iter()
+            .partition(|player| player.seat_idx < max_players);
+        self.data.players = seated;
+        for player in unseated.into_iter().rev() {
+            self.data.waitlist.push_front(player.user);
+        }
+        self.data.open_seats = (0..max_players)
+            .filter(|seat_idx| {
+                !self
+                    .data
+                    .players
+                    .iter()
+                    .any(|player| player.seat_idx == *seat_idx)
+            })
+            .collect();
+        if reset_stacks {
+            let users = self
+                .data
+                .spectators
+                .values_mut()
+                .chain(self.data.waitlist.iter_mut())
+                .chain(self.data.players.iter_mut().map(|player| &mut player.user));
+            for user in users {
+                let entry = self.data.ledger.entry(user.name.clone()).or_default();
+                entry.cashed_out += user.money;
+                entry.bought_in += settings.starting_stack;
+                user.money = settings.starting_stack;
+            }
+        }
+        self.data.deck = new_deck(settings.variant);
+        if let Some(seed) = settings.seed {
+            self.data.rng = StdRng::seed_from_u64(seed);
+        }
+        self.data.small_blind = settings.min_small_blind;
+        self.data.big_blind = settings.min_big_blind;
+        self.data.pot = Pot::new(max_players);
+        self.data.settings = settings;
+        self.data
+            .events
+            .push_back(GameEvent::FormatChanged(name.to_string()));
+    }
 }
 
 impl From<GameSettings> for Game<Lobby> {
@@ -752,7 +2047,8 @@ impl From<GameSettings> for Game<Lobby> {
 }
 
 impl From<Game<Lobby>> for Game<SeatPlayers> {
-    fn from(value: Game<Lobby>) -> Self {
+    fn from(mut value: Game<Lobby>) -> Self {
+        value.data.action_history.clear();
         Self {
             data: value.data,
             state: SeatPlayers {},
@@ -771,6 +2067,16 @@ impl From<Game<SeatPlayers>> for Game<Lobby> {
 
 impl From<Game<SeatPlayers>> for Game<MoveButton> {
     fn from(mut value: Game<SeatPlayers>) -> Self {
+        // Handing out open seats in a random order is the same as drawing
+        // for them.
+        let draw = value.data.settings.random_first_seating && value.get_num_players() == 0;
+        if draw {
+            value
+                .data
+                .open_seats
+                .make_contiguous()
+                .shuffle(&mut value.data.rng);
+        }
         while !value.data.open_seats.is_empty() && !value.data.waitlist.is_empty() {
             let open_seat_idx = value.data.open_seats.pop_front().expect("not empty");
             let user = value.data.waitlist.pop_front().expect("not empty");
@@ -792,7 +2098,36 @@ impl From<Game<SeatPlayers>> for Game<MoveButton> {
                 }
             }
         }
-        value.data.num_players_active = value.get_num_players();
+        let num_players = value.get_num_players();
+        if draw && num_players > 0 {
+            for player in value.data.players.iter() {
+                value.data.events.push_back(GameEvent::SeatDrawn(
+                    player.user.name.clone(),
+                    player.seat_idx,
+                ));
+            }
+            // The button goes to the first player seated after the last
+            // button, so the last button is put right before the drawn
+            // one.
+            let button_idx = value.data.rng.gen_range(0..num_players);
+            let last_button_idx = (button_idx + num_players - 1) % num_players;
+            value.data.button_seat_idx = Some(value.data.players[last_button_idx].seat_idx);
+            let username = value.data.players[button_idx].user.name.clone();
+            value
+                .data
+                .events
+                .push_back(GameEvent::ButtonDrawn(username));
+        }
+        value.data.num_players_active = num_players;
+        value.data.hand_id += 1;
+        // Any renames left were made between hands, which count as this
+        // hand.
+        for renamed_hand_id in value.data.renamed_users.values_mut() {
+            *renamed_hand_id = value.data.hand_id;
+        }
+        let bomb_pot_frequency = value.data.settings.bomb_pot_frequency as HandId;
+        value.data.bomb_pot =
+            bomb_pot_frequency > 0 && value.data.hand_id.is_multiple_of(bomb_pot_frequency);
         Self {
             data: value.data,
             state: MoveButton {},
@@ -800,34 +2135,43 @@ impl From<Game<SeatPlayers>> for Game<MoveButton> {
     }
 }
 
-/// Move the blinds and next action indices, preparing the next game
-/// by determining who will be paying blinds and who will be making
-/// the first action.
+/// Move the button to the next occupied seat and derive the blinds and
+/// next action indices from it, preparing the next game by determining
+/// who will be paying blinds and who will be making the first action.
 impl From<Game<MoveButton>> for Game<CollectBlinds> {
     fn from(mut value: Game<MoveButton>) -> Self {
         let num_players = value.get_num_players();
-        let player_indices = value
+        // Players are sorted by seat, so the button goes to the first
+        // player seated after last hand's button, wrapping around the
+        // table. Seats that opened up since then are skipped.
+        let button_idx = value
             .data
-            .players
-            .iter()
-            .enumerate()
-            .map(|(player_idx, _)| player_idx);
-        // Search for the big blind and starting positions.
-        let mut seats = player_indices
-            .clone()
-            .cycle()
-            .skip(value.data.big_blind_idx + 1);
-        value.data.big_blind_idx = seats.next().expect("big blind position exists");
-        value.data.starting_action_idx = seats.next().expect("starting action position exists");
+            .button_seat_idx
+            .and_then(|button_seat_idx| {
+                value
+                    .data
+                    .players
+                    .iter()
+                    .position(|player| player.seat_idx > button_seat_idx)
+            })
+            .unwrap_or(0);
+        value.data.button_idx = button_idx;
+        value.data.button_seat_idx = Some(value.data.players[button_idx].seat_idx);
+        // The small blind follows the button, except when there are only
+        // two players, in which case the button is the small blind.
+        value.data.small_blind_idx = if num_players > 2 {
+            (button_idx + 1) % num_players
+        } else {
+            button_idx
+        };
+        value.data.big_blind_idx = (value.data.small_blind_idx + 1) % num_players;
+        value.data.starting_action_idx = (value.data.big_blind_idx + 1) % num_players;
+        // Bomb pots start at the flop, so action starts left of the
+        // button like it does for every other post-flop round.
+        if value.data.bomb_pot {
+            value.data.starting_action_idx = (button_idx + 1) % num_players;
+        }
         value.data.next_action_idx = Some(value.data.starting_action_idx);
-        // Reverse the table search to find the small blind position relative
-        // to the big blind position since the small blind must always trail the big
-        // blind.
-        let mut seats = player_indices
-            .rev()
-            .cycle()
-            .skip(num_players - value.data.big_blind_idx);
-        value.data.small_blind_idx = seats.next().expect("small blind position exists");
         Self {
             data: value.data,
             state: CollectBlinds {},
@@ -835,10 +2179,52 @@ impl From<Game<MoveButton>> for Game<CollectBlinds> {
     }
 }
 
-/// Collect blinds, initializing the main pot.
+impl Game<CollectBlinds> {
+    /// Collect the same ante from every player for a bomb pot. Players
+    /// that can't cover the ante go all-in.
+    fn collect_antes(&mut self) {
+        let ante = self.get_bomb_pot_ante();
+        for (player_idx, player) in self.data.players.iter_mut().enumerate() {
+            let bet = if player.user.money <= ante {
+                player.state = PlayerState::AllIn;
+                self.data.num_players_active -= 1;
+                self.data.events.push_back(GameEvent::AllIn(
+                    player.user.name.clone(),
+                    player.user.money,
+                ));
+                Bet {
+                    action: BetAction::AllIn,
+                    amount: player.user.money,
+                }
+            } else {
+                player.state = PlayerState::Wait;
+                Bet {
+                    action: BetAction::Call,
+                    amount: ante,
+                }
+            };
+            self.data.pot.bet(player_idx, &bet);
+            player.user.money -= bet.amount;
+        }
+    }
+
+    fn get_bomb_pot_ante(&self) -> Usd {
+        self.data.settings.bomb_pot_ante * self.data.big_blind
+    }
+}
+
+/// Collect blinds (or antes for a bomb pot), initializing the main pot.
 impl From<Game<CollectBlinds>> for Game<Deal> {
     fn from(mut value: Game<CollectBlinds>) -> Self {
         value.data.pot = Pot::new(value.data.settings.max_players);
+        if value.data.bomb_pot {
+            value.collect_antes();
+            value.data.num_players_called = 0;
+            return Self {
+                data: value.data,
+                state: Deal {},
+            };
+        }
         for (player_idx, blind) in [
             (value.data.small_blind_idx, value.data.small_blind),
             (value.data.big_blind_idx, value.data.big_blind),
@@ -848,6 +2234,10 @@ impl From<Game<CollectBlinds>> for Game<Deal> {
                 Ordering::Equal => {
                     player.state = PlayerState::AllIn;
                     value.data.num_players_active -= 1;
+                    value.data.events.push_back(GameEvent::AllIn(
+                        player.user.name.clone(),
+                        player.user.money,
+                    ));
                     Bet {
                         action: BetAction::AllIn,
                         amount: player.user.money,
@@ -856,7 +2246,7 @@ impl From<Game<CollectBlinds>> for Game<Deal> {
                 Ordering::Greater => {
                     player.state = PlayerState::Wait;
                     Bet {
-                        action: BetAction::Raise,
+                        action: BetAction::Blind,
                         amount: blind,
                     }
                 }
@@ -867,8 +2257,12 @@ impl From<Game<CollectBlinds>> for Game<Deal> {
             // Impossible for a side pot to be created from the blinds, so
             // we don't even need to check.
             value.data.pot.bet(player_idx, &bet);
+            player.record_bet(&bet);
             player.user.money -= blind;
         }
+        // Posting a blind isn't acting, so the round can't end until
+        // the big blind has had their option to check or raise, even if
+        // everyone else just calls.
         value.data.num_players_called = 0;
         Self {
             data: value.data,
@@ -877,23 +2271,43 @@ impl From<Game<CollectBlinds>> for Game<Deal> {
     }
 }
 
-/// Shuffle the game's deck and deal 2 cards to each player.
-impl From<Game<Deal>> for Game<TakeAction> {
-    fn from(mut value: Game<Deal>) -> Self {
-        value.data.deck.shuffle(&mut thread_rng());
-        value.data.deck_idx = 0;
-
-        let num_players = value.get_num_players();
-        let mut seats = (0..num_players).cycle().skip(value.data.small_blind_idx);
-        // Deal 2 cards per player, looping over players and dealing them 1 card
-        // at a time.
-        while value.data.deck_idx < (2 * num_players) {
+impl Game<Deal> {
+    fn step(&mut self) {
+        let variant = self.data.settings.variant;
+        if self.data.deck.len() != variant.deck_size() {
+            self.data.deck = new_deck(variant);
+        }
+        self.data.deck.shuffle(&mut self.data.rng);
+        self.data.deck_idx = 0;
+        self.data.burned.clear();
+        let nonce: Nonce = self.data.rng.gen();
+        self.data.deck_nonce = Some(nonce);
+        self.data
+            .events
+            .push_back(GameEvent::DeckCommitment(fairness::commit(
+                &self.data.deck,
+                &nonce,
+            )));
+
+        let num_players = self.get_num_players();
+        let num_hole_cards = self.data.settings.variant.num_hole_cards();
+        let mut seats = (0..num_players).cycle().skip(self.data.small_blind_idx);
+        // Deal hole cards to each player, looping over players and dealing
+        // them 1 card at a time.
+        while self.data.deck_idx < (num_hole_cards * num_players) {
             let deal_idx = seats.next().expect("dealing position exists");
-            let player = &mut value.data.players[deal_idx];
-            let card = value.data.deck[value.data.deck_idx];
+            let player = &mut self.data.players[deal_idx];
+            let card = self.data.deck[self.data.deck_idx];
             player.cards.push(card);
-            value.data.deck_idx += 1;
+            self.data.deck_idx += 1;
         }
+    }
+}
+
+/// Shuffle the game's deck and deal hole cards to each player.
+impl From<Game<Deal>> for Game<TakeAction> {
+    fn from(mut value: Game<Deal>) -> Self {
+        value.step();
         let action_options = value.prepare_for_next_phase();
         Self {
             data: value.data,
@@ -902,111 +2316,375 @@ impl From<Game<Deal>> for Game<TakeAction> {
     }
 }
 
+/// Shuffle the game's deck and deal hole cards to each player, skipping
+/// the pre-flop betting round for a bomb pot.
+impl From<Game<Deal>> for Game<Flop> {
+    fn from(mut value: Game<Deal>) -> Self {
+        value.step();
+        Self {
+            data: value.data,
+            state: Flop {},
+        }
+    }
+}
+
+/// Pick the action a player's pending action calls for given the action
+/// options they were offered, or return `None` if the pending action no
+/// longer fits the situation and the player should decide for themselves.
+///
+/// - Check/fold checks if it can and folds otherwise.
+/// - Check/call checks or calls, but won't call if it takes the player's
+///   whole stack.
+/// - Call any checks or calls whatever it takes, going all-in if needed.
+/// - Fold to any raise checks or calls the big blind and folds to
+///   anything more. `big_blind` is the big blind while it's still the
+///   bet to call (i.e., before the flop), and `None` otherwise.
+pub fn resolve_pending_action(
+    pending_action: PendingAction,
+    action_options: &HashSet<Action>,
+    big_blind: Option<Usd>,
+) -> Option<Action> {
+    let check = action_options.get(&Action::Check);
+    let call = action_options.get(&Action::Call(0));
+    match pending_action {
+        PendingAction::CheckFold => check.or(action_options.get(&Action::Fold)),
+        PendingAction::CheckCall => check.or(call),
+        PendingAction::CallAny => check.or(call).or(action_options.get(&Action::AllIn)),
+        PendingAction::FoldToAnyRaise => match (check, call, big_blind) {
+            (Some(action), ..) => Some(action),
+            (None, Some(Action::Call(amount)), Some(big_blind)) if *amount <= big_blind => call,
+            _ => action_options.get(&Action::Fold),
+        },
+    }
+    .cloned()
+}
+
+/// Check whether a player can take an action, returning the action
+/// sanitized the same way the game would sanitize it (e.g., bets that
+/// use up the player's stack become all-ins). This only needs the
+/// player's action options and their stack, so clients can validate
+/// actions before sending them to the server.
+pub fn validate_action(
+    action_options: &HashSet<Action>,
+    money: Usd,
+    action: Action,
+) -> Result<Action, UserError> {
+    // Action options carry the amount the player has to call and the
+    // minimum amount they have to raise.
+    let expected_amount = match action_options.get(&action) {
+        Some(Action::Call(amount) | Action::Raise(amount)) => *amount,
+        Some(_) => 0,
+        None => return Err(UserError::InvalidAction { action }),
+    };
+    // Convert the action to a valid bet. Sanitize the bet amount according
+    // to the player's intended action.
+    let mut bet = match action {
+        Action::AllIn => Bet {
+            action: BetAction::AllIn,
+            amount: money,
+        },
+        Action::Call(amount) => Bet {
+            action: BetAction::Call,
+            amount,
+        },
+        Action::Check | Action::Fold => return Ok(action),
+        Action::Raise(amount) => Bet {
+            action: BetAction::Raise,
+            amount,
+        },
+    };
+    if bet.amount >= money {
+        bet.action = BetAction::AllIn;
+        bet.amount = money;
+    }
+    // Do some additional bet validation based on the bet's amount.
+    match bet.action {
+        BetAction::Call if bet.amount != expected_amount => Err(UserError::InvalidBet { bet }),
+        BetAction::Raise if bet.amount < expected_amount => Err(UserError::InvalidBet { bet }),
+        _ => Ok(bet.into()),
+    }
+}
+
 impl Game<TakeAction> {
     pub fn act(&mut self, action: Action) -> Result<Action, UserError> {
+        let player_idx = self.data.next_action_idx;
         let sanitized_action = self.affect(action)?;
+        if let Some(player_idx) = player_idx {
+            self.data.action_history.push(ActionRecord {
+                username: self.data.players[player_idx].user.name.clone(),
+                action: sanitized_action.clone(),
+                street: Street::from_num_community_cards(self.data.board.len()),
+            });
+        }
         self.data.next_action_idx = self.get_next_action_idx(false);
+        self.data.turn_id += 1;
         self.state.action_options = self.get_next_action_options();
+        self.return_uncalled_bet();
         Ok(sanitized_action)
     }
 
+    /// Fold players that're leaving in the middle of a betting round,
+    /// passing the turn along right away if it was theirs. Players that
+    /// leave after betting is over keep their claim on the pot.
+    fn fold_players_leaving_round(&mut self) {
+        if self.state.action_options.is_none() {
+            return;
+        }
+        if self.fold_players_to_remove() {
+            if self
+                .data
+                .next_action_idx
+                .is_some_and(|action_idx| self.data.players[action_idx].state == PlayerState::Fold)
+            {
+                self.data.next_action_idx = self.get_next_action_idx(false);
+                self.data.turn_id += 1;
+            }
+            self.state.action_options = self.get_next_action_options();
+            self.return_uncalled_bet();
+        }
+    }
+
+    /// Return the part of the biggest bet that no one else matched to
+    /// the player that bet it once betting is over. It was never at risk,
+    /// so it shouldn't be won by someone else or counted as part of the
+    /// pot.
+    fn return_uncalled_bet(&mut self) {
+        if !self.is_ready_for_next_phase() {
+            return;
+        }
+        let mut investments: Vec<(usize, Usd)> = self
+            .data
+            .pot
+            .investments
+            .iter()
+            .map(|(player_idx, investment)| (*player_idx, *investment))
+            .collect();
+        investments.sort_by_key(|(_, investment)| Reverse(*investment));
+        let (player_idx, uncalled) = match investments[..] {
+            [(player_idx, top), (_, next), ..] => (player_idx, top - next),
+            _ => return,
+        };
+        if uncalled == 0 {
+            return;
+        }
+        self.data
+            .pot
+            .investments
+            .insert(player_idx, investments[0].1 - uncalled);
+        let player = &mut self.data.players[player_idx];
+        player.user.money += uncalled;
+        if let Some(last_bet) = player.last_bet.as_mut() {
+            last_bet.amount = last_bet.amount.saturating_sub(uncalled);
+        }
+        self.data.events.push_back(GameEvent::UncalledBetReturned(
+            player.user.name.clone(),
+            uncalled,
+        ));
+    }
+
     fn affect(&mut self, action: Action) -> Result<Action, UserError> {
         match (self.data.next_action_idx, &self.state.action_options) {
             (Some(player_idx), Some(action_options)) => {
-                if !action_options.contains(&action) {
-                    return Err(UserError::InvalidAction { action });
-                }
-                let player = &mut self.data.players[player_idx];
-                // Convert the action to a valid bet. Sanitize the bet amount according
-                // to the player's intended action.
-                let mut bet = match action {
-                    Action::AllIn => Bet {
-                        action: BetAction::AllIn,
-                        amount: player.user.money,
-                    },
-                    Action::Call(amount) => Bet {
-                        action: BetAction::Call,
-                        amount,
-                    },
-                    Action::Check => {
-                        self.data.num_players_called += 1;
-                        player.state = PlayerState::Check;
-                        return Ok(action);
-                    }
-                    Action::Fold => {
-                        self.data.num_players_active -= 1;
-                        player.state = PlayerState::Fold;
-                        return Ok(action);
-                    }
-                    Action::Raise(amount) => Bet {
-                        action: BetAction::Raise,
-                        amount,
-                    },
-                };
-                if bet.amount >= player.user.money {
-                    bet.action = BetAction::AllIn;
-                    bet.amount = player.user.money;
-                }
-                // Do some additional bet validation based on the bet's amount.
-                let call = self.data.pot.get_call();
-                let investment = self.data.pot.get_investment_by_player_idx(player_idx);
-                let new_investment = investment + bet.amount;
-                match bet.action {
-                    BetAction::AllIn => {
-                        self.data.num_players_active -= 1;
-                        if new_investment > call {
-                            self.data.num_players_called = 0;
-                        }
-                        player.state = PlayerState::AllIn;
-                    }
-                    BetAction::Call => {
-                        if new_investment != call {
+                let money = self.data.players[player_idx].user.money;
+                let action = validate_action(action_options, money, action)?;
+                // Fixed-limit raises have to be exactly one bet, including
+                // raises that would've been sanitized into all-ins.
+                if self.data.settings.limit.is_some() {
+                    match (&action, action_options.get(&Action::Raise(0))) {
+                        (Action::Raise(amount), Some(Action::Raise(raise))) if amount != raise => {
+                            let bet = Bet {
+                                action: BetAction::Raise,
+                                amount: *amount,
+                            };
                             return Err(UserError::InvalidBet { bet });
                         }
-                        self.data.num_players_called += 1;
-                        player.state = PlayerState::Call;
-                    }
-                    BetAction::Raise => {
-                        if new_investment < (2 * call) {
-                            return Err(UserError::InvalidBet { bet });
+                        (Action::AllIn, _) if !action_options.contains(&Action::AllIn) => {
+                            return Err(UserError::InvalidAction { action });
                         }
-                        self.data.num_players_called = 1;
-                        player.state = PlayerState::Raise;
+                        _ => {}
                     }
                 }
-                // The player's bet is OK. Remove the bet amount from the player's
-                // stack and start distributing it appropriately amongst all the pots.
-                player.user.money -= bet.amount;
-                self.data.pot.bet(player_idx, &bet);
+                self.apply(player_idx, action.clone());
+                Ok(action)
+            }
+            _ => Err(UserError::OutOfTurnAction),
+        }
+    }
 
-                // Reset other player states that're still in the hand based on the bet.
-                if self.data.num_players_called <= 1 {
-                    for player in self
-                        .data
-                        .players
-                        .iter_mut()
-                        .enumerate()
-                        .filter(|(idx, player)| {
-                            matches!(player.state,
-                            PlayerState::Call | PlayerState::Check | PlayerState::Raise
-                                if *idx != player_idx)
-                        })
-                        .map(|(_, player)| player)
-                    {
-                        player.state = PlayerState::Wait
-                    }
+    /// Apply an action that's already been validated and sanitized by
+    /// [`validate_action`].
+    fn apply(&mut self, player_idx: usize, action: Action) {
+        let player = &mut self.data.players[player_idx];
+        let bet = match action {
+            Action::AllIn => Bet {
+                action: BetAction::AllIn,
+                amount: player.user.money,
+            },
+            Action::Call(amount) => Bet {
+                action: BetAction::Call,
+                amount,
+            },
+            Action::Check => {
+                self.data.num_players_called += 1;
+                player.state = PlayerState::Check;
+                return;
+            }
+            Action::Fold => {
+                self.data.num_players_active -= 1;
+                player.state = PlayerState::Fold;
+                return;
+            }
+            Action::Raise(amount) => Bet {
+                action: BetAction::Raise,
+                amount,
+            },
+        };
+        self.track_raise(player_idx, &bet);
+        let player = &mut self.data.players[player_idx];
+        match bet.action {
+            BetAction::AllIn => {
+                let call = self.data.pot.get_call();
+                let investment = self.data.pot.get_investment_by_player_idx(player_idx);
+                self.data.num_players_active -= 1;
+                if investment + bet.amount > call {
+                    self.data.num_players_called = 0;
                 }
-
-                // Return the santized action.
-                Ok(bet.into())
+                player.state = PlayerState::AllIn;
+                self.data
+                    .events
+                    .push_back(GameEvent::AllIn(player.user.name.clone(), bet.amount));
+            }
+            BetAction::Blind => unreachable!("blinds are only posted while collecting blinds"),
+            BetAction::Call => {
+                self.data.num_players_called += 1;
+                player.state = PlayerState::Call;
+            }
+            BetAction::Raise => {
+                self.data.num_players_called = 1;
+                player.state = PlayerState::Raise;
+            }
+        }
+        // Remove the bet amount from the player's stack and start distributing
+        // it appropriately amongst all the pots.
+        player.user.money -= bet.amount;
+        player.record_bet(&bet);
+        self.data.pot.bet(player_idx, &bet);
+
+        // Reset other player states that're still in the hand based on the bet.
+        if self.data.num_players_called <= 1 {
+            for player in self
+                .data
+                .players
+                .iter_mut()
+                .enumerate()
+                .filter(|(idx, player)| {
+                    matches!(player.state,
+                    PlayerState::Call | PlayerState::Check | PlayerState::Raise
+                        if *idx != player_idx)
+                })
+                .map(|(_, player)| player)
+            {
+                player.state = PlayerState::Wait
             }
-            _ => Err(UserError::OutOfTurnAction),
         }
     }
 
     pub fn get_action_options(&self) -> Option<HashSet<Action>> {
         self.state.action_options.clone()
     }
+
+    /// Return the most the next player can raise by, which is their whole
+    /// stack (or exactly one bet in a fixed-limit game), or `None` if they
+    /// can't raise.
+    pub fn get_max_raise(&self) -> Option<Usd> {
+        match (self.data.next_action_idx, &self.state.action_options) {
+            (Some(action_idx), Some(action_options)) => {
+                match (
+                    action_options.get(&Action::Raise(0)),
+                    self.data.settings.limit,
+                ) {
+                    (Some(Action::Raise(raise)), Some(_)) => Some(*raise),
+                    (Some(_), None) => Some(self.data.players[action_idx].user.money),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Return everything the next player needs to decide on their action,
+    /// or `None` if no one is waiting to act.
+    pub fn get_decision_context(&self) -> Option<DecisionContext> {
+        let action_idx = self.data.next_action_idx?;
+        let action_options = self.state.action_options.as_ref()?;
+        let player = &self.data.players[action_idx];
+        let num_players = self.data.players.len();
+        let min_raise = match action_options.get(&Action::Raise(0)) {
+            Some(Action::Raise(raise)) => Some(*raise),
+            _ => None,
+        };
+        Some(DecisionContext {
+            variant: self.data.settings.variant,
+            board: self.data.board.clone(),
+            cards: player.cards.clone(),
+            pot: self.data.pot.get_size(),
+            call: self.data.pot.get_call_by_player_idx(action_idx),
+            min_raise,
+            max_raise: self.get_max_raise(),
+            stack: player.user.money,
+            position: (action_idx + num_players - self.data.button_idx) % num_players,
+            num_players,
+        })
+    }
+
+    /// Track the size of a bet's raise, if it raises at all. Full raises
+    /// set the minimum size of the next raise and reopen the betting for
+    /// everyone. Smaller (all-in) raises leave players that've already
+    /// acted with only the option to call or fold. A full raise in a
+    /// fixed-limit game is one bet.
+    fn track_raise(&mut self, player_idx: usize, bet: &Bet) {
+        let investment = self.data.pot.get_investment_by_player_idx(player_idx);
+        let raise_size = (investment + bet.amount).saturating_sub(self.data.pot.get_call());
+        if raise_size == 0 {
+            return;
+        }
+        let full_raise_size = match self.data.settings.limit {
+            Some(limit) => limit.get_bet_size(self.data.board.len()),
+            None => max(self.data.pot.last_raise_size, self.data.big_blind),
+        };
+        if raise_size >= full_raise_size {
+            self.data.pot.last_raise_size = raise_size;
+            self.data.pot.players_closed_to_raises.clear();
+            self.data.pot.num_raises += 1;
+        } else {
+            let acted = self
+                .data
+                .players
+                .iter()
+                .enumerate()
+                .filter(|(_, player)| {
+                    matches!(
+                        player.state,
+                        PlayerState::Call | PlayerState::Check | PlayerState::Raise
+                    )
+                })
+                .map(|(idx, _)| idx);
+            self.data.pot.players_closed_to_raises.extend(acted);
+        }
+    }
+
+    /// Return the action to take on behalf of the next player when they
+    /// can't act for themselves, either because they ran out of time or
+    /// because they left. Checking is preferred since it's free.
+    pub fn get_auto_action(&self) -> Option<Action> {
+        self.state.action_options.as_ref().map(|action_options| {
+            if action_options.contains(&Action::Check) {
+                Action::Check
+            } else {
+                Action::Fold
+            }
+        })
+    }
 }
 
 impl From<Game<TakeAction>> for Game<Flop> {
@@ -1038,20 +2716,36 @@ impl From<Game<TakeAction>> for Game<River> {
 
 impl From<Game<TakeAction>> for Game<ShowHands> {
     fn from(value: Game<TakeAction>) -> Self {
+        let state = ShowHands::new(value.data.settings.max_players);
         Self {
             data: value.data,
-            state: ShowHands::new(),
+            state,
+        }
+    }
+}
+
+/// Skip the rest of the board and the showdown when everyone else folded.
+/// The last player in the hand takes the pot without showing their hand,
+/// and no one gets to see what cards would've come.
+impl From<Game<TakeAction>> for Game<DistributePot> {
+    fn from(value: Game<TakeAction>) -> Self {
+        let max_players = value.data.settings.max_players;
+        Self {
+            data: value.data,
+            state: DistributePot {
+                hand_eval_cache: HashMap::with_capacity(max_players),
+            },
         }
     }
 }
 
 impl Game<Flop> {
     fn step(&mut self) {
+        self.burn_card();
         for _ in 0..3 {
-            let card = self.data.deck[self.data.deck_idx];
-            self.data.board.push(card);
-            self.data.deck_idx += 1;
+            self.deal_community_card();
         }
+        self.update_equities();
     }
 }
 
@@ -1081,9 +2775,9 @@ impl From<Game<Flop>> for Game<Turn> {
 
 impl Game<Turn> {
     fn step(&mut self) {
-        let card = self.data.deck[self.data.deck_idx];
-        self.data.board.push(card);
-        self.data.deck_idx += 1;
+        self.burn_card();
+        self.deal_community_card();
+        self.update_equities();
     }
 }
 
@@ -1113,9 +2807,9 @@ impl From<Game<Turn>> for Game<River> {
 
 impl Game<River> {
     fn step(&mut self) {
-        let card = self.data.deck[self.data.deck_idx];
-        self.data.board.push(card);
-        self.data.deck_idx += 1;
+        self.burn_card();
+        self.deal_community_card();
+        self.update_equities();
     }
 }
 
@@ -1136,9 +2830,10 @@ impl From<Game<River>> for Game<TakeAction> {
 impl From<Game<River>> for Game<ShowHands> {
     fn from(mut value: Game<River>) -> Self {
         value.step();
+        let state = ShowHands::new(value.data.settings.max_players);
         Self {
             data: value.data,
-            state: ShowHands::new(),
+            state,
         }
     }
 }
@@ -1146,23 +2841,12 @@ impl From<Game<River>> for Game<ShowHands> {
 macro_rules! impl_show_hands {
     ($($t:ty),+) => {
         $(impl $t {
+            pub fn show_card(&mut self, username: &str, card_idx: usize) -> Result<(), UserError> {
+                self.reveal_cards(username, Some(card_idx))
+            }
+
             pub fn show_hand(&mut self, username: &str) -> Result<(), UserError> {
-                match self
-                    .data
-                    .players
-                    .iter_mut()
-                    .find(|p| p.user.name == username)
-                {
-                    Some(player) => {
-                        if !player.showing {
-                            player.showing = true;
-                            Ok(())
-                        } else {
-                            Err(UserError::UserAlreadyShowingHand)
-                        }
-                    }
-                    None => Err(UserError::UserNotPlaying),
-                }
+                self.reveal_cards(username, None)
             }
         })*
     }
@@ -1176,44 +2860,310 @@ impl_show_hands!(
     Game<UpdateBlinds>
 );
 
-impl From<Game<ShowHands>> for Game<DistributePot> {
-    fn from(mut value: Game<ShowHands>) -> Self {
-        let num_players_remaining: usize = value
-            .data
-            .players
-            .iter()
-            .map(|p| if p.state == PlayerState::Fold { 0 } else { 1 })
-            .sum();
-        if num_players_remaining > 1 {
-            for player_idx in value.data.pot.investments.keys() {
-                let player = &mut value.data.players[*player_idx];
-                if player.state != PlayerState::Fold {
-                    player.showing = true;
+/// Players can show their cards before the showdown as long as betting
+/// no longer involves them, such as when they're all-in.
+macro_rules! impl_show_hands_early {
+    ($($t:ty),+) => {
+        $(impl $t {
+            fn can_show_early(&self, username: &str) -> bool {
+                match self.data.players.iter().find(|p| p.user.name == username) {
+                    Some(player) => match player.state {
+                        PlayerState::AllIn => true,
+                        PlayerState::Fold => false,
+                        _ => self.is_ready_for_showdown(),
+                    },
+                    // Let non-players get the usual error.
+                    None => true,
                 }
             }
+
+            pub fn show_card(&mut self, username: &str, card_idx: usize) -> Result<(), UserError> {
+                if !self.can_show_early(username) {
+                    return Err(UserError::CannotShowHand);
+                }
+                self.reveal_cards(username, Some(card_idx))
+            }
+
+            pub fn show_hand(&mut self, username: &str) -> Result<(), UserError> {
+                if !self.can_show_early(username) {
+                    return Err(UserError::CannotShowHand);
+                }
+                self.reveal_cards(username, None)?;
+                self.update_equities();
+                Ok(())
+            }
+        })*
+    }
+}
+
+impl_show_hands_early!(Game<TakeAction>, Game<Flop>, Game<Turn>, Game<River>);
+
+/// Insurance can only be offered and accepted while an all-in hand is
+/// running out to the showdown, when no one can bet anymore.
+macro_rules! impl_insurance {
+    ($($t:ty),+) => {
+        $(impl $t {
+            pub fn accept_insurance(&mut self, username: &str, insurer: &str) -> Result<(), UserError> {
+                if !self.can_insure(username) {
+                    return Err(UserError::CannotInsure);
+                }
+                let Some(offer_idx) = self
+                    .data
+                    .insurance_offers
+                    .iter()
+                    .position(|i| i.insurer == insurer && i.insured == username)
+                else {
+                    return Err(UserError::NoInsuranceOffer);
+                };
+                let payout = self.data.insurance_offers[offer_idx].payout;
+                match self.get_user_mut(insurer) {
+                    Some(user) if user.money >= payout => user.money -= payout,
+                    _ => return Err(UserError::InsurerCannotCover { payout }),
+                }
+                let insurance = self.data.insurance_offers.remove(offer_idx);
+                self.data.events.push_back(GameEvent::InsuranceAccepted(insurance.clone()));
+                self.data.insurance.push(insurance);
+                Ok(())
+            }
+
+            fn can_insure(&self, username: &str) -> bool {
+                self.data.settings.insurance
+                    && self.is_ready_for_showdown()
+                    && self.is_contested()
+                    && self
+                        .data
+                        .players
+                        .iter()
+                        .any(|p| p.user.name == username && p.state != PlayerState::Fold)
+            }
+
+            /// Offer insurance to an all-in player. The payout is the
+            /// premium scaled by the odds. A new offer to the same player
+            /// replaces the old one.
+            pub fn offer_insurance(
+                &mut self,
+                username: &str,
+                insured: &str,
+                premium: Usd,
+                odds_numer: Usd,
+                odds_denom: Usd,
+            ) -> Result<(), UserError> {
+                if username == insured || !self.contains_user(username) || !self.can_insure(insured) {
+                    return Err(UserError::CannotInsure);
+                } else if premium == 0 || odds_numer == 0 || odds_denom == 0 {
+                    return Err(UserError::InvalidInsurance);
+                }
+                let payout = (premium as u64 * odds_numer as u64 / odds_denom as u64)
+                    .try_into()
+                    .map_err(|_| UserError::InvalidInsurance)?;
+                let insurance = Insurance {
+                    insurer: username.to_string(),
+                    insured: insured.to_string(),
+                    premium,
+                    payout,
+                };
+                self.data
+                    .insurance_offers
+                    .retain(|i| i.insurer != username || i.insured != insured);
+                self.data.events.push_back(GameEvent::InsuranceOffered(insurance.clone()));
+                self.data.insurance_offers.push(insurance);
+                Ok(())
+            }
+        })*
+    }
+}
+
+impl_insurance!(Game<TakeAction>, Game<Flop>, Game<Turn>, Game<River>);
+
+/// Spectators can only bet while players are betting, so the pool is
+/// locked once no one can bet anymore, like when everyone's all-in, and
+/// by the time the hands are shown.
+impl Game<TakeAction> {
+    pub fn place_side_bet(
+        &mut self,
+        username: &str,
+        player: &str,
+        amount: Usd,
+    ) -> Result<(), UserError> {
+        if !self.data.settings.spectator_bets {
+            return Err(UserError::SideBetsDisabled);
+        } else if self.is_ready_for_showdown()
+            || !self
+                .data
+                .players
+                .iter()
+                .any(|p| p.user.name == player && p.state != PlayerState::Fold)
+        {
+            return Err(UserError::CannotSideBet);
+        }
+        let Some(spectator) = self.data.spectators.get_mut(username) else {
+            return Err(UserError::CannotSideBet);
+        };
+        if amount == 0 || amount > spectator.money {
+            return Err(UserError::InvalidSideBet {
+                money: spectator.money,
+            });
+        }
+        spectator.money -= amount;
+        let bet = SideBet {
+            spectator: username.to_string(),
+            player: player.to_string(),
+            amount,
+        };
+        self.data
+            .events
+            .push_back(GameEvent::SideBetPlaced(bet.clone()));
+        self.data.spectator_pool.bets.push(bet);
+        Ok(())
+    }
+}
+
+macro_rules! impl_muck_hands {
+    ($($t:ty),+) => {
+        $(impl $t {
+            pub fn muck_hand(&mut self, username: &str) -> Result<(), UserError> {
+                match self
+                    .data
+                    .players
+                    .iter_mut()
+                    .find(|p| p.user.name == username)
+                {
+                    Some(player) => {
+                        if player.showing {
+                            Err(UserError::UserAlreadyShowingHand)
+                        } else if !player.mucked {
+                            player.mucked = true;
+                            Ok(())
+                        } else {
+                            Err(UserError::UserMuckedHand)
+                        }
+                    }
+                    None => Err(UserError::UserNotPlaying),
+                }
+            }
+        })*
+    }
+}
+
+impl_muck_hands!(
+    Game<TakeAction>,
+    Game<Flop>,
+    Game<Turn>,
+    Game<River>,
+    Game<ShowHands>
+);
+
+/// Reveal hands for the pot that's about to be distributed. Winners of
+/// a contested pot always have to show their hand. Losers only show their
+/// hand if the game is configured to automatically show losing hands and
+/// they didn't muck.
+impl From<Game<ShowHands>> for Game<DistributePot> {
+    fn from(value: Game<ShowHands>) -> Self {
+        let mut game = Self {
+            data: value.data,
+            state: DistributePot {
+                hand_eval_cache: value.state.hand_eval_cache,
+            },
+        };
+        let num_players_remaining = game
+            .data
+            .players
+            .iter()
+            .filter(|p| p.state != PlayerState::Fold)
+            .count();
+        if num_players_remaining > 1 {
+            // Winners of every contested pot, main or side, are shown.
+            // Uncontested segments are just uncalled bets being returned,
+            // so they don't show anyone's hand.
+            let mut showing: HashSet<usize> = game
+                .plan_distribution()
+                .into_iter()
+                .filter(|segment| segment.winning_hand.is_some())
+                .flat_map(|segment| segment.winners)
+                .collect();
+            if game.data.settings.auto_show_losers {
+                showing.extend(game.data.pot.investments.keys().filter(|player_idx| {
+                    let player = &game.data.players[**player_idx];
+                    player.state != PlayerState::Fold && !player.mucked
+                }));
+            }
+            for player_idx in showing {
+                game.data.players[player_idx].showing = true;
+            }
         }
-        Self {
-            data: value.data,
-            state: DistributePot {
-                hand_eval_cache: value.state.hand_eval_cache,
-            },
-        }
+        game
     }
 }
 
+/// A pot segment that's been decided but not paid out yet, tracked by
+/// player indices so it can be applied to the pot.
+struct PlannedSegment {
+    /// How much of each player's investment goes into the segment.
+    contributions: Vec<(usize, Usd)>,
+    /// Players that haven't folded and can win the segment.
+    eligible: Vec<usize>,
+    /// Players that split the segment, in the order they're paid.
+    winners: Vec<usize>,
+    /// Value of the winning hands, if any hands were compared.
+    winning_hand: Option<HandValue>,
+}
+
 impl Game<DistributePot> {
-    /// Get all players in the pot that haven't folded and compare their
-    /// hands to one another. Get the winning indices and distribute
-    /// the pot accordingly.
-    fn distribute(&mut self) {
-        let mut investments = Vec::from_iter(self.data.pot.investments.iter_mut());
-        investments
-            .sort_unstable_by(|(_, investment1), (_, investment2)| investment1.cmp(investment2));
-        if let Some((_, largest_call)) = investments.last() {
+    /// Return how what's left of the pot will be distributed without
+    /// paying anything out, segment by segment in the order they're
+    /// distributed.
+    pub fn get_distribution_outcome(&mut self) -> DistributionOutcome {
+        let segments = self
+            .plan_distribution()
+            .into_iter()
+            .map(|segment| {
+                let usernames = |player_indices: &[usize]| {
+                    player_indices
+                        .iter()
+                        .map(|player_idx| self.data.players[*player_idx].user.name.clone())
+                        .collect()
+                };
+                PotSegment {
+                    amount: segment.contributions.iter().map(|(_, amount)| amount).sum(),
+                    eligible: usernames(&segment.eligible),
+                    winners: usernames(&segment.winners),
+                    winning_rank: segment.winning_hand.map(|hand| hand.rank()),
+                }
+            })
+            .collect();
+        DistributionOutcome { segments }
+    }
+
+    /// Split what's left of the pot into segments and decide who wins
+    /// each one. Each segment is made of the largest investments of the
+    /// players that haven't folded, along with whatever folded players
+    /// invested up to that much. The game itself isn't changed other than
+    /// caching hand evaluations.
+    fn plan_distribution(&mut self) -> Vec<PlannedSegment> {
+        let mut investments = self.data.pot.investments.clone();
+        let mut segments = vec![];
+        loop {
+            // Investments are sorted the same way no matter what order
+            // they're stored in. Folded players come first when investments
+            // are tied so their money stays in the pot the players they tied
+            // with are in.
+            let players = &self.data.players;
+            let mut sorted_investments = Vec::from_iter(investments.iter_mut());
+            sorted_investments.sort_unstable_by_key(|(player_idx, investment)| {
+                (
+                    **investment,
+                    players[**player_idx].state != PlayerState::Fold,
+                    **player_idx,
+                )
+            });
+            let Some((_, largest_call)) = sorted_investments.last() else {
+                break;
+            };
+
             // Get the pot size and the player indices in the pot.
-            let mut pot_idx = investments.len() - 1;
+            let mut pot_idx = sorted_investments.len() - 1;
             let mut pot_call = **largest_call;
-            for (idx, (player_idx, investment)) in investments.iter().enumerate().rev() {
+            for (idx, (player_idx, investment)) in sorted_investments.iter().enumerate().rev() {
                 let player = &self.data.players[**player_idx];
                 // If the player's investment doesn't match the pot's call, then
                 // that means they and everyone with smaller investements aren't
@@ -1224,33 +3174,69 @@ impl Game<DistributePot> {
                 }
                 pot_idx = idx;
             }
-
-            // Evaluate the hands in the pot and get the winners.
-            let mut pot_size: Usd = 0;
+            let mut contributions = Vec::with_capacity(sorted_investments.len() - pot_idx);
             let mut seats_in_pot = Vec::with_capacity(self.data.settings.max_players);
-            let mut hands_in_pot = Vec::with_capacity(self.data.settings.max_players);
-            for (player_idx, investment) in investments[pot_idx..].as_mut() {
+            for (player_idx, investment) in sorted_investments[pot_idx..].as_mut() {
                 let pot_investment = min(pot_call, **investment);
-                pot_size += pot_investment;
                 **investment -= pot_investment;
-                let player = &mut self.data.players[**player_idx];
-                if player.state != PlayerState::Fold {
-                    seats_in_pot.push(*player_idx);
-                    let hand_eval = || {
-                        let mut cards = player.cards.clone();
-                        cards.extend(self.data.board.clone());
-                        functional::prepare_hand(&mut cards);
-                        functional::eval(&cards)
-                    };
-                    let hand = self
-                        .state
-                        .hand_eval_cache
-                        .entry(**player_idx)
-                        .or_insert_with(hand_eval);
-                    hands_in_pot.push(hand.clone());
-                }
-            }
-            let winner_indices = functional::argmax(&hands_in_pot);
+                contributions.push((**player_idx, pot_investment));
+                if self.data.players[**player_idx].state != PlayerState::Fold {
+                    seats_in_pot.push(**player_idx);
+                }
+            }
+
+            // Evaluate the hands in the pot and get the winners. An
+            // uncontested pot goes to the last player in it without
+            // looking at their hand, which might not even have a board
+            // to go with it.
+            let (winners, winning_hand) = if seats_in_pot.len() == 1 {
+                (seats_in_pot.clone(), None)
+            } else {
+                let hands_in_pot: Vec<HandValue> = seats_in_pot
+                    .iter()
+                    .map(|player_idx| {
+                        eval_player_hand(
+                            &mut self.state.hand_eval_cache,
+                            *player_idx,
+                            &self.data.players[*player_idx],
+                            &self.data.board,
+                            self.data.settings.variant,
+                        )
+                    })
+                    .collect();
+                let winner_indices = functional::argmax(&hands_in_pot);
+                let winning_hand = winner_indices.first().map(|idx| hands_in_pot[*idx]);
+                let winners = winner_indices
+                    .into_iter()
+                    .map(|winner_idx| seats_in_pot[winner_idx])
+                    .collect();
+                (winners, winning_hand)
+            };
+            segments.push(PlannedSegment {
+                contributions,
+                eligible: seats_in_pot,
+                winners,
+                winning_hand,
+            });
+            investments.retain(|_, investment| *investment > 0);
+        }
+        segments
+    }
+
+    /// Pay out the next segment of the pot to its winners.
+    fn distribute(&mut self) {
+        if let Some(segment) = self.plan_distribution().into_iter().next() {
+            let mut pot_size: Usd = 0;
+            for (player_idx, pot_investment) in &segment.contributions {
+                let investment = self
+                    .data
+                    .pot
+                    .investments
+                    .get_mut(player_idx)
+                    .expect("contributions come from investments");
+                *investment -= pot_investment;
+                pot_size += pot_investment;
+            }
 
             // Finally, split the pot amongst all the winners. There's
             // a possibility for the pot to not split perfectly
@@ -1259,16 +3245,23 @@ impl Game<DistributePot> {
             // amongst remaining users. This also encourages users to
             // stay in the game so they can be donated these breadcrumbs
             // and continue playing with them.
-            let num_winners = winner_indices.len();
+            let num_winners = segment.winners.len();
             let pot_split = pot_size / num_winners as Usd;
             let mut pot_remainder = pot_size as Usdf;
-            for winner_idx in winner_indices {
-                let winner_player_idx = seats_in_pot[winner_idx];
-                let player = &mut self.data.players[*winner_player_idx];
+            let mut winners = Vec::with_capacity(num_winners);
+            for winner_player_idx in segment.winners {
+                self.data.hand_winners.insert(winner_player_idx);
+                let player = &mut self.data.players[winner_player_idx];
                 player.user.money += pot_split;
                 pot_remainder -= pot_split as Usdf;
+                winners.push(player.user.name.clone());
             }
             self.data.donations += pot_remainder;
+            self.data.events.push_back(GameEvent::PotAwarded(
+                winners,
+                pot_size,
+                segment.winning_hand.map(|hand| hand.rank()),
+            ));
         }
 
         // Remove null investments.
@@ -1277,6 +3270,86 @@ impl Game<DistributePot> {
             .investments
             .retain(|_, investment| *investment > 0);
     }
+
+    /// Settle insurance now that all the pots have been distributed.
+    /// Insured players that won any pot pay their premium out of their
+    /// winnings, and insured players that didn't win get the payout
+    /// that's been held since they accepted the insurance. Money owed
+    /// to users that've left is donated.
+    fn settle_insurance(&mut self) {
+        self.data.insurance_offers.clear();
+        for insurance in mem::take(&mut self.data.insurance) {
+            let held =
+                self.data.hand_winners.iter().any(|player_idx| {
+                    self.data.players[*player_idx].user.name == insurance.insured
+                });
+            let (from, to, amount) = if held {
+                let premium = match self.get_user_mut(&insurance.insured) {
+                    Some(user) => {
+                        let premium = min(insurance.premium, user.money);
+                        user.money -= premium;
+                        premium
+                    }
+                    None => 0,
+                };
+                match self.get_user_mut(&insurance.insurer) {
+                    Some(user) => user.money += insurance.payout + premium,
+                    None => self.data.donations += (insurance.payout + premium) as Usdf,
+                }
+                (insurance.insured, insurance.insurer, premium)
+            } else {
+                match self.get_user_mut(&insurance.insured) {
+                    Some(user) => user.money += insurance.payout,
+                    None => self.data.donations += insurance.payout as Usdf,
+                }
+                (insurance.insurer, insurance.insured, insurance.payout)
+            };
+            self.data
+                .events
+                .push_back(GameEvent::InsuranceSettled(from, to, amount));
+        }
+        self.data.hand_winners.clear();
+    }
+
+    /// Pay out the spectator pool now that all the pots have been
+    /// distributed. Money owed to users that've left is donated, as is
+    /// whatever's left over from splitting the pool, or the whole pool if
+    /// no one bet on a winner.
+    fn settle_side_bets(&mut self) {
+        let pool = mem::take(&mut self.data.spectator_pool);
+        let winners: Vec<String> = self
+            .data
+            .hand_winners
+            .iter()
+            .map(|player_idx| self.data.players[*player_idx].user.name.clone())
+            .collect();
+        let (payouts, leftover) = pool.get_payouts(&winners);
+        for (username, payout) in payouts {
+            match self.get_user_mut(&username) {
+                Some(user) => user.money += payout,
+                None => self.data.donations += payout as Usdf,
+            }
+            self.data
+                .events
+                .push_back(GameEvent::SideBetPaid(username, payout));
+        }
+        self.data.donations += leftover as Usdf;
+    }
+
+    /// Let the collusion detector look over the hand that just ended,
+    /// raising whatever suspicions it has.
+    fn detect_collusion(&mut self) {
+        if let Some(detector) = self.data.settings.collusion_detector.as_mut() {
+            for suspicion in detector.observe(&self.data.action_history, self.data.big_blind) {
+                let (username1, username2) = suspicion.users;
+                self.data.events.push_back(GameEvent::SuspicionRaised(
+                    username1,
+                    username2,
+                    suspicion.score,
+                ));
+            }
+        }
+    }
 }
 
 impl From<Game<DistributePot>> for Game<ShowHands> {
@@ -1294,6 +3367,9 @@ impl From<Game<DistributePot>> for Game<ShowHands> {
 impl From<Game<DistributePot>> for Game<RemovePlayers> {
     fn from(mut value: Game<DistributePot>) -> Self {
         value.distribute();
+        value.settle_side_bets();
+        value.settle_insurance();
+        value.detect_collusion();
         value.data.num_players_active = 0;
         Self {
             data: value.data,
@@ -1304,12 +3380,37 @@ impl From<Game<DistributePot>> for Game<RemovePlayers> {
 
 impl From<Game<RemovePlayers>> for Game<DivideDonations> {
     fn from(mut value: Game<RemovePlayers>) -> Self {
+        // Disconnected players that've run out of hands are removed like
+        // any other player that left.
+        let players_to_remove = &mut value.data.players_to_remove;
+        value
+            .data
+            .disconnected_players
+            .retain(|username, grace_hands| {
+                if *grace_hands == 0 {
+                    players_to_remove.insert(username.clone());
+                    false
+                } else {
+                    *grace_hands -= 1;
+                    true
+                }
+            });
         while let Some(username) = value.data.players_to_remove.pop_first() {
             // It is possible for a user to leave in this state but right before
             // this state transition occurs. That'd cause this method to return
             // an error, but it's really OK if they left since they were going
-            // to be removed anyways.
-            value.remove_user(&username).ok();
+            // to be removed anyways. Players moving to the rail leave if the
+            // rail filled up in the meantime.
+            let railed =
+                value.data.players_to_rail.remove(&username) && value.rail_user(&username).is_ok();
+            if !railed {
+                value.remove_user(&username).ok();
+            }
+        }
+        while let Some((username, amount)) = value.data.players_to_top_up.pop_first() {
+            // The player's stack may have changed during the hand, so
+            // it's OK if they can no longer add chips.
+            value.add_chips(&username, amount).ok();
         }
         Self {
             data: value.data,
@@ -1366,7 +3467,7 @@ impl From<Game<UpdateBlinds>> for Game<BootPlayers> {
             .min()
             .unwrap_or(Usd::MAX);
         if min_money < Usd::MAX {
-            let multiple = max(1, min_money / value.data.settings.buy_in);
+            let multiple = max(1, min_money / value.data.settings.starting_stack);
             value.data.small_blind = multiple * value.data.settings.min_small_blind;
             value.data.big_blind = multiple * value.data.settings.min_big_blind;
         }
@@ -1379,10 +3480,53 @@ impl From<Game<UpdateBlinds>> for Game<BootPlayers> {
 
 /// Spectate players that don't have enough money to satisfy the big blind
 /// from seats, and reset player states for players that do have enough
-/// money to play.
+/// money to play. Players that opted into auto-rebuy have their stack
+/// reset to the starting stack instead, as long as that covers the big
+/// blind and they're still connected. Players that asked to change
+/// seats during the hand are moved last, once every seat that's being
+/// given up is open.
 impl From<Game<BootPlayers>> for Game<Lobby> {
     fn from(mut value: Game<BootPlayers>) -> Self {
+        if let Some(nonce) = value.data.deck_nonce.take() {
+            value
+                .data
+                .events
+                .push_back(GameEvent::DeckReveal(DeckReveal {
+                    deck: value.data.deck.clone(),
+                    nonce,
+                    burned: mem::take(&mut value.data.burned),
+                }));
+        }
         value.data.board.clear();
+        value.data.equities.clear();
+        value.data.bomb_pot = false;
+        // The lobby counts as the next hand, so renames made during
+        // this one don't count against users anymore.
+        value.data.renamed_users.clear();
+        let starting_stack = value.data.settings.starting_stack;
+        if value.data.settings.auto_rebuy && starting_stack >= value.data.big_blind {
+            for player in value.data.players.iter_mut().filter(|player| {
+                player.user.money < value.data.big_blind
+                    && value.data.auto_rebuy_users.contains(&player.user.name)
+                    && !value
+                        .data
+                        .disconnected_players
+                        .contains_key(&player.user.name)
+            }) {
+                let amount = starting_stack - player.user.money;
+                player.user.money = starting_stack;
+                value
+                    .data
+                    .ledger
+                    .entry(player.user.name.clone())
+                    .or_default()
+                    .bought_in += amount;
+                value
+                    .data
+                    .events
+                    .push_back(GameEvent::Rebuy(player.user.name.clone(), amount));
+            }
+        }
         for player in value.data.players.iter_mut() {
             if player.user.money < value.data.big_blind {
                 value.data.open_seats.push_back(player.seat_idx);
@@ -1398,8 +3542,18 @@ impl From<Game<BootPlayers>> for Game<Lobby> {
             // It is possible for a user to leave in this state but right before
             // this state transition occurs. That'd cause this method to return
             // an error, but it's really OK if they left since spectating them
-            // is a softer action.
-            value.spectate_user(&username).ok();
+            // is a softer action. Disconnected players have no one to
+            // spectate for, so they're removed instead.
+            if value.is_disconnected(&username) {
+                value.remove_user(&username).ok();
+            } else {
+                value.spectate_user(&username).ok();
+            }
+        }
+        while let Some((username, seat_idx)) = value.data.players_to_change_seat.pop_first() {
+            // The player may have left or lost their seat since they asked
+            // to move, so it's OK if they can't move anymore.
+            value.change_seat(&username, seat_idx).ok();
         }
         Self {
             data: value.data,
@@ -1408,6 +3562,64 @@ impl From<Game<BootPlayers>> for Game<Lobby> {
     }
 }
 
+/// How long a server should pause on each kind of state before stepping
+/// it. States that deal cards or distribute money are paused on so users
+/// can follow along, but only while the hand is contested. Otherwise,
+/// they're paused on as briefly as administrative states.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StepDurations {
+    /// Administrative states that users don't need time to follow, like
+    /// moving the button, collecting blinds, and booting players.
+    pub admin: Duration,
+    /// The lobby, which is stepped repeatedly while users wait for a
+    /// game to start, so it shouldn't be stepped any faster than it
+    /// needs to be.
+    pub lobby: Duration,
+    /// Dealing hole cards.
+    pub deal: Duration,
+    /// A betting round that's over, before the next street is revealed.
+    pub betting: Duration,
+    /// Revealing the flop, turn, or river.
+    pub street: Duration,
+    /// Showing hands at showdown.
+    pub show_hands: Duration,
+    /// Paying out the pot.
+    pub distribute_pot: Duration,
+}
+
+impl StepDurations {
+    /// Pause for `brief` on administrative states and for `deliberate`
+    /// on every state users need time to follow.
+    pub fn new(brief: Duration, deliberate: Duration) -> Self {
+        Self {
+            admin: brief,
+            lobby: deliberate,
+            deal: deliberate,
+            betting: deliberate,
+            street: deliberate,
+            show_hands: deliberate,
+            distribute_pot: deliberate,
+        }
+    }
+}
+
+impl Default for StepDurations {
+    fn default() -> Self {
+        Self::new(DEFAULT_BRIEF_STEP_TIMEOUT, DEFAULT_STEP_TIMEOUT)
+    }
+}
+
+/// Hints for how a server should pace stepping through a state.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StepHint {
+    /// How long to pause on the state before stepping it.
+    pub min_wait: Duration,
+    /// Whether the state is waiting on a player's action. If so, the
+    /// server should wait for the action (or for the player to run out
+    /// of time) instead of pausing.
+    pub waits_for_action: bool,
+}
+
 #[derive(Debug)]
 pub enum PokerState {
     Lobby(Game<Lobby>),
@@ -1435,52 +3647,50 @@ impl Default for PokerState {
 
 impl fmt::Display for PokerState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let repr = match &self {
-            PokerState::Lobby(_) => "in lobby",
-            PokerState::SeatPlayers(_) => "seating players",
-            PokerState::MoveButton(_) => "moving button",
-            PokerState::CollectBlinds(ref game) => {
-                let big_blind = game.data.big_blind;
-                let big_blind_username = &game.data.players[game.data.big_blind_idx].user.name;
-                let small_blind = game.data.small_blind;
-                let small_blind_username = &game.data.players[game.data.small_blind_idx].user.name;
-                &format!("collecting ${big_blind} from {big_blind_username} and ${small_blind} from {small_blind_username}")
-            }
-            PokerState::Deal(_) => "dealing cards",
-            PokerState::TakeAction(ref game) => {
-                if game.is_ready_for_next_phase() {
-                    "end of betting round"
-                } else {
-                    "betting round transition"
-                }
-            }
-            PokerState::Flop(_) => "the flop",
-            PokerState::Turn(_) => "the turn",
-            PokerState::River(_) => "the river",
-            PokerState::ShowHands(ref game) => {
-                let num_pots = game.get_num_pots();
-                match num_pots {
-                    1 => "showing main pot",
-                    i => &format!("showing side pot #{}", i - 1),
-                }
-            }
-            PokerState::DistributePot(ref game) => {
-                let num_pots = game.get_num_pots();
-                match num_pots {
-                    1 => "distributing main pot",
-                    i => &format!("distributing side pot #{}", i - 1),
-                }
-            }
-            PokerState::RemovePlayers(_) => "updating players that joined spectators or left",
-            PokerState::DivideDonations(_) => "dividing donations",
-            PokerState::UpdateBlinds(_) => "updating blinds",
-            PokerState::BootPlayers(_) => "spectating players that can't afford the big blind",
-        };
-        write!(f, "{repr}")
+        write!(f, "{}", self.phase())
     }
 }
 
 impl PokerState {
+    pub fn phase(&self) -> GamePhase {
+        match self {
+            PokerState::Lobby(_) => GamePhase::Lobby,
+            PokerState::SeatPlayers(_) => GamePhase::SeatingPlayers,
+            PokerState::MoveButton(_) => GamePhase::MovingButton,
+            PokerState::CollectBlinds(ref game) if game.data.bomb_pot => {
+                GamePhase::CollectingAntes {
+                    ante: game.get_bomb_pot_ante(),
+                }
+            }
+            PokerState::CollectBlinds(ref game) => GamePhase::CollectingBlinds {
+                small: game.data.small_blind,
+                small_from: game.data.players[game.data.small_blind_idx]
+                    .user
+                    .name
+                    .clone(),
+                big: game.data.big_blind,
+                big_from: game.data.players[game.data.big_blind_idx].user.name.clone(),
+            },
+            PokerState::Deal(_) => GamePhase::Dealing,
+            PokerState::TakeAction(ref game) => GamePhase::Betting {
+                round_over: game.is_ready_for_next_phase(),
+            },
+            PokerState::Flop(_) => GamePhase::Flop,
+            PokerState::Turn(_) => GamePhase::Turn,
+            PokerState::River(_) => GamePhase::River,
+            PokerState::ShowHands(ref game) => GamePhase::ShowingPot {
+                index: game.get_num_pots().saturating_sub(1),
+            },
+            PokerState::DistributePot(ref game) => GamePhase::DistributingPot {
+                index: game.get_num_pots().saturating_sub(1),
+            },
+            PokerState::RemovePlayers(_) => GamePhase::RemovingPlayers,
+            PokerState::DivideDonations(_) => GamePhase::DividingDonations,
+            PokerState::UpdateBlinds(_) => GamePhase::UpdatingBlinds,
+            PokerState::BootPlayers(_) => GamePhase::BootingPlayers,
+        }
+    }
+
     pub fn get_action_options(&self) -> Option<HashSet<Action>> {
         match self {
             PokerState::TakeAction(ref game) => game.get_action_options(),
@@ -1488,93 +3698,577 @@ impl PokerState {
         }
     }
 
-    pub fn get_next_action_username(&self) -> Option<String> {
+    pub fn get_auto_action(&self) -> Option<Action> {
         match self {
-            PokerState::TakeAction(ref game) => game.get_next_action_username(),
+            PokerState::TakeAction(ref game) => game.get_auto_action(),
             _ => None,
         }
     }
 
-    pub fn get_views(&self) -> GameViews {
+    pub fn get_max_raise(&self) -> Option<Usd> {
         match self {
-            PokerState::Lobby(ref game) => game.get_views(),
-            PokerState::SeatPlayers(ref game) => game.get_views(),
-            PokerState::MoveButton(ref game) => game.get_views(),
-            PokerState::CollectBlinds(ref game) => game.get_views(),
-            PokerState::Deal(ref game) => game.get_views(),
-            PokerState::TakeAction(ref game) => game.get_views(),
-            PokerState::Flop(ref game) => game.get_views(),
-            PokerState::Turn(ref game) => game.get_views(),
-            PokerState::River(ref game) => game.get_views(),
-            PokerState::ShowHands(ref game) => game.get_views(),
-            PokerState::DistributePot(ref game) => game.get_views(),
-            PokerState::RemovePlayers(ref game) => game.get_views(),
-            PokerState::DivideDonations(ref game) => game.get_views(),
-            PokerState::UpdateBlinds(ref game) => game.get_views(),
-            PokerState::BootPlayers(ref game) => game.get_views(),
+            PokerState::TakeAction(ref game) => game.get_max_raise(),
+            _ => None,
         }
     }
 
-    pub fn init_start(&mut self, username: &str) -> Result<(), UserError> {
+    /// Return how what's left of the pot will be distributed if the pot
+    /// is being distributed.
+    pub fn get_distribution_outcome(&mut self) -> Option<DistributionOutcome> {
         match self {
-            PokerState::Lobby(ref mut game) => {
-                if game.contains_waitlister(username) || game.contains_player(username) {
-                    game.init_start()?;
-                    Ok(())
-                } else {
-                    Err(UserError::CannotStartGame)
-                }
-            }
-            PokerState::SeatPlayers(_) => Err(UserError::GameAlreadyStarting),
-            _ => Err(UserError::GameAlreadyInProgress),
+            PokerState::DistributePot(ref mut game) => Some(game.get_distribution_outcome()),
+            _ => None,
         }
     }
 
-    pub fn new() -> Self {
-        let game = Game::<Lobby>::new();
-        PokerState::Lobby(game)
+    pub fn get_decision_context(&self) -> Option<DecisionContext> {
+        match self {
+            PokerState::TakeAction(ref game) => game.get_decision_context(),
+            _ => None,
+        }
     }
 
-    fn phase_transition(game: Game<TakeAction>) -> PokerState {
-        match game.get_num_community_cards() {
-            0 => PokerState::Flop(game.into()),
-            3 => PokerState::Turn(game.into()),
-            4 => PokerState::River(game.into()),
-            5 => PokerState::ShowHands(game.into()),
-            _ => unreachable!(
-                "there can only be 0, 3, 4, or 5 community cards on the board at a time"
-            ),
+    pub fn get_next_action_username(&self) -> Option<String> {
+        match self {
+            PokerState::TakeAction(ref game) => game.get_next_action_username(),
+            _ => None,
         }
     }
 
-    pub fn show_hand(&mut self, username: &str) -> Result<(), UserError> {
+    /// Return the identifier of the current turn if it's someone's turn.
+    pub fn get_turn_id(&self) -> Option<TurnId> {
         match self {
-            PokerState::ShowHands(ref mut game) => {
-                game.show_hand(username)?;
-                Ok(())
-            }
-            PokerState::DistributePot(ref mut game) => {
-                game.show_hand(username)?;
-                Ok(())
-            }
-            PokerState::RemovePlayers(ref mut game) => {
-                game.show_hand(username)?;
-                Ok(())
-            }
-            PokerState::UpdateBlinds(ref mut game) => {
-                game.show_hand(username)?;
-                Ok(())
+            PokerState::TakeAction(ref game) if game.data.next_action_idx.is_some() => {
+                Some(game.get_turn_id())
             }
-            _ => Err(UserError::CannotShowHand),
+            _ => None,
         }
     }
 
-    pub fn step(self) -> Self {
+    pub fn accept_insurance(&mut self, username: &str, insurer: &str) -> Result<(), UserError> {
         match self {
-            PokerState::Lobby(game) => {
-                if game.is_ready_to_start() {
-                    PokerState::SeatPlayers(game.into())
-                } else {
+            PokerState::TakeAction(ref mut game) => game.accept_insurance(username, insurer),
+            PokerState::Flop(ref mut game) => game.accept_insurance(username, insurer),
+            PokerState::Turn(ref mut game) => game.accept_insurance(username, insurer),
+            PokerState::River(ref mut game) => game.accept_insurance(username, insurer),
+            _ => Err(UserError::CannotInsure),
+        }
+    }
+
+    pub fn add_chips(&mut self, username: &str, amount: Usd) -> Result<(), UserError> {
+        match self {
+            PokerState::Lobby(ref mut game) => {
+                game.add_chips(username, amount)?;
+            }
+            PokerState::SeatPlayers(ref mut game) => {
+                game.add_chips(username, amount)?;
+            }
+            PokerState::MoveButton(ref mut game) => {
+                game.add_chips(username, amount)?;
+            }
+            PokerState::CollectBlinds(ref mut game) => {
+                game.add_chips(username, amount)?;
+            }
+            PokerState::Deal(ref mut game) => {
+                game.add_chips(username, amount)?;
+            }
+            PokerState::TakeAction(ref mut game) => {
+                game.add_chips(username, amount)?;
+            }
+            PokerState::Flop(ref mut game) => {
+                game.add_chips(username, amount)?;
+            }
+            PokerState::Turn(ref mut game) => {
+                game.add_chips(username, amount)?;
+            }
+            PokerState::River(ref mut game) => {
+                game.add_chips(username, amount)?;
+            }
+            PokerState::ShowHands(ref mut game) => {
+                game.add_chips(username, amount)?;
+            }
+            PokerState::DistributePot(ref mut game) => {
+                game.add_chips(username, amount)?;
+            }
+            PokerState::RemovePlayers(ref mut game) => {
+                game.add_chips(username, amount)?;
+            }
+            PokerState::DivideDonations(ref mut game) => {
+                game.add_chips(username, amount)?;
+            }
+            PokerState::UpdateBlinds(ref mut game) => {
+                game.add_chips(username, amount)?;
+            }
+            PokerState::BootPlayers(ref mut game) => {
+                game.add_chips(username, amount)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn change_seat(&mut self, username: &str, seat_idx: SeatIndex) -> Result<(), UserError> {
+        match self {
+            PokerState::Lobby(ref mut game) => {
+                game.change_seat(username, seat_idx)?;
+            }
+            PokerState::SeatPlayers(ref mut game) => {
+                game.change_seat(username, seat_idx)?;
+            }
+            PokerState::MoveButton(ref mut game) => {
+                game.change_seat(username, seat_idx)?;
+            }
+            PokerState::CollectBlinds(ref mut game) => {
+                game.change_seat(username, seat_idx)?;
+            }
+            PokerState::Deal(ref mut game) => {
+                game.change_seat(username, seat_idx)?;
+            }
+            PokerState::TakeAction(ref mut game) => {
+                game.change_seat(username, seat_idx)?;
+            }
+            PokerState::Flop(ref mut game) => {
+                game.change_seat(username, seat_idx)?;
+            }
+            PokerState::Turn(ref mut game) => {
+                game.change_seat(username, seat_idx)?;
+            }
+            PokerState::River(ref mut game) => {
+                game.change_seat(username, seat_idx)?;
+            }
+            PokerState::ShowHands(ref mut game) => {
+                game.change_seat(username, seat_idx)?;
+            }
+            PokerState::DistributePot(ref mut game) => {
+                game.change_seat(username, seat_idx)?;
+            }
+            PokerState::RemovePlayers(ref mut game) => {
+                game.change_seat(username, seat_idx)?;
+            }
+            PokerState::DivideDonations(ref mut game) => {
+                game.change_seat(username, seat_idx)?;
+            }
+            PokerState::UpdateBlinds(ref mut game) => {
+                game.change_seat(username, seat_idx)?;
+            }
+            PokerState::BootPlayers(ref mut game) => {
+                game.change_seat(username, seat_idx)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the game's data, whatever state the game is in.
+    fn data(&self) -> &GameData {
+        match self {
+            PokerState::Lobby(ref game) => &game.data,
+            PokerState::SeatPlayers(ref game) => &game.data,
+            PokerState::MoveButton(ref game) => &game.data,
+            PokerState::CollectBlinds(ref game) => &game.data,
+            PokerState::Deal(ref game) => &game.data,
+            PokerState::TakeAction(ref game) => &game.data,
+            PokerState::Flop(ref game) => &game.data,
+            PokerState::Turn(ref game) => &game.data,
+            PokerState::River(ref game) => &game.data,
+            PokerState::ShowHands(ref game) => &game.data,
+            PokerState::DistributePot(ref game) => &game.data,
+            PokerState::RemovePlayers(ref game) => &game.data,
+            PokerState::DivideDonations(ref game) => &game.data,
+            PokerState::UpdateBlinds(ref game) => &game.data,
+            PokerState::BootPlayers(ref game) => &game.data,
+        }
+    }
+
+    pub fn drain_events(&mut self) -> VecDeque<GameEvent> {
+        match self {
+            PokerState::Lobby(ref mut game) => game.drain_events(),
+            PokerState::SeatPlayers(ref mut game) => game.drain_events(),
+            PokerState::MoveButton(ref mut game) => game.drain_events(),
+            PokerState::CollectBlinds(ref mut game) => game.drain_events(),
+            PokerState::Deal(ref mut game) => game.drain_events(),
+            PokerState::TakeAction(ref mut game) => game.drain_events(),
+            PokerState::Flop(ref mut game) => game.drain_events(),
+            PokerState::Turn(ref mut game) => game.drain_events(),
+            PokerState::River(ref mut game) => game.drain_events(),
+            PokerState::ShowHands(ref mut game) => game.drain_events(),
+            PokerState::DistributePot(ref mut game) => game.drain_events(),
+            PokerState::RemovePlayers(ref mut game) => game.drain_events(),
+            PokerState::DivideDonations(ref mut game) => game.drain_events(),
+            PokerState::UpdateBlinds(ref mut game) => game.drain_events(),
+            PokerState::BootPlayers(ref mut game) => game.drain_events(),
+        }
+    }
+
+    pub fn get_hand_id(&self) -> HandId {
+        match self {
+            PokerState::Lobby(ref game) => game.get_hand_id(),
+            PokerState::SeatPlayers(ref game) => game.get_hand_id(),
+            PokerState::MoveButton(ref game) => game.get_hand_id(),
+            PokerState::CollectBlinds(ref game) => game.get_hand_id(),
+            PokerState::Deal(ref game) => game.get_hand_id(),
+            PokerState::TakeAction(ref game) => game.get_hand_id(),
+            PokerState::Flop(ref game) => game.get_hand_id(),
+            PokerState::Turn(ref game) => game.get_hand_id(),
+            PokerState::River(ref game) => game.get_hand_id(),
+            PokerState::ShowHands(ref game) => game.get_hand_id(),
+            PokerState::DistributePot(ref game) => game.get_hand_id(),
+            PokerState::RemovePlayers(ref game) => game.get_hand_id(),
+            PokerState::DivideDonations(ref game) => game.get_hand_id(),
+            PokerState::UpdateBlinds(ref game) => game.get_hand_id(),
+            PokerState::BootPlayers(ref game) => game.get_hand_id(),
+        }
+    }
+
+    pub fn get_leaderboard(&self) -> Leaderboard {
+        match self {
+            PokerState::Lobby(ref game) => game.get_leaderboard(),
+            PokerState::SeatPlayers(ref game) => game.get_leaderboard(),
+            PokerState::MoveButton(ref game) => game.get_leaderboard(),
+            PokerState::CollectBlinds(ref game) => game.get_leaderboard(),
+            PokerState::Deal(ref game) => game.get_leaderboard(),
+            PokerState::TakeAction(ref game) => game.get_leaderboard(),
+            PokerState::Flop(ref game) => game.get_leaderboard(),
+            PokerState::Turn(ref game) => game.get_leaderboard(),
+            PokerState::River(ref game) => game.get_leaderboard(),
+            PokerState::ShowHands(ref game) => game.get_leaderboard(),
+            PokerState::DistributePot(ref game) => game.get_leaderboard(),
+            PokerState::RemovePlayers(ref game) => game.get_leaderboard(),
+            PokerState::DivideDonations(ref game) => game.get_leaderboard(),
+            PokerState::UpdateBlinds(ref game) => game.get_leaderboard(),
+            PokerState::BootPlayers(ref game) => game.get_leaderboard(),
+        }
+    }
+
+    pub fn as_omniscient_view(&self) -> GameView {
+        match self {
+            PokerState::Lobby(ref game) => game.as_omniscient_view(),
+            PokerState::SeatPlayers(ref game) => game.as_omniscient_view(),
+            PokerState::MoveButton(ref game) => game.as_omniscient_view(),
+            PokerState::CollectBlinds(ref game) => game.as_omniscient_view(),
+            PokerState::Deal(ref game) => game.as_omniscient_view(),
+            PokerState::TakeAction(ref game) => game.as_omniscient_view(),
+            PokerState::Flop(ref game) => game.as_omniscient_view(),
+            PokerState::Turn(ref game) => game.as_omniscient_view(),
+            PokerState::River(ref game) => game.as_omniscient_view(),
+            PokerState::ShowHands(ref game) => game.as_omniscient_view(),
+            PokerState::DistributePot(ref game) => game.as_omniscient_view(),
+            PokerState::RemovePlayers(ref game) => game.as_omniscient_view(),
+            PokerState::DivideDonations(ref game) => game.as_omniscient_view(),
+            PokerState::UpdateBlinds(ref game) => game.as_omniscient_view(),
+            PokerState::BootPlayers(ref game) => game.as_omniscient_view(),
+        }
+    }
+
+    pub fn get_views(&self) -> GameViews {
+        match self {
+            PokerState::Lobby(ref game) => game.get_views(),
+            PokerState::SeatPlayers(ref game) => game.get_views(),
+            PokerState::MoveButton(ref game) => game.get_views(),
+            PokerState::CollectBlinds(ref game) => game.get_views(),
+            PokerState::Deal(ref game) => game.get_views(),
+            PokerState::TakeAction(ref game) => game.get_views(),
+            PokerState::Flop(ref game) => game.get_views(),
+            PokerState::Turn(ref game) => game.get_views(),
+            PokerState::River(ref game) => game.get_views(),
+            PokerState::ShowHands(ref game) => game.get_views(),
+            PokerState::DistributePot(ref game) => game.get_views(),
+            PokerState::RemovePlayers(ref game) => game.get_views(),
+            PokerState::DivideDonations(ref game) => game.get_views(),
+            PokerState::UpdateBlinds(ref game) => game.get_views(),
+            PokerState::BootPlayers(ref game) => game.get_views(),
+        }
+    }
+
+    pub fn is_disconnected(&self, username: &str) -> bool {
+        match self {
+            PokerState::Lobby(ref game) => game.is_disconnected(username),
+            PokerState::SeatPlayers(ref game) => game.is_disconnected(username),
+            PokerState::MoveButton(ref game) => game.is_disconnected(username),
+            PokerState::CollectBlinds(ref game) => game.is_disconnected(username),
+            PokerState::Deal(ref game) => game.is_disconnected(username),
+            PokerState::TakeAction(ref game) => game.is_disconnected(username),
+            PokerState::Flop(ref game) => game.is_disconnected(username),
+            PokerState::Turn(ref game) => game.is_disconnected(username),
+            PokerState::River(ref game) => game.is_disconnected(username),
+            PokerState::ShowHands(ref game) => game.is_disconnected(username),
+            PokerState::DistributePot(ref game) => game.is_disconnected(username),
+            PokerState::RemovePlayers(ref game) => game.is_disconnected(username),
+            PokerState::DivideDonations(ref game) => game.is_disconnected(username),
+            PokerState::UpdateBlinds(ref game) => game.is_disconnected(username),
+            PokerState::BootPlayers(ref game) => game.is_disconnected(username),
+        }
+    }
+
+    pub fn init_start(&mut self, username: &str) -> Result<(), UserError> {
+        match self {
+            PokerState::Lobby(ref mut game) => {
+                if game.contains_waitlister(username) || game.contains_player(username) {
+                    game.init_start()?;
+                    Ok(())
+                } else {
+                    Err(UserError::CannotStartGame)
+                }
+            }
+            PokerState::SeatPlayers(_) => Err(UserError::GameAlreadyStarting),
+            _ => Err(UserError::GameAlreadyInProgress),
+        }
+    }
+
+    /// Start the game on behalf of the game's autostart policy rather
+    /// than a user.
+    pub fn init_autostart(&mut self) -> Result<(), UserError> {
+        match self {
+            PokerState::Lobby(ref mut game) => game.init_start(),
+            PokerState::SeatPlayers(_) => Err(UserError::GameAlreadyStarting),
+            _ => Err(UserError::GameAlreadyInProgress),
+        }
+    }
+
+    /// Stop new hands from being started, letting the hand in progress
+    /// finish.
+    pub fn end_session(&mut self) {
+        match self {
+            PokerState::Lobby(ref mut game) => game.end_session(),
+            PokerState::SeatPlayers(ref mut game) => game.end_session(),
+            PokerState::MoveButton(ref mut game) => game.end_session(),
+            PokerState::CollectBlinds(ref mut game) => game.end_session(),
+            PokerState::Deal(ref mut game) => game.end_session(),
+            PokerState::TakeAction(ref mut game) => game.end_session(),
+            PokerState::Flop(ref mut game) => game.end_session(),
+            PokerState::Turn(ref mut game) => game.end_session(),
+            PokerState::River(ref mut game) => game.end_session(),
+            PokerState::ShowHands(ref mut game) => game.end_session(),
+            PokerState::DistributePot(ref mut game) => game.end_session(),
+            PokerState::RemovePlayers(ref mut game) => game.end_session(),
+            PokerState::DivideDonations(ref mut game) => game.end_session(),
+            PokerState::UpdateBlinds(ref mut game) => game.end_session(),
+            PokerState::BootPlayers(ref mut game) => game.end_session(),
+        }
+    }
+
+    /// Switch to a different format, but only between hands.
+    pub fn change_format(
+        &mut self,
+        name: &str,
+        settings: GameSettings,
+        reset_stacks: bool,
+    ) -> Result<(), UserError> {
+        match self {
+            PokerState::Lobby(ref mut game) => {
+                game.change_format(name, settings, reset_stacks);
+                Ok(())
+            }
+            _ => Err(UserError::GameAlreadyInProgress),
+        }
+    }
+
+    pub fn is_ready_to_autostart(&self) -> bool {
+        match self {
+            PokerState::Lobby(ref game) => game.is_ready_to_autostart(),
+            _ => false,
+        }
+    }
+
+    /// Return whether the session has ended and its last hand is over,
+    /// so no more hands will be played.
+    pub fn is_session_over(&self) -> bool {
+        match self {
+            PokerState::Lobby(ref game) => game.data.session_ending,
+            _ => false,
+        }
+    }
+
+    /// Return whether the game is in the lobby without enough players to
+    /// start, so it's waiting on spectators to play.
+    pub fn is_waiting_for_players(&self) -> bool {
+        match self {
+            PokerState::Lobby(ref game) => game.get_num_potential_players() < MIN_PLAYERS,
+            _ => false,
+        }
+    }
+
+    pub fn new() -> Self {
+        let game = Game::<Lobby>::new();
+        PokerState::Lobby(game)
+    }
+
+    fn phase_transition(game: Game<TakeAction>) -> PokerState {
+        if !game.is_contested() {
+            return PokerState::DistributePot(game.into());
+        }
+        match game.get_num_community_cards() {
+            0 => PokerState::Flop(game.into()),
+            3 => PokerState::Turn(game.into()),
+            4 => PokerState::River(game.into()),
+            5 => PokerState::ShowHands(game.into()),
+            _ => unreachable!(
+                "there can only be 0, 3, 4, or 5 community cards on the board at a time"
+            ),
+        }
+    }
+
+    pub fn muck_hand(&mut self, username: &str) -> Result<(), UserError> {
+        match self {
+            PokerState::TakeAction(ref mut game) => {
+                game.muck_hand(username)?;
+                Ok(())
+            }
+            PokerState::Flop(ref mut game) => {
+                game.muck_hand(username)?;
+                Ok(())
+            }
+            PokerState::Turn(ref mut game) => {
+                game.muck_hand(username)?;
+                Ok(())
+            }
+            PokerState::River(ref mut game) => {
+                game.muck_hand(username)?;
+                Ok(())
+            }
+            PokerState::ShowHands(ref mut game) => {
+                game.muck_hand(username)?;
+                Ok(())
+            }
+            _ => Err(UserError::CannotMuckHand),
+        }
+    }
+
+    pub fn rename_user(&mut self, username: &str, new_username: &str) -> Result<(), UserError> {
+        match self {
+            PokerState::Lobby(ref mut game) => game.rename_user(username, new_username),
+            PokerState::SeatPlayers(ref mut game) => game.rename_user(username, new_username),
+            PokerState::MoveButton(ref mut game) => game.rename_user(username, new_username),
+            PokerState::CollectBlinds(ref mut game) => game.rename_user(username, new_username),
+            PokerState::Deal(ref mut game) => game.rename_user(username, new_username),
+            PokerState::TakeAction(ref mut game) => game.rename_user(username, new_username),
+            PokerState::Flop(ref mut game) => game.rename_user(username, new_username),
+            PokerState::Turn(ref mut game) => game.rename_user(username, new_username),
+            PokerState::River(ref mut game) => game.rename_user(username, new_username),
+            PokerState::ShowHands(ref mut game) => game.rename_user(username, new_username),
+            PokerState::DistributePot(ref mut game) => game.rename_user(username, new_username),
+            PokerState::RemovePlayers(ref mut game) => game.rename_user(username, new_username),
+            PokerState::DivideDonations(ref mut game) => game.rename_user(username, new_username),
+            PokerState::UpdateBlinds(ref mut game) => game.rename_user(username, new_username),
+            PokerState::BootPlayers(ref mut game) => game.rename_user(username, new_username),
+        }
+    }
+
+    pub fn set_profile(&mut self, username: &str, profile: Profile) -> Result<(), UserError> {
+        match self {
+            PokerState::Lobby(ref mut game) => game.set_profile(username, profile),
+            PokerState::SeatPlayers(ref mut game) => game.set_profile(username, profile),
+            PokerState::MoveButton(ref mut game) => game.set_profile(username, profile),
+            PokerState::CollectBlinds(ref mut game) => game.set_profile(username, profile),
+            PokerState::Deal(ref mut game) => game.set_profile(username, profile),
+            PokerState::TakeAction(ref mut game) => game.set_profile(username, profile),
+            PokerState::Flop(ref mut game) => game.set_profile(username, profile),
+            PokerState::Turn(ref mut game) => game.set_profile(username, profile),
+            PokerState::River(ref mut game) => game.set_profile(username, profile),
+            PokerState::ShowHands(ref mut game) => game.set_profile(username, profile),
+            PokerState::DistributePot(ref mut game) => game.set_profile(username, profile),
+            PokerState::RemovePlayers(ref mut game) => game.set_profile(username, profile),
+            PokerState::DivideDonations(ref mut game) => game.set_profile(username, profile),
+            PokerState::UpdateBlinds(ref mut game) => game.set_profile(username, profile),
+            PokerState::BootPlayers(ref mut game) => game.set_profile(username, profile),
+        }
+    }
+
+    pub fn set_auto_rebuy(&mut self, username: &str, enabled: bool) -> Result<(), UserError> {
+        match self {
+            PokerState::Lobby(ref mut game) => game.set_auto_rebuy(username, enabled),
+            PokerState::SeatPlayers(ref mut game) => game.set_auto_rebuy(username, enabled),
+            PokerState::MoveButton(ref mut game) => game.set_auto_rebuy(username, enabled),
+            PokerState::CollectBlinds(ref mut game) => game.set_auto_rebuy(username, enabled),
+            PokerState::Deal(ref mut game) => game.set_auto_rebuy(username, enabled),
+            PokerState::TakeAction(ref mut game) => game.set_auto_rebuy(username, enabled),
+            PokerState::Flop(ref mut game) => game.set_auto_rebuy(username, enabled),
+            PokerState::Turn(ref mut game) => game.set_auto_rebuy(username, enabled),
+            PokerState::River(ref mut game) => game.set_auto_rebuy(username, enabled),
+            PokerState::ShowHands(ref mut game) => game.set_auto_rebuy(username, enabled),
+            PokerState::DistributePot(ref mut game) => game.set_auto_rebuy(username, enabled),
+            PokerState::RemovePlayers(ref mut game) => game.set_auto_rebuy(username, enabled),
+            PokerState::DivideDonations(ref mut game) => game.set_auto_rebuy(username, enabled),
+            PokerState::UpdateBlinds(ref mut game) => game.set_auto_rebuy(username, enabled),
+            PokerState::BootPlayers(ref mut game) => game.set_auto_rebuy(username, enabled),
+        }
+    }
+
+    pub fn offer_insurance(
+        &mut self,
+        username: &str,
+        insured: &str,
+        premium: Usd,
+        odds_numer: Usd,
+        odds_denom: Usd,
+    ) -> Result<(), UserError> {
+        match self {
+            PokerState::TakeAction(ref mut game) => {
+                game.offer_insurance(username, insured, premium, odds_numer, odds_denom)
+            }
+            PokerState::Flop(ref mut game) => {
+                game.offer_insurance(username, insured, premium, odds_numer, odds_denom)
+            }
+            PokerState::Turn(ref mut game) => {
+                game.offer_insurance(username, insured, premium, odds_numer, odds_denom)
+            }
+            PokerState::River(ref mut game) => {
+                game.offer_insurance(username, insured, premium, odds_numer, odds_denom)
+            }
+            _ => Err(UserError::CannotInsure),
+        }
+    }
+
+    pub fn place_side_bet(
+        &mut self,
+        username: &str,
+        player: &str,
+        amount: Usd,
+    ) -> Result<(), UserError> {
+        match self {
+            PokerState::TakeAction(ref mut game) => game.place_side_bet(username, player, amount),
+            _ => Err(UserError::CannotSideBet),
+        }
+    }
+
+    pub fn show_card(&mut self, username: &str, card_idx: usize) -> Result<(), UserError> {
+        match self {
+            PokerState::Flop(ref mut game) => game.show_card(username, card_idx),
+            PokerState::Turn(ref mut game) => game.show_card(username, card_idx),
+            PokerState::River(ref mut game) => game.show_card(username, card_idx),
+            PokerState::TakeAction(ref mut game) => game.show_card(username, card_idx),
+            PokerState::ShowHands(ref mut game) => game.show_card(username, card_idx),
+            PokerState::DistributePot(ref mut game) => game.show_card(username, card_idx),
+            PokerState::RemovePlayers(ref mut game) => game.show_card(username, card_idx),
+            PokerState::DivideDonations(ref mut game) => game.show_card(username, card_idx),
+            PokerState::UpdateBlinds(ref mut game) => game.show_card(username, card_idx),
+            _ => Err(UserError::CannotShowHand),
+        }
+    }
+
+    pub fn show_hand(&mut self, username: &str) -> Result<(), UserError> {
+        match self {
+            PokerState::Flop(ref mut game) => game.show_hand(username),
+            PokerState::Turn(ref mut game) => game.show_hand(username),
+            PokerState::River(ref mut game) => game.show_hand(username),
+            PokerState::TakeAction(ref mut game) => game.show_hand(username),
+            PokerState::ShowHands(ref mut game) => game.show_hand(username),
+            PokerState::DistributePot(ref mut game) => game.show_hand(username),
+            PokerState::RemovePlayers(ref mut game) => game.show_hand(username),
+            PokerState::DivideDonations(ref mut game) => game.show_hand(username),
+            PokerState::UpdateBlinds(ref mut game) => game.show_hand(username),
+            _ => Err(UserError::CannotShowHand),
+        }
+    }
+
+    pub fn step(self) -> Self {
+        match self {
+            PokerState::Lobby(mut game) => {
+                if game.is_ready_to_start() {
+                    PokerState::SeatPlayers(game.into())
+                } else {
+                    // Players left before the game could start, so the
+                    // start is dropped rather than firing once more
+                    // players show up.
+                    game.state.start_game = false;
                     PokerState::Lobby(game)
                 }
             }
@@ -1587,12 +4281,19 @@ impl PokerState {
             }
             PokerState::MoveButton(game) => PokerState::CollectBlinds(game.into()),
             PokerState::CollectBlinds(game) => PokerState::Deal(game.into()),
-            PokerState::Deal(game) => PokerState::TakeAction(game.into()),
+            PokerState::Deal(game) => {
+                if game.data.bomb_pot {
+                    PokerState::Flop(game.into())
+                } else {
+                    PokerState::TakeAction(game.into())
+                }
+            }
             PokerState::TakeAction(mut game) => {
                 if game.is_ready_for_next_phase() {
                     PokerState::phase_transition(game)
                 } else {
-                    game.act(Action::Fold).expect("force folding is OK");
+                    let action = game.get_auto_action().expect("a player can act");
+                    game.act(action).expect("auto acting is OK");
                     if game.is_ready_for_next_phase() {
                         PokerState::phase_transition(game)
                     } else {
@@ -1636,6 +4337,30 @@ impl PokerState {
         }
     }
 
+    /// Return how a server should pace stepping through the state, given
+    /// how long it pauses on each kind of state.
+    pub fn step_hint(&self, durations: &StepDurations) -> StepHint {
+        let min_wait = match self {
+            PokerState::Lobby(_) => durations.lobby,
+            PokerState::Deal(_) => durations.deal,
+            PokerState::TakeAction(ref game) if game.is_contested() => durations.betting,
+            PokerState::Flop(ref game) if game.is_contested() => durations.street,
+            PokerState::Turn(ref game) if game.is_contested() => durations.street,
+            PokerState::River(ref game) if game.is_contested() => durations.street,
+            PokerState::ShowHands(ref game) if game.is_contested() => durations.show_hands,
+            PokerState::DistributePot(ref game) if game.is_contested() => durations.distribute_pot,
+            _ => durations.admin,
+        };
+        let waits_for_action = match self {
+            PokerState::TakeAction(ref game) => game.get_action_options().is_some(),
+            _ => false,
+        };
+        StepHint {
+            min_wait,
+            waits_for_action,
+        }
+    }
+
     pub fn take_action(&mut self, username: &str, action: Action) -> Result<Action, UserError> {
         match self {
             PokerState::TakeAction(ref mut game)
@@ -1647,6 +4372,26 @@ impl PokerState {
             _ => Err(UserError::OutOfTurnAction),
         }
     }
+
+    /// Take an action meant for the given turn. Actions that arrive after
+    /// their turn is over are rejected, even if it's the player's turn
+    /// again by then, so they aren't applied to a turn the player hasn't
+    /// seen yet.
+    pub fn take_turn_action(
+        &mut self,
+        username: &str,
+        action: Action,
+        turn_id: TurnId,
+    ) -> Result<Action, UserError> {
+        match self {
+            PokerState::TakeAction(ref game)
+                if game.is_turn(username) && game.get_turn_id() != turn_id =>
+            {
+                Err(UserError::StaleAction)
+            }
+            _ => self.take_action(username, action),
+        }
+    }
 }
 
 macro_rules! impl_user_managers {
@@ -1700,13 +4445,26 @@ macro_rules! impl_user_managers {
                         game.$name(username)?;
                     },
                 }
+                // Players that leave in the middle of a betting round
+                // shouldn't hold it up.
+                if let PokerState::TakeAction(ref mut game) = self {
+                    game.fold_players_leaving_round();
+                }
                 Ok(())
             })*
         }
     }
 }
 
-impl_user_managers!(new_user, remove_user, spectate_user, waitlist_user);
+impl_user_managers!(
+    disconnect_user,
+    new_user,
+    rail_user,
+    rejoin_user,
+    remove_user,
+    spectate_user,
+    waitlist_user
+);
 
 impl From<GameSettings> for PokerState {
     fn from(value: GameSettings) -> Self {
@@ -1717,15 +4475,25 @@ impl From<GameSettings> for PokerState {
 
 #[cfg(test)]
 mod game_tests {
-    use std::collections::HashSet;
+    use std::{
+        collections::{HashSet, VecDeque},
+        sync::Arc,
+    };
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
 
     use crate::entities::PlayerState;
 
     use super::{
-        entities::{Action, Card, Suit},
-        BootPlayers, CollectBlinds, Deal, DistributePot, DivideDonations, Flop, Game, Lobby,
-        MoveButton, RemovePlayers, River, SeatPlayers, ShowHands, TakeAction, Turn, UpdateBlinds,
-        UserError,
+        constants::{MAX_PLAYERS, MAX_TAGLINE_LENGTH, MAX_USER_INPUT_LENGTH},
+        entities::{
+            Action, Card, DecisionContext, GameEvent, GameVariant, GameViews, HoleCards,
+            LimitStructure, PendingAction, PotSegment, Privacy, Profile, Rank, Reveal, SideBet,
+            SpectatorPool, Suit, Usd, DEFAULT_BUY_IN, DEFAULT_MIN_BIG_BLIND,
+        },
+        functional, resolve_pending_action, validate_action, Autostart, BootPlayers, CollectBlinds,
+        Deal, DistributePot, DivideDonations, Flop, Game, Lobby, MoveButton, RemovePlayers, River,
+        SeatPlayers, ShowHands, TakeAction, Turn, UpdateBlinds, UserError,
     };
 
     fn init_2_player_game() -> Game<SeatPlayers> {
@@ -1780,24 +4548,77 @@ mod game_tests {
         game
     }
 
-    fn init_game_at_showdown_with_1_all_in() -> Game<ShowHands> {
+    fn init_game_at_distribute_pot_with_1_all_in() -> Game<DistributePot> {
         let mut game = init_game_at_deal();
         game.act(Action::AllIn).unwrap();
         game.act(Action::Fold).unwrap();
         game.act(Action::Fold).unwrap();
-        let game: Game<Flop> = game.into();
-        let game: Game<Turn> = game.into();
-        let game: Game<River> = game.into();
-        let game: Game<ShowHands> = game.into();
+        let game: Game<DistributePot> = game.into();
         game
     }
 
-    fn init_game_at_showdown_with_2_all_ins() -> Game<ShowHands> {
-        let mut game = init_game_at_deal();
+    /// Return a game at the showdown where two players are all-in, the
+    /// first of which bought insurance from the player that folded,
+    /// along with the indices of the insurer and insured, respectively.
+    fn init_insured_game_at_showdown() -> (Game<ShowHands>, usize, usize) {
+        let mut game = init_3_player_game();
+        game.data.settings.insurance = true;
+        let game: Game<MoveButton> = game.into();
+        let game: Game<CollectBlinds> = game.into();
+        let game: Game<Deal> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        let insurer_idx = game.data.next_action_idx.unwrap();
         game.act(Action::Fold).unwrap();
+        let insured_idx = game.data.next_action_idx.unwrap();
+        let insured = game.data.players[insured_idx].user.name.clone();
+        let insurer = game.data.players[insurer_idx].user.name.clone();
         game.act(Action::AllIn).unwrap();
+
+        // Can't insure while there's still betting to be done.
+        assert_eq!(
+            game.offer_insurance(&insurer, &insured, 20, 3, 1),
+            Err(UserError::CannotInsure)
+        );
         game.act(Action::AllIn).unwrap();
-        let game: Game<Flop> = game.into();
+        let mut game: Game<Flop> = game.into();
+        assert_eq!(
+            game.offer_insurance(&insured, &insurer, 20, 3, 1),
+            Err(UserError::CannotInsure)
+        );
+        assert_eq!(
+            game.offer_insurance(&insurer, &insured, 0, 3, 1),
+            Err(UserError::InvalidInsurance)
+        );
+        assert_eq!(
+            game.accept_insurance(&insured, &insurer),
+            Err(UserError::NoInsuranceOffer)
+        );
+        assert_eq!(game.offer_insurance(&insurer, &insured, 20, 3, 1), Ok(()));
+        let insurer_money = game.data.players[insurer_idx].user.money;
+        assert_eq!(game.accept_insurance(&insured, &insurer), Ok(()));
+        assert_eq!(
+            game.data.players[insurer_idx].user.money,
+            insurer_money - 60
+        );
+        let game: Game<Turn> = game.into();
+        let game: Game<River> = game.into();
+        let mut game: Game<ShowHands> = game.into();
+        game.data.board = vec![
+            Card(2, Suit::Club),
+            Card(7, Suit::Diamond),
+            Card(9, Suit::Heart),
+            Card(11, Suit::Spade),
+            Card(13, Suit::Club),
+        ];
+        (game, insurer_idx, insured_idx)
+    }
+
+    fn init_game_at_showdown_with_2_all_ins() -> Game<ShowHands> {
+        let mut game = init_game_at_deal();
+        game.act(Action::Fold).unwrap();
+        game.act(Action::AllIn).unwrap();
+        game.act(Action::AllIn).unwrap();
+        let game: Game<Flop> = game.into();
         let game: Game<Turn> = game.into();
         let game: Game<River> = game.into();
         let game: Game<ShowHands> = game.into();
@@ -1816,6 +4637,84 @@ mod game_tests {
         game
     }
 
+    #[test]
+    fn add_chips_capped_at_max_buy_in() {
+        let mut game = Game::<Lobby>::new();
+        let buy_in = game.data.settings.starting_stack;
+        let max_buy_in = 2 * buy_in;
+        game.data.settings.max_buy_in = max_buy_in;
+        game.new_user("0").unwrap();
+        assert_eq!(game.add_chips("0", 3 * buy_in), Ok(true));
+        assert_eq!(game.data.spectators["0"].money, max_buy_in);
+        assert_eq!(
+            game.add_chips("0", 1),
+            Err(UserError::MaxBuyInExceeded { max_buy_in })
+        );
+        assert_eq!(game.add_chips("1", 1), Err(UserError::UserDoesNotExist));
+        assert_eq!(
+            game.drain_events(),
+            VecDeque::from([GameEvent::AddedChips("0".to_string(), buy_in)])
+        );
+        assert!(game.drain_events().is_empty());
+    }
+
+    #[test]
+    fn add_chips_up_to_min_buy_in() {
+        let mut game = Game::<Lobby>::new();
+        let buy_in = game.data.settings.starting_stack;
+        game.data.settings.min_buy_in = buy_in;
+        game.data.settings.max_buy_in = 2 * buy_in;
+        game.new_user("0").unwrap();
+        game.data.spectators.get_mut("0").unwrap().money = 0;
+        assert_eq!(
+            game.add_chips("0", buy_in - 1),
+            Err(UserError::MinBuyInNotMet { min_buy_in: buy_in })
+        );
+        assert_eq!(game.data.spectators["0"].money, 0);
+        assert_eq!(game.add_chips("0", buy_in), Ok(true));
+        // Stacks already past the min buy-in can add any amount.
+        assert_eq!(game.add_chips("0", 1), Ok(true));
+        assert_eq!(game.data.spectators["0"].money, buy_in + 1);
+    }
+
+    #[test]
+    fn add_chips_queued_during_hand() {
+        let mut game = init_game_at_deal();
+        game.drain_events();
+        let buy_in = game.data.settings.starting_stack;
+        game.data.settings.max_buy_in = 2 * buy_in;
+        let money = game.data.players[1].user.money;
+        assert_eq!(game.add_chips("1", buy_in), Ok(false));
+        assert_eq!(game.data.players[1].user.money, money);
+        assert!(game.drain_events().is_empty());
+        game.act(Action::AllIn).unwrap();
+        game.act(Action::Fold).unwrap();
+        game.act(Action::Fold).unwrap();
+        let mut events = game.drain_events();
+        assert!(matches!(events.pop_front(), Some(GameEvent::AllIn(..))));
+        assert!(matches!(
+            events.pop_front(),
+            Some(GameEvent::UncalledBetReturned(..))
+        ));
+        let game: Game<Flop> = game.into();
+        let game: Game<Turn> = game.into();
+        let game: Game<River> = game.into();
+        let mut game: Game<ShowHands> = game.into();
+        assert_eq!(game.drain_events().len(), 5);
+        let game: Game<DistributePot> = game.into();
+        let mut game: Game<RemovePlayers> = game.into();
+        assert!(matches!(
+            game.drain_events().pop_front(),
+            Some(GameEvent::PotAwarded(winners, _, None)) if winners == ["0"]
+        ));
+        let mut game: Game<DivideDonations> = game.into();
+        assert_eq!(game.data.players[1].user.money, money + buy_in);
+        assert_eq!(
+            game.drain_events(),
+            VecDeque::from([GameEvent::AddedChips("1".to_string(), buy_in)])
+        );
+    }
+
     #[test]
     fn collect_blinds() {
         let game = init_game_at_collect_blinds();
@@ -1829,7 +4728,7 @@ mod game_tests {
         {
             assert_eq!(
                 game.data.players[i].user.money,
-                game.data.settings.buy_in - blind
+                game.data.settings.starting_stack - blind
             );
         }
     }
@@ -1861,14 +4760,16 @@ mod game_tests {
 
     #[test]
     fn early_showdown_1_all_in_2_folds() {
-        let game = init_game_at_showdown_with_1_all_in();
-        let game: Game<DistributePot> = game.into();
-        let game: Game<ShowHands> = game.into();
+        let game = init_game_at_distribute_pot_with_1_all_in();
+        assert_eq!(game.get_num_pots(), 1);
+        let game: Game<RemovePlayers> = game.into();
         assert!(game.is_pot_empty());
+        assert!(game.data.board.is_empty());
+        assert!(game.data.players.iter().all(|p| !p.showing));
         for (i, money) in [
-            game.data.settings.buy_in + game.data.small_blind + game.data.big_blind,
-            game.data.settings.buy_in - game.data.small_blind,
-            game.data.settings.buy_in - game.data.big_blind,
+            game.data.settings.starting_stack + game.data.small_blind + game.data.big_blind,
+            game.data.settings.starting_stack - game.data.small_blind,
+            game.data.settings.starting_stack - game.data.big_blind,
         ]
         .iter()
         .enumerate()
@@ -1881,12 +4782,9 @@ mod game_tests {
     fn early_showdown_1_forced_all_in_and_1_call() {
         let game = init_2_player_game();
         let mut game: Game<MoveButton> = game.into();
-        // Want to force two players to all-in
-        for i in 0..2 {
-            if i == 0 {
-                game.data.players[i].user.money = game.data.settings.min_big_blind;
-            }
-        }
+        // Want to force the big blind all-in. The button is the small
+        // blind heads-up, so the big blind is the other player.
+        game.data.players[1].user.money = game.data.settings.min_big_blind;
         let game: Game<CollectBlinds> = game.into();
         let game: Game<Deal> = game.into();
         let mut game: Game<TakeAction> = game.into();
@@ -1896,15 +4794,9 @@ mod game_tests {
         let game: Game<Turn> = game.into();
         let game: Game<River> = game.into();
         let mut game: Game<ShowHands> = game.into();
-        game.data.board = vec![
-            Card(1, Suit::Spade),
-            Card(4, Suit::Diamond),
-            Card(5, Suit::Diamond),
-            Card(6, Suit::Diamond),
-            Card(7, Suit::Diamond),
-        ];
-        game.data.players[0].cards = vec![Card(3, Suit::Heart), Card(1, Suit::Diamond)];
-        game.data.players[1].cards = vec![Card(1, Suit::Heart), Card(10, Suit::Diamond)];
+        game.data.board = functional::parse_cards("As 4d 5d 6d 7d").unwrap();
+        game.data.players[0].cards = functional::parse_cards("Ah Td").unwrap();
+        game.data.players[1].cards = functional::parse_cards("3h Ad").unwrap();
         let game: Game<DistributePot> = game.into();
         let game: Game<ShowHands> = game.into();
         let game: Game<DistributePot> = game.into();
@@ -1913,8 +4805,8 @@ mod game_tests {
         let game: Game<ShowHands> = game.into();
         assert!(game.is_pot_empty());
         for (i, money) in [
+            game.data.settings.starting_stack - game.data.settings.min_big_blind,
             2 * game.data.settings.min_big_blind,
-            game.data.settings.buy_in - game.data.settings.min_big_blind,
         ]
         .iter()
         .enumerate()
@@ -1930,20 +4822,19 @@ mod game_tests {
         // deterministic. Also test out a tricky scenario: the ace
         // (as 1) counts as a high ace as well, so seat 1 wins
         // the showdown with a higher flush.
-        game.data.board = vec![
-            Card(4, Suit::Diamond),
-            Card(5, Suit::Diamond),
-            Card(6, Suit::Diamond),
-            Card(7, Suit::Diamond),
-        ];
-        game.data.players[1].cards = vec![Card(1, Suit::Diamond), Card(7, Suit::Heart)];
-        game.data.players[2].cards = vec![Card(2, Suit::Diamond), Card(5, Suit::Heart)];
+        game.data.board = functional::parse_cards("4d 5d 6d 7d").unwrap();
+        game.data.players[1].cards = functional::parse_cards("Ad 7h").unwrap();
+        game.data.players[2].cards = functional::parse_cards("2d 5h").unwrap();
         let game: Game<DistributePot> = game.into();
         let game: Game<ShowHands> = game.into();
         assert!(game.is_pot_empty());
-        for (i, money) in [game.data.settings.buy_in, 2 * game.data.settings.buy_in, 0]
-            .iter()
-            .enumerate()
+        for (i, money) in [
+            game.data.settings.starting_stack,
+            2 * game.data.settings.starting_stack,
+            0,
+        ]
+        .iter()
+        .enumerate()
         {
             assert_eq!(game.data.players[i].user.money, *money);
         }
@@ -1952,20 +4843,17 @@ mod game_tests {
     #[test]
     fn early_showdown_2_winners() {
         let mut game = init_game_at_showdown_with_2_all_ins();
-        game.data.board = vec![
-            Card(2, Suit::Diamond),
-            Card(4, Suit::Diamond),
-            Card(5, Suit::Diamond),
-            Card(6, Suit::Diamond),
-            Card(7, Suit::Diamond),
-        ];
-        game.data.players[1].cards = vec![Card(1, Suit::Heart), Card(7, Suit::Heart)];
-        game.data.players[2].cards = vec![Card(2, Suit::Heart), Card(5, Suit::Heart)];
+        game.data.board = functional::parse_cards("2d 4d 5d 6d 7d").unwrap();
+        game.data.players[1].cards = functional::parse_cards("Ah 7h").unwrap();
+        game.data.players[2].cards = functional::parse_cards("2h 5h").unwrap();
         let game: Game<DistributePot> = game.into();
         let game: Game<ShowHands> = game.into();
         assert!(game.is_pot_empty());
         for i in 0..3 {
-            assert_eq!(game.data.players[i].user.money, game.data.settings.buy_in);
+            assert_eq!(
+                game.data.players[i].user.money,
+                game.data.settings.starting_stack
+            );
         }
     }
 
@@ -1974,7 +4862,7 @@ mod game_tests {
         let game = init_3_player_game();
         let mut game: Game<MoveButton> = game.into();
         for i in 0..3 {
-            game.data.players[i].user.money = game.data.settings.buy_in * (3 - i as u32);
+            game.data.players[i].user.money = game.data.settings.starting_stack * (3 - i as u32);
         }
         let game: Game<CollectBlinds> = game.into();
         let game: Game<Deal> = game.into();
@@ -2011,7 +4899,91 @@ mod game_tests {
         let game: Game<DistributePot> = game.into();
         let game: Game<ShowHands> = game.into();
         assert!(game.is_pot_empty());
-        for (i, money) in [6 * game.data.settings.buy_in, 0, 0].iter().enumerate() {
+        for (i, money) in [6 * game.data.settings.starting_stack, 0, 0]
+            .iter()
+            .enumerate()
+        {
+            assert_eq!(game.data.players[i].user.money, *money);
+        }
+    }
+
+    #[test]
+    fn distribution_outcome_matches_distribution() {
+        let game = init_3_player_game();
+        let mut game: Game<MoveButton> = game.into();
+        let starting_stack = game.data.settings.starting_stack;
+        for i in 0..3 {
+            game.data.players[i].user.money = starting_stack * (i as u32 + 1);
+        }
+        let game: Game<CollectBlinds> = game.into();
+        let game: Game<Deal> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        game.act(Action::AllIn).unwrap();
+        game.act(Action::AllIn).unwrap();
+        game.act(Action::Call(390)).unwrap();
+        let game: Game<Flop> = game.into();
+        let game: Game<Turn> = game.into();
+        let game: Game<River> = game.into();
+        let mut game: Game<ShowHands> = game.into();
+        game.data.board = vec![
+            Card(1, Suit::Spade),
+            Card(4, Suit::Diamond),
+            Card(5, Suit::Diamond),
+            Card(6, Suit::Diamond),
+            Card(7, Suit::Diamond),
+        ];
+        game.data.players[0].cards = vec![Card(3, Suit::Heart), Card(1, Suit::Diamond)];
+        game.data.players[1].cards = vec![Card(1, Suit::Heart), Card(10, Suit::Diamond)];
+        game.data.players[2].cards = vec![Card(2, Suit::Heart), Card(9, Suit::Diamond)];
+        let mut game: Game<DistributePot> = game.into();
+        game.drain_events();
+
+        // The side pot between the two bigger stacks is decided first,
+        // then the main pot everyone's in.
+        let outcome = game.get_distribution_outcome();
+        let usernames = |names: &[&str]| Vec::from_iter(names.iter().map(ToString::to_string));
+        assert_eq!(
+            outcome.segments,
+            vec![
+                PotSegment {
+                    amount: 2 * starting_stack,
+                    eligible: usernames(&["1", "2"]),
+                    winners: usernames(&["1"]),
+                    winning_rank: Some(Rank::Flush),
+                },
+                PotSegment {
+                    amount: 3 * starting_stack,
+                    eligible: usernames(&["0", "1", "2"]),
+                    winners: usernames(&["0"]),
+                    winning_rank: Some(Rank::Flush),
+                },
+            ]
+        );
+        // Looking at the outcome doesn't pay anything out.
+        assert_eq!(game.get_distribution_outcome(), outcome);
+        assert_eq!(game.data.pot.get_size(), 5 * starting_stack);
+
+        // Each distribution pays out the next segment of the outcome.
+        let mut game: Game<ShowHands> = game.into();
+        assert_eq!(
+            game.drain_events().pop_front(),
+            Some(GameEvent::PotAwarded(
+                usernames(&["1"]),
+                2 * starting_stack,
+                Some(Rank::Flush)
+            ))
+        );
+        let mut game: Game<DistributePot> = game.into();
+        assert_eq!(
+            game.get_distribution_outcome().segments,
+            outcome.segments[1..]
+        );
+        let game: Game<ShowHands> = game.into();
+        assert!(game.is_pot_empty());
+        for (i, money) in [3 * starting_stack, 2 * starting_stack, starting_stack]
+            .iter()
+            .enumerate()
+        {
             assert_eq!(game.data.players[i].user.money, *money);
         }
     }
@@ -2021,18 +4993,21 @@ mod game_tests {
         let game = init_3_player_game();
         let mut game: Game<MoveButton> = game.into();
         for i in 0..3 {
-            game.data.players[i].user.money = game.data.settings.buy_in * (i as u32 + 1);
+            game.data.players[i].user.money = game.data.settings.starting_stack * (i as u32 + 1);
         }
         let game: Game<CollectBlinds> = game.into();
         let game: Game<Deal> = game.into();
         let mut game: Game<TakeAction> = game.into();
         assert_eq!(game.act(Action::AllIn), Ok(Action::AllIn));
+        // The all-in is a full raise of 190, so the next full raise has to
+        // raise to 390 altogether.
         assert_eq!(
             game.get_next_action_options(),
             Some(HashSet::from([
                 Action::AllIn,
                 Action::Call(195),
                 Action::Fold,
+                Action::Raise(385),
             ]))
         );
         assert_eq!(game.act(Action::AllIn), Ok(Action::AllIn));
@@ -2063,9 +5038,9 @@ mod game_tests {
         let game: Game<ShowHands> = game.into();
         assert!(game.is_pot_empty());
         for (i, money) in [
-            3 * game.data.settings.buy_in,
-            2 * game.data.settings.buy_in,
-            game.data.settings.buy_in,
+            3 * game.data.settings.starting_stack,
+            2 * game.data.settings.starting_stack,
+            game.data.settings.starting_stack,
         ]
         .iter()
         .enumerate()
@@ -2118,9 +5093,150 @@ mod game_tests {
         assert_eq!(game.new_user(username), Err(UserError::CapacityReached));
     }
 
+    #[test]
+    fn autostart_waits_for_enough_players() {
+        for (autostart, num_players_to_start) in [
+            (Autostart::Off, None),
+            (Autostart::WhenPlayers(0), Some(2)),
+            (Autostart::WhenPlayers(3), Some(3)),
+            (Autostart::Always, Some(2)),
+        ] {
+            let mut game = Game::<Lobby>::new();
+            game.data.settings.autostart = autostart;
+            for i in 0..4 {
+                let username = i.to_string();
+                assert_eq!(game.new_user(&username), Ok(true));
+                assert_eq!(game.waitlist_user(&username), Ok(true));
+                assert_eq!(
+                    game.is_ready_to_autostart(),
+                    num_players_to_start.is_some_and(|n| i + 1 >= n),
+                    "{autostart:?} with {} players",
+                    i + 1
+                );
+            }
+        }
+
+        // Users leaving can drop the game back below the threshold.
+        let mut game = Game::<Lobby>::new();
+        game.data.settings.autostart = Autostart::WhenPlayers(3);
+        for username in ["0", "1", "2"] {
+            game.new_user(username).unwrap();
+            game.waitlist_user(username).unwrap();
+        }
+        assert!(game.is_ready_to_autostart());
+        assert_eq!(game.remove_user("2"), Ok(true));
+        assert!(!game.is_ready_to_autostart());
+    }
+
+    #[test]
+    fn resolve_each_pending_action() {
+        let nothing_to_call = HashSet::from([
+            Action::AllIn,
+            Action::Check,
+            Action::Fold,
+            Action::Raise(20),
+        ]);
+        let blind_to_call = HashSet::from([
+            Action::AllIn,
+            Action::Call(10),
+            Action::Fold,
+            Action::Raise(20),
+        ]);
+        let raise_to_call = HashSet::from([
+            Action::AllIn,
+            Action::Call(30),
+            Action::Fold,
+            Action::Raise(50),
+        ]);
+        let stack_to_call = HashSet::from([Action::AllIn, Action::Fold]);
+        for (pending_action, expected) in [
+            (
+                PendingAction::CheckFold,
+                ["check", "fold", "fold", "fold", "fold"],
+            ),
+            (
+                PendingAction::CheckCall,
+                ["check", "call $10", "call $10", "call $30", ""],
+            ),
+            (
+                PendingAction::CallAny,
+                ["check", "call $10", "call $10", "call $30", "all-in"],
+            ),
+            (
+                PendingAction::FoldToAnyRaise,
+                ["check", "call $10", "fold", "fold", "fold"],
+            ),
+        ] {
+            // Calling the same amount after the flop is calling a raise.
+            let situations = [
+                (&nothing_to_call, Some(10)),
+                (&blind_to_call, Some(10)),
+                (&blind_to_call, None),
+                (&raise_to_call, Some(10)),
+                (&stack_to_call, Some(10)),
+            ];
+            for ((action_options, big_blind), expected) in situations.into_iter().zip(expected) {
+                let action = resolve_pending_action(pending_action, action_options, big_blind);
+                assert_eq!(
+                    action.map_or(String::new(), |action| action.to_string()),
+                    expected,
+                    "{pending_action} with {action_options:?} and {big_blind:?}"
+                );
+            }
+        }
+    }
+
+    fn init_2_player_game_at_showdown_without_auto_show() -> Game<ShowHands> {
+        let game = init_2_player_game();
+        let mut game: Game<MoveButton> = game.into();
+        game.data.settings.auto_show_losers = false;
+        let game: Game<CollectBlinds> = game.into();
+        let game: Game<Deal> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        game.act(Action::AllIn).unwrap();
+        game.act(Action::AllIn).unwrap();
+        let game: Game<Flop> = game.into();
+        let game: Game<Turn> = game.into();
+        let game: Game<River> = game.into();
+        let mut game: Game<ShowHands> = game.into();
+        game.data.board = vec![
+            Card(1, Suit::Spade),
+            Card(4, Suit::Diamond),
+            Card(5, Suit::Diamond),
+            Card(6, Suit::Diamond),
+            Card(7, Suit::Diamond),
+        ];
+        game.data.players[0].cards = vec![Card(9, Suit::Club), Card(11, Suit::Heart)];
+        game.data.players[1].cards = vec![Card(1, Suit::Heart), Card(10, Suit::Diamond)];
+        game
+    }
+
+    #[test]
+    fn muck_losing_hand() {
+        let mut game = init_2_player_game_at_showdown_without_auto_show();
+        assert_eq!(game.muck_hand("0"), Ok(()));
+        assert_eq!(game.muck_hand("0"), Err(UserError::UserMuckedHand));
+        assert_eq!(game.muck_hand("2"), Err(UserError::UserNotPlaying));
+        let mut game: Game<DistributePot> = game.into();
+        assert!(!game.data.players[0].showing);
+        assert!(game.data.players[1].showing);
+        assert_eq!(game.show_hand("0"), Err(UserError::UserMuckedHand));
+    }
+
+    #[test]
+    fn show_losing_hand() {
+        let mut game = init_2_player_game_at_showdown_without_auto_show();
+        assert_eq!(game.show_hand("0"), Ok(()));
+        assert_eq!(game.muck_hand("0"), Err(UserError::UserAlreadyShowingHand));
+        let game: Game<DistributePot> = game.into();
+        assert!(game.data.players[0].showing);
+        assert!(game.data.players[1].showing);
+    }
+
     #[test]
     fn move_button() {
         let game = init_game_at_move_button();
+        assert_eq!(game.data.button_idx, 0);
         assert_eq!(game.data.small_blind_idx, 1);
         assert_eq!(game.data.big_blind_idx, 2);
         assert_eq!(game.data.starting_action_idx, 0);
@@ -2156,16 +5272,10 @@ mod game_tests {
     #[test]
     fn prepare_for_next_game() {
         let mut game = init_game_at_showdown_with_3_all_ins();
-        game.data.board = vec![
-            Card(1, Suit::Spade),
-            Card(4, Suit::Diamond),
-            Card(5, Suit::Diamond),
-            Card(6, Suit::Diamond),
-            Card(7, Suit::Diamond),
-        ];
-        game.data.players[0].cards = vec![Card(3, Suit::Heart), Card(8, Suit::Diamond)];
-        game.data.players[1].cards = vec![Card(1, Suit::Heart), Card(7, Suit::Heart)];
-        game.data.players[2].cards = vec![Card(2, Suit::Heart), Card(5, Suit::Heart)];
+        game.data.board = functional::parse_cards("As 4d 5d 6d 7d").unwrap();
+        game.data.players[0].cards = functional::parse_cards("3h 8d").unwrap();
+        game.data.players[1].cards = functional::parse_cards("Ah 7h").unwrap();
+        game.data.players[2].cards = functional::parse_cards("2h 5h").unwrap();
         let game: Game<DistributePot> = game.into();
         let game: Game<RemovePlayers> = game.into();
         let game: Game<DivideDonations> = game.into();
@@ -2176,6 +5286,56 @@ mod game_tests {
         assert_eq!(game.get_num_players(), 1);
     }
 
+    #[test]
+    fn auto_rebuy_keeps_busted_players_seated() {
+        let mut game = init_game_at_showdown_with_3_all_ins();
+        game.data.settings.auto_rebuy = true;
+        assert_eq!(game.set_auto_rebuy("1", true), Ok(()));
+        assert_eq!(game.set_auto_rebuy("2", true), Ok(()));
+        assert_eq!(game.set_auto_rebuy("2", false), Ok(()));
+        assert_eq!(
+            game.set_auto_rebuy("3", true),
+            Err(UserError::UserDoesNotExist)
+        );
+        game.data.board = functional::parse_cards("As 4d 5d 6d 7d").unwrap();
+        game.data.players[0].cards = functional::parse_cards("3h 8d").unwrap();
+        game.data.players[1].cards = functional::parse_cards("Ah 7h").unwrap();
+        game.data.players[2].cards = functional::parse_cards("2h 5h").unwrap();
+        let game: Game<DistributePot> = game.into();
+        let game: Game<RemovePlayers> = game.into();
+        let game: Game<DivideDonations> = game.into();
+        let game: Game<UpdateBlinds> = game.into();
+        let mut game: Game<BootPlayers> = game.into();
+        game.drain_events();
+
+        // Only the busted player that opted in rebuys, and they keep
+        // their seat with a fresh stack.
+        let starting_stack = game.data.settings.starting_stack;
+        let bought_in = game.data.ledger["1"].bought_in;
+        let mut game: Game<Lobby> = game.into();
+        assert_eq!(game.get_num_players(), 2);
+        assert!(game.contains_player("1"));
+        assert!(!game.contains_player("2"));
+        assert_eq!(game.data.players[1].user.money, starting_stack);
+        assert_eq!(game.data.ledger["1"].bought_in, bought_in + starting_stack);
+        let mut events = game.drain_events();
+        assert!(matches!(events.pop_front(), Some(GameEvent::DeckReveal(_))));
+        assert_eq!(
+            events,
+            VecDeque::from([GameEvent::Rebuy("1".to_string(), starting_stack)])
+        );
+    }
+
+    #[test]
+    fn auto_rebuy_needs_server_permission() {
+        let mut game = Game::<Lobby>::new();
+        game.new_user("0").unwrap();
+        assert_eq!(
+            game.set_auto_rebuy("0", true),
+            Err(UserError::AutoRebuyDisabled)
+        );
+    }
+
     #[test]
     fn remove_player() {
         let mut game = init_game_at_showdown_with_2_all_ins();
@@ -2197,7 +5357,10 @@ mod game_tests {
         assert!(game.contains_player("2"));
         let game: Game<UpdateBlinds> = game.into();
         for i in 0..2 {
-            assert_eq!(game.data.players[i].user.money, game.data.settings.buy_in);
+            assert_eq!(
+                game.data.players[i].user.money,
+                game.data.settings.starting_stack
+            );
         }
         let mut expected_open_seats = Vec::from_iter(3..game.data.settings.max_players);
         expected_open_seats.push(0);
@@ -2225,7 +5388,10 @@ mod game_tests {
         assert!(game.contains_player("2"));
         let game: Game<UpdateBlinds> = game.into();
         for i in 0..2 {
-            assert_eq!(game.data.players[i].user.money, game.data.settings.buy_in);
+            assert_eq!(
+                game.data.players[i].user.money,
+                game.data.settings.starting_stack
+            );
         }
         let mut expected_open_seats = Vec::from_iter(3..game.data.settings.max_players);
         expected_open_seats.push(0);
@@ -2233,252 +5399,3045 @@ mod game_tests {
     }
 
     #[test]
-    fn show_hands_after_checks() {
-        let game = init_3_player_game();
-        let game: Game<MoveButton> = game.into();
-        let game: Game<CollectBlinds> = game.into();
-        let game: Game<Deal> = game.into();
-        let mut game: Game<TakeAction> = game.into();
-        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
-        assert_eq!(game.act(Action::Call(5)), Ok(Action::Call(5)));
-        assert_eq!(game.act(Action::Check), Ok(Action::Check));
-        let game: Game<Flop> = game.into();
-        let mut game: Game<TakeAction> = game.into();
-        assert_eq!(game.act(Action::Check), Ok(Action::Check));
-        assert_eq!(game.act(Action::Check), Ok(Action::Check));
-        let game: Game<Turn> = game.into();
-        let mut game: Game<TakeAction> = game.into();
-        assert_eq!(game.act(Action::Check), Ok(Action::Check));
-        assert_eq!(game.act(Action::Check), Ok(Action::Check));
-        let game: Game<River> = game.into();
-        let mut game: Game<TakeAction> = game.into();
-        assert_eq!(game.act(Action::Check), Ok(Action::Check));
-        assert_eq!(game.act(Action::Check), Ok(Action::Check));
-        let game: Game<ShowHands> = game.into();
-        let game: Game<DistributePot> = game.into();
-        for (i, state) in [PlayerState::Fold, PlayerState::Check, PlayerState::Check]
+    fn reconnect_within_grace_period() {
+        let mut game = init_game_at_deal();
+        let username = game.get_next_action_username().unwrap();
+        assert_eq!(game.disconnect_user(&username), Ok(false));
+        assert!(game.is_disconnected(&username));
+        assert!(game
+            .get_views()
+            .get("0")
+            .unwrap()
+            .players
             .iter()
-            .enumerate()
-        {
-            assert_eq!(game.data.players[i].state, *state);
-        }
+            .any(|p| p.user.name == username && p.disconnected));
+        assert_eq!(game.new_user(&username), Ok(false));
+        assert!(!game.is_disconnected(&username));
+        assert!(game.contains_player(&username));
+        assert!(!game.data.players_to_remove.contains(&username));
+        assert!(game.is_turn(&username));
     }
 
     #[test]
-    fn show_hands_after_raise_and_call() {
-        let game = init_3_player_game();
+    fn bomb_pot_collects_antes_and_starts_at_flop() {
+        let mut game = init_3_player_game();
+        game.data.settings.bomb_pot_frequency = 1;
         let game: Game<MoveButton> = game.into();
+        assert!(game.data.bomb_pot);
         let game: Game<CollectBlinds> = game.into();
         let game: Game<Deal> = game.into();
-        let mut game: Game<TakeAction> = game.into();
-        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
-        assert_eq!(game.act(Action::Call(5)), Ok(Action::Call(5)));
-        assert_eq!(game.act(Action::Check), Ok(Action::Check));
+        let ante = game.data.settings.bomb_pot_ante * game.data.big_blind;
+        assert_eq!(game.data.pot.get_size(), 3 * ante);
+        for player in game.data.players.iter() {
+            assert_eq!(player.user.money, game.data.settings.starting_stack - ante);
+        }
         let game: Game<Flop> = game.into();
-        let mut game: Game<TakeAction> = game.into();
-        assert_eq!(game.act(Action::Check), Ok(Action::Check));
-        assert_eq!(game.act(Action::Check), Ok(Action::Check));
-        let game: Game<Turn> = game.into();
-        let mut game: Game<TakeAction> = game.into();
-        assert_eq!(game.act(Action::Check), Ok(Action::Check));
-        assert_eq!(game.act(Action::Check), Ok(Action::Check));
-        let game: Game<River> = game.into();
-        let mut game: Game<TakeAction> = game.into();
-        assert_eq!(game.act(Action::Raise(20)), Ok(Action::Raise(20)));
+        assert!(game.data.board.is_empty());
+        for player in game.data.players.iter() {
+            assert_eq!(player.cards.len(), 2);
+        }
+        let game: Game<TakeAction> = game.into();
+        assert_eq!(game.data.board.len(), 3);
+        assert_eq!(
+            game.data.next_action_idx,
+            Some((game.data.button_idx + 1) % 3)
+        );
+        assert!(game.get_action_options().unwrap().contains(&Action::Check));
+    }
+
+    #[test]
+    fn rename_player_during_hand() {
+        let mut game = init_game_at_deal();
+        game.drain_events();
+        let username = game.get_next_action_username().unwrap();
+        let idx = game.data.next_action_idx.unwrap();
+        let cards = game.data.players[idx].cards.clone();
+        let money = game.data.players[idx].user.money;
+        assert_eq!(game.remove_user(&username), Ok(false));
+        assert_eq!(game.rename_user(&username, "new"), Ok(()));
+        assert!(!game.contains_user(&username));
+        assert!(game.is_turn("new"));
+        assert_eq!(game.data.players[idx].cards, cards);
+        assert_eq!(game.data.players[idx].user.money, money);
+        assert!(game.data.players_to_remove.contains("new"));
+        assert_eq!(
+            game.drain_events(),
+            VecDeque::from([GameEvent::Renamed(username, "new".to_string())])
+        );
+        assert!(game.act(Action::Fold).is_ok());
+    }
+
+    #[test]
+    fn leaderboard_tracks_session_profit_and_loss() {
+        let mut game = Game::<Lobby>::new();
+        let buy_in = game.data.settings.starting_stack;
+        for username in ["a", "b", "c"] {
+            assert_eq!(game.new_user(username), Ok(true));
+        }
+        game.data.spectators.get_mut("a").unwrap().money = buy_in + 100;
+        game.data.spectators.get_mut("b").unwrap().money = 50;
+        game.data.spectators.get_mut("c").unwrap().money = buy_in + 50;
+        assert_eq!(game.add_chips("b", 150), Ok(true));
+        assert_eq!(game.data.spectators["b"].money, 200);
+        assert_eq!(game.data.ledger["b"].bought_in, buy_in + 150);
+        assert_eq!(game.remove_user("c"), Ok(true));
+        let expected = vec![
+            ("a".to_string(), 100),
+            ("c".to_string(), 50),
+            ("b".to_string(), 50 - buy_in as i64),
+        ];
+        assert_eq!(game.get_leaderboard(), expected);
+
+        // Rejoining takes a new buy-in, but doesn't reset results.
+        assert_eq!(game.new_user("c"), Ok(true));
+        assert_eq!(game.get_leaderboard(), expected);
+    }
+
+    #[test]
+    fn rename_user_errors() {
+        let mut game = init_game_at_deal();
+        assert_eq!(
+            game.rename_user("0", "1"),
+            Err(UserError::UserAlreadyExists)
+        );
+        for new_username in ["", "a b", &"a".repeat(MAX_USER_INPUT_LENGTH + 1)] {
+            assert_eq!(
+                game.rename_user("0", new_username),
+                Err(UserError::InvalidUsername {
+                    max_length: MAX_USER_INPUT_LENGTH
+                })
+            );
+        }
+        assert_eq!(
+            game.rename_user("3", "new"),
+            Err(UserError::UserDoesNotExist)
+        );
+        // Users that left keep their results under their username.
+        game.new_user("gone").unwrap();
+        game.remove_user("gone").unwrap();
+        assert!(!game.contains_user("gone"));
+        assert_eq!(game.rename_user("0", "gone"), Err(UserError::UsernameTaken));
+        assert_eq!(game.rename_user("0", "new"), Ok(()));
+        assert_eq!(
+            game.rename_user("new", "newer"),
+            Err(UserError::RenameLimitReached)
+        );
+        assert!(game.contains_player("new"));
+    }
+
+    #[test]
+    fn renames_between_hands_count_as_the_next_hand() {
+        let mut game = Game::<Lobby>::new();
+        for i in 0..3 {
+            let username = i.to_string();
+            game.new_user(&username).unwrap();
+            game.waitlist_user(&username).unwrap();
+        }
+        assert_eq!(game.rename_user("0", "new"), Ok(()));
+        assert_eq!(
+            game.rename_user("new", "newer"),
+            Err(UserError::RenameLimitReached)
+        );
+        // The hand that starts next is the one they renamed during.
+        let game: Game<SeatPlayers> = game.into();
+        let mut game: Game<MoveButton> = game.into();
+        assert_eq!(
+            game.rename_user("new", "newer"),
+            Err(UserError::RenameLimitReached)
+        );
+        assert_eq!(game.rename_user("1", "other"), Ok(()));
+
+        // Once a hand's over, everyone can rename again in the lobby.
+        let mut game = init_game_at_showdown_with_3_all_ins();
+        assert_eq!(game.rename_user("0", "new"), Ok(()));
+        let game: Game<DistributePot> = game.into();
+        let game: Game<RemovePlayers> = game.into();
+        let game: Game<DivideDonations> = game.into();
+        let game: Game<UpdateBlinds> = game.into();
+        let game: Game<BootPlayers> = game.into();
+        let mut game: Game<Lobby> = game.into();
+        assert_eq!(game.rename_user("new", "newer"), Ok(()));
+    }
+
+    #[test]
+    fn set_profile_errors() {
+        let mut game = Game::<Lobby>::new();
+        game.new_user("0").unwrap();
+        let profile = |avatar: &str, tagline: &str| Profile {
+            avatar: Some(avatar.to_string()),
+            tagline: Some(tagline.to_string()),
+        };
+        // Avatars are a single character, even if it takes several code
+        // points to make it, and surrounding whitespace is trimmed.
+        for avatar in ["a", "🂡", "🇨🇦", "👩‍👩‍👧", "🏳️‍🌈"] {
+            assert_eq!(game.set_profile("0", profile(avatar, " hi ")), Ok(()));
+            assert_eq!(game.data.spectators["0"].profile, profile(avatar, "hi"));
+        }
+        for (avatar, tagline) in [
+            ("ab", ""),
+            ("🇨🇦🇨🇦", ""),
+            ("\u{7}", ""),
+            ("a", "new\u{1b}[2Jline"),
+            ("a", &"a".repeat(MAX_TAGLINE_LENGTH + 1)),
+        ] {
+            assert_eq!(
+                game.set_profile("0", profile(avatar, tagline)),
+                Err(UserError::InvalidProfile {
+                    max_length: MAX_TAGLINE_LENGTH
+                })
+            );
+        }
+        // Empty parts are cleared.
+        assert_eq!(game.set_profile("0", profile(" ", "")), Ok(()));
+        assert_eq!(game.data.spectators["0"].profile, Profile::default());
+        assert_eq!(
+            game.set_profile("1", Profile::default()),
+            Err(UserError::UserDoesNotExist)
+        );
+    }
+
+    #[test]
+    fn profiles_survive_leaving() {
+        let mut game = Game::<Lobby>::new();
+        let profile = Profile {
+            avatar: Some("🇨🇦".to_string()),
+            tagline: Some("eh".to_string()),
+        };
+        game.new_user("0").unwrap();
+        game.waitlist_user("0").unwrap();
+        assert_eq!(game.set_profile("0", profile.clone()), Ok(()));
+        assert_eq!(game.data.waitlist[0].profile, profile);
+        assert_eq!(game.remove_user("0"), Ok(true));
+        assert!(!game.contains_user("0"));
+        game.new_user("0").unwrap();
+        assert_eq!(game.data.spectators["0"].profile, profile);
+        // Renamed users take their profile with them.
+        assert_eq!(game.rename_user("0", "1"), Ok(()));
+        game.remove_user("1").unwrap();
+        game.new_user("1").unwrap();
+        assert_eq!(game.data.spectators["1"].profile, profile);
+    }
+
+    #[test]
+    fn railbirds_get_their_stacks_back() {
+        let mut game = Game::<Lobby>::new();
+        game.new_user("0").unwrap();
+        game.data.spectators.get_mut("0").unwrap().money = 300;
+        let leaderboard = game.get_leaderboard();
+
+        // Railbirds' stacks are cashed out and held for them, so their
+        // results don't change, and they still get views.
+        assert_eq!(game.rail_user("0"), Ok(true));
+        assert_eq!(game.rail_user("0"), Ok(true));
+        assert!(game.contains_railbird("0"));
+        assert!(!game.contains_spectator("0"));
+        assert_eq!(game.data.ledger["0"].cashed_out, 300);
+        assert_eq!(game.data.ledger["0"].held, 300);
+        assert_eq!(game.get_leaderboard(), leaderboard);
+        assert!(game.get_views().contains("0"));
+        assert_eq!(game.new_user("0"), Err(UserError::UserAlreadyExists));
+        assert_eq!(game.waitlist_user("0"), Err(UserError::UserDoesNotExist));
+
+        // Railbirds don't take up room for users, but they can only rejoin
+        // if there's room for them.
+        for i in 1..=game.data.settings.max_users {
+            assert_eq!(game.new_user(&i.to_string()), Ok(true));
+        }
+        assert_eq!(game.rejoin_user("0"), Err(UserError::CapacityReached));
+        game.remove_user("1").unwrap();
+        assert_eq!(game.rejoin_user("0"), Ok(true));
+        assert_eq!(game.data.spectators["0"].money, 300);
+        assert_eq!(game.data.ledger["0"].held, 0);
+        assert_eq!(game.get_leaderboard()[0], leaderboard[0]);
+        assert_eq!(game.rejoin_user("0"), Err(UserError::UserNotRailbird));
+
+        // The rail has its own cap.
+        game.data.settings.max_railbirds = 1;
+        assert_eq!(game.rail_user("2"), Ok(true));
+        assert_eq!(game.rail_user("3"), Err(UserError::CapacityReached));
+
+        // Railbirds that leave donate what was held for them like anyone
+        // else that leaves.
+        game.data.ledger.get_mut("2").unwrap().held += 50;
+        let donations = game.data.donations;
+        assert_eq!(game.remove_user("2"), Ok(true));
+        assert!(!game.contains_user("2"));
+        assert_eq!(game.data.donations, donations + 50.0);
+        assert_eq!(game.data.ledger["2"].held, 0);
+    }
+
+    #[test]
+    fn players_move_to_the_rail_after_the_hand() {
+        let mut game = init_game_at_showdown_with_3_all_ins();
+        game.data.board = vec![
+            Card(1, Suit::Spade),
+            Card(4, Suit::Diamond),
+            Card(5, Suit::Diamond),
+            Card(6, Suit::Diamond),
+            Card(7, Suit::Diamond),
+        ];
+        game.data.players[0].cards = vec![Card(3, Suit::Heart), Card(8, Suit::Diamond)];
+        game.data.players[1].cards = vec![Card(1, Suit::Heart), Card(7, Suit::Heart)];
+        game.data.players[2].cards = vec![Card(2, Suit::Heart), Card(5, Suit::Heart)];
+        assert_eq!(game.rail_user("0"), Ok(false));
+        assert_eq!(game.rail_user("0"), Ok(false));
+        // Leaving for good after asking to move to the rail wins out.
+        assert_eq!(game.rail_user("1"), Ok(false));
+        assert_eq!(game.remove_user("1"), Ok(false));
+        assert!(game.contains_player("0"));
+
+        // Players keep their claim on the pot, and their stack is held
+        // once the hand is over.
+        let game: Game<DistributePot> = game.into();
+        let game: Game<RemovePlayers> = game.into();
+        let money = game.data.players[0].user.money;
+        assert!(money > 0);
+        let mut game: Game<DivideDonations> = game.into();
+        assert!(!game.contains_player("0"));
+        assert!(game.contains_railbird("0"));
+        assert_eq!(game.data.ledger["0"].held, money);
+        assert!(!game.contains_user("1"));
+        assert_eq!(game.rejoin_user("0"), Ok(true));
+        assert_eq!(game.data.spectators["0"].money, money);
+    }
+
+    #[test]
+    fn insured_player_holds() {
+        let (mut game, insurer_idx, insured_idx) = init_insured_game_at_showdown();
+        let loser_idx = 3 - insurer_idx - insured_idx;
+        game.data.players[insured_idx].cards = vec![Card(14, Suit::Heart), Card(14, Suit::Spade)];
+        game.data.players[loser_idx].cards = vec![Card(3, Suit::Heart), Card(4, Suit::Spade)];
+        let pot_size = game.data.pot.get_size();
+        let insurer_money = game.data.players[insurer_idx].user.money;
+        let game: Game<DistributePot> = game.into();
+        let mut game: Game<RemovePlayers> = game.into();
+        assert_eq!(game.data.players[insured_idx].user.money, pot_size - 20);
+        assert_eq!(game.data.players[loser_idx].user.money, 0);
+        assert_eq!(
+            game.data.players[insurer_idx].user.money,
+            insurer_money + 60 + 20
+        );
+        assert!(game.data.insurance.is_empty());
+        assert!(game.drain_events().contains(&GameEvent::InsuranceSettled(
+            game.data.players[insured_idx].user.name.clone(),
+            game.data.players[insurer_idx].user.name.clone(),
+            20
+        )));
+    }
+
+    #[test]
+    fn insured_player_gets_sucked_out_on() {
+        let (mut game, insurer_idx, insured_idx) = init_insured_game_at_showdown();
+        let winner_idx = 3 - insurer_idx - insured_idx;
+        game.data.players[insured_idx].cards = vec![Card(14, Suit::Heart), Card(14, Suit::Spade)];
+        game.data.players[winner_idx].cards = vec![Card(8, Suit::Heart), Card(10, Suit::Spade)];
+        let pot_size = game.data.pot.get_size();
+        let insurer_money = game.data.players[insurer_idx].user.money;
+        let game: Game<DistributePot> = game.into();
+        let mut game: Game<RemovePlayers> = game.into();
+        assert_eq!(game.data.players[insured_idx].user.money, 60);
+        assert_eq!(game.data.players[winner_idx].user.money, pot_size);
+        assert_eq!(game.data.players[insurer_idx].user.money, insurer_money);
+        assert!(game.drain_events().contains(&GameEvent::InsuranceSettled(
+            game.data.players[insurer_idx].user.name.clone(),
+            game.data.players[insured_idx].user.name.clone(),
+            60
+        )));
+    }
+
+    /// Return all the money in the game, wherever it is.
+    fn total_chips<T>(game: &Game<T>) -> Usd {
+        let money: Usd = game
+            .data
+            .spectators
+            .values()
+            .chain(game.data.waitlist.iter())
+            .chain(game.data.players.iter().map(|p| &p.user))
+            .map(|u| u.money)
+            .sum();
+        money
+            + game.data.pot.get_size()
+            + game.data.spectator_pool.get_size()
+            + game.data.donations as Usd
+    }
+
+    #[test]
+    fn spectator_pool_pays_out_winning_bets_in_proportion() {
+        let mut game = init_3_player_game();
+        for username in ["a", "b", "c", "d"] {
+            game.new_user(username).unwrap();
+        }
+        let game: Game<MoveButton> = game.into();
+        let game: Game<CollectBlinds> = game.into();
+        let game: Game<Deal> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        assert_eq!(
+            game.place_side_bet("a", "0", 10),
+            Err(UserError::SideBetsDisabled)
+        );
+        game.data.settings.spectator_bets = true;
+        let chips = total_chips(&game);
+        let money = game.data.settings.starting_stack;
+        let folder_idx = game.data.next_action_idx.unwrap();
+        let [folder, player1, player2] = [0, 1, 2].map(|offset| {
+            let player_idx = (folder_idx + offset) % 3;
+            game.data.players[player_idx].user.name.clone()
+        });
+
+        // Only spectators can bet, only on players still in the hand, and
+        // only as much as they have.
+        assert_eq!(
+            game.place_side_bet(&player1, &player2, 10),
+            Err(UserError::CannotSideBet)
+        );
+        assert_eq!(
+            game.place_side_bet("a", "e", 10),
+            Err(UserError::CannotSideBet)
+        );
+        for amount in [0, money + 1] {
+            assert_eq!(
+                game.place_side_bet("a", &player1, amount),
+                Err(UserError::InvalidSideBet { money })
+            );
+        }
+
+        // Bets on players that fold stay in the pool.
+        for (spectator, player, amount) in [
+            ("a", &player1, 30),
+            ("b", &player2, 10),
+            ("c", &player2, 20),
+            ("d", &folder, 40),
+        ] {
+            assert_eq!(game.place_side_bet(spectator, player, amount), Ok(()));
+        }
+        assert_eq!(game.data.spectators["a"].money, money - 30);
+        assert_eq!(game.data.spectator_pool.get_size(), 100);
+        game.act(Action::Fold).unwrap();
+        assert_eq!(
+            game.place_side_bet("a", &folder, 10),
+            Err(UserError::CannotSideBet)
+        );
+
+        // The pool locks once no one can bet anymore.
+        game.act(Action::AllIn).unwrap();
+        game.act(Action::AllIn).unwrap();
+        assert_eq!(
+            game.place_side_bet("a", &player1, 10),
+            Err(UserError::CannotSideBet)
+        );
+        assert_eq!(total_chips(&game), chips);
+
+        // The board plays for both players, so they split the pot, and
+        // everyone that bet on either of them splits the pool.
+        let game: Game<Flop> = game.into();
+        let game: Game<Turn> = game.into();
+        let game: Game<River> = game.into();
+        let mut game: Game<ShowHands> = game.into();
+        game.data.board = functional::parse_cards("As Ks Qs Js Ts").unwrap();
+        for (player, cards) in [(&player1, "2h 3h"), (&player2, "2d 3d")] {
+            let player_idx = game
+                .data
+                .players
+                .iter()
+                .position(|p| &p.user.name == player);
+            game.data.players[player_idx.unwrap()].cards = functional::parse_cards(cards).unwrap();
+        }
+        let game: Game<DistributePot> = game.into();
+        let mut game: Game<RemovePlayers> = game.into();
+        let events = game.drain_events();
+        // The $100 pool is split by the $60 bet on the winners, with
+        // the dollar left over from rounding down donated.
+        for (spectator, bet, payout) in [("a", 30, 50), ("b", 10, 16), ("c", 20, 33), ("d", 40, 0)]
+        {
+            assert_eq!(game.data.spectators[spectator].money, money - bet + payout);
+            assert_eq!(
+                events.contains(&GameEvent::SideBetPaid(spectator.to_string(), payout)),
+                payout > 0
+            );
+        }
+        assert_eq!(game.data.donations, 1.0);
+        assert_eq!(game.data.spectator_pool, SpectatorPool::default());
+        assert_eq!(total_chips(&game), chips);
+    }
+
+    #[test]
+    fn spectator_pool_without_winning_bets_is_donated() {
+        let bet = |spectator: &str, player: &str, amount: Usd| SideBet {
+            spectator: spectator.to_string(),
+            player: player.to_string(),
+            amount,
+        };
+        let pool = SpectatorPool {
+            bets: vec![bet("a", "0", 30), bet("b", "0", 5), bet("b", "1", 5)],
+        };
+        assert_eq!(pool.get_payouts(&["2".to_string()]), (vec![], 40));
+        // Spectators' bets on any of the winners count together.
+        assert_eq!(
+            pool.get_payouts(&["0".to_string(), "1".to_string()]),
+            (vec![("a".to_string(), 30), ("b".to_string(), 10)], 0)
+        );
+        assert_eq!(
+            pool.get_payouts(&["1".to_string()]),
+            (vec![("b".to_string(), 40)], 0)
+        );
+    }
+
+    #[test]
+    fn views_share_public_parts() {
+        let mut game = init_game_at_deal();
+        for username in ["a", "b"] {
+            game.new_user(username).unwrap();
+        }
+        let views = game.get_views();
+        let public_view = views.get("a").unwrap();
+        assert!(Arc::ptr_eq(&public_view, &views.get("b").unwrap()));
+        assert!(public_view
+            .players
+            .iter()
+            .all(|p| p.cards == HoleCards::Hidden { count: 2 }));
+        for (player_idx, player) in game.data.players.iter().enumerate() {
+            let view = views.get(&player.user.name).unwrap();
+            assert!(!Arc::ptr_eq(&view, &public_view));
+            for (other_player_idx, player_view) in view.players.iter().enumerate() {
+                if player_idx == other_player_idx {
+                    assert_eq!(player_view.cards, HoleCards::Revealed(player.cards.clone()));
+                } else {
+                    assert_eq!(player_view.cards, HoleCards::Hidden { count: 2 });
+                }
+            }
+        }
+        assert_eq!(views.usernames().count(), 5);
+        assert!(views.get("c").is_none());
+
+        // Players showing their hand don't need their own view.
+        game.data.players[0].showing = true;
+        let views = game.get_views();
+        let username = &game.data.players[0].user.name;
+        assert!(Arc::ptr_eq(
+            &views.get(username).unwrap(),
+            &views.get("a").unwrap()
+        ));
+    }
+
+    #[test]
+    fn views_tell_hidden_hands_from_missing_ones() {
+        let game = init_game_at_seat_players();
+        let views = game.get_views();
+        let view = views.get("0").unwrap();
+        assert!(view.players.iter().all(|p| p.cards == HoleCards::None));
+
+        let mut game = init_game_at_deal();
+        game.data.settings.auto_show_losers = false;
+        let cards_seen_by = |views: GameViews, username: &str| -> Vec<HoleCards> {
+            let view = views.get(username).unwrap();
+            view.players.iter().map(|p| p.cards.clone()).collect()
+        };
+        let hidden = HoleCards::Hidden { count: 2 };
+        let own = |cards: &[Card]| HoleCards::Revealed(cards.to_vec());
+        assert_eq!(
+            cards_seen_by(game.get_views(), "1"),
+            [
+                hidden.clone(),
+                own(&game.data.players[1].cards),
+                hidden.clone()
+            ]
+        );
+
+        // Folded hands are gone, though their player can still see them.
+        // All-in hands are still face down.
+        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
+        assert!(matches!(game.act(Action::AllIn), Ok(Action::AllIn)));
+        assert_eq!(
+            cards_seen_by(game.get_views(), "2"),
+            [
+                HoleCards::None,
+                hidden.clone(),
+                own(&game.data.players[2].cards)
+            ]
+        );
+        assert_eq!(
+            cards_seen_by(game.get_views(), "0"),
+            [
+                own(&game.data.players[0].cards),
+                hidden.clone(),
+                hidden.clone()
+            ]
+        );
+        assert!(matches!(game.act(Action::AllIn), Ok(Action::AllIn)));
+        let game: Game<Flop> = game.into();
+        let game: Game<Turn> = game.into();
+        let game: Game<River> = game.into();
+        let mut game: Game<ShowHands> = game.into();
+        game.data.board = functional::parse_cards("As 4d 5d 6d 7d").unwrap();
+        game.data.players[1].cards = functional::parse_cards("9c Jh").unwrap();
+        game.data.players[2].cards = functional::parse_cards("Ah Td").unwrap();
+
+        // The winner's hand is shown, and the loser's mucked hand is gone.
+        assert_eq!(game.muck_hand("1"), Ok(()));
+        let game: Game<DistributePot> = game.into();
+        assert_eq!(
+            cards_seen_by(game.get_views(), "0"),
+            [
+                own(&game.data.players[0].cards),
+                HoleCards::None,
+                own(&game.data.players[2].cards)
+            ]
+        );
+
+        // Nobody has cards between hands.
+        let game: Game<RemovePlayers> = game.into();
+        let game: Game<DivideDonations> = game.into();
+        let game: Game<UpdateBlinds> = game.into();
+        let game: Game<BootPlayers> = game.into();
+        let game: Game<Lobby> = game.into();
+        assert!(cards_seen_by(game.get_views(), "0")
+            .iter()
+            .all(|cards| *cards == HoleCards::None));
+    }
+
+    #[test]
+    fn omniscient_views_show_every_hand() {
+        let mut game = init_game_at_deal();
+        game.data.settings.privacy = Privacy::HideNonPlayers;
+        game.new_user("spectator").unwrap();
+        let folded_username = game.get_next_action_username().unwrap();
+        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
+        let all_in_username = game.get_next_action_username().unwrap();
+        assert!(matches!(game.act(Action::AllIn), Ok(Action::AllIn)));
+        assert_eq!(game.show_card(&all_in_username, 1), Ok(()));
+
+        // Hands show whether they're hidden, folded, or partly shown.
+        let omniscient_view = game.as_omniscient_view();
+        for (player_view, player) in omniscient_view.players.iter().zip(&game.data.players) {
+            assert_eq!(player_view.cards, HoleCards::Revealed(player.cards.clone()));
+        }
+        assert!(omniscient_view.spectators.contains_key("spectator"));
+
+        // Views made at the same time still hide them.
+        let views = game.get_views();
+        let view = views.get("spectator").unwrap();
+        for (player_view, player) in view.players.iter().zip(&game.data.players) {
+            let cards = if player.user.name == folded_username {
+                HoleCards::None
+            } else if player.user.name == all_in_username {
+                HoleCards::Revealed(vec![player.cards[1]])
+            } else {
+                HoleCards::Hidden { count: 2 }
+            };
+            assert_eq!(player_view.cards, cards);
+        }
+    }
+
+    #[test]
+    fn show_cards_early_when_all_in() {
+        let mut game = init_game_at_deal();
+        let all_in_username = game.get_next_action_username().unwrap();
+        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
+        let folded_username = all_in_username;
+        let all_in_username = game.get_next_action_username().unwrap();
+        assert!(matches!(game.act(Action::AllIn), Ok(Action::AllIn)));
+        let active_username = game.get_next_action_username().unwrap();
+
+        // Players that can still act or that folded can't show.
+        assert_eq!(
+            game.show_hand(&active_username),
+            Err(UserError::CannotShowHand)
+        );
+        assert_eq!(
+            game.show_card(&folded_username, 0),
+            Err(UserError::CannotShowHand)
+        );
+
+        // Show one card, which only reveals that card to others.
+        assert_eq!(game.show_card(&all_in_username, 1), Ok(()));
+        assert_eq!(
+            game.show_card(&all_in_username, 1),
+            Err(UserError::UserAlreadyShowingHand)
+        );
+        assert_eq!(
+            game.show_card(&all_in_username, 2),
+            Err(UserError::InvalidCard { num_cards: 2 })
+        );
+        let views = game.get_views();
+        let player_idx = game
+            .data
+            .players
+            .iter()
+            .position(|p| p.user.name == all_in_username)
+            .unwrap();
+        let cards = game.data.players[player_idx].cards.clone();
+        let player_view = &views.get(&active_username).unwrap().players[player_idx];
+        assert_eq!(player_view.revealed, Reveal::Card(1));
+        assert_eq!(player_view.cards, HoleCards::Revealed(vec![cards[1]]));
+        let player_view = &views.get(&all_in_username).unwrap().players[player_idx];
+        assert_eq!(player_view.cards, HoleCards::Revealed(cards.clone()));
+
+        // Showing the other card shows the whole hand.
+        assert_eq!(game.show_card(&all_in_username, 0), Ok(()));
+        let views = game.get_views();
+        let player_view = &views.get(&active_username).unwrap().players[player_idx];
+        assert_eq!(player_view.revealed, Reveal::Hand);
+        assert_eq!(player_view.cards, HoleCards::Revealed(cards));
+        assert_eq!(
+            game.show_hand(&all_in_username),
+            Err(UserError::UserAlreadyShowingHand)
+        );
+    }
+
+    #[test]
+    fn short_deck_flush_beats_full_house_at_showdown() {
+        let mut game = init_2_player_game();
+        game.data.settings.variant = GameVariant::ShortDeck;
+        let game: Game<MoveButton> = game.into();
+        let game: Game<CollectBlinds> = game.into();
+        let game: Game<Deal> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        assert_eq!(game.data.deck.len(), 36);
+        for player in game.data.players.iter() {
+            assert_eq!(player.cards.len(), 2);
+        }
+        game.act(Action::AllIn).unwrap();
+        game.act(Action::AllIn).unwrap();
+        let game: Game<Flop> = game.into();
+        let game: Game<Turn> = game.into();
+        let game: Game<River> = game.into();
+        assert!(game
+            .data
+            .board
+            .iter()
+            .all(|Card(value, _)| !(2..=5).contains(value)));
+        let mut game: Game<ShowHands> = game.into();
+        game.data.board = vec![
+            Card(9, Suit::Heart),
+            Card(9, Suit::Club),
+            Card(7, Suit::Heart),
+            Card(12, Suit::Heart),
+            Card(6, Suit::Spade),
+        ];
+        // Nines full of sixes would win in hold'em, but the flush wins
+        // with a short deck.
+        game.data.players[0].cards = vec![Card(9, Suit::Diamond), Card(6, Suit::Club)];
+        game.data.players[1].cards = vec![Card(1, Suit::Heart), Card(10, Suit::Heart)];
+        let game: Game<DistributePot> = game.into();
+        let game: Game<RemovePlayers> = game.into();
+        assert_eq!(game.data.players[0].user.money, 0);
+        assert_eq!(game.data.players[1].user.money, 2 * DEFAULT_BUY_IN);
+    }
+
+    #[test]
+    fn equities_are_shown_once_all_in_hands_are_shown() {
+        let game = init_2_player_game();
+        let game: Game<MoveButton> = game.into();
+        let game: Game<CollectBlinds> = game.into();
+        let game: Game<Deal> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        game.act(Action::AllIn).unwrap();
+        game.act(Action::AllIn).unwrap();
+        // Aces can only catch quad kings with the other two aces, and
+        // they're drawing dead once the turn blanks.
+        let hands = [
+            vec![Card(13, Suit::Club), Card(2, Suit::Diamond)],
+            vec![Card(1, Suit::Spade), Card(1, Suit::Heart)],
+        ];
+        let board = [
+            Card(13, Suit::Spade),
+            Card(13, Suit::Heart),
+            Card(13, Suit::Diamond),
+            Card(7, Suit::Club),
+        ];
+        let mut deck = [&hands.concat()[..], &board].concat();
+        let rest: Vec<Card> = functional::new_deck()
+            .into_iter()
+            .filter(|card| !deck.contains(card))
+            .collect();
+        deck.extend(rest);
+        game.data.deck = deck;
+        game.data.deck_idx = 4;
+        game.data.players[0].cards = hands[0].clone();
+        game.data.players[1].cards = hands[1].clone();
+
+        // Equities would give away a hidden hand, so they wait until
+        // everyone left in the hand has shown.
+        game.show_hand("0").unwrap();
+        assert!(game.get_views().get("1").unwrap().equities.is_empty());
+        game.show_hand("1").unwrap();
+        let game: Game<Flop> = game.into();
+        let game: Game<Turn> = game.into();
+        assert_eq!(game.data.equities.len(), 2);
+        let mut game: Game<River> = game.into();
+        let expected = vec![("0".to_string(), 1.0), ("1".to_string(), 0.0)];
+        assert_eq!(game.data.equities, expected);
+        assert!(game
+            .drain_events()
+            .contains(&GameEvent::Equity(expected.clone())));
+        let game: Game<ShowHands> = game.into();
+        assert_eq!(game.data.board[..4], board);
+        assert_eq!(game.get_views().get("1").unwrap().equities, expected);
+    }
+
+    #[test]
+    fn omaha_hands_use_exactly_2_hole_cards() {
+        let mut game = init_2_player_game();
+        game.data.settings.variant = GameVariant::Omaha;
+        let game: Game<MoveButton> = game.into();
+        let game: Game<CollectBlinds> = game.into();
+        let game: Game<Deal> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        assert_eq!(game.data.deck_idx, 8);
+        for player in game.data.players.iter() {
+            assert_eq!(player.cards.len(), 4);
+        }
+        game.act(Action::AllIn).unwrap();
+        game.act(Action::AllIn).unwrap();
+        let game: Game<Flop> = game.into();
+        let game: Game<Turn> = game.into();
+        let game: Game<River> = game.into();
+        let mut game: Game<ShowHands> = game.into();
+        game.data.board = vec![
+            Card(2, Suit::Heart),
+            Card(7, Suit::Heart),
+            Card(9, Suit::Heart),
+            Card(13, Suit::Heart),
+            Card(4, Suit::Club),
+        ];
+        // The ace of hearts makes the best hand overall with the board's
+        // four hearts, but it's illegal in Omaha, leaving just a pair of
+        // eights that loses to a pair of queens.
+        game.data.players[0].cards = vec![
+            Card(1, Suit::Heart),
+            Card(8, Suit::Spade),
+            Card(8, Suit::Diamond),
+            Card(3, Suit::Club),
+        ];
+        game.data.players[1].cards = vec![
+            Card(12, Suit::Spade),
+            Card(12, Suit::Diamond),
+            Card(5, Suit::Club),
+            Card(6, Suit::Diamond),
+        ];
+        let game: Game<DistributePot> = game.into();
+        let game: Game<RemovePlayers> = game.into();
+        assert_eq!(game.data.players[0].user.money, 0);
+        assert_eq!(game.data.players[1].user.money, 2 * DEFAULT_BUY_IN);
+    }
+
+    #[test]
+    fn show_hands_after_checks() {
+        let game = init_3_player_game();
+        let game: Game<MoveButton> = game.into();
+        let game: Game<CollectBlinds> = game.into();
+        let game: Game<Deal> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
+        assert_eq!(game.act(Action::Call(5)), Ok(Action::Call(5)));
+        assert_eq!(game.act(Action::Check), Ok(Action::Check));
+        let game: Game<Flop> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        assert_eq!(game.act(Action::Check), Ok(Action::Check));
+        assert_eq!(game.act(Action::Check), Ok(Action::Check));
+        let game: Game<Turn> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        assert_eq!(game.act(Action::Check), Ok(Action::Check));
+        assert_eq!(game.act(Action::Check), Ok(Action::Check));
+        let game: Game<River> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        assert_eq!(game.act(Action::Check), Ok(Action::Check));
+        assert_eq!(game.act(Action::Check), Ok(Action::Check));
+        let game: Game<ShowHands> = game.into();
+        let game: Game<DistributePot> = game.into();
+        for (i, state) in [PlayerState::Fold, PlayerState::Check, PlayerState::Check]
+            .iter()
+            .enumerate()
+        {
+            assert_eq!(game.data.players[i].state, *state);
+        }
+    }
+
+    #[test]
+    fn show_hands_after_raise_and_call() {
+        let game = init_3_player_game();
+        let game: Game<MoveButton> = game.into();
+        let game: Game<CollectBlinds> = game.into();
+        let game: Game<Deal> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
+        assert_eq!(game.act(Action::Call(5)), Ok(Action::Call(5)));
+        assert_eq!(game.act(Action::Check), Ok(Action::Check));
+        let game: Game<Flop> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        assert_eq!(game.act(Action::Check), Ok(Action::Check));
+        assert_eq!(game.act(Action::Check), Ok(Action::Check));
+        let game: Game<Turn> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        assert_eq!(game.act(Action::Check), Ok(Action::Check));
+        assert_eq!(game.act(Action::Check), Ok(Action::Check));
+        let game: Game<River> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        assert_eq!(game.act(Action::Raise(20)), Ok(Action::Raise(20)));
         assert_eq!(game.act(Action::Call(20)), Ok(Action::Call(20)));
         let game: Game<ShowHands> = game.into();
         let game: Game<DistributePot> = game.into();
-        for (i, state) in [PlayerState::Fold, PlayerState::Raise, PlayerState::Call]
+        for (i, state) in [PlayerState::Fold, PlayerState::Raise, PlayerState::Call]
+            .iter()
+            .enumerate()
+        {
+            assert_eq!(game.data.players[i].state, *state);
+        }
+    }
+
+    #[test]
+    fn seat_players() {
+        let game = init_game_at_seat_players();
+        assert_eq!(game.data.num_players_active, game.get_num_players());
+        assert!(game.contains_player("0"));
+        assert!(game.contains_player("1"));
+        assert!(game.contains_player("2"));
+    }
+
+    #[test]
+    fn take_action_2_all_ins() {
+        let mut game = init_game_at_deal();
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([
+                Action::AllIn,
+                Action::Call(10),
+                Action::Fold,
+                Action::Raise(20)
+            ]))
+        );
+        assert_eq!(game.act(Action::AllIn), Ok(Action::AllIn));
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([Action::AllIn, Action::Fold]))
+        );
+        assert_eq!(game.act(Action::AllIn), Ok(Action::AllIn));
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([Action::AllIn, Action::Fold]))
+        );
+        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
+        assert_eq!(game.get_next_action_options(), None);
+    }
+
+    #[test]
+    fn take_action_2_calls_1_check() {
+        let mut game = init_game_at_deal();
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([
+                Action::AllIn,
+                Action::Call(10),
+                Action::Fold,
+                Action::Raise(20)
+            ]))
+        );
+        assert_eq!(game.act(Action::Call(10)), Ok(Action::Call(10)));
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([
+                Action::AllIn,
+                Action::Call(5),
+                Action::Fold,
+                Action::Raise(15)
+            ]))
+        );
+        assert_eq!(game.act(Action::Call(5)), Ok(Action::Call(5)));
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([
+                Action::AllIn,
+                Action::Check,
+                Action::Fold,
+                Action::Raise(20)
+            ]))
+        );
+        assert_eq!(game.act(Action::Check), Ok(Action::Check));
+        assert_eq!(game.get_next_action_options(), None);
+    }
+
+    #[test]
+    fn take_action_2_folds() {
+        let mut game = init_game_at_deal();
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([
+                Action::AllIn,
+                Action::Call(10),
+                Action::Fold,
+                Action::Raise(20)
+            ]))
+        );
+        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([
+                Action::AllIn,
+                Action::Call(5),
+                Action::Fold,
+                Action::Raise(15)
+            ]))
+        );
+        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
+        assert_eq!(game.get_next_action_options(), None);
+    }
+
+    #[test]
+    fn take_action_2_reraises() {
+        let mut game = init_game_at_deal();
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([
+                Action::AllIn,
+                Action::Call(10),
+                Action::Fold,
+                Action::Raise(20)
+            ]))
+        );
+        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([
+                Action::AllIn,
+                Action::Call(5),
+                Action::Fold,
+                Action::Raise(15)
+            ]))
+        );
+        // Total call is 20
+        assert_eq!(game.act(Action::Raise(15)), Ok(Action::Raise(15)));
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([
+                Action::AllIn,
+                Action::Call(10),
+                Action::Fold,
+                Action::Raise(20)
+            ]))
+        );
+        // Total call is 40
+        assert_eq!(game.act(Action::Raise(30)), Ok(Action::Raise(30)));
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([
+                Action::AllIn,
+                Action::Call(20),
+                Action::Fold,
+                Action::Raise(40)
+            ]))
+        );
+        // Total call is 80
+        assert_eq!(game.act(Action::Raise(60)), Ok(Action::Raise(60)));
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([
+                Action::AllIn,
+                Action::Call(40),
+                Action::Fold,
+                Action::Raise(80)
+            ]))
+        );
+        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
+        assert_eq!(game.get_next_action_options(), None);
+    }
+
+    /// Return the amounts the next player has to call and raise by, since
+    /// comparing actions ignores their amounts.
+    fn get_call_and_min_raise(game: &Game<TakeAction>) -> (Option<Usd>, Option<Usd>) {
+        let action_options = game.get_next_action_options().unwrap_or_default();
+        let call = action_options.iter().find_map(|action| match action {
+            Action::Call(amount) => Some(*amount),
+            _ => None,
+        });
+        let raise = action_options.iter().find_map(|action| match action {
+            Action::Raise(amount) => Some(*amount),
+            _ => None,
+        });
+        (call, raise)
+    }
+
+    #[test]
+    fn privacy_redacts_non_players() {
+        for privacy in [
+            Privacy::Open,
+            Privacy::HideSpectatorMoney,
+            Privacy::HideNonPlayers,
+        ] {
+            let mut game = init_game_at_deal();
+            game.data.settings.privacy = privacy;
+            for username in ["spectator", "waitlister"] {
+                game.new_user(username).unwrap();
+            }
+            game.waitlist_user("waitlister").unwrap();
+            let views = game.get_views();
+            assert!(views.contains("spectator"));
+            assert!(views.contains("waitlister"));
+            let player_view = views.get("0").unwrap();
+            let spectator_view = views.get("spectator").unwrap();
+            let waitlister_view = views.get("waitlister").unwrap();
+
+            // Everyone can always see the players' stacks.
+            for view in [&player_view, &spectator_view, &waitlister_view] {
+                assert_eq!(view.privacy, privacy);
+                for (player_view, player) in view.players.iter().zip(&game.data.players) {
+                    assert_eq!(player_view.user.money, player.user.money);
+                }
+            }
+
+            // Users can always see themselves, but what they can see of
+            // other non-players depends on the privacy.
+            assert_eq!(spectator_view.spectators["spectator"].money, DEFAULT_BUY_IN);
+            assert_eq!(waitlister_view.waitlist[0].money, DEFAULT_BUY_IN);
+            match privacy {
+                Privacy::Open => {
+                    assert_eq!(player_view.spectators["spectator"].money, DEFAULT_BUY_IN);
+                    assert_eq!(player_view.waitlist[0].money, DEFAULT_BUY_IN);
+                    assert_eq!(spectator_view.waitlist[0].money, DEFAULT_BUY_IN);
+                    assert_eq!(
+                        waitlister_view.spectators["spectator"].money,
+                        DEFAULT_BUY_IN
+                    );
+                }
+                Privacy::HideSpectatorMoney => {
+                    assert_eq!(player_view.spectators["spectator"].money, 0);
+                    assert_eq!(player_view.waitlist[0].money, 0);
+                    assert_eq!(spectator_view.waitlist[0].money, 0);
+                    assert_eq!(waitlister_view.spectators["spectator"].money, 0);
+                }
+                Privacy::HideNonPlayers => {
+                    assert!(player_view.spectators.is_empty());
+                    assert!(player_view.waitlist.is_empty());
+                    assert_eq!(spectator_view.spectators.len(), 1);
+                    assert!(spectator_view.waitlist.is_empty());
+                    assert!(waitlister_view.spectators.is_empty());
+                    assert_eq!(waitlister_view.waitlist.len(), 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn max_raise_is_the_stack() {
+        let game = init_game_at_deal();
+        assert_eq!(game.get_max_raise(), Some(DEFAULT_BUY_IN));
+        let action_options = game.get_action_options().unwrap();
+        let repr = Game::<TakeAction>::action_options_to_string(
+            &HashSet::from([Action::Raise(20)]),
+            game.get_max_raise(),
+        );
+        assert_eq!(repr, "raise between $20 and $200");
+        assert!(
+            Game::<TakeAction>::action_options_to_string(&action_options, None)
+                .contains("raise (>= $20)")
+        );
+
+        // A stack that's too small for the min raise can only go all-in.
+        let game = init_game_at_deal_with_stacks(&[15, 200, 200]);
+        assert_eq!(
+            game.get_action_options(),
+            Some(HashSet::from([
+                Action::AllIn,
+                Action::Call(10),
+                Action::Fold
+            ]))
+        );
+        assert_eq!(game.get_max_raise(), None);
+    }
+
+    #[test]
+    fn min_raise_tracks_last_full_raise() {
+        let mut game = init_game_at_deal();
+        assert_eq!(get_call_and_min_raise(&game), (Some(10), Some(20)));
+
+        // Raising to 30 is a raise of 20, so the next raise has to be at
+        // least 20 on top of the call.
+        assert_eq!(game.act(Action::Raise(30)), Ok(Action::Raise(30)));
+        assert_eq!(get_call_and_min_raise(&game), (Some(25), Some(45)));
+        assert!(matches!(
+            game.act(Action::Raise(44)),
+            Err(UserError::InvalidBet { .. })
+        ));
+
+        // Raising to 80 is a raise of 50.
+        assert_eq!(game.act(Action::Raise(75)), Ok(Action::Raise(75)));
+        assert_eq!(get_call_and_min_raise(&game), (Some(70), Some(120)));
+        assert_eq!(game.act(Action::Call(70)), Ok(Action::Call(70)));
+        assert_eq!(get_call_and_min_raise(&game), (Some(50), Some(100)));
+        assert_eq!(game.act(Action::Call(50)), Ok(Action::Call(50)));
+
+        // Raise sizes reset to the big blind on the next street.
+        let game: Game<Flop> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        assert_eq!(get_call_and_min_raise(&game), (None, Some(10)));
+        assert_eq!(game.act(Action::Raise(25)), Ok(Action::Raise(25)));
+        assert_eq!(get_call_and_min_raise(&game), (Some(25), Some(50)));
+    }
+
+    #[test]
+    fn short_all_in_does_not_reopen_betting() {
+        // The button is first to act after the player under the gun.
+        let mut game = init_game_at_deal_with_stacks(&[27, 200, 200, 200]);
+        assert_eq!(game.act(Action::Raise(20)), Ok(Action::Raise(20)));
+
+        // Going all-in for 27 only raises by 7, which is less than the
+        // last full raise of 10.
+        assert_eq!(game.act(Action::AllIn), Ok(Action::AllIn));
+
+        // Players that haven't acted yet can still raise, but have to
+        // raise by the last full raise.
+        assert_eq!(get_call_and_min_raise(&game), (Some(22), Some(32)));
+        assert_eq!(game.act(Action::Call(22)), Ok(Action::Call(22)));
+        assert_eq!(get_call_and_min_raise(&game), (Some(17), Some(27)));
+        assert_eq!(game.act(Action::Call(17)), Ok(Action::Call(17)));
+
+        // The player under the gun already acted, so they can only call
+        // or fold.
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([Action::Call(7), Action::Fold]))
+        );
+        assert!(matches!(
+            game.act(Action::AllIn),
+            Err(UserError::InvalidAction { .. })
+        ));
+        assert_eq!(game.act(Action::Call(7)), Ok(Action::Call(7)));
+        assert_eq!(game.get_next_action_options(), None);
+    }
+
+    #[test]
+    fn uncalled_raise_is_returned_when_everyone_folds() {
+        let mut game = init_game_at_deal();
+        game.drain_events();
+        let starting_stack = game.data.settings.starting_stack;
+        let raiser_idx = game.data.next_action_idx.unwrap();
+        let raiser = game.data.players[raiser_idx].user.name.clone();
+        assert_eq!(game.act(Action::Raise(30)), Ok(Action::Raise(30)));
+        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
+        assert!(game.drain_events().is_empty());
+        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
+
+        // Only the big blind's 10 was called, so the other 20 goes back
+        // to the raiser before the pot is distributed.
+        assert_eq!(
+            game.drain_events(),
+            VecDeque::from([GameEvent::UncalledBetReturned(raiser, 20)])
+        );
+        assert_eq!(
+            game.data.players[raiser_idx].user.money,
+            starting_stack - 10
+        );
+        assert_eq!(game.data.pot.get_size(), 25);
+
+        // The raiser only wins the blinds.
+        let game: Game<DistributePot> = game.into();
+        let game: Game<RemovePlayers> = game.into();
+        assert_eq!(
+            game.data.players[raiser_idx].user.money,
+            starting_stack + 15
+        );
+    }
+
+    #[test]
+    fn uncalled_part_of_all_in_is_returned() {
+        // The big blind only has 40 behind after posting.
+        let mut game = init_game_at_deal_with_stacks(&[200, 200, 50, 200]);
+        game.drain_events();
+        let shover_idx = game.data.next_action_idx.unwrap();
+        let shover = game.data.players[shover_idx].user.name.clone();
+        assert_eq!(game.act(Action::AllIn), Ok(Action::AllIn));
+        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
+        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
+        let big_blind = game.data.players[game.data.next_action_idx.unwrap()]
+            .user
+            .name
+            .clone();
+        assert_eq!(game.act(Action::AllIn), Ok(Action::AllIn));
+
+        // The big blind could only call 50 of the 200, so the rest is
+        // returned and only the called part is contested. All-ins are
+        // announced with what the player committed.
+        assert_eq!(
+            game.drain_events(),
+            VecDeque::from([
+                GameEvent::AllIn(shover.clone(), 200),
+                GameEvent::AllIn(big_blind, 40),
+                GameEvent::UncalledBetReturned(shover, 150)
+            ])
+        );
+        assert_eq!(game.data.players[shover_idx].user.money, 150);
+        assert_eq!(game.data.pot.get_size(), 105);
+        // What's returned comes out of this street's bets.
+        assert_eq!(game.data.pot.get_street_bets(), 105);
+        assert!(game.is_ready_for_showdown());
+    }
+
+    #[test]
+    fn blind_that_takes_the_whole_stack_is_an_all_in() {
+        // The big blind can only just cover the blind.
+        let big_blind = DEFAULT_MIN_BIG_BLIND;
+        let mut game = init_game_at_deal_with_stacks(&[200, 200, big_blind]);
+        let big_blind_idx = game.data.big_blind_idx;
+        assert_eq!(game.data.players[big_blind_idx].state, PlayerState::AllIn);
+        assert_eq!(game.data.players[big_blind_idx].user.money, 0);
+        let all_ins: Vec<_> = game
+            .drain_events()
+            .into_iter()
+            .filter(|event| matches!(event, GameEvent::AllIn(..)))
+            .collect();
+        assert_eq!(
+            all_ins,
+            [GameEvent::AllIn(
+                game.data.players[big_blind_idx].user.name.clone(),
+                big_blind
+            )]
+        );
+    }
+
+    fn init_fixed_limit_game_at_deal_with_stacks(stacks: &[Usd]) -> Game<TakeAction> {
+        let mut game = init_game_at_deal_with_stacks(stacks);
+        game.data.settings.limit = Some(LimitStructure {
+            small_bet: 10,
+            big_bet: 20,
+            max_raises_per_street: 3,
+        });
+        game.state.action_options = game.get_next_action_options();
+        game
+    }
+
+    #[test]
+    fn fixed_limit_caps_raises_per_street() {
+        let mut game = init_fixed_limit_game_at_deal_with_stacks(&[200, 200, 200]);
+
+        // Raises are exactly one small bet before the flop, so there's no
+        // going all-in for more.
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([
+                Action::Call(10),
+                Action::Fold,
+                Action::Raise(20)
+            ]))
+        );
+        assert!(matches!(
+            game.act(Action::Raise(25)),
+            Err(UserError::InvalidBet { .. })
+        ));
+        assert!(matches!(
+            game.act(Action::Raise(200)),
+            Err(UserError::InvalidAction { .. })
+        ));
+
+        // Three raises cap the betting, leaving the last player with only
+        // the option to call or fold.
+        assert_eq!(game.act(Action::Raise(20)), Ok(Action::Raise(20)));
+        assert_eq!(get_call_and_min_raise(&game), (Some(15), Some(25)));
+        assert_eq!(game.act(Action::Raise(25)), Ok(Action::Raise(25)));
+        assert_eq!(get_call_and_min_raise(&game), (Some(20), Some(30)));
+        assert_eq!(game.act(Action::Raise(30)), Ok(Action::Raise(30)));
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([Action::Call(20), Action::Fold]))
+        );
+        assert_eq!(game.act(Action::Call(20)), Ok(Action::Call(20)));
+        assert_eq!(game.act(Action::Call(10)), Ok(Action::Call(10)));
+
+        // The cap resets on the next street.
+        let game: Game<Flop> = game.into();
+        let game: Game<TakeAction> = game.into();
+        assert_eq!(get_call_and_min_raise(&game), (None, Some(10)));
+    }
+
+    #[test]
+    fn fixed_limit_doubles_bet_on_turn() {
+        let mut game = init_fixed_limit_game_at_deal_with_stacks(&[200, 200, 200]);
+        assert_eq!(game.act(Action::Call(10)), Ok(Action::Call(10)));
+        assert_eq!(game.act(Action::Call(5)), Ok(Action::Call(5)));
+        assert_eq!(game.act(Action::Check), Ok(Action::Check));
+
+        // Small bets on the flop.
+        let game: Game<Flop> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        assert_eq!(get_call_and_min_raise(&game), (None, Some(10)));
+        for _ in 0..3 {
+            assert_eq!(game.act(Action::Check), Ok(Action::Check));
+        }
+
+        // Big bets from the turn on.
+        let game: Game<Turn> = game.into();
+        let mut game: Game<TakeAction> = game.into();
+        assert_eq!(get_call_and_min_raise(&game), (None, Some(20)));
+        assert!(matches!(
+            game.act(Action::Raise(10)),
+            Err(UserError::InvalidBet { .. })
+        ));
+        assert_eq!(game.act(Action::Raise(20)), Ok(Action::Raise(20)));
+        assert_eq!(get_call_and_min_raise(&game), (Some(20), Some(40)));
+        assert_eq!(game.get_max_raise(), Some(40));
+        assert_eq!(
+            game.as_public_view().limit.unwrap().to_string(),
+            "10/20 Limit"
+        );
+    }
+
+    #[test]
+    fn fixed_limit_all_in_for_less_than_a_bet() {
+        let mut game = init_fixed_limit_game_at_deal_with_stacks(&[27, 200, 200, 200]);
+        assert_eq!(game.act(Action::Raise(20)), Ok(Action::Raise(20)));
+
+        // Going all-in for 27 is less than the 30 it takes to raise, so
+        // it doesn't count towards the cap.
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([
+                Action::AllIn,
+                Action::Call(20),
+                Action::Fold
+            ]))
+        );
+        assert_eq!(game.act(Action::AllIn), Ok(Action::AllIn));
+        assert_eq!(game.data.pot.num_raises, 1);
+
+        // Raises are still one bet on top of the call.
+        assert_eq!(get_call_and_min_raise(&game), (Some(22), Some(32)));
+        assert_eq!(game.act(Action::Call(22)), Ok(Action::Call(22)));
+        assert_eq!(get_call_and_min_raise(&game), (Some(17), Some(27)));
+        assert_eq!(game.act(Action::Call(17)), Ok(Action::Call(17)));
+
+        // The all-in didn't reopen the betting.
+        assert_eq!(
+            game.get_next_action_options(),
+            Some(HashSet::from([Action::Call(7), Action::Fold]))
+        );
+    }
+
+    #[test]
+    fn decision_context_matches_game() {
+        let mut game = init_game_at_deal_with_stacks(&[15, 1000, 1000]);
+        let assert_context = |game: &Game<TakeAction>, expected: DecisionContext| {
+            let context = game.get_decision_context().unwrap();
+            let player_idx = game.data.next_action_idx.unwrap();
+            assert_eq!(context.cards, game.data.players[player_idx].cards);
+            assert_eq!(context.board, game.data.board);
+            let (call, min_raise) = get_call_and_min_raise(game);
+            assert_eq!(call.unwrap_or(context.call), context.call);
+            assert_eq!(min_raise, context.min_raise);
+            assert_eq!(game.get_max_raise(), context.max_raise);
+            assert_eq!(
+                context,
+                DecisionContext {
+                    cards: context.cards.clone(),
+                    ..expected
+                }
+            );
+        };
+        let context = |pot, call, min_raise, max_raise, stack, position| DecisionContext {
+            variant: GameVariant::Holdem,
+            board: vec![],
+            cards: vec![],
+            pot,
+            call,
+            min_raise,
+            max_raise,
+            stack,
+            position,
+            num_players: 3,
+        };
+
+        // The button can only afford to go all-in.
+        assert_context(&game, context(15, 10, None, None, 15, 0));
+        assert_eq!(game.act(Action::AllIn), Ok(Action::AllIn));
+
+        // The all-in wasn't a full raise, so the minimum raise is still
+        // a big blind on top of the call.
+        assert_context(&game, context(30, 10, Some(20), Some(995), 995, 1));
+        assert_eq!(game.act(Action::Call(10)), Ok(Action::Call(10)));
+        assert_context(&game, context(40, 5, Some(15), Some(990), 990, 2));
+        assert_eq!(game.act(Action::Call(5)), Ok(Action::Call(5)));
+        assert_eq!(game.get_decision_context(), None);
+    }
+
+    fn init_game_at_deal_with_stacks(stacks: &[Usd]) -> Game<TakeAction> {
+        let game = Game::<Lobby>::new();
+        let mut game: Game<SeatPlayers> = game.into();
+        for i in 0..stacks.len() {
+            let username = i.to_string();
+            game.new_user(&username).unwrap();
+            game.waitlist_user(&username).unwrap();
+        }
+        let mut game: Game<MoveButton> = game.into();
+        for (player, money) in game.data.players.iter_mut().zip(stacks) {
+            player.user.money = *money;
+        }
+        let game: Game<CollectBlinds> = game.into();
+        let game: Game<Deal> = game.into();
+        let game: Game<TakeAction> = game.into();
+        game
+    }
+
+    #[test]
+    fn side_pot_players_are_shown() {
+        for auto_show_losers in [true, false] {
+            let mut game = init_game_at_deal_with_stacks(&[100, 1000, 1000]);
+            while game.get_action_options().is_some() {
+                game.act(Action::AllIn).unwrap();
+            }
+            let game: Game<Flop> = game.into();
+            let game: Game<Turn> = game.into();
+            let game: Game<River> = game.into();
+            let mut game: Game<ShowHands> = game.into();
+            game.data.settings.auto_show_losers = auto_show_losers;
+            game.data.board = functional::parse_cards("As 4d 5d 6d 7d").unwrap();
+            // The short stack wins the main pot, and the side pot goes to
+            // the better of the two big stacks.
+            game.data.players[0].cards = functional::parse_cards("3h Ad").unwrap();
+            game.data.players[1].cards = functional::parse_cards("Ah 10d").unwrap();
+            game.data.players[2].cards = functional::parse_cards("2h 9d").unwrap();
+            let game: Game<DistributePot> = game.into();
+            let showing: Vec<bool> = game.data.players.iter().map(|p| p.showing).collect();
+            assert_eq!(showing, [true, true, auto_show_losers]);
+        }
+    }
+
+    #[test]
+    fn validate_action_agrees_with_act() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let big_blind = DEFAULT_MIN_BIG_BLIND;
+        for _ in 0..1000 {
+            let num_players = rng.gen_range(2..=MAX_PLAYERS);
+            let stacks: Vec<Usd> = (0..num_players)
+                .map(|_| rng.gen_range(big_blind..=5 * DEFAULT_BUY_IN))
+                .collect();
+            let mut game = init_game_at_deal_with_stacks(&stacks);
+            while let Some(action_options) = game.get_action_options() {
+                let player_idx = game.data.next_action_idx.unwrap();
+                let money = game.data.players[player_idx].user.money;
+                // Half of the actions are straight from the action options
+                // so hands make it past the first few bets.
+                let action = if rng.gen_bool(0.5) {
+                    let mut action_options: Vec<Action> = action_options.iter().cloned().collect();
+                    action_options.sort_by_key(|action| -> usize { action.clone().into() });
+                    action_options[rng.gen_range(0..action_options.len())].clone()
+                } else {
+                    let amount = rng.gen_range(0..=money + big_blind);
+                    match rng.gen_range(0..5) {
+                        0 => Action::AllIn,
+                        1 => Action::Call(amount),
+                        2 => Action::Check,
+                        3 => Action::Fold,
+                        _ => Action::Raise(amount),
+                    }
+                };
+
+                // Actions only compare their variants, so their debug
+                // representations are compared to include amounts.
+                let expected = validate_action(&action_options, money, action.clone());
+                let actual = game.act(action);
+                assert_eq!(format!("{actual:?}"), format!("{expected:?}"));
+                if actual.is_err() {
+                    assert_eq!(game.data.next_action_idx, Some(player_idx));
+                    assert_eq!(game.data.players[player_idx].user.money, money);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod state_tests {
+    use std::{
+        collections::{HashMap, HashSet, VecDeque},
+        mem::discriminant,
+    };
+
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+    use super::{
+        collusion::{SoftPlayConfig, SoftPlayDetector},
+        constants::{DEFAULT_MAX_USERS, MAX_PLAYERS, MIN_PLAYERS},
+        entities::{
+            Action, Bet, BetAction, Card, GameEvent, GamePhase, PlayerState, Street, Suit,
+            DEFAULT_BUY_IN, DEFAULT_MIN_BIG_BLIND, DEFAULT_MIN_SMALL_BLIND,
+        },
+        ConfigError, GameSettings, PokerState, UserError,
+    };
+
+    fn init_state() -> PokerState {
+        let mut state = PokerState::new();
+        for i in 0..3 {
+            let username = i.to_string();
+            state.new_user(&username).unwrap();
+            state.waitlist_user(&username).unwrap();
+        }
+        state
+    }
+
+    #[test]
+    fn all_in_player_wins_after_leaving() {
+        let mut state = init_state();
+        assert_eq!(state.init_start("0"), Ok(()));
+        // Step to TakeAction.
+        for _ in 0..5 {
+            state = state.step();
+        }
+        let all_in_username = state.get_next_action_username().unwrap();
+        assert_eq!(
+            state.take_action(&all_in_username, Action::AllIn),
+            Ok(Action::AllIn)
+        );
+        // The all-in player leaves during another player's turn.
+        let caller_username = state.get_next_action_username().unwrap();
+        assert_eq!(state.remove_user(&all_in_username), Ok(()));
+        assert_eq!(
+            state.take_action(&caller_username, Action::AllIn),
+            Ok(Action::AllIn)
+        );
+        // The last player can't check, so they're forced to fold.
+        assert_eq!(state.get_auto_action(), Some(Action::Fold));
+        while !matches!(state, PokerState::ShowHands(_)) {
+            state = state.step();
+        }
+        if let PokerState::ShowHands(ref mut game) = state {
+            game.data.board = vec![
+                Card(10, Suit::Spade),
+                Card(11, Suit::Spade),
+                Card(12, Suit::Spade),
+                Card(2, Suit::Diamond),
+                Card(3, Suit::Heart),
+            ];
+            for player in game.data.players.iter_mut() {
+                player.cards = if player.user.name == all_in_username {
+                    vec![Card(13, Suit::Spade), Card(1, Suit::Spade)]
+                } else {
+                    vec![Card(4, Suit::Club), Card(5, Suit::Club)]
+                };
+            }
+        }
+        while !matches!(state, PokerState::RemovePlayers(_)) {
+            state = state.step();
+        }
+        if let PokerState::RemovePlayers(ref game) = state {
+            let mut total_money = 3 * game.data.settings.starting_stack;
+            for player in game.data.players.iter() {
+                if player.user.name == caller_username {
+                    assert_eq!(player.user.money, 0);
+                } else if player.user.name != all_in_username {
+                    total_money -= player.user.money;
+                }
+            }
+            let player = game
+                .data
+                .players
+                .iter()
+                .find(|p| p.user.name == all_in_username)
+                .unwrap();
+            assert_eq!(player.user.money, total_money);
+            assert!(game.data.players_to_remove.contains(&all_in_username));
+        } else {
+            panic!("should be removing players");
+        }
+        state = state.step();
+        if let PokerState::DivideDonations(ref game) = state {
+            assert!(!game.contains_user(&all_in_username));
+        } else {
+            panic!("should be dividing donations");
+        }
+    }
+
+    /// Have everyone check or call until the betting round is over.
+    fn check_or_call(state: &mut PokerState) {
+        while let (Some(username), Some(action_options)) =
+            (state.get_next_action_username(), state.get_action_options())
+        {
+            let action = action_options
+                .get(&Action::Check)
+                .or(action_options.get(&Action::Call(0)))
+                .unwrap()
+                .clone();
+            assert!(state.take_action(&username, action).is_ok());
+        }
+    }
+
+    #[test]
+    fn community_cards_are_dealt_one_event_at_a_time() {
+        let mut state = init_state();
+        assert_eq!(state.init_start("0"), Ok(()));
+        // Everyone checks or calls down to a showdown.
+        let mut dealt = vec![];
+        while !matches!(state, PokerState::ShowHands(_)) {
+            state = state.step();
+            check_or_call(&mut state);
+            dealt.extend(
+                state
+                    .drain_events()
+                    .into_iter()
+                    .filter_map(|event| match event {
+                        GameEvent::CommunityCardDealt(card, position) => Some((card, position)),
+                        _ => None,
+                    }),
+            );
+        }
+        if let PokerState::ShowHands(ref game) = state {
+            let expected: Vec<(Card, usize)> = game
+                .data
+                .board
+                .iter()
+                .enumerate()
+                .map(|(position, card)| (*card, position))
+                .collect();
+            assert_eq!(expected.len(), 5);
+            assert_eq!(dealt, expected);
+        } else {
+            panic!("should be showing hands");
+        }
+    }
+
+    #[test]
+    fn deck_reveal_matches_commitment() {
+        let mut state = init_state();
+        assert_eq!(state.init_start("0"), Ok(()));
+        let mut events = vec![];
+        let mut dealt = None;
+        loop {
+            state = state.step();
+            check_or_call(&mut state);
+            let hand_id = state.get_hand_id();
+            events.extend(
+                state
+                    .drain_events()
+                    .into_iter()
+                    .map(|event| (hand_id, event)),
+            );
+            match state {
+                PokerState::ShowHands(ref game) if dealt.is_none() => {
+                    let hole_cards: Vec<Card> = game
+                        .data
+                        .players
+                        .iter()
+                        .flat_map(|player| player.cards.clone())
+                        .collect();
+                    dealt = Some((hole_cards, game.data.board.clone()));
+                }
+                PokerState::Lobby(_) => break,
+                _ => {}
+            }
+        }
+        let commitments: Vec<_> = events
+            .iter()
+            .filter_map(|(hand_id, event)| match event {
+                GameEvent::DeckCommitment(commitment) => Some((hand_id, commitment)),
+                _ => None,
+            })
+            .collect();
+        let reveals: Vec<_> = events
+            .iter()
+            .filter_map(|(hand_id, event)| match event {
+                GameEvent::DeckReveal(reveal) => Some((hand_id, reveal)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(commitments.len(), 1);
+        assert_eq!(reveals.len(), 1);
+        // Both are relayed with the same hand ID so clients can match
+        // them up.
+        let (commitment_hand_id, commitment) = commitments[0];
+        let (reveal_hand_id, reveal) = reveals[0];
+        assert_eq!(commitment_hand_id, reveal_hand_id);
+        assert!(reveal.verify(commitment));
+        let (hole_cards, board) = dealt.unwrap();
+        assert_eq!(board.len(), 5);
+        assert!(reveal.dealt(&hole_cards, &board));
+
+        // The reveal is only sent once per hand.
+        state = state.step();
+        assert!(!state
+            .drain_events()
+            .iter()
+            .any(|event| matches!(event, GameEvent::DeckReveal(_))));
+    }
+
+    #[test]
+    fn burned_cards_shift_the_board() {
+        let play_to_showdown = |burn_cards| {
+            let settings = GameSettings {
+                burn_cards,
+                seed: Some(7),
+                ..Default::default()
+            };
+            let mut state: PokerState = settings.into();
+            for i in 0..3 {
+                let username = i.to_string();
+                state.new_user(&username).unwrap();
+                state.waitlist_user(&username).unwrap();
+            }
+            assert_eq!(state.init_start("0"), Ok(()));
+            let mut dealt = None;
+            let mut reveal = None;
+            loop {
+                state = state.step();
+                check_or_call(&mut state);
+                for event in state.drain_events() {
+                    if let GameEvent::DeckReveal(deck_reveal) = event {
+                        reveal = Some(deck_reveal);
+                    }
+                }
+                match state {
+                    PokerState::ShowHands(ref game) if dealt.is_none() => {
+                        let hole_cards: Vec<Card> = game
+                            .data
+                            .players
+                            .iter()
+                            .flat_map(|player| player.cards.clone())
+                            .collect();
+                        dealt = Some((hole_cards, game.data.board.clone()));
+                    }
+                    PokerState::Lobby(_) => break,
+                    _ => {}
+                }
+            }
+            let (hole_cards, board) = dealt.unwrap();
+            (hole_cards, board, reveal.unwrap())
+        };
+
+        // Without burns, the board comes right after the hole cards.
+        let (hole_cards, board, reveal) = play_to_showdown(false);
+        let deck = reveal.deck.clone();
+        assert_eq!(board, deck[6..11]);
+        assert!(reveal.burned.is_empty());
+        assert!(reveal.dealt(&hole_cards, &board));
+
+        // With burns, the same deck deals a card later on the flop, two
+        // later on the turn, and three later on the river.
+        let (hole_cards, board, reveal) = play_to_showdown(true);
+        assert_eq!(reveal.deck, deck);
+        assert_eq!(reveal.burned, [deck[6], deck[10], deck[12]]);
+        assert_eq!(board, [&deck[7..10], &deck[11..12], &deck[13..14]].concat());
+        assert!(reveal.dealt(&hole_cards, &board));
+    }
+
+    #[test]
+    fn disconnected_player_checks_down_until_removed() {
+        let mut state = init_state();
+        assert_eq!(state.init_start("0"), Ok(()));
+        // Step to TakeAction.
+        for _ in 0..5 {
+            state = state.step();
+        }
+        let disconnected_username = match state {
+            PokerState::TakeAction(ref game) => {
+                game.data.players[game.data.big_blind_idx].user.name.clone()
+            }
+            _ => panic!("should be taking actions"),
+        };
+        assert_eq!(state.disconnect_user(&disconnected_username), Ok(()));
+        // Everyone else calls or checks, so the disconnected player
+        // is never facing a bet and checks down to the showdown.
+        while !matches!(state, PokerState::ShowHands(_)) {
+            match state.get_next_action_username() {
+                Some(username) if !state.is_disconnected(&username) => {
+                    let action = state
+                        .get_action_options()
+                        .unwrap()
+                        .into_iter()
+                        .find(|action| matches!(action, Action::Call(_)))
+                        .unwrap_or(Action::Check);
+                    assert!(state.take_action(&username, action).is_ok());
+                }
+                _ => state = state.step(),
+            }
+        }
+        if let PokerState::ShowHands(ref game) = state {
+            let player = game
+                .data
+                .players
+                .iter()
+                .find(|p| p.user.name == disconnected_username)
+                .unwrap();
+            assert_eq!(player.state, PlayerState::Check);
+        }
+        // The player keeps their seat for the next hand.
+        while !matches!(state, PokerState::DivideDonations(_)) {
+            state = state.step();
+        }
+        if let PokerState::DivideDonations(ref game) = state {
+            assert!(game.contains_player(&disconnected_username));
+        }
+        assert!(state.is_disconnected(&disconnected_username));
+        // And is removed after it.
+        while !matches!(state, PokerState::Lobby(_)) {
+            state = state.step();
+        }
+        let connected_username = ["0", "1"]
+            .into_iter()
+            .find(|username| *username != disconnected_username)
+            .unwrap();
+        assert_eq!(state.init_start(connected_username), Ok(()));
+        while !matches!(state, PokerState::DivideDonations(_)) {
+            state = state.step();
+        }
+        if let PokerState::DivideDonations(ref game) = state {
+            assert!(!game.contains_user(&disconnected_username));
+        }
+        assert!(!state.is_disconnected(&disconnected_username));
+    }
+
+    #[test]
+    fn disconnected_player_folds_to_a_bet() {
+        let mut state = init_state();
+        assert_eq!(state.init_start("0"), Ok(()));
+        // Step to TakeAction.
+        for _ in 0..5 {
+            state = state.step();
+        }
+        let username = state.get_next_action_username().unwrap();
+        assert_eq!(state.disconnect_user(&username), Ok(()));
+        assert!(state.is_disconnected(&username));
+        assert_eq!(state.get_auto_action(), Some(Action::Fold));
+        state = state.step();
+        assert_ne!(state.get_next_action_username(), Some(username.clone()));
+        if let PokerState::TakeAction(ref game) = state {
+            let player = game
+                .data
+                .players
+                .iter()
+                .find(|p| p.user.name == username)
+                .unwrap();
+            assert_eq!(player.state, PlayerState::Fold);
+            assert!(!game.data.players_to_remove.contains(&username));
+        } else {
+            panic!("should be taking actions");
+        }
+    }
+
+    #[test]
+    fn stale_actions_are_rejected_on_later_turns() {
+        let mut state = init_state();
+        assert_eq!(state.init_start("0"), Ok(()));
+        // Step to TakeAction.
+        for _ in 0..5 {
+            state = state.step();
+        }
+        let call_or_check = |state: &PokerState| {
+            state
+                .get_action_options()
+                .unwrap()
+                .into_iter()
+                .find(|action| matches!(action, Action::Call(_)))
+                .unwrap_or(Action::Check)
+        };
+        let username = state.get_next_action_username().unwrap();
+        let turn_id = state.get_turn_id().unwrap();
+        let action = call_or_check(&state);
+        assert!(state
+            .take_turn_action(&username, action.clone(), turn_id)
+            .is_ok());
+        // The same action can't be applied twice to the same turn, even
+        // though it names the right turn, since the turn has passed.
+        assert_eq!(
+            state.take_turn_action(&username, action, turn_id),
+            Err(UserError::OutOfTurnAction)
+        );
+        // Play until it's the same player's turn again.
+        loop {
+            match state.get_next_action_username() {
+                Some(next_username) if next_username == username => break,
+                Some(next_username) => {
+                    let action = call_or_check(&state);
+                    assert!(state.take_action(&next_username, action).is_ok());
+                }
+                None => state = state.step(),
+            }
+        }
+        // A delayed action meant for the earlier turn is rejected instead
+        // of being applied to this one.
+        let action = call_or_check(&state);
+        let next_turn_id = state.get_turn_id().unwrap();
+        assert_ne!(next_turn_id, turn_id);
+        assert_eq!(
+            state.take_turn_action(&username, action.clone(), turn_id),
+            Err(UserError::StaleAction)
+        );
+        assert_eq!(state.get_next_action_username(), Some(username.clone()));
+        assert!(state
+            .take_turn_action(&username, action, next_turn_id)
+            .is_ok());
+    }
+
+    #[test]
+    fn leaving_player_is_skipped_right_away() {
+        let mut state = init_state();
+        assert_eq!(state.init_start("0"), Ok(()));
+        // Step to TakeAction.
+        for _ in 0..5 {
+            state = state.step();
+        }
+        let username = state.get_next_action_username().unwrap();
+        assert_eq!(state.remove_user(&username), Ok(()));
+        // The turn passes without waiting for the leaving player.
+        let next_username = state.get_next_action_username().unwrap();
+        assert_ne!(next_username, username);
+        if let PokerState::TakeAction(ref game) = state {
+            let player = game
+                .data
+                .players
+                .iter()
+                .find(|p| p.user.name == username)
+                .unwrap();
+            assert_eq!(player.state, PlayerState::Fold);
+            assert_eq!(game.data.num_players_active, 2);
+        } else {
+            panic!("should be taking actions");
+        }
+        // The others play out the hand without the leaving player ever
+        // getting a turn.
+        while !matches!(state, PokerState::RemovePlayers(_)) {
+            match state.get_next_action_username() {
+                Some(next_username) => {
+                    assert_ne!(next_username, username);
+                    let action = state
+                        .get_action_options()
+                        .unwrap()
+                        .into_iter()
+                        .find(|action| matches!(action, Action::Call(_)))
+                        .unwrap_or(Action::Check);
+                    assert!(state.take_action(&next_username, action).is_ok());
+                }
+                None => state = state.step(),
+            }
+        }
+        state = state.step();
+        if let PokerState::DivideDonations(ref game) = state {
+            assert!(!game.contains_user(&username));
+        } else {
+            panic!("should be dividing donations");
+        }
+    }
+
+    #[test]
+    fn random_first_seating_draws_seats_and_the_button() {
+        let seed = 7;
+        let init_state = |random_first_seating| {
+            let settings = GameSettings {
+                random_first_seating,
+                seed: Some(seed),
+                ..Default::default()
+            };
+            let mut state: PokerState = settings.into();
+            for i in 0..3 {
+                let username = i.to_string();
+                state.new_user(&username).unwrap();
+                state.waitlist_user(&username).unwrap();
+            }
+            assert_eq!(state.init_start("0"), Ok(()));
+            // Step to CollectBlinds.
+            for _ in 0..3 {
+                state = state.step();
+            }
+            state
+        };
+        let seats = |state: &PokerState| match state {
+            PokerState::CollectBlinds(ref game) => {
+                let button = game.data.players[game.data.button_idx].user.name.clone();
+                let seats: Vec<_> = game
+                    .data
+                    .players
+                    .iter()
+                    .map(|player| (player.user.name.clone(), player.seat_idx))
+                    .collect();
+                (seats, button)
+            }
+            _ => panic!("should be collecting blinds"),
+        };
+
+        // Without a draw, players sit in waitlist order and the first
+        // seat gets the button.
+        let mut state = init_state(false);
+        let (seats_in_order, button) = seats(&state);
+        assert_eq!(
+            seats_in_order,
+            [
+                ("0".to_string(), 0),
+                ("1".to_string(), 1),
+                ("2".to_string(), 2)
+            ]
+        );
+        assert_eq!(button, "0");
+        assert!(state.drain_events().is_empty());
+
+        // The draw hands out seats and the button just like shuffling
+        // the seats and picking a player with the same seed would.
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut open_seats = Vec::from_iter(0..MAX_PLAYERS);
+        open_seats.shuffle(&mut rng);
+        let mut expected_seats: Vec<_> = (0..3).map(|i| (i.to_string(), open_seats[i])).collect();
+        expected_seats.sort_by_key(|(_, seat_idx)| *seat_idx);
+        let expected_button = expected_seats[rng.gen_range(0..3)].0.clone();
+        let mut state = init_state(true);
+        let (drawn_seats, button) = seats(&state);
+        assert_eq!(drawn_seats, expected_seats);
+        assert_eq!(button, expected_button);
+        let mut expected_events: VecDeque<_> = expected_seats
+            .into_iter()
+            .map(|(username, seat_idx)| GameEvent::SeatDrawn(username, seat_idx))
+            .collect();
+        expected_events.push_back(GameEvent::ButtonDrawn(expected_button));
+        assert_eq!(state.drain_events(), expected_events);
+
+        // Tables that already have players don't draw again.
+        while !matches!(state, PokerState::Lobby(_)) {
+            match state.get_next_action_username() {
+                Some(username) => {
+                    let action = state
+                        .get_action_options()
+                        .unwrap()
+                        .into_iter()
+                        .find(|action| matches!(action, Action::Call(_)))
+                        .unwrap_or(Action::Check);
+                    assert!(state.take_action(&username, action).is_ok());
+                }
+                None => state = state.step(),
+            }
+        }
+        assert_eq!(state.init_start("0"), Ok(()));
+        for _ in 0..3 {
+            state = state.step();
+        }
+        let (seats_next_hand, _) = seats(&state);
+        assert_eq!(seats_next_hand, drawn_seats);
+        assert!(!state
+            .drain_events()
             .iter()
-            .enumerate()
+            .any(|event| matches!(event, GameEvent::SeatDrawn(..) | GameEvent::ButtonDrawn(_))));
+    }
+
+    #[test]
+    fn bomb_pots_skip_pre_flop_betting() {
+        let settings = GameSettings {
+            bomb_pot_frequency: 2,
+            ..Default::default()
+        };
+        let mut state: PokerState = settings.into();
+        for i in 0..3 {
+            let username = i.to_string();
+            state.new_user(&username).unwrap();
+            state.waitlist_user(&username).unwrap();
+        }
+        assert_eq!(state.init_start("0"), Ok(()));
+        // Only the 2nd hand is a bomb pot.
+        let mut first_rounds = Vec::new();
+        while first_rounds.len() < 3 {
+            state = state.step();
+            match state {
+                PokerState::CollectBlinds(_) => {
+                    let is_bomb_pot = matches!(state.phase(), GamePhase::CollectingAntes { .. });
+                    assert_eq!(is_bomb_pot, first_rounds.len() == 1);
+                }
+                // Record the first betting round of each hand.
+                PokerState::TakeAction(ref game)
+                    if first_rounds.len() < game.data.hand_id as usize =>
+                {
+                    first_rounds.push((game.data.bomb_pot, game.data.board.len()));
+                }
+                PokerState::Lobby(_) => assert_eq!(state.init_start("0"), Ok(())),
+                _ => {}
+            }
+        }
+        assert_eq!(first_rounds, [(false, 0), (true, 3), (false, 0)]);
+    }
+
+    #[test]
+    fn configured_table_sizes_play_full_hands() {
+        for max_players in [2, 6] {
+            let settings =
+                GameSettings::new(max_players, DEFAULT_MAX_USERS, DEFAULT_BUY_IN).unwrap();
+            let mut state: PokerState = settings.into();
+            // One more user than there are seats so someone has to wait.
+            for i in 0..=max_players {
+                let username = i.to_string();
+                state.new_user(&username).unwrap();
+                state.waitlist_user(&username).unwrap();
+            }
+            assert_eq!(state.init_start("0"), Ok(()));
+            let mut num_hands = 0;
+            while num_hands < 2 {
+                state = state.step();
+                match state {
+                    PokerState::MoveButton(ref game) => {
+                        assert_eq!(game.data.players.len(), max_players);
+                        assert_eq!(game.data.waitlist.len(), 1);
+                        assert!(game.data.open_seats.is_empty());
+                    }
+                    PokerState::BootPlayers(_) => num_hands += 1,
+                    PokerState::Lobby(_) => assert_eq!(state.init_start("0"), Ok(())),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn collusion_is_suspected_after_the_hand() {
+        let settings = GameSettings {
+            collusion_detector: Some(Box::new(SoftPlayDetector::new(SoftPlayConfig {
+                min_hands: 1,
+                ..Default::default()
+            }))),
+            ..Default::default()
+        };
+        let mut state: PokerState = settings.into();
+        for i in 0..3 {
+            let username = i.to_string();
+            state.new_user(&username).unwrap();
+            state.waitlist_user(&username).unwrap();
+        }
+        assert_eq!(state.init_start("0"), Ok(()));
+
+        // The first player to act min-raises and everyone folds to them.
+        let mut raiser = None;
+        let mut suspicions = Vec::new();
+        while !matches!(state, PokerState::BootPlayers(_)) {
+            if let Some(username) = state.get_next_action_username() {
+                let action = match state.get_action_options().unwrap().get(&Action::Raise(0)) {
+                    Some(raise) if raiser.is_none() => raise.clone(),
+                    _ => Action::Fold,
+                };
+                raiser.get_or_insert(username.clone());
+                state.take_action(&username, action).unwrap();
+            }
+            state = state.step();
+            for event in state.drain_events() {
+                if let GameEvent::SuspicionRaised(username1, username2, _) = event {
+                    // Suspicions are only raised once the hand is over.
+                    assert!(matches!(state, PokerState::RemovePlayers(_)));
+                    suspicions.push([username1, username2]);
+                }
+            }
+        }
+        let raiser = raiser.unwrap();
+        assert_eq!(suspicions.len(), 2);
+        assert!(suspicions.iter().all(|pair| pair.contains(&raiser)));
+    }
+
+    #[test]
+    fn invalid_table_sizes() {
+        for max_players in [0, MIN_PLAYERS - 1, MAX_PLAYERS + 1, 13] {
+            assert_eq!(
+                GameSettings::new(max_players, DEFAULT_MAX_USERS, DEFAULT_BUY_IN).unwrap_err(),
+                ConfigError::InvalidMaxPlayers {
+                    min: MIN_PLAYERS,
+                    max: MAX_PLAYERS
+                }
+            );
+        }
+        assert_eq!(
+            GameSettings::new(6, 5, DEFAULT_BUY_IN).unwrap_err(),
+            ConfigError::InvalidMaxUsers { max_players: 6 }
+        );
+    }
+
+    #[test]
+    fn derived_blinds_have_to_be_playable() {
+        // Buy-ins this small would derive blinds that round down to zero
+        // and never go up.
+        for buy_in in [0, 1, 19, 20, 30, 39] {
+            assert_eq!(
+                GameSettings::new(MAX_PLAYERS, DEFAULT_MAX_USERS, buy_in).unwrap_err(),
+                ConfigError::InvalidBlinds
+            );
+        }
+
+        // The smallest buy-in that works still has blinds worth playing.
+        let settings = GameSettings::new(MAX_PLAYERS, DEFAULT_MAX_USERS, 40).unwrap();
+        assert_eq!(settings.min_small_blind, 1);
+        assert_eq!(settings.min_big_blind, 2);
+        let mut state: PokerState = settings.into();
+        for i in 0..2 {
+            let username = i.to_string();
+            state.new_user(&username).unwrap();
+            state.waitlist_user(&username).unwrap();
+        }
+        assert_eq!(state.init_start("0"), Ok(()));
+        for _ in 0..5 {
+            state = state.step();
+        }
+        let views = state.get_views();
+        let view = views.get("0").unwrap();
+        assert_eq!(view.pot.size, 3);
+    }
+
+    #[test]
+    fn invalid_built_settings() {
+        for (small_blind, big_blind) in [(0, 10), (10, 10), (10, 5)] {
+            assert_eq!(
+                GameSettings::builder()
+                    .blinds(small_blind, big_blind)
+                    .build()
+                    .unwrap_err(),
+                ConfigError::InvalidBlinds
+            );
+        }
+        assert_eq!(
+            GameSettings::builder()
+                .blinds(25, 50)
+                .starting_stack(49)
+                .build()
+                .unwrap_err(),
+            ConfigError::InvalidStartingStack { big_blind: 50 }
+        );
+        // The max buy-in follows the starting stack unless it's set.
+        assert_eq!(
+            GameSettings::builder()
+                .starting_stack(100)
+                .min_buy_in(150)
+                .build()
+                .unwrap_err(),
+            ConfigError::InvalidBuyIns {
+                min_buy_in: 150,
+                max_buy_in: 100
+            }
+        );
+        assert_eq!(
+            GameSettings::builder()
+                .starting_stack(300)
+                .max_buy_in(200)
+                .build()
+                .unwrap_err(),
+            ConfigError::InvalidBuyIns {
+                min_buy_in: 0,
+                max_buy_in: 200
+            }
+        );
+        assert_eq!(
+            GameSettings::builder()
+                .max_players(6)
+                .max_users(5)
+                .build()
+                .unwrap_err(),
+            ConfigError::InvalidMaxUsers { max_players: 6 }
+        );
+    }
+
+    #[test]
+    fn blinds_stay_put_when_only_the_stack_changes() {
+        let starting_stack = 100 * DEFAULT_MIN_BIG_BLIND;
+        let settings = GameSettings::builder()
+            .starting_stack(starting_stack)
+            .build()
+            .unwrap();
+        assert_eq!(settings.min_small_blind, DEFAULT_MIN_SMALL_BLIND);
+        assert_eq!(settings.min_big_blind, DEFAULT_MIN_BIG_BLIND);
+        assert_eq!(settings.max_buy_in, starting_stack);
+
+        // Blinds are only raised once stacks outgrow the starting stack,
+        // so they stay put after a hand too.
+        let mut state: PokerState = settings.into();
+        for i in 0..3 {
+            let username = i.to_string();
+            state.new_user(&username).unwrap();
+            state.waitlist_user(&username).unwrap();
+        }
+        assert_eq!(state.init_start("0"), Ok(()));
+        for _ in 0..5 {
+            state = state.step();
+        }
+        assert_eq!(state.take_action("0", Action::Fold), Ok(Action::Fold));
+        assert_eq!(state.take_action("1", Action::Fold), Ok(Action::Fold));
+        while !matches!(state, PokerState::Lobby(_)) {
+            state = state.step();
+        }
+        let view = state.get_views().get("0").unwrap();
+        assert_eq!(view.small_blind, DEFAULT_MIN_SMALL_BLIND);
+        assert_eq!(view.big_blind, DEFAULT_MIN_BIG_BLIND);
+        assert_eq!(view.players[0].user.money, starting_stack);
+    }
+
+    #[test]
+    fn cant_start_game() {
+        let mut state = init_state();
+        assert_eq!(state.init_start("0"), Ok(()));
+        // At SeatPlayers.
+        state = state.step();
+        assert_eq!(state.init_start("0"), Err(UserError::GameAlreadyStarting));
+        assert_eq!(state.remove_user("1"), Ok(()));
+        assert_eq!(state.remove_user("2"), Ok(()));
+        // Should be back at Lobby.
+        state = state.step();
+        assert_eq!(state.init_start("0"), Err(UserError::NotEnoughPlayers));
+    }
+
+    #[test]
+    fn stale_start_is_dropped() {
+        let mut state = init_state();
+        assert_eq!(state.init_start("0"), Ok(()));
+        // Starting again before the game starts is fine.
+        assert_eq!(state.init_start("1"), Ok(()));
+        assert_eq!(state.remove_user("1"), Ok(()));
+        assert_eq!(state.remove_user("2"), Ok(()));
+        // Still at Lobby since there aren't enough players anymore.
+        state = state.step();
+        assert!(matches!(state, PokerState::Lobby(_)));
+        assert!(state.is_waiting_for_players());
+
+        // The start from before doesn't fire once another player joins.
+        assert_eq!(state.new_user("3"), Ok(()));
+        assert_eq!(state.waitlist_user("3"), Ok(()));
+        assert!(!state.is_waiting_for_players());
+        state = state.step();
+        assert!(matches!(state, PokerState::Lobby(_)));
+        assert_eq!(state.init_start("3"), Ok(()));
+        state = state.step();
+        assert!(matches!(state, PokerState::SeatPlayers(_)));
+    }
+
+    #[test]
+    fn ending_session_finishes_the_hand_but_starts_no_more() {
+        let mut state = init_state();
+        assert_eq!(state.init_start("0"), Ok(()));
+        // Step to TakeAction.
+        for _ in 0..5 {
+            state = state.step();
+        }
+        state.end_session();
+        assert!(!state.is_session_over());
+        // The hand in progress is still played out.
+        assert_eq!(state.take_action("0", Action::Fold), Ok(Action::Fold));
+        assert_eq!(state.take_action("1", Action::Fold), Ok(Action::Fold));
+        while !matches!(state, PokerState::Lobby(_)) {
+            state = state.step();
+        }
+
+        // But no one can start another one.
+        assert!(state.is_session_over());
+        assert_eq!(state.init_start("0"), Err(UserError::SessionEnding));
+        assert_eq!(state.init_autostart(), Err(UserError::SessionEnding));
+        assert!(!state.is_ready_to_autostart());
+        state = state.step();
+        assert!(matches!(state, PokerState::Lobby(_)));
+
+        // The big blind won the small blind.
+        assert_eq!(
+            state.get_leaderboard(),
+            [
+                ("2".to_string(), 5),
+                ("0".to_string(), 0),
+                ("1".to_string(), -5)
+            ]
+        );
+    }
+
+    #[test]
+    fn every_state_has_a_distinct_phase() {
+        let mut state = init_state();
+        assert_eq!(state.init_start("0"), Ok(()));
+        let mut phases = HashMap::new();
+        // Step through an entire game, calling or checking for one player
+        // so the hand goes to a showdown while the rest fold.
+        loop {
+            let phase = state.phase();
+            phases.insert(discriminant(&state), discriminant(&phase));
+            if let PokerState::BootPlayers(_) = state {
+                break;
+            }
+            if let (Some(username @ "0"), Some(action_options)) = (
+                state.get_next_action_username().as_deref(),
+                state.get_action_options(),
+            ) {
+                let action = action_options
+                    .into_iter()
+                    .find(|action| matches!(action, Action::Call(_) | Action::Check))
+                    .unwrap();
+                state.take_action(username, action).unwrap();
+            }
+            state = state.step();
+        }
+        state = state.step();
+        phases.insert(discriminant(&state), discriminant(&state.phase()));
+        assert_eq!(phases.len(), 15);
+        assert_eq!(phases.values().collect::<HashSet<_>>().len(), 15);
+    }
+
+    #[test]
+    fn fold_around_deals_no_community_cards() {
+        let mut state = init_state();
+        assert_eq!(state.init_start("0"), Ok(()));
+        // Step to TakeAction.
+        for _ in 0..5 {
+            state = state.step();
+        }
+        // Everyone folds to the big blind.
+        assert_eq!(state.take_action("0", Action::Fold), Ok(Action::Fold));
+        assert_eq!(state.take_action("1", Action::Fold), Ok(Action::Fold));
+        state = state.step();
+        assert!(matches!(state, PokerState::DistributePot(_)));
+        while !matches!(state, PokerState::Lobby(_)) {
+            state = state.step();
+            let view = state.get_views().get("0").unwrap();
+            assert!(view.board.is_empty());
+            // The winner's hand stays hidden.
+            assert!(view.players[2].cards.revealed().is_empty());
+        }
+
+        // The big blind wins the small blind.
+        let view = state.get_views().get("0").unwrap();
+        for (player, money) in
+            view.players
+                .iter()
+                .zip([DEFAULT_BUY_IN, DEFAULT_BUY_IN - 5, DEFAULT_BUY_IN + 5])
         {
-            assert_eq!(game.data.players[i].state, *state);
+            assert_eq!(player.user.money, money);
+        }
+    }
+
+    #[test]
+    fn hand_ids_and_buttons_in_views() {
+        let mut state = init_state();
+        for (hand_id, button_idx) in [(1, 0), (2, 1)] {
+            assert_eq!(state.init_start("0"), Ok(()));
+            // Step to TakeAction.
+            for _ in 0..5 {
+                state = state.step();
+            }
+            assert_eq!(state.get_hand_id(), hand_id);
+            let views = state.get_views();
+            for view in views.usernames().filter_map(|username| views.get(username)) {
+                assert_eq!(view.hand_id, hand_id);
+                assert_eq!(view.button_idx, button_idx);
+            }
+            while !matches!(state, PokerState::Lobby(_)) {
+                state = state.step();
+            }
+            assert_eq!(state.get_hand_id(), hand_id);
+        }
+    }
+
+    #[test]
+    fn action_history_covers_the_hand() {
+        let mut state = init_state();
+        assert_eq!(state.init_start("0"), Ok(()));
+        let step_to_take_action = |mut state: PokerState| {
+            state = state.step();
+            while !matches!(state, PokerState::TakeAction(_)) {
+                state = state.step();
+            }
+            state
+        };
+        state = step_to_take_action(state);
+        assert_eq!(
+            state.take_action("0", Action::Call(10)),
+            Ok(Action::Call(10))
+        );
+        assert_eq!(state.take_action("1", Action::Call(5)), Ok(Action::Call(5)));
+        assert_eq!(state.take_action("2", Action::Check), Ok(Action::Check));
+        state = step_to_take_action(state);
+        assert_eq!(state.take_action("0", Action::Check), Ok(Action::Check));
+        assert_eq!(
+            state.take_action("1", Action::Raise(20)),
+            Ok(Action::Raise(20))
+        );
+        assert_eq!(state.take_action("2", Action::Fold), Ok(Action::Fold));
+        assert_eq!(
+            state.take_action("0", Action::Call(20)),
+            Ok(Action::Call(20))
+        );
+        state = step_to_take_action(state);
+        assert_eq!(state.take_action("0", Action::Check), Ok(Action::Check));
+        assert_eq!(state.take_action("1", Action::Check), Ok(Action::Check));
+        let expected = [
+            ("0", Action::Call(10), Street::Preflop),
+            ("1", Action::Call(5), Street::Preflop),
+            ("2", Action::Check, Street::Preflop),
+            ("0", Action::Check, Street::Flop),
+            ("1", Action::Raise(20), Street::Flop),
+            ("2", Action::Fold, Street::Flop),
+            ("0", Action::Call(20), Street::Flop),
+            ("0", Action::Check, Street::Turn),
+            ("1", Action::Check, Street::Turn),
+        ];
+        let assert_history = |state: &PokerState| {
+            let views = state.get_views();
+            // Spectators and players see the same history.
+            for view in views.usernames().filter_map(|username| views.get(username)) {
+                assert_eq!(view.action_history.len(), expected.len());
+                for (record, (username, action, street)) in
+                    view.action_history.iter().zip(expected.iter())
+                {
+                    assert_eq!(record.username, *username);
+                    assert_eq!(record.action.to_string(), action.to_string());
+                    assert_eq!(record.street, *street);
+                }
+            }
+        };
+        assert_history(&state);
+
+        // Check the river down. The history is kept through the end of
+        // the hand and only cleared once the next hand starts seating.
+        state = step_to_take_action(state);
+        assert_eq!(state.take_action("0", Action::Check), Ok(Action::Check));
+        assert_eq!(state.take_action("1", Action::Check), Ok(Action::Check));
+        while !matches!(state, PokerState::Lobby(_)) {
+            state = state.step();
         }
+        let views = state.get_views();
+        let view = views.get("0").unwrap();
+        assert_eq!(view.action_history.len(), expected.len() + 2);
+        assert_eq!(view.action_history.last().unwrap().street, Street::River);
+        assert_eq!(state.init_start("0"), Ok(()));
+        state = state.step();
+        assert!(matches!(state, PokerState::SeatPlayers(_)));
+        let views = state.get_views();
+        assert!(views.get("0").unwrap().action_history.is_empty());
     }
 
-    #[test]
-    fn seat_players() {
-        let game = init_game_at_seat_players();
-        assert_eq!(game.data.num_players_active, game.get_num_players());
-        assert!(game.contains_player("0"));
-        assert!(game.contains_player("1"));
-        assert!(game.contains_player("2"));
+    /// Play out preflop with the given actions in turn order, returning
+    /// how many times the big blind got the option to check or raise.
+    /// The actions have to end preflop betting.
+    fn count_big_blind_options(num_players: usize, actions: &[Action]) -> usize {
+        let mut state = PokerState::new();
+        for i in 0..num_players {
+            let username = i.to_string();
+            state.new_user(&username).unwrap();
+            state.waitlist_user(&username).unwrap();
+        }
+        assert_eq!(state.init_start("0"), Ok(()));
+        while !matches!(state, PokerState::TakeAction(_)) {
+            state = state.step();
+        }
+        let views = state.get_views();
+        let view = views.get("0").unwrap();
+        let big_blind = view.players[view.big_blind_idx].user.name.clone();
+        let mut num_options = 0;
+        for action in actions {
+            let username = state.get_next_action_username().unwrap();
+            let action_options = state.get_action_options().unwrap();
+            if username == big_blind
+                && action_options.contains(&Action::Check)
+                && action_options.contains(&Action::Raise(0))
+            {
+                num_options += 1;
+            }
+            assert_eq!(
+                state.take_action(&username, action.clone()),
+                Ok(action.clone())
+            );
+        }
+        assert_eq!(state.get_next_action_username(), None);
+        state = state.step();
+        assert!(matches!(state, PokerState::Flop(_)));
+        num_options
     }
 
     #[test]
-    fn take_action_2_all_ins() {
-        let mut game = init_game_at_deal();
-        assert_eq!(
-            game.get_next_action_options(),
-            Some(HashSet::from([
-                Action::AllIn,
-                Action::Call(10),
-                Action::Fold,
-                Action::Raise(20)
-            ]))
-        );
-        assert_eq!(game.act(Action::AllIn), Ok(Action::AllIn));
+    fn big_blind_gets_option_after_limps() {
+        // Everyone limps and the big blind checks their option.
         assert_eq!(
-            game.get_next_action_options(),
-            Some(HashSet::from([Action::AllIn, Action::Fold]))
+            count_big_blind_options(3, &[Action::Call(10), Action::Call(5), Action::Check]),
+            1
         );
-        assert_eq!(game.act(Action::AllIn), Ok(Action::AllIn));
         assert_eq!(
-            game.get_next_action_options(),
-            Some(HashSet::from([Action::AllIn, Action::Fold]))
+            count_big_blind_options(
+                4,
+                &[
+                    Action::Call(10),
+                    Action::Fold,
+                    Action::Call(5),
+                    Action::Check
+                ]
+            ),
+            1
         );
-        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
-        assert_eq!(game.get_next_action_options(), None);
-    }
 
-    #[test]
-    fn take_action_2_calls_1_check() {
-        let mut game = init_game_at_deal();
+        // The big blind raises their option, and everyone else calls
+        // without the option coming back around.
         assert_eq!(
-            game.get_next_action_options(),
-            Some(HashSet::from([
-                Action::AllIn,
-                Action::Call(10),
-                Action::Fold,
-                Action::Raise(20)
-            ]))
+            count_big_blind_options(
+                3,
+                &[
+                    Action::Call(10),
+                    Action::Call(5),
+                    Action::Raise(20),
+                    Action::Call(20),
+                    Action::Call(20)
+                ]
+            ),
+            1
         );
-        assert_eq!(game.act(Action::Call(10)), Ok(Action::Call(10)));
+
+        // A raise behind the limpers takes away the big blind's option.
         assert_eq!(
-            game.get_next_action_options(),
-            Some(HashSet::from([
-                Action::AllIn,
-                Action::Call(5),
-                Action::Fold,
-                Action::Raise(15)
-            ]))
+            count_big_blind_options(
+                3,
+                &[
+                    Action::Call(10),
+                    Action::Raise(15),
+                    Action::Call(10),
+                    Action::Call(10)
+                ]
+            ),
+            0
         );
-        assert_eq!(game.act(Action::Call(5)), Ok(Action::Call(5)));
+
+        // Heads-up, the small blind has the button and limps first.
         assert_eq!(
-            game.get_next_action_options(),
-            Some(HashSet::from([
-                Action::AllIn,
-                Action::Check,
-                Action::Fold,
-                Action::Raise(20)
-            ]))
+            count_big_blind_options(2, &[Action::Call(5), Action::Check]),
+            1
         );
-        assert_eq!(game.act(Action::Check), Ok(Action::Check));
-        assert_eq!(game.get_next_action_options(), None);
     }
 
     #[test]
-    fn take_action_2_folds() {
-        let mut game = init_game_at_deal();
+    fn last_bets_reset_each_street() {
+        let mut state = init_state();
+        assert_eq!(state.init_start("0"), Ok(()));
+        while !matches!(state, PokerState::TakeAction(_)) {
+            state = state.step();
+        }
+        let assert_bets = |state: &PokerState, expected: [(Option<Bet>, u32); 3]| {
+            let views = state.get_views();
+            let view = views.get("0").unwrap();
+            for (username, (last_bet, investment)) in ["0", "1", "2"].into_iter().zip(expected) {
+                let player = view
+                    .players
+                    .iter()
+                    .find(|player| player.user.name == username)
+                    .unwrap();
+                assert_eq!(player.last_bet, last_bet);
+                assert_eq!(player.investment, investment);
+            }
+        };
+        let bet = |action, amount| Some(Bet { action, amount });
+
+        // Posted blinds show up before anyone acts.
+        assert_bets(
+            &state,
+            [
+                (None, 0),
+                (bet(BetAction::Blind, 5), 5),
+                (bet(BetAction::Blind, 10), 10),
+            ],
+        );
+
+        // Calling a raise shows everything bet this street.
         assert_eq!(
-            game.get_next_action_options(),
-            Some(HashSet::from([
-                Action::AllIn,
-                Action::Call(10),
-                Action::Fold,
-                Action::Raise(20)
-            ]))
+            state.take_action("0", Action::Raise(20)),
+            Ok(Action::Raise(20))
         );
-        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
         assert_eq!(
-            game.get_next_action_options(),
-            Some(HashSet::from([
-                Action::AllIn,
-                Action::Call(5),
-                Action::Fold,
-                Action::Raise(15)
-            ]))
+            state.take_action("1", Action::Call(15)),
+            Ok(Action::Call(15))
         );
-        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
-        assert_eq!(game.get_next_action_options(), None);
+        assert_bets(
+            &state,
+            [
+                (bet(BetAction::Raise, 20), 20),
+                (bet(BetAction::Call, 20), 20),
+                (bet(BetAction::Blind, 10), 10),
+            ],
+        );
+        assert_eq!(state.take_action("2", Action::Fold), Ok(Action::Fold));
+
+        // Bets are cleared for the next street, but investments aren't.
+        state = state.step();
+        while !matches!(state, PokerState::TakeAction(_)) {
+            state = state.step();
+        }
+        assert_bets(&state, [(None, 20), (None, 20), (None, 10)]);
     }
 
     #[test]
-    fn take_action_2_reraises() {
-        let mut game = init_game_at_deal();
+    fn pot_splits_street_bets_from_the_middle() {
+        let mut state = init_state();
+        assert_eq!(state.init_start("0"), Ok(()));
+        while !matches!(state, PokerState::TakeAction(_)) {
+            state = state.step();
+        }
+        let assert_pot = |state: &PokerState, middle: u32, street_bets: u32| {
+            let views = state.get_views();
+            let pot = &views.get("0").unwrap().pot;
+            assert_eq!(
+                (pot.size - pot.street_bets, pot.street_bets),
+                (middle, street_bets)
+            );
+        };
+        let next_street = |mut state: PokerState| {
+            state = state.step();
+            while !matches!(state, PokerState::TakeAction(_)) {
+                state = state.step();
+            }
+            state
+        };
+
+        // Blinds are bets like any other.
+        assert_pot(&state, 0, 15);
+        state.take_action("0", Action::Raise(20)).unwrap();
+        state.take_action("1", Action::Call(15)).unwrap();
+        state.take_action("2", Action::Fold).unwrap();
+        assert_pot(&state, 0, 50);
+
+        // Each street's bets are pulled into the middle once the next
+        // street starts.
+        state = next_street(state);
+        assert_pot(&state, 50, 0);
+        state.take_action("0", Action::Raise(10)).unwrap();
+        assert_pot(&state, 50, 10);
+        state.take_action("1", Action::Call(10)).unwrap();
+        assert_pot(&state, 50, 20);
+
+        state = next_street(state);
+        assert_pot(&state, 70, 0);
+        state.take_action("0", Action::Raise(20)).unwrap();
+        state.take_action("1", Action::Call(20)).unwrap();
+        assert_pot(&state, 70, 40);
+
+        state = next_street(state);
+        assert_pot(&state, 110, 0);
+        state.take_action("0", Action::Raise(30)).unwrap();
+        state.take_action("1", Action::Call(30)).unwrap();
+        assert_pot(&state, 110, 60);
+        let views = state.get_views();
+        assert_eq!(views.get("0").unwrap().pot.to_string(), "$110 + $60");
+    }
+
+    /// Step through the rest of the hand, auto-acting for every player,
+    /// until the game is back in the lobby.
+    fn finish_hand(mut state: PokerState) -> PokerState {
+        while !matches!(state, PokerState::Lobby(_)) {
+            state = state.step();
+        }
+        state
+    }
+
+    #[test]
+    fn change_seat_after_hand() {
+        let mut state = init_state();
+        assert_eq!(state.init_start("0"), Ok(()));
+        while !matches!(state, PokerState::TakeAction(_)) {
+            state = state.step();
+        }
+
+        // Players can only ask for seats that'll be open after the hand,
+        // which includes the seats of players that're leaving.
+        assert_eq!(state.change_seat("0", 5), Ok(()));
         assert_eq!(
-            game.get_next_action_options(),
-            Some(HashSet::from([
-                Action::AllIn,
-                Action::Call(10),
-                Action::Fold,
-                Action::Raise(20)
-            ]))
+            state.change_seat("1", 2),
+            Err(UserError::SeatUnavailable { seat_idx: 2 })
         );
-        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
         assert_eq!(
-            game.get_next_action_options(),
-            Some(HashSet::from([
-                Action::AllIn,
-                Action::Call(5),
-                Action::Fold,
-                Action::Raise(15)
-            ]))
+            state.change_seat("2", 5),
+            Err(UserError::SeatUnavailable { seat_idx: 5 })
         );
-        // Total call is 20
-        assert_eq!(game.act(Action::Raise(15)), Ok(Action::Raise(15)));
         assert_eq!(
-            game.get_next_action_options(),
-            Some(HashSet::from([
-                Action::AllIn,
-                Action::Call(10),
-                Action::Fold,
-                Action::Raise(30)
-            ]))
+            state.change_seat("2", MAX_PLAYERS),
+            Err(UserError::SeatUnavailable {
+                seat_idx: MAX_PLAYERS
+            })
         );
-        // Total call is 40
-        assert_eq!(game.act(Action::Raise(30)), Ok(Action::Raise(30)));
+        assert_eq!(state.remove_user("1"), Ok(()));
+        assert_eq!(state.change_seat("2", 1), Ok(()));
         assert_eq!(
-            game.get_next_action_options(),
-            Some(HashSet::from([
-                Action::AllIn,
-                Action::Call(20),
-                Action::Fold,
-                Action::Raise(60)
-            ]))
+            state.change_seat("spectator", 3),
+            Err(UserError::UserNotPlaying)
         );
-        // Total call is 80
-        assert_eq!(game.act(Action::Raise(60)), Ok(Action::Raise(60)));
+        state.drain_events();
+
+        // No one moves until the hand is over.
+        if let PokerState::TakeAction(ref game) = state {
+            let seats: Vec<_> = game.data.players.iter().map(|p| p.seat_idx).collect();
+            assert_eq!(seats, [0, 1, 2]);
+        }
+        let mut state = finish_hand(state);
+        if let PokerState::Lobby(ref game) = state {
+            let players: Vec<_> = game
+                .data
+                .players
+                .iter()
+                .map(|p| (p.user.name.as_str(), p.seat_idx))
+                .collect();
+            assert_eq!(players, [("2", 1), ("0", 5)]);
+            assert!(game.data.open_seats.contains(&0));
+            assert!(game.data.open_seats.contains(&2));
+            assert!(!game.data.open_seats.contains(&1));
+            assert!(!game.data.open_seats.contains(&5));
+        } else {
+            panic!("expected the lobby");
+        }
+        let moves: Vec<_> = state
+            .drain_events()
+            .into_iter()
+            .filter(|event| matches!(event, GameEvent::ChangedSeat(..)))
+            .collect();
         assert_eq!(
-            game.get_next_action_options(),
-            Some(HashSet::from([
-                Action::AllIn,
-                Action::Call(40),
-                Action::Fold,
-                Action::Raise(120)
-            ]))
+            moves,
+            [
+                GameEvent::ChangedSeat("0".to_string(), 0, 5),
+                GameEvent::ChangedSeat("2".to_string(), 2, 1),
+            ]
         );
-        assert_eq!(game.act(Action::Fold), Ok(Action::Fold));
-        assert_eq!(game.get_next_action_options(), None);
     }
-}
 
-#[cfg(test)]
-mod state_tests {
-    use super::{entities::Action, PokerState, UserError};
+    /// Play the next hand, returning the usernames of its button and
+    /// blinds.
+    fn play_hand(mut state: PokerState) -> (PokerState, [String; 3]) {
+        assert_eq!(state.init_start("0"), Ok(()));
+        while !matches!(state, PokerState::TakeAction(_)) {
+            state = state.step();
+        }
+        let positions = match state {
+            PokerState::TakeAction(ref game) => [
+                game.data.button_idx,
+                game.data.small_blind_idx,
+                game.data.big_blind_idx,
+            ]
+            .map(|idx| game.data.players[idx].user.name.clone()),
+            _ => unreachable!(),
+        };
+        (finish_hand(state), positions)
+    }
 
-    fn init_state() -> PokerState {
-        let mut state = PokerState::new();
-        for i in 0..3 {
-            let username = i.to_string();
-            state.new_user(&username).unwrap();
-            state.waitlist_user(&username).unwrap();
+    #[test]
+    fn button_advances_one_occupied_seat_at_a_time() {
+        for num_players in [2, 3, MAX_PLAYERS] {
+            let mut state = PokerState::new();
+            for i in 0..num_players {
+                let username = i.to_string();
+                state.new_user(&username).unwrap();
+                state.waitlist_user(&username).unwrap();
+            }
+            // Go around the table twice. The button is the small blind
+            // heads-up.
+            for hand_idx in 0..2 * num_players {
+                let positions;
+                (state, positions) = play_hand(state);
+                let seat = |offset: usize| ((hand_idx + offset) % num_players).to_string();
+                let expected = if num_players == 2 {
+                    [seat(0), seat(0), seat(1)]
+                } else {
+                    [seat(0), seat(1), seat(2)]
+                };
+                assert_eq!(positions, expected);
+            }
         }
-        state
+
+        // Seats that open up between hands are skipped.
+        let mut state = init_state();
+        state.new_user("3").unwrap();
+        state.waitlist_user("3").unwrap();
+        let (mut state, positions) = play_hand(state);
+        assert_eq!(positions, ["0", "1", "2"]);
+        assert_eq!(state.remove_user("1"), Ok(()));
+        let (_, positions) = play_hand(state);
+        assert_eq!(positions, ["2", "3", "0"]);
     }
 
     #[test]
-    fn cant_start_game() {
+    fn button_rotates_across_seat_changes() {
+        let mut state = init_state();
+        state.new_user("3").unwrap();
+        state.waitlist_user("3").unwrap();
+        let (mut state, positions) = play_hand(state);
+        assert_eq!(positions, ["0", "1", "2"]);
+
+        // The button moves on from its old seat, so moving to a later
+        // seat doesn't skip anyone, even if the player that moved ends up
+        // paying the big blind again.
+        assert_eq!(state.change_seat("2", 6), Ok(()));
+        let (mut state, positions) = play_hand(state);
+        assert_eq!(positions, ["1", "3", "2"]);
+
+        // The button's player moving to a later seat doesn't hold the
+        // button back.
+        assert_eq!(state.change_seat("1", 7), Ok(()));
+        let (_, positions) = play_hand(state);
+        assert_eq!(positions, ["3", "2", "1"]);
+    }
+
+    #[test]
+    fn formats_change_between_hands() {
+        let heads_up = || {
+            GameSettings::builder()
+                .blinds(2 * DEFAULT_MIN_SMALL_BLIND, 2 * DEFAULT_MIN_BIG_BLIND)
+                .max_players(2)
+                .build()
+                .unwrap()
+        };
         let mut state = init_state();
         assert_eq!(state.init_start("0"), Ok(()));
-        // At SeatPlayers.
-        state = state.step();
-        assert_eq!(state.init_start("0"), Err(UserError::GameAlreadyStarting));
-        assert_eq!(state.remove_user("1"), Ok(()));
-        assert_eq!(state.remove_user("2"), Ok(()));
-        // Should be back at Lobby.
-        state = state.step();
-        assert_eq!(state.init_start("0"), Err(UserError::NotEnoughPlayers));
+        while !matches!(state, PokerState::TakeAction(_)) {
+            state = state.step();
+        }
+        assert_eq!(
+            state.change_format("heads up", heads_up(), false),
+            Err(UserError::GameAlreadyInProgress)
+        );
+        let mut state = finish_hand(state);
+        state.drain_events();
+
+        // Stacks carry over, and the player that no longer fits at the
+        // table is first in line for a seat.
+        let stacks: Vec<_> = state
+            .get_views()
+            .get("0")
+            .unwrap()
+            .players
+            .iter()
+            .map(|player| player.user.clone())
+            .collect();
+        assert_eq!(state.change_format("heads up", heads_up(), false), Ok(()));
+        assert_eq!(
+            state.drain_events(),
+            [GameEvent::FormatChanged("heads up".to_string())]
+        );
+        let views = state.get_views();
+        let view = views.get("0").unwrap();
+        assert_eq!(view.big_blind, 2 * DEFAULT_MIN_BIG_BLIND);
+        assert_eq!(
+            view.players
+                .iter()
+                .map(|player| player.user.clone())
+                .collect::<Vec<_>>(),
+            stacks[..2]
+        );
+        assert_eq!(view.waitlist, [stacks[2].clone()]);
+        assert!(view.open_seats.is_empty());
+
+        // Resetting stacks cashes everyone out and buys them back in.
+        assert_eq!(state.change_format("reset", heads_up(), true), Ok(()));
+        if let PokerState::Lobby(ref game) = state {
+            for user in stacks {
+                let money = game
+                    .data
+                    .players
+                    .iter()
+                    .map(|player| &player.user)
+                    .chain(game.data.waitlist.iter())
+                    .find(|other| other.name == user.name)
+                    .unwrap()
+                    .money;
+                assert_eq!(money, DEFAULT_BUY_IN);
+                let entry = &game.data.ledger[&user.name];
+                assert_eq!(entry.bought_in, 2 * DEFAULT_BUY_IN);
+                assert_eq!(entry.cashed_out, user.money);
+            }
+        } else {
+            panic!("should be in the lobby");
+        }
+    }
+
+    #[test]
+    fn phase_serde_round_trip() {
+        for phase in [
+            GamePhase::Lobby,
+            GamePhase::CollectingBlinds {
+                small: 5,
+                small_from: "0".to_string(),
+                big: 10,
+                big_from: "1".to_string(),
+            },
+            GamePhase::Betting { round_over: true },
+            GamePhase::ShowingPot { index: 1 },
+            GamePhase::DistributingPot { index: 0 },
+        ] {
+            let serialized = bincode::serialize(&phase).unwrap();
+            let deserialized: GamePhase = bincode::deserialize(&serialized).unwrap();
+            assert_eq!(deserialized, phase);
+        }
     }
 
     #[test]
@@ -2497,18 +8456,9 @@ mod state_tests {
         state = state.step();
         // 1st fold
         state = state.step();
-        // 2nd fold
-        state = state.step();
-        // Flop
-        state = state.step();
-        // Turn
-        state = state.step();
-        // River
-        state = state.step();
-        // ShowHands
-        state = state.step();
-        // DistributePot
+        // 2nd fold, skipping straight to DistributePot
         state = state.step();
+        assert!(matches!(state, PokerState::DistributePot(_)));
         // RemovePlayers
         state = state.step();
         // DivideDonations
@@ -2540,18 +8490,9 @@ mod state_tests {
         assert_eq!(state.take_action("0", Action::AllIn), Ok(Action::AllIn));
         // 1st fold
         state = state.step();
-        // 2nd fold
-        state = state.step();
-        // Flop
-        state = state.step();
-        // Turn
-        state = state.step();
-        // River
-        state = state.step();
-        // ShowHands
-        state = state.step();
-        // DistributePot
+        // 2nd fold, skipping straight to DistributePot
         state = state.step();
+        assert!(matches!(state, PokerState::DistributePot(_)));
         // RemovePlayers
         state = state.step();
         // DivideDonations
@@ -2618,10 +8559,9 @@ mod state_tests {
         assert_eq!(state.take_action("1", Action::Fold), Ok(Action::Fold));
         // Check
         assert_eq!(state.take_action("2", Action::Fold), Ok(Action::Fold));
-        // ShowHands
-        state = state.step();
-        // DistributePot
+        // DistributePot, skipping the showdown
         state = state.step();
+        assert!(matches!(state, PokerState::DistributePot(_)));
         // RemovePlayers
         state = state.step();
         // DivideDonations
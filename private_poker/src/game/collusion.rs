@@ -0,0 +1,341 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+use super::entities::{Action, ActionRecord, Street, Usd, Username};
+
+/// A pair of users that a collusion detector thinks are working together.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Suspicion {
+    /// The pair of users, in alphabetical order.
+    pub users: (Username, Username),
+    /// How suspicious the pair is. What a score means is up to the
+    /// detector that raised it.
+    pub score: u32,
+}
+
+/// Looks over hands as they end for signs of players colluding, like
+/// going easy on each other (soft play) or losing chips to each other on
+/// purpose (chip dumping). Detectors are stateful so they can build up
+/// evidence over many hands, and operators can plug in their own to tune
+/// what counts as suspicious.
+pub trait CollusionDetector: fmt::Debug + Send {
+    /// Look over the actions of a hand that just ended, returning the
+    /// pairs of users that are suspicious enough to report.
+    fn observe(&mut self, actions: &[ActionRecord], big_blind: Usd) -> Vec<Suspicion>;
+}
+
+/// Weights and thresholds for a [`SoftPlayDetector`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SoftPlayConfig {
+    /// Weight of a pair being the last two players in a pot that started
+    /// multiway and checking it down to the river.
+    pub check_down_weight: u32,
+    /// Weight of a player folding to the other's minimum bet or raise.
+    pub min_bet_fold_weight: u32,
+    /// Weight of a player folding to the other's all-in after putting
+    /// chips in the pot themselves.
+    pub dump_fold_weight: u32,
+    /// Number of hands a pair has to play together before they can be
+    /// flagged, so a few odd hands don't flag a pair.
+    pub min_hands: u32,
+    /// Pairs are flagged once their weighted count of suspicious plays
+    /// per 100 hands together reaches this score.
+    pub threshold: u32,
+}
+
+impl Default for SoftPlayConfig {
+    fn default() -> Self {
+        Self {
+            check_down_weight: 1,
+            min_bet_fold_weight: 2,
+            dump_fold_weight: 3,
+            min_hands: 10,
+            threshold: 100,
+        }
+    }
+}
+
+/// How often a pair of users did something suspicious to each other
+/// since they were last flagged.
+#[derive(Debug, Default)]
+struct PairStats {
+    hands: u32,
+    check_downs: u32,
+    min_bet_folds: u32,
+    dump_folds: u32,
+}
+
+/// The last bet or raise on a street, which is what a player that folds
+/// is folding to.
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Aggression {
+    AllIn,
+    Bet,
+    MinBet,
+}
+
+/// Flags pairs of users that check down pots they have to themselves,
+/// fold to each other's minimum bets, or fold to each other's all-ins
+/// after putting chips in (i.e., chip dumping) much more often than
+/// players normally would.
+#[derive(Debug, Default)]
+pub struct SoftPlayDetector {
+    config: SoftPlayConfig,
+    pairs: HashMap<(Username, Username), PairStats>,
+}
+
+impl SoftPlayDetector {
+    pub fn new(config: SoftPlayConfig) -> Self {
+        Self {
+            config,
+            pairs: HashMap::new(),
+        }
+    }
+
+    fn get_pair_mut(&mut self, username1: &str, username2: &str) -> &mut PairStats {
+        let users = if username1 < username2 {
+            (username1.to_string(), username2.to_string())
+        } else {
+            (username2.to_string(), username1.to_string())
+        };
+        self.pairs.entry(users).or_default()
+    }
+
+    fn get_score(&self, stats: &PairStats) -> u32 {
+        let weighted = self.config.check_down_weight * stats.check_downs
+            + self.config.min_bet_fold_weight * stats.min_bet_folds
+            + self.config.dump_fold_weight * stats.dump_folds;
+        100 * weighted / stats.hands.max(1)
+    }
+}
+
+impl CollusionDetector for SoftPlayDetector {
+    fn observe(&mut self, actions: &[ActionRecord], big_blind: Usd) -> Vec<Suspicion> {
+        let players: BTreeSet<&str> = actions.iter().map(|r| r.username.as_str()).collect();
+        let mut folded = BTreeSet::new();
+        // Players that put chips in the pot on their own, not counting
+        // blinds.
+        let mut invested = HashSet::new();
+        let mut postflop_aggressors = HashSet::new();
+        // Betting on the current street, where the largest bet starts
+        // out as the big blind before the flop.
+        let mut street = Street::Preflop;
+        let mut bets: HashMap<&str, Usd> = HashMap::new();
+        let mut largest_bet = big_blind;
+        let mut last_aggression: Option<(&str, Aggression)> = None;
+        for record in actions {
+            let username = record.username.as_str();
+            if record.street != street {
+                street = record.street;
+                bets.clear();
+                largest_bet = 0;
+                last_aggression = None;
+            }
+            let aggression = match record.action {
+                Action::AllIn => Some(Aggression::AllIn),
+                Action::Call(amount) => {
+                    *bets.entry(username).or_default() += amount;
+                    invested.insert(username);
+                    None
+                }
+                Action::Check => None,
+                Action::Fold => {
+                    folded.insert(username);
+                    if let Some((aggressor, aggression)) = last_aggression {
+                        let is_dump =
+                            aggression == Aggression::AllIn && invested.contains(username);
+                        let stats = self.get_pair_mut(username, aggressor);
+                        match aggression {
+                            Aggression::MinBet => stats.min_bet_folds += 1,
+                            Aggression::AllIn if is_dump => stats.dump_folds += 1,
+                            _ => {}
+                        }
+                    }
+                    None
+                }
+                Action::Raise(amount) => {
+                    let bet = bets.entry(username).or_default();
+                    let call = largest_bet.saturating_sub(*bet);
+                    *bet += amount;
+                    largest_bet = largest_bet.max(*bet);
+                    if amount.saturating_sub(call) <= big_blind {
+                        Some(Aggression::MinBet)
+                    } else {
+                        Some(Aggression::Bet)
+                    }
+                }
+            };
+            if let Some(aggression) = aggression {
+                invested.insert(username);
+                if street != Street::Preflop {
+                    postflop_aggressors.insert(username);
+                }
+                last_aggression = Some((username, aggression));
+            }
+        }
+
+        // Two players checking down a pot that everyone else folded out of.
+        let remaining: Vec<_> = players.difference(&folded).collect();
+        if let [username1, username2] = remaining[..] {
+            if players.len() > 2
+                && street == Street::River
+                && !postflop_aggressors.contains(username1)
+                && !postflop_aggressors.contains(username2)
+            {
+                self.get_pair_mut(username1, username2).check_downs += 1;
+            }
+        }
+
+        let mut suspicions = Vec::new();
+        let players: Vec<_> = players.into_iter().collect();
+        for (idx, username1) in players.iter().enumerate() {
+            for username2 in &players[idx + 1..] {
+                let stats = self.get_pair_mut(username1, username2);
+                stats.hands += 1;
+                if stats.hands < self.config.min_hands {
+                    continue;
+                }
+                let users = (username1.to_string(), username2.to_string());
+                let score = self.get_score(&self.pairs[&users]);
+                // Pairs start over once they're flagged so they aren't
+                // flagged again every hand.
+                if score >= self.config.threshold {
+                    self.pairs.remove(&users);
+                    suspicions.push(Suspicion { users, score });
+                }
+            }
+        }
+        suspicions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::entities::{Action, ActionRecord, Street};
+
+    use super::{CollusionDetector, SoftPlayConfig, SoftPlayDetector, Suspicion};
+
+    const BIG_BLIND: u32 = 10;
+
+    fn record(username: &str, action: Action, street: Street) -> ActionRecord {
+        ActionRecord {
+            username: username.to_string(),
+            action,
+            street,
+        }
+    }
+
+    #[test]
+    fn chip_dumping_is_flagged() {
+        let mut detector = SoftPlayDetector::new(SoftPlayConfig {
+            min_hands: 5,
+            ..Default::default()
+        });
+        // The dumper raises, then folds to their partner's all-in every
+        // hand while the third player stays out of the way.
+        let hand = [
+            record("dumper", Action::Raise(40), Street::Preflop),
+            record("partner", Action::AllIn, Street::Preflop),
+            record("bystander", Action::Fold, Street::Preflop),
+            record("dumper", Action::Fold, Street::Preflop),
+        ];
+        for _ in 0..4 {
+            assert!(detector.observe(&hand, BIG_BLIND).is_empty());
+        }
+        assert_eq!(
+            detector.observe(&hand, BIG_BLIND),
+            [Suspicion {
+                users: ("dumper".to_string(), "partner".to_string()),
+                score: 300,
+            }]
+        );
+        // The pair starts over after being flagged.
+        assert!(detector.observe(&hand, BIG_BLIND).is_empty());
+    }
+
+    #[test]
+    fn soft_play_is_flagged() {
+        let mut detector = SoftPlayDetector::new(SoftPlayConfig {
+            min_hands: 4,
+            ..Default::default()
+        });
+        // Partners fold to each other's minimum bets and check down the
+        // pots they have to themselves.
+        let min_bet_hand = [
+            record("0", Action::Call(10), Street::Preflop),
+            record("1", Action::Call(10), Street::Preflop),
+            record("2", Action::Check, Street::Preflop),
+            record("0", Action::Raise(10), Street::Flop),
+            record("1", Action::Fold, Street::Flop),
+            record("2", Action::Call(10), Street::Flop),
+            record("0", Action::Raise(60), Street::Turn),
+            record("2", Action::Fold, Street::Turn),
+        ];
+        let check_down_hand = [
+            record("0", Action::Call(10), Street::Preflop),
+            record("1", Action::Call(10), Street::Preflop),
+            record("2", Action::Raise(40), Street::Preflop),
+            record("0", Action::Fold, Street::Preflop),
+            record("1", Action::Call(30), Street::Preflop),
+            record("1", Action::Check, Street::Flop),
+            record("2", Action::Check, Street::Flop),
+            record("1", Action::Check, Street::Turn),
+            record("2", Action::Check, Street::Turn),
+            record("1", Action::Check, Street::River),
+            record("2", Action::Check, Street::River),
+        ];
+        for hand in [&min_bet_hand[..], &check_down_hand, &min_bet_hand] {
+            assert!(detector.observe(hand, BIG_BLIND).is_empty());
+        }
+        // "0" and "1" have 2 min-bet folds over 4 hands, and "1" and "2"
+        // have 2 check-downs. "0" and "2" only ever bet big at each other.
+        assert_eq!(
+            detector.observe(&check_down_hand, BIG_BLIND),
+            [Suspicion {
+                users: ("0".to_string(), "1".to_string()),
+                score: 100,
+            }]
+        );
+    }
+
+    #[test]
+    fn aggressive_play_is_not_flagged() {
+        let mut detector = SoftPlayDetector::new(SoftPlayConfig::default());
+        // The aggressor shoves or bets big every hand. Others usually
+        // fold without having put anything in, and sometimes call them
+        // down or fold after calling a bet.
+        let steal = [
+            record("aggressor", Action::AllIn, Street::Preflop),
+            record("0", Action::Fold, Street::Preflop),
+            record("1", Action::Fold, Street::Preflop),
+        ];
+        let called_down = [
+            record("aggressor", Action::Raise(30), Street::Preflop),
+            record("0", Action::Call(30), Street::Preflop),
+            record("1", Action::Fold, Street::Preflop),
+            record("0", Action::Check, Street::Flop),
+            record("aggressor", Action::Raise(50), Street::Flop),
+            record("0", Action::Call(50), Street::Flop),
+            record("0", Action::Check, Street::Turn),
+            record("aggressor", Action::Check, Street::Turn),
+            record("0", Action::Check, Street::River),
+            record("aggressor", Action::Raise(100), Street::River),
+            record("0", Action::Call(100), Street::River),
+        ];
+        let folded_to_shove = [
+            record("aggressor", Action::Raise(30), Street::Preflop),
+            record("0", Action::Fold, Street::Preflop),
+            record("1", Action::Call(30), Street::Preflop),
+            record("1", Action::Check, Street::Flop),
+            record("aggressor", Action::AllIn, Street::Flop),
+            record("1", Action::Fold, Street::Flop),
+        ];
+        for _ in 0..20 {
+            for hand in [&steal[..], &steal, &called_down, &steal, &folded_to_shove] {
+                assert!(detector.observe(hand, BIG_BLIND).is_empty());
+            }
+        }
+    }
+}
@@ -1,4 +1,31 @@
+use std::time::Duration;
+
+use super::entities::Usd;
+
 // Don't want too many people waiting to play the game.
 pub const MAX_PLAYERS: usize = 10;
+pub const MIN_PLAYERS: usize = 2;
 pub const DEFAULT_MAX_USERS: usize = MAX_PLAYERS + 6;
+// Railbirds only follow along, so there's room for more of them.
+pub const DEFAULT_MAX_RAILBIRDS: usize = DEFAULT_MAX_USERS;
 pub const MAX_USER_INPUT_LENGTH: usize = 16;
+pub const MAX_TAGLINE_LENGTH: usize = 32;
+// Flop, turn, and river.
+pub const MAX_COMMUNITY_CARDS: usize = 5;
+// Disconnected players get to finish the current hand and sit in for
+// this many more before they're removed.
+pub const DEFAULT_DISCONNECT_GRACE_HANDS: usize = 1;
+// Each player antes this many big blinds in a bomb pot.
+pub const DEFAULT_BOMB_POT_ANTE: Usd = 2;
+// Views only carry this many of a hand's most recent actions so a long
+// raising war can't blow up the size of a view.
+pub const MAX_VIEWED_ACTIONS: usize = 64;
+// Equities are estimated from this many random runouts when there are
+// more runouts left than this, like before the flop.
+pub const MAX_EQUITY_SAMPLES: usize = 10_000;
+// Servers pause this long on states users need time to follow, like
+// dealing and revealing streets.
+pub const DEFAULT_STEP_TIMEOUT: Duration = Duration::from_secs(5);
+// Servers pause this long on administrative states, like moving the
+// button, which users don't need time to follow.
+pub const DEFAULT_BRIEF_STEP_TIMEOUT: Duration = Duration::from_millis(250);
@@ -1,12 +1,19 @@
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, VecDeque},
+    cmp::max,
+    collections::{HashMap, HashSet, VecDeque},
     fmt,
     hash::{Hash, Hasher},
     mem::discriminant,
+    str::FromStr,
+    sync::Arc,
 };
+use thiserror::Error;
 
-use super::constants;
+use super::{
+    constants,
+    fairness::{self, Commitment, DeckReveal},
+};
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub enum Suit {
@@ -32,29 +39,111 @@ impl fmt::Display for Suit {
     }
 }
 
+impl Suit {
+    pub fn to_unicode(&self) -> char {
+        match self {
+            Suit::Club => '♣',
+            Suit::Spade => '♠',
+            Suit::Diamond => '♦',
+            Suit::Heart => '♥',
+            Suit::Wild => '★',
+        }
+    }
+}
+
 /// Placeholder for card values.
 pub type Value = u8;
 
+/// Card ranks as displayed to users, indexed by card value. Aces are
+/// displayed the same whether they're valued low or high.
+const RANK_REPRS: [&str; 15] = [
+    "0", "A", "2", "3", "4", "5", "6", "7", "8", "9", "10", "J", "Q", "K", "A",
+];
+
+/// Card ranks as written out in hand descriptions, indexed by card value.
+const RANK_NAMES: [&str; 15] = [
+    "joker", "ace", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten", "jack",
+    "queen", "king", "ace",
+];
+
 /// A card is a tuple of a uInt8 value (ace=1u8 ... ace=14u8)
 /// and a suit. A joker is depicted as 0u8.
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct Card(pub Value, pub Suit);
 
+impl Card {
+    /// Return the card's rank as displayed to users (e.g., "A" for aces
+    /// and "10" for tens). Values that aren't real ranks, such as ones
+    /// from a misbehaving peer, are displayed as "?".
+    pub fn rank_repr(&self) -> &'static str {
+        RANK_REPRS.get(self.0 as usize).copied().unwrap_or("?")
+    }
+
+    pub fn suit(&self) -> Suit {
+        self.1
+    }
+
+    /// Return the card's rank followed by its suit symbol (e.g., "A♠").
+    pub fn to_unicode(&self) -> String {
+        format!("{}{}", self.rank_repr(), self.1.to_unicode())
+    }
+}
+
 impl fmt::Display for Card {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let value = match self.0 {
-            1 | 14 => "A",
-            11 => "J",
-            12 => "Q",
-            13 => "K",
-            v => &v.to_string(),
-        };
-        let repr = format!("{value}/{}", self.1);
+        let repr = format!("{}/{}", self.rank_repr(), self.1);
         write!(f, "{repr:>4}")
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+/// Why a card or set of cards couldn't be parsed.
+#[derive(Debug, Eq, Error, PartialEq)]
+pub enum CardParseError {
+    #[error("{0:?} appears more than once")]
+    Duplicate(String),
+    #[error("invalid rank in {0:?}")]
+    InvalidRank(String),
+    #[error("invalid suit in {0:?}")]
+    InvalidSuit(String),
+    #[error("{0:?} isn't a rank followed by a suit")]
+    Malformed(String),
+}
+
+/// Parse a card from its rank followed by its suit letter (e.g., "As",
+/// "Td", or "7h"). Tens can also be written as "10", and the rank and
+/// suit can be separated by a slash, so cards parse from how they're
+/// displayed too. Ranks and suits are case-insensitive, and aces are
+/// always valued low, the same as in a new deck.
+impl FromStr for Card {
+    type Err = CardParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let mut chars = s.chars();
+        let suit = chars.next_back();
+        let rank = chars.as_str();
+        let rank = rank.strip_suffix('/').unwrap_or(rank);
+        let suit = match suit {
+            Some(suit) if !rank.is_empty() => suit,
+            _ => return Err(CardParseError::Malformed(s.to_string())),
+        };
+        let suit = match suit.to_ascii_lowercase() {
+            'c' => Suit::Club,
+            's' => Suit::Spade,
+            'd' => Suit::Diamond,
+            'h' => Suit::Heart,
+            _ => return Err(CardParseError::InvalidSuit(s.to_string())),
+        };
+        let rank = rank.to_ascii_uppercase();
+        let rank = if rank == "T" { "10" } else { &rank };
+        match RANK_REPRS[1..14].iter().position(|repr| *repr == rank) {
+            Some(idx) => Ok(Card(idx as Value + 1, suit)),
+            None => Err(CardParseError::InvalidRank(s.to_string())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub enum Rank {
     HighCard,
     OnePair,
@@ -90,6 +179,147 @@ pub struct SubHand {
     pub values: Vec<Value>,
 }
 
+/// A compact, totally ordered poker hand value. The hand's rank is packed
+/// into the highest bits, followed by (up to) five card values that break
+/// ties between hands of the same rank, ordered from most to least
+/// significant. Missing values are zeros, so comparing hand values gives
+/// the same ordering as comparing the subhands created from `eval`.
+///
+/// Ranks are packed as twice their order, leaving room to pack short-deck
+/// flushes between full houses and four of a kinds.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct HandValue(pub u32);
+
+impl HandValue {
+    const NUM_VALUES: usize = 5;
+    const VALUE_BITS: usize = 4;
+    const SHORT_DECK_FLUSH: u32 = 2 * Rank::FullHouse as u32 + 1;
+
+    pub fn new(rank: Rank, values: &[Value]) -> Self {
+        Self::pack(2 * rank as u32, values)
+    }
+
+    /// Create a hand value that's ordered by short-deck rankings, where
+    /// a flush beats a full house.
+    pub fn new_short_deck(rank: Rank, values: &[Value]) -> Self {
+        match rank {
+            Rank::Flush => Self::pack(Self::SHORT_DECK_FLUSH, values),
+            rank => Self::new(rank, values),
+        }
+    }
+
+    fn pack(rank_code: u32, values: &[Value]) -> Self {
+        let mut hand_value = rank_code;
+        for value_idx in 0..Self::NUM_VALUES {
+            let value = values.get(value_idx).copied().unwrap_or(0);
+            hand_value = (hand_value << Self::VALUE_BITS) | value as u32;
+        }
+        Self(hand_value)
+    }
+
+    pub fn rank(&self) -> Rank {
+        match self.0 >> (Self::NUM_VALUES * Self::VALUE_BITS) {
+            Self::SHORT_DECK_FLUSH => Rank::Flush,
+            0 => Rank::HighCard,
+            2 => Rank::OnePair,
+            4 => Rank::TwoPair,
+            6 => Rank::ThreeOfAKind,
+            8 => Rank::Straight,
+            10 => Rank::Flush,
+            12 => Rank::FullHouse,
+            14 => Rank::FourOfAKind,
+            16 => Rank::StraightFlush,
+            _ => unreachable!("hand values are always created from a rank"),
+        }
+    }
+
+    /// Describe the hand in words (e.g., "kings full of nines").
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use private_poker::entities::{HandValue, Rank};
+    ///
+    /// let hand_value = HandValue::new(Rank::TwoPair, &[14, 6, 13]);
+    /// assert_eq!(hand_value.describe(), "two pair, aces and sixes");
+    /// ```
+    pub fn describe(&self) -> String {
+        let values: Vec<Value> = self.values().collect();
+        let name = |idx: usize| RANK_NAMES[values.get(idx).copied().unwrap_or(0) as usize];
+        let plural = |idx: usize| match name(idx) {
+            "six" => "sixes".to_string(),
+            name => format!("{name}s"),
+        };
+        match self.rank() {
+            Rank::HighCard => format!("{} high", name(0)),
+            Rank::OnePair => format!("pair of {}", plural(0)),
+            Rank::TwoPair => format!("two pair, {} and {}", plural(0), plural(1)),
+            Rank::ThreeOfAKind => format!("three {}", plural(0)),
+            Rank::Straight => format!("{}-high straight", name(0)),
+            Rank::Flush => format!("{}-high flush", name(0)),
+            Rank::FullHouse => format!("{} full of {}", plural(0), plural(1)),
+            Rank::FourOfAKind => format!("four {}", plural(0)),
+            Rank::StraightFlush => format!("{}-high straight flush", name(0)),
+        }
+    }
+
+    /// Return the tie-breaking card values, ordered from most to least
+    /// significant.
+    pub fn values(&self) -> impl Iterator<Item = Value> + '_ {
+        (0..Self::NUM_VALUES)
+            .rev()
+            .map(|value_idx| ((self.0 >> (value_idx * Self::VALUE_BITS)) & 0xf) as Value)
+            .filter(|value| *value != 0)
+    }
+}
+
+impl From<HandValue> for Vec<SubHand> {
+    fn from(value: HandValue) -> Self {
+        let rank = value.rank();
+        let values: Vec<Value> = value.values().collect();
+        fn high_cards(kickers: &[Value]) -> impl Iterator<Item = SubHand> + '_ {
+            kickers.iter().map(|kicker| SubHand {
+                rank: Rank::HighCard,
+                values: vec![*kicker],
+            })
+        }
+
+        let mut hand = Vec::with_capacity(5);
+        match rank {
+            Rank::HighCard => hand.extend(high_cards(&values)),
+            Rank::OnePair | Rank::ThreeOfAKind | Rank::FourOfAKind => {
+                let num_cards = match rank {
+                    Rank::OnePair => 2,
+                    Rank::ThreeOfAKind => 3,
+                    _ => 4,
+                };
+                hand.push(SubHand {
+                    rank,
+                    values: vec![values[0]; num_cards],
+                });
+                hand.extend(high_cards(&values[1..]));
+            }
+            Rank::TwoPair => {
+                hand.push(SubHand {
+                    rank,
+                    values: vec![values[0], values[0], values[1], values[1]],
+                });
+                hand.extend(high_cards(&values[2..]));
+            }
+            Rank::Straight | Rank::StraightFlush => hand.push(SubHand {
+                rank,
+                values: (values[0] - 4..=values[0]).rev().collect(),
+            }),
+            Rank::Flush => hand.push(SubHand { rank, values }),
+            Rank::FullHouse => hand.push(SubHand {
+                rank,
+                values: vec![values[0], values[0], values[0], values[1], values[1]],
+            }),
+        }
+        hand
+    }
+}
+
 impl fmt::Display for SubHand {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let repr = self
@@ -122,6 +352,114 @@ pub type Usdf = f32;
 /// Type alias for poker user usernames.
 pub type Username = String;
 
+/// Type alias for seat indices. Seats are numbered from zero around the
+/// table.
+pub type SeatIndex = usize;
+
+/// Usernames and their net profit (or loss) over a session, sorted from
+/// most to least profitable.
+pub type Leaderboard = Vec<(Username, i64)>;
+
+/// Money a user has put into and taken out of the game over a session.
+/// Entries are kept after users leave so their results survive them
+/// rejoining.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LedgerEntry {
+    /// Starting stacks and chips added to stacks.
+    pub bought_in: Usd,
+    /// Stacks the user had when they left the game.
+    pub cashed_out: Usd,
+    /// Stack the user cashed out when they moved to the rail, held for
+    /// them until they rejoin.
+    pub held: Usd,
+    /// The user's profile, restored if they rejoin.
+    pub profile: Profile,
+}
+
+/// How a user presents themselves to everyone else. Both parts are
+/// optional, and clients that don't know about profiles can leave them
+/// out entirely.
+#[derive(Clone, Debug, Default, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(default)]
+pub struct Profile {
+    /// A single character, like an emoji or a flag, shown next to the
+    /// user's name.
+    pub avatar: Option<String>,
+    /// A short line about the user.
+    pub tagline: Option<String>,
+}
+
+/// A side bet between an all-in player and another user. The insured
+/// player pays the premium if they win the hand, and the insurer pays the
+/// payout if they don't.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Insurance {
+    pub insurer: Username,
+    pub insured: Username,
+    pub premium: Usd,
+    pub payout: Usd,
+}
+
+/// A spectator's play-money wager that a player wins the hand.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SideBet {
+    pub spectator: Username,
+    pub player: Username,
+    pub amount: Usd,
+}
+
+/// Side bets spectators placed on the current hand. Bets stay in the
+/// pool even if the player they're on folds, and spectators that bet on
+/// a player that won (part of) the pot split the whole pool in
+/// proportion to those bets.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SpectatorPool {
+    pub bets: Vec<SideBet>,
+}
+
+impl SpectatorPool {
+    pub fn get_size(&self) -> Usd {
+        self.bets.iter().map(|bet| bet.amount).sum()
+    }
+
+    /// Return how much of the pool each spectator that bet on one of the
+    /// winners is owed, in the order they first bet on a winner, along
+    /// with what's left over from rounding down. The whole pool is left
+    /// over if no one bet on a winner.
+    pub fn get_payouts(&self, winners: &[Username]) -> (Vec<(Username, Usd)>, Usd) {
+        let mut stakes: Vec<(Username, Usd)> = Vec::new();
+        for bet in self.bets.iter().filter(|bet| winners.contains(&bet.player)) {
+            match stakes
+                .iter_mut()
+                .find(|(spectator, _)| *spectator == bet.spectator)
+            {
+                Some((_, stake)) => *stake += bet.amount,
+                None => stakes.push((bet.spectator.clone(), bet.amount)),
+            }
+        }
+        let size = self.get_size();
+        let total_stake: Usd = stakes.iter().map(|(_, stake)| stake).sum();
+        let payouts: Vec<(Username, Usd)> = stakes
+            .into_iter()
+            .map(|(spectator, stake)| {
+                let payout = size as u64 * stake as u64 / total_stake as u64;
+                (spectator, payout as Usd)
+            })
+            .collect();
+        let paid: Usd = payouts.iter().map(|(_, payout)| payout).sum();
+        (payouts, size - paid)
+    }
+}
+
+/// Type alias for hand identifiers. Hands are numbered in the order
+/// they're played, starting from one.
+pub type HandId = u64;
+
+/// Type alias for turn identifiers. Turns are numbered in the order
+/// they're given over the whole game, so an action meant for one turn
+/// never matches a later one, even if it's the same player's turn again.
+pub type TurnId = u64;
+
 // By default, a player will be cleaned if they fold 20 rounds with the big
 // blind.
 pub const DEFAULT_BUY_IN: Usd = 200;
@@ -132,6 +470,8 @@ pub const DEFAULT_MIN_SMALL_BLIND: Usd = DEFAULT_MIN_BIG_BLIND / 2;
 pub struct User {
     pub name: String,
     pub money: Usd,
+    #[serde(default)]
+    pub profile: Profile,
 }
 
 impl fmt::Display for User {
@@ -166,6 +506,29 @@ impl Into<usize> for Action {
     }
 }
 
+/// An action a player picks ahead of their turn so it can be taken as
+/// soon as their turn comes. See [`crate::resolve_pending_action`] for
+/// what each one does.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PendingAction {
+    CheckFold,
+    CheckCall,
+    CallAny,
+    FoldToAnyRaise,
+}
+
+impl fmt::Display for PendingAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let repr = match self {
+            PendingAction::CheckFold => "check/fold",
+            PendingAction::CheckCall => "check/call",
+            PendingAction::CallAny => "call any",
+            PendingAction::FoldToAnyRaise => "fold to any raise",
+        };
+        write!(f, "{repr}")
+    }
+}
+
 impl fmt::Display for Action {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let repr = match self {
@@ -183,7 +546,7 @@ impl From<Bet> for Action {
     fn from(value: Bet) -> Self {
         match value.action {
             BetAction::AllIn => Action::AllIn,
-            BetAction::Call => Action::Call(value.amount),
+            BetAction::Blind | BetAction::Call => Action::Call(value.amount),
             BetAction::Raise => Action::Raise(value.amount),
         }
     }
@@ -230,6 +593,9 @@ impl PartialEq for Action {
 #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum BetAction {
     AllIn,
+    /// A small or big blind posted before any cards are dealt. Blinds
+    /// aren't raises, so they don't count towards the betting round.
+    Blind,
     Call,
     Raise,
 }
@@ -245,6 +611,7 @@ impl fmt::Display for Bet {
         let amount = self.amount;
         let repr = match self.action {
             BetAction::AllIn => format!("all-in of ${amount}"),
+            BetAction::Blind => format!("blind of ${amount}"),
             BetAction::Call => format!("call of ${amount}"),
             BetAction::Raise => format!("raise of ${amount}"),
         };
@@ -289,31 +656,93 @@ pub struct Player {
     pub state: PlayerState,
     pub cards: Vec<Card>,
     pub showing: bool,
-    pub seat_idx: usize,
+    /// Index of the single hole card the player chose to show, if they
+    /// chose to show one card rather than their whole hand.
+    pub shown_card: Option<usize>,
+    /// Whether the player mucked their hand. A mucked hand is never
+    /// shown for the rest of the hand unless it wins a contested pot.
+    pub mucked: bool,
+    pub seat_idx: SeatIndex,
+    /// The player's last bet this street, including blinds. The amount
+    /// is everything the player has bet this street, like the chips
+    /// in front of their seat.
+    pub last_bet: Option<Bet>,
 }
 
 impl Player {
-    pub fn new(user: User, seat_idx: usize) -> Player {
+    pub fn new(user: User, seat_idx: SeatIndex) -> Player {
         Player {
             user,
             state: PlayerState::Wait,
             cards: Vec::with_capacity(2),
             showing: false,
+            shown_card: None,
+            mucked: false,
             seat_idx,
+            last_bet: None,
         }
     }
 
+    /// Record a bet the player made, adding to what they've already bet
+    /// this street.
+    pub fn record_bet(&mut self, bet: &Bet) {
+        let amount = self.last_bet.as_ref().map_or(0, |last_bet| last_bet.amount) + bet.amount;
+        self.last_bet = Some(Bet {
+            action: bet.action,
+            amount,
+        });
+    }
+
     pub fn reset(&mut self) {
         self.state = PlayerState::Wait;
         self.cards.clear();
         self.showing = false;
+        self.shown_card = None;
+        self.mucked = false;
+        self.last_bet = None;
     }
 }
 
+/// A segment of the pot that's split amongst the best hands of the
+/// players with a claim on it. The pot is distributed one segment at a
+/// time, from the side pot with the largest investments down to the
+/// main pot.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PotSegment {
+    pub amount: Usd,
+    /// Players still in the hand that invested enough to win the segment.
+    pub eligible: Vec<Username>,
+    /// Players that split the segment. Whatever can't be split evenly
+    /// amongst them goes to the donations.
+    pub winners: Vec<Username>,
+    /// Rank of the winning hands, or `None` if there was only one player
+    /// left to win the segment and no hands were compared.
+    pub winning_rank: Option<Rank>,
+}
+
+/// How what's left of the pot is distributed, decided before anything is
+/// paid out.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct DistributionOutcome {
+    pub segments: Vec<PotSegment>,
+}
+
 #[derive(Clone, Debug)]
 pub struct Pot {
     // Map seat indices (players) to their investment in the pot.
     pub investments: HashMap<usize, Usd>,
+    /// Size of the last full raise in the current betting round. All-ins
+    /// that raise by less than this (or the big blind) aren't full raises.
+    pub last_raise_size: Usd,
+    /// Seat indices of players that've acted since the last full raise.
+    /// An all-in that isn't a full raise doesn't reopen the betting, so
+    /// these players can only call or fold.
+    pub players_closed_to_raises: HashSet<usize>,
+    /// Number of full raises in the current betting round.
+    pub num_raises: usize,
+    /// Size of the pot when the current street started. Everything put
+    /// in the pot since then was bet this street.
+    pub street_start_size: Usd,
 }
 
 impl Default for Pot {
@@ -345,15 +774,28 @@ impl Pot {
     }
 
     /// Return the minimum amount a player has to bet in order for their
-    /// raise to be considered a valid raise.
-    pub fn get_min_raise_by_player_idx(&self, player_idx: usize) -> Usd {
-        2 * self.get_call() - self.get_investment_by_player_idx(player_idx)
+    /// raise to be considered a valid raise. A raise has to be at least
+    /// as big as the last full raise and the big blind.
+    pub fn get_min_raise_by_player_idx(&self, player_idx: usize, big_blind: Usd) -> Usd {
+        self.get_call_by_player_idx(player_idx) + max(self.last_raise_size, big_blind)
+    }
+
+    /// Return whether the player is allowed to raise, which they aren't
+    /// if they've acted since the last full raise.
+    pub fn is_open_to_raises(&self, player_idx: usize) -> bool {
+        !self.players_closed_to_raises.contains(&player_idx)
     }
 
     pub fn get_size(&self) -> Usd {
         self.investments.values().sum()
     }
 
+    /// Return how much of the pot was bet this street. Bets that were
+    /// returned or paid out come out of this street's bets first.
+    pub fn get_street_bets(&self) -> Usd {
+        self.get_size().saturating_sub(self.street_start_size)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.get_size() == 0
     }
@@ -361,42 +803,619 @@ impl Pot {
     pub fn new(max_players: usize) -> Pot {
         Pot {
             investments: HashMap::with_capacity(max_players),
+            last_raise_size: 0,
+            players_closed_to_raises: HashSet::with_capacity(max_players),
+            num_raises: 0,
+            street_start_size: 0,
         }
     }
+
+    /// Pull this street's bets into the middle for the next street.
+    pub fn start_street(&mut self) {
+        self.street_start_size = self.get_size();
+    }
+
+    /// Forget raises from the last betting round. Investments carry over
+    /// between betting rounds, but raise sizes don't.
+    pub fn start_betting_round(&mut self) {
+        self.last_raise_size = 0;
+        self.players_closed_to_raises.clear();
+        self.num_raises = 0;
+    }
+}
+
+/// Notable game occurrences that clients may want to know about, but
+/// that aren't necessarily immediate results of user commands.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum GameEvent {
+    /// A user added chips to their stack.
+    AddedChips(Username, Usd),
+    /// A player went all-in, committing the rest of their stack, whether
+    /// they chose to or a blind or ante took all they had.
+    AllIn(Username, Usd),
+    /// A player drew the button at a new table.
+    ButtonDrawn(Username),
+    /// A player moved from the first seat to the second seat.
+    ChangedSeat(Username, SeatIndex, SeatIndex),
+    /// A community card was dealt to the given position on the board,
+    /// starting from zero for the first card of the flop.
+    CommunityCardDealt(Card, usize),
+    /// The deck was shuffled for a new hand and committed to before any
+    /// cards were dealt.
+    DeckCommitment(Commitment),
+    /// The hand is over, so the deck it was dealt from is revealed for
+    /// checking against its commitment.
+    DeckReveal(DeckReveal),
+    /// Every player left in an all-in hand has shown their hand, so each
+    /// player's chance of winning the pot from here, between 0 and 1, is
+    /// known to everyone.
+    Equity(Vec<(Username, f32)>),
+    /// The game switched to the named format between hands.
+    FormatChanged(String),
+    /// An all-in player took an insurance offer.
+    InsuranceAccepted(Insurance),
+    /// A user offered insurance to an all-in player.
+    InsuranceOffered(Insurance),
+    /// Insurance was settled at the end of the hand, with the first user
+    /// paying the second user the given amount.
+    InsuranceSettled(Username, Username, Usd),
+    /// A segment of the pot was split amongst the given winners, along
+    /// with the rank of their hands if any hands were compared.
+    PotAwarded(Vec<Username>, Usd, Option<Rank>),
+    /// A player that couldn't afford the big blind automatically rebought
+    /// this much to get back to the starting stack.
+    Rebuy(Username, Usd),
+    /// A user changed their username from the first username to the
+    /// second username.
+    Renamed(Username, Username),
+    /// A player drew the given seat at a new table.
+    SeatDrawn(Username, SeatIndex),
+    /// Betting closed with part of a bet that no one matched, so that
+    /// part was returned to the player that bet it.
+    UncalledBetReturned(Username, Usd),
+    /// A collusion detector suspects the two users are working together,
+    /// with a score that depends on the detector. Only the server's
+    /// operators see these.
+    SuspicionRaised(Username, Username, u32),
+    /// A spectator was paid the given amount from the spectator pool for
+    /// betting on a winner.
+    SideBetPaid(Username, Usd),
+    /// A spectator bet on a player winning the hand.
+    SideBetPlaced(SideBet),
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl GameEvent {
+    /// Whether the event is only meant for the server's operators rather
+    /// than everyone at the table.
+    pub fn is_operator_only(&self) -> bool {
+        matches!(self, GameEvent::SuspicionRaised(..))
+    }
+}
+
+impl fmt::Display for GameEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let repr = match self {
+            GameEvent::AddedChips(username, amount) => {
+                format!("{username} added ${amount} to their stack")
+            }
+            GameEvent::AllIn(username, amount) => format!("{username} is all-in for ${amount}"),
+            GameEvent::ButtonDrawn(username) => format!("{username} drew the button"),
+            GameEvent::ChangedSeat(username, from, to) => {
+                format!("{username} moved from seat {from} to seat {to}")
+            }
+            GameEvent::CommunityCardDealt(card, _) => {
+                format!("dealt {} to the board", card.to_unicode())
+            }
+            GameEvent::DeckCommitment(commitment) => {
+                format!(
+                    "shuffled a deck with commitment {}",
+                    fairness::to_hex(commitment)
+                )
+            }
+            GameEvent::DeckReveal(reveal) => format!(
+                "revealed the deck with commitment {}",
+                fairness::to_hex(&reveal.commitment())
+            ),
+            GameEvent::Equity(equities) => format!(
+                "equity: {}",
+                equities
+                    .iter()
+                    .map(|(username, equity)| format!("{username} {:.0}%", 100.0 * equity))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            GameEvent::FormatChanged(name) => format!("switched to {name}"),
+            GameEvent::InsuranceAccepted(insurance) => format!(
+                "{} took ${} of insurance from {} for ${}",
+                insurance.insured, insurance.payout, insurance.insurer, insurance.premium
+            ),
+            GameEvent::InsuranceOffered(insurance) => format!(
+                "{} offered {} ${} of insurance for ${}",
+                insurance.insurer, insurance.insured, insurance.payout, insurance.premium
+            ),
+            GameEvent::InsuranceSettled(from, to, amount) => {
+                format!("{from} paid {to} ${amount} for insurance")
+            }
+            GameEvent::PotAwarded(winners, amount, rank) => {
+                let winners = winners.join(", ");
+                match rank {
+                    Some(rank) => format!("{winners} won ${amount} ({rank})"),
+                    None => format!("{winners} won ${amount}"),
+                }
+            }
+            GameEvent::Rebuy(username, amount) => {
+                format!("{username} rebought ${amount}")
+            }
+            GameEvent::Renamed(username, new_username) => {
+                format!("{username} is now known as {new_username}")
+            }
+            GameEvent::SeatDrawn(username, seat_idx) => {
+                format!("{username} drew seat {seat_idx}")
+            }
+            GameEvent::UncalledBetReturned(username, amount) => {
+                format!("returned ${amount} uncalled bet to {username}")
+            }
+            GameEvent::SuspicionRaised(username1, username2, score) => {
+                format!("{username1} and {username2} may be colluding (score {score})")
+            }
+            GameEvent::SideBetPaid(username, amount) => {
+                format!("{username} won ${amount} from the spectator pool")
+            }
+            GameEvent::SideBetPlaced(bet) => {
+                format!("{} bet ${} on {}", bet.spectator, bet.amount, bet.player)
+            }
+        };
+        write!(f, "{repr}")
+    }
+}
+
+/// The phase of the game, one for each poker state. Phases carry the
+/// data needed to describe them so clients don't have to parse the
+/// game's status string.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum GamePhase {
+    Lobby,
+    SeatingPlayers,
+    MovingButton,
+    CollectingBlinds {
+        small: Usd,
+        small_from: Username,
+        big: Usd,
+        big_from: Username,
+    },
+    /// Every player is paying an ante for a bomb pot instead of blinds.
+    CollectingAntes {
+        ante: Usd,
+    },
+    Dealing,
+    /// Players are taking actions. The round is over once all players
+    /// have acted or can no longer act.
+    Betting {
+        round_over: bool,
+    },
+    Flop,
+    Turn,
+    River,
+    /// A pot is being shown. The main pot has index zero, and side
+    /// pots are numbered from one.
+    ShowingPot {
+        index: usize,
+    },
+    /// A pot is being distributed to its winners. Pots are indexed the
+    /// same as when they're shown.
+    DistributingPot {
+        index: usize,
+    },
+    RemovingPlayers,
+    DividingDonations,
+    UpdatingBlinds,
+    BootingPlayers,
+}
+
+impl fmt::Display for GamePhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let repr = match self {
+            GamePhase::Lobby => "in lobby",
+            GamePhase::SeatingPlayers => "seating players",
+            GamePhase::MovingButton => "moving button",
+            GamePhase::CollectingBlinds {
+                small,
+                small_from,
+                big,
+                big_from,
+            } => &format!("collecting ${big} from {big_from} and ${small} from {small_from}"),
+            GamePhase::CollectingAntes { ante } => {
+                &format!("bomb pot! collecting ${ante} from everyone")
+            }
+            GamePhase::Dealing => "dealing cards",
+            GamePhase::Betting { round_over: true } => "end of betting round",
+            GamePhase::Betting { round_over: false } => "betting round transition",
+            GamePhase::Flop => "the flop",
+            GamePhase::Turn => "the turn",
+            GamePhase::River => "the river",
+            GamePhase::ShowingPot { index: 0 } => "showing main pot",
+            GamePhase::ShowingPot { index } => &format!("showing side pot #{index}"),
+            GamePhase::DistributingPot { index: 0 } => "distributing main pot",
+            GamePhase::DistributingPot { index } => &format!("distributing side pot #{index}"),
+            GamePhase::RemovingPlayers => "updating players that joined spectators or left",
+            GamePhase::DividingDonations => "dividing donations",
+            GamePhase::UpdatingBlinds => "updating blinds",
+            GamePhase::BootingPlayers => "spectating players that can't afford the big blind",
+        };
+        write!(f, "{repr}")
+    }
+}
+
+/// A player's hole cards as the viewer sees them.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum HoleCards {
+    /// The player has no cards, either because they weren't dealt in or
+    /// because they folded or mucked them.
+    #[default]
+    None,
+    /// The player is holding this many cards face down.
+    Hidden { count: usize },
+    /// The player's cards that are face up to the viewer. Players that
+    /// only show one card have just that card here.
+    Revealed(Vec<Card>),
+}
+
+impl HoleCards {
+    /// Return the cards that are face up, if there are any.
+    pub fn revealed(&self) -> &[Card] {
+        match self {
+            Self::Revealed(cards) => cards,
+            Self::None | Self::Hidden { .. } => &[],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct PlayerView {
     pub user: User,
     pub state: PlayerState,
-    pub cards: Vec<Card>,
+    /// The player's hole cards as the viewer sees them. Players always
+    /// see their own hand, but everyone else only sees the cards the
+    /// player has revealed.
+    pub cards: HoleCards,
+    /// How much of their hand the player has revealed to everyone.
+    pub revealed: Reveal,
+    /// Whether the player's connection dropped and the game is acting
+    /// for them until they reconnect.
+    pub disconnected: bool,
+    /// The player's last bet this street, with everything they've bet
+    /// this street as its amount.
+    pub last_bet: Option<Bet>,
+    /// Everything the player has put in the pot this hand.
+    pub investment: Usd,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl PlayerView {
+    /// Hide the player's hole cards the same way they're hidden from the
+    /// rest of the table, keeping only the cards they've revealed.
+    pub fn conceal_cards(&mut self) {
+        let HoleCards::Revealed(ref cards) = self.cards else {
+            return;
+        };
+        self.cards = match self.revealed {
+            Reveal::Hand => return,
+            Reveal::Card(card_idx) if card_idx < cards.len() => {
+                HoleCards::Revealed(vec![cards[card_idx]])
+            }
+            Reveal::Card(_) => return,
+            Reveal::Hidden if self.state == PlayerState::Fold => HoleCards::None,
+            Reveal::Hidden => HoleCards::Hidden { count: cards.len() },
+        };
+    }
+}
+
+/// How much of a player's hand is visible to the rest of the table.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Reveal {
+    #[default]
+    Hidden,
+    /// Only the hole card at this index in the player's hand is shown.
+    Card(usize),
+    /// The player's whole hand is shown.
+    Hand,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct PotView {
     pub size: Usd,
+    /// How much of the pot was bet this street rather than carried over
+    /// from earlier streets.
+    pub street_bets: Usd,
 }
 
 impl fmt::Display for PotView {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "${}", self.size)
+        // Bets are only split out once there's something in the middle
+        // to add them to.
+        let base = self.size - self.street_bets;
+        if base == 0 || self.street_bets == 0 {
+            write!(f, "${}", self.size)
+        } else {
+            write!(f, "${base} + ${}", self.street_bets)
+        }
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Everything a player needs to decide on their action, taken from the
+/// game at the start of their turn so it doesn't have to be pieced
+/// together from the latest view.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct DecisionContext {
+    pub variant: GameVariant,
+    pub board: Vec<Card>,
+    /// The player's hole cards.
+    pub cards: Vec<Card>,
+    /// Everything that's been put in the pot this hand.
+    pub pot: Usd,
+    /// How much more the player has to put in to call. This can be more
+    /// than their stack, in which case calling means going all-in.
+    pub call: Usd,
+    /// The smallest amount the player can raise by, or `None` if they
+    /// can't raise.
+    pub min_raise: Option<Usd>,
+    /// The most the player can raise by, or `None` if they can't raise.
+    pub max_raise: Option<Usd>,
+    /// The player's money that isn't in the pot.
+    pub stack: Usd,
+    /// How many seats after the button the player is, so the button is
+    /// always 0.
+    pub position: usize,
+    /// How many players were dealt into the hand.
+    pub num_players: usize,
+}
+
+/// Poker variants that can be played at a table.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum GameVariant {
+    /// Players make their best hand from any of their 2 hole cards and
+    /// the board.
+    #[default]
+    Holdem,
+    /// Players are dealt 4 hole cards and must make their hand with
+    /// exactly 2 of them and exactly 3 cards from the board.
+    Omaha,
+    /// Hold'em without any 2s through 5s. Flushes beat full houses since
+    /// they're harder to make, and aces play low in A-6-7-8-9 straights.
+    ShortDeck,
+}
+
+impl GameVariant {
+    pub fn deck_size(&self) -> usize {
+        match self {
+            GameVariant::Holdem | GameVariant::Omaha => 52,
+            GameVariant::ShortDeck => 36,
+        }
+    }
+
+    pub fn num_hole_cards(&self) -> usize {
+        match self {
+            GameVariant::Holdem | GameVariant::ShortDeck => 2,
+            GameVariant::Omaha => 4,
+        }
+    }
+}
+
+impl fmt::Display for GameVariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            GameVariant::Holdem => "hold'em",
+            GameVariant::Omaha => "omaha",
+            GameVariant::ShortDeck => "short deck",
+        };
+        write!(f, "{repr}")
+    }
+}
+
+/// A betting round, named after the community cards dealt before it.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Street {
+    Preflop,
+    Flop,
+    Turn,
+    River,
+}
+
+impl Street {
+    pub fn from_num_community_cards(num_community_cards: usize) -> Self {
+        match num_community_cards {
+            0..=2 => Street::Preflop,
+            3 => Street::Flop,
+            4 => Street::Turn,
+            _ => Street::River,
+        }
+    }
+}
+
+impl fmt::Display for Street {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            Street::Preflop => "preflop",
+            Street::Flop => "flop",
+            Street::Turn => "turn",
+            Street::River => "river",
+        };
+        write!(f, "{repr}")
+    }
+}
+
+/// An action a player took during a hand, as the game sanitized it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ActionRecord {
+    pub username: Username,
+    pub action: Action,
+    pub street: Street,
+}
+
+/// Betting structure of a fixed-limit game. Bets and raises are always
+/// exactly one bet, where a bet is the small bet before the turn and the
+/// big bet from the turn on.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct LimitStructure {
+    pub small_bet: Usd,
+    pub big_bet: Usd,
+    /// Number of bets and raises allowed per street. All-ins for less
+    /// than a bet don't count towards it.
+    pub max_raises_per_street: usize,
+}
+
+impl LimitStructure {
+    /// Return the size of a bet given how many community cards have been
+    /// dealt.
+    pub fn get_bet_size(&self, num_community_cards: usize) -> Usd {
+        if num_community_cards < 4 {
+            self.small_bet
+        } else {
+            self.big_bet
+        }
+    }
+}
+
+impl fmt::Display for LimitStructure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} Limit", self.small_bet, self.big_bet)
+    }
+}
+
+/// How much of the users that aren't seated at the table everyone else
+/// can see. Players' stacks are always visible since they're needed for
+/// play, and users can always see themselves.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Privacy {
+    /// Everyone sees every spectator and waitlisted user, including
+    /// their money.
+    #[default]
+    Open,
+    /// Everyone sees every spectator and waitlisted user, but their money
+    /// is zeroed out.
+    HideSpectatorMoney,
+    /// Spectators and waitlisted users are left out of views entirely.
+    HideNonPlayers,
+}
+
+impl fmt::Display for Privacy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repr = match self {
+            Privacy::Open => "open",
+            Privacy::HideSpectatorMoney => "hiding spectator money",
+            Privacy::HideNonPlayers => "hiding non-players",
+        };
+        write!(f, "{repr}")
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct GameView {
+    pub hand_id: HandId,
     pub donations: Usdf,
     pub small_blind: Usd,
     pub big_blind: Usd,
     pub spectators: HashMap<String, User>,
     pub waitlist: VecDeque<User>,
-    pub open_seats: VecDeque<usize>,
+    pub open_seats: VecDeque<SeatIndex>,
     pub players: Vec<PlayerView>,
     pub board: Vec<Card>,
     pub pot: PotView,
+    pub button_idx: usize,
     pub small_blind_idx: usize,
     pub big_blind_idx: usize,
     pub next_action_idx: Option<usize>,
+    /// Whether the hand is a bomb pot, starting at the flop after every
+    /// player antes.
+    pub bomb_pot: bool,
+    pub variant: GameVariant,
+    /// How the spectators and waitlist were redacted, if at all. This and
+    /// the fields after it are last so clients that predate them, and
+    /// stop reading before them, can still parse views.
+    pub privacy: Privacy,
+    /// Betting structure if the game is fixed-limit, or `None` if it's
+    /// no-limit.
+    pub limit: Option<LimitStructure>,
+    /// The most recent actions taken during the current hand (or the last
+    /// hand if one isn't in progress), oldest first.
+    pub action_history: Vec<ActionRecord>,
+    /// Each player's chance of winning the pot, between 0 and 1, while an
+    /// all-in hand runs out with every remaining hand shown. Empty
+    /// otherwise.
+    pub equities: Vec<(Username, f32)>,
 }
 
-pub type GameViews = HashMap<String, GameView>;
+/// Views of the game for every user. Players' own hole cards are the only
+/// part of the game that differs between views, so everyone that can't see
+/// any hidden cards shares the same view, and players' views are only built
+/// when they're asked for.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GameViews {
+    /// Everything that's visible to everyone.
+    public: Arc<GameView>,
+    /// Hidden hands mapped to the index of the player they belong to.
+    /// Only the player can see their hand.
+    hands: HashMap<Username, (usize, Vec<Card>)>,
+    /// Spectators and waitlisted users before they were redacted from the
+    /// public view, so they can still see themselves in their own views.
+    spectators: HashMap<Username, User>,
+    waitlist: VecDeque<User>,
+    /// Railbirds aren't in the public view at all, but they still get it.
+    railbirds: HashSet<Username>,
+}
+
+impl GameViews {
+    pub fn contains(&self, username: &str) -> bool {
+        self.usernames().any(|u| u == username)
+    }
+
+    /// Return the user's view, or `None` if they aren't in the game.
+    /// Redacted spectators and waitlisted users are added back to their
+    /// own views. A redacted waitlisted user is put at the back of the
+    /// waitlist if the rest of the waitlist is hidden.
+    pub fn get(&self, username: &str) -> Option<Arc<GameView>> {
+        if let Some((player_idx, cards)) = self.hands.get(username) {
+            let mut view = GameView::clone(&self.public);
+            view.players[*player_idx].cards = HoleCards::Revealed(cards.clone());
+            return Some(Arc::new(view));
+        } else if self.public.privacy != Privacy::Open {
+            if let Some(user) = self.spectators.get(username) {
+                let mut view = GameView::clone(&self.public);
+                view.spectators.insert(user.name.clone(), user.clone());
+                return Some(Arc::new(view));
+            } else if let Some(user) = self.waitlist.iter().find(|u| u.name == username) {
+                let mut view = GameView::clone(&self.public);
+                match view.waitlist.iter_mut().find(|u| u.name == username) {
+                    Some(redacted) => redacted.money = user.money,
+                    None => view.waitlist.push_back(user.clone()),
+                }
+                return Some(Arc::new(view));
+            }
+        }
+        self.contains(username).then(|| Arc::clone(&self.public))
+    }
+
+    pub fn new(
+        public: GameView,
+        hands: HashMap<Username, (usize, Vec<Card>)>,
+        spectators: HashMap<Username, User>,
+        waitlist: VecDeque<User>,
+        railbirds: HashSet<Username>,
+    ) -> Self {
+        Self {
+            public: Arc::new(public),
+            hands,
+            spectators,
+            waitlist,
+            railbirds,
+        }
+    }
+
+    /// Return the usernames of every user that gets a view.
+    pub fn usernames(&self) -> impl Iterator<Item = &Username> {
+        self.spectators
+            .keys()
+            .chain(self.waitlist.iter().map(|u| &u.name))
+            .chain(self.public.players.iter().map(|p| &p.user.name))
+            .chain(self.railbirds.iter())
+    }
+}
@@ -0,0 +1,186 @@
+//! Chances of winning a hand that's running out to the showdown.
+//!
+//! Equities are exact when there are few enough runouts left to try them
+//! all, like on the turn or flop, and are estimated from random runouts
+//! otherwise so 3+ way all-ins before the flop stay fast.
+
+use rand::{seq::SliceRandom, Rng};
+
+use super::{
+    entities::{Card, GameVariant},
+    eval_hand, functional,
+};
+
+/// Return each hand's share of the wins over every way the rest of the
+/// board can run out using the unseen cards. Ties split the win evenly
+/// between the hands that tie. Runouts are sampled at random if there
+/// are more than `max_samples` of them.
+pub fn equities<R: Rng>(
+    hands: &[Vec<Card>],
+    board: &[Card],
+    unseen: &[Card],
+    variant: GameVariant,
+    max_samples: usize,
+    rng: &mut R,
+) -> Vec<f32> {
+    let num_cards_left = 5usize.saturating_sub(board.len());
+    let mut wins = vec![0.0; hands.len()];
+    let mut full_board = board.to_vec();
+    let mut add_wins = |runout: &[Card]| {
+        full_board.truncate(board.len());
+        full_board.extend_from_slice(runout);
+        let hand_values: Vec<_> = hands
+            .iter()
+            .map(|hand| eval_hand(hand, &full_board, variant))
+            .collect();
+        let winners = functional::argmax(&hand_values);
+        for winner in winners.iter() {
+            wins[*winner] += 1.0 / winners.len() as f64;
+        }
+    };
+
+    let num_runouts = match num_runouts(unseen.len(), num_cards_left) {
+        Some(num_runouts) if num_runouts <= max_samples => {
+            for_each_combination(unseen, num_cards_left, &mut add_wins);
+            num_runouts
+        }
+        _ => {
+            let mut unseen = unseen.to_vec();
+            for _ in 0..max_samples {
+                let (runout, _) = unseen.partial_shuffle(rng, num_cards_left);
+                add_wins(runout);
+            }
+            max_samples
+        }
+    };
+    wins.into_iter()
+        .map(|wins| (wins / num_runouts.max(1) as f64) as f32)
+        .collect()
+}
+
+/// Call `f` with every combination of `k` cards.
+fn for_each_combination(cards: &[Card], k: usize, f: &mut impl FnMut(&[Card])) {
+    let mut combination = Vec::with_capacity(k);
+    fn recurse(cards: &[Card], k: usize, combination: &mut Vec<Card>, f: &mut impl FnMut(&[Card])) {
+        if combination.len() == k {
+            f(combination);
+            return;
+        }
+        let num_needed = k - combination.len();
+        if cards.len() < num_needed {
+            return;
+        }
+        for card_idx in 0..=cards.len() - num_needed {
+            combination.push(cards[card_idx]);
+            recurse(&cards[card_idx + 1..], k, combination, f);
+            combination.pop();
+        }
+    }
+    recurse(cards, k, &mut combination, f);
+}
+
+/// Return the number of ways to choose `k` of `n` cards, or `None` if
+/// there are too many to count.
+fn num_runouts(n: usize, k: usize) -> Option<usize> {
+    if k > n {
+        return Some(0);
+    }
+    let mut num_runouts: usize = 1;
+    for i in 0..k {
+        num_runouts = num_runouts.checked_mul(n - i)? / (i + 1);
+    }
+    Some(num_runouts)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::game::{
+        constants::MAX_EQUITY_SAMPLES,
+        entities::{Card, GameVariant, Suit},
+        functional,
+    };
+
+    use super::{equities, num_runouts};
+
+    fn unseen(known: &[&[Card]]) -> Vec<Card> {
+        functional::new_deck()
+            .into_iter()
+            .filter(|card| !known.iter().any(|cards| cards.contains(card)))
+            .collect()
+    }
+
+    #[test]
+    fn drawing_dead_has_no_equity() {
+        // Quad kings on the turn can't be beaten with one card to come.
+        let board = [
+            Card(13, Suit::Spade),
+            Card(13, Suit::Heart),
+            Card(13, Suit::Diamond),
+            Card(7, Suit::Club),
+        ];
+        let hands = vec![
+            vec![Card(13, Suit::Club), Card(2, Suit::Diamond)],
+            vec![Card(1, Suit::Spade), Card(1, Suit::Heart)],
+        ];
+        let unseen = unseen(&[&board, &hands[0], &hands[1]]);
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(
+            equities(
+                &hands,
+                &board,
+                &unseen,
+                GameVariant::Holdem,
+                MAX_EQUITY_SAMPLES,
+                &mut rng
+            ),
+            vec![1.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn sampled_flip_is_close_to_exact() {
+        // Queens are about a 57% favorite over ace-king offsuit.
+        let hands = vec![
+            vec![Card(12, Suit::Club), Card(12, Suit::Diamond)],
+            vec![Card(1, Suit::Heart), Card(13, Suit::Spade)],
+        ];
+        let unseen = unseen(&[&hands[0], &hands[1]]);
+        let mut rng = StdRng::seed_from_u64(0);
+        let sampled = equities(
+            &hands,
+            &[],
+            &unseen,
+            GameVariant::Holdem,
+            MAX_EQUITY_SAMPLES,
+            &mut rng,
+        );
+        assert!((sampled[0] - 0.57).abs() < 0.03, "{sampled:?}");
+        assert!((sampled[0] + sampled[1] - 1.0).abs() < 1e-4);
+
+        // Every runout is tried once there are few enough of them, which
+        // always gives the same equities.
+        let board = [
+            Card(2, Suit::Heart),
+            Card(7, Suit::Heart),
+            Card(9, Suit::Spade),
+        ];
+        let unseen: Vec<Card> = unseen
+            .into_iter()
+            .filter(|card| !board.contains(card))
+            .collect();
+        assert_eq!(num_runouts(unseen.len(), 2), Some(990));
+        let exact = |seed| {
+            equities(
+                &hands,
+                &board,
+                &unseen,
+                GameVariant::Holdem,
+                MAX_EQUITY_SAMPLES,
+                &mut StdRng::seed_from_u64(seed),
+            )
+        };
+        assert_eq!(exact(0), exact(1));
+    }
+}
@@ -0,0 +1,176 @@
+//! Commit-reveal scheme for proving the deck wasn't stacked.
+//!
+//! Right after the deck is shuffled for a hand, the game commits to its
+//! order by announcing a hash of the order and a random nonce. Once the
+//! hand is over, the order and nonce are revealed so anyone can hash
+//! them again and check that the cards dealt came from the deck that was
+//! committed to before anyone acted.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::entities::Card;
+
+/// SHA-256 hash of a deck's order followed by a nonce.
+pub type Commitment = [u8; 32];
+
+/// Random bytes hashed with a deck's order so the order can't be guessed
+/// from its commitment.
+pub type Nonce = [u8; 32];
+
+/// Return the commitment to a deck's order. Each card is hashed as its
+/// value followed by its suit.
+pub fn commit(deck: &[Card], nonce: &Nonce) -> Commitment {
+    let mut hasher = Sha256::new();
+    for Card(value, suit) in deck {
+        hasher.update([*value, *suit as u8]);
+    }
+    hasher.update(nonce);
+    hasher.finalize().into()
+}
+
+/// Return a commitment as a lowercase hex string for people to compare.
+pub fn to_hex(commitment: &Commitment) -> String {
+    commitment
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Number of cards dealt on each street after a burn, if cards are
+/// burned: the flop, the turn, and the river.
+const STREET_SIZES: [usize; 3] = [3, 1, 1];
+
+/// A hand's whole deck order and the nonce it was committed to with,
+/// revealed once the hand is over. This includes the hole cards of
+/// players that folded or mucked.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct DeckReveal {
+    pub deck: Vec<Card>,
+    pub nonce: Nonce,
+    /// Cards burned before each street that was dealt, in order. Empty
+    /// if the game doesn't burn cards.
+    pub burned: Vec<Card>,
+}
+
+impl DeckReveal {
+    /// Return the commitment the revealed deck order and nonce hash to.
+    pub fn commitment(&self) -> Commitment {
+        commit(&self.deck, &self.nonce)
+    }
+
+    /// Return whether the board was dealt from the revealed deck after
+    /// all the hole cards, the same way the game deals. The board has to
+    /// show up in the deck in the order it was dealt, with each burned
+    /// card right before the street it was burned for, and the hole
+    /// cards have to come before it.
+    pub fn dealt(&self, hole_cards: &[Card], board: &[Card]) -> bool {
+        let dealt = self.dealt_after_hole_cards(board);
+        let board_idx = if dealt.is_empty() {
+            Some(self.deck.len())
+        } else {
+            self.deck
+                .windows(dealt.len())
+                .position(|window| window == dealt)
+        };
+        board_idx.is_some_and(|board_idx| {
+            hole_cards
+                .iter()
+                .all(|card| self.deck[..board_idx].contains(card))
+        })
+    }
+
+    /// Return the cards dealt after the hole cards in the order they were
+    /// dealt, putting each burned card before the street it was burned
+    /// for.
+    fn dealt_after_hole_cards(&self, board: &[Card]) -> Vec<Card> {
+        let mut dealt = Vec::with_capacity(self.burned.len() + board.len());
+        let mut streets = board.iter();
+        for (card, street_size) in self.burned.iter().zip(STREET_SIZES) {
+            dealt.push(*card);
+            dealt.extend(streets.by_ref().take(street_size));
+        }
+        dealt.extend(streets);
+        dealt
+    }
+
+    /// Return whether the revealed deck order and nonce hash to the
+    /// commitment.
+    pub fn verify(&self, commitment: &Commitment) -> bool {
+        self.commitment() == *commitment
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{entities::Suit, functional};
+
+    use super::{commit, to_hex, DeckReveal};
+
+    fn reveal() -> DeckReveal {
+        DeckReveal {
+            deck: functional::new_deck().to_vec(),
+            nonce: [7; 32],
+            burned: vec![],
+        }
+    }
+
+    #[test]
+    fn honest_reveal_matches_commitment() {
+        let reveal = reveal();
+        let commitment = commit(&reveal.deck, &reveal.nonce);
+        assert!(reveal.verify(&commitment));
+        assert_eq!(to_hex(&commitment).len(), 64);
+
+        // Hole cards are dealt first, then the board.
+        let hole_cards = reveal.deck[..4].to_vec();
+        let board = reveal.deck[4..9].to_vec();
+        assert!(reveal.dealt(&hole_cards, &board));
+        assert!(reveal.dealt(&hole_cards, &[]));
+    }
+
+    #[test]
+    fn burned_cards_come_before_their_streets() {
+        let mut reveal = reveal();
+        let deck = reveal.deck.clone();
+        let hole_cards = deck[..4].to_vec();
+        let flop = deck[5..8].to_vec();
+        reveal.burned = vec![deck[4]];
+        assert!(reveal.dealt(&hole_cards, &flop));
+
+        let board: Vec<_> = [&deck[5..8], &deck[9..10], &deck[11..12]].concat();
+        reveal.burned = vec![deck[4], deck[8], deck[10]];
+        assert!(reveal.dealt(&hole_cards, &board));
+
+        // Boards that skip over the burns or burn somewhere else weren't
+        // dealt that way.
+        assert!(!reveal.dealt(&hole_cards, &deck[4..9]));
+        reveal.burned = vec![deck[8], deck[4], deck[10]];
+        assert!(!reveal.dealt(&hole_cards, &board));
+    }
+
+    #[test]
+    fn tampered_reveal_fails_commitment() {
+        let reveal = reveal();
+        let commitment = commit(&reveal.deck, &reveal.nonce);
+
+        let mut swapped = reveal.clone();
+        swapped.deck.swap(0, 1);
+        assert!(!swapped.verify(&commitment));
+
+        let mut renonced = reveal.clone();
+        renonced.nonce[0] ^= 1;
+        assert!(!renonced.verify(&commitment));
+
+        let mut resuited = reveal.clone();
+        resuited.deck[0].1 = Suit::Wild;
+        assert!(!resuited.verify(&commitment));
+
+        // A board that wasn't dealt in order, or that was dealt before
+        // the hole cards, didn't come from the deck.
+        let mut board = reveal.deck[4..9].to_vec();
+        assert!(!reveal.dealt(&reveal.deck[..4], &reveal.deck[..3]));
+        board.swap(0, 1);
+        assert!(!reveal.dealt(&reveal.deck[..4], &board));
+    }
+}
@@ -1,9 +1,17 @@
 use std::{
-    cmp::Ordering,
-    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet},
+    cmp::{max, Ordering},
+    collections::HashSet,
+    iter,
 };
 
-use super::entities::{Card, Rank, SubHand, Suit, Value};
+use super::entities::{Card, CardParseError, HandValue, Rank, SubHand, Suit, Value};
+
+const ACE_LOW: u16 = 1 << 1;
+const ACE_HIGH: u16 = 1 << 14;
+const VALUES_MASK: u16 = 0b0111_1111_1111_1110;
+/// Aces play in place of 5s in short-deck straights, since there aren't
+/// any 2s through 5s.
+const SHORT_DECK_ACE_LOW: u16 = 1 << 5;
 
 /// Get the indices corresponding to the winning hands from an array
 /// of hands that were each created from `eval`.
@@ -19,21 +27,16 @@ use super::entities::{Card, Rank, SubHand, Suit, Value};
 /// let hand2 = eval(&cards2);
 /// assert_eq!(argmax(&[hand1, hand2]), vec![1])
 /// ```
-pub fn argmax(hands: &[Vec<SubHand>]) -> Vec<usize> {
-    let mut max = vec![SubHand {
-        rank: Rank::HighCard,
-        values: vec![0],
-    }];
+pub fn argmax<T: Ord>(hands: &[T]) -> Vec<usize> {
     let mut argmaxes: Vec<usize> = Vec::new();
     for (i, hand) in hands.iter().enumerate() {
-        match hand.cmp(&max) {
-            Ordering::Equal => argmaxes.push(i),
-            Ordering::Greater => {
+        match argmaxes.first().map(|max_idx| hand.cmp(&hands[*max_idx])) {
+            None | Some(Ordering::Greater) => {
                 argmaxes.clear();
                 argmaxes.push(i);
-                max.clone_from(hand);
             }
-            _ => {}
+            Some(Ordering::Equal) => argmaxes.push(i),
+            Some(Ordering::Less) => {}
         }
     }
     argmaxes
@@ -57,292 +60,304 @@ pub fn argmax(hands: &[Vec<SubHand>]) -> Vec<usize> {
 /// assert_eq!(subhands[0].rank, Rank::OnePair)
 /// ```
 pub fn eval(cards: &[Card]) -> Vec<SubHand> {
-    // Mapping of suit to (sorted) cards within that suit.
-    // Used for tracking whether there's a flush or straight flush.
-    let mut values_per_suit: HashMap<Suit, Vec<Value>> = HashMap::new();
-
-    // Used for tracking whether there's a straight.
-    let mut straight_count: usize = 0;
-    let mut straight_prev_value: Value = 0;
-
-    // Mapping of rank to each subhand for that rank. Helps track
-    // the highest subhand in each rank.
-    let mut subhands_per_rank: BTreeMap<Rank, BTreeSet<SubHand>> = BTreeMap::new();
-    // Count number of times a card value appears. Helps track one pair,
-    // two pair, etc.
-    let mut value_counts: HashMap<Value, usize> = HashMap::new();
-
-    // Loop through cards in hand assuming the hand is sorted
-    // and that each ace appears in the hand twice (at the low
-    // end with a value of 1 and at the high end with a value
-    // of 14). We push hands into a binary heap so we can
-    // easily get the best hand at the end.
-    let mut hands: BinaryHeap<SubHand> = BinaryHeap::new();
+    let mut suit_masks = [0; 4];
     for Card(value, suit) in cards.iter() {
-        // Keep a count of cards for each suit. If the suit count
-        // reaches a flush, it's also checked for a straight
-        // for the straight flush potential.
-        let values_in_suit = values_per_suit.entry(*suit).or_default();
-        values_in_suit.push(*value);
-
-        // Since aces appear in the cards twice, we need to make sure
-        // they aren't counted twice for the flush. To get around this,
-        // we just subtract one from the flush count in the case of the
-        // high valued ace.
-        let mut flush_count = values_in_suit.len();
-        if *value == 14 {
-            flush_count -= 1;
+        if let Some(suit_idx) = suit_to_idx(suit) {
+            suit_masks[suit_idx] |= 1 << value;
         }
+    }
+    eval_suit_masks(&suit_masks, false).into()
+}
 
-        // A flush was found.
-        if flush_count >= 5 {
-            let maybe_straight_flush_start_idx = values_in_suit.len() - 5;
-            let maybe_straight_flush_cards = &values_in_suit[maybe_straight_flush_start_idx..];
-            let mut is_straight_flush = true;
-            for flush_idx in 0..4 {
-                if (maybe_straight_flush_cards[flush_idx] + 1)
-                    != maybe_straight_flush_cards[flush_idx + 1]
-                {
-                    is_straight_flush = false;
-                    break;
-                }
-            }
+/// Evaluate exactly 7 cards (e.g., a player's hole cards and the board),
+/// returning a compact hand value that can be compared with other hand
+/// values to determine a winner. Unlike `eval`, this doesn't allocate
+/// and cards don't need to be prepared with `prepare_hand` beforehand.
+/// Aces can either be 1s or 14s.
+///
+/// # Examples
+///
+/// ```
+/// use private_poker::{entities::{Card, Rank, Suit}, functional::eval_7};
+///
+/// let cards = [
+///     Card(1, Suit::Club),
+///     Card(1, Suit::Heart),
+///     Card(4, Suit::Spade),
+///     Card(4, Suit::Heart),
+///     Card(7, Suit::Diamond),
+///     Card(9, Suit::Spade),
+///     Card(13, Suit::Club),
+/// ];
+/// let hand_value = eval_7(&cards);
+/// assert_eq!(hand_value.rank(), Rank::TwoPair);
+/// assert_eq!(hand_value.values().collect::<Vec<_>>(), vec![14, 4, 13]);
+/// ```
+pub fn eval_7(cards: &[Card; 7]) -> HandValue {
+    eval_cards(cards, false)
+}
 
-            let values = maybe_straight_flush_cards.iter().rev().copied().collect();
-            if is_straight_flush {
-                hands.push(SubHand {
-                    rank: Rank::StraightFlush,
-                    values,
-                })
-            } else {
-                hands.push(SubHand {
-                    rank: Rank::Flush,
-                    values,
-                })
-            }
-        }
+/// Evaluate any number of cards, returning a compact hand value like
+/// `eval_7` does. Aces can either be 1s or 14s.
+///
+/// # Examples
+///
+/// ```
+/// use private_poker::{entities::{Card, Rank, Suit}, functional::eval_value};
+///
+/// let cards = [Card(1, Suit::Club), Card(1, Suit::Heart), Card(9, Suit::Spade)];
+/// let hand_value = eval_value(&cards);
+/// assert_eq!(hand_value.rank(), Rank::OnePair);
+/// assert_eq!(hand_value.values().collect::<Vec<_>>(), vec![14, 9]);
+/// ```
+pub fn eval_value(cards: &[Card]) -> HandValue {
+    eval_cards(cards, false)
+}
 
-        // Keep a count of cards that're in sequential order to check for
-        // a straight. If the same value appears again, we can keep the
-        // straight count the same and don't have to reset.
-        if (straight_prev_value + 1) == *value {
-            straight_count += 1;
-        } else if straight_prev_value == *value {
-        } else {
-            straight_count = 1;
-        }
+/// Evaluate any number of cards from a short deck, where flushes beat
+/// full houses and A-6-7-8-9 is the lowest straight. Like `eval_7`,
+/// aces can either be 1s or 14s.
+///
+/// # Examples
+///
+/// ```
+/// use private_poker::{entities::{Card, Rank, Suit}, functional::eval_short_deck};
+///
+/// let cards = [
+///     Card(1, Suit::Club),
+///     Card(6, Suit::Heart),
+///     Card(7, Suit::Spade),
+///     Card(8, Suit::Heart),
+///     Card(9, Suit::Diamond),
+/// ];
+/// let hand_value = eval_short_deck(&cards);
+/// assert_eq!(hand_value.rank(), Rank::Straight);
+/// assert_eq!(hand_value.values().collect::<Vec<_>>(), vec![9]);
+/// ```
+pub fn eval_short_deck(cards: &[Card]) -> HandValue {
+    eval_cards(cards, true)
+}
 
-        // A straight was found.
-        straight_prev_value = *value;
-        if straight_count >= 5 {
-            let straight_subhand = SubHand {
-                rank: Rank::Straight,
-                values: (*value - 4..=*value).rev().collect(),
+/// Evaluate any number of cards without allocating, treating aces as
+/// either 1s or 14s.
+fn eval_cards<'a>(cards: impl IntoIterator<Item = &'a Card>, short_deck: bool) -> HandValue {
+    let mut suit_masks = [0; 4];
+    for Card(value, suit) in cards {
+        if let Some(suit_idx) = suit_to_idx(suit) {
+            suit_masks[suit_idx] |= match value {
+                1 | 14 => ACE_LOW | ACE_HIGH,
+                value => 1 << value,
             };
-            // We don't need to push the straight into the heap if something
-            // better was already found.
-            let best_subhand = hands.peek();
-            match best_subhand {
-                None => hands.push(straight_subhand),
-                Some(subhand) => {
-                    if *subhand < straight_subhand {
-                        hands.push(straight_subhand);
-                    }
-                }
-            }
         }
+    }
+    eval_suit_masks(&suit_masks, short_deck)
+}
 
-        // Now start checking for hands besides straights and flushes.
-        let value_count = value_counts.entry(*value).or_insert(0);
-        *value_count += 1;
-
-        match *value_count {
-            1 => {
-                let high_card_subhand = SubHand {
-                    rank: Rank::HighCard,
-                    values: vec![*value],
-                };
-                subhands_per_rank
-                    .entry(Rank::HighCard)
-                    .or_default()
-                    .insert(high_card_subhand);
-            }
-
-            2 => {
-                let one_pair_subhand = SubHand {
-                    rank: Rank::OnePair,
-                    values: vec![*value; 2],
-                };
-                let one_pairs = subhands_per_rank.entry(Rank::OnePair).or_default();
-                one_pairs.insert(one_pair_subhand);
-
-                // Check if a pair also occurs, then both pairs
-                // make a two pair.
-                if let Some(next_best_one_pair) = one_pairs.iter().nth_back(1) {
-                    // Ignore the case where a high ace and low ace get counted together
-                    // as a two pair.
-                    if *value != 14 || next_best_one_pair.values != vec![1, 1] {
-                        let mut two_pair_cards = vec![*value; 2];
-                        two_pair_cards.extend(next_best_one_pair.values.clone());
-                        let two_pair_subhand = SubHand {
-                            rank: Rank::TwoPair,
-                            values: two_pair_cards,
-                        };
-                        subhands_per_rank
-                            .entry(Rank::TwoPair)
-                            .or_default()
-                            .insert(two_pair_subhand);
-                    }
-                }
-
-                // Check if a three of a kind also occurs, then the pair
-                // and three of a kind make a full house.
-                if let Some(three_of_a_kinds) = subhands_per_rank.get(&Rank::ThreeOfAKind) {
-                    if let Some(best_three_of_a_kind) = three_of_a_kinds.iter().next() {
-                        // Ignore the case where a high ace and low ace get counted together
-                        // as a full house.
-                        if *value != 14 || best_three_of_a_kind.values != vec![1, 1, 1] {
-                            let mut full_house_cards = best_three_of_a_kind.values.clone();
-                            full_house_cards.extend(vec![*value; 2]);
-                            let full_house_subhand = SubHand {
-                                rank: Rank::FullHouse,
-                                values: full_house_cards,
-                            };
-
-                            subhands_per_rank
-                                .entry(Rank::FullHouse)
-                                .or_default()
-                                .insert(full_house_subhand);
-                        }
-                    }
-                }
-            }
+/// Evaluate an Omaha hand, where exactly 2 of the player's hole cards
+/// must be used with exactly 3 cards from the board. Boards with fewer
+/// than 3 cards (e.g., before the flop) are used as-is.
+///
+/// # Examples
+///
+/// ```
+/// use private_poker::{entities::{Card, Rank, Suit}, functional::eval_omaha};
+///
+/// // A single heart in the hand can't make a flush with the board.
+/// let hand = [
+///     Card(1, Suit::Heart),
+///     Card(8, Suit::Spade),
+///     Card(8, Suit::Diamond),
+///     Card(3, Suit::Club),
+/// ];
+/// let board = [
+///     Card(2, Suit::Heart),
+///     Card(7, Suit::Heart),
+///     Card(9, Suit::Heart),
+///     Card(13, Suit::Heart),
+///     Card(4, Suit::Club),
+/// ];
+/// assert_eq!(eval_omaha(&hand, &board).rank(), Rank::OnePair);
+/// ```
+pub fn eval_omaha(hand: &[Card], board: &[Card]) -> HandValue {
+    let num_board_cards = board.len().min(3);
+    let mut best_hand_value = None;
+    for hole_cards in combinations(hand, hand.len().min(2)) {
+        for board_cards in combinations(board, num_board_cards) {
+            let hand_value = eval_cards(hole_cards.iter().chain(&board_cards).copied(), false);
+            best_hand_value = max(best_hand_value, Some(hand_value));
+        }
+    }
+    best_hand_value.unwrap_or_else(|| eval_cards(&[], false))
+}
 
-            3 => {
-                let one_pair_subhand = SubHand {
-                    rank: Rank::OnePair,
-                    values: vec![*value; 2],
-                };
-                let three_of_a_kind_subhand = SubHand {
-                    rank: Rank::ThreeOfAKind,
-                    values: vec![*value; 3],
-                };
-                subhands_per_rank
-                    .get_mut(&Rank::OnePair)
-                    .map(|one_pairs| one_pairs.remove(&one_pair_subhand));
-                subhands_per_rank
-                    .entry(Rank::ThreeOfAKind)
-                    .or_default()
-                    .insert(three_of_a_kind_subhand);
-
-                // Check if a pair also occurs, then the three of a kind
-                // and the pair make a full house.
-                if let Some(one_pairs) = subhands_per_rank.get(&Rank::OnePair) {
-                    if let Some(best_one_pair) = one_pairs.iter().next_back() {
-                        // Ignore the case where a high ace and low ace get counted together
-                        // as a full house.
-                        if *value != 14 || best_one_pair.values != vec![1, 1] {
-                            let mut full_house_cards = vec![*value; 3];
-                            full_house_cards.extend(best_one_pair.values.clone());
-                            let full_house_subhand = SubHand {
-                                rank: Rank::FullHouse,
-                                values: full_house_cards,
-                            };
-                            subhands_per_rank
-                                .entry(Rank::FullHouse)
-                                .or_default()
-                                .insert(full_house_subhand);
-                        }
-                    }
-                }
+/// Return all combinations of `k` cards, where `k` is at most 3.
+fn combinations(cards: &[Card], k: usize) -> Vec<Vec<&Card>> {
+    let n = cards.len();
+    match k {
+        0 => vec![vec![]],
+        1 => cards.iter().map(|card| vec![card]).collect(),
+        2 => (0..n)
+            .flat_map(|i| (i + 1..n).map(move |j| vec![&cards[i], &cards[j]]))
+            .collect(),
+        3 => (0..n)
+            .flat_map(|i| {
+                (i + 1..n).flat_map(move |j| {
+                    (j + 1..n).map(move |k| vec![&cards[i], &cards[j], &cards[k]])
+                })
+            })
+            .collect(),
+        _ => unreachable!("only combinations of up to 3 cards are needed"),
+    }
+}
 
-                // Check if another three of a kind occurs, then both three
-                // of a kinds make a full house.
-                if let Some(three_of_a_kinds) = subhands_per_rank.get(&Rank::ThreeOfAKind) {
-                    if let Some(next_best_three_of_a_kind) = three_of_a_kinds.iter().nth_back(1) {
-                        // Ignore the case where a high ace and low ace get counted together
-                        // as a full house.
-                        if *value != 14 || next_best_three_of_a_kind.values != vec![1, 1, 1] {
-                            let mut full_house_cards = vec![*value; 3];
-                            full_house_cards.extend(vec![next_best_three_of_a_kind.values[0]; 2]);
-                            let full_house_subhand = SubHand {
-                                rank: Rank::FullHouse,
-                                values: full_house_cards,
-                            };
-                            subhands_per_rank
-                                .entry(Rank::FullHouse)
-                                .or_default()
-                                .insert(full_house_subhand);
-                        }
-                    }
-                }
-            }
+/// Evaluate cards represented as a bitmask of card values for each suit,
+/// where the bit at index `value` is set if the card is in the hand. Low
+/// aces that are paired with a high ace of the same suit are treated as
+/// the same card and only count towards straights. Short-deck hands are
+/// ranked by short-deck rules.
+fn eval_suit_masks(suit_masks: &[u16; 4], short_deck: bool) -> HandValue {
+    let new_hand = if short_deck {
+        HandValue::new_short_deck
+    } else {
+        HandValue::new
+    };
+    let mut value_counts = [0u8; 15];
+    // Values of all cards, including low aces that duplicate high aces.
+    let mut straight_mask = 0;
+    // Values of all cards, excluding low aces that duplicate high aces.
+    let mut values_mask = 0;
+    let mut best_hand: Option<HandValue> = None;
+    for suit_mask in suit_masks.iter().map(|suit_mask| suit_mask & VALUES_MASK) {
+        straight_mask |= suit_mask;
+        let card_mask = if suit_mask & ACE_HIGH != 0 {
+            suit_mask & !ACE_LOW
+        } else {
+            suit_mask
+        };
+        values_mask |= card_mask;
+        for value in mask_to_values(card_mask) {
+            value_counts[value as usize] += 1;
+        }
 
-            4 => {
-                let three_of_a_kind_subhand = SubHand {
-                    rank: Rank::ThreeOfAKind,
-                    values: vec![*value; 3],
-                };
-                let four_of_a_kind_subhand = SubHand {
-                    rank: Rank::FourOfAKind,
-                    values: vec![*value; 4],
-                };
-                subhands_per_rank
-                    .get_mut(&Rank::ThreeOfAKind)
-                    .map(|three_of_a_kinds| three_of_a_kinds.remove(&three_of_a_kind_subhand));
-                subhands_per_rank
-                    .entry(Rank::FourOfAKind)
-                    .or_default()
-                    .insert(four_of_a_kind_subhand);
-
-                // You can't get a four of a kind and a straight flush
-                // in the same round for any individual player.
-                break;
-            }
+        // A straight flush is the best possible hand, but a flush can
+        // still lose to a four of a kind, or a full house outside of a
+        // short deck.
+        if let Some(high_value) = find_straight(suit_mask, short_deck) {
+            let hand = new_hand(Rank::StraightFlush, &[high_value]);
+            best_hand = max(best_hand, Some(hand));
+        } else if card_mask.count_ones() >= 5 {
+            let hand = new_hand(Rank::Flush, &values_with_kickers(&[], card_mask, 5));
+            best_hand = max(best_hand, Some(hand));
+        }
+    }
+    if let Some(hand) = best_hand.filter(|hand| hand.rank() == Rank::StraightFlush) {
+        return hand;
+    }
 
-            _ => unreachable!("cheater"),
+    // The two values that appear the most, breaking ties with the
+    // values themselves.
+    let (mut best_count, mut best_value) = (0, 0);
+    let (mut next_best_count, mut next_best_value) = (0, 0);
+    for value in mask_to_values(values_mask) {
+        let count = value_counts[value as usize];
+        if count > best_count {
+            (next_best_count, next_best_value) = (best_count, best_value);
+            (best_count, best_value) = (count, value);
+        } else if count > next_best_count {
+            (next_best_count, next_best_value) = (count, value);
         }
     }
 
-    // Move subhands according to rank to the temporary hands heap.
-    // Can only keep the best subhand for each except for high cards.
-    // There can be up to 5 high cards in the final hand.
-    while let Some((rank, mut subhands)) = subhands_per_rank.pop_last() {
-        if let Some(best_subhand) = hands.peek() {
-            if best_subhand.rank >= Rank::Straight {
-                break;
+    match (best_count, next_best_count) {
+        (4, _) => new_hand(
+            Rank::FourOfAKind,
+            &values_with_kickers(&[best_value], values_mask, 1),
+        ),
+        // Whether a flush beats a full house depends on the deck.
+        (3, 2..) => max(
+            best_hand,
+            Some(new_hand(Rank::FullHouse, &[best_value, next_best_value])),
+        )
+        .expect("there's a full house"),
+        _ => {
+            if let Some(hand) = best_hand {
+                return hand;
             }
-        }
-        if rank == Rank::HighCard {
-            while let Some(best_subhand) = subhands.pop_last() {
-                hands.push(best_subhand);
+            if let Some(high_value) = find_straight(straight_mask, short_deck) {
+                return new_hand(Rank::Straight, &[high_value]);
+            }
+            match (best_count, next_best_count) {
+                (3, _) => new_hand(
+                    Rank::ThreeOfAKind,
+                    &values_with_kickers(&[best_value], values_mask, 2),
+                ),
+                (2, 2) => new_hand(
+                    Rank::TwoPair,
+                    &values_with_kickers(&[best_value, next_best_value], values_mask, 1),
+                ),
+                (2, _) => new_hand(
+                    Rank::OnePair,
+                    &values_with_kickers(&[best_value], values_mask, 3),
+                ),
+                _ => new_hand(Rank::HighCard, &values_with_kickers(&[], values_mask, 5)),
             }
-        } else if let Some(best_subhand) = subhands.pop_last() {
-            hands.push(best_subhand);
         }
     }
+}
+
+/// Return the high card value of the best straight within a bitmask of
+/// card values. Short-deck aces also play as 5s.
+fn find_straight(values_mask: u16, short_deck: bool) -> Option<Value> {
+    let values_mask = if short_deck && values_mask & ACE_HIGH != 0 {
+        values_mask | SHORT_DECK_ACE_LOW
+    } else {
+        values_mask
+    };
+    (5..=14).rev().find(|high_value| {
+        let straight_mask = 0b11111 << (high_value - 4);
+        values_mask & straight_mask == straight_mask
+    })
+}
+
+/// Return card values within a bitmask of card values from highest to
+/// lowest.
+fn mask_to_values(mut values_mask: u16) -> impl Iterator<Item = Value> {
+    iter::from_fn(move || {
+        (values_mask != 0).then(|| {
+            let value = (u16::BITS - 1 - values_mask.leading_zeros()) as Value;
+            values_mask &= !(1 << value);
+            value
+        })
+    })
+}
+
+/// Return the given values followed by the highest remaining values
+/// within a bitmask of card values that can be used as kickers.
+fn values_with_kickers(values: &[Value], values_mask: u16, num_kickers: usize) -> [Value; 5] {
+    let mut kickers_mask = values_mask;
+    for value in values.iter() {
+        kickers_mask &= !(1 << value);
+    }
+    let mut hand_values = [0; 5];
+    for (hand_value, value) in hand_values.iter_mut().zip(
+        values
+            .iter()
+            .copied()
+            .chain(mask_to_values(kickers_mask).take(num_kickers)),
+    ) {
+        *hand_value = value;
+    }
+    hand_values
+}
 
-    // Now convert the binary heap to a vector containing the best
-    // hand. Do this by popping from the binary heap until we get
-    // the 5 best cards in our hand to construct the best hand.
-    let mut cards_in_hand: HashSet<Value> = HashSet::with_capacity(5);
-    let mut num_cards: usize = 0;
-    let mut hand: Vec<SubHand> = Vec::with_capacity(5);
-    while let Some(subhand) = hands.pop() {
-        if hand.is_empty()
-            || (subhand.rank == Rank::HighCard && !cards_in_hand.contains(&subhand.values[0]))
-        {
-            num_cards += subhand.values.len();
-            cards_in_hand.extend(subhand.values.clone());
-            hand.push(subhand);
-        }
-        if let Some(best_subhand) = hand.first() {
-            if best_subhand.rank >= Rank::Straight || num_cards >= 5 {
-                break;
-            }
-        }
+fn suit_to_idx(suit: &Suit) -> Option<usize> {
+    match suit {
+        Suit::Club => Some(0),
+        Suit::Spade => Some(1),
+        Suit::Diamond => Some(2),
+        Suit::Heart => Some(3),
+        Suit::Wild => None,
     }
-    hand
 }
 
 /// Create a new, unshuffled deck of cards.
@@ -371,6 +386,48 @@ pub fn new_deck() -> [Card; 52] {
     deck
 }
 
+/// Create a new, unshuffled short deck, which is a deck without any 2s
+/// through 5s.
+pub fn new_short_deck() -> [Card; 36] {
+    let mut deck: [Card; 36] = [Card(0, Suit::Wild); 36];
+    for (i, card) in new_deck()
+        .into_iter()
+        .filter(|Card(value, _)| !(2..=5).contains(value))
+        .enumerate()
+    {
+        deck[i] = card;
+    }
+    deck
+}
+
+/// Parse whitespace-separated cards (e.g., "As Kd 7h 7c 2s") in the
+/// order they're written. See `Card`'s `FromStr` implementation for the
+/// notation. Cards can't be repeated since they're all from the same
+/// deck.
+///
+/// # Examples
+///
+/// ```
+/// use private_poker::{entities::{Card, Suit}, functional::parse_cards};
+///
+/// let cards = parse_cards("As Td 7h").unwrap();
+/// assert_eq!(cards, vec![Card(1, Suit::Spade), Card(10, Suit::Diamond), Card(7, Suit::Heart)]);
+/// assert!(parse_cards("As as").is_err());
+/// ```
+pub fn parse_cards(s: &str) -> Result<Vec<Card>, CardParseError> {
+    let mut seen = HashSet::new();
+    s.split_ascii_whitespace()
+        .map(|repr| {
+            let card: Card = repr.parse()?;
+            if seen.insert((card.0, card.1)) {
+                Ok(card)
+            } else {
+                Err(CardParseError::Duplicate(repr.to_string()))
+            }
+        })
+        .collect()
+}
+
 /// Prepare a hand for evaluation by sorting it and adding high
 /// aces to it so aces can be treated as 1s in addition to 14s.
 ///
@@ -395,8 +452,603 @@ pub fn prepare_hand(cards: &mut Vec<Card>) {
 
 #[cfg(test)]
 mod tests {
-    use super::{argmax, eval};
-    use crate::game::entities::{Card, Rank, SubHand, Suit};
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+    use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
+
+    use super::{
+        argmax, eval, eval_7, eval_omaha, eval_short_deck, new_deck, new_short_deck, parse_cards,
+        prepare_hand,
+    };
+    use crate::game::entities::{Card, CardParseError, HandValue, Rank, SubHand, Suit, Value};
+
+    /// The original, allocating evaluation that `eval` used before it
+    /// was implemented with hand values. Kept around as a reference to
+    /// make sure hand values are ordered the same way.
+    fn legacy_eval(cards: &[Card]) -> Vec<SubHand> {
+        // Mapping of suit to (sorted) cards within that suit.
+        // Used for tracking whether there's a flush or straight flush.
+        let mut values_per_suit: HashMap<Suit, Vec<Value>> = HashMap::new();
+
+        // Used for tracking whether there's a straight.
+        let mut straight_count: usize = 0;
+        let mut straight_prev_value: Value = 0;
+
+        // Mapping of rank to each subhand for that rank. Helps track
+        // the highest subhand in each rank.
+        let mut subhands_per_rank: BTreeMap<Rank, BTreeSet<SubHand>> = BTreeMap::new();
+        // Count number of times a card value appears. Helps track one pair,
+        // two pair, etc.
+        let mut value_counts: HashMap<Value, usize> = HashMap::new();
+
+        // Loop through cards in hand assuming the hand is sorted
+        // and that each ace appears in the hand twice (at the low
+        // end with a value of 1 and at the high end with a value
+        // of 14). We push hands into a binary heap so we can
+        // easily get the best hand at the end.
+        let mut hands: BinaryHeap<SubHand> = BinaryHeap::new();
+        for Card(value, suit) in cards.iter() {
+            // Keep a count of cards for each suit. If the suit count
+            // reaches a flush, it's also checked for a straight
+            // for the straight flush potential.
+            let values_in_suit = values_per_suit.entry(*suit).or_default();
+            values_in_suit.push(*value);
+
+            // Since aces appear in the cards twice, we need to make sure
+            // they aren't counted twice for the flush. To get around this,
+            // we just subtract one from the flush count in the case of the
+            // high valued ace.
+            let mut flush_count = values_in_suit.len();
+            if *value == 14 {
+                flush_count -= 1;
+            }
+
+            // A flush was found.
+            if flush_count >= 5 {
+                let maybe_straight_flush_start_idx = values_in_suit.len() - 5;
+                let maybe_straight_flush_cards = &values_in_suit[maybe_straight_flush_start_idx..];
+                let mut is_straight_flush = true;
+                for flush_idx in 0..4 {
+                    if (maybe_straight_flush_cards[flush_idx] + 1)
+                        != maybe_straight_flush_cards[flush_idx + 1]
+                    {
+                        is_straight_flush = false;
+                        break;
+                    }
+                }
+
+                let values = maybe_straight_flush_cards.iter().rev().copied().collect();
+                if is_straight_flush {
+                    hands.push(SubHand {
+                        rank: Rank::StraightFlush,
+                        values,
+                    })
+                } else {
+                    hands.push(SubHand {
+                        rank: Rank::Flush,
+                        values,
+                    })
+                }
+            }
+
+            // Keep a count of cards that're in sequential order to check for
+            // a straight. If the same value appears again, we can keep the
+            // straight count the same and don't have to reset.
+            if (straight_prev_value + 1) == *value {
+                straight_count += 1;
+            } else if straight_prev_value == *value {
+            } else {
+                straight_count = 1;
+            }
+
+            // A straight was found.
+            straight_prev_value = *value;
+            if straight_count >= 5 {
+                let straight_subhand = SubHand {
+                    rank: Rank::Straight,
+                    values: (*value - 4..=*value).rev().collect(),
+                };
+                // We don't need to push the straight into the heap if something
+                // better was already found.
+                let best_subhand = hands.peek();
+                match best_subhand {
+                    None => hands.push(straight_subhand),
+                    Some(subhand) => {
+                        if *subhand < straight_subhand {
+                            hands.push(straight_subhand);
+                        }
+                    }
+                }
+            }
+
+            // Now start checking for hands besides straights and flushes.
+            let value_count = value_counts.entry(*value).or_insert(0);
+            *value_count += 1;
+
+            match *value_count {
+                1 => {
+                    let high_card_subhand = SubHand {
+                        rank: Rank::HighCard,
+                        values: vec![*value],
+                    };
+                    subhands_per_rank
+                        .entry(Rank::HighCard)
+                        .or_default()
+                        .insert(high_card_subhand);
+                }
+
+                2 => {
+                    let one_pair_subhand = SubHand {
+                        rank: Rank::OnePair,
+                        values: vec![*value; 2],
+                    };
+                    let one_pairs = subhands_per_rank.entry(Rank::OnePair).or_default();
+                    one_pairs.insert(one_pair_subhand);
+
+                    // Check if a pair also occurs, then both pairs
+                    // make a two pair.
+                    if let Some(next_best_one_pair) = one_pairs.iter().nth_back(1) {
+                        // Ignore the case where a high ace and low ace get counted together
+                        // as a two pair.
+                        if *value != 14 || next_best_one_pair.values != vec![1, 1] {
+                            let mut two_pair_cards = vec![*value; 2];
+                            two_pair_cards.extend(next_best_one_pair.values.clone());
+                            let two_pair_subhand = SubHand {
+                                rank: Rank::TwoPair,
+                                values: two_pair_cards,
+                            };
+                            subhands_per_rank
+                                .entry(Rank::TwoPair)
+                                .or_default()
+                                .insert(two_pair_subhand);
+                        }
+                    }
+
+                    // Check if a three of a kind also occurs, then the pair
+                    // and three of a kind make a full house.
+                    if let Some(three_of_a_kinds) = subhands_per_rank.get(&Rank::ThreeOfAKind) {
+                        if let Some(best_three_of_a_kind) = three_of_a_kinds.iter().next() {
+                            // Ignore the case where a high ace and low ace get counted together
+                            // as a full house.
+                            if *value != 14 || best_three_of_a_kind.values != vec![1, 1, 1] {
+                                let mut full_house_cards = best_three_of_a_kind.values.clone();
+                                full_house_cards.extend(vec![*value; 2]);
+                                let full_house_subhand = SubHand {
+                                    rank: Rank::FullHouse,
+                                    values: full_house_cards,
+                                };
+
+                                subhands_per_rank
+                                    .entry(Rank::FullHouse)
+                                    .or_default()
+                                    .insert(full_house_subhand);
+                            }
+                        }
+                    }
+                }
+
+                3 => {
+                    let one_pair_subhand = SubHand {
+                        rank: Rank::OnePair,
+                        values: vec![*value; 2],
+                    };
+                    let three_of_a_kind_subhand = SubHand {
+                        rank: Rank::ThreeOfAKind,
+                        values: vec![*value; 3],
+                    };
+                    subhands_per_rank
+                        .get_mut(&Rank::OnePair)
+                        .map(|one_pairs| one_pairs.remove(&one_pair_subhand));
+                    subhands_per_rank
+                        .entry(Rank::ThreeOfAKind)
+                        .or_default()
+                        .insert(three_of_a_kind_subhand);
+
+                    // Check if a pair also occurs, then the three of a kind
+                    // and the pair make a full house.
+                    if let Some(one_pairs) = subhands_per_rank.get(&Rank::OnePair) {
+                        if let Some(best_one_pair) = one_pairs.iter().next_back() {
+                            // Ignore the case where a high ace and low ace get counted together
+                            // as a full house.
+                            if *value != 14 || best_one_pair.values != vec![1, 1] {
+                                let mut full_house_cards = vec![*value; 3];
+                                full_house_cards.extend(best_one_pair.values.clone());
+                                let full_house_subhand = SubHand {
+                                    rank: Rank::FullHouse,
+                                    values: full_house_cards,
+                                };
+                                subhands_per_rank
+                                    .entry(Rank::FullHouse)
+                                    .or_default()
+                                    .insert(full_house_subhand);
+                            }
+                        }
+                    }
+
+                    // Check if another three of a kind occurs, then both three
+                    // of a kinds make a full house.
+                    if let Some(three_of_a_kinds) = subhands_per_rank.get(&Rank::ThreeOfAKind) {
+                        if let Some(next_best_three_of_a_kind) = three_of_a_kinds.iter().nth_back(1)
+                        {
+                            // Ignore the case where a high ace and low ace get counted together
+                            // as a full house.
+                            if *value != 14 || next_best_three_of_a_kind.values != vec![1, 1, 1] {
+                                let mut full_house_cards = vec![*value; 3];
+                                full_house_cards
+                                    .extend(vec![next_best_three_of_a_kind.values[0]; 2]);
+                                let full_house_subhand = SubHand {
+                                    rank: Rank::FullHouse,
+                                    values: full_house_cards,
+                                };
+                                subhands_per_rank
+                                    .entry(Rank::FullHouse)
+                                    .or_default()
+                                    .insert(full_house_subhand);
+                            }
+                        }
+                    }
+                }
+
+                4 => {
+                    let three_of_a_kind_subhand = SubHand {
+                        rank: Rank::ThreeOfAKind,
+                        values: vec![*value; 3],
+                    };
+                    let four_of_a_kind_subhand = SubHand {
+                        rank: Rank::FourOfAKind,
+                        values: vec![*value; 4],
+                    };
+                    subhands_per_rank
+                        .get_mut(&Rank::ThreeOfAKind)
+                        .map(|three_of_a_kinds| three_of_a_kinds.remove(&three_of_a_kind_subhand));
+                    subhands_per_rank
+                        .entry(Rank::FourOfAKind)
+                        .or_default()
+                        .insert(four_of_a_kind_subhand);
+
+                    // You can't get a four of a kind and a straight flush
+                    // in the same round for any individual player.
+                    break;
+                }
+
+                _ => unreachable!("cheater"),
+            }
+        }
+
+        // Move subhands according to rank to the temporary hands heap.
+        // Can only keep the best subhand for each except for high cards.
+        // There can be up to 5 high cards in the final hand.
+        while let Some((rank, mut subhands)) = subhands_per_rank.pop_last() {
+            if let Some(best_subhand) = hands.peek() {
+                if best_subhand.rank >= Rank::Straight {
+                    break;
+                }
+            }
+            if rank == Rank::HighCard {
+                while let Some(best_subhand) = subhands.pop_last() {
+                    hands.push(best_subhand);
+                }
+            } else if let Some(best_subhand) = subhands.pop_last() {
+                hands.push(best_subhand);
+            }
+        }
+
+        // Now convert the binary heap to a vector containing the best
+        // hand. Do this by popping from the binary heap until we get
+        // the 5 best cards in our hand to construct the best hand.
+        let mut cards_in_hand: HashSet<Value> = HashSet::with_capacity(5);
+        let mut num_cards: usize = 0;
+        let mut hand: Vec<SubHand> = Vec::with_capacity(5);
+        while let Some(subhand) = hands.pop() {
+            if hand.is_empty()
+                || (subhand.rank == Rank::HighCard && !cards_in_hand.contains(&subhand.values[0]))
+            {
+                num_cards += subhand.values.len();
+                cards_in_hand.extend(subhand.values.clone());
+                hand.push(subhand);
+            }
+            if let Some(best_subhand) = hand.first() {
+                if best_subhand.rank >= Rank::Straight || num_cards >= 5 {
+                    break;
+                }
+            }
+        }
+        hand
+    }
+
+    #[test]
+    fn eval_7_agrees_with_legacy_eval() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut deck = new_deck();
+        for _ in 0..100_000 {
+            deck.shuffle(&mut rng);
+            // Two hands that share the same board.
+            let board = &deck[..5];
+            let mut hands = [[Card(0, Suit::Wild); 7]; 2];
+            let mut legacy_hands = Vec::with_capacity(2);
+            for (hand_idx, hand) in hands.iter_mut().enumerate() {
+                hand[..5].copy_from_slice(board);
+                hand[5..].copy_from_slice(&deck[5 + 2 * hand_idx..7 + 2 * hand_idx]);
+                let mut cards = hand.to_vec();
+                prepare_hand(&mut cards);
+                legacy_hands.push(legacy_eval(&cards));
+            }
+            // The legacy evaluation stops looking at cards once it finds a
+            // four of a kind, so its kickers (and four aces, which are found
+            // as low aces first) are incorrect.
+            if legacy_hands
+                .iter()
+                .any(|hand| hand[0].rank == Rank::FourOfAKind)
+            {
+                continue;
+            }
+            let hand_value1 = eval_7(&hands[0]);
+            let hand_value2 = eval_7(&hands[1]);
+            assert_eq!(
+                hand_value1.cmp(&hand_value2),
+                legacy_hands[0].cmp(&legacy_hands[1]),
+                "{hands:?}"
+            );
+            assert_eq!(Vec::<SubHand>::from(hand_value1), legacy_hands[0]);
+        }
+    }
+
+    #[test]
+    fn eval_7_four_of_a_kind() {
+        let board = [
+            Card(1, Suit::Club),
+            Card(1, Suit::Diamond),
+            Card(13, Suit::Club),
+            Card(13, Suit::Diamond),
+            Card(4, Suit::Heart),
+        ];
+        let mut aces = [Card(1, Suit::Heart); 7];
+        aces[..5].copy_from_slice(&board);
+        aces[6] = Card(1, Suit::Spade);
+        let mut kings = [Card(13, Suit::Heart); 7];
+        kings[..5].copy_from_slice(&board);
+        kings[6] = Card(13, Suit::Spade);
+        let aces = eval_7(&aces);
+        let kings = eval_7(&kings);
+        assert_eq!(aces, HandValue::new(Rank::FourOfAKind, &[14, 13]));
+        assert_eq!(kings, HandValue::new(Rank::FourOfAKind, &[13, 14]));
+        assert_eq!(argmax(&[kings, aces]), vec![1]);
+    }
+
+    #[test]
+    fn eval_7_wheel() {
+        let cards = [
+            Card(1, Suit::Club),
+            Card(2, Suit::Diamond),
+            Card(3, Suit::Club),
+            Card(4, Suit::Heart),
+            Card(5, Suit::Spade),
+            Card(9, Suit::Spade),
+            Card(13, Suit::Club),
+        ];
+        let hand_value = eval_7(&cards);
+        assert_eq!(hand_value, HandValue::new(Rank::Straight, &[5]));
+        assert_eq!(
+            Vec::<SubHand>::from(hand_value),
+            vec![SubHand {
+                rank: Rank::Straight,
+                values: vec![5, 4, 3, 2, 1]
+            }]
+        );
+    }
+
+    #[test]
+    fn eval_omaha_uses_exactly_2_hole_cards() {
+        // Hold'em would make an ace-high flush with the ace of hearts and
+        // the board's four hearts, but Omaha needs 2 hearts in the hand.
+        let mut hand = parse_cards("Ah 8s 8d 3c").unwrap();
+        let board = parse_cards("2h 7h 9h Kh 4c").unwrap();
+        let mut cards = [Card(0, Suit::Wild); 7];
+        cards[..5].copy_from_slice(&board);
+        cards[5..].copy_from_slice(&hand[..2]);
+        assert_eq!(eval_7(&cards).rank(), Rank::Flush);
+        assert_eq!(
+            eval_omaha(&hand, &board),
+            HandValue::new(Rank::OnePair, &[8, 13, 9, 7])
+        );
+
+        // Only 3 of the board's 4 nines can be used, so the best hand is
+        // trips with the hand's 2 best cards as kickers.
+        let board = parse_cards("9c 9d 9h 9s 2d").unwrap();
+        hand[1] = "Kd".parse().unwrap();
+        hand[2] = "Qc".parse().unwrap();
+        cards[..5].copy_from_slice(&board);
+        cards[5..].copy_from_slice(&hand[..2]);
+        assert_eq!(eval_7(&cards), HandValue::new(Rank::FourOfAKind, &[9, 14]));
+        assert_eq!(
+            eval_omaha(&hand, &board),
+            HandValue::new(Rank::ThreeOfAKind, &[9, 14, 13])
+        );
+    }
+
+    #[test]
+    fn eval_omaha_is_never_better_than_holdem() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut deck = new_deck();
+        for _ in 0..10_000 {
+            deck.shuffle(&mut rng);
+            // Every Omaha hand is also a hand with 2 of the hole cards
+            // and the whole board.
+            let (board, hand) = (&deck[..5], &deck[5..9]);
+            let mut cards = [Card(0, Suit::Wild); 7];
+            cards[..5].copy_from_slice(board);
+            let mut holdem = None;
+            for i in 0..4 {
+                for j in i + 1..4 {
+                    cards[5] = hand[i];
+                    cards[6] = hand[j];
+                    holdem = holdem.max(Some(eval_7(&cards)));
+                }
+            }
+            assert!(
+                Some(eval_omaha(hand, board)) <= holdem,
+                "{hand:?} {board:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn eval_short_deck_flush_beats_full_house() {
+        let board = parse_cards("9h 9c 7h Qh 6s").unwrap();
+        let flush_cards = parse_cards("Ah Th").unwrap();
+        let full_house_cards = parse_cards("9d 6c").unwrap();
+        let flush = eval_short_deck(&[&board[..], &flush_cards].concat());
+        let full_house = eval_short_deck(&[&board[..], &full_house_cards].concat());
+        assert_eq!(flush.rank(), Rank::Flush);
+        assert_eq!(flush.values().collect::<Vec<_>>(), vec![14, 12, 10, 9, 7]);
+        assert_eq!(full_house.rank(), Rank::FullHouse);
+
+        // Hold'em ranks the full house higher, but a short deck has fewer
+        // cards of each suit, so flushes are harder to make.
+        assert_eq!(argmax(&[flush, full_house]), vec![0]);
+        assert!(HandValue::new(Rank::Flush, &[14]) < HandValue::new(Rank::FullHouse, &[6, 7]));
+
+        // A hand with both plays as a flush.
+        let both = eval_short_deck(&[&board[..], &flush_cards, &full_house_cards].concat());
+        assert_eq!(both, flush);
+
+        // Both still lose to four of a kind.
+        let quads = HandValue::new_short_deck(Rank::FourOfAKind, &[6, 7]);
+        assert_eq!(argmax(&[flush, quads, full_house]), vec![1]);
+    }
+
+    #[test]
+    fn eval_short_deck_ace_plays_low_in_a6789() {
+        let cards = [
+            Card(1, Suit::Club),
+            Card(6, Suit::Diamond),
+            Card(7, Suit::Club),
+            Card(8, Suit::Heart),
+            Card(9, Suit::Spade),
+            Card(12, Suit::Spade),
+            Card(13, Suit::Club),
+        ];
+        let wheel = eval_short_deck(&cards);
+        assert_eq!(wheel, HandValue::new_short_deck(Rank::Straight, &[9]));
+
+        // It's the lowest straight, and it doesn't exist outside of a
+        // short deck.
+        let six_to_ten = eval_short_deck(&[
+            Card(6, Suit::Diamond),
+            Card(7, Suit::Club),
+            Card(8, Suit::Heart),
+            Card(9, Suit::Spade),
+            Card(10, Suit::Spade),
+        ]);
+        assert!(wheel < six_to_ten);
+        assert_eq!(eval_7(&cards).rank(), Rank::HighCard);
+
+        // Suited, it's a straight flush.
+        let suited = eval_short_deck(&[
+            Card(1, Suit::Heart),
+            Card(6, Suit::Heart),
+            Card(7, Suit::Heart),
+            Card(8, Suit::Heart),
+            Card(9, Suit::Heart),
+        ]);
+        assert_eq!(suited, HandValue::new(Rank::StraightFlush, &[9]));
+    }
+
+    #[test]
+    fn cards_parse_from_their_notation() {
+        for card in new_deck() {
+            // Cards parse from how they're displayed, which pads them.
+            assert_eq!(card.to_string().parse(), Ok(card));
+            let notation = format!("{}{}", card.rank_repr().replace("10", "T"), card.1);
+            assert_eq!(notation.parse(), Ok(card));
+            assert_eq!(notation.to_lowercase().parse(), Ok(card));
+            assert_eq!(notation.to_uppercase().parse(), Ok(card));
+        }
+        assert_eq!("10s".parse(), Ok(Card(10, Suit::Spade)));
+        assert_eq!("T/s".parse(), Ok(Card(10, Suit::Spade)));
+        // Aces are valued low no matter how they were valued before.
+        assert_eq!(
+            Card(14, Suit::Club).to_string().parse(),
+            Ok(Card(1, Suit::Club))
+        );
+        // Cards come off the wire, so they don't always have real ranks.
+        assert_eq!(Card(200, Suit::Club).rank_repr(), "?");
+        assert_eq!(Card(200, Suit::Club).to_string(), " ?/c");
+
+        let parse = |s: &str| s.parse::<Card>().unwrap_err();
+        for s in ["", "s", "/s", "A"] {
+            assert_eq!(parse(s), CardParseError::Malformed(s.to_string()), "{s:?}");
+        }
+        assert_eq!(parse("A/"), CardParseError::InvalidSuit("A/".to_string()));
+        assert_eq!(parse("As/"), CardParseError::InvalidSuit("As/".to_string()));
+        assert_eq!(parse("Aw"), CardParseError::InvalidSuit("Aw".to_string()));
+        assert_eq!(parse("A♠"), CardParseError::InvalidSuit("A♠".to_string()));
+        for rank in ["0", "1", "11", "14", "B", "AA", "A//", "1O", "♠"] {
+            let s = format!("{rank}s");
+            assert_eq!(parse(&s), CardParseError::InvalidRank(s), "{rank:?}");
+        }
+    }
+
+    #[test]
+    fn parse_cards_from_whitespace_separated_notation() {
+        let board = vec![
+            Card(1, Suit::Spade),
+            Card(13, Suit::Diamond),
+            Card(7, Suit::Heart),
+            Card(7, Suit::Club),
+            Card(2, Suit::Spade),
+        ];
+        for s in [
+            "As Kd 7h 7c 2s",
+            "  As   Kd 7h\t7c\n2s\n",
+            "as kd 7h 7c 2s",
+            "A/s K/d 7/h 7/c 2/s",
+        ] {
+            assert_eq!(parse_cards(s), Ok(board.clone()), "{s:?}");
+        }
+        // Cards display in a way that parses back.
+        let displayed: Vec<String> = board.iter().map(ToString::to_string).collect();
+        assert_eq!(parse_cards(&displayed.join(" ")), Ok(board));
+        assert_eq!(parse_cards(""), Ok(vec![]));
+        assert_eq!(parse_cards(" \t\n"), Ok(vec![]));
+
+        // The first bad card is the one that's reported.
+        assert_eq!(
+            parse_cards("As Xd 7z"),
+            Err(CardParseError::InvalidRank("Xd".to_string()))
+        );
+        assert_eq!(
+            parse_cards("As,Kd"),
+            Err(CardParseError::InvalidRank("As,Kd".to_string()))
+        );
+
+        // A full board can't have the same card twice, however it's
+        // written.
+        assert_eq!(
+            parse_cards("As Kd 7h 7c As"),
+            Err(CardParseError::Duplicate("As".to_string()))
+        );
+        assert_eq!(
+            parse_cards("Td 7h 10d"),
+            Err(CardParseError::Duplicate("10d".to_string()))
+        );
+        assert_eq!(
+            parse_cards("as 2c AS"),
+            Err(CardParseError::Duplicate("AS".to_string()))
+        );
+        assert_eq!(parse_cards("As Ah Ad Ac").map(|cards| cards.len()), Ok(4));
+        assert_eq!(
+            parse_cards(&new_deck().map(|card| card.to_string()).join(" ")),
+            Ok(new_deck().to_vec())
+        );
+    }
+
+    #[test]
+    fn short_deck_has_no_2s_through_5s() {
+        let deck = new_short_deck();
+        let unique: BTreeSet<Card> = deck.iter().copied().collect();
+        assert_eq!(unique.len(), 36);
+        assert!(deck.iter().all(|Card(value, _)| *value == 1 || *value >= 6));
+    }
 
     struct TestHand {
         expected_best_subhand: SubHand,
@@ -0,0 +1,335 @@
+//! Headless self-play for balance testing.
+//!
+//! Simulations drive a [`PokerState`] directly, the same way the server
+//! does, but without sockets, timeouts, or sleeps. Every seat is played by
+//! a [`Policy`], and a [`SimReport`] of aggregate statistics is collected
+//! along the way. Seeding the game's settings and every policy makes a
+//! simulation replay exactly.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    time::Instant,
+};
+
+use super::{
+    entities::{Action, DecisionContext, HandId, PlayerState, Rank, Usd, Usdf, Username},
+    eval_player_hand, GameSettings, PokerState, UserError,
+};
+
+/// Chooses actions for a simulated player.
+pub trait Policy: fmt::Debug + Send {
+    /// Choose one of the player's action options, or return `None` to let
+    /// their turn run out, so the game checks or folds for them like it
+    /// does for users that don't act in time.
+    fn choose(
+        &mut self,
+        context: &DecisionContext,
+        action_options: &HashSet<Action>,
+    ) -> Option<Action>;
+}
+
+/// Picks uniformly from the action options, raising by a random amount
+/// between the min and max raise, and occasionally lets its turn run out.
+#[derive(Debug)]
+pub struct RandomPolicy {
+    rng: StdRng,
+}
+
+impl RandomPolicy {
+    /// One in this many turns runs out.
+    const TIMEOUT_ODDS: u32 = 50;
+
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Policy for RandomPolicy {
+    fn choose(
+        &mut self,
+        context: &DecisionContext,
+        action_options: &HashSet<Action>,
+    ) -> Option<Action> {
+        if self.rng.gen_ratio(1, Self::TIMEOUT_ODDS) {
+            return None;
+        }
+        // Options are sorted so the same seed always picks the same
+        // option, whatever order the set iterates in. Going all-in is
+        // only an option when there's nothing else to do but fold, or
+        // random players would be all-in most hands.
+        let mut action_options: Vec<Action> = action_options
+            .iter()
+            .filter(|action| **action != Action::AllIn || action_options.len() <= 2)
+            .cloned()
+            .collect();
+        action_options.sort_by_key(|action| -> usize { action.clone().into() });
+        let action = action_options[self.rng.gen_range(0..action_options.len())].clone();
+        match (action, context.min_raise, context.max_raise) {
+            (Action::Raise(_), Some(min_raise), Some(max_raise)) => {
+                let max_raise = max_raise.min(2 * min_raise);
+                Some(Action::Raise(self.rng.gen_range(min_raise..=max_raise)))
+            }
+            (action, ..) => Some(action),
+        }
+    }
+}
+
+/// Blinds a simulation was playing with starting at a hand.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BlindLevel {
+    pub hand_id: HandId,
+    pub small_blind: Usd,
+    pub big_blind: Usd,
+}
+
+/// Aggregate statistics from a simulation.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct SimReport {
+    /// Number of hands played to completion.
+    pub num_hands: usize,
+    pub hands_per_sec: f64,
+    /// Average size of a hand's pot, across all of its side pots.
+    pub avg_pot: f64,
+    /// Number of times a player ended a hand unable to afford the big
+    /// blind, whether they rebought or were moved to the spectators.
+    pub num_busts: usize,
+    /// Busts per hand.
+    pub bust_rate: f64,
+    /// Number of turns that ran out, leaving the game to check or fold
+    /// for the player.
+    pub num_forced_actions: usize,
+    /// Blinds at the start of the simulation and every time they changed.
+    pub blinds: Vec<BlindLevel>,
+    /// Number of pots won at a showdown with each hand rank. Split pots
+    /// count once per winner.
+    pub winning_ranks: BTreeMap<Rank, usize>,
+    /// Number of pots won without a showdown.
+    pub num_uncontested_pots: usize,
+    /// Money users have bought into the game, including starting stacks
+    /// and rebuys.
+    pub bought_in: Usd,
+    /// Money in users' stacks and the pot at the end of the simulation.
+    pub chips: Usd,
+    /// Remainders from split pots that haven't been divided yet at the end
+    /// of the simulation.
+    pub donations: Usdf,
+}
+
+/// Plays hands of self-play between policies, one policy per seat.
+#[derive(Debug)]
+pub struct Simulation {
+    state: PokerState,
+    players: Vec<(Username, Box<dyn Policy>)>,
+}
+
+impl Simulation {
+    /// Seat a player for each policy. Players opt into auto-rebuy if the
+    /// settings allow it.
+    pub fn new(settings: GameSettings, policies: Vec<Box<dyn Policy>>) -> Result<Self, UserError> {
+        let auto_rebuy = settings.auto_rebuy;
+        let mut state: PokerState = settings.into();
+        let mut players = Vec::with_capacity(policies.len());
+        for (player_idx, policy) in policies.into_iter().enumerate() {
+            let username = format!("sim{player_idx}");
+            state.new_user(&username)?;
+            state.waitlist_user(&username)?;
+            if auto_rebuy {
+                state.set_auto_rebuy(&username, true)?;
+            }
+            players.push((username, policy));
+        }
+        Ok(Self { state, players })
+    }
+
+    /// Play up to the given number of hands, stopping early if fewer than
+    /// two players can afford to play.
+    pub fn run(self, num_hands: usize) -> SimReport {
+        let Self {
+            mut state,
+            mut players,
+        } = self;
+        let mut report = SimReport::default();
+        let mut total_pot: Usd = 0;
+        let mut last_pot_hand_id = None;
+        let start = Instant::now();
+        while report.num_hands < num_hands {
+            // Any player can start the game, but players that busted
+            // without rebuying are spectating.
+            if let PokerState::Lobby(_) = state {
+                if !players
+                    .iter()
+                    .any(|(username, _)| state.init_start(username).is_ok())
+                {
+                    break;
+                }
+            }
+
+            // Stats that depend on the state being stepped out of.
+            let data = state.data();
+            let stacks: Vec<Usd> = data.players.iter().map(|p| p.user.money).collect();
+            match state {
+                PokerState::CollectBlinds(ref game) => {
+                    let (small_blind, big_blind) = (game.data.small_blind, game.data.big_blind);
+                    if report
+                        .blinds
+                        .last()
+                        .is_none_or(|level| level.big_blind != big_blind)
+                    {
+                        report.blinds.push(BlindLevel {
+                            hand_id: game.data.hand_id,
+                            small_blind,
+                            big_blind,
+                        });
+                    }
+                }
+                PokerState::ShowHands(ref game) if last_pot_hand_id != Some(game.data.hand_id) => {
+                    last_pot_hand_id = Some(game.data.hand_id);
+                    total_pot += game.data.pot.get_size();
+                }
+                PokerState::BootPlayers(ref game) => {
+                    report.num_busts += game
+                        .data
+                        .players
+                        .iter()
+                        .filter(|p| p.user.money < game.data.big_blind)
+                        .count();
+                }
+                _ => {}
+            }
+            let was_distributing = matches!(state, PokerState::DistributePot(_));
+            let was_booting = matches!(state, PokerState::BootPlayers(_));
+
+            state = state.step();
+            state.drain_events();
+
+            // Winners are whoever's stack grew when a pot was distributed.
+            // Winners of contested pots had their hands evaluated.
+            if was_distributing {
+                let data = state.data();
+                let contested = data
+                    .players
+                    .iter()
+                    .filter(|p| p.state != PlayerState::Fold)
+                    .count()
+                    > 1;
+                for (player_idx, player) in data.players.iter().enumerate() {
+                    if player.user.money <= stacks[player_idx] {
+                        continue;
+                    }
+                    if contested {
+                        let hand_value = eval_player_hand(
+                            &mut Default::default(),
+                            player_idx,
+                            player,
+                            &data.board,
+                            data.settings.variant,
+                        );
+                        *report.winning_ranks.entry(hand_value.rank()).or_default() += 1;
+                    } else {
+                        report.num_uncontested_pots += 1;
+                    }
+                }
+            }
+            if was_booting {
+                report.num_hands += 1;
+            }
+
+            // Policies act until the betting round is over or a turn runs
+            // out, at which point the next step acts for the player.
+            while let (Some(username), Some(action_options)) =
+                (state.get_next_action_username(), state.get_action_options())
+            {
+                let context = state.get_decision_context().expect("it's a user's turn");
+                let (_, policy) = players
+                    .iter_mut()
+                    .find(|(name, _)| *name == username)
+                    .expect("players are simulated");
+                let acted = policy
+                    .choose(&context, &action_options)
+                    .is_some_and(|action| state.take_action(&username, action).is_ok());
+                if !acted {
+                    report.num_forced_actions += 1;
+                    break;
+                }
+            }
+        }
+
+        let secs = start.elapsed().as_secs_f64();
+        if secs > 0.0 {
+            report.hands_per_sec = report.num_hands as f64 / secs;
+        }
+        if report.num_hands > 0 {
+            report.avg_pot = total_pot as f64 / report.num_hands as f64;
+            report.bust_rate = report.num_busts as f64 / report.num_hands as f64;
+        }
+        let data = state.data();
+        report.bought_in = data
+            .ledger
+            .values()
+            .map(|entry| entry.bought_in - entry.cashed_out)
+            .sum();
+        report.chips = data
+            .spectators
+            .values()
+            .chain(data.waitlist.iter())
+            .chain(data.players.iter().map(|p| &p.user))
+            .map(|user| user.money)
+            .sum::<Usd>()
+            + data.pot.get_size();
+        report.donations = data.donations;
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{constants::MAX_PLAYERS, GameSettings};
+
+    use super::{Policy, RandomPolicy, SimReport, Simulation};
+
+    fn run(num_players: usize, num_hands: usize, seed: u64) -> SimReport {
+        let settings = GameSettings {
+            auto_rebuy: true,
+            seed: Some(seed),
+            ..Default::default()
+        };
+        let policies = (0..num_players)
+            .map(|player_idx| {
+                Box::new(RandomPolicy::new(seed + player_idx as u64)) as Box<dyn Policy>
+            })
+            .collect();
+        Simulation::new(settings, policies).unwrap().run(num_hands)
+    }
+
+    #[test]
+    fn random_bots_play_a_thousand_hands() {
+        let report = run(6, 1000, 0);
+        assert_eq!(report.num_hands, 1000);
+        assert!(report.avg_pot > 0.0);
+        assert!(report.num_busts > 0);
+        assert!(report.num_forced_actions > 0);
+        assert!(!report.blinds.is_empty());
+        assert!(report.num_uncontested_pots > 0);
+        assert!(!report.winning_ranks.is_empty());
+
+        // Chips are never made or lost, only moved around, with
+        // remainders of split pots set aside as donations.
+        let chips = report.chips as f64 + report.donations as f64;
+        assert!((chips - report.bought_in as f64).abs() < 1.0);
+        assert!(bincode::serialize(&report).is_ok());
+    }
+
+    #[test]
+    fn seeded_simulations_replay_exactly() {
+        let mut report1 = run(MAX_PLAYERS, 100, 1);
+        let mut report2 = run(MAX_PLAYERS, 100, 1);
+        report1.hands_per_sec = 0.0;
+        report2.hands_per_sec = 0.0;
+        assert_eq!(report1, report2);
+    }
+}
@@ -1,9 +1,19 @@
+#[cfg(feature = "bots")]
+pub mod bots;
+
+#[cfg(feature = "net")]
 pub mod net;
-pub use net::{client::Client, messages, server, utils};
+#[cfg(feature = "net")]
+pub use net::{
+    client::{Client, MultiClient, Session},
+    messages, server, utils,
+};
 
 pub mod game;
 pub use game::{
-    constants::{self, DEFAULT_MAX_USERS, MAX_PLAYERS},
+    collusion,
+    constants::{self, DEFAULT_MAX_USERS, MAX_PLAYERS, MIN_PLAYERS},
     entities::{self, DEFAULT_BUY_IN, DEFAULT_MIN_BIG_BLIND, DEFAULT_MIN_SMALL_BLIND},
-    functional, GameSettings, PokerState, UserError,
+    fairness, functional, resolve_pending_action, sim, validate_action, Autostart, ConfigError,
+    GameSettings, GameSettingsBuilder, PokerState, UserError,
 };
@@ -1,16 +1,30 @@
-//! A low-level TCP poker client.
+//! A low-level TCP poker client and a higher-level session built on top
+//! of it.
 //!
-//! This client is blocking and so is primarily used as a testing utility
-//! rather than an actual poker client.
+//! Both are blocking and so are primarily used as testing utilities and
+//! for scripted play rather than as actual poker clients.
 
 use anyhow::{bail, Error};
-use std::{net::TcpStream, thread, time::Duration};
+use std::{
+    collections::{HashSet, VecDeque},
+    io::{self, ErrorKind},
+    net::{Shutdown, TcpStream},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
 
-use crate::game::{entities::Action, UserError};
+use crate::game::{
+    entities::{Action, GameEvent, HandId, Profile, SeatIndex, TurnId, Usd, Username},
+    UserError,
+};
 
 use super::{
-    messages::{ClientError, ClientMessage, GameView, ServerMessage, UserCommand, UserState},
-    utils,
+    messages::{
+        ClientError, ClientFrame, ClientMessage, GameView, OperatorSecret, ReconnectSecret, Seq,
+        SequencedMessage, ServerMessage, Snapshot, UserCommand, UserState, Verbosity,
+    },
+    utils::{self, FrameReader},
 };
 
 pub const READ_TIMEOUT: Duration = Duration::from_secs(10);
@@ -20,19 +34,58 @@ pub struct Client {
     pub username: String,
     pub addr: String,
     pub stream: TcpStream,
+    /// Secret the server issued for reconnecting as the user, if it's
+    /// been received.
+    pub secret: Option<ReconnectSecret>,
+    /// Sequence number of the last message received.
+    seq: Seq,
+    /// Turn the user was last signaled for, if they have been. Actions
+    /// are tied to it so they can't be applied to a later turn.
+    turn_id: Option<TurnId>,
+    /// Whatever's been read of a message that hasn't fully arrived yet,
+    /// so a read that times out partway through doesn't lose it.
+    reader: FrameReader,
 }
 
 impl Client {
+    pub fn add_chips(&mut self, amount: Usd) -> Result<(), Error> {
+        self.send(UserCommand::AddChips(amount))
+    }
+
+    pub fn change_seat(&mut self, seat_idx: SeatIndex) -> Result<(), Error> {
+        self.send(UserCommand::ChangeSeat(seat_idx))
+    }
+
     pub fn change_state(&mut self, state: UserState) -> Result<(), Error> {
-        let msg = ClientMessage {
-            username: self.username.clone(),
-            command: UserCommand::ChangeState(state),
-        };
-        utils::write_prefixed(&mut self.stream, &msg)?;
-        Ok(())
+        self.send(UserCommand::ChangeState(state))
     }
 
     pub fn connect(username: &str, addr: &str) -> Result<(Self, GameView), Error> {
+        let mut client = Client::open(username, addr, ClientFrame::Connect(username.to_string()))?;
+        // Then receive the game view.
+        let view = Client::recv_view(&mut client.stream)?;
+        Ok((client, view))
+    }
+
+    /// Connect as the server's operator, presenting the secret the server
+    /// was configured with.
+    pub fn connect_as_operator(
+        username: &str,
+        secret: &str,
+        addr: &str,
+    ) -> Result<(Self, GameView), Error> {
+        let frame = ClientFrame::ConnectAsOperator {
+            username: username.to_string(),
+            secret: OperatorSecret::from(secret),
+        };
+        let mut client = Client::open(username, addr, frame)?;
+        let view = Client::recv_view(&mut client.stream)?;
+        Ok((client, view))
+    }
+
+    /// Open a connection to the server and send the frame saying who the
+    /// client is, returning the client once the server acks it.
+    fn open(username: &str, addr: &str, frame: ClientFrame) -> Result<Self, Error> {
         let addr = addr.parse()?;
         let mut connect_timeouts = vec![
             Duration::from_secs(1),
@@ -44,26 +97,17 @@ impl Client {
                 Ok(mut stream) => {
                     stream.set_read_timeout(Some(READ_TIMEOUT))?;
                     stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
-                    let msg = ClientMessage {
+                    utils::write_prefixed(&mut stream, &frame)?;
+                    let secret = Client::recv_ack(&mut stream)?;
+                    return Ok(Self {
                         username: username.to_string(),
-                        command: UserCommand::Connect,
-                    };
-                    utils::write_prefixed(&mut stream, &msg)?;
-                    Client::recv_ack(&mut stream)?;
-                    // Then receive the game view.
-                    match Client::recv_view(&mut stream) {
-                        Ok(view) => {
-                            return Ok((
-                                Self {
-                                    username: username.to_string(),
-                                    addr: addr.to_string(),
-                                    stream,
-                                },
-                                view,
-                            ))
-                        }
-                        Err(error) => bail!(error),
-                    }
+                        addr: addr.to_string(),
+                        stream,
+                        secret,
+                        seq: 0,
+                        turn_id: None,
+                        reader: FrameReader::new(utils::MAX_FRAME_SIZE),
+                    });
                 }
                 _ => thread::sleep(connect_timeout),
             }
@@ -71,29 +115,91 @@ impl Client {
         bail!("couldn't connect to {addr} as {username}")
     }
 
+    pub fn pong(&mut self) -> Result<(), Error> {
+        self.send(UserCommand::Pong)
+    }
+
+    /// Connect again as a user that was connected before. With the secret
+    /// the user was issued, the server hands the user's old connection
+    /// over even if it hasn't noticed the connection dropped yet, keeping
+    /// the user right where they were. No view follows the ack when that
+    /// happens, so the client should resync to catch up.
+    pub fn reconnect(
+        username: &str,
+        addr: &str,
+        secret: Option<ReconnectSecret>,
+    ) -> Result<Self, Error> {
+        let frame = match secret {
+            Some(secret) => ClientFrame::Reconnect {
+                username: username.to_string(),
+                secret,
+            },
+            None => ClientFrame::Connect(username.to_string()),
+        };
+        let mut client = Client::open(username, addr, frame)?;
+        // A user that was already gone connects like anyone else and is
+        // issued a new secret, but keeps the old one otherwise.
+        client.secret = client.secret.or(secret);
+        Ok(client)
+    }
+
+    /// Receive the next message from the server. Pings are answered
+    /// along the way and aren't returned. A message that's out of order
+    /// is returned as an error, and the client should resync.
     pub fn recv(&mut self) -> Result<ServerMessage, Error> {
-        match utils::read_prefixed::<ServerMessage, TcpStream>(&mut self.stream) {
-            Ok(ServerMessage::ClientError(error)) => bail!(error),
-            Ok(ServerMessage::UserError(error)) => bail!(error),
-            Ok(msg) => Ok(msg),
-            Err(error) => bail!(error),
+        loop {
+            let SequencedMessage { seq, msg } = self
+                .reader
+                .read_from::<SequencedMessage, TcpStream>(&mut self.stream)?;
+            if seq < self.seq {
+                bail!("received message {seq} after message {}", self.seq);
+            }
+            self.seq = seq;
+            match msg {
+                ServerMessage::ClientError(error) => bail!(error),
+                ServerMessage::Ping => self.pong()?,
+                ServerMessage::ReconnectSecret(secret) => self.secret = Some(secret),
+                ServerMessage::UserError(error) => bail!(error),
+                ServerMessage::Snapshot(ref snapshot) => {
+                    self.turn_id = snapshot.turn_id;
+                    return Ok(msg);
+                }
+                ServerMessage::TurnSignal { turn_id, .. } => {
+                    self.turn_id = Some(turn_id);
+                    return Ok(msg);
+                }
+                msg => return Ok(msg),
+            }
         }
     }
 
-    pub fn recv_ack(stream: &mut TcpStream) -> Result<(), Error> {
-        match utils::read_prefixed::<ServerMessage, TcpStream>(stream) {
-            Ok(ServerMessage::Ack(_)) => Ok(()),
-            Ok(ServerMessage::ClientError(error)) => bail!(error),
-            Ok(ServerMessage::UserError(error)) => bail!(error),
-            Ok(response) => {
-                bail!("invalid server response: {response}")
+    /// Receive an ack, returning the reconnect secret the server sends
+    /// right before acking a connect if it was sent. Views are batched and
+    /// sent independently of acks, so any views received before the ack
+    /// are skipped.
+    pub fn recv_ack(stream: &mut TcpStream) -> Result<Option<ReconnectSecret>, Error> {
+        let mut secret = None;
+        loop {
+            match utils::read_prefixed::<SequencedMessage, TcpStream>(stream)
+                .map(|sequenced| sequenced.msg)
+            {
+                Ok(ServerMessage::Ack(_)) => return Ok(secret),
+                Ok(ServerMessage::ReconnectSecret(new_secret)) => secret = Some(new_secret),
+                Ok(ServerMessage::ClientError(error)) => bail!(error),
+                Ok(ServerMessage::GameView(_)) => {}
+                Ok(ServerMessage::UserError(error)) => bail!(error),
+                Ok(response) => {
+                    bail!("invalid server response: {response}")
+                }
+                Err(error) => bail!(error),
             }
-            Err(error) => bail!(error),
         }
     }
 
     pub fn recv_client_error(stream: &mut TcpStream) -> Result<ClientError, Error> {
-        match utils::read_prefixed::<ServerMessage, TcpStream>(stream) {
+        match utils::read_prefixed::<SequencedMessage, TcpStream>(stream)
+            .map(|sequenced| sequenced.msg)
+        {
             Ok(ServerMessage::ClientError(error)) => Ok(error),
             Ok(response) => {
                 bail!("invalid server response: {response}")
@@ -103,7 +209,9 @@ impl Client {
     }
 
     pub fn recv_user_error(stream: &mut TcpStream) -> Result<UserError, Error> {
-        match utils::read_prefixed::<ServerMessage, TcpStream>(stream) {
+        match utils::read_prefixed::<SequencedMessage, TcpStream>(stream)
+            .map(|sequenced| sequenced.msg)
+        {
             Ok(ServerMessage::UserError(error)) => Ok(error),
             Ok(response) => {
                 bail!("invalid server response: {response}")
@@ -113,9 +221,11 @@ impl Client {
     }
 
     pub fn recv_view(stream: &mut TcpStream) -> Result<GameView, Error> {
-        match utils::read_prefixed::<ServerMessage, TcpStream>(stream) {
+        match utils::read_prefixed::<SequencedMessage, TcpStream>(stream)
+            .map(|sequenced| sequenced.msg)
+        {
             Ok(ServerMessage::ClientError(error)) => bail!(error),
-            Ok(ServerMessage::GameView(view)) => Ok(view),
+            Ok(ServerMessage::GameView(view)) => Ok(Arc::unwrap_or_clone(view)),
             Ok(ServerMessage::UserError(error)) => bail!(error),
             Ok(response) => {
                 bail!("invalid server response: {response}")
@@ -124,30 +234,462 @@ impl Client {
         }
     }
 
+    pub fn accept_insurance(&mut self, from: &str) -> Result<(), Error> {
+        self.send(UserCommand::AcceptInsurance {
+            from: from.to_string(),
+        })
+    }
+
+    pub fn muck_hand(&mut self) -> Result<(), Error> {
+        self.send(UserCommand::MuckHand)
+    }
+
+    /// Stop hearing about what a user does outside of the hand being
+    /// played. Only this client's user gets an ack.
+    pub fn mute(&mut self, username: &str) -> Result<(), Error> {
+        self.send(UserCommand::Mute(username.to_string()))
+    }
+
+    /// Offer insurance to an all-in player, where `amount` is the premium
+    /// and `odds_numer:odds_denom` are the odds the payout is at.
+    pub fn offer_insurance(
+        &mut self,
+        to: &str,
+        amount: Usd,
+        odds_numer: Usd,
+        odds_denom: Usd,
+    ) -> Result<(), Error> {
+        self.send(UserCommand::OfferInsurance {
+            to: to.to_string(),
+            amount,
+            odds_numer,
+            odds_denom,
+        })
+    }
+
+    /// Request a new username. The client's username should only be
+    /// updated once the server acknowledges the request.
+    pub fn rename(&mut self, username: &str) -> Result<(), Error> {
+        self.send(UserCommand::Rename(username.to_string()))
+    }
+
+    /// Come back from the rail as a spectator with the stack that was
+    /// held for this client's user.
+    pub fn rejoin(&mut self) -> Result<(), Error> {
+        self.send(UserCommand::Rejoin)
+    }
+
+    /// Ask for a snapshot of the game. Responses to anything sent before
+    /// the resync may still arrive before the snapshot.
+    pub fn resync(&mut self) -> Result<(), Error> {
+        self.send(UserCommand::Resync)
+    }
+
+    /// Send a command as this client's user. The server knows who the
+    /// connection belongs to, so the username isn't sent along with it.
+    fn send(&mut self, command: UserCommand) -> Result<(), Error> {
+        utils::write_prefixed(&mut self.stream, &ClientFrame::Command(command))?;
+        Ok(())
+    }
+
+    /// Return the sequence number of the last message received. Apps
+    /// that read the stream themselves should resync if a message's
+    /// sequence number is ever lower than this.
+    pub fn seq(&self) -> Seq {
+        self.seq
+    }
+
+    /// Opt in or out of rebuying when this client's user can't afford
+    /// the big blind. Servers that don't allow auto-rebuy respond with
+    /// an error.
+    pub fn set_auto_rebuy(&mut self, enabled: bool) -> Result<(), Error> {
+        self.send(UserCommand::SetAutoRebuy(enabled))
+    }
+
+    /// Change the avatar and tagline shown next to this client's user.
+    /// The server rejects avatars that aren't a single character and
+    /// taglines that are too long.
+    pub fn set_profile(&mut self, profile: Profile) -> Result<(), Error> {
+        self.send(UserCommand::SetProfile(profile))
+    }
+
+    /// Change how much the server sends this client. The new verbosity
+    /// applies once the server acknowledges the request.
+    pub fn set_verbosity(&mut self, verbosity: Verbosity) -> Result<(), Error> {
+        self.send(UserCommand::SetVerbosity(verbosity))
+    }
+
+    pub fn show_card(&mut self, card_idx: usize) -> Result<(), Error> {
+        self.send(UserCommand::ShowCard(card_idx))
+    }
+
     pub fn show_hand(&mut self) -> Result<(), Error> {
-        let msg = ClientMessage {
-            username: self.username.to_string(),
-            command: UserCommand::ShowHand,
+        self.send(UserCommand::ShowHand)
+    }
+
+    /// Bet on a player winning the current hand while spectating.
+    pub fn side_bet(&mut self, player: &str, amount: Usd) -> Result<(), Error> {
+        self.send(UserCommand::SideBet(player.to_string(), amount))
+    }
+
+    pub fn start_game(&mut self) -> Result<(), Error> {
+        self.send(UserCommand::StartGame)
+    }
+
+    /// Ask to see every player's hole cards. Only the server's operator
+    /// can.
+    pub fn view_all_cards(&mut self) -> Result<(), Error> {
+        self.send(UserCommand::ViewAllCards)
+    }
+
+    /// Take an action on the turn the user was last signaled for.
+    pub fn take_action(&mut self, action: Action) -> Result<(), Error> {
+        self.send(UserCommand::TakeAction(action).for_turn(self.turn_id))
+    }
+
+    pub fn unmute(&mut self, username: &str) -> Result<(), Error> {
+        self.send(UserCommand::Unmute(username.to_string()))
+    }
+}
+
+/// Actions a player can choose from when it's their turn.
+pub type ActionChoices = HashSet<Action>;
+
+/// A client session that keeps track of the latest game view and game
+/// events while waiting on the server, so scripts only need to handle the
+/// messages they care about.
+pub struct Session {
+    client: Client,
+    events: VecDeque<(HandId, GameEvent)>,
+    view: Arc<GameView>,
+}
+
+impl Session {
+    pub fn act(&mut self, action: Action) -> Result<(), Error> {
+        self.client.take_action(action)
+    }
+
+    pub fn change_seat(&mut self, seat_idx: SeatIndex) -> Result<(), Error> {
+        self.client.change_seat(seat_idx)
+    }
+
+    pub fn change_state(&mut self, state: UserState) -> Result<(), Error> {
+        self.client.change_state(state)
+    }
+
+    pub fn connect(username: &str, addr: &str) -> Result<Self, Error> {
+        let (client, view) = Client::connect(username, addr)?;
+        Ok(Self {
+            client,
+            events: VecDeque::new(),
+            view: Arc::new(view),
+        })
+    }
+
+    pub fn connect_as_operator(username: &str, secret: &str, addr: &str) -> Result<Self, Error> {
+        let (client, view) = Client::connect_as_operator(username, secret, addr)?;
+        Ok(Self {
+            client,
+            events: VecDeque::new(),
+            view: Arc::new(view),
+        })
+    }
+
+    /// Drain game events received since the last drain along with the
+    /// hands they occurred during.
+    pub fn events(&mut self) -> impl Iterator<Item = (HandId, GameEvent)> + '_ {
+        self.events.drain(..)
+    }
+
+    pub fn latest_view(&self) -> &GameView {
+        &self.view
+    }
+
+    pub fn mute(&mut self, username: &str) -> Result<(), Error> {
+        self.client.mute(username)
+    }
+
+    /// Receive the next message from the server, waiting forever if there's
+    /// no timeout. Game views and events are recorded before the message is
+    /// returned. Client and user errors are returned as errors.
+    pub fn recv(&mut self, timeout: Option<Duration>) -> Result<ServerMessage, Error> {
+        if timeout == Some(Duration::ZERO) {
+            bail!("timed out waiting for a server message");
+        }
+        self.client.stream.set_read_timeout(timeout)?;
+        let msg = self.client.recv()?;
+        match msg {
+            ServerMessage::GameEvent(hand_id, ref event) => {
+                self.events.push_back((hand_id, event.clone()))
+            }
+            ServerMessage::GameView(ref view) => self.view = Arc::clone(view),
+            ServerMessage::Snapshot(ref snapshot) => self.view = Arc::clone(&snapshot.view),
+            _ => {}
+        }
+        Ok(msg)
+    }
+
+    /// Drop the connection and connect again as the same user, then
+    /// resync. The server hands the old connection over if the user's
+    /// reconnect secret is known, and connecting is retried until the
+    /// server lets the user back in otherwise.
+    pub fn reconnect(&mut self, timeout: Option<Duration>) -> Result<Snapshot, Error> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let _ = self.shutdown();
+        let client = loop {
+            match Client::reconnect(&self.client.username, &self.client.addr, self.client.secret) {
+                Ok(client) => break client,
+                Err(error) if deadline.is_some_and(|deadline| Instant::now() >= deadline) => {
+                    return Err(error)
+                }
+                Err(_) => thread::sleep(Duration::from_millis(100)),
+            }
         };
-        utils::write_prefixed(&mut self.stream, &msg)?;
+        self.client = client;
+        self.resync(deadline.map(|deadline| deadline.saturating_duration_since(Instant::now())))
+    }
+
+    /// Ask for a snapshot of the game and wait for it, replacing the
+    /// latest view with the snapshot's view.
+    pub fn resync(&mut self, timeout: Option<Duration>) -> Result<Snapshot, Error> {
+        self.client.resync()?;
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let timeout =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            if let ServerMessage::Snapshot(snapshot) = self.recv(timeout)? {
+                return Ok(*snapshot);
+            }
+        }
+    }
+
+    /// Return the sequence number of the last message received.
+    pub fn seq(&self) -> Seq {
+        self.client.seq()
+    }
+
+    /// Opt in or out of auto-rebuy and wait for the server to respond,
+    /// returning whether the server allows auto-rebuy at all.
+    pub fn set_auto_rebuy(
+        &mut self,
+        enabled: bool,
+        timeout: Option<Duration>,
+    ) -> Result<bool, Error> {
+        self.client.set_auto_rebuy(enabled)?;
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let timeout =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            match self.recv(timeout) {
+                Ok(ServerMessage::Ack(ClientMessage {
+                    ref username,
+                    command: UserCommand::SetAutoRebuy(_),
+                })) if username == self.username() => return Ok(true),
+                Ok(_) => {}
+                Err(error)
+                    if error.downcast_ref::<UserError>() == Some(&UserError::AutoRebuyDisabled) =>
+                {
+                    return Ok(false)
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    pub fn set_verbosity(&mut self, verbosity: Verbosity) -> Result<(), Error> {
+        self.client.set_verbosity(verbosity)
+    }
+
+    pub fn shutdown(&self) -> Result<(), Error> {
+        self.client.stream.shutdown(Shutdown::Both)?;
         Ok(())
     }
 
     pub fn start_game(&mut self) -> Result<(), Error> {
-        let msg = ClientMessage {
-            username: self.username.to_string(),
-            command: UserCommand::StartGame,
-        };
-        utils::write_prefixed(&mut self.stream, &msg)?;
+        self.client.start_game()
+    }
+
+    pub fn unmute(&mut self, username: &str) -> Result<(), Error> {
+        self.client.unmute(username)
+    }
+
+    pub fn username(&self) -> &str {
+        &self.client.username
+    }
+
+    pub fn view_all_cards(&mut self) -> Result<(), Error> {
+        self.client.view_all_cards()
+    }
+
+    /// Wait until it's the user's turn, returning the actions they can
+    /// choose from.
+    pub fn wait_for_turn(&mut self, timeout: Option<Duration>) -> Result<ActionChoices, Error> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let timeout =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            if let ServerMessage::TurnSignal {
+                action_options: action_choices,
+                ..
+            } = self.recv(timeout)?
+            {
+                return Ok(action_choices);
+            }
+        }
+    }
+
+    /// Wait until a game view satisfies the predicate, returning that view.
+    /// The latest view is checked before waiting on the server.
+    pub fn wait_for_view<P: Fn(&GameView) -> bool>(
+        &mut self,
+        timeout: Option<Duration>,
+        predicate: P,
+    ) -> Result<&GameView, Error> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        while !predicate(&self.view) {
+            let timeout =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            self.recv(timeout)?;
+        }
+        Ok(&self.view)
+    }
+}
+
+/// How long a multi-client waits on one user's connection before moving
+/// on to the next one's.
+const MULTI_CLIENT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Sessions for several users at the same table, for hosts that play more
+/// than one seat from a single process. Commands are sent as the active
+/// user. Switching users hides the new active user's hole cards until
+/// they're revealed, so whoever's at the keyboard only ever sees the
+/// cards of the user they're playing as.
+pub struct MultiClient {
+    sessions: Vec<Session>,
+    active: usize,
+    /// Whether the active user's hole cards are hidden from their view.
+    hidden: bool,
+    /// Session to check for messages first, so one busy connection
+    /// doesn't hold up the others.
+    next_idx: usize,
+}
+
+impl MultiClient {
+    pub fn act(&mut self, action: Action) -> Result<(), Error> {
+        self.sessions[self.active].act(action)
+    }
+
+    /// Return the username of the user commands are sent as.
+    pub fn active(&self) -> &str {
+        self.sessions[self.active].username()
+    }
+
+    pub fn change_state(&mut self, state: UserState) -> Result<(), Error> {
+        self.sessions[self.active].change_state(state)
+    }
+
+    /// Connect as each user, with the first one active.
+    pub fn connect(usernames: &[&str], addr: &str) -> Result<Self, Error> {
+        if usernames.is_empty() {
+            bail!("need at least one username to connect as");
+        }
+        let sessions = usernames
+            .iter()
+            .map(|username| Session::connect(username, addr))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            sessions,
+            active: 0,
+            hidden: false,
+            next_idx: 0,
+        })
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /// Receive the next message sent to any of the users, waiting forever
+    /// if there's no timeout. Returns the user the message was sent to
+    /// along with the message, or with the error the server sent them.
+    /// Only running out of time is returned as an error itself.
+    pub fn recv(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Result<(Username, Result<ServerMessage, Error>), Error> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            for _ in 0..self.sessions.len() {
+                let session_idx = self.next_idx;
+                self.next_idx = (self.next_idx + 1) % self.sessions.len();
+                let session = &mut self.sessions[session_idx];
+                match session.recv(Some(MULTI_CLIENT_POLL_INTERVAL)) {
+                    Err(error) if is_timeout(&error) => {}
+                    result => return Ok((session.username().to_string(), result)),
+                }
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                bail!("timed out waiting for a server message");
+            }
+        }
+    }
+
+    /// Show the active user's hole cards again after switching to them.
+    pub fn reveal(&mut self) {
+        self.hidden = false;
+    }
+
+    pub fn shutdown(&self) -> Result<(), Error> {
+        for session in self.sessions.iter() {
+            session.shutdown()?;
+        }
         Ok(())
     }
 
-    pub fn take_action(&mut self, action: Action) -> Result<(), Error> {
-        let msg = ClientMessage {
-            username: self.username.to_string(),
-            command: UserCommand::TakeAction(action),
+    pub fn start_game(&mut self) -> Result<(), Error> {
+        self.sessions[self.active].start_game()
+    }
+
+    /// Send commands as another user from now on, hiding their hole cards
+    /// until they're revealed.
+    pub fn switch(&mut self, username: &str) -> Result<(), Error> {
+        let Some(session_idx) = self
+            .sessions
+            .iter()
+            .position(|session| session.username() == username)
+        else {
+            bail!("not connected as {username}");
         };
-        utils::write_prefixed(&mut self.stream, &msg)?;
+        if session_idx != self.active {
+            self.active = session_idx;
+            self.hidden = true;
+        }
         Ok(())
     }
+
+    pub fn usernames(&self) -> impl Iterator<Item = &str> {
+        self.sessions.iter().map(Session::username)
+    }
+
+    /// Return the active user's latest view, with their hole cards hidden
+    /// like everyone else's if they haven't been revealed since switching.
+    pub fn view(&self) -> GameView {
+        let mut view = self.sessions[self.active].latest_view().clone();
+        if self.hidden {
+            let username = self.active();
+            for player in view.players.iter_mut() {
+                if player.user.name == username {
+                    player.conceal_cards();
+                }
+            }
+        }
+        view
+    }
+}
+
+/// Return whether the error is from a read that timed out.
+fn is_timeout(error: &Error) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|error| matches!(error.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock))
 }
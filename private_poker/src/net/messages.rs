@@ -1,9 +1,15 @@
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, fmt};
+use std::{collections::HashSet, fmt, sync::Arc};
+
+use super::utils::Tag;
 
 pub use crate::game::entities::GameView;
 use crate::game::{
-    entities::{Action, Username},
+    constants::MAX_USER_INPUT_LENGTH,
+    entities::{
+        Action, DecisionContext, GameEvent, GamePhase, HandId, Leaderboard, Profile, SeatIndex,
+        TurnId, Usd, Username,
+    },
     Game, TakeAction, UserError,
 };
 
@@ -17,8 +23,16 @@ pub enum ClientError {
     DoesNotExist,
     #[error("expired")]
     Expired,
+    #[error("server draining")]
+    ServerDraining,
     #[error("unassociated")]
     Unassociated,
+    #[error("rate limited")]
+    RateLimited,
+    #[error("not the operator")]
+    NotOperator,
+    #[error("reserved for the server")]
+    ServerOnly,
 }
 
 /// Type of user state change requests.
@@ -26,6 +40,9 @@ pub enum ClientError {
 pub enum UserState {
     Play,
     Spectate,
+    /// Give up a seat and stack but keep following the game. The stack is
+    /// held until the user rejoins.
+    Railbird,
 }
 
 impl fmt::Display for UserState {
@@ -33,6 +50,28 @@ impl fmt::Display for UserState {
         let repr = match self {
             UserState::Play => "waitlister",
             UserState::Spectate => "spectator",
+            UserState::Railbird => "railbird",
+        };
+        write!(f, "{repr}")
+    }
+}
+
+/// How much a client wants to hear from the server.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Verbosity {
+    /// Everything, including status strings meant for people.
+    #[default]
+    Verbose,
+    /// Everything except status strings. Programs can follow along with
+    /// phases, events, and views instead.
+    Terse,
+}
+
+impl fmt::Display for Verbosity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let repr = match self {
+            Verbosity::Verbose => "verbose",
+            Verbosity::Terse => "terse",
         };
         write!(f, "{repr}")
     }
@@ -41,17 +80,73 @@ impl fmt::Display for UserState {
 /// A user command.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum UserCommand {
+    /// An all-in player wants to take the insurance another user
+    /// offered them.
+    AcceptInsurance { from: Username },
+    /// User wants to add chips to their stack, up to the max buy-in.
+    /// Players can only add chips after the current hand is over.
+    AddChips(Usd),
+    /// Player wants to move to an open seat. Players are moved right
+    /// away between hands, and after the current hand otherwise.
+    ChangeSeat(SeatIndex),
     /// The user wants to change their state (play or spectate).
     ChangeState(UserState),
     /// A new user wants to connect to the game.
     Connect,
+    /// User's connection dropped. This is generated by the server on
+    /// the user's behalf so seated players can be given a chance to
+    /// reconnect. Servers refuse it from clients.
+    Disconnect,
     /// User wants to leave the game. This is really just a
     /// friendly courtesy and doesn't need to be sent by
     /// clients.
     Leave,
+    /// User wants to muck their hand, keeping it hidden at the
+    /// showdown. Can only occur if they're a player and haven't
+    /// already shown their hand.
+    MuckHand,
+    /// User wants to stop hearing about what another user does outside
+    /// of the hand being played. Mutes only affect the muting user and
+    /// are remembered across their reconnects, so only they get an ack.
+    Mute(Username),
+    /// User wants to insure an all-in player during the runout to the
+    /// showdown. The player pays the premium (`amount`) if they win the
+    /// hand, and the user pays the premium scaled by the odds if they
+    /// don't. Only possible if the game allows insurance.
+    OfferInsurance {
+        to: Username,
+        amount: Usd,
+        odds_numer: Usd,
+        odds_denom: Usd,
+    },
+    /// A response to a server ping, letting the server know the client
+    /// is still there. Clients should send one for every ping they
+    /// receive.
+    Pong,
+    /// User wants to see how much every user has won or lost over the
+    /// session. Only the requesting user gets a response.
+    QueryLeaderboard,
+    /// User wants to change their username. Their stack, seat, and
+    /// any other game state is kept.
+    Rename(Username),
+    /// User wants everything they need to follow along with the game in
+    /// one go, usually after reconnecting. Only the requesting user gets
+    /// a response, a snapshot of the game.
+    Resync,
+    /// User wants their stack reset to the starting stack rather than
+    /// being spectated when they can't afford the big blind. Only
+    /// possible if the game allows auto-rebuy.
+    SetAutoRebuy(bool),
+    /// User wants to change how much they hear from the server. Only
+    /// affects the user's own connection, so only they get an ack.
+    SetVerbosity(Verbosity),
+    /// User wants to show the hole card at the given index, keeping
+    /// the rest of their hand hidden. Has the same requirements as
+    /// showing their hand.
+    ShowCard(usize),
     /// User wants to show their hand. Can only occur if they're
-    /// a player and the game is in a state that allows hands to
-    /// be shown.
+    /// a player and either the game is at the showdown or betting
+    /// no longer involves them (e.g., they're all-in).
     ShowHand,
     /// User wants to start the game. Can only start a game when
     /// there are 2+ potential players.
@@ -59,24 +154,150 @@ pub enum UserCommand {
     /// User wants to make a bet. Can only occur if they're a
     /// player and it's their turn.
     TakeAction(Action),
+    /// User wants to hear from a user they muted again.
+    Unmute(Username),
+    /// User wants to make a bet on the turn they were signaled for. This
+    /// is like taking an action, but it's rejected if that turn is over
+    /// by the time it arrives. It comes last so the commands before it
+    /// are encoded the same as they were for clients that predate it.
+    TakeTurn { action: Action, turn_id: TurnId },
+    /// User wants to change the avatar and tagline shown next to their
+    /// name. Profiles are kept if the user leaves and rejoins.
+    SetProfile(Profile),
+    /// Railbird wants to come back as a spectator with the stack that
+    /// was held for them.
+    Rejoin,
+    /// Spectator wants to bet play money from their stack that the player
+    /// wins the current hand. Only possible while players are betting and
+    /// if the game allows side bets.
+    SideBet(Username, Usd),
+    /// The server's operator wants to see every player's hole cards.
+    /// Anyone else asking is refused, and only the operator gets a
+    /// response, an omniscient view.
+    ViewAllCards,
+}
+
+impl UserCommand {
+    /// Tags identifying each kind of command on the wire, in the order
+    /// they're declared. New kinds of commands get new tags, and tags are
+    /// never changed or reused, so servers can skip commands from newer
+    /// clients that they don't know about.
+    pub const TAGS: [Tag; 26] = [
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
+        25,
+    ];
+
+    /// Tie an action to the turn it's meant for, if the turn is known.
+    /// Other commands are returned as they are.
+    pub fn for_turn(self, turn_id: Option<TurnId>) -> Self {
+        match (self, turn_id) {
+            (UserCommand::TakeAction(action), Some(turn_id)) => {
+                UserCommand::TakeTurn { action, turn_id }
+            }
+            (command, _) => command,
+        }
+    }
 }
 
 impl fmt::Display for UserCommand {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let repr = match &self {
+            UserCommand::AcceptInsurance { from } => &format!("accepted insurance from {from}"),
+            UserCommand::AddChips(amount) => &format!("asked to add ${amount} to their stack"),
+            UserCommand::ChangeSeat(seat_idx) => &format!("asked to move to seat {seat_idx}"),
             UserCommand::ChangeState(state) => &format!("joined the {state}s"),
             UserCommand::Connect => "connected",
+            UserCommand::Disconnect => "disconnected",
             UserCommand::Leave => "left the game",
+            UserCommand::MuckHand => "mucked their hand",
+            UserCommand::Mute(username) => &format!("muted {username}"),
+            UserCommand::OfferInsurance {
+                to,
+                amount,
+                odds_numer,
+                odds_denom,
+            } => &format!("offered {to} insurance for ${amount} at {odds_numer}:{odds_denom}"),
+            UserCommand::Pong => "responded to a ping",
+            UserCommand::QueryLeaderboard => "checked the leaderboard",
+            UserCommand::Rename(username) => &format!("renamed themselves to {username}"),
+            UserCommand::Resync => "asked to resync",
+            UserCommand::SetAutoRebuy(true) => "turned on auto-rebuy",
+            UserCommand::SetAutoRebuy(false) => "turned off auto-rebuy",
+            UserCommand::SetVerbosity(verbosity) => &format!("asked for {verbosity} messages"),
+            UserCommand::ShowCard(_) => "showed one of their cards",
             UserCommand::ShowHand => "showed their hand",
             UserCommand::StartGame => "started the game",
             UserCommand::TakeAction(action) => &action.to_action_string(),
+            UserCommand::Unmute(username) => &format!("unmuted {username}"),
+            UserCommand::TakeTurn { action, .. } => &action.to_action_string(),
+            UserCommand::SetProfile(_) => "updated their profile",
+            UserCommand::Rejoin => "rejoined from the rail",
+            UserCommand::SideBet(player, amount) => &format!("bet ${amount} on {player}"),
+            UserCommand::ViewAllCards => "asked to see every hand",
         };
         write!(f, "{repr}")
     }
 }
 
-/// A message from a poker client to the poker server, indicating some
-/// type of user action or command request.
+/// A frame from a poker client to the poker server. Users only say who
+/// they are when they connect. Everything else is from whichever user
+/// the connection belongs to, so clients can't send commands on other
+/// users' behalf.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ClientFrame {
+    /// A new user wants to connect to the game with the given username.
+    Connect(Username),
+    /// The connection's user is sending a command.
+    Command(UserCommand),
+    /// A user that was connected wants to connect again, presenting the
+    /// secret they were issued so the server hands their old connection
+    /// over instead of treating them as someone else with their username.
+    Reconnect {
+        username: Username,
+        secret: ReconnectSecret,
+    },
+    /// The server's operator wants to connect with the given username,
+    /// presenting the secret the server was configured with so nobody
+    /// else can connect as them.
+    ConnectAsOperator {
+        username: Username,
+        secret: OperatorSecret,
+    },
+}
+
+impl ClientFrame {
+    /// Tag for connecting on the wire, kept well away from commands'
+    /// tags.
+    pub const CONNECT_TAG: Tag = Tag::MAX;
+
+    /// Tag for reconnecting on the wire, right below connecting's.
+    pub const RECONNECT_TAG: Tag = Tag::MAX - 1;
+
+    /// Tag for connecting as the operator on the wire, right below
+    /// reconnecting's.
+    pub const OPERATOR_TAG: Tag = Tag::MAX - 2;
+
+    pub fn command(&self) -> &UserCommand {
+        match self {
+            ClientFrame::Connect(_)
+            | ClientFrame::ConnectAsOperator { .. }
+            | ClientFrame::Reconnect { .. } => &UserCommand::Connect,
+            ClientFrame::Command(command) => command,
+        }
+    }
+}
+
+/// Secret a client is issued when it connects, which it presents if it
+/// reconnects to prove it's the same client.
+pub type ReconnectSecret = u128;
+
+/// Secret the server's operator connects with, which the server is
+/// configured with.
+pub type OperatorSecret = String;
+
+/// A user command along with the user it's from, indicating some type
+/// of user action or command request. The server makes these from
+/// client frames using the username the connection is associated with.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ClientMessage {
     /// User the message is from.
@@ -85,12 +306,83 @@ pub struct ClientMessage {
     pub command: UserCommand,
 }
 
+/// Strip control characters and cap the length of user input.
+fn sanitize_user_input(input: &mut String) {
+    if input.len() > MAX_USER_INPUT_LENGTH || input.chars().any(char::is_control) {
+        *input = input
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(MAX_USER_INPUT_LENGTH)
+            .collect();
+    }
+}
+
+impl ClientMessage {
+    /// Make user input safe to log and relay to other clients. This should
+    /// be called on every message read from a client before it's processed.
+    pub fn sanitize(&mut self) {
+        sanitize_user_input(&mut self.username);
+        // No catch-all, so new commands have to decide what to sanitize.
+        match self.command {
+            UserCommand::AcceptInsurance {
+                from: ref mut username,
+            }
+            | UserCommand::Mute(ref mut username)
+            | UserCommand::OfferInsurance {
+                to: ref mut username,
+                ..
+            }
+            | UserCommand::Rename(ref mut username)
+            | UserCommand::SideBet(ref mut username, _)
+            | UserCommand::Unmute(ref mut username) => sanitize_user_input(username),
+            // Profiles are rejected by the game if they have control
+            // characters or are too long, and they're never logged.
+            UserCommand::SetProfile(_) => {}
+            UserCommand::AddChips(_)
+            | UserCommand::ChangeSeat(_)
+            | UserCommand::ChangeState(_)
+            | UserCommand::Connect
+            | UserCommand::Disconnect
+            | UserCommand::Leave
+            | UserCommand::MuckHand
+            | UserCommand::Pong
+            | UserCommand::QueryLeaderboard
+            | UserCommand::Rejoin
+            | UserCommand::Resync
+            | UserCommand::SetAutoRebuy(_)
+            | UserCommand::SetVerbosity(_)
+            | UserCommand::ShowCard(_)
+            | UserCommand::ShowHand
+            | UserCommand::StartGame
+            | UserCommand::TakeAction(_)
+            | UserCommand::TakeTurn { .. }
+            | UserCommand::ViewAllCards => {}
+        }
+    }
+}
+
 impl fmt::Display for ClientMessage {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{} {}", self.username, self.command)
     }
 }
 
+/// Everything a user needs to follow along with the game, sent all at
+/// once so a client can replace its state rather than piece it together
+/// from messages that may have been missed.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Snapshot {
+    pub view: Arc<GameView>,
+    pub phase: GamePhase,
+    pub status: String,
+    /// The actions the user can choose from and the most they can raise
+    /// by, just like a turn signal, if it's their turn.
+    pub action_options: Option<HashSet<Action>>,
+    pub max_raise: Option<Usd>,
+    /// The turn to tie actions to, if it's the user's turn.
+    pub turn_id: Option<TurnId>,
+}
+
 /// A message from the poker server to a poker client.
 #[derive(Debug, Deserialize, Serialize)]
 pub enum ServerMessage {
@@ -100,30 +392,114 @@ pub enum ServerMessage {
     /// An indication that the poker client caused an error, resulting in
     /// the client's message not being processed correctly.
     ClientError(ClientError),
-    /// The game state as viewed from the client's perspective.
-    GameView(GameView),
+    /// A notable game event and the hand it occurred during.
+    GameEvent(HandId, GameEvent),
+    /// The game state as viewed from the client's perspective. Views are
+    /// shared between clients that see the same thing.
+    GameView(Arc<GameView>),
+    /// Net profit or loss of every user over the session, sent in
+    /// response to a leaderboard query.
+    Leaderboard(Leaderboard),
+    /// The game with every player's hole cards showing. Only ever sent
+    /// to the server's operator, alongside their regular views and in
+    /// response to them asking to see every hand.
+    OmniscientView(Arc<GameView>),
+    /// The game's current phase. Sent alongside the status, but meant
+    /// for programs rather than people.
+    Phase(GamePhase),
+    /// A heartbeat sent to clients the server hasn't heard from in a
+    /// while. Clients that don't respond with a pong in time are removed.
+    Ping,
+    /// The secret the client can reconnect with, sent to the client
+    /// right before its connect is acknowledged.
+    ReconnectSecret(ReconnectSecret),
+    /// A snapshot of the game, sent in response to a resync.
+    Snapshot(Box<Snapshot>),
     /// The game state represented as a string.
     Status(String),
-    /// A sginal indicating that it's the user's turn.
-    TurnSignal(HashSet<Action>),
+    /// Whose turn it is and a summary of the actions they can choose
+    /// from, sent to everyone. The user whose turn it is also gets a turn
+    /// signal right after.
+    TurnAnnouncement {
+        username: Username,
+        choices_summary: String,
+    },
+    /// A sginal indicating that it's the user's turn, along with the
+    /// actions they can choose from. If they can raise, the most they can
+    /// raise by is their whole stack, which puts them all-in. The
+    /// context has everything else they need to decide, so they don't
+    /// have to piece it together from the latest view.
+    TurnSignal {
+        action_options: HashSet<Action>,
+        max_raise: Option<Usd>,
+        context: DecisionContext,
+        /// The turn this signal is for. Clients echo it back with their
+        /// action so it can't be applied to a later turn.
+        turn_id: TurnId,
+    },
     /// An indication that the poker client sent a message that was read
     /// properly, but the type of action that it relayed was invalid
     /// for the game state, resulting in a user error.
     UserError(UserError),
 }
 
+impl ServerMessage {
+    /// Tags identifying each kind of message on the wire, in the order
+    /// they're declared. New kinds of messages get new tags, and tags are
+    /// never changed or reused, so clients can skip messages from newer
+    /// servers that they don't know about. Kinds of messages are declared
+    /// wherever they fit, but they're tagged after the ones before them.
+    pub const TAGS: [Tag; 14] = [0, 1, 2, 3, 4, 13, 5, 6, 12, 7, 8, 9, 10, 11];
+}
+
 impl fmt::Display for ServerMessage {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let repr = match &self {
             ServerMessage::Ack(msg) => msg.to_string(),
             ServerMessage::ClientError(error) => error.to_string(),
+            ServerMessage::GameEvent(_, event) => event.to_string(),
             ServerMessage::GameView(_) => "game view".to_string(),
+            ServerMessage::Leaderboard(leaderboard) => leaderboard
+                .iter()
+                .map(|(username, net)| format!("{username}: {net:+}"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            ServerMessage::OmniscientView(_) => "omniscient view".to_string(),
+            ServerMessage::Phase(phase) => phase.to_string(),
+            ServerMessage::Ping => "ping".to_string(),
+            // Secrets aren't meant to end up in logs.
+            ServerMessage::ReconnectSecret(_) => "reconnect secret".to_string(),
+            ServerMessage::Snapshot(snapshot) => snapshot.status.clone(),
             ServerMessage::Status(status) => status.to_string(),
-            ServerMessage::TurnSignal(action_options) => {
-                Game::<TakeAction>::action_options_to_string(action_options)
-            }
+            ServerMessage::TurnAnnouncement {
+                username,
+                choices_summary,
+            } => format!("it's {username}'s turn and they can {choices_summary}"),
+            ServerMessage::TurnSignal {
+                action_options,
+                max_raise,
+                ..
+            } => Game::<TakeAction>::action_options_to_string(action_options, *max_raise),
             ServerMessage::UserError(error) => error.to_string(),
         };
         write!(f, "{repr}")
     }
 }
+
+/// Position of a server message in the order the game thread sent the
+/// update it came from. Numbers only ever increase over a server's
+/// lifetime.
+pub type Seq = u64;
+
+/// A server message along with its sequence number, which is how server
+/// messages are framed on the wire. Each client gets its messages in
+/// sequence order, so a sequence number lower than the last one means
+/// something went wrong and the client should resync. Messages from the
+/// same update (e.g., a batch of game events) share a sequence number,
+/// and messages the server sends on its own (e.g., pings) reuse the
+/// sequence number of the last update.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SequencedMessage {
+    pub seq: Seq,
+    pub msg: ServerMessage,
+}
@@ -1,39 +1,93 @@
-use anyhow::{bail, Error};
-use log::{debug, error, info, warn};
+use anyhow::{anyhow, bail, Error};
 use mio::{
     net::{TcpListener, TcpStream},
     Events, Interest, Poll, Token, Waker,
 };
 use serde::{Deserialize, Serialize};
 use std::{
+    cell::Cell,
     cmp::max,
     collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
-    io,
-    sync::mpsc::{channel, Receiver, Sender},
-    thread,
+    fmt, io, mem,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError, SendError, Sender, TryRecvError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
     time::{Duration, Instant},
 };
+use tracing::{debug, debug_span, dispatcher, error, info, info_span, warn, Dispatch};
+
+pub mod clock;
+pub mod journal;
+pub mod metrics;
 
-use crate::{
-    constants::MAX_USER_INPUT_LENGTH,
-    game::{
-        entities::{Action, GameView, Username},
-        GameSettings, PokerState,
+use crate::game::{
+    entities::{
+        Action, GameEvent, GamePhase, GameView, GameViews, HandId, Leaderboard, Usd, Username,
     },
+    ConfigError, GameSettings, PokerState, StepDurations, UserError,
 };
 
+use clock::{Clock, SystemClock};
+use journal::{Journal, JournalConfig, JournalRecord};
+use metrics::{Metrics, PhaseTimer};
+
 use super::{
-    messages::{ClientError, ClientMessage, ServerMessage, UserCommand, UserState},
-    utils::{read_prefixed, write_prefixed},
+    messages::{
+        ClientError, ClientFrame, ClientMessage, OperatorSecret, ReconnectSecret, Seq,
+        SequencedMessage, ServerMessage, Snapshot, UserCommand, UserState, Verbosity,
+    },
+    utils::{FrameReader, PartialFrame},
 };
 
+// Step pauses moved next to the game's other defaults, but they're still
+// server timeouts.
+pub use crate::game::constants::{DEFAULT_BRIEF_STEP_TIMEOUT, DEFAULT_STEP_TIMEOUT};
+
 pub const DEFAULT_ACTION_TIMEOUT: Duration = Duration::from_secs(30);
+pub const DEFAULT_AUTOSTART_TIMEOUT: Duration = Duration::from_secs(10);
 pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_FRAME_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(15);
+pub const DEFAULT_HEARTBEAT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Plenty of room for a full table of bots and their owner all
+/// connecting from the same machine.
+pub const DEFAULT_MAX_CONNECTIONS_PER_IP: usize = 64;
+pub const DEFAULT_MAX_UNCONFIRMED_CONNECTIONS: usize = 256;
 pub const DEFAULT_POLL_TIMEOUT: Duration = Duration::from_secs(1);
-pub const DEFAULT_STEP_TIMEOUT: Duration = Duration::from_secs(5);
+/// Users that keep going over their rate limit this long are removed.
+pub const DEFAULT_RATE_LIMIT_ABUSE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Enough for a bot to join, start the game, and act all at once.
+pub const DEFAULT_RATE_LIMIT_BURST: f32 = 10.0;
+pub const DEFAULT_RATE_LIMIT_PER_SECOND: f32 = 5.0;
+/// Long enough for the last hand of a session to be played out.
+pub const DEFAULT_SESSION_GRACE: Duration = Duration::from_secs(5 * 60);
+pub const DEFAULT_SESSION_WARNINGS: [Duration; 3] = [
+    Duration::from_secs(30 * 60),
+    Duration::from_secs(15 * 60),
+    Duration::from_secs(5 * 60),
+];
+pub const DEFAULT_VIEW_BATCH_TIMEOUT: Duration = Duration::from_millis(100);
+/// How long the IO thread keeps writing to clients after the game thread
+/// is done before giving up on the ones that aren't reading.
+const MAX_FINAL_WRITE_TIME: Duration = Duration::from_secs(5);
+/// Client messages are small, so anything bigger than this is
+/// malicious and results in the client being dropped.
+pub const MAX_CLIENT_FRAME_SIZE: usize = 1 << 10;
 pub const MAX_NETWORK_EVENTS_PER_USER: usize = 6;
+/// Servers can listen on at most this many addresses at once.
+pub const MAX_LISTENERS: usize = 16;
+/// Token of the first address the server listens on. Each address after
+/// it takes the next token, up to the waker's.
 pub const SERVER: Token = Token(0);
-pub const WAKER: Token = Token(1);
+/// Status sent when the game returns to the lobby without enough players
+/// to start another hand.
+pub const WAITING_FOR_PLAYERS: &str = "waiting for players, spectators can play to join";
+/// Clients take the tokens after the waker's.
+pub const WAKER: Token = Token(MAX_LISTENERS);
 
 /// A server message for communication between poker server threads. This
 /// message is never sent directly to poker clients, but fields within the
@@ -43,52 +97,290 @@ enum ServerData {
     /// An acknowledgement of a client message, signaling that the client's
     /// command was successfully processed by the game thread.
     Ack(ClientMessage),
+    /// Game events that all clients are notified of and the hand they
+    /// occurred during.
+    Events(HandId, VecDeque<GameEvent>),
+    /// Net results of every user over the session, which all clients are
+    /// notified of once the session's over.
+    Leaderboard(Leaderboard),
+    /// The game with every player's hole cards showing, which only the
+    /// operator is sent.
+    OmniscientView(GameView),
     /// A server message sent to a specific client.
     Response {
         username: Username,
         data: Box<ServerMessage>,
     },
-    /// Game state represented as a string.
+    /// The game's current phase.
+    Phase(GamePhase),
+    /// The game's current phase represented as a string, which replaces
+    /// the last one if a client hasn't been written it yet.
+    PhaseStatus(String),
+    /// A one-off status all clients are notified of (e.g., that the
+    /// server's shutting down), which is never replaced by newer ones.
     Status(String),
+    /// Whose turn it is and what they can do, which all clients are
+    /// notified of.
+    TurnAnnouncement {
+        username: Username,
+        choices_summary: String,
+    },
     /// Mapping of usernames to their game views.
-    Views(HashMap<Username, GameView>),
+    Views(GameViews),
+}
+
+/// Server data stamped with its sequence number.
+type SequencedData = (Seq, ServerData);
+
+/// Relays server data from the game thread to the IO thread, stamping
+/// each with the next sequence number so clients can tell what order it
+/// was sent in.
+struct ServerDataSender {
+    seq: Cell<Seq>,
+    tx: Sender<SequencedData>,
+}
+
+impl ServerDataSender {
+    fn new(tx: Sender<SequencedData>) -> Self {
+        Self {
+            seq: Cell::new(0),
+            tx,
+        }
+    }
+
+    fn send(&self, data: ServerData) -> Result<(), Error> {
+        let seq = self.seq.get() + 1;
+        self.seq.set(seq);
+        self.tx.send((seq, data))?;
+        Ok(())
+    }
 }
 
-fn token_to_string(token: &Token) -> String {
-    let id = token.0;
-    format!("token({id})")
+/// Receive everything the game thread has sent so far in the order it was
+/// sent, along with whether the game thread hung up.
+fn recv_server_data(rx_server: &Receiver<SequencedData>) -> (Vec<SequencedData>, bool) {
+    let mut received = vec![];
+    loop {
+        match rx_server.try_recv() {
+            Ok(data) => received.push(data),
+            Err(TryRecvError::Empty) => return (received, false),
+            Err(TryRecvError::Disconnected) => return (received, true),
+        }
+    }
 }
 
 pub struct ServerTimeouts {
     pub action: Duration,
+    /// How long the lobby counts down before the game's autostart policy
+    /// starts the game, giving late users a chance to join.
+    pub autostart: Duration,
     pub connect: Duration,
+    /// How long a client has to finish sending a frame once it starts
+    /// sending it before it's removed, so clients can't hold a
+    /// connection open by trickling out a frame that never ends.
+    pub frame: Duration,
+    /// How long a client can go without sending anything before it's
+    /// pinged. Zero disables heartbeats.
+    pub heartbeat: Duration,
+    /// How long a pinged client has to respond before it's removed.
+    pub heartbeat_response: Duration,
     pub poll: Duration,
-    pub step: Duration,
+    /// How long to pause on each kind of state before stepping it.
+    pub steps: StepDurations,
+    /// How long changes to the game are batched before views are sent.
+    /// Views are always sent before a user is signaled that it's their
+    /// turn. Zero sends views after every change.
+    pub view_batch: Duration,
+}
+
+impl ServerTimeouts {
+    fn get_step_timeout(&self, state: &PokerState) -> Duration {
+        let hint = state.step_hint(&self.steps);
+        if hint.waits_for_action {
+            self.action
+        } else {
+            hint.min_wait
+        }
+    }
 }
 
 impl Default for ServerTimeouts {
     fn default() -> Self {
         Self {
             action: DEFAULT_ACTION_TIMEOUT,
+            autostart: DEFAULT_AUTOSTART_TIMEOUT,
             connect: DEFAULT_CONNECT_TIMEOUT,
+            frame: DEFAULT_FRAME_TIMEOUT,
+            heartbeat: DEFAULT_HEARTBEAT_TIMEOUT,
+            heartbeat_response: DEFAULT_HEARTBEAT_RESPONSE_TIMEOUT,
             poll: DEFAULT_POLL_TIMEOUT,
-            step: DEFAULT_STEP_TIMEOUT,
+            steps: StepDurations::default(),
+            view_batch: DEFAULT_VIEW_BATCH_TIMEOUT,
+        }
+    }
+}
+
+/// Limits on simultaneous connections that keep a single host from
+/// exhausting the server's tokens with connections that never declare
+/// a username. Connections beyond these limits are closed as soon as
+/// they're accepted.
+pub struct ConnectionLimits {
+    /// Connections from the same IP, whether they've declared a username
+    /// or not.
+    pub per_ip: usize,
+    /// Connections across all IPs that haven't had their username
+    /// confirmed by the game yet.
+    pub unconfirmed: usize,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            per_ip: DEFAULT_MAX_CONNECTIONS_PER_IP,
+            unconfirmed: DEFAULT_MAX_UNCONFIRMED_CONNECTIONS,
+        }
+    }
+}
+
+/// Token bucket limits on how many commands each user can send. Every
+/// command takes a token from the user's bucket, and buckets refill at a
+/// steady rate up to their burst. Commands sent with an empty bucket are
+/// dropped, and users that keep sending them are removed.
+#[derive(Clone, Copy)]
+pub struct RateLimits {
+    /// How long a user can keep sending commands with an empty bucket
+    /// before they're removed. Backing off until the bucket refills
+    /// resets this.
+    pub abuse: Duration,
+    /// Most commands a user can send at once.
+    pub burst: f32,
+    /// Commands a user can send per second once they've used up their
+    /// burst.
+    pub per_second: f32,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            abuse: DEFAULT_RATE_LIMIT_ABUSE_TIMEOUT,
+            burst: DEFAULT_RATE_LIMIT_BURST,
+            per_second: DEFAULT_RATE_LIMIT_PER_SECOND,
+        }
+    }
+}
+
+/// When users are warned that a session with a max length is ending.
+pub struct SessionWarnings {
+    /// How long before the session ends each warning is sent.
+    pub before: Vec<Duration>,
+    /// How long before the session ends new hands stop being started,
+    /// giving the last hand time to finish.
+    pub grace: Duration,
+}
+
+impl Default for SessionWarnings {
+    fn default() -> Self {
+        Self {
+            before: DEFAULT_SESSION_WARNINGS.to_vec(),
+            grace: DEFAULT_SESSION_GRACE,
         }
     }
 }
 
+/// What happens to players that don't act in time, after they're forced
+/// to check or fold. Timeouts only count against players while they time
+/// out turn after turn, so acting starts them over. Other commands don't,
+/// since they don't show players are back to play.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TimeoutPolicy {
+    /// Players keep their seat however many times they time out.
+    FoldOnly,
+    /// Players sit out as spectators, keeping their stack, after this
+    /// many timeouts in a row.
+    SitOutAfter(usize),
+    /// Players are removed from the game after this many timeouts in a
+    /// row.
+    RemoveAfter(usize),
+}
+
+impl Default for TimeoutPolicy {
+    fn default() -> Self {
+        Self::RemoveAfter(1)
+    }
+}
+
+/// The server's operator, who's sent views with every player's hole
+/// cards while they aren't playing. Only clients that connect with the
+/// secret can connect as the operator, and nobody can rename to the
+/// operator's username.
+#[derive(Clone)]
+pub struct OperatorConfig {
+    pub username: Username,
+    pub secret: OperatorSecret,
+}
+
+impl OperatorConfig {
+    pub fn is_operator(&self, username: &str) -> bool {
+        self.username == username
+    }
+}
+
 #[derive(Default)]
 pub struct PokerConfig {
+    /// Optional clock for the game thread to tell time with. Defaults to
+    /// the system's clock.
+    pub clock: Option<Arc<dyn Clock>>,
+    pub connection_limits: ConnectionLimits,
     pub game_settings: GameSettings,
+    /// Optional journal of accepted commands, forced actions, and game
+    /// events for settling disputes.
+    pub journal: Option<JournalConfig>,
+    /// Optional length of the session. Once it's up, no new hands are
+    /// started and everyone is shown how they did after the last hand.
+    pub max_session: Option<Duration>,
+    /// Optional address to serve Prometheus-style metrics from.
+    pub metrics_addr: Option<String>,
+    /// Optional operator of the server.
+    pub operator: Option<OperatorConfig>,
+    /// Formats to play in order. The game starts with the first format
+    /// rather than `game_settings` if there are any, and the last format
+    /// is played indefinitely.
+    pub playlist: Vec<PlaylistEntry>,
+    pub rate_limits: RateLimits,
     pub server_timeouts: ServerTimeouts,
+    pub session_warnings: SessionWarnings,
+    pub timeout_policy: TimeoutPolicy,
+}
+
+impl PokerConfig {
+    /// Check that the game settings and every format in the playlist make
+    /// for playable games. Servers refuse to start with configs that
+    /// don't.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.game_settings.validate()?;
+        for entry in &self.playlist {
+            entry.settings.validate()?;
+        }
+        Ok(())
+    }
 }
 
 impl From<GameSettings> for PokerConfig {
     fn from(value: GameSettings) -> Self {
         let server_timeouts = ServerTimeouts::default();
         Self {
+            clock: None,
+            connection_limits: ConnectionLimits::default(),
             game_settings: value,
+            journal: None,
+            max_session: None,
+            metrics_addr: None,
+            operator: None,
+            playlist: Vec::new(),
+            rate_limits: RateLimits::default(),
             server_timeouts,
+            session_warnings: SessionWarnings::default(),
+            timeout_policy: TimeoutPolicy::default(),
         }
     }
 }
@@ -97,10 +389,528 @@ impl From<ServerTimeouts> for PokerConfig {
     fn from(value: ServerTimeouts) -> Self {
         let game_config = GameSettings::default();
         Self {
+            clock: None,
+            connection_limits: ConnectionLimits::default(),
             game_settings: game_config,
+            journal: None,
+            max_session: None,
+            metrics_addr: None,
+            operator: None,
+            playlist: Vec::new(),
+            rate_limits: RateLimits::default(),
             server_timeouts: value,
+            session_warnings: SessionWarnings::default(),
+            timeout_policy: TimeoutPolicy::default(),
+        }
+    }
+}
+
+/// How long a format in a playlist is played for.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaylistLength {
+    Hands(u64),
+    Minutes(u64),
+}
+
+/// A format in a playlist, like a cash game that's followed by a few
+/// hands of a bomb pot game.
+#[derive(Debug, Deserialize)]
+pub struct PlaylistEntry {
+    pub name: String,
+    pub settings: GameSettings,
+    pub length: PlaylistLength,
+    /// Whether everyone's stack is reset to the format's starting stack
+    /// when the format starts, like at the start of a tournament.
+    /// Otherwise, stacks carry over from the last format.
+    #[serde(default)]
+    pub reset_stacks: bool,
+}
+
+/// Moves the game through its playlist. Formats only change between
+/// hands, so a format can run over its length to finish its last hand.
+struct Playlist {
+    entries: VecDeque<PlaylistEntry>,
+    length: Option<PlaylistLength>,
+    started_at: Instant,
+    started_hand_id: HandId,
+}
+
+impl Playlist {
+    pub fn new(entries: Vec<PlaylistEntry>) -> Self {
+        Self {
+            entries: entries.into(),
+            length: None,
+            started_at: Instant::now(),
+            started_hand_id: 0,
+        }
+    }
+
+    /// Return the next format if the current one is over, starting it.
+    pub fn next(&mut self, hand_id: HandId, now: Instant) -> Option<PlaylistEntry> {
+        let is_over = match self.length {
+            Some(PlaylistLength::Hands(hands)) => hand_id - self.started_hand_id >= hands,
+            Some(PlaylistLength::Minutes(minutes)) => {
+                now - self.started_at >= Duration::from_secs(60 * minutes)
+            }
+            None => true,
+        };
+        if !is_over {
+            return None;
+        }
+        let entry = self.entries.pop_front()?;
+        self.length = Some(entry.length);
+        self.started_at = now;
+        self.started_hand_id = hand_id;
+        Some(entry)
+    }
+}
+
+/// Counts down to the end of a session with a max length, warning users
+/// along the way.
+struct SessionLimit {
+    deadline: Instant,
+    grace: Duration,
+    /// How long before the deadline each warning that hasn't been sent
+    /// yet is due, longest first.
+    warnings: Vec<Duration>,
+}
+
+impl SessionLimit {
+    /// Return whether the session is close enough to its end that new
+    /// hands shouldn't be started.
+    pub fn is_ending(&self, now: Instant) -> bool {
+        self.deadline.saturating_duration_since(now) <= self.grace
+    }
+
+    pub fn new(max_length: Duration, warnings: &SessionWarnings, now: Instant) -> Self {
+        let mut before = warnings.before.clone();
+        before.sort_unstable_by(|a, b| b.cmp(a));
+        Self {
+            deadline: now + max_length,
+            grace: warnings.grace,
+            warnings: before,
+        }
+    }
+
+    /// Return how long is left in the session if a warning is due.
+    /// Warnings that came due at the same time are sent as one.
+    pub fn take_warning(&mut self, now: Instant) -> Option<Duration> {
+        let remaining = self.deadline.saturating_duration_since(now);
+        let num_due = self
+            .warnings
+            .iter()
+            .take_while(|before| remaining <= **before)
+            .count();
+        if num_due == 0 {
+            return None;
+        }
+        self.warnings.drain(..num_due);
+        Some(remaining)
+    }
+}
+
+/// Describe how long is left in a session, rounding up.
+fn format_remaining(remaining: Duration) -> String {
+    let secs = remaining.as_secs_f32().ceil() as u64;
+    match secs.div_ceil(60) {
+        0 | 1 if secs < 60 => format!("{secs} seconds"),
+        1 => "1 minute".to_string(),
+        mins => format!("{mins} minutes"),
+    }
+}
+
+/// What happens to a player that just timed out.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum TimeoutOutcome {
+    /// They keep their seat no matter what.
+    Stay,
+    /// They sit out after this many more timeouts in a row.
+    SitOutIn(usize),
+    /// They're removed after this many more timeouts in a row.
+    RemoveIn(usize),
+    /// They sit out once the hand's over.
+    SitOut,
+    /// They're removed once the hand's over.
+    Remove,
+}
+
+impl fmt::Display for TimeoutOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (num_timeouts, consequence) = match self {
+            Self::Stay => return write!(f, "you timed out and were acted for"),
+            Self::SitOutIn(num_timeouts) => (num_timeouts, "sit out"),
+            Self::RemoveIn(num_timeouts) => (num_timeouts, "be removed"),
+            Self::SitOut => return write!(f, "you timed out and will sit out after this hand"),
+            Self::Remove => return write!(f, "you timed out and will be removed after this hand"),
+        };
+        match num_timeouts {
+            1 => write!(
+                f,
+                "you timed out, one more timeout and you'll {consequence}"
+            ),
+            _ => write!(
+                f,
+                "you timed out, {num_timeouts} more timeouts in a row and you'll {consequence}"
+            ),
+        }
+    }
+}
+
+/// Counts how many times in a row each player has timed out, deciding
+/// what happens to them according to the timeout policy.
+struct TimeoutStrikes {
+    policy: TimeoutPolicy,
+    strikes: HashMap<Username, usize>,
+}
+
+impl TimeoutStrikes {
+    /// Start a player over, like when they act.
+    pub fn clear(&mut self, username: &str) {
+        self.strikes.remove(username);
+    }
+
+    pub fn new(policy: TimeoutPolicy) -> Self {
+        Self {
+            policy,
+            strikes: HashMap::new(),
+        }
+    }
+
+    /// Count a timeout against a player, returning what happens to them.
+    /// Players that sit out or are removed start over if they come back.
+    pub fn strike(&mut self, username: &str) -> TimeoutOutcome {
+        let (max_strikes, is_removal) = match self.policy {
+            TimeoutPolicy::FoldOnly => return TimeoutOutcome::Stay,
+            TimeoutPolicy::SitOutAfter(max_strikes) => (max_strikes, false),
+            TimeoutPolicy::RemoveAfter(max_strikes) => (max_strikes, true),
+        };
+        let num_strikes = self.strikes.entry(username.to_string()).or_default();
+        *num_strikes += 1;
+        match (max_strikes.saturating_sub(*num_strikes), is_removal) {
+            (0, false) => {
+                self.clear(username);
+                TimeoutOutcome::SitOut
+            }
+            (0, true) => {
+                self.clear(username);
+                TimeoutOutcome::Remove
+            }
+            (remaining, false) => TimeoutOutcome::SitOutIn(remaining),
+            (remaining, true) => TimeoutOutcome::RemoveIn(remaining),
+        }
+    }
+}
+
+/// Tracks when the server last heard from each client so half-open
+/// connections (e.g., a client machine went to sleep) can be detected
+/// without waiting for a write to fail.
+struct Heartbeats {
+    interval: Duration,
+    last_heard: HashMap<Token, Instant>,
+    pings: HashMap<Token, Instant>,
+    timeout: Duration,
+}
+
+impl Heartbeats {
+    /// Return the tokens that need to be pinged and the tokens that
+    /// didn't respond to their ping in time, respectively.
+    pub fn check<'a>(
+        &mut self,
+        tokens: impl IntoIterator<Item = &'a Token>,
+        now: Instant,
+    ) -> (Vec<Token>, Vec<Token>) {
+        let mut tokens_to_ping = Vec::new();
+        let mut expired_tokens = Vec::new();
+        if self.interval.is_zero() {
+            return (tokens_to_ping, expired_tokens);
+        }
+        for token in tokens {
+            let last_heard = *self.last_heard.entry(*token).or_insert(now);
+            match self.pings.get(token) {
+                Some(t) => {
+                    if now - *t >= self.timeout {
+                        expired_tokens.push(*token);
+                    }
+                }
+                None => {
+                    if now - last_heard >= self.interval {
+                        self.pings.insert(*token, now);
+                        tokens_to_ping.push(*token);
+                    }
+                }
+            }
+        }
+        (tokens_to_ping, expired_tokens)
+    }
+
+    pub fn new(interval: Duration, timeout: Duration) -> Self {
+        Self {
+            interval,
+            last_heard: HashMap::new(),
+            pings: HashMap::new(),
+            timeout,
+        }
+    }
+
+    /// Any message from a client is proof that it's still there, so
+    /// this should be called whenever a client's message is read.
+    pub fn record(&mut self, token: Token, now: Instant) {
+        self.last_heard.insert(token, now);
+        self.pings.remove(&token);
+    }
+
+    pub fn remove(&mut self, token: &Token) {
+        self.last_heard.remove(token);
+        self.pings.remove(token);
+    }
+}
+
+/// Whether a user's command fits within their rate limit.
+#[derive(Debug, Eq, PartialEq)]
+enum RateCheck {
+    Allowed,
+    /// The command is dropped, but the user can keep playing.
+    Limited,
+    /// The user has been going over their rate limit for too long and
+    /// should be removed.
+    Abusive,
+}
+
+struct Bucket {
+    tokens: f32,
+    last_refill: Instant,
+    /// When the user first went over their rate limit without backing
+    /// off long enough for their bucket to refill since.
+    limited_since: Option<Instant>,
+}
+
+/// Rate limits each user's commands with a token bucket. Buckets follow
+/// usernames rather than connections so reconnecting doesn't refill them.
+struct RateLimiter {
+    buckets: HashMap<Username, Bucket>,
+    limits: RateLimits,
+}
+
+impl RateLimiter {
+    /// Take a token from the user's bucket for a command, returning
+    /// whether the command fits within their rate limit.
+    pub fn check(&mut self, username: &str, now: Instant) -> RateCheck {
+        let bucket = self
+            .buckets
+            .entry(username.to_string())
+            .or_insert_with(|| Bucket {
+                tokens: self.limits.burst,
+                last_refill: now,
+                limited_since: None,
+            });
+        self.limits.refill(bucket, now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return RateCheck::Allowed;
+        }
+        let limited_since = *bucket.limited_since.get_or_insert(now);
+        if now - limited_since >= self.limits.abuse {
+            RateCheck::Abusive
+        } else {
+            RateCheck::Limited
+        }
+    }
+
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            buckets: HashMap::new(),
+            limits,
+        }
+    }
+
+    /// Forget buckets that have refilled. They'd be the same as new
+    /// buckets anyways.
+    pub fn remove_full(&mut self, now: Instant) {
+        let limits = &self.limits;
+        self.buckets.retain(|_, bucket| {
+            limits.refill(bucket, now);
+            bucket.tokens < limits.burst
+        });
+    }
+}
+
+impl RateLimits {
+    fn refill(&self, bucket: &mut Bucket, now: Instant) {
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        bucket.tokens = (bucket.tokens + self.per_second * elapsed.as_secs_f32()).min(self.burst);
+        bucket.last_refill = now;
+        if bucket.tokens >= self.burst {
+            bucket.limited_since = None;
+        }
+    }
+}
+
+/// Coalesces view updates so a burst of commands results in one view
+/// broadcast rather than a full table's worth of views per command.
+struct ViewBatcher {
+    clock: Arc<dyn Clock>,
+    interval: Duration,
+    /// When the views that were last sent became outdated.
+    dirty_since: Option<Instant>,
+}
+
+impl ViewBatcher {
+    pub fn mark_dirty(&mut self) {
+        let now = self.clock.now();
+        self.dirty_since.get_or_insert(now);
+    }
+
+    pub fn new(interval: Duration, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            interval,
+            dirty_since: None,
+        }
+    }
+
+    /// Return whether views should be sent now, starting a new batch if
+    /// so. Forcing sends outdated views regardless of the interval.
+    pub fn take_due(&mut self, force: bool) -> bool {
+        match self.time_until_due() {
+            Some(remaining) if force || remaining.is_zero() => {
+                self.dirty_since = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Return how long until outdated views should be sent, if there
+    /// are any.
+    pub fn time_until_due(&self) -> Option<Duration> {
+        let now = self.clock.now();
+        self.dirty_since.map(|t| {
+            self.interval
+                .saturating_sub(now.saturating_duration_since(t))
+        })
+    }
+}
+
+/// Messages waiting to be written to each client. A client that stops
+/// reading can't be allowed to pile up full game views, so a newer view
+/// or phase status replaces the one that's waiting to be written. A view
+/// or phase status is only replaced if nothing else was queued after it,
+/// so clients still see the game as it was when each ack, event, or
+/// phase was sent. Other statuses are notices meant for whoever they're
+/// sent to and are never replaced. A client whose queue fills up anyway
+/// is removed.
+///
+/// A message that was only partially written is set aside until the rest
+/// of it is written, and it can't be replaced in the meantime.
+struct WriteQueues {
+    capacity: usize,
+    /// Messages that were partially written to each client.
+    in_flight: HashMap<Token, (ServerMessage, PartialFrame)>,
+    /// Tokens that had a message rejected because their queue was full.
+    overflowed: HashSet<Token>,
+    /// Messages waiting to be written to each client, in sequence order.
+    queues: HashMap<Token, VecDeque<QueuedMessage>>,
+}
+
+/// A message waiting to be written to a client.
+struct QueuedMessage {
+    sequenced: SequencedMessage,
+    /// Whether a newer message of the same kind replaces this one.
+    coalescable: bool,
+}
+
+impl WriteQueues {
+    /// Return the tokens whose queues overflowed since the last drain.
+    pub fn drain_overflowed(&mut self) -> Vec<Token> {
+        self.overflowed.drain().collect()
+    }
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            in_flight: HashMap::new(),
+            overflowed: HashSet::new(),
+            queues: HashMap::new(),
+        }
+    }
+
+    /// Queue a message to be written to a client, replacing the view it
+    /// supersedes. The message is dropped and the client is marked as
+    /// overflowed if its queue is full.
+    ///
+    /// Messages must be pushed in sequence order. Superseded messages are
+    /// removed rather than replaced in place and new messages are only
+    /// ever pushed to the back, so queues never reorder messages.
+    pub fn push(&mut self, token: Token, seq: Seq, msg: ServerMessage) {
+        let coalescable = matches!(
+            msg,
+            ServerMessage::GameView(_) | ServerMessage::OmniscientView(_)
+        );
+        self.push_queued(token, seq, msg, coalescable);
+    }
+
+    /// Queue the game's phase as a status, replacing the phase status it
+    /// supersedes the same way views are replaced.
+    pub fn push_phase_status(&mut self, token: Token, seq: Seq, status: String) {
+        self.push_queued(token, seq, ServerMessage::Status(status), true);
+    }
+
+    fn push_queued(&mut self, token: Token, seq: Seq, msg: ServerMessage, coalescable: bool) {
+        let queue = self.queues.entry(token).or_default();
+        debug_assert!(queue
+            .back()
+            .is_none_or(|queued| queued.sequenced.seq <= seq));
+        if coalescable {
+            let kind = mem::discriminant(&msg);
+            let superseded = queue
+                .iter()
+                .rev()
+                .take_while(|queued| queued.coalescable)
+                .position(|queued| mem::discriminant(&queued.sequenced.msg) == kind);
+            if let Some(idx_from_back) = superseded {
+                queue.remove(queue.len() - 1 - idx_from_back);
+            }
+        }
+        if queue.len() >= self.capacity {
+            self.overflowed.insert(token);
+        } else {
+            queue.push_back(QueuedMessage {
+                sequenced: SequencedMessage { seq, msg },
+                coalescable,
+            });
         }
     }
+
+    /// Return the next message to write to a client along with its frame,
+    /// resuming a partially written message before any queued ones.
+    pub fn pop_front(
+        &mut self,
+        token: &Token,
+    ) -> Option<io::Result<(ServerMessage, PartialFrame)>> {
+        if let Some(write) = self.in_flight.remove(token) {
+            return Some(Ok(write));
+        }
+        let QueuedMessage { sequenced, .. } = self.queues.get_mut(token)?.pop_front()?;
+        Some(PartialFrame::new(&sequenced).map(|frame| (sequenced.msg, frame)))
+    }
+
+    /// Set aside a message that couldn't be completely written so the
+    /// rest of it is written next.
+    pub fn push_in_flight(&mut self, token: Token, msg: ServerMessage, frame: PartialFrame) {
+        self.in_flight.insert(token, (msg, frame));
+    }
+
+    /// Return whether there's nothing left to write to any client.
+    pub fn is_empty(&self) -> bool {
+        self.in_flight.is_empty() && self.queues.values().all(VecDeque::is_empty)
+    }
+
+    pub fn remove(&mut self, token: &Token) {
+        self.in_flight.remove(token);
+        self.overflowed.remove(token);
+        self.queues.remove(token);
+    }
 }
 
 struct UnconfirmedClient {
@@ -133,8 +943,17 @@ impl UnconfirmedClient {
 struct TokenManager {
     pub confirmed_tokens: BTreeMap<Token, TcpStream>,
     confirmed_usernames_to_tokens: HashMap<Username, Token>,
+    ips_to_num_tokens: HashMap<IpAddr, usize>,
+    /// Users and the users they muted. Mutes are kept by username rather
+    /// than by token so they outlive reconnects.
+    mutes: HashMap<Username, HashSet<Username>>,
     recycled_tokens: BTreeSet<Token>,
+    terse_tokens: BTreeSet<Token>,
     token_association_timeout: Duration,
+    tokens_to_ips: BTreeMap<Token, IpAddr>,
+    /// Secrets confirmed clients were issued, which they can present to
+    /// take their username over from a connection that dropped.
+    tokens_to_secrets: BTreeMap<Token, ReconnectSecret>,
     tokens_to_usernames: BTreeMap<Token, Username>,
     unconfirmed_tokens: BTreeMap<Token, UnconfirmedClient>,
     unconfirmed_usernames_to_tokens: HashMap<Username, Token>,
@@ -149,10 +968,26 @@ impl TokenManager {
     /// 3. Associate the token and stream with the token manager.
     ///
     /// This transfers ownership of the stream to the token manager, allowing
-    /// deallocation of the stream wheenver the token is recycled.
-    pub fn associate_token_and_stream(&mut self, token: Token, stream: TcpStream) {
+    /// deallocation of the stream wheenver the token is recycled. The stream's
+    /// peer IP is tracked until then so connections can be limited per IP.
+    pub fn associate_token_and_stream(&mut self, token: Token, stream: TcpStream, ip: IpAddr) {
         let unconfirmed_client = UnconfirmedClient::new(stream);
         self.unconfirmed_tokens.insert(token, unconfirmed_client);
+        self.tokens_to_ips.insert(token, ip);
+        *self.ips_to_num_tokens.entry(ip).or_default() += 1;
+    }
+
+    /// Stop tracking a token's IP, forgetting the IP entirely once it
+    /// has no tokens left.
+    fn disassociate_token_and_ip(&mut self, token: &Token) {
+        if let Some(ip) = self.tokens_to_ips.remove(token) {
+            if let Some(num_tokens) = self.ips_to_num_tokens.get_mut(&ip) {
+                *num_tokens -= 1;
+                if *num_tokens == 0 {
+                    self.ips_to_num_tokens.remove(&ip);
+                }
+            }
+        }
     }
 
     /// Associate a token with a username. This should be called in response
@@ -235,17 +1070,68 @@ impl TokenManager {
             self.confirmed_usernames_to_tokens.get(username),
         ) {
             (Some(token), None) => Ok(*token),
-            (None, Some(token)) => Ok(*token),
+            // A confirmed username takes priority in the rare case that a
+            // user renamed themselves to a username that another client
+            // is trying to connect with.
+            (_, Some(token)) => Ok(*token),
             _ => Err(ClientError::Unassociated),
         }
     }
 
+    pub fn get_num_tokens(&self) -> usize {
+        self.unconfirmed_tokens.len() + self.confirmed_tokens.len()
+    }
+
+    pub fn get_num_tokens_with_ip(&self, ip: &IpAddr) -> usize {
+        self.ips_to_num_tokens.get(ip).copied().unwrap_or_default()
+    }
+
+    pub fn get_num_unconfirmed_tokens(&self) -> usize {
+        self.unconfirmed_tokens.len()
+    }
+
+    pub fn get_verbosity(&self, token: &Token) -> Verbosity {
+        if self.terse_tokens.contains(token) {
+            Verbosity::Terse
+        } else {
+            Verbosity::Verbose
+        }
+    }
+
+    /// Issue a confirmed token's client a secret it can reconnect with,
+    /// returning the secret.
+    pub fn issue_secret(&mut self, token: Token) -> ReconnectSecret {
+        let secret = rand::random();
+        self.tokens_to_secrets.insert(token, secret);
+        secret
+    }
+
+    /// Return whether the user with the given token muted a user.
+    pub fn is_muted(&self, token: &Token, username: &str) -> bool {
+        self.tokens_to_usernames
+            .get(token)
+            .and_then(|muter| self.mutes.get(muter))
+            .is_some_and(|muted| muted.contains(username))
+    }
+
+    /// Mute a user for the confirmed user with the given token.
+    pub fn mute(&mut self, token: &Token, username: Username) -> Result<(), ClientError> {
+        let muter = self.get_confirmed_username_with_token(token)?;
+        self.mutes.entry(muter).or_default().insert(username);
+        Ok(())
+    }
+
     pub fn new(token_association_timeout: Duration) -> Self {
         Self {
             confirmed_tokens: BTreeMap::new(),
             confirmed_usernames_to_tokens: HashMap::new(),
+            ips_to_num_tokens: HashMap::new(),
+            mutes: HashMap::new(),
             recycled_tokens: BTreeSet::new(),
+            terse_tokens: BTreeSet::new(),
             token_association_timeout,
+            tokens_to_ips: BTreeMap::new(),
+            tokens_to_secrets: BTreeMap::new(),
             tokens_to_usernames: BTreeMap::new(),
             unconfirmed_tokens: BTreeMap::new(),
             unconfirmed_usernames_to_tokens: HashMap::new(),
@@ -272,31 +1158,120 @@ impl TokenManager {
         token
     }
 
-    /// Recycle tokens that've gone stale because the client has yet
-    /// to associate a username with itself before the association timeout.
-    pub fn recycle_expired_tokens(&mut self) -> VecDeque<(Token, TcpStream)> {
-        let mut tokens_to_recycle = VecDeque::new();
-        for (token, unknown_client) in self
-            .unconfirmed_tokens
-            .iter_mut()
-            .filter(|(token, _)| !self.tokens_to_usernames.contains_key(token))
-        {
-            let t = Instant::now();
-            let dt = t - unknown_client.t;
-            unknown_client.t = t;
-            unknown_client.timeout += dt;
-            if unknown_client.timeout >= self.token_association_timeout {
-                tokens_to_recycle.push_back(*token);
+    /// Change a confirmed username. This should be called in response to
+    /// the poker game accepting a user's new username.
+    pub fn rename_confirmed_username(
+        &mut self,
+        username: &str,
+        new_username: Username,
+    ) -> Result<(), ClientError> {
+        match self.confirmed_usernames_to_tokens.remove(username) {
+            Some(token) => {
+                // Mutes follow users through renames, both ways.
+                if let Some(muted) = self.mutes.remove(username) {
+                    self.mutes.insert(new_username.clone(), muted);
+                }
+                for muted in self.mutes.values_mut() {
+                    if muted.remove(username) {
+                        muted.insert(new_username.clone());
+                    }
+                }
+                self.tokens_to_usernames.insert(token, new_username.clone());
+                self.confirmed_usernames_to_tokens
+                    .insert(new_username, token);
+                Ok(())
             }
+            None => Err(ClientError::Unassociated),
         }
-        let mut recyclables = VecDeque::new();
-        for token in tokens_to_recycle {
-            match self.unconfirmed_tokens.remove(&token) {
+    }
+
+    /// Hand a confirmed username over to a new token, recycling the token
+    /// it was associated with. This should be called in response to a
+    /// client reconnecting before its old connection was noticed to have
+    /// dropped, and only works if the client presents the secret it was
+    /// issued. The game never hears about the new token, so it's confirmed
+    /// right away. Returns the old token and its stream so it can be
+    /// deregistered.
+    pub fn transfer_username(
+        &mut self,
+        token: Token,
+        username: &str,
+        secret: ReconnectSecret,
+    ) -> Result<(Token, TcpStream), ClientError> {
+        if self.tokens_to_usernames.contains_key(&token) {
+            return Err(ClientError::AlreadyAssociated);
+        } else if self.recycled_tokens.contains(&token) {
+            return Err(ClientError::Expired);
+        }
+        let old_token = *self
+            .confirmed_usernames_to_tokens
+            .get(username)
+            .ok_or(ClientError::Unassociated)?;
+        if self.tokens_to_secrets.get(&old_token) != Some(&secret) {
+            return Err(ClientError::AlreadyAssociated);
+        }
+        let unconfirmed_client = self
+            .unconfirmed_tokens
+            .remove(&token)
+            .ok_or(ClientError::DoesNotExist)?;
+        let old_stream = self.recycle_token(old_token)?;
+        self.confirmed_tokens
+            .insert(token, unconfirmed_client.stream);
+        self.tokens_to_secrets.insert(token, secret);
+        self.tokens_to_usernames.insert(token, username.to_string());
+        self.confirmed_usernames_to_tokens
+            .insert(username.to_string(), token);
+        Ok((old_token, old_stream))
+    }
+
+    /// Change how much a token's client hears from the server. Verbosity
+    /// is forgotten when the token is recycled.
+    pub fn set_verbosity(&mut self, token: Token, verbosity: Verbosity) {
+        match verbosity {
+            Verbosity::Verbose => self.terse_tokens.remove(&token),
+            Verbosity::Terse => self.terse_tokens.insert(token),
+        };
+    }
+
+    /// Unmute a user for the confirmed user with the given token.
+    pub fn unmute(&mut self, token: &Token, username: &str) -> Result<(), ClientError> {
+        let muter = self.get_confirmed_username_with_token(token)?;
+        if let Some(muted) = self.mutes.get_mut(&muter) {
+            muted.remove(username);
+            if muted.is_empty() {
+                self.mutes.remove(&muter);
+            }
+        }
+        Ok(())
+    }
+
+    /// Recycle tokens that've gone stale because the client has yet
+    /// to associate a username with itself before the association timeout.
+    pub fn recycle_expired_tokens(&mut self) -> VecDeque<(Token, TcpStream)> {
+        let mut tokens_to_recycle = VecDeque::new();
+        for (token, unknown_client) in self
+            .unconfirmed_tokens
+            .iter_mut()
+            .filter(|(token, _)| !self.tokens_to_usernames.contains_key(token))
+        {
+            let t = Instant::now();
+            let dt = t - unknown_client.t;
+            unknown_client.t = t;
+            unknown_client.timeout += dt;
+            if unknown_client.timeout >= self.token_association_timeout {
+                tokens_to_recycle.push_back(*token);
+            }
+        }
+        let mut recyclables = VecDeque::new();
+        for token in tokens_to_recycle {
+            match self.unconfirmed_tokens.remove(&token) {
                 Some(unconfirmed_client) => {
                     recyclables.push_back((token, unconfirmed_client.stream))
                 }
                 None => unreachable!("an unassociated token is always unconfirmed"),
             }
+            self.disassociate_token_and_ip(&token);
+            self.terse_tokens.remove(&token);
             self.recycled_tokens.insert(token);
         }
         recyclables
@@ -306,8 +1281,14 @@ impl TokenManager {
     /// unfaithful, or when a user leaves the game.
     pub fn recycle_token(&mut self, token: Token) -> Result<TcpStream, ClientError> {
         if let Some(username) = self.tokens_to_usernames.remove(&token) {
-            self.unconfirmed_usernames_to_tokens.remove(&username);
-            self.confirmed_usernames_to_tokens.remove(&username);
+            // Only remove usernames that're actually associated with the token
+            // since a username could've been taken over by a rename.
+            if self.unconfirmed_usernames_to_tokens.get(&username) == Some(&token) {
+                self.unconfirmed_usernames_to_tokens.remove(&username);
+            }
+            if self.confirmed_usernames_to_tokens.get(&username) == Some(&token) {
+                self.confirmed_usernames_to_tokens.remove(&username);
+            }
         }
         let stream = match (
             self.unconfirmed_tokens.remove(&token),
@@ -318,40 +1299,245 @@ impl TokenManager {
             (None, None) => return Err(ClientError::DoesNotExist),
             _ => unreachable!("a token must be either unconfirmed or confirmed"),
         };
+        self.disassociate_token_and_ip(&token);
+        self.terse_tokens.remove(&token);
+        self.tokens_to_secrets.remove(&token);
         self.recycled_tokens.insert(token);
         Ok(stream)
     }
 }
 
-/// Run the poker server in two separate threads. The parent thread manages
-/// the poker game state while the child thread manages non-blocking networking
-/// IO.
+/// Drains a poker server so it can be stopped without cutting a hand
+/// short. Drainers can be cloned and moved elsewhere, like into a signal
+/// handler.
+#[derive(Clone)]
+pub struct Drainer {
+    draining: Arc<AtomicBool>,
+    waker: Arc<Waker>,
+}
+
+impl Drainer {
+    /// Stop letting new users connect and stop the server once the
+    /// current hand is over.
+    pub fn drain(&self) -> Result<(), Error> {
+        self.draining.store(true, Ordering::Relaxed);
+        self.waker.wake()?;
+        Ok(())
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+}
+
+/// A handle to a poker server running in the background. Dropping the
+/// handle detaches the server rather than stopping it.
+pub struct ServerHandle {
+    addrs: Vec<SocketAddr>,
+    draining: Arc<AtomicBool>,
+    game_thread: JoinHandle<Result<(), Error>>,
+    io_thread: JoinHandle<Result<(), Error>>,
+    shutdown: Arc<AtomicBool>,
+    waker: Arc<Waker>,
+}
+
+impl ServerHandle {
+    /// The first address the server is bound to. Useful for finding out
+    /// which port the OS assigned when binding to port zero.
+    pub fn addr(&self) -> SocketAddr {
+        self.addrs[0]
+    }
+
+    /// Every address the server is bound to, in the order they were
+    /// given.
+    pub fn addrs(&self) -> &[SocketAddr] {
+        &self.addrs
+    }
+
+    /// Stop the server once the current hand is over and wait for its
+    /// threads to exit. See [`Drainer::drain`].
+    pub fn drain(self) -> Result<(), Error> {
+        self.drainer().drain()?;
+        self.join()
+    }
+
+    pub fn drainer(&self) -> Drainer {
+        Drainer {
+            draining: self.draining.clone(),
+            waker: self.waker.clone(),
+        }
+    }
+
+    fn join_threads(self) -> (Result<(), Error>, Result<(), Error>) {
+        let game_result = self
+            .game_thread
+            .join()
+            .unwrap_or_else(|_| Err(anyhow!("game thread panicked")));
+        // The IO thread finishes writing to clients before following a
+        // game thread that's done draining, but it has to be told to
+        // stop if the game thread failed. Either way, it's woken up to
+        // notice the game thread is gone.
+        if game_result.is_err() {
+            self.shutdown.store(true, Ordering::Relaxed);
+        }
+        let io_result = self.waker.wake().map_err(Error::from).and_then(|()| {
+            self.io_thread
+                .join()
+                .unwrap_or_else(|_| Err(anyhow!("IO thread panicked")))
+        });
+        (game_result, io_result)
+    }
+
+    /// Block until the server stops because of an error or because it
+    /// finished draining.
+    pub fn join(self) -> Result<(), Error> {
+        let (game_result, io_result) = self.join_threads();
+        game_result.and(io_result)
+    }
+
+    /// Stop the server, disconnecting all clients, and wait for its
+    /// threads to exit. The metrics endpoint, if any, keeps being served
+    /// until the process exits.
+    pub fn shutdown(self) -> Result<(), Error> {
+        self.shutdown.store(true, Ordering::Relaxed);
+        self.waker.wake()?;
+        let (game_result, io_result) = self.join_threads();
+        // The game and IO threads can fail to relay messages to one another
+        // while they're both on their way out.
+        let is_disconnected = |error: &Error| {
+            error.is::<SendError<SequencedData>>() || error.is::<SendError<ClientMessage>>()
+        };
+        match (game_result, io_result) {
+            (Err(error), _) | (_, Err(error)) if !is_disconnected(&error) => Err(error),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Run the poker server, blocking until it fails. See [`spawn`] for
+/// running a server that can be shut down or drained.
 pub fn run(addr: &str, config: PokerConfig) -> Result<(), Error> {
-    let addr = addr.parse()?;
-    let max_network_events = MAX_NETWORK_EVENTS_PER_USER * config.game_settings.max_users;
+    spawn(addr, config)?.join()
+}
+
+/// Run the poker server on several addresses at once, blocking until it
+/// fails. See [`spawn_multi`].
+pub fn run_multi(addrs: Vec<SocketAddr>, config: PokerConfig) -> Result<(), Error> {
+    spawn_multi(addrs, config)?.join()
+}
+
+/// Run the poker server in two separate background threads. One thread
+/// manages the poker game state while the other manages non-blocking
+/// networking IO. The server is bound before this returns, so clients can
+/// connect as soon as it does.
+pub fn spawn(addr: &str, config: PokerConfig) -> Result<ServerHandle, Error> {
+    spawn_multi(vec![addr.parse()?], config)
+}
+
+/// Run the poker server like [`spawn`], but listening on each of the
+/// addresses, like an IPv4 and an IPv6 address. Users from every address
+/// play in the same game.
+pub fn spawn_multi(addrs: Vec<SocketAddr>, config: PokerConfig) -> Result<ServerHandle, Error> {
+    if addrs.is_empty() || addrs.len() > MAX_LISTENERS {
+        bail!("can only listen on 1-{MAX_LISTENERS} addresses");
+    }
+    config.validate()?;
+    let max_users = config
+        .playlist
+        .iter()
+        .map(|entry| entry.settings.max_users + entry.settings.max_railbirds)
+        .fold(
+            config.game_settings.max_users + config.game_settings.max_railbirds,
+            max,
+        );
+    let max_network_events = MAX_NETWORK_EVENTS_PER_USER * max_users;
 
     let (tx_client, rx_client): (Sender<ClientMessage>, Receiver<ClientMessage>) = channel();
-    let (tx_server, rx_server): (Sender<ServerData>, Receiver<ServerData>) = channel();
+    let (tx_server, rx_server): (Sender<SequencedData>, Receiver<SequencedData>) = channel();
 
     let mut poll = Poll::new()?;
-    let waker = Waker::new(poll.registry(), WAKER)?;
+    let waker = Arc::new(Waker::new(poll.registry(), WAKER)?);
+    let mut listeners = addrs
+        .into_iter()
+        .map(TcpListener::bind)
+        .collect::<Result<Vec<_>, _>>()?;
+    let addrs = listeners
+        .iter()
+        .map(TcpListener::local_addr)
+        .collect::<Result<Vec<_>, _>>()?;
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let draining = Arc::new(AtomicBool::new(false));
+
+    // Server threads log to whichever subscriber was in scope when the
+    // server was spawned rather than only the global one, so embedders
+    // and tests can capture a server's logs on their own.
+    let dispatch = dispatcher::get_default(Dispatch::clone);
+
+    // Metrics are shared by all threads and are optionally served
+    // from their own thread. The metrics address is bound here so the
+    // server fails to start rather than running without metrics.
+    let metrics = Arc::new(Metrics::default());
+    if let Some(metrics_addr) = config.metrics_addr.as_deref() {
+        let listener = std::net::TcpListener::bind(metrics_addr)
+            .map_err(|error| anyhow!("couldn't serve metrics from {metrics_addr}: {error}"))?;
+        let metrics = metrics.clone();
+        let dispatch = dispatch.clone();
+        info!(addr = %metrics_addr, "serving metrics");
+        thread::spawn(move || {
+            dispatcher::with_default(&dispatch, || metrics::serve(listener, metrics))
+        });
+    }
+    let io_dispatch = dispatch.clone();
+    let io_metrics = metrics.clone();
+    let io_shutdown = shutdown.clone();
+    let io_draining = draining.clone();
+    let io_operator = config.operator.clone();
 
     // This thread is where the actual networking happens for non-blocking IO.
     // A server is bound to the address and manages connections to clients.
     // Messages from the main thread are queued for each client/user
     // connection.
-    thread::spawn(move || -> Result<(), Error> {
+    let io_thread = thread::spawn(move || -> Result<(), Error> {
+        let _dispatch = dispatcher::set_default(&io_dispatch);
         let mut events = Events::with_capacity(max_network_events);
-        let mut messages_to_process: HashMap<Token, VecDeque<ClientMessage>> = HashMap::new();
-        let mut messages_to_write: HashMap<Token, VecDeque<ServerMessage>> = HashMap::new();
-        let mut server = TcpListener::bind(addr)?;
+        let mut messages_to_process: HashMap<Token, VecDeque<ClientFrame>> = HashMap::new();
+        // Frames can arrive over several readable events, so whatever's
+        // been read of them is kept with the connection until they're done.
+        let mut frame_readers: HashMap<Token, FrameReader> = HashMap::new();
+        let mut messages_to_write = WriteQueues::new(max_network_events);
+        // Messages the IO thread sends on its own are sequenced after the
+        // last update from the game thread.
+        let mut latest_seq: Seq = 0;
         let mut token_manager = TokenManager::new(config.server_timeouts.connect);
+        let mut heartbeats = Heartbeats::new(
+            config.server_timeouts.heartbeat,
+            config.server_timeouts.heartbeat_response,
+        );
+        let mut rate_limiter = RateLimiter::new(config.rate_limits);
         let mut tokens_to_remove: HashSet<Token> = HashSet::new();
         let mut tokens_to_reregister: HashSet<Token> = HashSet::new();
-        poll.registry()
-            .register(&mut server, SERVER, Interest::READABLE)?;
+        // When the game thread hung up after draining, if it has.
+        let mut game_over_at: Option<Instant> = None;
+        for (listener_idx, listener) in listeners.iter_mut().enumerate() {
+            poll.registry().register(
+                listener,
+                Token(SERVER.0 + listener_idx),
+                Interest::READABLE,
+            )?;
+        }
 
         loop {
+            // Dropping the listener and streams on the way out disconnects
+            // all clients. Clients get whatever the game thread had left to
+            // say to them before it hung up, unless they aren't reading.
+            if io_shutdown.load(Ordering::Relaxed)
+                || game_over_at.is_some_and(|t| {
+                    messages_to_write.is_empty() || t.elapsed() >= MAX_FINAL_WRITE_TIME
+                })
+            {
+                return Ok(());
+            }
+
             if let Err(error) = poll.poll(&mut events, Some(config.server_timeouts.poll)) {
                 match error.kind() {
                     io::ErrorKind::Interrupted => continue,
@@ -359,13 +1545,197 @@ pub fn run(addr: &str, config: PokerConfig) -> Result<(), Error> {
                 }
             }
 
+            // Relay whatever the game thread sent to the respective clients
+            // no matter which events fired. Several sends can share one wake,
+            // so data sent just after a drain would otherwise wait on the next
+            // unrelated wake.
+            let (received, hung_up) = recv_server_data(&rx_server);
+            if hung_up {
+                game_over_at.get_or_insert_with(Instant::now);
+            }
+            for (seq, msg) in received {
+                latest_seq = seq;
+                match msg {
+                    // Acks are effectively successful responses to client
+                    // messages and are relayed to all clients.
+                    ServerData::Ack(msg) => {
+                        // We only need to check this connect edge case because all other
+                        // client commands can only go through to the parent thread if the
+                        // client's username has already been confirmed by the parent
+                        // thread.
+                        if msg.command == UserCommand::Connect {
+                            let confirmed = token_manager
+                                .get_token_with_username(&msg.username)
+                                .ok()
+                                .filter(|token| token_manager.confirm_username(*token).is_ok());
+                            // Confirmed clients get a secret they can reconnect with
+                            // before they hear they're connected.
+                            if let Some(token) = confirmed {
+                                info!(
+                                    token = token.0,
+                                    username = %msg.username,
+                                    "confirmed username"
+                                );
+                                let secret = token_manager.issue_secret(token);
+                                let msg = ServerMessage::ReconnectSecret(secret);
+                                messages_to_write.push(token, seq, msg);
+                            }
+                            // The client disconnected before the server could confirm their
+                            // username even though the username was OK. A bit of an edge case,
+                            // we need to notify the main thread that they disconnected. We'll
+                            // still send out the acknowledgement to other clients saying that
+                            // they were able to connect briefly.
+                            if confirmed.is_none() {
+                                let msg = ClientMessage {
+                                    username: msg.username.clone(),
+                                    command: UserCommand::Leave,
+                                };
+                                tx_client.send(msg)?;
+                            }
+                        }
+                        // Renames need to be reflected in the token manager before
+                        // any new views are sent so the client keeps receiving them.
+                        if let UserCommand::Rename(ref new_username) = msg.command {
+                            token_manager
+                                .rename_confirmed_username(&msg.username, new_username.clone())
+                                .ok();
+                        }
+                        // Users don't hear about what the users they muted
+                        // do, except for the actions that make up the hand
+                        // and renames so they can keep track of who's who.
+                        let is_mutable = !matches!(
+                            msg.command,
+                            UserCommand::Rename(_) | UserCommand::TakeAction(_)
+                        );
+                        for token in token_manager.confirmed_tokens.keys().filter(|token| {
+                            !is_mutable || !token_manager.is_muted(token, &msg.username)
+                        }) {
+                            let msg = ServerMessage::Ack(msg.clone());
+                            messages_to_write.push(*token, seq, msg);
+                            tokens_to_reregister.insert(*token);
+                        }
+                    }
+                    // Game events are relayed to all clients.
+                    ServerData::Events(hand_id, events) => {
+                        for token in token_manager.confirmed_tokens.keys() {
+                            for event in events.iter() {
+                                let msg = ServerMessage::GameEvent(hand_id, event.clone());
+                                messages_to_write.push(*token, seq, msg);
+                            }
+                            tokens_to_reregister.insert(*token);
+                        }
+                    }
+                    // The final leaderboard goes to all clients.
+                    ServerData::Leaderboard(leaderboard) => {
+                        for token in token_manager.confirmed_tokens.keys() {
+                            let msg = ServerMessage::Leaderboard(leaderboard.clone());
+                            messages_to_write.push(*token, seq, msg);
+                            tokens_to_reregister.insert(*token);
+                        }
+                    }
+                    // A response goes to a single client. We can safely ignore cases where a
+                    // client no longer exists to receive a response because the response
+                    // is meant just for the client.
+                    // Omniscient views are refused here so they can't reach
+                    // anyone but the operator even if they're sent as a response.
+                    ServerData::Response { username, data } => {
+                        if matches!(*data, ServerMessage::OmniscientView(_))
+                            && !io_operator
+                                .as_ref()
+                                .is_some_and(|operator| operator.is_operator(&username))
+                        {
+                            error!(%username, "refused to send an omniscient view");
+                        } else if let Ok(token) = token_manager.get_token_with_username(&username) {
+                            messages_to_write.push(token, seq, *data);
+                            tokens_to_reregister.insert(token);
+                        }
+                    }
+                    // Omniscient views only ever go to the operator.
+                    ServerData::OmniscientView(view) => {
+                        if let Some(token) = io_operator.as_ref().and_then(|operator| {
+                            token_manager
+                                .get_token_with_username(&operator.username)
+                                .ok()
+                        }) {
+                            let msg = ServerMessage::OmniscientView(Arc::new(view));
+                            messages_to_write.push(token, seq, msg);
+                            tokens_to_reregister.insert(token);
+                        }
+                    }
+                    // The game phase goes to all clients, just like the status.
+                    ServerData::Phase(phase) => {
+                        for token in token_manager.confirmed_tokens.keys() {
+                            let msg = ServerMessage::Phase(phase.clone());
+                            messages_to_write.push(*token, seq, msg);
+                            tokens_to_reregister.insert(*token);
+                        }
+                    }
+                    // Server status is a game status update to all clients
+                    // that want to hear it.
+                    ServerData::PhaseStatus(status) => {
+                        for token in token_manager.confirmed_tokens.keys().filter(|token| {
+                            token_manager.get_verbosity(token) == Verbosity::Verbose
+                        }) {
+                            messages_to_write.push_phase_status(*token, seq, status.clone());
+                            tokens_to_reregister.insert(*token);
+                        }
+                    }
+                    ServerData::Status(msg) => {
+                        for token in token_manager.confirmed_tokens.keys().filter(|token| {
+                            token_manager.get_verbosity(token) == Verbosity::Verbose
+                        }) {
+                            let msg = ServerMessage::Status(msg.clone());
+                            messages_to_write.push(*token, seq, msg);
+                            tokens_to_reregister.insert(*token);
+                        }
+                    }
+                    // Turn announcements are structured, so they go to all
+                    // clients regardless of verbosity.
+                    ServerData::TurnAnnouncement {
+                        username,
+                        choices_summary,
+                    } => {
+                        for token in token_manager.confirmed_tokens.keys() {
+                            let msg = ServerMessage::TurnAnnouncement {
+                                username: username.clone(),
+                                choices_summary: choices_summary.clone(),
+                            };
+                            messages_to_write.push(*token, seq, msg);
+                            tokens_to_reregister.insert(*token);
+                        }
+                    }
+                    // Views go to all clients. We can safely ignore cases where a client
+                    // no longer exists to receive a view because the view is specific
+                    // to the client. Views are only built for clients that'll get them.
+                    ServerData::Views(views) => {
+                        for username in views.usernames() {
+                            if let Ok(token) = token_manager.get_token_with_username(username) {
+                                let view = views.get(username).expect("user has a view");
+                                let msg = ServerMessage::GameView(view);
+                                messages_to_write.push(token, seq, msg);
+                                tokens_to_reregister.insert(token);
+                            }
+                        }
+                    }
+                }
+            }
+            for token in tokens_to_reregister.drain() {
+                if let Ok(stream) = token_manager.get_mut_stream_with_token(&token) {
+                    poll.registry().reregister(
+                        stream,
+                        token,
+                        Interest::READABLE | Interest::WRITABLE,
+                    )?;
+                }
+            }
+
             for event in events.iter() {
                 match event.token() {
-                    SERVER => loop {
-                        // Received an event for the TCP server socket, which
-                        // indicates we can accept a connection.
-                        let mut stream = match server.accept() {
-                            Ok((stream, _)) => stream,
+                    token if token.0 - SERVER.0 < listeners.len() => loop {
+                        // Received an event for one of the TCP server sockets,
+                        // which indicates we can accept a connection.
+                        let (mut stream, peer) = match listeners[token.0 - SERVER.0].accept() {
+                            Ok(accepted) => accepted,
                             Err(error) => {
                                 match error.kind() {
                                     // If we get a `WouldBlock` error we know our
@@ -380,170 +1750,91 @@ pub fn run(addr: &str, config: PokerConfig) -> Result<(), Error> {
                             }
                         };
 
+                        // Refused streams are dropped right away, closing
+                        // the connection before it costs us a token.
+                        let ip = peer.ip();
+                        if token_manager.get_num_tokens_with_ip(&ip)
+                            >= config.connection_limits.per_ip
+                        {
+                            warn!(%ip, "refused connection with too many connections");
+                            continue;
+                        }
+                        if token_manager.get_num_unconfirmed_tokens()
+                            >= config.connection_limits.unconfirmed
+                        {
+                            warn!(%ip, "refused connection with too many unconfirmed connections");
+                            continue;
+                        }
+
                         let token = token_manager.new_token();
                         poll.registry()
                             .register(&mut stream, token, Interest::READABLE)?;
-                        token_manager.associate_token_and_stream(token, stream);
-                        let repr = token_to_string(&token);
-                        debug!("accepted new connection with {repr}");
+                        token_manager.associate_token_and_stream(token, stream, ip);
+                        io_metrics
+                            .accepted_connections
+                            .fetch_add(1, Ordering::Relaxed);
+                        debug!(token = token.0, %ip, "accepted connection");
                     },
-                    WAKER => {
-                        // Drain server messages received from the parent thread so
-                        // they can be relayed to the respective clients.
-                        while let Ok(msg) = rx_server.try_recv() {
-                            match msg {
-                                // Acks are effectively successful responses to client
-                                // messages and are relayed to all clients.
-                                ServerData::Ack(msg) => {
-                                    // We only need to check this connect edge case because all other
-                                    // client commands can only go through to the parent thread if the
-                                    // client's username has already been confirmed by the parent
-                                    // thread.
-                                    if msg.command == UserCommand::Connect {
-                                        let disconnected = token_manager
-                                            .get_token_with_username(&msg.username)
-                                            .map_or(true, |token| {
-                                                token_manager.confirm_username(token).is_err()
-                                            });
-                                        // The client disconnected before the server could confirm their
-                                        // username even though the username was OK. A bit of an edge case,
-                                        // we need to notify the main thread that they disconnected. We'll
-                                        // still send out the acknowledgement to other clients saying that
-                                        // they were able to connect briefly.
-                                        if disconnected {
-                                            let msg = ClientMessage {
-                                                username: msg.username.clone(),
-                                                command: UserCommand::Leave,
-                                            };
-                                            tx_client.send(msg)?;
-                                        }
-                                    }
-                                    for token in token_manager.confirmed_tokens.keys() {
-                                        let msg = ServerMessage::Ack(msg.clone());
-                                        messages_to_write.entry(*token).or_default().push_back(msg);
-                                        tokens_to_reregister.insert(*token);
-                                    }
-                                }
-                                // A response goes to a single client. We can safely ignore cases where a
-                                // client no longer exists to receive a response because the response
-                                // is meant just for the client.
-                                ServerData::Response { username, data } => {
-                                    if let Ok(token) =
-                                        token_manager.get_token_with_username(&username)
-                                    {
-                                        messages_to_write
-                                            .entry(token)
-                                            .or_default()
-                                            .push_back(*data);
-                                        tokens_to_reregister.insert(token);
-                                    }
-                                }
-                                // Server status is a game status update to all clients.
-                                ServerData::Status(msg) => {
-                                    for token in token_manager.confirmed_tokens.keys() {
-                                        let msg = ServerMessage::Status(msg.clone());
-                                        messages_to_write.entry(*token).or_default().push_back(msg);
-                                        tokens_to_reregister.insert(*token);
-                                    }
-                                }
-                                // Views go to all clients. We can safely ignore cases where a client
-                                // no longer exists to receive a view because the view is specific
-                                // to the client.
-                                ServerData::Views(views) => {
-                                    for (username, view) in views {
-                                        if let Ok(token) =
-                                            token_manager.get_token_with_username(&username)
-                                        {
-                                            let msg = ServerMessage::GameView(view);
-                                            messages_to_write
-                                                .entry(token)
-                                                .or_default()
-                                                .push_back(msg);
-                                            tokens_to_reregister.insert(token);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        for token in tokens_to_reregister.drain() {
-                            if let Ok(stream) = token_manager.get_mut_stream_with_token(&token) {
-                                poll.registry().reregister(
-                                    stream,
-                                    token,
-                                    Interest::READABLE | Interest::WRITABLE,
-                                )?;
-                            }
-                        }
-                    }
+                    // Wakes only cut polling short so whatever the game thread
+                    // sent is relayed sooner. It's drained after every poll.
+                    WAKER => {}
                     // Only care about events associated with clients that are
                     // still valid.
                     token if !tokens_to_remove.contains(&token) => {
+                        let _span = debug_span!("client", token = token.0).entered();
                         // Maybe received an event for a TCP connection.
                         if let Ok(stream) = token_manager.get_mut_stream_with_token(&token) {
                             if event.is_writable() {
-                                if let Some(messages) = messages_to_write.get_mut(&token) {
-                                    // Need to handle the case where there's an unresponsive or
-                                    // misbehaving client that doesn't let us write messages to
-                                    // them. If their message queue reaches a certain size, queue
-                                    // them for removal.
-                                    if messages.len() >= max_network_events {
-                                        let repr = token_to_string(&token);
-                                        error!("{repr} has not been receiving and will be removed");
-                                        tokens_to_remove.insert(token);
-                                        continue;
-                                    }
-                                    while let Some(msg) = messages.pop_front() {
-                                        match write_prefixed::<ServerMessage, TcpStream>(
-                                            stream, &msg,
-                                        ) {
-                                            Ok(_) => {
-                                                // Client errors are strict and result in the removal of a connection.
-                                                if let ServerMessage::ClientError(_) = msg {
-                                                    let repr = token_to_string(&token);
-                                                    debug!("{repr}: {msg}");
+                                while let Some(write) = messages_to_write.pop_front(&token) {
+                                    let (msg, mut frame) = write?;
+                                    match frame.write_to(stream) {
+                                        Ok(_) => {
+                                            // Client errors are strict and result in the removal of a connection,
+                                            // except for rate limits which only drop the command.
+                                            if matches!(
+                                                msg,
+                                                ServerMessage::ClientError(ref error)
+                                                    if *error != ClientError::RateLimited
+                                            ) {
+                                                debug!(%msg, "sent strict client error");
+                                                tokens_to_remove.insert(token);
+                                                break;
+                                            }
+                                        }
+                                        Err(error) => {
+                                            match error.kind() {
+                                                // The frame is written with repeated writes, so we know
+                                                // that if any of these occur, then the connection was probably
+                                                // dropped at some point.
+                                                io::ErrorKind::BrokenPipe
+                                                | io::ErrorKind::ConnectionAborted
+                                                | io::ErrorKind::ConnectionReset
+                                                | io::ErrorKind::TimedOut
+                                                | io::ErrorKind::UnexpectedEof => {
+                                                    debug!("connection dropped");
                                                     tokens_to_remove.insert(token);
-                                                    break;
                                                 }
-                                            }
-                                            Err(error) => {
-                                                match error.kind() {
-                                                    // `write_prefixed` uses `write_all` under the hood, so we know
-                                                    // that if any of these occur, then the connection was probably
-                                                    // dropped at some point.
-                                                    io::ErrorKind::BrokenPipe
-                                                    | io::ErrorKind::ConnectionAborted
-                                                    | io::ErrorKind::ConnectionReset
-                                                    | io::ErrorKind::TimedOut
-                                                    | io::ErrorKind::UnexpectedEof => {
-                                                        let repr = token_to_string(&token);
-                                                        debug!("{repr} connection dropped");
-                                                        tokens_to_remove.insert(token);
-                                                    }
-                                                    // Would block "errors" are the OS's way of saying that the
-                                                    // connection is not actually ready to perform this I/O operation.
-                                                    io::ErrorKind::WouldBlock => {
-                                                        // The message couldn't be sent, so we need to push it back
-                                                        // onto the queue so we don't accidentally forget about it.
-                                                        messages.push_front(msg);
-                                                    }
-                                                    // Retry writing in the case that the full message couldn't
-                                                    // be written. This should be infrequent.
-                                                    io::ErrorKind::WriteZero => {
-                                                        let repr = token_to_string(&token);
-                                                        debug!("{repr} got a zero write, but will retry");
-                                                        messages.push_front(msg);
-                                                        continue;
-                                                    }
-                                                    // Other errors we'll consider fatal.
-                                                    _ => bail!(error),
+                                                // Would block "errors" are the OS's way of saying that the
+                                                // connection is not actually ready to perform this I/O operation.
+                                                // The frame may have been partially written, so it has to be
+                                                // finished on the next writable event before anything else is
+                                                // written. Writable interest is kept so that event comes.
+                                                io::ErrorKind::WouldBlock => {
+                                                    messages_to_write
+                                                        .push_in_flight(token, msg, frame);
                                                 }
-                                                poll.registry().reregister(
-                                                    stream,
-                                                    token,
-                                                    Interest::READABLE,
-                                                )?;
-                                                break;
+                                                // Retry writing in the case that the full message couldn't
+                                                // be written. This should be infrequent.
+                                                io::ErrorKind::WriteZero => {
+                                                    debug!("got a zero write, but will retry");
+                                                    messages_to_write
+                                                        .push_in_flight(token, msg, frame);
+                                                    continue;
+                                                }
+                                                // Other errors we'll consider fatal.
+                                                _ => bail!(error),
                                             }
+                                            break;
                                         }
                                     }
                                 }
@@ -551,38 +1842,42 @@ pub fn run(addr: &str, config: PokerConfig) -> Result<(), Error> {
 
                             if event.is_readable() {
                                 // We can (maybe) read from the connection.
+                                let frame_reader = frame_readers
+                                    .entry(token)
+                                    .or_insert_with(|| FrameReader::new(MAX_CLIENT_FRAME_SIZE));
                                 loop {
-                                    match read_prefixed::<ClientMessage, TcpStream>(stream) {
-                                        Ok(mut msg) => {
-                                            msg.username.truncate(MAX_USER_INPUT_LENGTH);
-                                            let messages =
-                                                messages_to_process.entry(token).or_default();
-                                            messages.push_back(msg);
-                                            if messages.len() >= MAX_NETWORK_EVENTS_PER_USER {
-                                                let repr = token_to_string(&token);
-                                                error!(
-                                                    "{repr} has been spamming and will be removed"
-                                                );
-                                                tokens_to_remove.insert(token);
-                                                break;
+                                    match frame_reader.read_from::<ClientFrame, TcpStream>(stream) {
+                                        Ok(frame) => {
+                                            io_metrics.record_client_message(frame.command());
+                                            heartbeats.record(token, Instant::now());
+                                            // Pongs only let us know the client is still there,
+                                            // so they don't go to the game or count towards
+                                            // the rate limit.
+                                            if *frame.command() == UserCommand::Pong {
+                                                continue;
                                             }
+                                            messages_to_process
+                                                .entry(token)
+                                                .or_default()
+                                                .push_back(frame);
                                         }
                                         Err(error) => {
                                             match error.kind() {
-                                                // `read_prefixed` uses `read_exact` under the hood, so we know
-                                                // that an Eof error means the connection was dropped.
+                                                // Reads only come up empty once the connection was dropped,
+                                                // and invalid data means the client can't be trusted.
                                                 io::ErrorKind::BrokenPipe
                                                 | io::ErrorKind::ConnectionAborted
                                                 | io::ErrorKind::ConnectionReset
                                                 | io::ErrorKind::InvalidData
                                                 | io::ErrorKind::TimedOut
                                                 | io::ErrorKind::UnexpectedEof => {
-                                                    let repr = token_to_string(&token);
-                                                    debug!("{repr}'s connection dropped");
+                                                    debug!("connection dropped");
                                                     tokens_to_remove.insert(token);
                                                 }
                                                 // Would block "errors" are the OS's way of saying that the
                                                 // connection is not actually ready to perform this I/O operation.
+                                                // Part of a frame may have been read, which the frame reader
+                                                // keeps until the rest of it arrives.
                                                 io::ErrorKind::WouldBlock => {}
                                                 // Other errors we'll consider fatal.
                                                 _ => {
@@ -603,45 +1898,255 @@ pub fn run(addr: &str, config: PokerConfig) -> Result<(), Error> {
             }
 
             // Process all the messages received from the clients.
-            for (token, msgs) in messages_to_process
+            let now = Instant::now();
+            rate_limiter.remove_full(now);
+            let messages: Vec<_> = messages_to_process
                 .drain()
                 .filter(|(t, _)| !tokens_to_remove.contains(t))
-            {
-                for msg in msgs {
-                    let result = match msg.command {
+                .collect();
+            for (token, msgs) in messages {
+                let _span = debug_span!("client", token = token.0).entered();
+                for frame in msgs {
+                    let result = match frame {
                         // Check if the client wasn't able to associate its token with a username
                         // in time, or if that username is already taken.
-                        UserCommand::Connect => {
-                            token_manager.associate_token_and_username(token, msg.username.clone())
+                        // Draining servers don't take new users.
+                        ClientFrame::Connect(_) | ClientFrame::ConnectAsOperator { .. }
+                            if io_draining.load(Ordering::Relaxed) =>
+                        {
+                            Err(ClientError::ServerDraining)
+                        }
+                        // Only the operator's secret lets anyone connect with the
+                        // operator's username.
+                        ClientFrame::Connect(username) => {
+                            let mut msg = ClientMessage {
+                                username,
+                                command: UserCommand::Connect,
+                            };
+                            msg.sanitize();
+                            if io_operator
+                                .as_ref()
+                                .is_some_and(|operator| operator.is_operator(&msg.username))
+                            {
+                                warn!(%msg, "refused to connect as the operator without the secret");
+                                Err(ClientError::NotOperator)
+                            } else {
+                                token_manager
+                                    .associate_token_and_username(token, msg.username.clone())
+                                    .map(|()| msg)
+                            }
                         }
-                        // Check if the client is being faithful and sending messages with
-                        // the correct username.
-                        _ => match token_manager.get_token_with_username(&msg.username) {
-                            Ok(associated_token) => {
-                                if token == associated_token {
-                                    Ok(())
-                                } else {
-                                    Err(ClientError::Unassociated)
+                        ClientFrame::ConnectAsOperator { username, secret } => {
+                            let mut msg = ClientMessage {
+                                username,
+                                command: UserCommand::Connect,
+                            };
+                            msg.sanitize();
+                            if io_operator.as_ref().is_some_and(|operator| {
+                                operator.is_operator(&msg.username) && operator.secret == secret
+                            }) {
+                                token_manager
+                                    .associate_token_and_username(token, msg.username.clone())
+                                    .map(|()| msg)
+                            } else {
+                                warn!(%msg, "refused to connect as the operator");
+                                Err(ClientError::NotOperator)
+                            }
+                        }
+                        // Reconnecting clients with the right secret take their username
+                        // over from their old connection, which the game never hears
+                        // about, so they're acked here. Anyone else connects as usual,
+                        // except as the operator.
+                        ClientFrame::Reconnect { username, secret } => {
+                            let mut msg = ClientMessage {
+                                username,
+                                command: UserCommand::Connect,
+                            };
+                            msg.sanitize();
+                            match token_manager.transfer_username(token, &msg.username, secret) {
+                                Ok((old_token, mut old_stream)) => {
+                                    info!(
+                                        token = token.0,
+                                        old_token = old_token.0,
+                                        username = %msg.username,
+                                        "reconnected"
+                                    );
+                                    poll.registry().deregister(&mut old_stream)?;
+                                    // Whatever was still queued for the old token is
+                                    // cleaned up along with removed tokens.
+                                    tokens_to_remove.insert(old_token);
+                                    messages_to_write.push(
+                                        token,
+                                        latest_seq,
+                                        ServerMessage::Ack(msg),
+                                    );
+                                    tokens_to_reregister.insert(token);
+                                    continue;
                                 }
+                                Err(ClientError::Unassociated)
+                                    if io_draining.load(Ordering::Relaxed) =>
+                                {
+                                    Err(ClientError::ServerDraining)
+                                }
+                                Err(ClientError::Unassociated)
+                                    if io_operator.as_ref().is_some_and(|operator| {
+                                        operator.is_operator(&msg.username)
+                                    }) =>
+                                {
+                                    Err(ClientError::NotOperator)
+                                }
+                                Err(ClientError::Unassociated) => token_manager
+                                    .associate_token_and_username(token, msg.username.clone())
+                                    .map(|()| msg),
+                                Err(error) => Err(error),
+                            }
+                        }
+                        // Commands are from whoever the connection belongs to, once the
+                        // game has confirmed who that is. Commands over the user's rate
+                        // limit are dropped, and users that don't back off are removed.
+                        ClientFrame::Command(command) => {
+                            match token_manager.get_confirmed_username_with_token(&token) {
+                                Ok(username) => match rate_limiter.check(&username, now) {
+                                    RateCheck::Allowed => {
+                                        let mut msg = ClientMessage { username, command };
+                                        msg.sanitize();
+                                        Ok(msg)
+                                    }
+                                    RateCheck::Limited => Err(ClientError::RateLimited),
+                                    RateCheck::Abusive => {
+                                        error!(%username, "spamming and will be removed");
+                                        tokens_to_remove.insert(token);
+                                        break;
+                                    }
+                                },
+                                Err(error) => Err(error),
                             }
-                            Err(error) => Err(error),
-                        },
+                        }
                     };
-                    let repr = token_to_string(&token);
                     match result {
-                        Ok(_) => {
-                            debug!("{repr}: {msg}");
+                        Ok(msg) => {
+                            // Disconnects are only ever made here when a connection
+                            // drops, so clients can't dodge timeout policies by
+                            // claiming theirs did.
+                            if msg.command == UserCommand::Disconnect {
+                                warn!(%msg, "refused a disconnect sent by a client");
+                                let msg = ServerMessage::ClientError(ClientError::ServerOnly);
+                                messages_to_write.push(token, latest_seq, msg);
+                                tokens_to_reregister.insert(token);
+                                continue;
+                            }
+                            // Only the operator can see every hand, and the game never
+                            // hears from anyone else asking.
+                            if msg.command == UserCommand::ViewAllCards
+                                && !io_operator
+                                    .as_ref()
+                                    .is_some_and(|operator| operator.is_operator(&msg.username))
+                            {
+                                warn!(%msg, "refused to show every hand to a non-operator");
+                                let msg = ServerMessage::ClientError(ClientError::NotOperator);
+                                messages_to_write.push(token, latest_seq, msg);
+                                tokens_to_reregister.insert(token);
+                                continue;
+                            }
+                            // The game doesn't know about usernames that're still being
+                            // confirmed, so renames to those usernames are caught here.
+                            // Nobody can take the operator's username by renaming either.
+                            if let UserCommand::Rename(ref new_username) = msg.command {
+                                if token_manager.get_token_with_username(new_username).is_ok()
+                                    || io_operator
+                                        .as_ref()
+                                        .is_some_and(|operator| operator.is_operator(new_username))
+                                {
+                                    debug!(%msg, "rename to an unconfirmed username");
+                                    let msg =
+                                        ServerMessage::UserError(UserError::UserAlreadyExists);
+                                    messages_to_write.push(token, latest_seq, msg);
+                                    tokens_to_reregister.insert(token);
+                                    continue;
+                                }
+                            }
+                            // Verbosity only concerns this connection, so the game
+                            // never hears about it.
+                            if let UserCommand::SetVerbosity(verbosity) = msg.command {
+                                debug!(%msg, "handled by the IO thread");
+                                token_manager.set_verbosity(token, verbosity);
+                                let msg = ServerMessage::Ack(msg);
+                                messages_to_write.push(token, latest_seq, msg);
+                                tokens_to_reregister.insert(token);
+                                continue;
+                            }
+                            // Mutes are kept with the connection too, so the game
+                            // never hears about them either.
+                            let mute_result = match msg.command {
+                                UserCommand::Mute(ref username) => {
+                                    Some(token_manager.mute(&token, username.clone()))
+                                }
+                                UserCommand::Unmute(ref username) => {
+                                    Some(token_manager.unmute(&token, username))
+                                }
+                                _ => None,
+                            };
+                            if let Some(result) = mute_result {
+                                debug!(%msg, "handled by the IO thread");
+                                let msg = match result {
+                                    Ok(()) => ServerMessage::Ack(msg),
+                                    Err(error) => ServerMessage::ClientError(error),
+                                };
+                                messages_to_write.push(token, latest_seq, msg);
+                                tokens_to_reregister.insert(token);
+                                continue;
+                            }
+                            if msg.command == UserCommand::Connect {
+                                info!(username = %msg.username, "connecting");
+                            } else {
+                                debug!(%msg, "relaying to the game");
+                            }
                             tx_client.send(msg)?
                         }
                         Err(error) => {
-                            debug!("{repr}: {error}");
+                            debug!(%error, "client error");
                             let msg = ServerMessage::ClientError(error);
-                            messages_to_write.entry(token).or_default().push_back(msg);
+                            messages_to_write.push(token, latest_seq, msg);
                             tokens_to_reregister.insert(token);
                         }
                     }
                 }
             }
+            // Ping clients we haven't heard from in a while and remove
+            // clients that didn't respond to their ping in time.
+            let (tokens_to_ping, expired_tokens) =
+                heartbeats.check(token_manager.confirmed_tokens.keys(), Instant::now());
+            for token in tokens_to_ping {
+                messages_to_write.push(token, latest_seq, ServerMessage::Ping);
+                tokens_to_reregister.insert(token);
+            }
+            for token in expired_tokens {
+                debug!(
+                    token = token.0,
+                    "didn't respond to a ping and will be removed"
+                );
+                tokens_to_remove.insert(token);
+            }
+            // Clients that started a frame and never finished it are only
+            // holding onto a connection.
+            let now = Instant::now();
+            for (token, frame_reader) in &frame_readers {
+                if frame_reader.is_stalled(now, config.server_timeouts.frame) {
+                    debug!(
+                        token = token.0,
+                        "didn't finish sending a frame and will be removed"
+                    );
+                    tokens_to_remove.insert(*token);
+                }
+            }
+            // Need to handle the case where there's an unresponsive or
+            // misbehaving client that doesn't let us write messages to
+            // them. If their message queue fills up, queue them for removal.
+            for token in messages_to_write.drain_overflowed() {
+                error!(token = token.0, "not receiving and will be removed");
+                tokens_to_remove.insert(token);
+            }
+
             // Make sure we allow writing errors back to the client.
             for token in tokens_to_reregister.drain() {
                 if let Ok(stream) = token_manager.get_mut_stream_with_token(&token) {
@@ -656,55 +2161,267 @@ pub fn run(addr: &str, config: PokerConfig) -> Result<(), Error> {
             // Recycle all tokens that need to be removed, deregistering their streams
             // with the poll.
             for token in tokens_to_remove.drain() {
-                let repr = token_to_string(&token);
-                debug!("{repr} is being removed");
+                debug!(token = token.0, "removing");
                 if let Ok(username) = token_manager.get_confirmed_username_with_token(&token) {
                     let msg = ClientMessage {
                         username,
-                        command: UserCommand::Leave,
+                        command: UserCommand::Disconnect,
                     };
                     tx_client.send(msg)?;
                 }
                 messages_to_write.remove(&token);
+                heartbeats.remove(&token);
+                frame_readers.remove(&token);
                 if let Ok(mut stream) = token_manager.recycle_token(token) {
                     poll.registry().deregister(&mut stream)?;
                 }
             }
             for (token, mut stream) in token_manager.recycle_expired_tokens() {
-                let repr = token_to_string(&token);
-                debug!("{repr} expired");
+                debug!(token = token.0, "expired");
                 messages_to_write.remove(&token);
+                heartbeats.remove(&token);
+                frame_readers.remove(&token);
                 poll.registry().deregister(&mut stream)?;
             }
+            io_metrics
+                .active_tokens
+                .store(token_manager.get_num_tokens(), Ordering::Relaxed);
         }
     });
 
-    let mut state: PokerState = config.game_settings.into();
+    // The game thread takes over the waker, but the handle still needs it
+    // to stop the IO thread.
+    let game_waker = waker.clone();
+    let game_draining = draining.clone();
+    let tx_server = ServerDataSender::new(tx_server);
+    let game_thread = thread::spawn(move || {
+        dispatcher::with_default(&dispatch, || {
+            run_game(
+                config,
+                metrics,
+                game_draining,
+                rx_client,
+                tx_server,
+                game_waker,
+            )
+        })
+    });
+
+    Ok(ServerHandle {
+        addrs,
+        draining,
+        game_thread,
+        io_thread,
+        shutdown,
+        waker,
+    })
+}
+
+/// Manage the poker game state, stepping the game at fixed intervals and
+/// in response to user commands relayed by the IO thread.
+fn run_game(
+    config: PokerConfig,
+    metrics: Arc<Metrics>,
+    draining: Arc<AtomicBool>,
+    rx_client: Receiver<ClientMessage>,
+    tx_server: ServerDataSender,
+    waker: Arc<Waker>,
+) -> Result<(), Error> {
+    let clock = config.clock.unwrap_or_else(|| Arc::new(SystemClock));
+    let mut playlist = Playlist::new(config.playlist);
+    let mut state: PokerState = match playlist.next(0, clock.now()) {
+        Some(entry) => {
+            info!(format = %entry.name, "starting playlist");
+            entry.settings.into()
+        }
+        None => config.game_settings.into(),
+    };
     let mut status = state.to_string();
+    let mut view_batcher = ViewBatcher::new(config.server_timeouts.view_batch, clock.clone());
+    let mut journal = config.journal.map(Journal::open);
+    let mut autostart_deadline = None;
+    let mut is_draining = false;
+    let mut session = config
+        .max_session
+        .map(|max_length| SessionLimit::new(max_length, &config.session_warnings, clock.now()));
+    let mut is_session_ending = false;
+    let mut is_session_over = false;
+    let mut timer = PhaseTimer::new(clock.now());
+    let mut strikes = TimeoutStrikes::new(config.timeout_policy);
+    let operator_username = config
+        .operator
+        .as_ref()
+        .map(|operator| operator.username.as_str());
     loop {
+        let _span = info_span!("hand", hand_id = state.get_hand_id()).entered();
+
+        // Entries are only buffered while the game steps through a state,
+        // so the journal is never more than a state behind.
+        if let Some(journal) = journal.as_mut() {
+            journal.flush();
+        }
+
+        // Draining servers finish the hand that's being played, stopping
+        // in the lobby before the next one can start.
+        if draining.load(Ordering::Relaxed) {
+            if let PokerState::Lobby(_) = state {
+                info!("drained, shutting down");
+                let msg = ServerData::Status("server is shutting down".to_string());
+                tx_server.send(msg)?;
+                waker.wake()?;
+                return Ok(());
+            }
+            if !is_draining {
+                is_draining = true;
+                info!("draining, shutting down after this hand");
+                let msg = ServerData::Status("server is shutting down after this hand".to_string());
+                tx_server.send(msg)?;
+                waker.wake()?;
+            }
+        }
+
+        // Sessions end in the lobby once the last hand is over, and
+        // users can only watch or leave from then on.
+        if let Some(session) = session.as_mut() {
+            let now = clock.now();
+            if let Some(remaining) = session.take_warning(now) {
+                let status = format!("session ends in {}", format_remaining(remaining));
+                info!("{status}");
+                tx_server.send(ServerData::Status(status))?;
+                waker.wake()?;
+            }
+            if !is_session_ending && session.is_ending(now) {
+                is_session_ending = true;
+                info!("session ending, not starting any more hands");
+                state.end_session();
+                autostart_deadline = None;
+                let msg = ServerData::Status("session is ending, no more hands".to_string());
+                tx_server.send(msg)?;
+                waker.wake()?;
+            }
+            if !is_session_over && state.is_session_over() {
+                is_session_over = true;
+                let leaderboard = state.get_leaderboard();
+                info!(?leaderboard, "session over");
+                tx_server.send(ServerData::Leaderboard(leaderboard))?;
+                let msg = ServerData::Status("session is over, thanks for playing".to_string());
+                tx_server.send(msg)?;
+                waker.wake()?;
+            }
+        }
+
+        if let PokerState::Lobby(_) = state {
+            if let Some(entry) = playlist.next(state.get_hand_id(), clock.now()) {
+                info!(format = %entry.name, "changing format");
+                let msg = ServerData::Status(format!("switching to {}", entry.name));
+                tx_server.send(msg)?;
+                waker.wake()?;
+                state.change_format(&entry.name, entry.settings, entry.reset_stacks)?;
+                relay_events(&mut state, journal.as_mut(), &tx_server, &waker)?;
+                view_batcher.mark_dirty();
+            }
+        }
+
         // Order is kind of key here. We get the status string before
         // we step so we can inform users what's happening rather than
         // what's going to happen in the future. This allows faster
         // feedback from a user's perspective.
-        let repr = state.to_string();
+        let phase = state.phase();
+        let repr = phase.to_string();
         // Only send new statuses to clients to avoid spam.
         if status != repr {
             info!("{repr}");
             status = repr;
-            let msg = ServerData::Status(status.clone());
+            if let Some(journal) = journal.as_mut() {
+                journal.record(state.get_hand_id(), JournalRecord::Phase(phase.clone()));
+            }
+            let msg = ServerData::Phase(phase);
             tx_server.send(msg)?;
+            let msg = ServerData::PhaseStatus(status.clone());
+            tx_server.send(msg)?;
+            // Players that left during the last hand can leave the game
+            // without enough players to go on, so spectators are asked
+            // to fill in.
+            if state.is_waiting_for_players() {
+                let msg = ServerData::Status(WAITING_FOR_PLAYERS.to_string());
+                tx_server.send(msg)?;
+            }
             waker.wake()?;
         }
+        // Booting players is always the last step of a hand.
+        if let PokerState::BootPlayers(_) = state {
+            metrics.hands_completed.fetch_add(1, Ordering::Relaxed);
+            info!("{metrics}");
+        }
+        // Apply every command received up to this instant before stepping
+        // rather than leaving them for the next state. Otherwise, users
+        // that ask to play right before a hand ends could be left out of
+        // the next one. States that wait on a player's action are only
+        // stepped once the turn is over, so their commands wait.
+        if !state
+            .step_hint(&config.server_timeouts.steps)
+            .waits_for_action
+        {
+            while let Ok(msg) = rx_client.try_recv() {
+                handle_client_message(
+                    &mut state,
+                    msg,
+                    &mut None,
+                    &tx_server,
+                    &waker,
+                    &mut view_batcher,
+                    journal.as_mut(),
+                )?;
+            }
+        }
+        update_autostart_countdown(
+            &state,
+            &mut autostart_deadline,
+            config.server_timeouts.autostart,
+            clock.as_ref(),
+            &tx_server,
+            &waker,
+        )?;
+        if autostart_deadline.is_some_and(|deadline| clock.now() >= deadline) {
+            info!("autostarting the game");
+            autostart_deadline = None;
+            // Users can beat the countdown by starting the game themselves.
+            let _ = state.init_autostart();
+        }
+        // Pots don't have events of their own, so winnings are found by
+        // comparing stacks from before and after they're distributed.
+        let stacks = match state {
+            PokerState::DistributePot(_) => Some(get_stacks(&state)),
+            _ => None,
+        };
+        if let Some(timings) = timer.record_step(&state, clock.now(), &metrics) {
+            debug!("{timings}");
+        }
         state = state.step();
+        if let Some(stacks) = stacks {
+            for (username, money) in get_stacks(&state) {
+                let amount = money.saturating_sub(stacks.get(&username).copied().unwrap_or(money));
+                if amount > 0 {
+                    info!(%username, amount, "won pot");
+                }
+            }
+        }
 
-        let views = state.get_views();
-        let msg = ServerData::Views(views);
-        tx_server.send(msg)?;
-        waker.wake()?;
+        relay_events(&mut state, journal.as_mut(), &tx_server, &waker)?;
+
+        view_batcher.mark_dirty();
 
         let mut next_action_username = state.get_next_action_username();
-        let mut timeout = config.server_timeouts.step;
+        let mut timeout = config.server_timeouts.get_step_timeout(&state);
+        if let Some(deadline) = autostart_deadline {
+            timeout = timeout.min(deadline.saturating_duration_since(clock.now()));
+        }
         'command: loop {
+            if view_batcher.take_due(false) {
+                send_views(&state, operator_username, &tx_server)?;
+                waker.wake()?;
+            }
+
             // Check if it's a user's turn. If so, send them a turn signal
             // and increase the timeout to give them time to make their
             // decision. We also keep track of their username so we
@@ -716,12 +2433,30 @@ pub fn run(addr: &str, config: PokerConfig) -> Result<(), Error> {
                     // was a timeout.
                     if let Some(ref last_username) = next_action_username {
                         // If there's a timeout, then that means the user didn't
-                        // make a decision in time, and they have to fold.
-                        if timeout.as_secs() == 0 && &username == last_username {
-                            // Ack that they will fold (the poker state will
-                            // fold for them).
-                            warn!("{username} ran out of time and will be forced to fold");
-                            let command = UserCommand::TakeAction(Action::Fold);
+                        // make a decision in time (or they left), and they have
+                        // to check or fold. Disconnected players can't make a
+                        // decision at all, so there's no point in waiting on them.
+                        let disconnected = state.is_disconnected(&username);
+                        if (timeout.is_zero() || disconnected) && &username == last_username {
+                            // Ack the action they'll be forced to take (the poker
+                            // state will take the action for them).
+                            let action = state.get_auto_action().expect("it's a user's turn");
+                            let hand_id = state.get_hand_id();
+                            if disconnected {
+                                info!(%username, %action, "forced to act while disconnected");
+                            } else {
+                                warn!(%username, %action, "forced to act after not acting in time");
+                            }
+                            if action == Action::Fold {
+                                metrics.forced_folds.fetch_add(1, Ordering::Relaxed);
+                                timer.record_forced_fold();
+                            }
+                            if let Some(journal) = journal.as_mut() {
+                                let record =
+                                    JournalRecord::Forced(username.clone(), action.clone());
+                                journal.record(hand_id, record);
+                            }
+                            let command = UserCommand::TakeAction(action);
                             let msg = ServerData::Ack(ClientMessage {
                                 username: username.clone(),
                                 command,
@@ -729,22 +2464,55 @@ pub fn run(addr: &str, config: PokerConfig) -> Result<(), Error> {
                             tx_server.send(msg)?;
                             waker.wake()?;
 
-                            // Force remove them so they don't disrupt future games.
-                            warn!("{username} will be removed at the end of the game");
-                            state.remove_user(&username)?;
+                            // Deal with them according to the timeout policy so
+                            // they don't disrupt future games. Disconnected players
+                            // are removed once their grace period runs out instead.
+                            if !disconnected {
+                                let outcome = strikes.strike(&username);
+                                match outcome {
+                                    TimeoutOutcome::SitOut => {
+                                        warn!(%username, "will sit out at the end of the game");
+                                        state.spectate_user(&username)?;
+                                    }
+                                    TimeoutOutcome::Remove => {
+                                        warn!(%username, "will be removed at the end of the game");
+                                        state.remove_user(&username)?;
+                                    }
+                                    _ => {}
+                                }
+                                let msg = ServerData::Response {
+                                    username: username.clone(),
+                                    data: Box::new(ServerMessage::Status(outcome.to_string())),
+                                };
+                                tx_server.send(msg)?;
+                                waker.wake()?;
+                            }
 
                             break 'command;
                         } else {
+                            // Make sure the user sees the latest game before
+                            // they're asked to act on it.
+                            if view_batcher.take_due(true) {
+                                send_views(&state, operator_username, &tx_server)?;
+                                waker.wake()?;
+                            }
+
                             // Let all users know whose turn it is.
-                            let turn_signal = ServerMessage::TurnSignal(action_options);
-                            let status =
-                                format!("it's {username}'s turn and they can {turn_signal}");
-                            let msg = ServerData::Status(status.clone());
+                            let turn_signal = ServerMessage::TurnSignal {
+                                action_options,
+                                max_raise: state.get_max_raise(),
+                                context: state.get_decision_context().expect("it's a user's turn"),
+                                turn_id: state.get_turn_id().expect("it's a user's turn"),
+                            };
+                            let msg = ServerData::TurnAnnouncement {
+                                username: username.clone(),
+                                choices_summary: turn_signal.to_string(),
+                            };
                             tx_server.send(msg)?;
                             waker.wake()?;
 
                             // Let player know it's their turn.
-                            info!("{status}");
+                            info!(%username, options = %turn_signal, "turn");
                             let msg = ServerData::Response {
                                 username: username.clone(),
                                 data: Box::new(turn_signal),
@@ -760,7 +2528,7 @@ pub fn run(addr: &str, config: PokerConfig) -> Result<(), Error> {
                 // If it's no one's turn and there's a timeout, then we must
                 // break to update the poker state.
                 _ => {
-                    if timeout.as_secs() == 0 {
+                    if timeout.is_zero() {
                         break 'command;
                     }
                 }
@@ -768,70 +2536,426 @@ pub fn run(addr: &str, config: PokerConfig) -> Result<(), Error> {
 
             // Use the timeout duration to process events from the server's
             // IO thread.
-            while timeout.as_secs() > 0 {
-                let start = Instant::now();
-                if let Ok(mut msg) = rx_client.recv_timeout(timeout) {
-                    let result = match msg.command {
-                        UserCommand::ChangeState(ref new_user_state) => match new_user_state {
-                            UserState::Play => state.waitlist_user(&msg.username),
-                            UserState::Spectate => state.spectate_user(&msg.username),
-                        },
-                        UserCommand::Connect => state.new_user(&msg.username),
-                        UserCommand::Leave => state.remove_user(&msg.username),
-                        UserCommand::ShowHand => state.show_hand(&msg.username),
-                        UserCommand::StartGame => state.init_start(&msg.username),
-                        UserCommand::TakeAction(ref mut action) => state
-                            .take_action(&msg.username, action.clone())
-                            .map(|new_action| {
-                                timeout = Duration::ZERO;
-                                *action = new_action;
-                            }),
-                    };
+            while !timeout.is_zero() {
+                let start = clock.now();
+                if view_batcher.take_due(false) {
+                    send_views(&state, operator_username, &tx_server)?;
+                    waker.wake()?;
+                }
+                // Wake up in time to send outdated views.
+                let recv_timeout = view_batcher
+                    .time_until_due()
+                    .map_or(timeout, |remaining| remaining.min(timeout));
+                let msg = match clock.recv_timeout(&rx_client, recv_timeout) {
+                    Ok(msg) => msg,
+                    Err(RecvTimeoutError::Timeout) => {
+                        let waited = clock.now() - start;
+                        timer.record_waiting(waited);
+                        timeout = timeout.saturating_sub(waited);
+                        continue;
+                    }
+                    // The IO thread only hangs up when the server is
+                    // shutting down.
+                    Err(RecvTimeoutError::Disconnected) => return Ok(()),
+                };
+                let received = clock.now();
+                timer.record_waiting(received - start);
+                let username = msg.username.clone();
+                let is_action = matches!(
+                    msg.command,
+                    UserCommand::TakeAction(_) | UserCommand::TakeTurn { .. }
+                );
+                if handle_client_message(
+                    &mut state,
+                    msg,
+                    &mut next_action_username,
+                    &tx_server,
+                    &waker,
+                    &mut view_batcher,
+                    journal.as_mut(),
+                )? {
+                    // Only acting shows users are back at their keyboard,
+                    // so only their accepted actions start their timeouts
+                    // over.
+                    if is_action {
+                        strikes.clear(&username);
+                    }
+                    timeout = Duration::ZERO;
+                }
+                let processed = clock.now();
+                timer.record_processing(processed - received);
+                timeout = timeout.saturating_sub(processed - start);
+                // Users coming and going can start or cancel the
+                // countdown, and the countdown can't be overslept.
+                update_autostart_countdown(
+                    &state,
+                    &mut autostart_deadline,
+                    config.server_timeouts.autostart,
+                    clock.as_ref(),
+                    &tx_server,
+                    &waker,
+                )?;
+                if let Some(deadline) = autostart_deadline {
+                    timeout = timeout.min(deadline.saturating_duration_since(clock.now()));
+                }
+            }
+        }
+    }
+}
 
-                    // Get the result from a client's command. If their command
-                    // is OK, ack the command to all clients so they know what
-                    // happened. If their command is bad, send an error back to
-                    // the commanding client.
-                    match result {
-                        Ok(()) => {
-                            info!("{msg}");
-                            let msg = ServerData::Ack(msg);
-                            tx_server.send(msg)?;
-                            waker.wake()?;
+/// Return the operator's omniscient view, unless they're playing and
+/// would see their opponents' cards.
+fn get_omniscient_view(state: &PokerState, operator: &str) -> Option<GameView> {
+    let view = state.as_omniscient_view();
+    let is_playing = view
+        .players
+        .iter()
+        .any(|player| player.user.name == operator);
+    (!is_playing).then_some(view)
+}
 
-                            let msg = ServerData::Views(state.get_views());
-                            tx_server.send(msg)?;
-                            waker.wake()?;
-                        }
-                        Err(error) => {
-                            error!("{error}: {msg}");
-                            let msg = ServerData::Response {
-                                username: msg.username,
-                                data: Box::new(ServerMessage::UserError(error)),
-                            };
-                            tx_server.send(msg)?;
-                            waker.wake()?;
-                        }
-                    }
+/// Send every user their view of the game, and the operator their
+/// omniscient view if there is one.
+fn send_views(
+    state: &PokerState,
+    operator: Option<&str>,
+    tx_server: &ServerDataSender,
+) -> Result<(), Error> {
+    tx_server.send(ServerData::Views(state.get_views()))?;
+    if let Some(view) = operator.and_then(|operator| get_omniscient_view(state, operator)) {
+        tx_server.send(ServerData::OmniscientView(view))?;
+    }
+    Ok(())
+}
+
+/// Return the stack of every player at the table.
+fn get_stacks(state: &PokerState) -> HashMap<Username, Usd> {
+    let views = state.get_views();
+    let Some(view) = views.usernames().next().and_then(|u| views.get(u)) else {
+        return HashMap::new();
+    };
+    view.players
+        .iter()
+        .map(|player| (player.user.name.clone(), player.user.money))
+        .collect()
+}
+
+/// Start the autostart countdown once the game's autostart policy would
+/// start the game, and cancel it if that's no longer the case, letting
+/// users know either way. The game is started by the caller once the
+/// deadline passes.
+fn update_autostart_countdown(
+    state: &PokerState,
+    deadline: &mut Option<Instant>,
+    delay: Duration,
+    clock: &dyn Clock,
+    tx_server: &ServerDataSender,
+    waker: &Waker,
+) -> Result<(), Error> {
+    let status = match (state.is_ready_to_autostart(), deadline.is_some()) {
+        (true, false) => {
+            *deadline = Some(clock.now() + delay);
+            let secs = delay.as_secs_f32();
+            format!("game starting in {secs} seconds")
+        }
+        // The game started before the countdown ran out.
+        (false, true) if !matches!(state, PokerState::Lobby(_)) => {
+            *deadline = None;
+            return Ok(());
+        }
+        (false, true) => {
+            *deadline = None;
+            "game start cancelled, waiting for more players".to_string()
+        }
+        _ => return Ok(()),
+    };
+    info!("{status}");
+    tx_server.send(ServerData::Status(status))?;
+    waker.wake()?;
+    Ok(())
+}
+
+/// Journal the game's latest events and relay them to all users. Events
+/// only meant for operators are logged instead of relayed.
+fn relay_events(
+    state: &mut PokerState,
+    journal: Option<&mut Journal>,
+    tx_server: &ServerDataSender,
+    waker: &Waker,
+) -> Result<(), Error> {
+    let mut events = state.drain_events();
+    if events.is_empty() {
+        return Ok(());
+    }
+    let hand_id = state.get_hand_id();
+    if let Some(journal) = journal {
+        journal.record_events(hand_id, &events);
+    }
+    events.retain(|event| {
+        if event.is_operator_only() {
+            warn!(%event, "operator-only event");
+        }
+        !event.is_operator_only()
+    });
+    if !events.is_empty() {
+        tx_server.send(ServerData::Events(hand_id, events))?;
+        waker.wake()?;
+    }
+    Ok(())
+}
+
+/// Apply a user's command to the poker state, acking it to all users if
+/// it's OK and sending an error back to the user if it isn't. Accepted
+/// commands are journaled. Returns whether the command ended the current
+/// turn, in which case the state should be stepped right away. Once the
+/// session's over, users can only spectate or leave.
+fn handle_client_message(
+    state: &mut PokerState,
+    mut msg: ClientMessage,
+    next_action_username: &mut Option<Username>,
+    tx_server: &ServerDataSender,
+    waker: &Waker,
+    view_batcher: &mut ViewBatcher,
+    mut journal: Option<&mut Journal>,
+) -> Result<bool, Error> {
+    let _span = info_span!("user", username = %msg.username).entered();
+    // Leaderboard queries don't change the game, so only the
+    // querying user needs to hear back.
+    if msg.command == UserCommand::QueryLeaderboard {
+        let msg = ServerData::Response {
+            username: msg.username,
+            data: Box::new(ServerMessage::Leaderboard(state.get_leaderboard())),
+        };
+        tx_server.send(msg)?;
+        waker.wake()?;
+        return Ok(false);
+    }
+    // Same for omniscient views, which the IO thread only lets the
+    // operator ask for.
+    if msg.command == UserCommand::ViewAllCards {
+        let msg = match get_omniscient_view(state, &msg.username) {
+            Some(view) => ServerData::OmniscientView(view),
+            None => ServerData::Response {
+                username: msg.username,
+                data: Box::new(ServerMessage::UserError(UserError::CannotViewAllCards)),
+            },
+        };
+        tx_server.send(msg)?;
+        waker.wake()?;
+        return Ok(false);
+    }
+    // Same for resyncs, except they need everything the user would've
+    // heard about the game so far.
+    if msg.command == UserCommand::Resync {
+        let data = match state.get_views().get(&msg.username) {
+            Some(view) => {
+                let is_turn = state.get_next_action_username().as_ref() == Some(&msg.username);
+                let action_options = state.get_action_options().filter(|_| is_turn);
+                let max_raise = action_options.as_ref().and_then(|_| state.get_max_raise());
+                let turn_id = action_options.as_ref().and_then(|_| state.get_turn_id());
+                let phase = state.phase();
+                ServerMessage::Snapshot(Box::new(Snapshot {
+                    view,
+                    status: phase.to_string(),
+                    phase,
+                    action_options,
+                    max_raise,
+                    turn_id,
+                }))
+            }
+            None => ServerMessage::UserError(UserError::UserDoesNotExist),
+        };
+        let msg = ServerData::Response {
+            username: msg.username,
+            data: Box::new(data),
+        };
+        tx_server.send(msg)?;
+        waker.wake()?;
+        return Ok(false);
+    }
+    let mut turn_over = false;
+    let result = match msg.command {
+        _ if state.is_session_over()
+            && !matches!(
+                msg.command,
+                UserCommand::ChangeState(UserState::Spectate)
+                    | UserCommand::Connect
+                    | UserCommand::Disconnect
+                    | UserCommand::Leave
+            ) =>
+        {
+            Err(UserError::SessionEnding)
+        }
+        UserCommand::AcceptInsurance { ref from } => state.accept_insurance(&msg.username, from),
+        UserCommand::AddChips(amount) => state.add_chips(&msg.username, amount),
+        UserCommand::ChangeSeat(seat_idx) => state.change_seat(&msg.username, seat_idx),
+        UserCommand::ChangeState(ref new_user_state) => match new_user_state {
+            UserState::Play => state.waitlist_user(&msg.username),
+            UserState::Spectate => state.spectate_user(&msg.username),
+            UserState::Railbird => state.rail_user(&msg.username),
+        },
+        UserCommand::Connect => state.new_user(&msg.username),
+        UserCommand::Disconnect => state.disconnect_user(&msg.username),
+        UserCommand::Leave => state.remove_user(&msg.username),
+        UserCommand::MuckHand => state.muck_hand(&msg.username),
+        UserCommand::Mute(_) | UserCommand::Unmute(_) => {
+            unreachable!("mutes are handled by the IO thread")
+        }
+        UserCommand::OfferInsurance {
+            ref to,
+            amount,
+            odds_numer,
+            odds_denom,
+        } => state.offer_insurance(&msg.username, to, amount, odds_numer, odds_denom),
+        UserCommand::Pong => unreachable!("pongs are handled by the IO thread"),
+        UserCommand::QueryLeaderboard => {
+            unreachable!("leaderboard queries are answered above")
+        }
+        UserCommand::Rejoin => state.rejoin_user(&msg.username),
+        UserCommand::Rename(ref new_username) => state.rename_user(&msg.username, new_username),
+        UserCommand::Resync => unreachable!("resyncs are answered above"),
+        UserCommand::SetAutoRebuy(enabled) => state.set_auto_rebuy(&msg.username, enabled),
+        UserCommand::SetProfile(ref profile) => state.set_profile(&msg.username, profile.clone()),
+        UserCommand::SetVerbosity(_) => unreachable!("verbosity is handled by the IO thread"),
+        UserCommand::ShowCard(card_idx) => state.show_card(&msg.username, card_idx),
+        UserCommand::ShowHand => state.show_hand(&msg.username),
+        UserCommand::SideBet(ref player, amount) => {
+            state.place_side_bet(&msg.username, player, amount)
+        }
+        UserCommand::StartGame => state.init_start(&msg.username),
+        UserCommand::TakeAction(ref mut action) => state
+            .take_action(&msg.username, action.clone())
+            .map(|new_action| {
+                turn_over = true;
+                *action = new_action;
+            }),
+        // Actions tied to a turn are acked like any other action once
+        // they're taken, since the turn doesn't matter to anyone else.
+        UserCommand::TakeTurn {
+            ref action,
+            turn_id,
+        } => state
+            .take_turn_action(&msg.username, action.clone(), turn_id)
+            .map(|new_action| {
+                turn_over = true;
+                msg.command = UserCommand::TakeAction(new_action);
+            }),
+        UserCommand::ViewAllCards => unreachable!("omniscient views are answered above"),
+    };
+
+    // Get the result from a client's command. If their command
+    // is OK, ack the command to all clients so they know what
+    // happened. If their command is bad, send an error back to
+    // the commanding client.
+    match result {
+        Ok(()) => {
+            let hand_id = state.get_hand_id();
+            match msg.command {
+                UserCommand::Connect => info!("connected"),
+                UserCommand::TakeAction(ref action) => info!(%action, "acted"),
+                ref command => info!(%command, "accepted command"),
+            }
+            if let Some(journal) = journal.as_mut() {
+                journal.record(hand_id, JournalRecord::Accepted(msg.clone()));
+            }
+            // Keep track of the user whose turn it is so renaming
+            // doesn't reset their action timeout.
+            if let UserCommand::Rename(ref new_username) = msg.command {
+                if next_action_username.as_ref() == Some(&msg.username) {
+                    *next_action_username = Some(new_username.clone());
                 }
-                timeout = timeout.saturating_sub(Instant::now() - start);
             }
+            // There's no point in waiting for a user to act if
+            // they've left, so act for them right away.
+            if matches!(
+                msg.command,
+                UserCommand::Disconnect
+                    | UserCommand::Leave
+                    | UserCommand::ChangeState(UserState::Railbird)
+            ) && state.get_next_action_username().as_ref() == Some(&msg.username)
+            {
+                turn_over = true;
+            }
+            // New users can't follow along without a view, so
+            // they get one right away rather than waiting on
+            // the next batch.
+            let view = match msg.command {
+                UserCommand::Connect => state.get_views().get(&msg.username),
+                _ => None,
+            };
+            let username = msg.username.clone();
+            let msg = ServerData::Ack(msg);
+            tx_server.send(msg)?;
+            if let Some(view) = view {
+                let msg = ServerData::Response {
+                    username,
+                    data: Box::new(ServerMessage::GameView(view)),
+                };
+                tx_server.send(msg)?;
+            }
+            waker.wake()?;
+
+            relay_events(state, journal, tx_server, waker)?;
+
+            view_batcher.mark_dirty();
+        }
+        Err(error) => {
+            error!(%error, command = %msg.command, "rejected command");
+            let msg = ServerData::Response {
+                username: msg.username,
+                data: Box::new(ServerMessage::UserError(error)),
+            };
+            tx_server.send(msg)?;
+            waker.wake()?;
         }
     }
+    Ok(turn_over)
 }
 
 #[cfg(test)]
 mod tests {
-    use std::time::Duration;
+    use std::time::{Duration, Instant};
 
     use mio::{
         net::{TcpListener, TcpStream},
-        Token,
+        Events, Poll, Token, Waker,
     };
 
-    use crate::net::messages::ClientError;
+    use std::{
+        collections::{HashMap, HashSet, VecDeque},
+        io::{self, Write},
+        net::{IpAddr, Ipv4Addr},
+        sync::{mpsc::channel, Arc, Mutex},
+        thread,
+    };
 
-    use super::TokenManager;
+    use crate::{
+        game::{
+            constants::{
+                DEFAULT_STEP_TIMEOUT, MAX_PLAYERS, MAX_TAGLINE_LENGTH, MAX_USER_INPUT_LENGTH,
+                MAX_VIEWED_ACTIONS,
+            },
+            entities::{
+                Action, ActionRecord, Bet, BetAction, Card, DecisionContext, GameEvent,
+                GameVariant, GameView, HoleCards, PlayerState, PlayerView, PotView, Privacy,
+                Profile, Reveal, Street, Suit, User,
+            },
+            PokerState,
+        },
+        net::{
+            messages::{ClientError, ClientMessage, SequencedMessage, ServerMessage, UserCommand},
+            utils::{read_prefixed, MAX_FRAME_SIZE},
+        },
+    };
+
+    use super::{
+        clock::VirtualClock, format_remaining, recv_server_data, Heartbeats, RateCheck,
+        RateLimiter, RateLimits, ServerData, ServerDataSender, ServerTimeouts, SessionLimit,
+        SessionWarnings, TimeoutOutcome, TimeoutPolicy, TimeoutStrikes, TokenManager, ViewBatcher,
+        WriteQueues, WAKER,
+    };
+
+    const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
 
     fn get_random_open_port() -> u16 {
         let addr = "127.0.0.1:0".parse().unwrap();
@@ -855,6 +2979,705 @@ mod tests {
         stream
     }
 
+    #[test]
+    fn server_data_is_relayed_in_order_without_waiting_on_wakes() {
+        const NUM_BURSTS: usize = 50;
+        const BURST_SIZE: usize = 100;
+        let poll_timeout = Duration::from_millis(500);
+        let mut poll = Poll::new().unwrap();
+        let waker = Arc::new(Waker::new(poll.registry(), WAKER).unwrap());
+        let (tx_server, rx_server) = channel();
+        let sent_at = Arc::new(Mutex::new(vec![]));
+
+        // Bursts of sends share wakes, and the quiet gaps between them are
+        // when data that missed a drain would wait the longest.
+        let game_thread = {
+            let sent_at = sent_at.clone();
+            let waker = waker.clone();
+            thread::spawn(move || {
+                let tx_server = ServerDataSender::new(tx_server);
+                let views = PokerState::default().get_views();
+                for burst in 0..NUM_BURSTS {
+                    for i in 0..BURST_SIZE {
+                        let data = match i % 3 {
+                            0 => ServerData::Status(format!("{burst}.{i}")),
+                            1 => ServerData::Views(views.clone()),
+                            _ => ServerData::Response {
+                                username: "ognf".to_string(),
+                                data: Box::new(ServerMessage::Ping),
+                            },
+                        };
+                        sent_at.lock().unwrap().push(Instant::now());
+                        tx_server.send(data).unwrap();
+                        waker.wake().unwrap();
+                    }
+                    thread::sleep(Duration::from_millis(5));
+                }
+            })
+        };
+
+        let mut events = Events::with_capacity(8);
+        let mut next_seq = 1;
+        loop {
+            poll.poll(&mut events, Some(poll_timeout)).unwrap();
+            let (received, hung_up) = recv_server_data(&rx_server);
+            let received_at = Instant::now();
+            for (seq, _) in received {
+                assert_eq!(seq, next_seq);
+                let delay = received_at - sent_at.lock().unwrap()[seq as usize - 1];
+                assert!(delay < poll_timeout, "#{seq} waited {delay:?}");
+                next_seq += 1;
+            }
+            if hung_up {
+                break;
+            }
+        }
+        game_thread.join().unwrap();
+        assert_eq!(next_seq as usize, NUM_BURSTS * BURST_SIZE + 1);
+    }
+
+    #[test]
+    fn large_view_fits_in_a_frame() {
+        let user = |i: usize| User {
+            name: format!("{i:0>width$}", width = MAX_USER_INPUT_LENGTH),
+            money: u32::MAX,
+            profile: Profile {
+                avatar: Some("🏳️‍🌈".to_string()),
+                tagline: Some("😀".repeat(MAX_TAGLINE_LENGTH)),
+            },
+        };
+        let players = (0..MAX_PLAYERS)
+            .map(|i| PlayerView {
+                user: user(i),
+                state: PlayerState::AllIn,
+                cards: HoleCards::Revealed(vec![Card(14, Suit::Spade); 4]),
+                revealed: Reveal::Hand,
+                disconnected: false,
+                last_bet: Some(Bet {
+                    action: BetAction::AllIn,
+                    amount: u32::MAX,
+                }),
+                investment: u32::MAX,
+            })
+            .collect();
+        // A full table with a crowd watching or waiting to play.
+        let num_users = 1000;
+        let view = GameView {
+            hand_id: u64::MAX,
+            donations: f32::MAX,
+            small_blind: u32::MAX,
+            big_blind: u32::MAX,
+            spectators: HashMap::from_iter((0..num_users).map(|i| (user(i).name, user(i)))),
+            waitlist: VecDeque::from_iter((0..num_users).map(user)),
+            open_seats: VecDeque::from_iter(0..MAX_PLAYERS),
+            players,
+            board: vec![Card(14, Suit::Spade); 5],
+            pot: PotView {
+                size: u32::MAX,
+                street_bets: u32::MAX,
+            },
+            button_idx: 0,
+            small_blind_idx: 0,
+            big_blind_idx: 0,
+            next_action_idx: Some(0),
+            bomb_pot: false,
+            variant: GameVariant::Omaha,
+            privacy: Privacy::Open,
+            limit: None,
+            action_history: (0..MAX_VIEWED_ACTIONS)
+                .map(|i| ActionRecord {
+                    username: user(i).name,
+                    action: Action::Raise(u32::MAX),
+                    street: Street::River,
+                })
+                .collect(),
+            equities: (0..MAX_PLAYERS).map(|i| (user(i).name, 1.0)).collect(),
+        };
+        let msg = SequencedMessage {
+            seq: u64::MAX,
+            msg: ServerMessage::GameView(Arc::new(view)),
+        };
+        let size = bincode::serialized_size(&msg).unwrap() as usize;
+        assert!(size <= MAX_FRAME_SIZE, "{size} > {MAX_FRAME_SIZE}");
+    }
+
+    #[test]
+    fn turn_signals_roundtrip() {
+        for max_raise in [None, Some(180)] {
+            let context = DecisionContext {
+                variant: GameVariant::Holdem,
+                board: vec![
+                    Card(2, Suit::Heart),
+                    Card(9, Suit::Club),
+                    Card(14, Suit::Spade),
+                ],
+                cards: vec![Card(3, Suit::Diamond), Card(3, Suit::Heart)],
+                pot: 60,
+                call: 20,
+                min_raise: max_raise.map(|_| 40),
+                max_raise,
+                stack: 180,
+                position: 1,
+                num_players: 3,
+            };
+            let msg = ServerMessage::TurnSignal {
+                action_options: HashSet::from([Action::Fold, Action::Raise(20)]),
+                max_raise,
+                context: context.clone(),
+                turn_id: 7,
+            };
+            let bytes = bincode::serialize(&msg).unwrap();
+            match bincode::deserialize(&bytes).unwrap() {
+                ServerMessage::TurnSignal {
+                    action_options,
+                    max_raise: deserialized_max_raise,
+                    context: deserialized_context,
+                    turn_id,
+                } => {
+                    assert_eq!(deserialized_context, context);
+                    assert_eq!(turn_id, 7);
+                    // Actions only compare their variants.
+                    assert!(action_options
+                        .iter()
+                        .any(|action| matches!(action, Action::Raise(20))));
+                    assert_eq!(action_options.len(), 2);
+                    assert_eq!(deserialized_max_raise, max_raise);
+                }
+                other => panic!("expected a turn signal, got {other}"),
+            }
+        }
+    }
+
+    #[test]
+    fn profiles_can_be_left_out() {
+        // Clients that don't know about profiles don't send them, and
+        // clients can send only part of one.
+        let user: User = serde_json::from_str(r#"{"name":"ognf","money":200}"#).unwrap();
+        assert_eq!(user.profile, Profile::default());
+        let command: UserCommand =
+            serde_json::from_str(r#"{"SetProfile":{"avatar":"🇨🇦"}}"#).unwrap();
+        assert_eq!(
+            command,
+            UserCommand::SetProfile(Profile {
+                avatar: Some("🇨🇦".to_string()),
+                tagline: None,
+            })
+        );
+        // Setting a profile comes after every other command, so older
+        // commands are encoded like they were before profiles.
+        let bytes = bincode::serialize(&UserCommand::TakeTurn {
+            action: Action::Check,
+            turn_id: 1,
+        })
+        .unwrap();
+        assert_eq!(bytes[..4], [21, 0, 0, 0]);
+        let bytes = bincode::serialize(&command).unwrap();
+        assert_eq!(bytes[..4], [22, 0, 0, 0]);
+    }
+
+    #[test]
+    fn sanitize_client_messages() {
+        let mut msg = ClientMessage {
+            username: format!("a\u{7}b\n{}", "c".repeat(2 * MAX_USER_INPUT_LENGTH)),
+            command: UserCommand::Rename("new\u{1b}[2Jname".to_string()),
+        };
+        msg.sanitize();
+        assert_eq!(msg.username.chars().count(), MAX_USER_INPUT_LENGTH);
+        assert!(msg.username.starts_with("abc"));
+        assert_eq!(msg.command, UserCommand::Rename("new[2Jname".to_string()));
+
+        // Every command that names another user gets its name sanitized.
+        let username = "new\u{1b}[2Jname".to_string();
+        for (command, expected) in [
+            (
+                UserCommand::AcceptInsurance {
+                    from: username.clone(),
+                },
+                UserCommand::AcceptInsurance {
+                    from: "new[2Jname".to_string(),
+                },
+            ),
+            (
+                UserCommand::Mute(username.clone()),
+                UserCommand::Mute("new[2Jname".to_string()),
+            ),
+            (
+                UserCommand::OfferInsurance {
+                    to: username.clone(),
+                    amount: 1,
+                    odds_numer: 1,
+                    odds_denom: 1,
+                },
+                UserCommand::OfferInsurance {
+                    to: "new[2Jname".to_string(),
+                    amount: 1,
+                    odds_numer: 1,
+                    odds_denom: 1,
+                },
+            ),
+            (
+                UserCommand::SideBet(username.clone(), 1),
+                UserCommand::SideBet("new[2Jname".to_string(), 1),
+            ),
+            (
+                UserCommand::Unmute(username.clone()),
+                UserCommand::Unmute("new[2Jname".to_string()),
+            ),
+        ] {
+            let mut msg = ClientMessage {
+                username: "a".to_string(),
+                command,
+            };
+            msg.sanitize();
+            assert_eq!(msg.command, expected);
+        }
+    }
+
+    #[test]
+    fn view_batcher_flushes_once_per_interval() {
+        let interval = Duration::from_millis(100);
+        let clock = Arc::new(VirtualClock::new());
+        let mut view_batcher = ViewBatcher::new(interval, clock.clone());
+        assert_eq!(view_batcher.time_until_due(), None);
+        assert!(!view_batcher.take_due(true));
+        view_batcher.mark_dirty();
+        clock.advance(interval / 2);
+        view_batcher.mark_dirty();
+        assert_eq!(view_batcher.time_until_due(), Some(interval / 2));
+        assert!(!view_batcher.take_due(false));
+        clock.advance(interval / 2);
+        assert!(view_batcher.take_due(false));
+        assert!(!view_batcher.take_due(false));
+        // Forcing sends views before the interval is up.
+        view_batcher.mark_dirty();
+        assert!(view_batcher.take_due(true));
+        assert_eq!(view_batcher.time_until_due(), None);
+    }
+
+    fn view(hand_id: u64) -> ServerMessage {
+        ServerMessage::GameView(Arc::new(GameView {
+            hand_id,
+            donations: 0.0,
+            small_blind: 5,
+            big_blind: 10,
+            spectators: HashMap::new(),
+            waitlist: VecDeque::new(),
+            open_seats: VecDeque::new(),
+            players: vec![],
+            board: vec![],
+            pot: PotView {
+                size: 0,
+                street_bets: 0,
+            },
+            button_idx: 0,
+            small_blind_idx: 0,
+            big_blind_idx: 0,
+            next_action_idx: None,
+            bomb_pot: false,
+            variant: GameVariant::Holdem,
+            privacy: Privacy::Open,
+            limit: None,
+            action_history: vec![],
+            equities: vec![],
+        }))
+    }
+
+    fn ack() -> ServerMessage {
+        ServerMessage::Ack(ClientMessage {
+            username: "ognf".to_string(),
+            command: UserCommand::StartGame,
+        })
+    }
+
+    #[test]
+    fn write_queues_coalesce_views_and_phase_statuses() {
+        let capacity = 4;
+        let mut queues = WriteQueues::new(capacity);
+        let token = Token(2);
+        let pop_front = |queues: &mut WriteQueues| queues.pop_front(&token).unwrap().unwrap().0;
+
+        // A client that isn't reading only ever has one view and one phase
+        // status waiting on it, and the newest of each is the one that's
+        // written.
+        for hand_id in 0..100 {
+            queues.push(token, hand_id, view(hand_id));
+            queues.push_phase_status(token, hand_id, hand_id.to_string());
+        }
+        queues.push(token, 100, ack());
+        assert!(queues.drain_overflowed().is_empty());
+        assert_eq!(queues.queues[&token].len(), 3);
+        match pop_front(&mut queues) {
+            ServerMessage::GameView(view) => assert_eq!(view.hand_id, 99),
+            other => panic!("expected a view, got {other:?}"),
+        }
+        match pop_front(&mut queues) {
+            ServerMessage::Status(status) => assert_eq!(status, "99"),
+            other => panic!("expected a status, got {other:?}"),
+        }
+        assert!(matches!(pop_front(&mut queues), ServerMessage::Ack(_)));
+
+        // Notices are never replaced, and phase statuses aren't replaced
+        // across them.
+        queues.push_phase_status(token, 101, "phase".to_string());
+        queues.push(token, 101, ServerMessage::Status("notice".to_string()));
+        queues.push_phase_status(token, 102, "next phase".to_string());
+        queues.push(token, 102, ServerMessage::Status("notice".to_string()));
+        for expected in ["phase", "notice", "next phase", "notice"] {
+            match pop_front(&mut queues) {
+                ServerMessage::Status(status) => assert_eq!(status, expected),
+                other => panic!("expected a status, got {other:?}"),
+            }
+        }
+
+        // Views aren't replaced across other messages so the client sees
+        // the game as it was when the ack was sent.
+        queues.push(token, 103, view(100));
+        queues.push(token, 104, ack());
+        queues.push(token, 105, view(101));
+        let mut hand_ids = vec![];
+        while let Some(write) = queues.pop_front(&token) {
+            if let (ServerMessage::GameView(view), _) = write.unwrap() {
+                hand_ids.push(view.hand_id);
+            }
+        }
+        assert_eq!(hand_ids, vec![100, 101]);
+        queues.remove(&token);
+
+        // A partially written view is finished before anything else is
+        // written, even if a newer view is queued in the meantime.
+        queues.push(token, 106, view(102));
+        let (msg, frame) = queues.pop_front(&token).unwrap().unwrap();
+        queues.push_in_flight(token, msg, frame);
+        queues.push(token, 107, view(103));
+        for hand_id in [102, 103] {
+            match queues.pop_front(&token).unwrap().unwrap() {
+                (ServerMessage::GameView(view), _) => assert_eq!(view.hand_id, hand_id),
+                other => panic!("expected a view, got {other:?}"),
+            }
+        }
+        assert!(queues.pop_front(&token).is_none());
+        queues.remove(&token);
+
+        // Acks are never coalesced, so they can still fill the queue, and
+        // a full queue marks its client for removal.
+        for _ in 0..=capacity {
+            queues.push(token, 108, ack());
+        }
+        assert_eq!(queues.queues[&token].len(), capacity);
+        assert_eq!(queues.drain_overflowed(), vec![token]);
+        assert!(queues.drain_overflowed().is_empty());
+        queues.remove(&token);
+        assert!(!queues.queues.contains_key(&token));
+    }
+
+    #[test]
+    fn write_queues_keep_sequence_order_across_would_blocks() {
+        // Accepts a few bytes at a time and blocks on every other write,
+        // like a slow client's socket.
+        struct TrickleWriter {
+            buf: Vec<u8>,
+            would_block: bool,
+        }
+
+        impl Write for TrickleWriter {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.would_block = !self.would_block;
+                if self.would_block {
+                    return Err(io::ErrorKind::WouldBlock.into());
+                }
+                let n = buf.len().min(3);
+                self.buf.extend(&buf[..n]);
+                Ok(n)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut queues = WriteQueues::new(64);
+        let token = Token(2);
+        let mut writer = TrickleWriter {
+            buf: vec![],
+            would_block: false,
+        };
+        // Interleave pushes with partial writes the way the IO thread does,
+        // so new messages (some of which coalesce) are queued while others
+        // are stuck in flight.
+        for seq in 1..=40 {
+            let msg = match seq % 4 {
+                0 => ack(),
+                1 => view(seq),
+                2 => ServerMessage::Status(seq.to_string()),
+                _ => ServerMessage::GameEvent(seq, GameEvent::AddedChips("ognf".to_string(), 10)),
+            };
+            match msg {
+                ServerMessage::Status(status) if seq % 8 == 2 => {
+                    queues.push_phase_status(token, seq, status)
+                }
+                msg => queues.push(token, seq, msg),
+            }
+            if let Some(write) = queues.pop_front(&token) {
+                let (msg, mut frame) = write.unwrap();
+                if let Err(error) = frame.write_to(&mut writer) {
+                    assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+                    queues.push_in_flight(token, msg, frame);
+                }
+            }
+        }
+        while let Some(write) = queues.pop_front(&token) {
+            let (msg, mut frame) = write.unwrap();
+            if let Err(error) = frame.write_to(&mut writer) {
+                assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+                queues.push_in_flight(token, msg, frame);
+            }
+        }
+
+        let mut reader = writer.buf.as_slice();
+        let mut seqs = vec![];
+        while !reader.is_empty() {
+            let sequenced: SequencedMessage = read_prefixed(&mut reader).unwrap();
+            seqs.push(sequenced.seq);
+        }
+        assert!(seqs.len() > 10);
+        assert!(seqs.windows(2).all(|pair| pair[0] < pair[1]), "{seqs:?}");
+        assert_eq!(seqs.last(), Some(&40));
+    }
+
+    #[test]
+    fn heartbeats_ping_then_expire() {
+        let interval = Duration::from_secs(2);
+        let timeout = Duration::from_secs(1);
+        let mut heartbeats = Heartbeats::new(interval, timeout);
+        let (quiet, chatty) = (Token(2), Token(3));
+        let tokens = [quiet, chatty];
+        let t = Instant::now();
+        assert_eq!(heartbeats.check(&tokens, t), (vec![], vec![]));
+        heartbeats.record(chatty, t + interval);
+        assert_eq!(
+            heartbeats.check(&tokens, t + interval),
+            (vec![quiet], vec![])
+        );
+        // Pings aren't repeated while waiting for a response.
+        assert_eq!(
+            heartbeats.check(&tokens, t + interval + timeout / 2),
+            (vec![], vec![])
+        );
+        assert_eq!(
+            heartbeats.check(&tokens, t + interval + timeout),
+            (vec![], vec![quiet])
+        );
+        heartbeats.remove(&quiet);
+        assert_eq!(
+            heartbeats.check(&[chatty], t + 2 * interval),
+            (vec![chatty], vec![])
+        );
+        // Responding to a ping resets the client's heartbeat.
+        heartbeats.record(chatty, t + 2 * interval);
+        assert_eq!(
+            heartbeats.check(&[chatty], t + 2 * interval + timeout),
+            (vec![], vec![])
+        );
+    }
+
+    #[test]
+    fn sessions_warn_then_end() {
+        let minute = Duration::from_secs(60);
+        let warnings = SessionWarnings {
+            before: vec![5 * minute, 30 * minute, 15 * minute],
+            grace: 5 * minute,
+        };
+        let t = Instant::now();
+        let mut session = SessionLimit::new(60 * minute, &warnings, t);
+        assert_eq!(session.take_warning(t), None);
+        assert_eq!(session.take_warning(t + 29 * minute), None);
+        assert!(!session.is_ending(t + 29 * minute));
+
+        // Each warning is only sent once, whenever it's checked for.
+        assert_eq!(session.take_warning(t + 31 * minute), Some(29 * minute));
+        assert_eq!(session.take_warning(t + 31 * minute), None);
+        assert_eq!(session.take_warning(t + 45 * minute), Some(15 * minute));
+        assert!(!session.is_ending(t + 45 * minute));
+
+        // New hands stop being started once the grace period starts.
+        assert_eq!(session.take_warning(t + 55 * minute), Some(5 * minute));
+        assert!(session.is_ending(t + 55 * minute));
+        assert_eq!(session.take_warning(t + 60 * minute), None);
+        assert!(session.is_ending(t + 61 * minute));
+
+        // Warnings that came due at the same time are sent as one.
+        let mut session = SessionLimit::new(20 * minute, &warnings, t);
+        assert_eq!(session.take_warning(t), Some(20 * minute));
+        assert_eq!(session.take_warning(t + minute), None);
+        assert_eq!(session.take_warning(t + 50 * minute), Some(Duration::ZERO));
+        assert_eq!(session.take_warning(t + 50 * minute), None);
+    }
+
+    #[test]
+    fn session_time_left_rounds_up() {
+        assert_eq!(format_remaining(Duration::ZERO), "0 seconds");
+        assert_eq!(format_remaining(Duration::from_millis(1500)), "2 seconds");
+        assert_eq!(format_remaining(Duration::from_secs(60)), "1 minute");
+        assert_eq!(format_remaining(Duration::from_secs(61)), "2 minutes");
+        assert_eq!(format_remaining(Duration::from_secs(30 * 60)), "30 minutes");
+    }
+
+    #[derive(Clone, Copy)]
+    enum Turn {
+        Act,
+        TimeOut,
+    }
+
+    /// Play out the player's turn each hand, returning the hand they sat
+    /// out or were removed on, counting from 1, and what they were told
+    /// each time they timed out.
+    fn play_turns(policy: TimeoutPolicy, turns: &[Turn]) -> (Option<usize>, Vec<String>) {
+        let mut strikes = TimeoutStrikes::new(policy);
+        let mut warnings = vec![];
+        for (hand_idx, turn) in turns.iter().enumerate() {
+            match turn {
+                Turn::Act => strikes.clear("ognf"),
+                Turn::TimeOut => {
+                    let outcome = strikes.strike("ognf");
+                    warnings.push(outcome.to_string());
+                    if matches!(outcome, TimeoutOutcome::SitOut | TimeoutOutcome::Remove) {
+                        return (Some(hand_idx + 1), warnings);
+                    }
+                }
+            }
+        }
+        (None, warnings)
+    }
+
+    #[test]
+    fn timeouts_only_fold_when_folding_is_the_policy() {
+        let (hand, warnings) = play_turns(TimeoutPolicy::FoldOnly, &[Turn::TimeOut; 10]);
+        assert_eq!(hand, None);
+        assert_eq!(warnings.len(), 10);
+        assert!(warnings
+            .iter()
+            .all(|warning| warning == "you timed out and were acted for"));
+    }
+
+    #[test]
+    fn timeouts_in_a_row_sit_players_out() {
+        use Turn::{Act, TimeOut};
+
+        let policy = TimeoutPolicy::SitOutAfter(3);
+        let (hand, warnings) = play_turns(
+            policy,
+            &[TimeOut, TimeOut, Act, TimeOut, TimeOut, TimeOut, Act],
+        );
+        assert_eq!(hand, Some(6));
+        assert_eq!(
+            warnings,
+            [
+                "you timed out, 2 more timeouts in a row and you'll sit out",
+                "you timed out, one more timeout and you'll sit out",
+                // Acting on their own started them over.
+                "you timed out, 2 more timeouts in a row and you'll sit out",
+                "you timed out, one more timeout and you'll sit out",
+                "you timed out and will sit out after this hand",
+            ]
+        );
+
+        // Acting every other hand keeps them seated for good.
+        let turns: Vec<Turn> = [TimeOut, TimeOut, Act].repeat(5);
+        assert_eq!(play_turns(policy, &turns).0, None);
+    }
+
+    #[test]
+    fn timeouts_in_a_row_remove_players() {
+        use Turn::{Act, TimeOut};
+
+        // By default, players are removed the first time they time out.
+        let (hand, warnings) = play_turns(TimeoutPolicy::default(), &[Act, Act, TimeOut]);
+        assert_eq!(hand, Some(3));
+        assert_eq!(
+            warnings,
+            ["you timed out and will be removed after this hand"]
+        );
+
+        let policy = TimeoutPolicy::RemoveAfter(2);
+        let (hand, warnings) = play_turns(policy, &[TimeOut, Act, TimeOut, Act, TimeOut, TimeOut]);
+        assert_eq!(hand, Some(6));
+        assert_eq!(
+            warnings[0],
+            "you timed out, one more timeout and you'll be removed"
+        );
+    }
+
+    #[test]
+    fn timeout_strikes_are_counted_per_player_and_start_over() {
+        let mut strikes = TimeoutStrikes::new(TimeoutPolicy::SitOutAfter(2));
+        assert_eq!(strikes.strike("ognf"), TimeoutOutcome::SitOutIn(1));
+        assert_eq!(strikes.strike("ognf2"), TimeoutOutcome::SitOutIn(1));
+        assert_eq!(strikes.strike("ognf2"), TimeoutOutcome::SitOut);
+
+        // Players that sat out start over if they come back.
+        assert_eq!(strikes.strike("ognf2"), TimeoutOutcome::SitOutIn(1));
+        strikes.clear("ognf2");
+        assert_eq!(strikes.strike("ognf2"), TimeoutOutcome::SitOutIn(1));
+    }
+
+    fn get_rate_limiter() -> RateLimiter {
+        RateLimiter::new(RateLimits {
+            abuse: Duration::from_secs(3),
+            burst: 4.0,
+            per_second: 2.0,
+        })
+    }
+
+    #[test]
+    fn bursts_fit_within_rate_limits() {
+        let mut rate_limiter = get_rate_limiter();
+        let t = Instant::now();
+        // A bot joining, starting the game, and acting all at once.
+        for _ in 0..4 {
+            assert_eq!(rate_limiter.check("bot", t), RateCheck::Allowed);
+        }
+        assert_eq!(rate_limiter.check("bot", t), RateCheck::Limited);
+        // Other users have their own buckets.
+        assert_eq!(rate_limiter.check("ognf", t), RateCheck::Allowed);
+
+        // Buckets refill at a steady rate, so acting every so often after
+        // a burst is fine.
+        for secs in 1..10 {
+            let now = t + Duration::from_secs(secs);
+            assert_eq!(rate_limiter.check("bot", now), RateCheck::Allowed);
+            assert_eq!(rate_limiter.check("bot", now), RateCheck::Allowed);
+        }
+
+        // Full buckets are forgotten.
+        rate_limiter.remove_full(t + Duration::from_secs(20));
+        assert!(rate_limiter.buckets.is_empty());
+    }
+
+    #[test]
+    fn sustained_spam_is_abusive() {
+        let mut rate_limiter = get_rate_limiter();
+        let t = Instant::now();
+        // Going over the limit once and then backing off is fine.
+        for _ in 0..4 {
+            rate_limiter.check("ognf", t);
+        }
+        assert_eq!(rate_limiter.check("ognf", t), RateCheck::Limited);
+        let t = t + Duration::from_secs(2);
+        for _ in 0..4 {
+            assert_eq!(rate_limiter.check("ognf", t), RateCheck::Allowed);
+        }
+
+        // Dripping commands faster than the bucket refills keeps it empty,
+        // even though some of the commands still get through.
+        let mut checks = vec![];
+        for millis in (0..4000).step_by(100) {
+            checks.push(rate_limiter.check("ognf", t + Duration::from_millis(millis)));
+        }
+        assert!(checks.contains(&RateCheck::Allowed));
+        assert_eq!(checks.first(), Some(&RateCheck::Limited));
+        assert_eq!(checks.last(), Some(&RateCheck::Abusive));
+    }
+
     #[test]
     fn confirm_username() {
         let server = get_server();
@@ -862,7 +3685,7 @@ mod tests {
         let mut token_manager = TokenManager::new(Duration::ZERO);
 
         let token = token_manager.new_token();
-        token_manager.associate_token_and_stream(token, stream);
+        token_manager.associate_token_and_stream(token, stream, LOCALHOST);
 
         let username = "ognf".to_string();
         assert_eq!(
@@ -890,7 +3713,7 @@ mod tests {
         let mut token_manager = TokenManager::new(Duration::ZERO);
 
         let token = token_manager.new_token();
-        token_manager.associate_token_and_stream(token, stream);
+        token_manager.associate_token_and_stream(token, stream, LOCALHOST);
         token_manager.recycle_expired_tokens();
 
         let username = "ognf".to_string();
@@ -904,6 +3727,117 @@ mod tests {
         );
     }
 
+    #[test]
+    fn rename_confirmed_username() {
+        let server = get_server();
+        let stream = get_stream(&server);
+        let mut token_manager = TokenManager::new(Duration::ZERO);
+
+        let token = token_manager.new_token();
+        token_manager.associate_token_and_stream(token, stream, LOCALHOST);
+
+        let username = "ognf".to_string();
+        let new_username = "ognf2".to_string();
+        assert_eq!(
+            token_manager.rename_confirmed_username(&username, new_username.clone()),
+            Err(ClientError::Unassociated)
+        );
+        assert_eq!(
+            token_manager.associate_token_and_username(token, username.clone()),
+            Ok(())
+        );
+        assert_eq!(token_manager.confirm_username(token), Ok(()));
+        assert_eq!(
+            token_manager.rename_confirmed_username(&username, new_username.clone()),
+            Ok(())
+        );
+        assert_eq!(
+            token_manager.get_confirmed_username_with_token(&token),
+            Ok(new_username.clone())
+        );
+        assert_eq!(
+            token_manager.get_token_with_username(&new_username),
+            Ok(token)
+        );
+        assert_eq!(
+            token_manager.get_token_with_username(&username),
+            Err(ClientError::Unassociated)
+        );
+    }
+
+    #[test]
+    fn mutes_outlive_tokens_and_follow_renames() {
+        let server = get_server();
+        let mut token_manager = TokenManager::new(Duration::ZERO);
+        let connect = |token_manager: &mut TokenManager, username: &str| {
+            let token = token_manager.new_token();
+            token_manager.associate_token_and_stream(token, get_stream(&server), LOCALHOST);
+            token_manager
+                .associate_token_and_username(token, username.to_string())
+                .unwrap();
+            token
+        };
+
+        // Only confirmed users can mute.
+        let token = connect(&mut token_manager, "ognf");
+        assert_eq!(
+            token_manager.mute(&token, "ognf2".to_string()),
+            Err(ClientError::Unassociated)
+        );
+        assert_eq!(token_manager.confirm_username(token), Ok(()));
+        assert_eq!(token_manager.mute(&token, "ognf2".to_string()), Ok(()));
+        assert!(token_manager.is_muted(&token, "ognf2"));
+        assert!(!token_manager.is_muted(&token, "ognf3"));
+
+        // Reconnecting under the same username keeps the mute.
+        assert!(token_manager.recycle_token(token).is_ok());
+        let token = connect(&mut token_manager, "ognf");
+        assert_eq!(token_manager.confirm_username(token), Ok(()));
+        assert!(token_manager.is_muted(&token, "ognf2"));
+
+        // Renaming either user keeps the mute.
+        let other_token = connect(&mut token_manager, "ognf2");
+        assert_eq!(token_manager.confirm_username(other_token), Ok(()));
+        assert_eq!(
+            token_manager.rename_confirmed_username("ognf2", "ognf3".to_string()),
+            Ok(())
+        );
+        assert!(token_manager.is_muted(&token, "ognf3"));
+        assert!(!token_manager.is_muted(&token, "ognf2"));
+        assert_eq!(
+            token_manager.rename_confirmed_username("ognf", "ognf4".to_string()),
+            Ok(())
+        );
+        assert!(token_manager.is_muted(&token, "ognf3"));
+
+        assert_eq!(token_manager.unmute(&token, "ognf3"), Ok(()));
+        assert!(!token_manager.is_muted(&token, "ognf3"));
+    }
+
+    #[test]
+    fn fold_around_hand_is_quick() {
+        let mut state = PokerState::new();
+        for i in 0..3 {
+            let username = i.to_string();
+            state.new_user(&username).unwrap();
+            state.waitlist_user(&username).unwrap();
+        }
+        state.init_start("0").unwrap();
+        state = state.step();
+
+        // Stepping takes the default action for players, so everyone folds
+        // to the big blind. Time spent waiting on actions doesn't count.
+        let timeouts = ServerTimeouts::default();
+        let mut total_timeout = Duration::ZERO;
+        while !matches!(state, PokerState::Lobby(_)) {
+            if !state.step_hint(&timeouts.steps).waits_for_action {
+                total_timeout += timeouts.get_step_timeout(&state);
+            }
+            state = state.step();
+        }
+        assert!(total_timeout < 13 * DEFAULT_STEP_TIMEOUT / 4);
+    }
+
     #[test]
     fn recycle_expired_tokens() {
         let server = get_server();
@@ -915,22 +3849,60 @@ mod tests {
 
         // Create a couple of tokens and immediately recycle them.
         let token1 = token_manager.new_token();
-        token_manager.associate_token_and_stream(token1, stream1);
+        token_manager.associate_token_and_stream(token1, stream1, LOCALHOST);
         let token2 = token_manager.new_token();
-        token_manager.associate_token_and_stream(token2, stream2);
+        token_manager.associate_token_and_stream(token2, stream2, LOCALHOST);
         token_manager.recycle_expired_tokens();
 
         // Tokens are immediately resused.
         let token3 = token_manager.new_token();
-        token_manager.associate_token_and_stream(token1, stream3);
+        token_manager.associate_token_and_stream(token1, stream3, LOCALHOST);
         let token4 = token_manager.new_token();
-        token_manager.associate_token_and_stream(token2, stream4);
-        assert_eq!(token1, Token(2));
+        token_manager.associate_token_and_stream(token2, stream4, LOCALHOST);
+        assert_eq!(token1, Token(WAKER.0 + 1));
         assert_eq!(token1, token3);
-        assert_eq!(token2, Token(3));
+        assert_eq!(token2, Token(WAKER.0 + 2));
         assert_eq!(token2, token4);
     }
 
+    #[test]
+    fn count_tokens_per_ip() {
+        let server = get_server();
+        let stream1 = get_stream(&server);
+        let stream2 = get_stream(&server);
+        let stream3 = get_stream(&server);
+        let mut token_manager = TokenManager::new(Duration::ZERO);
+        let other_ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+        let token1 = token_manager.new_token();
+        token_manager.associate_token_and_stream(token1, stream1, LOCALHOST);
+        let token2 = token_manager.new_token();
+        token_manager.associate_token_and_stream(token2, stream2, LOCALHOST);
+        let token3 = token_manager.new_token();
+        token_manager.associate_token_and_stream(token3, stream3, other_ip);
+        assert_eq!(token_manager.get_num_tokens_with_ip(&LOCALHOST), 2);
+        assert_eq!(token_manager.get_num_tokens_with_ip(&other_ip), 1);
+        assert_eq!(token_manager.get_num_unconfirmed_tokens(), 3);
+
+        // Confirmed tokens still count against their IP.
+        let username = "ognf".to_string();
+        assert_eq!(
+            token_manager.associate_token_and_username(token1, username),
+            Ok(())
+        );
+        assert_eq!(token_manager.confirm_username(token1), Ok(()));
+        assert_eq!(token_manager.get_num_tokens_with_ip(&LOCALHOST), 2);
+        assert_eq!(token_manager.get_num_unconfirmed_tokens(), 2);
+
+        // Both manually recycled and expired tokens free up their IP.
+        assert!(token_manager.recycle_token(token1).is_ok());
+        assert_eq!(token_manager.get_num_tokens_with_ip(&LOCALHOST), 1);
+        token_manager.recycle_expired_tokens();
+        assert_eq!(token_manager.get_num_tokens_with_ip(&LOCALHOST), 0);
+        assert_eq!(token_manager.get_num_tokens_with_ip(&other_ip), 0);
+        assert_eq!(token_manager.get_num_unconfirmed_tokens(), 0);
+    }
+
     #[test]
     fn recycle_token() {
         let server = get_server();
@@ -939,9 +3911,9 @@ mod tests {
         let mut token_manager = TokenManager::new(Duration::ZERO);
 
         let token1 = token_manager.new_token();
-        token_manager.associate_token_and_stream(token1, stream1);
+        token_manager.associate_token_and_stream(token1, stream1, LOCALHOST);
         let token2 = token_manager.new_token();
-        token_manager.associate_token_and_stream(token2, stream2);
+        token_manager.associate_token_and_stream(token2, stream2, LOCALHOST);
 
         let username = "ognf".to_string();
         assert_eq!(
@@ -959,4 +3931,66 @@ mod tests {
         );
         assert_eq!(token1, token_manager.new_token());
     }
+
+    #[test]
+    fn transfer_username() {
+        let server = get_server();
+        let mut token_manager = TokenManager::new(Duration::ZERO);
+        let mut new_token = || {
+            let token = token_manager.new_token();
+            token_manager.associate_token_and_stream(token, get_stream(&server), LOCALHOST);
+            token
+        };
+        let token1 = new_token();
+        let token2 = new_token();
+        let token3 = new_token();
+
+        // Usernames have to be confirmed before they can be handed over.
+        let username = "ognf".to_string();
+        assert_eq!(
+            token_manager.transfer_username(token2, &username, 0).err(),
+            Some(ClientError::Unassociated)
+        );
+        assert!(token_manager
+            .associate_token_and_username(token1, username.clone())
+            .is_ok());
+        assert!(token_manager.confirm_username(token1).is_ok());
+        let secret = token_manager.issue_secret(token1);
+
+        // Only the secret the username was issued hands it over.
+        assert_eq!(
+            token_manager
+                .transfer_username(token2, &username, secret.wrapping_add(1))
+                .err(),
+            Some(ClientError::AlreadyAssociated)
+        );
+        assert_eq!(
+            token_manager
+                .transfer_username(token2, &username, secret)
+                .map(|(old_token, _)| old_token),
+            Ok(token1)
+        );
+        assert_eq!(
+            token_manager.get_confirmed_username_with_token(&token2),
+            Ok(username.clone())
+        );
+        assert_eq!(
+            token_manager.get_confirmed_username_with_token(&token1),
+            Err(ClientError::Unassociated)
+        );
+
+        // The secret follows the username to its new token.
+        assert_eq!(
+            token_manager
+                .transfer_username(token3, &username, secret)
+                .map(|(old_token, _)| old_token),
+            Ok(token2)
+        );
+        assert_eq!(
+            token_manager
+                .transfer_username(token1, &username, secret)
+                .err(),
+            Some(ClientError::Expired)
+        );
+    }
 }
@@ -0,0 +1,189 @@
+//! Clocks the game thread tells time with.
+//!
+//! Servers go by the system's clock. Tests can swap in a virtual clock
+//! that only moves when they move it, playing out action timeouts, step
+//! timeouts, and countdowns without actually waiting on them.
+
+use std::{
+    sync::{
+        mpsc::{Receiver, RecvTimeoutError},
+        Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::net::messages::ClientMessage;
+
+/// How often a virtual clock checks whether it's been moved while
+/// waiting on messages.
+const VIRTUAL_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Source of time for the game thread, which also waits on messages from
+/// the IO thread since waits are only as long as the clock says they are.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// Wait up to `timeout` for a message from the IO thread.
+    fn recv_timeout(
+        &self,
+        rx: &Receiver<ClientMessage>,
+        timeout: Duration,
+    ) -> Result<ClientMessage, RecvTimeoutError>;
+}
+
+/// The system's clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn recv_timeout(
+        &self,
+        rx: &Receiver<ClientMessage>,
+        timeout: Duration,
+    ) -> Result<ClientMessage, RecvTimeoutError> {
+        rx.recv_timeout(timeout)
+    }
+}
+
+/// Virtual time, measured from when the clock was made.
+#[derive(Debug, Default)]
+struct VirtualTime {
+    elapsed: Duration,
+    /// When the wait in progress times out, if there is one.
+    deadline: Option<Duration>,
+}
+
+/// A clock that stands still until it's advanced. Messages are still
+/// received as soon as they're sent, but waits only time out once the
+/// clock's been advanced past them.
+#[derive(Debug)]
+pub struct VirtualClock {
+    start: Instant,
+    time: Mutex<VirtualTime>,
+}
+
+impl VirtualClock {
+    /// Move the clock forward, timing out any waits that are over.
+    pub fn advance(&self, duration: Duration) {
+        self.lock().elapsed += duration;
+    }
+
+    /// Move the clock forward to when the wait in progress times out,
+    /// returning whether there was one.
+    pub fn advance_to_deadline(&self) -> bool {
+        let mut time = self.lock();
+        match time.deadline {
+            Some(deadline) => {
+                time.elapsed = time.elapsed.max(deadline);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return how far the clock has been advanced since it was made.
+    pub fn elapsed(&self) -> Duration {
+        self.lock().elapsed
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VirtualTime> {
+        self.time.lock().expect("virtual clock lock")
+    }
+
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            time: Mutex::new(VirtualTime::default()),
+        }
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn recv_timeout(
+        &self,
+        rx: &Receiver<ClientMessage>,
+        timeout: Duration,
+    ) -> Result<ClientMessage, RecvTimeoutError> {
+        let deadline = {
+            let mut time = self.lock();
+            let deadline = time.elapsed + timeout;
+            time.deadline = Some(deadline);
+            deadline
+        };
+        let result = loop {
+            match rx.recv_timeout(VIRTUAL_POLL_INTERVAL) {
+                Err(RecvTimeoutError::Timeout) if self.elapsed() < deadline => continue,
+                result => break result,
+            }
+        };
+        self.lock().deadline = None;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::mpsc::{channel, RecvTimeoutError},
+        thread,
+        time::Duration,
+    };
+
+    use crate::net::messages::{ClientMessage, UserCommand};
+
+    use super::{Clock, VirtualClock};
+
+    #[test]
+    fn virtual_clocks_only_time_out_once_advanced() {
+        let clock = VirtualClock::new();
+        let start = clock.now();
+        assert!(!clock.advance_to_deadline());
+        let (tx, rx) = channel();
+        let rx = thread::scope(|s| {
+            let clock = &clock;
+            let waiter = s.spawn(move || {
+                let result = clock.recv_timeout(&rx, Duration::from_secs(30));
+                (result, rx)
+            });
+            thread::sleep(Duration::from_millis(20));
+            clock.advance(Duration::from_secs(29));
+            thread::sleep(Duration::from_millis(20));
+            assert!(!waiter.is_finished());
+            assert!(clock.advance_to_deadline());
+            let (result, rx) = waiter.join().unwrap();
+            assert!(matches!(result, Err(RecvTimeoutError::Timeout)));
+            rx
+        });
+        assert_eq!(clock.now() - start, Duration::from_secs(30));
+        assert!(!clock.advance_to_deadline());
+
+        // Messages don't wait on the clock.
+        tx.send(ClientMessage {
+            username: "ognf".to_string(),
+            command: UserCommand::Leave,
+        })
+        .unwrap();
+        let msg = clock.recv_timeout(&rx, Duration::from_secs(30)).unwrap();
+        assert_eq!(msg.command, UserCommand::Leave);
+        drop(tx);
+        assert!(matches!(
+            clock.recv_timeout(&rx, Duration::from_secs(1)),
+            Err(RecvTimeoutError::Disconnected)
+        ));
+        assert_eq!(clock.elapsed(), Duration::from_secs(30));
+    }
+}
@@ -0,0 +1,290 @@
+//! An append-only journal of what the game accepted, for settling disputes
+//! about what happened during a hand after the fact.
+//!
+//! The journal is written by the game thread as line-delimited JSON, and is
+//! rotated once it reaches a configurable size. Journaling is best-effort;
+//! if the journal can't be written to, journaling is disabled rather than
+//! interrupting the game.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::error;
+
+use crate::{
+    game::entities::{Action, GameEvent, GamePhase, HandId, Username},
+    net::messages::ClientMessage,
+};
+
+pub const DEFAULT_JOURNAL_MAX_FILE_SIZE: u64 = 1 << 24;
+pub const DEFAULT_JOURNAL_NUM_ROTATED_FILES: usize = 4;
+
+pub struct JournalConfig {
+    /// File that's currently being written to. Rotated files have their
+    /// rotation number appended to this path, with higher numbers being
+    /// older (e.g., `journal.jsonl.1` is the most recently rotated file).
+    pub path: PathBuf,
+    /// Size in bytes the journal can reach before it's rotated.
+    pub max_file_size: u64,
+    /// Number of rotated files to keep. Older files are deleted. If zero,
+    /// the journal is truncated instead of rotated.
+    pub num_rotated_files: usize,
+}
+
+impl JournalConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_file_size: DEFAULT_JOURNAL_MAX_FILE_SIZE,
+            num_rotated_files: DEFAULT_JOURNAL_NUM_ROTATED_FILES,
+        }
+    }
+
+    fn get_rotated_path(&self, rotation: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{rotation}"));
+        path.into()
+    }
+}
+
+/// Something the game accepted or did.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum JournalRecord {
+    /// A user's command that the game accepted, with any action
+    /// sanitized the same way the game sanitized it.
+    Accepted(ClientMessage),
+    /// An action the game took on behalf of a user that didn't act in
+    /// time or couldn't act at all.
+    Forced(Username, Action),
+    Event(GameEvent),
+    Phase(GamePhase),
+}
+
+/// A line in the journal.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JournalEntry {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The hand that was being played, or the last hand that was played
+    /// if a hand wasn't in progress.
+    pub hand_id: HandId,
+    pub record: JournalRecord,
+}
+
+pub struct Journal {
+    config: JournalConfig,
+    /// Size of the file that's currently being written to, including
+    /// anything that's still buffered.
+    size: u64,
+    /// `None` if journaling was disabled because of an error.
+    writer: Option<BufWriter<File>>,
+}
+
+impl Journal {
+    /// Write anything that's buffered to the journal.
+    pub fn flush(&mut self) {
+        let result = match self.writer {
+            Some(ref mut writer) => writer.flush(),
+            None => return,
+        };
+        self.disable_on_error(result);
+    }
+
+    /// Open the journal, appending to it if it already exists. Journaling
+    /// is disabled if the journal can't be opened.
+    pub fn open(config: JournalConfig) -> Self {
+        let mut journal = Self {
+            config,
+            size: 0,
+            writer: None,
+        };
+        let result = journal.open_writer();
+        journal.disable_on_error(result);
+        journal
+    }
+
+    pub fn record(&mut self, hand_id: HandId, record: JournalRecord) {
+        if self.writer.is_none() {
+            return;
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |t| t.as_millis() as u64);
+        let entry = JournalEntry {
+            timestamp,
+            hand_id,
+            record,
+        };
+        let result = self.write_entry(&entry);
+        self.disable_on_error(result);
+    }
+
+    pub fn record_events<'a>(
+        &mut self,
+        hand_id: HandId,
+        events: impl IntoIterator<Item = &'a GameEvent>,
+    ) {
+        for event in events {
+            self.record(hand_id, JournalRecord::Event(event.clone()));
+        }
+    }
+
+    fn disable_on_error(&mut self, result: io::Result<()>) {
+        if let Err(error) = result {
+            let path = self.config.path.display();
+            error!("disabling journal at {path}: {error}");
+            self.writer = None;
+        }
+    }
+
+    fn open_writer(&mut self) -> io::Result<()> {
+        let file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.config.path)?;
+        self.size = file.metadata()?.len();
+        self.writer = Some(BufWriter::new(file));
+        Ok(())
+    }
+
+    /// Move the current file (and every rotated file) back one rotation,
+    /// deleting the oldest one, and start a new file.
+    fn rotate(&mut self) -> io::Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+        if self.config.num_rotated_files == 0 {
+            fs::remove_file(&self.config.path)?;
+        } else {
+            for rotation in (1..self.config.num_rotated_files).rev() {
+                let from = self.config.get_rotated_path(rotation);
+                if from.exists() {
+                    fs::rename(from, self.config.get_rotated_path(rotation + 1))?;
+                }
+            }
+            fs::rename(&self.config.path, self.config.get_rotated_path(1))?;
+        }
+        self.open_writer()
+    }
+
+    fn write_entry(&mut self, entry: &JournalEntry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let len = line.len() as u64;
+        if self.size > 0 && self.size + len > self.config.max_file_size {
+            self.rotate()?;
+        }
+        match self.writer {
+            Some(ref mut writer) => {
+                writer.write_all(&line)?;
+                self.size += len;
+                Ok(())
+            }
+            None => unreachable!("entries are only written while journaling is enabled"),
+        }
+    }
+}
+
+impl Drop for Journal {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        io::{BufRead, BufReader},
+        path::PathBuf,
+        process,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use crate::{
+        game::entities::{Action, GamePhase},
+        net::messages::{ClientMessage, UserCommand},
+    };
+
+    use super::{Journal, JournalConfig, JournalEntry, JournalRecord};
+
+    fn get_temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("pp_journal_{name}_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn read_entries(path: &PathBuf) -> Vec<JournalEntry> {
+        let file = fs::File::open(path).unwrap();
+        BufReader::new(file)
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn rotate_at_max_file_size() {
+        let dir = get_temp_dir("rotate");
+        let record = JournalRecord::Accepted(ClientMessage {
+            username: "ognf".to_string(),
+            command: UserCommand::TakeAction(Action::Fold),
+        });
+
+        // Size the journal so it fits exactly 2 entries.
+        let entry = JournalEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64,
+            hand_id: 0,
+            record: record.clone(),
+        };
+        let entry_size = serde_json::to_vec(&entry).unwrap().len() as u64 + 1;
+        let mut config = JournalConfig::new(dir.join("journal.jsonl"));
+        config.max_file_size = 2 * entry_size;
+        config.num_rotated_files = 2;
+
+        // Hand IDs are used to tell the entries apart.
+        let mut journal = Journal::open(config);
+        for hand_id in 0..7 {
+            journal.record(hand_id, record.clone());
+        }
+        drop(journal);
+        let hand_ids = |path: &str| -> Vec<_> {
+            read_entries(&dir.join(path))
+                .into_iter()
+                .map(|entry| entry.hand_id)
+                .collect()
+        };
+        assert_eq!(hand_ids("journal.jsonl"), vec![6]);
+        assert_eq!(hand_ids("journal.jsonl.1"), vec![4, 5]);
+        assert_eq!(hand_ids("journal.jsonl.2"), vec![2, 3]);
+        assert!(!dir.join("journal.jsonl.3").exists());
+
+        // Reopening the journal appends to it rather than overwriting it.
+        let mut config = JournalConfig::new(dir.join("journal.jsonl"));
+        config.max_file_size = 2 * entry_size;
+        config.num_rotated_files = 2;
+        let mut journal = Journal::open(config);
+        journal.record(7, JournalRecord::Phase(GamePhase::Lobby));
+        drop(journal);
+        assert_eq!(hand_ids("journal.jsonl"), vec![6, 7]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unwritable_journal_is_disabled() {
+        let dir = get_temp_dir("disabled");
+        // The journal's path is a directory, so it can't be opened.
+        let mut journal = Journal::open(JournalConfig::new(&dir));
+        assert!(journal.writer.is_none());
+        journal.record(0, JournalRecord::Phase(GamePhase::Lobby));
+        journal.flush();
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,509 @@
+//! Lightweight server metrics for monitoring long-lived poker servers.
+//!
+//! Counters are shared between the server's IO thread and game thread,
+//! and can be exposed as Prometheus-style text over a bare-bones HTTP
+//! endpoint. The game thread also times how long it spends in each
+//! state so hands that stall can be traced to the state they stall in.
+
+use std::{
+    fmt,
+    io::{Read, Write},
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+use crate::{game::PokerState, net::messages::UserCommand};
+
+pub const METRICS_READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Declare the label of each user command variant once, generating the
+/// labels in order along with an exhaustive match from commands to their
+/// labels, so new commands can't be counted under another's label.
+macro_rules! command_labels {
+    ($($command:pat => $label:literal,)*) => {
+        /// Labels for client messages, one for each user command variant.
+        const COMMAND_LABELS: &[&str] = &[$($label),*];
+
+        fn command_to_label(command: &UserCommand) -> &'static str {
+            match command {
+                $($command => $label,)*
+            }
+        }
+    };
+}
+
+command_labels! {
+    UserCommand::AcceptInsurance { .. } => "accept_insurance",
+    UserCommand::AddChips(_) => "add_chips",
+    UserCommand::ChangeSeat(_) => "change_seat",
+    UserCommand::ChangeState(_) => "change_state",
+    UserCommand::Connect => "connect",
+    UserCommand::Disconnect => "disconnect",
+    UserCommand::Leave => "leave",
+    UserCommand::MuckHand => "muck_hand",
+    UserCommand::Mute(_) => "mute",
+    UserCommand::OfferInsurance { .. } => "offer_insurance",
+    UserCommand::Pong => "pong",
+    UserCommand::QueryLeaderboard => "query_leaderboard",
+    UserCommand::Rejoin => "rejoin",
+    UserCommand::Rename(_) => "rename",
+    UserCommand::Resync => "resync",
+    UserCommand::SetAutoRebuy(_) => "set_auto_rebuy",
+    UserCommand::SetProfile(_) => "set_profile",
+    UserCommand::SetVerbosity(_) => "set_verbosity",
+    UserCommand::ShowCard(_) => "show_card",
+    UserCommand::ShowHand => "show_hand",
+    UserCommand::SideBet(..) => "side_bet",
+    UserCommand::StartGame => "start_game",
+    UserCommand::TakeAction(_) => "take_action",
+    UserCommand::TakeTurn { .. } => "take_turn",
+    UserCommand::Unmute(_) => "unmute",
+    UserCommand::ViewAllCards => "view_all_cards",
+}
+
+fn command_to_idx(command: &UserCommand) -> usize {
+    let label = command_to_label(command);
+    COMMAND_LABELS
+        .iter()
+        .position(|command_label| *command_label == label)
+        .expect("every command has a label")
+}
+
+/// Declare the label of each poker state variant once, generating the
+/// labels in order along with an exhaustive match from states to their
+/// labels, so new states can't be timed under another's label.
+macro_rules! phase_labels {
+    ($($state:pat => $label:literal,)*) => {
+        /// Labels for poker states, one for each state variant.
+        const PHASE_LABELS: &[&str] = &[$($label),*];
+
+        fn state_to_label(state: &PokerState) -> &'static str {
+            match state {
+                $($state => $label,)*
+            }
+        }
+    };
+}
+
+phase_labels! {
+    PokerState::Lobby(_) => "lobby",
+    PokerState::SeatPlayers(_) => "seat_players",
+    PokerState::MoveButton(_) => "move_button",
+    PokerState::CollectBlinds(_) => "collect_blinds",
+    PokerState::Deal(_) => "deal",
+    PokerState::TakeAction(_) => "take_action",
+    PokerState::Flop(_) => "flop",
+    PokerState::Turn(_) => "turn",
+    PokerState::River(_) => "river",
+    PokerState::ShowHands(_) => "show_hands",
+    PokerState::DistributePot(_) => "distribute_pot",
+    PokerState::RemovePlayers(_) => "remove_players",
+    PokerState::DivideDonations(_) => "divide_donations",
+    PokerState::UpdateBlinds(_) => "update_blinds",
+    PokerState::BootPlayers(_) => "boot_players",
+}
+
+fn state_to_idx(state: &PokerState) -> usize {
+    let label = state_to_label(state);
+    PHASE_LABELS
+        .iter()
+        .position(|phase_label| *phase_label == label)
+        .expect("every state has a label")
+}
+
+/// Upper bounds of histogram buckets, in seconds. They span states that
+/// should be instant up to players using their whole action timeout.
+const BUCKET_BOUNDS: [f64; 8] = [0.001, 0.01, 0.1, 0.5, 1.0, 5.0, 15.0, 60.0];
+
+/// A Prometheus-style histogram of durations.
+#[derive(Debug, Default)]
+pub struct Histogram {
+    /// Number of observations in each bucket. Unlike Prometheus buckets,
+    /// these aren't cumulative, and observations larger than the last
+    /// bound are only counted in the total.
+    buckets: [AtomicUsize; BUCKET_BOUNDS.len()],
+    count: AtomicUsize,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    pub fn get_count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn get_sum(&self) -> Duration {
+        Duration::from_micros(self.sum_micros.load(Ordering::Relaxed))
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(idx) = BUCKET_BOUNDS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    /// Render the histogram's series, with `labels` being any labels
+    /// to prefix the bucket label with (e.g., `phase="deal",`).
+    fn write_prometheus(&self, text: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (bound, value) in BUCKET_BOUNDS.iter().zip(self.buckets.iter()) {
+            cumulative += value.load(Ordering::Relaxed);
+            text.push_str(&format!(
+                "{name}_bucket{{{labels}le=\"{bound}\"}} {cumulative}\n"
+            ));
+        }
+        let count = self.get_count();
+        let sum = self.get_sum().as_secs_f64();
+        text.push_str(&format!("{name}_bucket{{{labels}le=\"+Inf\"}} {count}\n"));
+        let labels = labels.trim_end_matches(',');
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{labels}}}")
+        };
+        text.push_str(&format!("{name}_sum{labels} {sum}\n"));
+        text.push_str(&format!("{name}_count{labels} {count}\n"));
+    }
+}
+
+/// Server counters. All counters are monotonically increasing except
+/// for the number of active tokens, which is a gauge.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Number of TCP connections accepted by the server.
+    pub accepted_connections: AtomicUsize,
+    /// Number of tokens currently associated with a connection.
+    pub active_tokens: AtomicUsize,
+    /// Number of client messages received, indexed by user command.
+    client_messages: [AtomicUsize; COMMAND_LABELS.len()],
+    /// Number of times a player was forced to fold because they
+    /// ran out of time or left, and they couldn't check.
+    pub forced_folds: AtomicUsize,
+    /// Number of hands played to completion.
+    pub hands_completed: AtomicUsize,
+    /// Time spent in each state, indexed by state, from when the game
+    /// entered the state until it stepped out of it.
+    phase_durations: [Histogram; PHASE_LABELS.len()],
+    /// Time spent waiting on the player during each turn.
+    pub turn_waiting: Histogram,
+    /// Time spent processing commands during each turn.
+    pub turn_processing: Histogram,
+}
+
+impl Metrics {
+    pub fn get_client_messages(&self, command: &UserCommand) -> usize {
+        self.client_messages[command_to_idx(command)].load(Ordering::Relaxed)
+    }
+
+    pub fn record_client_message(&self, command: &UserCommand) {
+        self.client_messages[command_to_idx(command)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Render metrics in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut text = String::new();
+        for (name, kind, value) in [
+            (
+                "pp_accepted_connections_total",
+                "counter",
+                &self.accepted_connections,
+            ),
+            ("pp_active_tokens", "gauge", &self.active_tokens),
+            ("pp_forced_folds_total", "counter", &self.forced_folds),
+            ("pp_hands_completed_total", "counter", &self.hands_completed),
+        ] {
+            let value = value.load(Ordering::Relaxed);
+            text.push_str(&format!("# TYPE {name} {kind}\n{name} {value}\n"));
+        }
+        text.push_str("# TYPE pp_client_messages_total counter\n");
+        for (label, value) in COMMAND_LABELS.iter().zip(self.client_messages.iter()) {
+            let value = value.load(Ordering::Relaxed);
+            text.push_str(&format!(
+                "pp_client_messages_total{{command=\"{label}\"}} {value}\n"
+            ));
+        }
+        text.push_str("# TYPE pp_phase_duration_seconds histogram\n");
+        for (label, histogram) in PHASE_LABELS.iter().zip(self.phase_durations.iter()) {
+            let labels = format!("phase=\"{label}\",");
+            histogram.write_prometheus(&mut text, "pp_phase_duration_seconds", &labels);
+        }
+        for (name, histogram) in [
+            ("pp_turn_waiting_seconds", &self.turn_waiting),
+            ("pp_turn_processing_seconds", &self.turn_processing),
+        ] {
+            text.push_str(&format!("# TYPE {name} histogram\n"));
+            histogram.write_prometheus(&mut text, name, "");
+        }
+        text
+    }
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let num_client_messages: usize = self
+            .client_messages
+            .iter()
+            .map(|value| value.load(Ordering::Relaxed))
+            .sum();
+        write!(
+            f,
+            "{} connections accepted, {} active tokens, {} client messages, {} hands completed, {} forced folds",
+            self.accepted_connections.load(Ordering::Relaxed),
+            self.active_tokens.load(Ordering::Relaxed),
+            num_client_messages,
+            self.hands_completed.load(Ordering::Relaxed),
+            self.forced_folds.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Where a hand's time went, summarized once the hand is over.
+#[derive(Clone, Debug, Default)]
+pub struct HandTimings {
+    /// Time spent in each state, indexed by state, or `None` for states
+    /// the hand never entered.
+    phases: [Option<Duration>; PHASE_LABELS.len()],
+    /// Time spent waiting on players to act.
+    pub waiting: Duration,
+    /// Time spent processing commands while players were acting.
+    pub processing: Duration,
+    /// Number of times a player was forced to fold.
+    pub forced_folds: usize,
+}
+
+impl HandTimings {
+    /// Return the total time spent in all states.
+    pub fn total(&self) -> Duration {
+        self.phases.iter().flatten().sum()
+    }
+}
+
+impl fmt::Display for HandTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "hand took {:.3?} (", self.total())?;
+        let mut phases = PHASE_LABELS
+            .iter()
+            .zip(self.phases.iter())
+            .filter_map(|(label, duration)| duration.map(|duration| (label, duration)));
+        if let Some((label, duration)) = phases.next() {
+            write!(f, "{label} {duration:.3?}")?;
+        }
+        for (label, duration) in phases {
+            write!(f, ", {label} {duration:.3?}")?;
+        }
+        write!(
+            f,
+            "), {:.3?} waiting on players, {:.3?} processing commands, {} forced folds",
+            self.waiting, self.processing, self.forced_folds
+        )
+    }
+}
+
+/// Times the states the game steps through. The game thread tells the
+/// timer right before it steps a state, and the time since the last step
+/// is charged to that state.
+#[derive(Debug)]
+pub struct PhaseTimer {
+    entered_at: Instant,
+    hand: HandTimings,
+    turn_waiting: Duration,
+    turn_processing: Duration,
+}
+
+impl PhaseTimer {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            entered_at: now,
+            hand: HandTimings::default(),
+            turn_waiting: Duration::ZERO,
+            turn_processing: Duration::ZERO,
+        }
+    }
+
+    pub fn record_forced_fold(&mut self) {
+        self.hand.forced_folds += 1;
+    }
+
+    /// Record time spent processing commands during a turn.
+    pub fn record_processing(&mut self, duration: Duration) {
+        self.turn_processing += duration;
+    }
+
+    /// Record time spent waiting on a player during a turn.
+    pub fn record_waiting(&mut self, duration: Duration) {
+        self.turn_waiting += duration;
+    }
+
+    /// Charge the time since the last step to the state that's about to
+    /// be stepped. Returns the hand's timings if the state is the last
+    /// one of a hand, starting the next hand's timings from scratch.
+    pub fn record_step(
+        &mut self,
+        state: &PokerState,
+        now: Instant,
+        metrics: &Metrics,
+    ) -> Option<HandTimings> {
+        let elapsed = now.saturating_duration_since(self.entered_at);
+        self.entered_at = now;
+        let idx = state_to_idx(state);
+        metrics.phase_durations[idx].observe(elapsed);
+        *self.hand.phases[idx].get_or_insert(Duration::ZERO) += elapsed;
+        if let PokerState::TakeAction(_) = state {
+            metrics.turn_waiting.observe(self.turn_waiting);
+            metrics.turn_processing.observe(self.turn_processing);
+            self.hand.waiting += self.turn_waiting;
+            self.hand.processing += self.turn_processing;
+        }
+        self.turn_waiting = Duration::ZERO;
+        self.turn_processing = Duration::ZERO;
+        match state {
+            PokerState::BootPlayers(_) => Some(std::mem::take(&mut self.hand)),
+            _ => None,
+        }
+    }
+}
+
+/// Serve metrics over plaintext HTTP from a listener that's already
+/// bound, so a bad address is caught before the server starts. Every
+/// request gets the same response regardless of its method or path, so
+/// the listener should only be bound to an address that's private to
+/// monitoring tools.
+pub fn serve(listener: TcpListener, metrics: Arc<Metrics>) {
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                warn!("failed to accept metrics connection: {error}");
+                continue;
+            }
+        };
+        // The request itself doesn't matter, but we do our best to
+        // read it so clients don't see a reset connection.
+        let mut buf = [0; 1024];
+        if let Err(error) = stream
+            .set_read_timeout(Some(METRICS_READ_TIMEOUT))
+            .and_then(|()| stream.read(&mut buf))
+        {
+            debug!("failed to read metrics request: {error}");
+        }
+        let body = metrics.to_prometheus();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        if let Err(error) = stream.write_all(response.as_bytes()) {
+            debug!("failed to write metrics response: {error}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+        entities::Action,
+        game::PokerState,
+        net::messages::{UserCommand, UserState},
+    };
+
+    use super::{Metrics, PhaseTimer, COMMAND_LABELS, PHASE_LABELS};
+
+    #[test]
+    fn command_labels_are_unique() {
+        // Commands are counted by their label's position.
+        for (idx, label) in COMMAND_LABELS.iter().enumerate() {
+            assert_eq!(COMMAND_LABELS.iter().position(|l| l == label), Some(idx));
+        }
+    }
+
+    #[test]
+    fn phase_labels_are_unique() {
+        // States are timed by their label's position.
+        for (idx, label) in PHASE_LABELS.iter().enumerate() {
+            assert_eq!(PHASE_LABELS.iter().position(|l| l == label), Some(idx));
+        }
+    }
+
+    #[test]
+    fn record_client_messages() {
+        let metrics = Metrics::default();
+        metrics.record_client_message(&UserCommand::Connect);
+        metrics.record_client_message(&UserCommand::ChangeState(UserState::Play));
+        metrics.record_client_message(&UserCommand::ChangeState(UserState::Spectate));
+        assert_eq!(metrics.get_client_messages(&UserCommand::Connect), 1);
+        assert_eq!(
+            metrics.get_client_messages(&UserCommand::ChangeState(UserState::Play)),
+            2
+        );
+        assert_eq!(metrics.get_client_messages(&UserCommand::StartGame), 0);
+        let text = metrics.to_prometheus();
+        assert!(text.contains("pp_client_messages_total{command=\"change_state\"} 2\n"));
+        assert!(text.contains("pp_client_messages_total{command=\"connect\"} 1\n"));
+        assert!(text.contains("pp_hands_completed_total 0\n"));
+    }
+
+    #[test]
+    fn record_phase_durations() {
+        let metrics = Metrics::default();
+        let mut state = PokerState::new();
+        for i in 0..3 {
+            let username = i.to_string();
+            state.new_user(&username).unwrap();
+            state.waitlist_user(&username).unwrap();
+        }
+        state.init_start("0").unwrap();
+
+        // Everyone calls or checks so the hand goes through every state.
+        let started_at = Instant::now();
+        let mut timer = PhaseTimer::new(started_at);
+        let timings = loop {
+            if let Some(username) = state.get_next_action_username() {
+                timer.record_waiting(Duration::from_millis(1));
+                let action = state
+                    .get_action_options()
+                    .unwrap()
+                    .into_iter()
+                    .find(|action| matches!(action, Action::Call(_)))
+                    .unwrap_or(Action::Check);
+                state.take_action(&username, action).unwrap();
+            }
+            let now = Instant::now();
+            if let Some(timings) = timer.record_step(&state, now, &metrics) {
+                assert_eq!(timings.total(), now - started_at);
+                break timings;
+            }
+            state = state.step();
+        };
+        assert!(timings.total() <= started_at.elapsed());
+        assert!(timings.waiting >= Duration::from_millis(1));
+        assert_eq!(timings.forced_folds, 0);
+
+        let text = metrics.to_prometheus();
+        for (idx, label) in PHASE_LABELS.iter().enumerate() {
+            let histogram = &metrics.phase_durations[idx];
+            assert!(histogram.get_count() > 0, "{label} wasn't recorded");
+            assert!(text.contains(&format!(
+                "pp_phase_duration_seconds_count{{phase=\"{label}\"}} {}\n",
+                histogram.get_count()
+            )));
+            // Every state is also part of the hand's summary.
+            assert!(timings.phases[idx].is_some(), "{label} wasn't summarized");
+            assert!(timings.to_string().contains(label));
+        }
+        // Every turn is split into waiting and processing.
+        let take_action = PHASE_LABELS.iter().position(|l| *l == "take_action");
+        let turns = metrics.phase_durations[take_action.unwrap()].get_count();
+        assert_eq!(metrics.turn_waiting.get_count(), turns);
+        assert_eq!(metrics.turn_processing.get_count(), turns);
+        assert!(text.contains("pp_turn_waiting_seconds_bucket{le=\"+Inf\"}"));
+    }
+}
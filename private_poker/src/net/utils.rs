@@ -1,29 +1,176 @@
 use bincode::{deserialize, serialize, ErrorKind};
 use serde::{de::DeserializeOwned, Serialize};
-use std::io::{self, Read, Write};
-
-pub fn read_prefixed<T: DeserializeOwned, R: Read>(reader: &mut R) -> io::Result<T> {
-    // Read the size as a u32
-    let mut len_bytes = [0; 4];
-    reader.read_exact(&mut len_bytes)?;
-    let len = u32::from_le_bytes(len_bytes) as usize;
-
-    // Read the remaining data. If we get a would block error,
-    // then it's very likely that the sender doesn't follow the
-    // prefix protocol. Return an invalid data error to let
-    // the readers determine how to handle such senders. It is
-    // possible for the would block error to be something that
-    // isn't as sketchy, but that should be pretty rare.
-    let mut buf = vec![0; len];
-    if let Err(error) = reader.read_exact(&mut buf) {
-        let kind = match error.kind() {
-            io::ErrorKind::WouldBlock => io::ErrorKind::InvalidData,
-            error => error,
+use std::{
+    io::{self, Read, Write},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+use super::messages::{ClientFrame, SequencedMessage, ServerMessage, UserCommand};
+
+/// Largest frame that can be written or read, large enough for a game
+/// view of a full table with plenty of spectators and waitlisters.
+pub const MAX_FRAME_SIZE: usize = 1 << 20;
+
+/// How much is read from a stream at a time when reassembling frames.
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// Size of the tag that comes after a frame's length.
+const TAG_SIZE: usize = size_of::<Tag>();
+
+/// Identifies what kind of message a frame holds.
+pub type Tag = u16;
+
+/// Messages that are framed with a tag saying what kind of message they
+/// are, followed by the message's payload.
+///
+/// Frames are a little-endian `u32` length, then a little-endian [`Tag`],
+/// then the payload, with the length covering the tag and the payload.
+/// Readers skip frames with tags they don't know about instead of
+/// failing on them, so newer versions can add kinds of messages without
+/// breaking older versions that'll never need them.
+pub trait Tagged: Sized {
+    /// Split the message into its tag and its payload.
+    fn to_tagged(&self) -> io::Result<(Tag, Vec<u8>)>;
+
+    /// Put the message back together from its tag and its payload,
+    /// returning `None` if the tag isn't one this version knows about.
+    fn from_tagged(tag: Tag, payload: &[u8]) -> Option<io::Result<Self>>;
+}
+
+/// Messages are tagged with the kind of server message they are, and
+/// their sequence number starts their payload.
+impl Tagged for SequencedMessage {
+    fn to_tagged(&self) -> io::Result<(Tag, Vec<u8>)> {
+        let (tag, fields) = split_variant(&self.msg, &ServerMessage::TAGS)?;
+        let mut payload = Vec::from(self.seq.to_le_bytes());
+        payload.extend(fields);
+        Ok((tag, payload))
+    }
+
+    fn from_tagged(tag: Tag, payload: &[u8]) -> Option<io::Result<Self>> {
+        let Some((seq_bytes, fields)) = payload.split_first_chunk() else {
+            return ServerMessage::TAGS
+                .contains(&tag)
+                .then(|| Err(io::ErrorKind::InvalidData.into()));
         };
-        return Err(kind.into());
+        let seq = u64::from_le_bytes(*seq_bytes);
+        join_variant(tag, fields, &ServerMessage::TAGS).map(|msg| Ok(Self { seq, msg: msg? }))
+    }
+}
+
+/// Commands are tagged with the kind of command they are, so servers can
+/// skip commands from newer clients that they don't know about.
+impl Tagged for ClientFrame {
+    fn to_tagged(&self) -> io::Result<(Tag, Vec<u8>)> {
+        match self {
+            ClientFrame::Connect(username) => {
+                Ok((ClientFrame::CONNECT_TAG, serialize_payload(username)?))
+            }
+            ClientFrame::Command(command) => split_variant(command, &UserCommand::TAGS),
+            ClientFrame::ConnectAsOperator { username, secret } => Ok((
+                ClientFrame::OPERATOR_TAG,
+                serialize_payload(&(username, secret))?,
+            )),
+            ClientFrame::Reconnect { username, secret } => Ok((
+                ClientFrame::RECONNECT_TAG,
+                serialize_payload(&(username, secret))?,
+            )),
+        }
     }
 
-    match deserialize(&buf) {
+    fn from_tagged(tag: Tag, payload: &[u8]) -> Option<io::Result<Self>> {
+        if tag == ClientFrame::CONNECT_TAG {
+            return Some(deserialize_frame(payload).map(ClientFrame::Connect));
+        } else if tag == ClientFrame::RECONNECT_TAG {
+            return Some(
+                deserialize_frame(payload)
+                    .map(|(username, secret)| ClientFrame::Reconnect { username, secret }),
+            );
+        } else if tag == ClientFrame::OPERATOR_TAG {
+            return Some(
+                deserialize_frame(payload)
+                    .map(|(username, secret)| ClientFrame::ConnectAsOperator { username, secret }),
+            );
+        }
+        join_variant(tag, payload, &UserCommand::TAGS)
+            .map(|command| command.map(ClientFrame::Command))
+    }
+}
+
+/// Split an enum into its variant's tag and the variant's serialized
+/// contents, given the tags of the enum's variants in the order they're
+/// declared.
+fn split_variant<T: Serialize>(value: &T, tags: &[Tag]) -> io::Result<(Tag, Vec<u8>)> {
+    let mut serialized = serialize_payload(value)?;
+    // Enums are serialized starting with the index of their variant.
+    let tag = serialized
+        .first_chunk::<4>()
+        .and_then(|variant_idx| tags.get(u32::from_le_bytes(*variant_idx) as usize))
+        .ok_or(io::ErrorKind::InvalidData)?;
+    serialized.drain(..4);
+    Ok((*tag, serialized))
+}
+
+/// Put an enum back together from its variant's tag and the variant's
+/// serialized contents, returning `None` if the tag isn't one of the
+/// enum's.
+fn join_variant<T: DeserializeOwned>(
+    tag: Tag,
+    payload: &[u8],
+    tags: &[Tag],
+) -> Option<io::Result<T>> {
+    let variant_idx = tags.iter().position(|variant_tag| *variant_tag == tag)? as u32;
+    let mut serialized = Vec::from(variant_idx.to_le_bytes());
+    serialized.extend(payload);
+    Some(deserialize_frame(&serialized))
+}
+
+pub fn read_prefixed<T: Tagged, R: Read>(reader: &mut R) -> io::Result<T> {
+    read_prefixed_with_max_size(reader, MAX_FRAME_SIZE)
+}
+
+/// Read a tagged, length-prefixed value, refusing frames larger than
+/// `max_size` before anything is allocated for them. Frames with tags
+/// this version doesn't know about are skipped.
+pub fn read_prefixed_with_max_size<T: Tagged, R: Read>(
+    reader: &mut R,
+    max_size: usize,
+) -> io::Result<T> {
+    loop {
+        // Read the size as a u32
+        let mut len_bytes = [0; 4];
+        reader.read_exact(&mut len_bytes)?;
+        let len = u32::from_le_bytes(len_bytes) as usize;
+        if len > max_size {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        // Read the remaining data. If we get a would block error,
+        // then it's very likely that the sender doesn't follow the
+        // prefix protocol. Return an invalid data error to let
+        // the readers determine how to handle such senders. It is
+        // possible for the would block error to be something that
+        // isn't as sketchy, but that should be pretty rare.
+        let mut buf = vec![0; len];
+        if let Err(error) = reader.read_exact(&mut buf) {
+            let kind = match error.kind() {
+                io::ErrorKind::WouldBlock => io::ErrorKind::InvalidData,
+                error => error,
+            };
+            return Err(kind.into());
+        }
+
+        if let Some(result) = untag_frame(&buf) {
+            return result;
+        }
+    }
+}
+
+/// Deserialize a frame's payload, treating anything that isn't an IO
+/// error as invalid data.
+fn deserialize_frame<T: DeserializeOwned>(buf: &[u8]) -> io::Result<T> {
+    match deserialize(buf) {
         Ok(value) => Ok(value),
         Err(error) => match *error {
             ErrorKind::Io(error) => Err(error),
@@ -32,17 +179,11 @@ pub fn read_prefixed<T: DeserializeOwned, R: Read>(reader: &mut R) -> io::Result
     }
 }
 
-pub fn write_prefixed<T: Serialize, W: Write>(writer: &mut W, value: &T) -> io::Result<()> {
-    match serialize(&value) {
-        Ok(serialized) => {
-            // Write the size of the serialized data and the serialized data
-            // all in one chunk to prevent read-side EOF race conditions.
-            let size = serialized.len() as u32;
-            let mut buf = Vec::from(size.to_le_bytes());
-            buf.extend(serialized);
-            writer.write_all(&buf)?;
-            Ok(())
-        }
+/// Serialize a payload, treating anything that isn't an IO error as
+/// invalid data.
+fn serialize_payload<T: Serialize>(value: &T) -> io::Result<Vec<u8>> {
+    match serialize(value) {
+        Ok(serialized) => Ok(serialized),
         Err(error) => match *error {
             ErrorKind::Io(error) => Err(error),
             _ => Err(io::ErrorKind::InvalidData.into()),
@@ -50,13 +191,239 @@ pub fn write_prefixed<T: Serialize, W: Write>(writer: &mut W, value: &T) -> io::
     }
 }
 
+/// Return the message in a frame that's already been read in full,
+/// minus its length, or `None` if its tag is unknown and it should be
+/// skipped.
+fn untag_frame<T: Tagged>(buf: &[u8]) -> Option<io::Result<T>> {
+    let Some((tag_bytes, payload)) = buf.split_first_chunk::<TAG_SIZE>() else {
+        return Some(Err(io::ErrorKind::InvalidData.into()));
+    };
+    let tag = Tag::from_le_bytes(*tag_bytes);
+    let result = T::from_tagged(tag, payload);
+    if result.is_none() {
+        warn!(tag, "skipped a frame with an unknown tag");
+    }
+    result
+}
+
+/// Reassembles length-prefixed frames from a stream that may only have
+/// part of a frame ready at a time.
+///
+/// Reading a frame in one go from a non-blocking stream throws away
+/// whatever was read once the stream would block, so a frame that
+/// arrives in pieces can never be read. The reader keeps everything it
+/// reads across calls instead, and only hands out frames once they're
+/// complete. It also remembers when the frame it's waiting on started,
+/// so senders that never finish a frame can be caught.
+#[derive(Debug)]
+pub struct FrameReader {
+    buf: Vec<u8>,
+    max_size: usize,
+    /// When the first byte of the incomplete frame in the buffer was
+    /// read, or `None` if there isn't one.
+    started_at: Option<Instant>,
+}
+
+impl FrameReader {
+    /// Return whether part of a frame has been waiting on the rest of it
+    /// for longer than `timeout`.
+    pub fn is_stalled(&self, now: Instant, timeout: Duration) -> bool {
+        self.started_at
+            .is_some_and(|started_at| now.saturating_duration_since(started_at) > timeout)
+    }
+
+    /// Make a reader that refuses frames larger than `max_size`.
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            buf: vec![],
+            max_size,
+            started_at: None,
+        }
+    }
+
+    /// Return the next complete frame, reading from the stream only when
+    /// the buffer doesn't have one already. Errors from the stream (like
+    /// would block errors) are returned as-is, keeping everything read
+    /// so far, so the same reader should be read from again once the
+    /// stream has more. Frames larger than the max size are invalid data
+    /// and are rejected before the rest of them is read. Frames with
+    /// tags this version doesn't know about are skipped.
+    pub fn read_from<T: Tagged, R: Read>(&mut self, reader: &mut R) -> io::Result<T> {
+        loop {
+            if let Some(len_bytes) = self.buf.first_chunk::<4>() {
+                let len = u32::from_le_bytes(*len_bytes) as usize;
+                if len > self.max_size {
+                    return Err(io::ErrorKind::InvalidData.into());
+                }
+                if self.buf.len() >= 4 + len {
+                    let result = untag_frame(&self.buf[4..4 + len]);
+                    self.buf.drain(..4 + len);
+                    // Whatever's left over is the start of the next frame,
+                    // which only just arrived.
+                    self.started_at = match self.buf.is_empty() {
+                        true => None,
+                        false => Some(Instant::now()),
+                    };
+                    match result {
+                        Some(result) => return result,
+                        None => continue,
+                    }
+                }
+            }
+
+            let mut chunk = [0; READ_CHUNK_SIZE];
+            match reader.read(&mut chunk) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => {
+                    self.buf.extend(&chunk[..n]);
+                    self.started_at.get_or_insert_with(Instant::now);
+                }
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+pub fn write_prefixed<T: Tagged, W: Write>(writer: &mut W, value: &T) -> io::Result<()> {
+    PartialFrame::new(value)?.write_to(writer)
+}
+
+/// A tagged, length-prefixed frame that may take several writes to send.
+///
+/// Non-blocking streams can accept only part of a frame before they
+/// would block. Writing the whole frame again later would corrupt the
+/// stream, so the frame remembers how much of it was already written
+/// and picks up from there on the next attempt.
+#[derive(Debug)]
+pub struct PartialFrame {
+    buf: Vec<u8>,
+    offset: usize,
+}
+
+impl PartialFrame {
+    pub fn is_complete(&self) -> bool {
+        self.offset == self.buf.len()
+    }
+
+    /// Serialize a value into a frame that hasn't been written yet.
+    pub fn new<T: Tagged>(value: &T) -> io::Result<Self> {
+        let (tag, payload) = value.to_tagged()?;
+        // Readers would reject the frame anyways, so don't bother
+        // sending it.
+        let size = TAG_SIZE + payload.len();
+        if size > MAX_FRAME_SIZE {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        // Keep the size, tag, and payload in one chunk to prevent
+        // read-side EOF race conditions.
+        let mut buf = Vec::with_capacity(4 + size);
+        buf.extend((size as u32).to_le_bytes());
+        buf.extend(tag.to_le_bytes());
+        buf.extend(payload);
+        Ok(Self { buf, offset: 0 })
+    }
+
+    /// Write the rest of the frame, returning once all of it is written.
+    /// Errors (like would block errors) leave the frame where it was, so
+    /// the same frame should be written again before any other frame.
+    pub fn write_to<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        while !self.is_complete() {
+            match writer.write(&self.buf[self.offset..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => self.offset += n,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::io::{self, Write};
+    use std::{
+        io::{self, Cursor, Read, Write},
+        time::{Duration, Instant},
+    };
 
     use mio::net::{TcpListener, TcpStream};
 
-    use super::{read_prefixed, write_prefixed};
+    use crate::net::messages::{
+        ClientFrame, SequencedMessage, ServerMessage, UserCommand, UserState,
+    };
+
+    use super::{
+        deserialize_frame, read_prefixed, read_prefixed_with_max_size, serialize_payload,
+        write_prefixed, FrameReader, PartialFrame, Tag, Tagged, MAX_FRAME_SIZE,
+    };
+
+    /// Strings are plain payloads with the only tag there is.
+    impl Tagged for String {
+        fn to_tagged(&self) -> io::Result<(Tag, Vec<u8>)> {
+            Ok((0, serialize_payload(self)?))
+        }
+
+        fn from_tagged(tag: Tag, payload: &[u8]) -> Option<io::Result<Self>> {
+            (tag == 0).then(|| deserialize_frame(payload))
+        }
+    }
+
+    /// Return a frame with the given tag and payload, like one written by
+    /// a newer version.
+    fn raw_frame(tag: Tag, payload: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::from((2 + payload.len() as u32).to_le_bytes());
+        frame.extend(tag.to_le_bytes());
+        frame.extend(payload);
+        frame
+    }
+
+    /// Hands out queued chunks one read at a time, and would block
+    /// whenever it runs out, like a non-blocking stream that data
+    /// trickles into.
+    struct ChunkedReader {
+        chunks: Vec<Vec<u8>>,
+    }
+
+    impl Read for ChunkedReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.chunks.is_empty() {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            let chunk = &mut self.chunks[0];
+            let n = buf.len().min(chunk.len());
+            buf[..n].copy_from_slice(&chunk[..n]);
+            chunk.drain(..n);
+            if chunk.is_empty() {
+                self.chunks.remove(0);
+            }
+            Ok(n)
+        }
+    }
+
+    /// Accepts at most a few bytes per write, and would block on every
+    /// other write, like a congested non-blocking stream.
+    struct TrickleWriter {
+        buf: Vec<u8>,
+        bytes_per_write: usize,
+        would_block: bool,
+    }
+
+    impl Write for TrickleWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.would_block = !self.would_block;
+            if !self.would_block {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            let n = buf.len().min(self.bytes_per_write);
+            self.buf.extend(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
 
     fn get_random_open_port() -> u16 {
         let addr = "127.0.0.1:0".parse().unwrap();
@@ -96,13 +463,263 @@ mod tests {
         );
     }
 
+    #[test]
+    fn write_and_read_oversized_frame() {
+        let (mut client, mut stream) = setup();
+        let value = "Hello, World!".to_string();
+        assert!(write_prefixed(&mut stream, &value).is_ok());
+        assert_eq!(
+            read_prefixed_with_max_size::<String, TcpStream>(&mut client, 4).map_err(|e| e.kind()),
+            Err(io::ErrorKind::InvalidData)
+        );
+
+        // Frames that readers would reject aren't written in the
+        // first place.
+        let value = "a".repeat(MAX_FRAME_SIZE);
+        assert_eq!(
+            write_prefixed(&mut stream, &value).map_err(|e| e.kind()),
+            Err(io::ErrorKind::InvalidData)
+        );
+    }
+
+    #[test]
+    fn write_and_read_fragmented_frames() {
+        let mut writer = TrickleWriter {
+            buf: vec![],
+            bytes_per_write: 3,
+            would_block: false,
+        };
+        let values: Vec<_> = (0..4).map(|i| format!("Hello, {i}!")).collect();
+        let mut num_would_blocks = 0;
+        for value in &values {
+            let mut frame = PartialFrame::new(value).unwrap();
+            loop {
+                match frame.write_to(&mut writer) {
+                    Ok(()) => break,
+                    Err(error) => {
+                        // Retrying the same frame resumes where it left off.
+                        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+                        assert!(!frame.is_complete());
+                        num_would_blocks += 1;
+                    }
+                }
+            }
+            assert!(frame.is_complete());
+        }
+        assert!(num_would_blocks > values.len());
+
+        // Every frame arrives intact and in order despite being split
+        // across many writes.
+        let mut reader = Cursor::new(writer.buf);
+        for value in values {
+            assert!(read_prefixed::<String, _>(&mut reader).is_ok_and(|v| v == value));
+        }
+        assert_eq!(
+            read_prefixed::<String, _>(&mut reader).map_err(|e| e.kind()),
+            Err(io::ErrorKind::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn read_frames_split_at_every_byte() {
+        let values: Vec<_> = (0..3).map(|i| format!("Hello, {i}!")).collect();
+        let mut stream = vec![];
+        for value in &values {
+            assert!(write_prefixed(&mut stream, value).is_ok());
+        }
+
+        for split_idx in 1..stream.len() {
+            let mut reader = ChunkedReader {
+                chunks: vec![stream[..split_idx].to_vec()],
+            };
+            let mut frames = FrameReader::new(MAX_FRAME_SIZE);
+            let mut read = vec![];
+            loop {
+                match frames.read_from::<String, _>(&mut reader) {
+                    Ok(value) => read.push(value),
+                    Err(error) => {
+                        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+                        break;
+                    }
+                }
+            }
+
+            // Whatever's left of the split frame arrives later, and
+            // nothing read before it was lost.
+            reader.chunks.push(stream[split_idx..].to_vec());
+            while let Ok(value) = frames.read_from::<String, _>(&mut reader) {
+                read.push(value);
+            }
+            assert_eq!(read, values, "split at byte {split_idx}");
+        }
+
+        // Same for a stream that only ever has one byte ready at a time.
+        let mut reader = ChunkedReader { chunks: vec![] };
+        let mut frames = FrameReader::new(MAX_FRAME_SIZE);
+        let mut read = vec![];
+        for byte in &stream {
+            reader.chunks.push(vec![*byte]);
+            while let Ok(value) = frames.read_from::<String, _>(&mut reader) {
+                read.push(value);
+            }
+        }
+        assert_eq!(read, values);
+    }
+
+    #[test]
+    fn read_frames_oversized_and_closed() {
+        let mut stream = vec![];
+        assert!(write_prefixed(&mut stream, &"Hello, World!".to_string()).is_ok());
+
+        // Oversized frames are rejected as soon as their size arrives.
+        let mut reader = ChunkedReader {
+            chunks: vec![stream[..4].to_vec()],
+        };
+        let mut frames = FrameReader::new(4);
+        assert_eq!(
+            frames
+                .read_from::<String, _>(&mut reader)
+                .map_err(|e| e.kind()),
+            Err(io::ErrorKind::InvalidData)
+        );
+
+        let mut reader = Cursor::new(stream[..6].to_vec());
+        let mut frames = FrameReader::new(MAX_FRAME_SIZE);
+        assert_eq!(
+            frames
+                .read_from::<String, _>(&mut reader)
+                .map_err(|e| e.kind()),
+            Err(io::ErrorKind::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn read_frames_stalled() {
+        let mut stream = vec![];
+        assert!(write_prefixed(&mut stream, &"Hello, World!".to_string()).is_ok());
+        let timeout = Duration::from_secs(1);
+
+        // Readers only stall while they're partway through a frame.
+        let mut reader = ChunkedReader {
+            chunks: vec![stream[..5].to_vec()],
+        };
+        let mut frames = FrameReader::new(MAX_FRAME_SIZE);
+        let start = Instant::now();
+        assert!(!frames.is_stalled(start + 2 * timeout, timeout));
+        assert!(frames.read_from::<String, _>(&mut reader).is_err());
+        assert!(!frames.is_stalled(Instant::now(), timeout));
+        assert!(frames.is_stalled(Instant::now() + 2 * timeout, timeout));
+
+        reader.chunks.push(stream[5..].to_vec());
+        assert!(frames.read_from::<String, _>(&mut reader).is_ok());
+        assert!(!frames.is_stalled(Instant::now() + 2 * timeout, timeout));
+    }
+
+    #[test]
+    fn tagged_messages_round_trip() {
+        let mut stream = vec![];
+        let frames = [
+            ClientFrame::Connect("ognf".to_string()),
+            ClientFrame::Command(UserCommand::ChangeState(UserState::Play)),
+            ClientFrame::Command(UserCommand::Rejoin),
+            ClientFrame::Command(UserCommand::SideBet("ognf".to_string(), 10)),
+            ClientFrame::Command(UserCommand::ViewAllCards),
+            ClientFrame::Reconnect {
+                username: "ognf".to_string(),
+                secret: u128::MAX,
+            },
+            ClientFrame::ConnectAsOperator {
+                username: "ognf".to_string(),
+                secret: "hunter2".to_string(),
+            },
+        ];
+        for frame in &frames {
+            assert!(write_prefixed(&mut stream, frame).is_ok());
+        }
+        let mut reader = Cursor::new(stream);
+        for frame in frames {
+            assert!(read_prefixed::<ClientFrame, _>(&mut reader).is_ok_and(|v| v == frame));
+        }
+
+        let mut stream = vec![];
+        let msg = SequencedMessage {
+            seq: 7,
+            msg: ServerMessage::Status("Hello, World!".to_string()),
+        };
+        assert!(write_prefixed(&mut stream, &msg).is_ok());
+        // The tag is the kind of message, and the sequence number starts
+        // the payload.
+        assert_eq!(
+            stream[4..6],
+            ServerMessage::TAGS[10].to_le_bytes(),
+            "status tag"
+        );
+        assert_eq!(stream[6..14], 7u64.to_le_bytes());
+        let read = read_prefixed::<SequencedMessage, _>(&mut Cursor::new(stream)).unwrap();
+        assert_eq!(read.seq, 7);
+        assert!(matches!(read.msg, ServerMessage::Status(status) if status == "Hello, World!"));
+    }
+
+    #[test]
+    fn unknown_tags_are_skipped() {
+        let mut stream = raw_frame(ServerMessage::TAGS.len() as Tag, &[1; 16]);
+        let msg = SequencedMessage {
+            seq: 1,
+            msg: ServerMessage::Ping,
+        };
+        assert!(write_prefixed(&mut stream, &msg).is_ok());
+        stream.extend(raw_frame(Tag::MAX, &[]));
+
+        // Frames from newer servers are skipped rather than treated as
+        // invalid, whether they're read all at once...
+        let mut reader = Cursor::new(stream.clone());
+        let read = read_prefixed::<SequencedMessage, _>(&mut reader).unwrap();
+        assert!(matches!(read.msg, ServerMessage::Ping));
+        assert_eq!(
+            read_prefixed::<SequencedMessage, _>(&mut reader)
+                .err()
+                .map(|e| e.kind()),
+            Some(io::ErrorKind::UnexpectedEof)
+        );
+
+        // ...or as they trickle in.
+        let mut reader = ChunkedReader { chunks: vec![] };
+        let mut frames = FrameReader::new(MAX_FRAME_SIZE);
+        let mut read = vec![];
+        for byte in &stream {
+            reader.chunks.push(vec![*byte]);
+            while let Ok(msg) = frames.read_from::<SequencedMessage, _>(&mut reader) {
+                read.push(msg.seq);
+            }
+        }
+        assert_eq!(read, [1]);
+        assert!(!frames.is_stalled(Instant::now() + Duration::from_secs(60), Duration::ZERO));
+
+        // Same for commands from newer clients.
+        let mut stream = raw_frame(UserCommand::TAGS.len() as Tag, &[]);
+        let frame = ClientFrame::Command(UserCommand::Leave);
+        assert!(write_prefixed(&mut stream, &frame).is_ok());
+        let mut reader = Cursor::new(stream);
+        assert!(read_prefixed::<ClientFrame, _>(&mut reader).is_ok_and(|v| v == frame));
+
+        // Known tags with bad payloads are still invalid.
+        let mut reader = Cursor::new(raw_frame(ServerMessage::TAGS[0], &[]));
+        assert_eq!(
+            read_prefixed::<SequencedMessage, _>(&mut reader)
+                .err()
+                .map(|e| e.kind()),
+            Some(io::ErrorKind::InvalidData)
+        );
+    }
+
     #[test]
     fn write_and_read_unexpected_eof() {
         let (mut client, mut stream) = setup();
         let value = "Hello, World!".to_string();
         let buf = value.as_bytes();
-        let incorrect_size = buf.len() as u32 - 2;
+        let incorrect_size = buf.len() as u32;
         assert!(stream.write_all(&incorrect_size.to_le_bytes()).is_ok());
+        assert!(stream.write_all(&0u16.to_le_bytes()).is_ok());
         assert!(stream.write_all(buf).is_ok());
         assert_eq!(
             read_prefixed::<String, TcpStream>(&mut client).map_err(|e| e.kind()),
@@ -1,11 +1,33 @@
 use mio::net::TcpListener;
 
-use std::{thread, time::Duration};
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    net::TcpStream,
+    process,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing_subscriber::{filter::LevelFilter, util::SubscriberInitExt};
 
 use private_poker::{
-    messages,
-    server::{self, PokerConfig, ServerTimeouts},
-    Client, UserError,
+    entities::{Action, GameEvent, GamePhase, HoleCards},
+    game::StepDurations,
+    messages::{
+        self, ClientError, ClientFrame, ClientMessage, SequencedMessage, ServerMessage,
+        UserCommand, Verbosity,
+    },
+    server::{
+        self,
+        clock::VirtualClock,
+        journal::{JournalConfig, JournalEntry, JournalRecord},
+        OperatorConfig, PlaylistEntry, PlaylistLength, PokerConfig, ServerTimeouts,
+        SessionWarnings, TimeoutPolicy, DEFAULT_STEP_TIMEOUT,
+    },
+    utils::{read_prefixed, write_prefixed},
+    Autostart, Client, ConfigError, GameSettings, MultiClient, Session, UserError, DEFAULT_BUY_IN,
+    DEFAULT_MAX_USERS, DEFAULT_MIN_BIG_BLIND,
 };
 
 fn get_random_open_port() -> u16 {
@@ -16,6 +38,56 @@ fn get_random_open_port() -> u16 {
     listener.local_addr().unwrap().port()
 }
 
+/// Return a frame with the given tag and payload as-is.
+fn tagged_frame(tag: u16, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::from((2 + payload.len() as u32).to_le_bytes());
+    frame.extend(tag.to_le_bytes());
+    frame.extend(payload);
+    frame
+}
+
+fn get_metrics(addr: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+/// Receive everything the session is sent over the given duration.
+fn recv_for(session: &mut Session, duration: Duration) -> Vec<ServerMessage> {
+    let deadline = Instant::now() + duration;
+    let mut msgs = Vec::new();
+    while let Ok(msg) = session.recv(Some(deadline.saturating_duration_since(Instant::now()))) {
+        msgs.push(msg);
+    }
+    msgs
+}
+
+/// Receive until the session is sent a status starting with the given
+/// prefix, returning whether the game started before then.
+fn recv_until_status(session: &mut Session, prefix: &str) -> bool {
+    let timeout = Some(Duration::from_secs(10));
+    let mut started = false;
+    loop {
+        match session.recv(timeout).unwrap() {
+            ServerMessage::Phase(GamePhase::SeatingPlayers) => started = true,
+            ServerMessage::Status(status) if status.starts_with(prefix) => return started,
+            _ => {}
+        }
+    }
+}
+
+fn recv_until_ack(client: &mut Client, command: UserCommand) {
+    loop {
+        if let ServerMessage::Ack(msg) = client.recv().unwrap() {
+            if msg.username == client.username && msg.command == command {
+                return;
+            }
+        }
+    }
+}
+
 #[test]
 fn already_associated_err() {
     let port = get_random_open_port();
@@ -36,6 +108,205 @@ fn already_associated_err() {
     assert!(Client::connect(username, &addr).is_err());
 }
 
+#[test]
+fn clients_cannot_send_commands_as_other_users() {
+    let port = get_random_open_port();
+    let addr = format!("127.0.0.1:{port}");
+    thread::spawn(move || server::run(&addr, server::PokerConfig::default()));
+
+    let addr = format!("127.0.0.1:{port}");
+    let (mut victim, _) = Client::connect("ognf", &addr).unwrap();
+    let (mut mallory, _) = Client::connect("mallory", &addr).unwrap();
+
+    // Frames don't have a username to spoof anymore, so a message
+    // naming someone else isn't a valid command and the server hangs up
+    // on whoever sent it.
+    let msg = ClientMessage {
+        username: victim.username.clone(),
+        command: UserCommand::ChangeState(messages::UserState::Play),
+    };
+    let frame = tagged_frame(UserCommand::TAGS[3], &bincode::serialize(&msg).unwrap());
+    mallory.stream.write_all(&frame).unwrap();
+    let error = loop {
+        if let Err(error) = mallory.recv() {
+            break error;
+        }
+    };
+    let error = error.downcast::<io::Error>().unwrap();
+    assert!(matches!(
+        error.kind(),
+        io::ErrorKind::UnexpectedEof | io::ErrorKind::ConnectionReset
+    ));
+
+    // Connections have to connect as a user before sending commands,
+    // and can't connect as a user that's already connected. Client
+    // errors hang up on the client, so each try needs a new connection.
+    let recv_client_error = |frame: ClientFrame| {
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        write_prefixed(&mut stream, &frame).unwrap();
+        match read_prefixed::<SequencedMessage, TcpStream>(&mut stream) {
+            Ok(SequencedMessage {
+                msg: ServerMessage::ClientError(error),
+                ..
+            }) => error,
+            other => panic!("expected a client error, got {other:?}"),
+        }
+    };
+    assert_eq!(
+        recv_client_error(ClientFrame::Command(UserCommand::Leave)),
+        ClientError::Unassociated
+    );
+    assert_eq!(
+        recv_client_error(ClientFrame::Connect(victim.username.clone())),
+        ClientError::AlreadyAssociated
+    );
+
+    // The victim is still just spectating.
+    victim.resync().unwrap();
+    let snapshot = loop {
+        if let ServerMessage::Snapshot(snapshot) = victim.recv().unwrap() {
+            break snapshot;
+        }
+    };
+    assert!(snapshot.view.spectators.contains_key(&victim.username));
+    assert!(snapshot.view.waitlist.is_empty());
+}
+
+#[test]
+fn clients_reconnect_with_their_secret() {
+    let port = get_random_open_port();
+    let addr = format!("127.0.0.1:{port}");
+    thread::spawn(move || server::run(&addr, server::PokerConfig::default()));
+
+    // Get on the waitlist so there's something to lose.
+    let addr = format!("127.0.0.1:{port}");
+    let (mut old, _) = Client::connect("ognf", &addr).unwrap();
+    let secret = old.secret.expect("connecting issues a secret");
+    old.change_state(messages::UserState::Play).unwrap();
+    while !matches!(old.recv().unwrap(), ServerMessage::Ack(_)) {}
+
+    // The server hasn't noticed the old connection is gone, so the
+    // username can't be taken without the secret.
+    let recv_client_error = |frame: ClientFrame| {
+        let mut stream = TcpStream::connect(&addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        write_prefixed(&mut stream, &frame).unwrap();
+        match read_prefixed::<SequencedMessage, TcpStream>(&mut stream) {
+            Ok(SequencedMessage {
+                msg: ServerMessage::ClientError(error),
+                ..
+            }) => error,
+            other => panic!("expected a client error, got {other:?}"),
+        }
+    };
+    assert_eq!(
+        recv_client_error(ClientFrame::Connect(old.username.clone())),
+        ClientError::AlreadyAssociated
+    );
+    assert_eq!(
+        recv_client_error(ClientFrame::Reconnect {
+            username: old.username.clone(),
+            secret: secret.wrapping_add(1),
+        }),
+        ClientError::AlreadyAssociated
+    );
+
+    // With the secret, the new connection takes over the old one without
+    // leaving the waitlist.
+    let mut new = Client::reconnect("ognf", &addr, Some(secret)).unwrap();
+    assert_eq!(new.secret, Some(secret));
+    new.resync().unwrap();
+    let snapshot = loop {
+        if let ServerMessage::Snapshot(snapshot) = new.recv().unwrap() {
+            break snapshot;
+        }
+    };
+    assert!(snapshot
+        .view
+        .waitlist
+        .iter()
+        .any(|user| user.name == new.username));
+    assert!(snapshot.view.spectators.is_empty());
+
+    // And the old connection is hung up on.
+    let error = loop {
+        if let Err(error) = old.recv() {
+            break error;
+        }
+    };
+    let error = error.downcast::<io::Error>().unwrap();
+    assert!(matches!(
+        error.kind(),
+        io::ErrorKind::UnexpectedEof | io::ErrorKind::ConnectionReset
+    ));
+}
+
+#[test]
+fn servers_skip_commands_they_dont_know() {
+    let handle = server::spawn("127.0.0.1:0", PokerConfig::default()).unwrap();
+    let addr = handle.addr().to_string();
+
+    // Commands from newer clients are skipped, and whatever's sent after
+    // them is handled as usual.
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let frame = tagged_frame(UserCommand::TAGS.len() as u16, b"from the future");
+    stream.write_all(&frame).unwrap();
+    write_prefixed(&mut stream, &ClientFrame::Connect("ognf".to_string())).unwrap();
+    assert!(Client::recv_ack(&mut stream).is_ok());
+}
+
+#[test]
+fn clients_that_never_finish_a_frame_are_removed() {
+    let config: PokerConfig = ServerTimeouts {
+        action: Duration::ZERO,
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_millis(500),
+        heartbeat: Duration::ZERO,
+        heartbeat_response: Duration::ZERO,
+        poll: Duration::from_millis(50),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_secs(5)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    let handle = server::spawn("127.0.0.1:0", config).unwrap();
+    let addr = handle.addr().to_string();
+
+    // A frame that trickles in is put back together and handled as
+    // usual, as long as it's finished in time.
+    let mut frame = vec![];
+    write_prefixed(&mut frame, &ClientFrame::Connect("ognf".to_string())).unwrap();
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    for byte in &frame {
+        stream.write_all(&[*byte]).unwrap();
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(Client::recv_ack(&mut stream).is_ok());
+
+    // A frame that's never finished gets its sender hung up on.
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(&frame[..frame.len() / 2]).unwrap();
+    let error = read_prefixed::<SequencedMessage, TcpStream>(&mut stream).unwrap_err();
+    assert!(matches!(
+        error.kind(),
+        io::ErrorKind::UnexpectedEof | io::ErrorKind::ConnectionReset
+    ));
+}
+
 #[test]
 fn one_user_connects_to_lobby() {
     let port = get_random_open_port();
@@ -87,9 +358,14 @@ fn one_user_fails_to_connect_to_lobby() {
     let addr = format!("127.0.0.1:{port}");
     let config: PokerConfig = ServerTimeouts {
         action: Duration::ZERO,
+        autostart: Duration::ZERO,
         connect: Duration::ZERO,
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::ZERO,
+        heartbeat_response: Duration::ZERO,
         poll: Duration::from_secs(5),
-        step: Duration::from_secs(5),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_secs(5)),
+        view_batch: Duration::ZERO,
     }
     .into();
     thread::spawn(move || server::run(&addr, config));
@@ -99,3 +375,2067 @@ fn one_user_fails_to_connect_to_lobby() {
     let username = "ognf";
     assert!(Client::connect(username, &addr).is_err());
 }
+
+#[test]
+fn metrics_count_scripted_hand() {
+    let port = get_random_open_port();
+    let addr = format!("127.0.0.1:{port}");
+    let metrics_port = get_random_open_port();
+    let metrics_addr = format!("127.0.0.1:{metrics_port}");
+    let mut config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(1),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_secs(1),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_secs(1)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    config.metrics_addr = Some(metrics_addr.clone());
+    thread::spawn(move || server::run(&addr, config));
+
+    // Two users connect and join the waitlist.
+    let addr = format!("127.0.0.1:{port}");
+    let (mut client1, _) = Client::connect("ognf", &addr).unwrap();
+    let (mut client2, _) = Client::connect("ognf2", &addr).unwrap();
+    let command = UserCommand::ChangeState(messages::UserState::Play);
+    client1.change_state(messages::UserState::Play).unwrap();
+    recv_until_ack(&mut client1, command.clone());
+    client2.change_state(messages::UserState::Play).unwrap();
+    recv_until_ack(&mut client2, command);
+
+    // Start the game, but never act, so the first player to act is
+    // forced to fold, ending the hand.
+    client1.start_game().unwrap();
+    let mut metrics = String::new();
+    for _ in 0..60 {
+        metrics = get_metrics(&metrics_addr);
+        if metrics.contains("pp_hands_completed_total 1\n") {
+            break;
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+    assert!(metrics.starts_with("HTTP/1.1 200 OK"));
+    for line in [
+        "pp_accepted_connections_total 2\n",
+        "pp_active_tokens 2\n",
+        "pp_forced_folds_total 1\n",
+        "pp_hands_completed_total 1\n",
+        "pp_client_messages_total{command=\"change_state\"} 2\n",
+        "pp_client_messages_total{command=\"connect\"} 2\n",
+        "pp_client_messages_total{command=\"start_game\"} 1\n",
+        "pp_client_messages_total{command=\"take_action\"} 0\n",
+    ] {
+        assert!(metrics.contains(line), "missing {line:?} in {metrics}");
+    }
+}
+
+#[test]
+fn servers_refuse_metrics_addresses_in_use() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let config = PokerConfig {
+        metrics_addr: Some(listener.local_addr().unwrap().to_string()),
+        ..Default::default()
+    };
+    assert!(server::spawn("127.0.0.1:0", config).is_err());
+}
+
+#[test]
+fn two_sessions_play_scripted_hand() {
+    let port = get_random_open_port();
+    let addr = format!("127.0.0.1:{port}");
+    let config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(5),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_secs(1),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_secs(1)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    thread::spawn(move || server::run(&addr, config));
+
+    // Two sessions connect and join the waitlist.
+    let addr = format!("127.0.0.1:{port}");
+    let timeout = Some(Duration::from_secs(10));
+    let mut sessions = Vec::new();
+    for username in ["ognf", "ognf2"] {
+        let mut session = Session::connect(username, &addr).unwrap();
+        session.change_state(messages::UserState::Play).unwrap();
+        session
+            .wait_for_view(timeout, |view| {
+                view.waitlist.iter().any(|user| user.name == username)
+            })
+            .unwrap();
+        sessions.push(session);
+    }
+    sessions[0].start_game().unwrap();
+
+    // Both sessions check or call until the hand is over, so the hand
+    // always goes to a showdown.
+    let handles: Vec<_> = sessions
+        .into_iter()
+        .map(|mut session| {
+            thread::spawn(move || {
+                let mut num_actions = 0;
+                loop {
+                    match session.recv(timeout).unwrap() {
+                        ServerMessage::Phase(GamePhase::BootingPlayers) => break,
+                        ServerMessage::TurnSignal {
+                            action_options: action_choices,
+                            context,
+                            ..
+                        } => {
+                            // The view is always sent before the turn
+                            // signal, so the two agree.
+                            let view = session.latest_view();
+                            let player = view
+                                .players
+                                .iter()
+                                .find(|p| p.user.name == session.username())
+                                .unwrap();
+                            assert_eq!(context.board, view.board);
+                            assert_eq!(player.cards, HoleCards::Revealed(context.cards.clone()));
+                            assert_eq!(context.pot, view.pot.size);
+                            assert_eq!(context.stack, player.user.money);
+                            let action = action_choices
+                                .into_iter()
+                                .find(|action| matches!(action, Action::Call(_) | Action::Check))
+                                .unwrap();
+                            session.act(action).unwrap();
+                            num_actions += 1;
+                        }
+                        _ => {}
+                    }
+                }
+                (session, num_actions)
+            })
+        })
+        .collect();
+    for handle in handles {
+        let (session, num_actions) = handle.join().unwrap();
+        assert!(num_actions > 0);
+        // Sessions error on messages that arrive out of order, so getting
+        // here means every message arrived in order.
+        assert!(session.seq() > 0);
+        let view = session.latest_view();
+        assert_eq!(view.hand_id, 1);
+        assert_eq!(view.board.len(), 5);
+        let total_money: u32 = view.players.iter().map(|p| p.user.money).sum();
+        assert_eq!(total_money, 2 * DEFAULT_BUY_IN);
+    }
+}
+
+#[test]
+fn servers_refuse_unplayable_settings() {
+    let mut config = PokerConfig::default();
+    config.game_settings.min_small_blind = 0;
+    let error = server::spawn("127.0.0.1:0", config).err().unwrap();
+    assert_eq!(
+        error.downcast::<ConfigError>().unwrap(),
+        ConfigError::InvalidBlinds
+    );
+
+    // Formats are checked too, even though they're only played later.
+    let mut config = PokerConfig::default();
+    let mut settings = GameSettings::default();
+    settings.starting_stack = settings.min_big_blind - 1;
+    config.playlist.push(PlaylistEntry {
+        name: "short stacks".to_string(),
+        settings,
+        length: PlaylistLength::Hands(1),
+        reset_stacks: true,
+    });
+    let error = server::spawn("127.0.0.1:0", config).err().unwrap();
+    assert_eq!(
+        error.downcast::<ConfigError>().unwrap(),
+        ConfigError::InvalidStartingStack {
+            big_blind: DEFAULT_MIN_BIG_BLIND
+        }
+    );
+}
+
+#[test]
+fn listeners_on_each_address_feed_the_same_game() {
+    let config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(5),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_millis(100),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_millis(100)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    let addrs = vec![
+        "127.0.0.1:0".parse().unwrap(),
+        "127.0.0.1:0".parse().unwrap(),
+    ];
+    let handle = server::spawn_multi(addrs, config).unwrap();
+    assert_eq!(handle.addrs().len(), 2);
+    assert_ne!(handle.addrs()[0], handle.addrs()[1]);
+    assert_eq!(handle.addr(), handle.addrs()[0]);
+
+    // Each user connects to a different address, but both join the same
+    // waitlist.
+    let timeout = Some(Duration::from_secs(10));
+    let mut sessions = Vec::new();
+    for (username, addr) in ["ognf", "ognf2"].into_iter().zip(handle.addrs()) {
+        let mut session = Session::connect(username, &addr.to_string()).unwrap();
+        session.change_state(messages::UserState::Play).unwrap();
+        sessions.push(session);
+    }
+    for session in sessions.iter_mut() {
+        session
+            .wait_for_view(timeout, |view| view.waitlist.len() == 2)
+            .unwrap();
+    }
+    sessions[1].start_game().unwrap();
+
+    // Both users check or call until the hand is over.
+    let handles: Vec<_> = sessions
+        .into_iter()
+        .map(|mut session| {
+            thread::spawn(move || {
+                loop {
+                    match session.recv(timeout).unwrap() {
+                        ServerMessage::Phase(GamePhase::BootingPlayers) => break,
+                        ServerMessage::TurnSignal {
+                            action_options: action_choices,
+                            ..
+                        } => {
+                            let action = action_choices
+                                .into_iter()
+                                .find(|action| matches!(action, Action::Call(_) | Action::Check))
+                                .unwrap();
+                            session.act(action).unwrap();
+                        }
+                        _ => {}
+                    }
+                }
+                session
+            })
+        })
+        .collect();
+    for session in handles {
+        let session = session.join().unwrap();
+        let view = session.latest_view();
+        assert_eq!(view.hand_id, 1);
+        assert_eq!(view.players.len(), 2);
+    }
+    handle.shutdown().unwrap();
+}
+
+#[test]
+fn draining_server_finishes_hand_before_exiting() {
+    let config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(5),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_secs(1),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_secs(1)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    let handle = server::spawn("127.0.0.1:0", config).unwrap();
+    let drainer = handle.drainer();
+
+    let addr = handle.addr().to_string();
+    let timeout = Some(Duration::from_secs(10));
+    let mut sessions = Vec::new();
+    for username in ["ognf", "ognf2"] {
+        let mut session = Session::connect(username, &addr).unwrap();
+        session.change_state(messages::UserState::Play).unwrap();
+        session
+            .wait_for_view(timeout, |view| {
+                view.waitlist.iter().any(|user| user.name == username)
+            })
+            .unwrap();
+        sessions.push(session);
+    }
+    sessions[0].start_game().unwrap();
+
+    // Whoever acts first drains the server mid-hand, then both sessions
+    // check or call until the server says it's shutting down.
+    let (tx_drained, rx_drained) = mpsc::channel();
+    let handles: Vec<_> = sessions
+        .into_iter()
+        .map(|mut session| {
+            let drainer = drainer.clone();
+            let tx_drained = tx_drained.clone();
+            thread::spawn(move || {
+                let mut pot_distributed = false;
+                loop {
+                    match session.recv(timeout).unwrap() {
+                        ServerMessage::Phase(GamePhase::DistributingPot { .. }) => {
+                            pot_distributed = true;
+                        }
+                        ServerMessage::Status(status) if status == "server is shutting down" => {
+                            break;
+                        }
+                        ServerMessage::TurnSignal { action_options, .. } => {
+                            if !drainer.is_draining() {
+                                drainer.drain().unwrap();
+                                tx_drained.send(()).unwrap();
+                            }
+                            let action = action_options
+                                .into_iter()
+                                .find(|action| matches!(action, Action::Call(_) | Action::Check))
+                                .unwrap();
+                            session.act(action).unwrap();
+                        }
+                        _ => {}
+                    }
+                }
+                (session, pot_distributed)
+            })
+        })
+        .collect();
+
+    // Users that show up while the hand is being finished are turned away.
+    rx_drained.recv_timeout(Duration::from_secs(10)).unwrap();
+    let Err(error) = Client::connect("ognf3", &addr) else {
+        panic!("connected to a draining server");
+    };
+    assert_eq!(error.to_string(), ClientError::ServerDraining.to_string());
+
+    for handle in handles {
+        let (session, pot_distributed) = handle.join().unwrap();
+        assert!(pot_distributed);
+        let view = session.latest_view();
+        assert_eq!(view.hand_id, 1);
+        let total_money: u32 = view.players.iter().map(|p| p.user.money).sum();
+        assert_eq!(total_money, 2 * DEFAULT_BUY_IN);
+    }
+    handle.join().unwrap();
+}
+
+#[test]
+fn late_waitlister_is_dealt_into_next_hand() {
+    let config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(5),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_secs(1),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_secs(1)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    let handle = server::spawn("127.0.0.1:0", config).unwrap();
+
+    // Two sessions join the waitlist while a third watches.
+    let addr = handle.addr().to_string();
+    let timeout = Some(Duration::from_secs(10));
+    let mut sessions = Vec::new();
+    for username in ["ognf", "ognf2"] {
+        let mut session = Session::connect(username, &addr).unwrap();
+        session.change_state(messages::UserState::Play).unwrap();
+        session
+            .wait_for_view(timeout, |view| {
+                view.waitlist.iter().any(|user| user.name == username)
+            })
+            .unwrap();
+        sessions.push(session);
+    }
+    let mut spectator = Session::connect("ognf3", &addr).unwrap();
+    sessions[0].start_game().unwrap();
+
+    // The first player to act folds, ending the hand, and the first
+    // session starts the next hand as soon as it can.
+    let handles: Vec<_> = sessions
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut session)| {
+            thread::spawn(move || {
+                loop {
+                    match session.recv(timeout).unwrap() {
+                        ServerMessage::Phase(GamePhase::BootingPlayers) => break,
+                        ServerMessage::TurnSignal { .. } => session.act(Action::Fold).unwrap(),
+                        _ => {}
+                    }
+                }
+                if i == 0 {
+                    while !matches!(
+                        session.recv(timeout).unwrap(),
+                        ServerMessage::Phase(GamePhase::Lobby)
+                    ) {}
+                    session.start_game().unwrap();
+                }
+                session
+            })
+        })
+        .collect();
+
+    // Ask to play just as the hand is wrapping up.
+    while !matches!(
+        spectator.recv(timeout).unwrap(),
+        ServerMessage::Phase(GamePhase::DividingDonations)
+    ) {}
+    spectator.change_state(messages::UserState::Play).unwrap();
+    let view = spectator
+        .wait_for_view(timeout, |view| {
+            view.players
+                .iter()
+                .any(|player| player.user.name == "ognf3" && player.cards != HoleCards::None)
+        })
+        .unwrap();
+    assert_eq!(view.hand_id, 2);
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    handle.shutdown().unwrap();
+}
+
+#[test]
+fn playlist_changes_format_after_its_hands() {
+    let mut config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(5),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_secs(1),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_secs(1)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    let high_stakes = GameSettings::builder().blinds(10, 20).build().unwrap();
+    config.playlist = vec![
+        PlaylistEntry {
+            name: "warm up".to_string(),
+            settings: GameSettings::default(),
+            length: PlaylistLength::Hands(1),
+            reset_stacks: false,
+        },
+        PlaylistEntry {
+            name: "high stakes".to_string(),
+            settings: high_stakes,
+            length: PlaylistLength::Hands(1),
+            reset_stacks: false,
+        },
+    ];
+    let handle = server::spawn("127.0.0.1:0", config).unwrap();
+
+    let addr = handle.addr().to_string();
+    let timeout = Some(Duration::from_secs(10));
+    let mut sessions = Vec::new();
+    for username in ["ognf", "ognf2"] {
+        let mut session = Session::connect(username, &addr).unwrap();
+        session.change_state(messages::UserState::Play).unwrap();
+        session
+            .wait_for_view(timeout, |view| {
+                view.waitlist.iter().any(|user| user.name == username)
+            })
+            .unwrap();
+        sessions.push(session);
+    }
+    sessions[0].start_game().unwrap();
+
+    // The first player to act folds, and the format changes once the
+    // hand is over.
+    let handles: Vec<_> = sessions
+        .into_iter()
+        .map(|mut session| {
+            thread::spawn(move || {
+                let mut announced = false;
+                loop {
+                    match session.recv(timeout).unwrap() {
+                        ServerMessage::TurnSignal { .. } => session.act(Action::Fold).unwrap(),
+                        ServerMessage::Status(status) if status == "switching to high stakes" => {
+                            announced = true;
+                        }
+                        ServerMessage::GameEvent(_, GameEvent::FormatChanged(name)) => {
+                            assert!(announced);
+                            assert_eq!(name, "high stakes");
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                session
+            })
+        })
+        .collect();
+    for handle in handles {
+        let mut session = handle.join().unwrap();
+        let view = session
+            .wait_for_view(timeout, |view| view.big_blind == 20)
+            .unwrap();
+        // Stacks carry over from the last format, blinds lost and all.
+        let stacks: Vec<_> = view
+            .players
+            .iter()
+            .map(|player| player.user.money)
+            .collect();
+        assert_eq!(stacks.len(), 2);
+        assert_eq!(stacks.iter().sum::<u32>(), 2 * DEFAULT_BUY_IN);
+        assert!(stacks.iter().all(|money| *money != DEFAULT_BUY_IN));
+    }
+    handle.shutdown().unwrap();
+}
+
+#[test]
+fn autostart_counts_down_once_enough_players_join() {
+    let delay = Duration::from_secs(2);
+    let mut config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(5),
+        autostart: delay,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_secs(1),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_secs(1)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    config.game_settings.autostart = Autostart::WhenPlayers(3);
+    let handle = server::spawn("127.0.0.1:0", config).unwrap();
+
+    // Two players aren't enough to start the countdown.
+    let addr = handle.addr().to_string();
+    let timeout = Some(Duration::from_secs(10));
+    let mut sessions = Vec::new();
+    for username in ["ognf", "ognf2", "ognf3"] {
+        sessions.push(Session::connect(username, &addr).unwrap());
+    }
+    for session in sessions.iter_mut().take(2) {
+        let username = session.username().to_string();
+        session.change_state(messages::UserState::Play).unwrap();
+        session
+            .wait_for_view(timeout, |view| {
+                view.waitlist.iter().any(|user| user.name == username)
+            })
+            .unwrap();
+    }
+    let msgs = recv_for(&mut sessions[0], 2 * delay);
+    assert!(!msgs.iter().any(|msg| match msg {
+        ServerMessage::Phase(phase) => *phase == GamePhase::SeatingPlayers,
+        ServerMessage::Status(status) => status.starts_with("game starting"),
+        _ => false,
+    }));
+
+    // A third player starts the countdown, but leaving before it's over
+    // cancels it.
+    sessions[2].change_state(messages::UserState::Play).unwrap();
+    assert!(!recv_until_status(&mut sessions[0], "game starting"));
+    sessions[2]
+        .change_state(messages::UserState::Spectate)
+        .unwrap();
+    assert!(!recv_until_status(&mut sessions[0], "game start cancelled"));
+    let msgs = recv_for(&mut sessions[0], 2 * delay);
+    assert!(!msgs
+        .iter()
+        .any(|msg| matches!(msg, ServerMessage::Phase(GamePhase::SeatingPlayers))));
+
+    // Coming back restarts the countdown, and the game starts once it's
+    // over.
+    sessions[2].change_state(messages::UserState::Play).unwrap();
+    assert!(!recv_until_status(&mut sessions[0], "game starting"));
+    let start = Instant::now();
+    loop {
+        if let ServerMessage::Phase(GamePhase::SeatingPlayers) = sessions[0].recv(timeout).unwrap()
+        {
+            break;
+        }
+    }
+    assert!(start.elapsed() >= delay - Duration::from_millis(500));
+    handle.shutdown().unwrap();
+}
+
+/// Receive until the session is sent a user error, returning it.
+fn recv_user_error(session: &mut Session) -> UserError {
+    let timeout = Some(Duration::from_secs(10));
+    loop {
+        if let Err(error) = session.recv(timeout) {
+            return error.downcast::<UserError>().unwrap();
+        }
+    }
+}
+
+#[test]
+fn sessions_end_after_their_max_length() {
+    let mut config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(5),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_secs(1),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_millis(500)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    config.max_session = Some(Duration::from_secs(3));
+    config.session_warnings = SessionWarnings {
+        before: vec![Duration::from_millis(2500)],
+        grace: Duration::from_secs(2),
+    };
+    let handle = server::spawn("127.0.0.1:0", config).unwrap();
+
+    let addr = handle.addr().to_string();
+    let timeout = Some(Duration::from_secs(10));
+    let mut sessions = Vec::new();
+    for username in ["ognf", "ognf2"] {
+        let mut session = Session::connect(username, &addr).unwrap();
+        session.change_state(messages::UserState::Play).unwrap();
+        session
+            .wait_for_view(timeout, |view| {
+                view.waitlist.iter().any(|user| user.name == username)
+            })
+            .unwrap();
+        sessions.push(session);
+    }
+    sessions[0].start_game().unwrap();
+
+    // Everyone's warned, and the hand that's being played is finished
+    // before everyone's shown how they did.
+    let handles: Vec<_> = sessions
+        .into_iter()
+        .map(|mut session| {
+            thread::spawn(move || {
+                let mut warned = false;
+                loop {
+                    match session.recv(timeout).unwrap() {
+                        ServerMessage::TurnSignal { .. } => session.act(Action::Fold).unwrap(),
+                        ServerMessage::Status(status) if status.starts_with("session ends in") => {
+                            warned = true;
+                        }
+                        ServerMessage::Leaderboard(leaderboard) => {
+                            return (session, warned, leaderboard);
+                        }
+                        _ => {}
+                    }
+                }
+            })
+        })
+        .collect();
+    let mut sessions = Vec::new();
+    for handle in handles {
+        let (session, warned, leaderboard) = handle.join().unwrap();
+        assert!(warned);
+        // The small blind folded to the big blind.
+        let nets: Vec<_> = leaderboard.iter().map(|(_, net)| *net).collect();
+        assert_eq!(nets, [5, -5]);
+        let view = session.latest_view();
+        assert_eq!(view.hand_id, 1);
+        for (username, net) in leaderboard {
+            let player = view
+                .players
+                .iter()
+                .find(|p| p.user.name == username)
+                .unwrap();
+            assert_eq!(player.user.money as i64, DEFAULT_BUY_IN as i64 + net);
+        }
+        sessions.push(session);
+    }
+
+    // No more hands are started, and users can only watch or leave.
+    sessions[0].start_game().unwrap();
+    assert_eq!(recv_user_error(&mut sessions[0]), UserError::SessionEnding);
+    sessions[0].change_state(messages::UserState::Play).unwrap();
+    assert_eq!(recv_user_error(&mut sessions[0]), UserError::SessionEnding);
+    sessions[1]
+        .change_state(messages::UserState::Spectate)
+        .unwrap();
+    sessions[1]
+        .wait_for_view(timeout, |view| view.spectators.contains_key("ognf2"))
+        .unwrap();
+    handle.shutdown().unwrap();
+}
+
+#[test]
+fn one_client_plays_as_several_users() {
+    let config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(5),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_secs(1),
+        steps: StepDurations::new(Duration::ZERO, Duration::ZERO),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    let handle = server::spawn("127.0.0.1:0", config).unwrap();
+
+    let addr = handle.addr().to_string();
+    let timeout = Some(Duration::from_secs(10));
+    let mut client = MultiClient::connect(&["ognf", "ognf2"], &addr).unwrap();
+    assert_eq!(client.usernames().collect::<Vec<_>>(), ["ognf", "ognf2"]);
+    assert_eq!(client.active(), "ognf");
+    assert!(client.switch("alice").is_err());
+    for username in ["ognf", "ognf2"] {
+        client.switch(username).unwrap();
+        client.change_state(messages::UserState::Play).unwrap();
+    }
+    while client.view().waitlist.len() < 2 {
+        client.recv(timeout).unwrap().1.unwrap();
+    }
+    assert!(client.is_hidden());
+    client.reveal();
+    client.start_game().unwrap();
+
+    let num_cards = |client: &MultiClient, username: &str| {
+        let view = client.view();
+        let player = view.players.iter().find(|p| p.user.name == username);
+        player.unwrap().cards.revealed().len()
+    };
+    let mut acted = Vec::new();
+    let mut acked = Vec::new();
+    let mut finished = Vec::new();
+    let mut switches = 0;
+    while finished.len() < 2 {
+        let (username, msg) = client.recv(timeout).unwrap();
+        match msg.unwrap() {
+            // Turns are only signaled to the user whose turn it is, so the
+            // host switches to them and hands over the keyboard.
+            ServerMessage::TurnSignal { action_options, .. } => {
+                let switched = username != client.active();
+                client.switch(&username).unwrap();
+                assert_eq!(client.is_hidden(), switched);
+                if switched {
+                    switches += 1;
+                    assert_eq!(num_cards(&client, &username), 0);
+                    client.reveal();
+                }
+                // Only the active user's cards are ever shown.
+                let other = if username == "ognf" { "ognf2" } else { "ognf" };
+                assert_eq!(num_cards(&client, &username), 2);
+                assert_eq!(num_cards(&client, other), 0);
+
+                let action = action_options
+                    .iter()
+                    .find(|action| matches!(action, Action::Check))
+                    .or_else(|| {
+                        action_options
+                            .iter()
+                            .find(|action| matches!(action, Action::Call(_)))
+                    })
+                    .cloned()
+                    .unwrap();
+                client.act(action).unwrap();
+                acted.push(username);
+            }
+            // Everyone hears about every action, so only count the acks
+            // users get for their own.
+            ServerMessage::Ack(ClientMessage {
+                username: acked_username,
+                command: UserCommand::TakeAction(_) | UserCommand::TakeTurn { .. },
+            }) if acked_username == username => acked.push(acked_username),
+            ServerMessage::Phase(GamePhase::BootingPlayers) => finished.push(username),
+            _ => {}
+        }
+    }
+    // Acks and turns arrive over different connections, so they can be
+    // received in a different order than the actions were taken.
+    assert!(switches > 0);
+    acked.sort();
+    acted.sort();
+    assert_eq!(acked, acted);
+    handle.shutdown().unwrap();
+}
+
+#[test]
+fn journal_records_a_hand_in_order() {
+    let dir = env::temp_dir().join(format!("pp_journal_hand_{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("journal.jsonl");
+    let mut config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(5),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_secs(1),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_secs(1)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    config.journal = Some(JournalConfig::new(&path));
+    let handle = server::spawn("127.0.0.1:0", config).unwrap();
+
+    // Two players join, and the first player to act folds, ending the hand.
+    let addr = handle.addr().to_string();
+    let timeout = Some(Duration::from_secs(10));
+    let mut sessions = Vec::new();
+    for username in ["ognf", "ognf2"] {
+        let mut session = Session::connect(username, &addr).unwrap();
+        session.change_state(messages::UserState::Play).unwrap();
+        session
+            .wait_for_view(timeout, |view| {
+                view.waitlist.iter().any(|user| user.name == username)
+            })
+            .unwrap();
+        sessions.push(session);
+    }
+    sessions[0].start_game().unwrap();
+    let handles: Vec<_> = sessions
+        .into_iter()
+        .map(|mut session| {
+            thread::spawn(move || {
+                loop {
+                    match session.recv(timeout).unwrap() {
+                        ServerMessage::Phase(GamePhase::BootingPlayers) => break,
+                        ServerMessage::TurnSignal { .. } => session.act(Action::Fold).unwrap(),
+                        _ => {}
+                    }
+                }
+                session
+            })
+        })
+        .collect();
+    // Sessions are kept around so their disconnects aren't journaled, and
+    // shutting down flushes the journal.
+    let _sessions: Vec<_> = handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect();
+    handle.shutdown().unwrap();
+
+    let entries: Vec<JournalEntry> = fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert!(entries
+        .windows(2)
+        .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+    let records: Vec<String> = entries
+        .iter()
+        .filter_map(|entry| match entry.record {
+            JournalRecord::Accepted(ref msg) => {
+                Some(format!("{} {:?}", entry.hand_id, msg.command))
+            }
+            JournalRecord::Phase(
+                ref phase @ (GamePhase::SeatingPlayers | GamePhase::BootingPlayers),
+            ) => Some(format!("{} {phase:?}", entry.hand_id)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        records,
+        [
+            "0 Connect",
+            "0 ChangeState(Play)",
+            "0 Connect",
+            "0 ChangeState(Play)",
+            "0 StartGame",
+            "0 SeatingPlayers",
+            "1 TakeAction(Fold)",
+            "1 BootingPlayers",
+        ]
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// Collects logs in memory so their fields can be checked.
+#[derive(Clone, Default)]
+struct Logs(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn logs_carry_hand_and_user_fields() {
+    let logs = Logs::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .with_max_level(LevelFilter::INFO)
+        .with_writer(move || writer.clone())
+        .finish();
+    let config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(5),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_secs(1),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_secs(1)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    // The server's threads keep logging to the subscriber that was in
+    // scope when it was spawned.
+    let handle = {
+        let _guard = subscriber.set_default();
+        server::spawn("127.0.0.1:0", config).unwrap()
+    };
+
+    // Two players join, and the first player to act folds, giving the
+    // pot to the other player.
+    let addr = handle.addr().to_string();
+    let timeout = Some(Duration::from_secs(10));
+    let mut sessions = Vec::new();
+    for username in ["ognf", "ognf2"] {
+        let mut session = Session::connect(username, &addr).unwrap();
+        session.change_state(messages::UserState::Play).unwrap();
+        session
+            .wait_for_view(timeout, |view| {
+                view.waitlist.iter().any(|user| user.name == username)
+            })
+            .unwrap();
+        sessions.push(session);
+    }
+    sessions[0].start_game().unwrap();
+    let handles: Vec<_> = sessions
+        .into_iter()
+        .map(|mut session| {
+            thread::spawn(move || {
+                loop {
+                    match session.recv(timeout).unwrap() {
+                        ServerMessage::Phase(GamePhase::BootingPlayers) => break,
+                        ServerMessage::TurnSignal { .. } => session.act(Action::Fold).unwrap(),
+                        _ => {}
+                    }
+                }
+                session
+            })
+        })
+        .collect();
+    let _sessions: Vec<_> = handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect();
+    handle.shutdown().unwrap();
+
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    let records: Vec<serde_json::Value> = logs
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let find = |message: &str| -> Vec<&serde_json::Value> {
+        records
+            .iter()
+            .filter(|record| record["fields"]["message"] == message)
+            .collect()
+    };
+
+    // Users are confirmed by the IO thread once the game lets them in.
+    let confirmed: Vec<&serde_json::Value> = find("confirmed username")
+        .into_iter()
+        .map(|record| &record["fields"]["username"])
+        .collect();
+    assert_eq!(confirmed, ["ognf", "ognf2"]);
+
+    // Actions and winnings are logged within the hand they're from, and
+    // actions within the user that took them.
+    let acted = find("acted");
+    assert_eq!(acted.len(), 1);
+    assert_eq!(acted[0]["fields"]["action"], Action::Fold.to_string());
+    assert_eq!(acted[0]["spans"][0]["name"], "hand");
+    assert_eq!(acted[0]["spans"][0]["hand_id"], 1);
+    assert_eq!(acted[0]["span"]["name"], "user");
+    let won = find("won pot");
+    assert_eq!(won.len(), 1);
+    assert_eq!(won[0]["span"]["hand_id"], 1);
+    assert_ne!(won[0]["fields"]["username"], acted[0]["span"]["username"]);
+    assert!(won[0]["fields"]["amount"].as_u64().unwrap() > 0);
+}
+
+#[test]
+fn muted_users_acks_only_skip_the_muter() {
+    let handle = server::spawn("127.0.0.1:0", PokerConfig::default()).unwrap();
+    let addr = handle.addr().to_string();
+    let timeout = Some(Duration::from_secs(10));
+    let mut sessions: Vec<_> = ["ognf", "ognf2", "ognf3"]
+        .into_iter()
+        .map(|username| Session::connect(username, &addr).unwrap())
+        .collect();
+
+    // Only the muter hears that they muted someone.
+    sessions[0].mute("ognf2").unwrap();
+    loop {
+        match sessions[0].recv(timeout).unwrap() {
+            ServerMessage::Ack(msg) if msg.command == UserCommand::Mute("ognf2".to_string()) => {
+                break
+            }
+            _ => {}
+        }
+    }
+
+    // The muted user toggles their state a couple of times, then another
+    // user joins the waitlist so everyone knows when to stop listening.
+    sessions[1].change_state(messages::UserState::Play).unwrap();
+    sessions[1]
+        .change_state(messages::UserState::Spectate)
+        .unwrap();
+    while !matches!(
+        sessions[1].recv(timeout).unwrap(),
+        ServerMessage::Ack(msg) if msg.command == UserCommand::ChangeState(messages::UserState::Spectate)
+    ) {}
+    sessions[2].change_state(messages::UserState::Play).unwrap();
+    let recv_acks = |session: &mut Session| {
+        let mut acks = Vec::new();
+        loop {
+            if let ServerMessage::Ack(msg) = session.recv(timeout).unwrap() {
+                if msg.username == "ognf3" {
+                    return acks;
+                }
+                acks.push(msg);
+            }
+        }
+    };
+    let muter_acks = recv_acks(&mut sessions[0]);
+    assert!(muter_acks.iter().all(|msg| msg.username != "ognf2"));
+    let bystander_acks = recv_acks(&mut sessions[2]);
+    assert_eq!(
+        bystander_acks
+            .iter()
+            .filter(|msg| msg.username == "ognf2")
+            .map(|msg| &msg.command)
+            .collect::<Vec<_>>(),
+        [
+            &UserCommand::ChangeState(messages::UserState::Play),
+            &UserCommand::ChangeState(messages::UserState::Spectate)
+        ]
+    );
+    // No one else hears about the mute itself.
+    assert!(bystander_acks
+        .iter()
+        .all(|msg| !matches!(msg.command, UserCommand::Mute(_))));
+    handle.shutdown().unwrap();
+}
+
+#[test]
+fn terse_client_gets_no_status_messages() {
+    let config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(5),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_secs(1),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_secs(1)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    let handle = server::spawn("127.0.0.1:0", config).unwrap();
+
+    // The first session asks for terse messages before anyone plays.
+    let addr = handle.addr().to_string();
+    let timeout = Some(Duration::from_secs(10));
+    let mut sessions = Vec::new();
+    for (username, verbosity) in [("ognf", Verbosity::Terse), ("ognf2", Verbosity::Verbose)] {
+        let mut session = Session::connect(username, &addr).unwrap();
+        if verbosity == Verbosity::Terse {
+            session.set_verbosity(verbosity).unwrap();
+            while !matches!(session.recv(timeout).unwrap(), ServerMessage::Ack(_)) {}
+        }
+        session.change_state(messages::UserState::Play).unwrap();
+        session
+            .wait_for_view(timeout, |view| {
+                view.waitlist.iter().any(|user| user.name == username)
+            })
+            .unwrap();
+        sessions.push(session);
+    }
+    sessions[0].start_game().unwrap();
+
+    // Both players check or call down a full hand, counting the status
+    // messages and turn signals they get along the way.
+    let handles: Vec<_> = sessions
+        .into_iter()
+        .map(|mut session| {
+            thread::spawn(move || {
+                let mut num_statuses = 0;
+                let mut num_turn_signals = 0;
+                loop {
+                    match session.recv(timeout).unwrap() {
+                        ServerMessage::Phase(GamePhase::BootingPlayers) => break,
+                        ServerMessage::Status(_) => num_statuses += 1,
+                        ServerMessage::TurnSignal {
+                            action_options: action_choices,
+                            ..
+                        } => {
+                            num_turn_signals += 1;
+                            let action = action_choices
+                                .into_iter()
+                                .find(|action| matches!(action, Action::Call(_) | Action::Check))
+                                .unwrap();
+                            session.act(action).unwrap();
+                        }
+                        _ => {}
+                    }
+                }
+                (num_statuses, num_turn_signals)
+            })
+        })
+        .collect();
+    let counts: Vec<_> = handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect();
+    assert_eq!(counts[0].0, 0);
+    assert!(counts[0].1 > 0);
+    assert!(counts[1].0 > 0);
+    assert!(counts[1].1 > 0);
+    handle.shutdown().unwrap();
+}
+
+#[test]
+fn turns_are_announced_once_before_turn_signals() {
+    let config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(5),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_secs(1),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_secs(1)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    let handle = server::spawn("127.0.0.1:0", config).unwrap();
+
+    let addr = handle.addr().to_string();
+    let timeout = Some(Duration::from_secs(10));
+    let mut sessions = Vec::new();
+    for username in ["ognf", "ognf2"] {
+        let mut session = Session::connect(username, &addr).unwrap();
+        session.change_state(messages::UserState::Play).unwrap();
+        session
+            .wait_for_view(timeout, |view| {
+                view.waitlist.iter().any(|user| user.name == username)
+            })
+            .unwrap();
+        sessions.push(session);
+    }
+    sessions[0].start_game().unwrap();
+
+    // Both players check or call down a full hand, recording whose turns
+    // were announced and making sure their own turn signals come right
+    // after their own announcements.
+    let handles: Vec<_> = sessions
+        .into_iter()
+        .map(|mut session| {
+            thread::spawn(move || {
+                let username = session.username().to_string();
+                let mut announced = Vec::new();
+                let mut num_turn_signals = 0;
+                loop {
+                    match session.recv(timeout).unwrap() {
+                        ServerMessage::Phase(GamePhase::BootingPlayers) => break,
+                        ServerMessage::Status(status) => assert!(!status.starts_with("it's")),
+                        ServerMessage::TurnAnnouncement {
+                            username: announced_username,
+                            ..
+                        } => announced.push(announced_username),
+                        ServerMessage::TurnSignal {
+                            action_options: action_choices,
+                            ..
+                        } => {
+                            // The signal is for the turn that was just announced,
+                            // and that turn was only announced once.
+                            assert_eq!(announced.last(), Some(&username));
+                            num_turn_signals += 1;
+                            let num_announced =
+                                announced.iter().filter(|u| **u == username).count();
+                            assert_eq!(num_announced, num_turn_signals);
+                            let action = action_choices
+                                .into_iter()
+                                .find(|action| matches!(action, Action::Call(_) | Action::Check))
+                                .unwrap();
+                            session.act(action).unwrap();
+                        }
+                        _ => {}
+                    }
+                }
+                (username, announced, num_turn_signals)
+            })
+        })
+        .collect();
+    let results: Vec<_> = handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect();
+    // Everyone hears about every turn, and each turn comes with exactly
+    // one turn signal.
+    assert_eq!(results[0].1, results[1].1);
+    for (username, announced, num_turn_signals) in &results {
+        assert!(*num_turn_signals > 0);
+        let num_announced = announced.iter().filter(|u| *u == username).count();
+        assert_eq!(num_announced, *num_turn_signals);
+    }
+    handle.shutdown().unwrap();
+}
+
+#[test]
+fn disconnected_player_folds_without_waiting() {
+    let port = get_random_open_port();
+    let addr = format!("127.0.0.1:{port}");
+    let config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(30),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_secs(1),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_secs(1)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    thread::spawn(move || server::run(&addr, config));
+
+    let addr = format!("127.0.0.1:{port}");
+    let timeout = Some(Duration::from_secs(10));
+    let mut sessions = Vec::new();
+    for username in ["ognf", "ognf2"] {
+        let mut session = Session::connect(username, &addr).unwrap();
+        session.change_state(messages::UserState::Play).unwrap();
+        session
+            .wait_for_view(timeout, |view| {
+                view.waitlist.iter().any(|user| user.name == username)
+            })
+            .unwrap();
+        sessions.push(session);
+    }
+    sessions[0].start_game().unwrap();
+
+    // The first player to act drops their connection while facing the
+    // big blind.
+    let view = sessions[0]
+        .wait_for_view(timeout, |view| view.next_action_idx.is_some())
+        .unwrap()
+        .clone();
+    let username = view.players[view.next_action_idx.unwrap()]
+        .user
+        .name
+        .clone();
+    let idx = sessions
+        .iter()
+        .position(|session| session.username() == username)
+        .unwrap();
+    let dropped = sessions.remove(idx);
+    dropped.shutdown().unwrap();
+    drop(dropped);
+
+    // They're folded well before the action timeout, but keep their seat.
+    let mut session = sessions.pop().unwrap();
+    loop {
+        if let ServerMessage::Ack(msg) = session.recv(timeout).unwrap() {
+            if msg.username == username && matches!(msg.command, UserCommand::TakeAction(_)) {
+                assert_eq!(msg.command, UserCommand::TakeAction(Action::Fold));
+                break;
+            }
+        }
+    }
+    let view = session
+        .wait_for_view(timeout, |view| {
+            view.players
+                .iter()
+                .any(|player| player.user.name == username && player.disconnected)
+        })
+        .unwrap();
+    assert_eq!(view.players.len(), 2);
+}
+
+#[test]
+fn clients_cannot_claim_to_have_disconnected() {
+    let handle = server::spawn("127.0.0.1:0", PokerConfig::default()).unwrap();
+    let addr = handle.addr().to_string();
+    let (mut client, _) = Client::connect("ognf", &addr).unwrap();
+    let (mut other, _) = Client::connect("ognf2", &addr).unwrap();
+
+    // Only the server says when a connection's dropped.
+    let frame = ClientFrame::Command(UserCommand::Disconnect);
+    write_prefixed(&mut client.stream, &frame).unwrap();
+    let error = loop {
+        if let Err(error) = client.recv() {
+            break error;
+        }
+    };
+    assert_eq!(error.to_string(), ClientError::ServerOnly.to_string());
+
+    // Like any other client error, the client's hung up on, so the
+    // only disconnect anyone hears about is the real one.
+    assert!(client.recv().is_err());
+    loop {
+        if let ServerMessage::Ack(msg) = other.recv().unwrap() {
+            if msg.username == client.username {
+                assert_eq!(msg.command, UserCommand::Disconnect);
+                break;
+            }
+        }
+    }
+}
+
+#[test]
+fn players_sit_out_after_timing_out_in_a_row() {
+    let mut config: PokerConfig = ServerTimeouts {
+        action: Duration::from_millis(200),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_millis(50),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_millis(50)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    config.game_settings.autostart = Autostart::Always;
+    config.timeout_policy = TimeoutPolicy::SitOutAfter(2);
+    let handle = server::spawn("127.0.0.1:0", config).unwrap();
+
+    let addr = handle.addr().to_string();
+    let timeout = Some(Duration::from_secs(10));
+    let mut sessions = Vec::new();
+    for username in ["ognf", "idle"] {
+        let mut session = Session::connect(username, &addr).unwrap();
+        session.change_state(messages::UserState::Play).unwrap();
+        session
+            .wait_for_view(timeout, |view| !view.spectators.contains_key(username))
+            .unwrap();
+        sessions.push(session);
+    }
+
+    // One player folds whenever it's their turn, ending every hand the
+    // idle player would've had to act in right after they time out.
+    let mut idle = sessions.pop().unwrap();
+    let mut folder = sessions.pop().unwrap();
+    let folder = thread::spawn(move || loop {
+        match folder.recv(timeout).unwrap() {
+            ServerMessage::TurnSignal { .. } => folder.act(Action::Fold).unwrap(),
+            ServerMessage::GameView(view)
+                if view.hand_id > 1 && view.spectators.contains_key("idle") =>
+            {
+                break
+            }
+            _ => {}
+        }
+    });
+
+    let mut timed_out_hand_ids = vec![];
+    let mut warnings = vec![];
+    let mut last_dealt_hand_id = 0;
+    let mut resynced = false;
+    while idle.latest_view().hand_id == 1 || !idle.latest_view().spectators.contains_key("idle") {
+        match idle.recv(timeout).unwrap() {
+            ServerMessage::Ack(msg) if msg.username == "idle" => {
+                assert_eq!(msg.command, UserCommand::TakeAction(Action::Fold));
+                timed_out_hand_ids.push(idle.latest_view().hand_id);
+            }
+            ServerMessage::Status(status) if status.starts_with("you timed out") => {
+                warnings.push(status);
+            }
+            ServerMessage::GameView(view)
+                if view.players.iter().any(|player| {
+                    player.user.name == "idle" && player.cards != HoleCards::None
+                }) =>
+            {
+                last_dealt_hand_id = view.hand_id;
+                // Commands that aren't actions don't show the player is
+                // back, so they don't start their timeouts over.
+                if timed_out_hand_ids.len() == 1 && !resynced {
+                    idle.resync(timeout).unwrap();
+                    resynced = true;
+                }
+            }
+            _ => {}
+        }
+    }
+    assert!(resynced);
+    assert_eq!(
+        warnings,
+        [
+            "you timed out, one more timeout and you'll sit out",
+            "you timed out and will sit out after this hand",
+        ]
+    );
+    // They sit out right after the hand they timed out a second time in,
+    // keeping what's left of their stack.
+    assert_eq!(timed_out_hand_ids.len(), 2);
+    assert_eq!(last_dealt_hand_id, timed_out_hand_ids[1]);
+    assert!(idle.latest_view().spectators["idle"].money > 0);
+    folder.join().unwrap();
+    handle.shutdown().unwrap();
+}
+
+/// Receive the session's next message, moving the virtual clock to
+/// the end of the game thread's wait whenever there's nothing to receive.
+fn recv_in_virtual_time(session: &mut Session, clock: &VirtualClock) -> ServerMessage {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        match session.recv(Some(Duration::from_millis(5))) {
+            Ok(msg) => return msg,
+            Err(_) => {
+                assert!(Instant::now() < deadline, "nothing's happening");
+                clock.advance_to_deadline();
+            }
+        }
+    }
+}
+
+#[test]
+fn action_timeouts_play_out_in_virtual_time() {
+    let action_timeout = Duration::from_secs(600);
+    let mut config: PokerConfig = ServerTimeouts {
+        action: action_timeout,
+        ..Default::default()
+    }
+    .into();
+    config.game_settings.autostart = Autostart::Always;
+    let clock = Arc::new(VirtualClock::new());
+    config.clock = Some(clock.clone());
+    let handle = server::spawn("127.0.0.1:0", config).unwrap();
+
+    let addr = handle.addr().to_string();
+    let mut sessions = Vec::new();
+    for username in ["ognf", "ognf2"] {
+        let mut session = Session::connect(username, &addr).unwrap();
+        session.change_state(messages::UserState::Play).unwrap();
+        sessions.push(session);
+    }
+
+    // Neither player acts, so whoever's first to act is folded for them
+    // once the action timeout is up on the virtual clock rather than the
+    // real one.
+    let session = &mut sessions[0];
+    let (username, turn_started_at, real_turn_started_at) = loop {
+        if let ServerMessage::TurnAnnouncement { username, .. } =
+            recv_in_virtual_time(session, &clock)
+        {
+            break (username, clock.elapsed(), Instant::now());
+        }
+    };
+    loop {
+        if let ServerMessage::Ack(msg) = recv_in_virtual_time(session, &clock) {
+            if msg.username == username {
+                assert_eq!(msg.command, UserCommand::TakeAction(Action::Fold));
+                break;
+            }
+        }
+    }
+    assert!(clock.elapsed() - turn_started_at >= action_timeout);
+    assert!(real_turn_started_at.elapsed() < action_timeout / 100);
+    handle.shutdown().unwrap();
+}
+
+#[test]
+fn fold_around_hand_is_quick_with_default_step_hints() {
+    let mut config = PokerConfig::default();
+    config.game_settings.autostart = Autostart::Always;
+    let clock = Arc::new(VirtualClock::new());
+    config.clock = Some(clock.clone());
+    let handle = server::spawn("127.0.0.1:0", config).unwrap();
+
+    let addr = handle.addr().to_string();
+    let timeout = Some(Duration::from_secs(10));
+    let mut sessions = Vec::new();
+    for username in ["ognf", "ognf2"] {
+        let mut session = Session::connect(username, &addr).unwrap();
+        session.change_state(messages::UserState::Play).unwrap();
+        sessions.push(session);
+    }
+
+    // The first player to act folds right away, and the hand is timed on
+    // the virtual clock from when players are seated until it's over.
+    let mut started_at = None;
+    loop {
+        match recv_in_virtual_time(&mut sessions[0], &clock) {
+            ServerMessage::Phase(GamePhase::SeatingPlayers) => started_at = Some(clock.elapsed()),
+            ServerMessage::Phase(GamePhase::Lobby) if started_at.is_some() => break,
+            ServerMessage::TurnAnnouncement { username, .. } => {
+                let session = sessions
+                    .iter_mut()
+                    .find(|session| session.username() == username)
+                    .unwrap();
+                session.act(Action::Fold).unwrap();
+                // The clock stays put until the fold's in so the player
+                // isn't timed out in the meantime.
+                loop {
+                    if let ServerMessage::Ack(msg) = sessions[0].recv(timeout).unwrap() {
+                        if msg.username == username {
+                            assert_eq!(msg.command, UserCommand::TakeAction(Action::Fold));
+                            break;
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    let elapsed = clock.elapsed() - started_at.unwrap();
+    assert!(elapsed >= DEFAULT_STEP_TIMEOUT, "{elapsed:?}");
+    assert!(elapsed < 13 * DEFAULT_STEP_TIMEOUT / 4, "{elapsed:?}");
+    handle.shutdown().unwrap();
+}
+
+#[test]
+fn only_the_operator_sees_every_hand() {
+    let mut config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(30),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_millis(50),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_millis(50)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    config.game_settings.autostart = Autostart::Always;
+    config.operator = Some(OperatorConfig {
+        username: "host".to_string(),
+        secret: "hunter2".to_string(),
+    });
+    let handle = server::spawn("127.0.0.1:0", config).unwrap();
+
+    let addr = handle.addr().to_string();
+    let timeout = Some(Duration::from_secs(10));
+
+    // Connecting first doesn't make anyone the operator. Only the secret
+    // does.
+    let (mut client, _) = Client::connect("mallory", &addr).unwrap();
+    for result in [
+        Client::connect("host", &addr),
+        Client::connect_as_operator("host", "hunter3", &addr),
+        Client::connect_as_operator("mallory2", "hunter2", &addr),
+    ] {
+        let error = result
+            .err()
+            .expect("only the operator connects as the operator");
+        assert_eq!(error.to_string(), ClientError::NotOperator.to_string());
+    }
+    let error = Client::reconnect("host", &addr, Some(0))
+        .err()
+        .expect("reconnecting doesn't skip the secret");
+    assert_eq!(error.to_string(), ClientError::NotOperator.to_string());
+    let mut host = Session::connect_as_operator("host", "hunter2", &addr).unwrap();
+    let mut session = Session::connect("ognf", &addr).unwrap();
+    let mut other_session = Session::connect("ognf2", &addr).unwrap();
+    session.change_state(messages::UserState::Play).unwrap();
+    other_session
+        .change_state(messages::UserState::Play)
+        .unwrap();
+
+    // The operator's sent every hand alongside their regular views once
+    // cards are dealt.
+    let omniscient_view = loop {
+        if let ServerMessage::OmniscientView(view) = host.recv(timeout).unwrap() {
+            if view.players.len() == 2
+                && view
+                    .players
+                    .iter()
+                    .all(|player| matches!(player.cards, HoleCards::Revealed(_)))
+            {
+                break view;
+            }
+        }
+    };
+    let view = host.latest_view();
+    assert_eq!(view.hand_id, omniscient_view.hand_id);
+    assert!(view
+        .players
+        .iter()
+        .all(|player| player.cards == HoleCards::Hidden { count: 2 }));
+
+    // Players only see their own hand, and can't ask to see the rest.
+    let view = session
+        .wait_for_view(timeout, |view| {
+            view.players
+                .iter()
+                .all(|player| player.cards != HoleCards::None)
+        })
+        .unwrap();
+    for (player, omniscient_player) in view.players.iter().zip(&omniscient_view.players) {
+        if player.user.name == "ognf" {
+            assert_eq!(player.cards, omniscient_player.cards);
+        } else {
+            assert_eq!(player.cards, HoleCards::Hidden { count: 2 });
+        }
+    }
+    session.view_all_cards().unwrap();
+    let error = loop {
+        match session.recv(timeout) {
+            Ok(ServerMessage::OmniscientView(_)) => panic!("a player saw every hand"),
+            Ok(_) => {}
+            Err(error) => break error,
+        }
+    };
+    assert_eq!(error.to_string(), ClientError::NotOperator.to_string());
+
+    // Nobody can become the operator by renaming themselves either.
+    client.rename("host").unwrap();
+    let error = loop {
+        if let Err(error) = client.recv() {
+            break error;
+        }
+    };
+    assert_eq!(error.to_string(), UserError::UserAlreadyExists.to_string());
+
+    // The operator can also ask for every hand on demand.
+    host.view_all_cards().unwrap();
+    loop {
+        if let ServerMessage::OmniscientView(_) = host.recv(timeout).unwrap() {
+            break;
+        }
+    }
+    handle.shutdown().unwrap();
+}
+
+#[test]
+fn reconnected_player_resyncs_mid_hand() {
+    let port = get_random_open_port();
+    let addr = format!("127.0.0.1:{port}");
+    let config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(30),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_secs(1),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_secs(1)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    thread::spawn(move || server::run(&addr, config));
+
+    let addr = format!("127.0.0.1:{port}");
+    let timeout = Some(Duration::from_secs(10));
+    let mut sessions = Vec::new();
+    for username in ["ognf", "ognf2"] {
+        let mut session = Session::connect(username, &addr).unwrap();
+        session.change_state(messages::UserState::Play).unwrap();
+        session
+            .wait_for_view(timeout, |view| {
+                view.waitlist.iter().any(|user| user.name == username)
+            })
+            .unwrap();
+        sessions.push(session);
+    }
+    sessions[0].start_game().unwrap();
+
+    // The player that isn't first to act drops their connection and
+    // comes right back while the other player is deciding.
+    let view = sessions[0]
+        .wait_for_view(timeout, |view| view.next_action_idx.is_some())
+        .unwrap()
+        .clone();
+    let acting_username = view.players[view.next_action_idx.unwrap()]
+        .user
+        .name
+        .clone();
+    let acting_idx = sessions
+        .iter()
+        .position(|session| session.username() == acting_username)
+        .unwrap();
+    let mut acting = sessions.remove(acting_idx);
+    acting.wait_for_turn(timeout).unwrap();
+    let mut reconnected = sessions.pop().unwrap();
+    let snapshot = reconnected.reconnect(timeout).unwrap();
+    assert_eq!(snapshot.phase, GamePhase::Betting { round_over: false });
+    assert!(snapshot.action_options.is_none());
+
+    // The snapshot matches what the player that stayed connected sees
+    // once they hear the other player is back.
+    let username = reconnected.username().to_string();
+    let view = acting
+        .wait_for_view(timeout, |view| {
+            view.players
+                .iter()
+                .any(|player| player.user.name == username && !player.disconnected)
+        })
+        .unwrap();
+    assert_eq!(snapshot.view.hand_id, view.hand_id);
+    assert_eq!(snapshot.view.board, view.board);
+    assert_eq!(snapshot.view.pot.size, view.pot.size);
+    assert_eq!(snapshot.view.next_action_idx, view.next_action_idx);
+    assert_eq!(snapshot.view.players.len(), view.players.len());
+    for (resynced, player) in snapshot.view.players.iter().zip(view.players.iter()) {
+        assert_eq!(resynced.user, player.user);
+        assert_eq!(resynced.state, player.state);
+        assert!(!resynced.disconnected);
+    }
+    // They still have their own hand, and nobody else's.
+    let player_idx = snapshot
+        .view
+        .players
+        .iter()
+        .position(|player| player.user.name == username)
+        .unwrap();
+    assert_eq!(snapshot.view.players[player_idx].cards.revealed().len(), 2);
+    assert_eq!(
+        snapshot.view.players[1 - player_idx].cards,
+        HoleCards::Hidden { count: 2 }
+    );
+
+    // Once it's their turn, a snapshot includes what they can do.
+    acting.act(Action::Call(5)).unwrap();
+    let action_choices = reconnected.wait_for_turn(timeout).unwrap();
+    let snapshot = reconnected.resync(timeout).unwrap();
+    assert_eq!(snapshot.action_options, Some(action_choices));
+    assert!(snapshot.max_raise.is_some());
+}
+
+#[test]
+fn unresponsive_client_is_removed() {
+    let port = get_random_open_port();
+    let addr = format!("127.0.0.1:{port}");
+    let config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(5),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(1),
+        heartbeat_response: Duration::from_secs(1),
+        poll: Duration::from_millis(100),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_secs(1)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    thread::spawn(move || server::run(&addr, config));
+
+    // The client reads everything the server sends, but never pongs, so
+    // the server should give up on it shortly after pinging it.
+    let addr = format!("127.0.0.1:{port}");
+    let (mut client, _) = Client::connect("ognf", &addr).unwrap();
+    let start = Instant::now();
+    let mut pinged = false;
+    while read_prefixed::<SequencedMessage, _>(&mut client.stream)
+        .inspect(|sequenced| pinged |= matches!(sequenced.msg, ServerMessage::Ping))
+        .is_ok()
+    {}
+    assert!(pinged);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn oversized_client_frame_is_dropped() {
+    let port = get_random_open_port();
+    let addr = format!("127.0.0.1:{port}");
+    thread::spawn(move || server::run(&addr, server::PokerConfig::default()));
+
+    // Claim a frame that's way too big for a client message without
+    // bothering to send it. The server should hang up rather than try
+    // to read it.
+    let addr = format!("127.0.0.1:{port}");
+    let mut stream = loop {
+        if let Ok(stream) = TcpStream::connect(&addr) {
+            break stream;
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    stream.write_all(&u32::MAX.to_le_bytes()).unwrap();
+    let mut buf = [0; 1];
+    match stream.read(&mut buf) {
+        Ok(num_bytes) => assert_eq!(num_bytes, 0),
+        Err(error) => assert_eq!(error.kind(), std::io::ErrorKind::ConnectionReset),
+    }
+}
+
+#[test]
+fn rapid_commands_share_views() {
+    let port = get_random_open_port();
+    let addr = format!("127.0.0.1:{port}");
+    let mut config: PokerConfig = ServerTimeouts {
+        view_batch: Duration::from_millis(500),
+        ..Default::default()
+    }
+    .into();
+    config.rate_limits.burst = 20.0;
+    thread::spawn(move || server::run(&addr, config));
+
+    // Flip between playing and spectating as fast as possible without
+    // going over the rate limit. Every command is acked, but views are only sent
+    // once per batch.
+    let addr = format!("127.0.0.1:{port}");
+    let (mut client, _) = Client::connect("ognf", &addr).unwrap();
+    let num_commands = 20;
+    let mut num_views = 0;
+    for i in 0..num_commands {
+        let state = if i % 2 == 0 {
+            messages::UserState::Play
+        } else {
+            messages::UserState::Spectate
+        };
+        let command = UserCommand::ChangeState(state.clone());
+        client.change_state(state).unwrap();
+        loop {
+            match client.recv().unwrap() {
+                ServerMessage::Ack(msg) if msg.command == command => break,
+                ServerMessage::GameView(_) => num_views += 1,
+                _ => {}
+            }
+        }
+    }
+    let view = loop {
+        if let ServerMessage::GameView(view) = client.recv().unwrap() {
+            num_views += 1;
+            break view;
+        }
+    };
+    assert!(num_views < num_commands / 4);
+
+    // The batched view is the same as if it was sent after the last command.
+    assert_eq!(view.spectators.len(), 1);
+    assert_eq!(view.waitlist.len(), 0);
+    assert!(view.spectators.contains_key(&client.username));
+}
+
+#[test]
+fn commands_over_the_rate_limit_are_dropped() {
+    let handle = server::spawn("127.0.0.1:0", PokerConfig::default()).unwrap();
+    let addr = handle.addr().to_string();
+    let (mut client, _) = Client::connect("ognf", &addr).unwrap();
+
+    // Bursts are fine until they use up the user's bucket. After that,
+    // commands are dropped but the user stays connected.
+    let burst = PokerConfig::default().rate_limits.burst as usize;
+    for _ in 0..=burst {
+        client.mute("someone").unwrap();
+    }
+    let mut num_acks = 0;
+    let error = loop {
+        match client.recv() {
+            Ok(ServerMessage::Ack(_)) => num_acks += 1,
+            Ok(_) => {}
+            Err(error) => break error,
+        }
+    };
+    assert_eq!(num_acks, burst);
+    assert_eq!(error.to_string(), ClientError::RateLimited.to_string());
+
+    // The bucket refills after backing off.
+    thread::sleep(Duration::from_secs(1));
+    client.unmute("someone").unwrap();
+    loop {
+        if let ServerMessage::Ack(msg) = client.recv().unwrap() {
+            assert_eq!(msg.command, UserCommand::Unmute("someone".to_string()));
+            break;
+        }
+    }
+    handle.shutdown().unwrap();
+}
+
+#[test]
+fn full_table_connects_from_one_host() {
+    let handle = server::spawn("127.0.0.1:0", PokerConfig::default()).unwrap();
+
+    // Bots are typically run from the same machine, one connection each,
+    // and can fill up a table along with their owner.
+    let addr = handle.addr().to_string();
+    let clients: Vec<_> = (0..DEFAULT_MAX_USERS)
+        .map(|i| Client::connect(&i.to_string(), &addr).unwrap())
+        .collect();
+    assert_eq!(clients.len(), DEFAULT_MAX_USERS);
+    handle.shutdown().unwrap();
+}
+
+#[test]
+fn connections_beyond_per_ip_cap_are_refused() {
+    let mut config = PokerConfig::default();
+    config.connection_limits.per_ip = 2;
+    let handle = server::spawn("127.0.0.1:0", config).unwrap();
+
+    let addr = handle.addr().to_string();
+    let (client1, _) = Client::connect("ognf", &addr).unwrap();
+    let (_client2, _) = Client::connect("ognf2", &addr).unwrap();
+    assert!(Client::connect("ognf3", &addr).is_err());
+
+    // Closing a connection makes room for another.
+    drop(client1);
+    let start = Instant::now();
+    while Client::connect("ognf3", &addr).is_err() {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(100));
+    }
+    handle.shutdown().unwrap();
+}
+
+#[test]
+fn connections_beyond_unconfirmed_cap_are_refused() {
+    let mut config = PokerConfig::default();
+    config.connection_limits.unconfirmed = 1;
+    let handle = server::spawn("127.0.0.1:0", config).unwrap();
+
+    // A connection that never declares a username holds onto the only
+    // unconfirmed slot until it expires.
+    let addr = handle.addr().to_string();
+    let _stream = TcpStream::connect(&addr).unwrap();
+    assert!(Client::connect("ognf", &addr).is_err());
+    handle.shutdown().unwrap();
+}
+
+#[cfg(feature = "bots")]
+#[test]
+fn practice_table_plays_a_hand() {
+    use private_poker::bots::PracticeTable;
+
+    let config: PokerConfig = ServerTimeouts {
+        action: Duration::from_secs(5),
+        autostart: Duration::ZERO,
+        connect: Duration::from_secs(5),
+        frame: Duration::from_secs(10),
+        heartbeat: Duration::from_secs(15),
+        heartbeat_response: Duration::from_secs(10),
+        poll: Duration::from_secs(1),
+        steps: StepDurations::new(Duration::ZERO, Duration::from_secs(1)),
+        view_batch: Duration::ZERO,
+    }
+    .into();
+    let num_bots = 3;
+    let table = PracticeTable::start(config, num_bots, Duration::ZERO..Duration::ZERO).unwrap();
+
+    // Join the bots and start the game once they're all waiting to play.
+    let timeout = Some(Duration::from_secs(10));
+    let mut session = Session::connect("ognf", &table.addr()).unwrap();
+    session.change_state(messages::UserState::Play).unwrap();
+    session
+        .wait_for_view(timeout, |view| view.waitlist.len() == num_bots + 1)
+        .unwrap();
+    session.start_game().unwrap();
+
+    // Play along until the hand is over, whether or not the bots let us
+    // see a showdown.
+    loop {
+        match session.recv(timeout).unwrap() {
+            ServerMessage::Phase(GamePhase::BootingPlayers) => break,
+            ServerMessage::TurnSignal {
+                action_options: action_choices,
+                ..
+            } => {
+                let action = action_choices
+                    .into_iter()
+                    .find(|action| matches!(action, Action::Call(_) | Action::Check))
+                    .unwrap_or(Action::Fold);
+                session.act(action).unwrap();
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(session.latest_view().hand_id, 1);
+
+    // Shutting down the table stops the server and the bots, and leaves
+    // us without a server to talk to.
+    table.shutdown().unwrap();
+    while session.recv(timeout).is_ok() {}
+}
+
+#[cfg(feature = "bots")]
+#[test]
+fn bots_reconnect_when_the_server_restarts() {
+    use private_poker::bots::{
+        self, Bot, BotStatus, QLearning, Reconnect, DEFAULT_ALPHA, DEFAULT_GAMMA,
+    };
+    use std::sync::{Arc, Mutex};
+
+    let config = || {
+        let mut config: PokerConfig = ServerTimeouts {
+            action: Duration::from_secs(5),
+            autostart: Duration::ZERO,
+            connect: Duration::from_secs(5),
+            frame: Duration::from_secs(10),
+            heartbeat: Duration::from_secs(15),
+            heartbeat_response: Duration::from_secs(10),
+            poll: Duration::from_millis(50),
+            steps: StepDurations::new(Duration::ZERO, Duration::from_millis(50)),
+            view_batch: Duration::ZERO,
+        }
+        .into();
+        config.game_settings.autostart = Autostart::Always;
+        config
+    };
+    let handle = server::spawn("127.0.0.1:0", config()).unwrap();
+    let addr = handle.addr().to_string();
+
+    let policy = Arc::new(Mutex::new(QLearning::new(DEFAULT_ALPHA, DEFAULT_GAMMA)));
+    let reconnect = Reconnect {
+        max_retries: None,
+        min_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(100),
+    };
+    let botnames = ["bot1", "bot2"];
+    let bots: Vec<_> = botnames
+        .iter()
+        .map(|botname| {
+            let bot = Bot::new(botname, &addr, Duration::ZERO..Duration::ZERO)
+                .unwrap()
+                .reconnect_with(reconnect.clone());
+            let status = bot.status();
+            let policy = policy.clone();
+            let (interrupter, interrupt) = mpsc::channel();
+            let worker = thread::spawn(move || bots::play(bot, policy, interrupt));
+            (status, interrupter, worker)
+        })
+        .collect();
+
+    // Watch until both bots are seated and have played a couple hands.
+    let timeout = Some(Duration::from_secs(10));
+    let wait_for_hands = |addr: &str| {
+        let mut session = Session::connect("ognf", addr).unwrap();
+        session
+            .wait_for_view(timeout, |view| {
+                view.hand_id >= 2
+                    && botnames
+                        .iter()
+                        .all(|botname| view.players.iter().any(|p| p.user.name == *botname))
+            })
+            .unwrap();
+        session.shutdown().unwrap();
+
+        // Bots that went broke reconnect between hands, so they may not
+        // have seen themselves get seated again just yet.
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while bots
+            .iter()
+            .any(|(status, ..)| *status.lock().unwrap() != BotStatus::Seated)
+        {
+            assert!(Instant::now() < deadline, "bots weren't all seated");
+            thread::sleep(Duration::from_millis(10));
+        }
+    };
+    wait_for_hands(&addr);
+
+    // The bots find their way back to a server that restarts on the same
+    // address, where hands start over from the first.
+    handle.shutdown().unwrap();
+    let handle = server::spawn(&addr, config()).unwrap();
+    wait_for_hands(&addr);
+
+    // Bots that are interrupted while waiting to reconnect stop cleanly.
+    for (_, interrupter, _) in bots.iter() {
+        interrupter.send(()).unwrap();
+    }
+    handle.shutdown().unwrap();
+    for (_, _, worker) in bots {
+        worker.join().unwrap().unwrap();
+    }
+}